@@ -0,0 +1,83 @@
+//! Setup shared by the benchmarks that measure the fixture grammars.
+
+// Each benchmark only uses some of these.
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use anyhow::Context;
+use tree_sitter::Language;
+use tree_sitter_loader::{CompileConfig, Loader};
+
+include!("../../src/tests/helpers/dirs.rs");
+
+pub static LANGUAGE_FILTER: LazyLock<Option<String>> =
+    LazyLock::new(|| env::var("TREE_SITTER_BENCHMARK_LANGUAGE_FILTER").ok());
+pub static REPETITION_COUNT: LazyLock<usize> = LazyLock::new(|| {
+    env::var("TREE_SITTER_BENCHMARK_REPETITION_COUNT").map_or(3, |s| s.parse::<usize>().unwrap())
+});
+
+static TEST_LOADER: LazyLock<Loader> =
+    LazyLock::new(|| Loader::with_parser_lib_path(SCRATCH_DIR.clone()));
+
+/// Find the fixture grammars' directories that satisfy the given predicate and aren't excluded
+/// by `LANGUAGE_FILTER`, in order.
+pub fn fixture_grammar_dirs(predicate: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    let mut grammar_dirs = fs::read_dir(GRAMMARS_DIR.as_path()).map_or_else(
+        |_| Vec::new(),
+        |entries| {
+            entries
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| predicate(path))
+                .filter(|path| {
+                    LANGUAGE_FILTER
+                        .as_ref()
+                        .is_none_or(|filter| path.file_name().unwrap() == filter.as_str())
+                })
+                .collect::<Vec<_>>()
+        },
+    );
+    grammar_dirs.sort();
+    grammar_dirs
+}
+
+/// Compile and load the language of a fixture grammar.
+pub fn load_language(grammar_dir: &Path) -> Language {
+    let src_path = grammar_dir.join("src");
+    TEST_LOADER
+        .load_language_at_path(CompileConfig::new(&src_path, None, None))
+        .with_context(|| format!("Failed to load language at path {}", src_path.display()))
+        .unwrap()
+}
+
+/// Concatenate the grammar's examples over and over until they fill `size` bytes. Returns
+/// `None` if the grammar has no examples.
+pub fn repeated_examples(grammar_dir: &Path, size: usize) -> Option<Vec<u8>> {
+    let mut example_paths = fs::read_dir(grammar_dir.join("examples"))
+        .ok()?
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    example_paths.sort();
+
+    let mut example = Vec::new();
+    for path in example_paths {
+        example.extend(fs::read(path).unwrap());
+        if !example.ends_with(b"\n") {
+            example.push(b'\n');
+        }
+    }
+    if example.is_empty() {
+        return None;
+    }
+
+    let mut source = Vec::with_capacity(size + example.len());
+    while source.len() < size {
+        source.extend_from_slice(&example);
+    }
+    Some(source)
+}
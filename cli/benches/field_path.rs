@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+use tree_sitter::{Node, Parser};
+
+mod common;
+
+use common::{load_language, repeated_examples, GRAMMARS_DIR, REPETITION_COUNT};
+
+/// The size of the source that the C grammar's examples are repeated to fill.
+const SOURCE_SIZE: usize = 1024 * 1024;
+
+/// The nested fields that lead from a function definition to the function's name.
+const FIELD_PATH: &[&str] = &["declarator", "declarator"];
+
+/// Compare the time it takes to follow a path of nested fields from every node of a large C
+/// source, by chaining calls to `child_by_field_name`, with `child_by_field_path`, and with
+/// `child_by_field_id_path` using field ids that are resolved once.
+fn main() {
+    let grammar_dir = GRAMMARS_DIR.join("c");
+    let language = load_language(&grammar_dir);
+    let source = repeated_examples(&grammar_dir, SOURCE_SIZE).unwrap();
+
+    let mut parser = Parser::new();
+    parser.set_language(&language).unwrap();
+    let tree = parser.parse(&source, None).unwrap();
+    let nodes = all_nodes(tree.root_node());
+
+    eprintln!(
+        "Benchmarking with {} repetitions, following {FIELD_PATH:?} from {} nodes",
+        *REPETITION_COUNT,
+        nodes.len(),
+    );
+
+    let chained = measure(&nodes, |node| {
+        FIELD_PATH.iter().try_fold(node, |node, field_name| {
+            node.child_by_field_name(field_name)
+        })
+    });
+    let by_name = measure(&nodes, |node| node.child_by_field_path(FIELD_PATH));
+    let field_ids = FIELD_PATH
+        .iter()
+        .map(|field_name| language.field_id_for_name(field_name).unwrap())
+        .collect::<Vec<_>>();
+    let by_id = measure(&nodes, |node| node.child_by_field_id_path(&field_ids));
+
+    eprintln!("  Chained names: {} ms", chained.as_millis());
+    eprintln!("  Name path:     {} ms", by_name.as_millis());
+    eprintln!("  Field id path: {} ms", by_id.as_millis());
+    eprintln!(
+        "  Speedup:       {:.2}x",
+        chained.as_secs_f64() / by_id.as_secs_f64()
+    );
+    eprintln!();
+}
+
+fn all_nodes(root: Node) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut cursor = root.walk();
+    'outer: loop {
+        nodes.push(cursor.node());
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'outer;
+            }
+        }
+    }
+    nodes
+}
+
+/// Return the average time it takes to call `follow_path` on every node, and check that it finds
+/// at least one node.
+fn measure<'a>(nodes: &[Node<'a>], follow_path: impl Fn(Node<'a>) -> Option<Node<'a>>) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..*REPETITION_COUNT {
+        let start = Instant::now();
+        let found_count = nodes
+            .iter()
+            .filter(|node| follow_path(**node).is_some())
+            .count();
+        total += start.elapsed();
+        assert!(found_count > 0);
+    }
+    total / *REPETITION_COUNT as u32
+}
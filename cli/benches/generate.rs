@@ -0,0 +1,77 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+mod common;
+
+use common::{fixture_grammar_dirs, FIXTURES_DIR, LANGUAGE_FILTER, REPETITION_COUNT};
+
+/// Compare the time it takes to generate the fixture grammars' parsers using a single
+/// thread and using all of the available threads.
+fn main() {
+    let single_threaded_pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let multi_threaded_pool = ThreadPoolBuilder::new().build().unwrap();
+
+    eprintln!(
+        "Benchmarking with {} repetitions, using 1 and {} threads",
+        *REPETITION_COUNT,
+        multi_threaded_pool.current_num_threads()
+    );
+
+    let mut grammar_dirs = fixture_grammar_dirs(Path::is_dir);
+    if LANGUAGE_FILTER
+        .as_ref()
+        .is_none_or(|filter| filter == "readme_grammar")
+    {
+        grammar_dirs.push(FIXTURES_DIR.join("test_grammars").join("readme_grammar"));
+        grammar_dirs.sort();
+    }
+
+    for grammar_dir in grammar_dirs {
+        let Some(grammar_path) = grammar_path(&grammar_dir) else {
+            continue;
+        };
+        let language_name = grammar_dir.file_name().unwrap().to_str().unwrap();
+
+        eprintln!("\nLanguage: {language_name}");
+        let grammar_json = tree_sitter_generate::load_grammar_file(&grammar_path, None).unwrap();
+        let single_threaded = generate(&single_threaded_pool, &grammar_json);
+        let multi_threaded = generate(&multi_threaded_pool, &grammar_json);
+        eprintln!("  Single-threaded: {} ms", single_threaded.as_millis());
+        eprintln!("  Multi-threaded:  {} ms", multi_threaded.as_millis());
+        eprintln!(
+            "  Speedup:         {:.2}x",
+            single_threaded.as_secs_f64() / multi_threaded.as_secs_f64()
+        );
+    }
+    eprintln!();
+}
+
+/// Find the grammar file of a fixture grammar's directory, preferring the generated
+/// `grammar.json` so that no JavaScript runtime is needed.
+fn grammar_path(dir: &Path) -> Option<PathBuf> {
+    let json_path = dir.join("src").join("grammar.json");
+    if json_path.exists() {
+        return Some(json_path);
+    }
+    let js_path = dir.join("grammar.js");
+    js_path.exists().then_some(js_path)
+}
+
+/// Return the average time it takes to generate a parser for the grammar in the given
+/// thread pool.
+fn generate(pool: &ThreadPool, grammar_json: &str) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..*REPETITION_COUNT {
+        let start = Instant::now();
+        pool.install(|| {
+            tree_sitter_generate::generate_parser_for_grammar(grammar_json, Some((0, 0, 0)))
+                .expect("Failed to generate parser");
+        });
+        total += start.elapsed();
+    }
+    total / *REPETITION_COUNT as u32
+}
@@ -0,0 +1,113 @@
+use std::{
+    fs,
+    ops::Range,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use tree_sitter::Point;
+use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter};
+
+mod common;
+
+use common::{fixture_grammar_dirs, load_language, repeated_examples, REPETITION_COUNT};
+
+/// The size of the source that each language's examples are repeated to fill.
+const SOURCE_SIZE: usize = 4 * 1024 * 1024;
+
+/// The number of rows that are highlighted on their own, from the middle of the source.
+const ROW_COUNT: usize = 100;
+
+const HIGHLIGHT_NAMES: &[&str] = &[
+    "attribute",
+    "comment",
+    "constant",
+    "constructor",
+    "function",
+    "keyword",
+    "number",
+    "operator",
+    "property",
+    "punctuation",
+    "string",
+    "tag",
+    "type",
+    "variable",
+];
+
+/// Compare the time it takes to highlight a multi-megabyte source built from each fixture
+/// grammar's examples, and to highlight only a few rows in the middle of it.
+fn main() {
+    eprintln!(
+        "Benchmarking with {} repetitions, highlighting {} MB and {ROW_COUNT} rows",
+        *REPETITION_COUNT,
+        SOURCE_SIZE / 1024 / 1024,
+    );
+
+    let grammar_dirs =
+        fixture_grammar_dirs(|path| path.join("queries").join("highlights.scm").exists());
+    for grammar_dir in grammar_dirs {
+        let language_name = grammar_dir.file_name().unwrap().to_str().unwrap();
+        let Some(source) = repeated_examples(&grammar_dir, SOURCE_SIZE) else {
+            continue;
+        };
+        let config = highlight_config(&grammar_dir, language_name);
+        let row_count = source.split(|&byte| byte == b'\n').count() - 1;
+        let start_row = row_count.saturating_sub(ROW_COUNT) / 2;
+        let rows = Point::new(start_row, 0)..Point::new(start_row + ROW_COUNT, 0);
+
+        eprintln!("\nLanguage: {language_name}");
+        let full = highlight(
+            &config,
+            &source,
+            Point::new(0, 0)..Point::new(usize::MAX, 0),
+        );
+        let partial = highlight(&config, &source, rows);
+        eprintln!("  Full source: {} ms", full.as_millis());
+        eprintln!("  {ROW_COUNT} rows:    {} ms", partial.as_millis());
+        eprintln!(
+            "  Speedup:     {:.2}x",
+            full.as_secs_f64() / partial.as_secs_f64()
+        );
+    }
+    eprintln!();
+}
+
+fn highlight_config(grammar_dir: &Path, language_name: &str) -> HighlightConfiguration {
+    let language = load_language(grammar_dir);
+    let queries_path = grammar_dir.join("queries");
+    let highlights_query = fs::read_to_string(queries_path.join("highlights.scm")).unwrap();
+    let locals_query = fs::read_to_string(queries_path.join("locals.scm")).unwrap_or_default();
+    let mut config = HighlightConfiguration::new(
+        language,
+        language_name,
+        &highlights_query,
+        "",
+        &locals_query,
+    )
+    .unwrap();
+    config.configure(HIGHLIGHT_NAMES);
+    config
+}
+
+/// Return the average time it takes to highlight the given rows of the source, including
+/// parsing it.
+fn highlight(config: &HighlightConfiguration, source: &[u8], rows: Range<Point>) -> Duration {
+    let mut highlighter = Highlighter::new();
+    let mut total = Duration::ZERO;
+    for _ in 0..*REPETITION_COUNT {
+        let start = Instant::now();
+        let events = highlighter
+            .highlight_point_range(config, source, rows.clone(), None, |_| None)
+            .unwrap();
+        let mut highlighted_bytes = 0;
+        for event in events {
+            if let HighlightEvent::Source { start, end } = event.unwrap() {
+                highlighted_bytes += end - start;
+            }
+        }
+        assert!(highlighted_bytes > 0);
+        total += start.elapsed();
+    }
+    total / *REPETITION_COUNT as u32
+}
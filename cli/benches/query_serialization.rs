@@ -0,0 +1,72 @@
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use tree_sitter::Query;
+
+mod common;
+
+use common::{fixture_grammar_dirs, load_language, REPETITION_COUNT};
+
+/// The queries of each grammar that are combined into one query, like an editor does when it
+/// highlights a language.
+const QUERY_FILE_NAMES: &[&str] = &["highlights.scm", "injections.scm", "locals.scm"];
+
+/// Compare the time it takes to load each fixture grammar's combined highlight queries by
+/// compiling their source code, and by deserializing a query that was serialized beforehand.
+fn main() {
+    eprintln!(
+        "Benchmarking with {} repetitions, loading the combined highlight queries",
+        *REPETITION_COUNT,
+    );
+
+    let grammar_dirs =
+        fixture_grammar_dirs(|path| path.join("queries").join("highlights.scm").exists());
+    let (mut total_compiled, mut total_deserialized) = (Duration::ZERO, Duration::ZERO);
+    for grammar_dir in grammar_dirs {
+        let language_name = grammar_dir.file_name().unwrap().to_str().unwrap();
+        let language = load_language(&grammar_dir);
+        let source = combined_queries(&grammar_dir);
+        let bytes = Query::new(&language, &source).unwrap().serialize();
+
+        eprintln!("\nLanguage: {language_name}");
+        let compiled = measure(|| Query::new(&language, &source).unwrap());
+        let deserialized = measure(|| Query::deserialize(&language, &bytes).unwrap());
+        eprintln!("  Compiled:     {} us", compiled.as_micros());
+        eprintln!("  Deserialized: {} us", deserialized.as_micros());
+        eprintln!(
+            "  Speedup:      {:.2}x",
+            compiled.as_secs_f64() / deserialized.as_secs_f64()
+        );
+        total_compiled += compiled;
+        total_deserialized += deserialized;
+    }
+
+    eprintln!("\nAll languages");
+    eprintln!("  Compiled:     {} ms", total_compiled.as_millis());
+    eprintln!("  Deserialized: {} ms", total_deserialized.as_millis());
+    eprintln!();
+}
+
+fn combined_queries(grammar_dir: &Path) -> String {
+    let queries_path = grammar_dir.join("queries");
+    QUERY_FILE_NAMES
+        .iter()
+        .filter_map(|file_name| fs::read_to_string(queries_path.join(file_name)).ok())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Return the average time it takes to load the query.
+fn measure(load: impl Fn() -> Query) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..*REPETITION_COUNT {
+        let start = Instant::now();
+        let query = load();
+        total += start.elapsed();
+        assert!(query.pattern_count() > 0);
+    }
+    total / *REPETITION_COUNT as u32
+}
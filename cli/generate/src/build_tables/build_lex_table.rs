@@ -4,10 +4,10 @@ use std::{
 };
 
 use log::info;
+use rayon::prelude::*;
 
 use super::{coincident_tokens::CoincidentTokenIndex, token_conflicts::TokenConflictMap};
 use crate::{
-    dedup::split_state_id_groups,
     grammars::{LexicalGrammar, SyntaxGrammar},
     nfa::{CharacterSet, NfaCursor},
     rules::{Symbol, TokenSet},
@@ -26,16 +26,22 @@ pub fn build_lex_table(
     parse_table: &mut ParseTable,
     syntax_grammar: &SyntaxGrammar,
     lexical_grammar: &LexicalGrammar,
-    keywords: &TokenSet,
+    keywords: &[TokenSet],
     coincident_token_index: &CoincidentTokenIndex,
     token_conflict_map: &TokenConflictMap,
+    minimize_lexer: bool,
 ) -> LexTables {
-    let keyword_lex_table = if syntax_grammar.word_token.is_some() {
+    // The keyword lex table has one start state for each word token, so that the
+    // keywords captured by the word token at index `i` are lexed starting in state `i`.
+    let keyword_lex_table = if syntax_grammar.word_tokens.is_empty() {
+        LexTable::default()
+    } else {
         let mut builder = LexTableBuilder::new(lexical_grammar);
-        builder.add_state_for_tokens(keywords);
+        builder.add_start_states_for_tokens(keywords);
+        if minimize_lexer {
+            minimize_lex_table(&mut builder.table, keywords.len());
+        }
         builder.table
-    } else {
-        LexTable::default()
     };
 
     let mut parse_state_ids_by_token_set = Vec::<(TokenSet, Vec<ParseStateId>)>::new();
@@ -47,11 +53,10 @@ pub fn build_lex_table(
             .chain(state.reserved_words.iter())
             .filter_map(|token| {
                 if token.is_terminal() {
-                    if keywords.contains(&token) {
-                        syntax_grammar.word_token
-                    } else {
-                        Some(token)
-                    }
+                    keywords
+                        .iter()
+                        .position(|keywords| keywords.contains(&token))
+                        .map_or(Some(token), |i| Some(syntax_grammar.word_tokens[i]))
                 } else if token.is_eof() {
                     Some(token)
                 } else {
@@ -88,37 +93,57 @@ pub fn build_lex_table(
         }
     }
 
+    // The first lex state belongs to the error recovery parse state, and keeps the id 0.
     let mut main_lex_table = mem::take(&mut builder.table);
-    minimize_lex_table(&mut main_lex_table, parse_table);
+    if minimize_lexer {
+        let new_lex_state_ids = minimize_lex_table(&mut main_lex_table, 1);
+        for state in &mut parse_table.states {
+            state.lex_state_id = new_lex_state_ids[state.lex_state_id];
+        }
+    }
     sort_states(&mut main_lex_table, parse_table);
 
-    let mut large_character_sets = Vec::new();
-    for (variable_ix, _variable) in lexical_grammar.variables.iter().enumerate() {
-        let symbol = Symbol::terminal(variable_ix);
-        builder.reset();
-        builder.add_state_for_tokens(&TokenSet::from_iter([symbol]));
-        for state in &builder.table.states {
-            let mut characters = CharacterSet::empty();
-            for (chars, action) in &state.advance_actions {
-                if action.in_main_token {
-                    characters = characters.add(chars);
-                    continue;
-                }
+    // Build a separate lex table for each token in order to find the large character
+    // sets that it uses. The tokens are independent, so their tables are built in parallel,
+    // and then the character sets are deduplicated in the order of the tokens.
+    let character_sets_by_token = (0..lexical_grammar.variables.len())
+        .into_par_iter()
+        .map_init(
+            || LexTableBuilder::new(lexical_grammar),
+            |builder, variable_ix| {
+                let symbol = Symbol::terminal(variable_ix);
+                builder.reset();
+                builder.add_state_for_tokens(&TokenSet::from_iter([symbol]));
+                let mut character_sets = Vec::new();
+                for state in &builder.table.states {
+                    let mut characters = CharacterSet::empty();
+                    for (chars, action) in &state.advance_actions {
+                        if action.in_main_token {
+                            characters = characters.add(chars);
+                            continue;
+                        }
+
+                        if chars.range_count() > LARGE_CHARACTER_RANGE_COUNT {
+                            character_sets.push((None, chars.clone()));
+                        }
+                    }
 
-                if chars.range_count() > LARGE_CHARACTER_RANGE_COUNT
-                    && !large_character_sets.iter().any(|(_, set)| set == chars)
-                {
-                    large_character_sets.push((None, chars.clone()));
+                    if characters.range_count() > LARGE_CHARACTER_RANGE_COUNT {
+                        character_sets.push((Some(symbol), characters));
+                    }
                 }
-            }
+                character_sets
+            },
+        )
+        .collect::<Vec<_>>();
 
-            if characters.range_count() > LARGE_CHARACTER_RANGE_COUNT
-                && !large_character_sets
-                    .iter()
-                    .any(|(_, set)| *set == characters)
-            {
-                large_character_sets.push((Some(symbol), characters));
-            }
+    let mut large_character_sets = Vec::<(Option<Symbol>, CharacterSet)>::new();
+    for (symbol, characters) in character_sets_by_token.into_iter().flatten() {
+        if !large_character_sets
+            .iter()
+            .any(|(_, set)| *set == characters)
+        {
+            large_character_sets.push((symbol, characters));
         }
     }
 
@@ -196,6 +221,36 @@ impl<'a> LexTableBuilder<'a> {
         state_id
     }
 
+    /// Add a separate start state for each of the given sets of tokens, so that the start
+    /// state for the set at index `i` has the id `i`.
+    fn add_start_states_for_tokens(&mut self, token_sets: &[TokenSet]) {
+        for tokens in token_sets {
+            let nfa_states = tokens
+                .iter()
+                .map(|token| self.lexical_grammar.variables[token.index].start_state)
+                .collect();
+            // Word tokens that capture the same keywords still need distinct start states.
+            let (_, is_new) = self.add_state(nfa_states, false);
+            if !is_new {
+                let state_id = self.table.states.len();
+                self.table.states.push(LexState::default());
+                self.state_queue.push_back(QueueEntry {
+                    state_id,
+                    nfa_states: self.cursor.state_ids.clone(),
+                    eof_valid: false,
+                });
+            }
+        }
+        while let Some(QueueEntry {
+            state_id,
+            nfa_states,
+            eof_valid,
+        }) = self.state_queue.pop_front()
+        {
+            self.populate_state(state_id, nfa_states, eof_valid);
+        }
+    }
+
     fn add_state(&mut self, nfa_states: Vec<u32>, eof_valid: bool) -> (usize, bool) {
         self.cursor.reset(nfa_states);
         match self
@@ -314,80 +369,178 @@ fn merge_token_set(
     true
 }
 
-fn minimize_lex_table(table: &mut LexTable, parse_table: &mut ParseTable) {
-    // Initially group the states by their accept action and their
-    // valid lookahead characters.
-    let mut state_ids_by_signature = HashMap::new();
-    for (i, state) in table.states.iter().enumerate() {
-        let signature = (
-            i == 0,
-            state.accept_action,
-            state.eof_action.is_some(),
-            state
-                .advance_actions
-                .iter()
-                .map(|(characters, action)| (characters.clone(), action.in_main_token))
-                .collect::<Vec<_>>(),
-        );
-        state_ids_by_signature
-            .entry(signature)
-            .or_insert(Vec::new())
-            .push(i);
-    }
-    let mut state_ids_by_group_id = state_ids_by_signature
-        .into_iter()
-        .map(|e| e.1)
-        .collect::<Vec<_>>();
-    state_ids_by_group_id.sort();
-    let error_group_index = state_ids_by_group_id
-        .iter()
-        .position(|g| g.contains(&0))
-        .unwrap();
-    state_ids_by_group_id.swap(error_group_index, 0);
+/// The characters that lead from a lex state into a given group of states, split by whether
+/// the transition is part of the main token, and whether the state advances into the group
+/// at EOF.
+#[derive(Default, PartialEq, Eq, Hash)]
+struct GroupInputs {
+    main_token_chars: CharacterSet,
+    separator_chars: CharacterSet,
+    eof: bool,
+}
 
+/// Merge the lex states that can't be distinguished, because they accept the same token, and
+/// for every lookahead character and at EOF, they advance to states that can't be distinguished
+/// either. This uses Hopcroft's partition refinement algorithm, in which each splitter is a
+/// group of states together with all of the characters that lead into it, so the states only
+/// need to be compared by the transitions that lead into each group.
+///
+/// The first `fixed_state_count` states are referred to from outside of the table, so they are
+/// never merged, and they keep their ids. Returns the new id of each of the original states.
+fn minimize_lex_table(table: &mut LexTable, fixed_state_count: usize) -> Vec<usize> {
+    // Initially group the states by their accept action and by the lookahead characters
+    // for which they have a transition.
+    let mut group_ids_by_signature = HashMap::new();
+    let mut state_ids_by_group_id = Vec::<Vec<usize>>::new();
     let mut group_ids_by_state_id = vec![0; table.states.len()];
-    for (group_id, state_ids) in state_ids_by_group_id.iter().enumerate() {
-        for state_id in state_ids {
-            group_ids_by_state_id[*state_id] = group_id;
+    let mut predecessors = vec![Vec::new(); table.states.len()];
+    for (state_id, state) in table.states.iter().enumerate() {
+        let mut main_token_chars = CharacterSet::empty();
+        let mut separator_chars = CharacterSet::empty();
+        for (transition_index, (chars, action)) in state.advance_actions.iter().enumerate() {
+            if action.in_main_token {
+                main_token_chars = main_token_chars.add(chars);
+            } else {
+                separator_chars = separator_chars.add(chars);
+            }
+            predecessors[action.state].push((state_id, Some(transition_index)));
+        }
+        if let Some(eof_action) = &state.eof_action {
+            predecessors[eof_action.state].push((state_id, None));
         }
+
+        let group_id = if state_id < fixed_state_count {
+            state_ids_by_group_id.push(Vec::new());
+            state_ids_by_group_id.len() - 1
+        } else {
+            let signature = (
+                state.accept_action,
+                state.eof_action.is_some(),
+                main_token_chars,
+                separator_chars,
+            );
+            *group_ids_by_signature.entry(signature).or_insert_with(|| {
+                state_ids_by_group_id.push(Vec::new());
+                state_ids_by_group_id.len() - 1
+            })
+        };
+        state_ids_by_group_id[group_id].push(state_id);
+        group_ids_by_state_id[state_id] = group_id;
     }
 
-    while split_state_id_groups(
-        &table.states,
-        &mut state_ids_by_group_id,
-        &mut group_ids_by_state_id,
-        1,
-        lex_states_differ,
-    ) {}
-
-    let mut new_states = Vec::with_capacity(state_ids_by_group_id.len());
-    for state_ids in &state_ids_by_group_id {
-        let mut new_state = LexState::default();
-        mem::swap(&mut new_state, &mut table.states[state_ids[0]]);
-
-        for (_, advance_action) in &mut new_state.advance_actions {
-            advance_action.state = group_ids_by_state_id[advance_action.state];
+    let mut pending_group_ids = (0..state_ids_by_group_id.len()).rev().collect::<Vec<_>>();
+    let mut is_pending = vec![true; state_ids_by_group_id.len()];
+    while let Some(splitter_group_id) = pending_group_ids.pop() {
+        is_pending[splitter_group_id] = false;
+
+        // Find the characters that lead from each state into the splitter group.
+        let mut inputs_by_state_id = HashMap::<usize, GroupInputs>::new();
+        for target_state_id in &state_ids_by_group_id[splitter_group_id] {
+            for (state_id, transition_index) in &predecessors[*target_state_id] {
+                let inputs = inputs_by_state_id.entry(*state_id).or_default();
+                if let Some(transition_index) = transition_index {
+                    let (chars, action) =
+                        &table.states[*state_id].advance_actions[*transition_index];
+                    if action.in_main_token {
+                        inputs.main_token_chars =
+                            mem::take(&mut inputs.main_token_chars).add(chars);
+                    } else {
+                        inputs.separator_chars = mem::take(&mut inputs.separator_chars).add(chars);
+                    }
+                } else {
+                    inputs.eof = true;
+                }
+            }
         }
-        if let Some(eof_action) = &mut new_state.eof_action {
-            eof_action.state = group_ids_by_state_id[eof_action.state];
+
+        // Split each group whose states don't all have the same inputs.
+        let mut affected_group_ids = inputs_by_state_id
+            .keys()
+            .map(|state_id| group_ids_by_state_id[*state_id])
+            .collect::<Vec<_>>();
+        affected_group_ids.sort_unstable();
+        affected_group_ids.dedup();
+        for group_id in affected_group_ids {
+            let mut part_ids_by_inputs = HashMap::new();
+            let mut parts = Vec::<Vec<usize>>::new();
+            for state_id in &state_ids_by_group_id[group_id] {
+                let part_id = *part_ids_by_inputs
+                    .entry(inputs_by_state_id.get(state_id))
+                    .or_insert_with(|| {
+                        parts.push(Vec::new());
+                        parts.len() - 1
+                    });
+                parts[part_id].push(*state_id);
+            }
+            if parts.len() == 1 {
+                continue;
+            }
+
+            // If the group was already used as a splitter, then splitting by all but one of
+            // its parts is equivalent to splitting by all of them, so the largest part can be
+            // skipped.
+            let was_pending = is_pending[group_id];
+            let largest_part_index = (0..parts.len())
+                .max_by_key(|i| (parts[*i].len(), std::cmp::Reverse(*i)))
+                .unwrap();
+            for (part_index, part) in parts.into_iter().enumerate() {
+                let part_group_id = if part_index == 0 {
+                    state_ids_by_group_id[group_id] = part;
+                    group_id
+                } else {
+                    let part_group_id = state_ids_by_group_id.len();
+                    for state_id in &part {
+                        group_ids_by_state_id[*state_id] = part_group_id;
+                    }
+                    state_ids_by_group_id.push(part);
+                    is_pending.push(false);
+                    part_group_id
+                };
+                if !is_pending[part_group_id] && (was_pending || part_index != largest_part_index) {
+                    is_pending[part_group_id] = true;
+                    pending_group_ids.push(part_group_id);
+                }
+            }
         }
-        new_states.push(new_state);
     }
 
-    for state in &mut parse_table.states {
-        state.lex_state_id = group_ids_by_state_id[state.lex_state_id];
+    // Number the groups in the order of their first state, so that the fixed states keep
+    // their ids, and replace each group with a single state.
+    let mut new_ids_by_group_id = vec![None; state_ids_by_group_id.len()];
+    let mut new_ids_by_state_id = vec![0; table.states.len()];
+    let mut representative_state_ids = Vec::with_capacity(state_ids_by_group_id.len());
+    for (state_id, group_id) in group_ids_by_state_id.iter().enumerate() {
+        new_ids_by_state_id[state_id] = *new_ids_by_group_id[*group_id].get_or_insert_with(|| {
+            representative_state_ids.push(state_id);
+            representative_state_ids.len() - 1
+        });
     }
 
-    table.states = new_states;
-}
+    table.states = representative_state_ids
+        .into_iter()
+        .map(|state_id| {
+            let mut state = mem::take(&mut table.states[state_id]);
+            if let Some(eof_action) = &mut state.eof_action {
+                eof_action.state = new_ids_by_state_id[eof_action.state];
+            }
 
-fn lex_states_differ(left: &LexState, right: &LexState, group_ids_by_state_id: &[usize]) -> bool {
-    left.advance_actions
-        .iter()
-        .zip(right.advance_actions.iter())
-        .any(|(left, right)| {
-            group_ids_by_state_id[left.1.state] != group_ids_by_state_id[right.1.state]
+            // Transitions that now lead to the same state are combined.
+            let mut advance_actions = Vec::<(CharacterSet, AdvanceAction)>::new();
+            for (chars, mut action) in state.advance_actions {
+                action.state = new_ids_by_state_id[action.state];
+                if let Some(existing) = advance_actions.iter_mut().find(|(_, a)| *a == action) {
+                    existing.0 = mem::take(&mut existing.0).add(&chars);
+                } else {
+                    advance_actions.push((chars, action));
+                }
+            }
+            advance_actions.sort_by(|a, b| a.0.cmp(&b.0));
+            state.advance_actions = advance_actions;
+            state
         })
+        .collect();
+
+    new_ids_by_state_id
 }
 
 fn sort_states(table: &mut LexTable, parse_table: &mut ParseTable) {
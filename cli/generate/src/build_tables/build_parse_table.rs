@@ -2,18 +2,22 @@ use std::{
     cmp::Ordering,
     collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     hash::BuildHasherDefault,
+    time::{Duration, Instant},
 };
 
 use indexmap::{map::Entry, IndexMap};
+use rayon::prelude::*;
 use rustc_hash::FxHasher;
 use serde::Serialize;
 use thiserror::Error;
 
 use super::{
+    get_state_indices_by_variable,
     item::{ParseItem, ParseItemSet, ParseItemSetCore, ParseItemSetEntry},
     item_set_builder::ParseItemSetBuilder,
 };
 use crate::{
+    dynamic_precedence_report::{DynamicPrecedenceReport, GlrConflict},
     grammars::{LexicalGrammar, PrecedenceEntry, ReservedWordSetId, SyntaxGrammar, VariableType},
     node_types::VariableInfo,
     rules::{Associativity, Precedence, Symbol, SymbolType, TokenSet},
@@ -21,8 +25,21 @@ use crate::{
         FieldLocation, GotoAction, ParseAction, ParseState, ParseStateId, ParseTable,
         ParseTableEntry, ProductionInfo, ProductionInfoId,
     },
+    timing::TimingReport,
 };
 
+// The maximum number of parse states whose transitive closures are computed at the
+// same time. This bounds the memory used by item sets that are waiting to be processed.
+const MAX_CLOSURE_BATCH_SIZE: usize = 256;
+
+// The maximum number of example derivations that are reported for each possible
+// interpretation of a conflict.
+const MAX_EXAMPLE_DERIVATIONS: usize = 2;
+
+// The number of rules that are listed when the parse table exceeds its maximum number
+// of states.
+const STATE_LIMIT_REPORTED_RULE_COUNT: usize = 10;
+
 // For conflict reporting, each parse state is associated with an example
 // sequence of symbols that could lead to that parse state.
 type SymbolSequence = Vec<Symbol>;
@@ -60,8 +77,11 @@ struct ParseTableBuilder<'a> {
     parse_state_info_by_id: Vec<ParseStateInfo<'a>>,
     parse_state_queue: VecDeque<ParseStateQueueEntry>,
     non_terminal_extra_states: Vec<(Symbol, usize)>,
-    actual_conflicts: HashSet<Vec<Symbol>>,
+    actual_conflicts: BTreeSet<Vec<Symbol>>,
     parse_table: ParseTable,
+    max_states: Option<usize>,
+    closure_timings: Option<Vec<(Duration, usize)>>,
+    glr_conflicts: Option<Vec<GlrConflict>>,
 }
 
 pub type BuildTableResult<T> = Result<T, ParseTableBuilderError>;
@@ -76,6 +96,26 @@ pub enum ParseTableBuilderError {
         "The non-terminal rule `{0}` is used in a non-terminal `extra` rule, which is not allowed."
     )]
     ImproperNonTerminalExtra(String),
+    #[error(transparent)]
+    StateLimit(#[from] StateLimitError),
+}
+
+impl ParseTableBuilderError {
+    pub(crate) const fn code(&self) -> &'static str {
+        match self {
+            Self::Conflict(_) => "unresolved-conflict",
+            Self::AmbiguousExtra(_) => "ambiguous-extra",
+            Self::ImproperNonTerminalExtra(_) => "non-terminal-extra",
+            Self::StateLimit(_) => "state-limit",
+        }
+    }
+
+    pub(crate) fn rule(&self) -> Option<&str> {
+        match self {
+            Self::ImproperNonTerminalExtra(rule) => Some(rule),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Default, Debug, Serialize)]
@@ -96,6 +136,17 @@ pub struct Interpretation {
     pub conflicting_lookahead: String,
     pub precedence: Option<String>,
     pub associativity: Option<String>,
+    pub derivations: Vec<Vec<DerivationStep>>,
+}
+
+/// One rule in an example derivation of a conflicting item, starting from the start rule.
+/// For every rule but the last, `step_index` is the index of the child that the next rule
+/// is derived from. For the last rule, it is the position of the conflict.
+#[derive(Debug, Serialize)]
+pub struct DerivationStep {
+    pub variable_name: String,
+    pub production_step_symbols: Vec<String>,
+    pub step_index: u32,
 }
 
 #[derive(Debug, Serialize)]
@@ -110,6 +161,12 @@ pub struct AmbiguousExtraError {
     pub parent_symbols: Vec<String>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct StateLimitError {
+    pub max_states: usize,
+    pub state_counts_by_rule: Vec<(String, usize)>,
+}
+
 impl std::fmt::Display for ConflictError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         for symbol in &self.symbol_sequence {
@@ -135,7 +192,7 @@ impl std::fmt::Display for ConflictError {
                         .map(|precedence| format!("(precedence: {precedence})"))
                 };
 
-                (line, prec_line)
+                (line, prec_line, i)
             })
             .collect::<Vec<_>>();
         let max_interpretation_length = interpretations
@@ -143,11 +200,11 @@ impl std::fmt::Display for ConflictError {
             .map(|i| i.0.chars().count())
             .max()
             .unwrap();
-        interpretations.sort_unstable();
-        for (i, (line, prec_suffix)) in interpretations.into_iter().enumerate() {
+        interpretations.sort_unstable_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        for (i, (line, prec_suffix, _)) in interpretations.iter().enumerate() {
             write!(f, "  {}:", i + 1).unwrap();
             write!(f, "{line}")?;
-            if let Some(prec_suffix) = prec_suffix {
+            if let Some(prec_suffix) = prec_suffix.as_ref() {
                 write!(
                     f,
                     "{:1$}",
@@ -159,6 +216,29 @@ impl std::fmt::Display for ConflictError {
             writeln!(f)?;
         }
 
+        if interpretations
+            .iter()
+            .any(|(.., interpretation)| !interpretation.derivations.is_empty())
+        {
+            writeln!(f, "\nExample derivations:")?;
+            for (i, (.., interpretation)) in interpretations.iter().enumerate() {
+                let label = format!("  {}:  ", i + 1);
+                for (j, derivation) in interpretation.derivations.iter().enumerate() {
+                    if j == 0 {
+                        write!(f, "\n{label}")?;
+                    } else {
+                        write!(f, "\n{:1$}", "", label.chars().count())?;
+                    }
+                    write_derivation(
+                        f,
+                        label.chars().count(),
+                        derivation,
+                        &interpretation.conflicting_lookahead,
+                    )?;
+                }
+            }
+        }
+
         writeln!(f, "\nPossible resolutions:\n")?;
         for (i, resolution) in self.possible_resolutions.iter().enumerate() {
             writeln!(f, "  {}:  {resolution}", i + 1)?;
@@ -167,6 +247,41 @@ impl std::fmt::Display for ConflictError {
     }
 }
 
+/// Write a derivation as an indented tree, where each rule is written below the child of
+/// the previous rule that it is derived from.
+fn write_derivation(
+    f: &mut std::fmt::Formatter,
+    indent: usize,
+    derivation: &[DerivationStep],
+    conflicting_lookahead: &str,
+) -> std::fmt::Result {
+    writeln!(f, "{}", derivation[0].variable_name)?;
+    let mut column = indent;
+    for (i, step) in derivation.iter().enumerate() {
+        let is_last = i == derivation.len() - 1;
+        let mut width = column + 1;
+        let mut child_column = column;
+        write!(f, "{:column$}↳", "")?;
+        for (j, symbol) in step.production_step_symbols.iter().enumerate() {
+            if j == step.step_index as usize {
+                if is_last {
+                    write!(f, "  •")?;
+                } else {
+                    child_column = width + 2;
+                }
+            }
+            write!(f, "  {symbol}")?;
+            width += symbol.chars().count() + 2;
+        }
+        if is_last && step.step_index as usize == step.production_step_symbols.len() {
+            write!(f, "  •  {conflicting_lookahead}  …")?;
+        }
+        writeln!(f)?;
+        column = child_column;
+    }
+    Ok(())
+}
+
 impl std::fmt::Display for Interpretation {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         for symbol in &self.preceding_symbols {
@@ -235,8 +350,29 @@ impl std::fmt::Display for AmbiguousExtraError {
     }
 }
 
+impl std::fmt::Display for StateLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "The parse table exceeded the maximum of {} states. The rules that appear in the most states are:\n",
+            self.max_states
+        )?;
+        let max_name_length = self
+            .state_counts_by_rule
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (name, count) in &self.state_counts_by_rule {
+            writeln!(f, "  {name:max_name_length$}  {count}")?;
+        }
+        Ok(())
+    }
+}
+
 impl std::error::Error for ConflictError {}
 impl std::error::Error for AmbiguousExtraError {}
+impl std::error::Error for StateLimitError {}
 
 impl<'a> ParseTableBuilder<'a> {
     fn new(
@@ -244,6 +380,9 @@ impl<'a> ParseTableBuilder<'a> {
         lexical_grammar: &'a LexicalGrammar,
         item_set_builder: ParseItemSetBuilder<'a>,
         variable_info: &'a [VariableInfo],
+        max_states: Option<usize>,
+        record_closure_timings: bool,
+        record_glr_conflicts: bool,
     ) -> Self {
         Self {
             syntax_grammar,
@@ -263,10 +402,18 @@ impl<'a> ParseTableBuilder<'a> {
                 production_infos: Vec::new(),
                 max_aliased_production_length: 1,
             },
+            max_states,
+            closure_timings: record_closure_timings
+                .then(|| vec![(Duration::ZERO, 0); syntax_grammar.variables.len()]),
+            glr_conflicts: record_glr_conflicts.then(Vec::new),
         }
     }
 
-    fn build(mut self) -> BuildTableResult<(ParseTable, Vec<ParseStateInfo<'a>>)> {
+    fn build(
+        mut self,
+        timings: &mut TimingReport,
+        dynamic_precedence_report: Option<&mut DynamicPrecedenceReport>,
+    ) -> BuildTableResult<(ParseTable, Vec<ParseStateInfo<'a>>)> {
         // Ensure that the empty alias sequence has index 0.
         self.parse_table
             .production_infos
@@ -325,17 +472,49 @@ impl<'a> ParseTableBuilder<'a> {
             self.add_parse_state(&Vec::new(), &Vec::new(), item_set);
         }
 
-        while let Some(entry) = self.parse_state_queue.pop_front() {
-            let item_set = self
-                .item_set_builder
-                .transitive_closure(&self.parse_state_info_by_id[entry.state_id].1);
+        // Process the queued states in batches. The transitive closures of the states in a
+        // batch are computed in parallel, and then their actions are added in queue order,
+        // so that states are numbered the same way regardless of the number of threads.
+        while !self.parse_state_queue.is_empty() {
+            let batch_size = self.parse_state_queue.len().min(MAX_CLOSURE_BATCH_SIZE);
+            let entries = self
+                .parse_state_queue
+                .drain(..batch_size)
+                .collect::<Vec<_>>();
+            let item_set_builder = &self.item_set_builder;
+            let parse_state_info_by_id = &self.parse_state_info_by_id;
+            let variable_count = self.syntax_grammar.variables.len();
+            let record_closure_timings = self.closure_timings.is_some();
+            let item_sets = entries
+                .par_iter()
+                .map(|entry| {
+                    let mut closure_timings =
+                        record_closure_timings.then(|| vec![(Duration::ZERO, 0); variable_count]);
+                    let item_set = item_set_builder.transitive_closure_with_timings(
+                        &parse_state_info_by_id[entry.state_id].1,
+                        closure_timings.as_deref_mut(),
+                    );
+                    (item_set, closure_timings)
+                })
+                .collect::<Vec<_>>();
 
-            self.add_actions(
-                self.parse_state_info_by_id[entry.state_id].0.clone(),
-                entry.preceding_auxiliary_symbols,
-                entry.state_id,
-                &item_set,
-            )?;
+            for (entry, (item_set, closure_timings)) in entries.into_iter().zip(item_sets) {
+                if let (Some(totals), Some(closure_timings)) =
+                    (self.closure_timings.as_mut(), closure_timings)
+                {
+                    for (total, timing) in totals.iter_mut().zip(closure_timings) {
+                        total.0 += timing.0;
+                        total.1 += timing.1;
+                    }
+                }
+                self.add_actions(
+                    self.parse_state_info_by_id[entry.state_id].0.clone(),
+                    entry.preceding_auxiliary_symbols,
+                    entry.state_id,
+                    &item_set,
+                )?;
+                self.check_state_limit()?;
+            }
         }
 
         if !self.actual_conflicts.is_empty() {
@@ -352,9 +531,50 @@ impl<'a> ParseTableBuilder<'a> {
             }
         }
 
+        if let Some(closure_timings) = self.closure_timings.take() {
+            timings.set_closure_timings(
+                self.syntax_grammar
+                    .variables
+                    .iter()
+                    .map(|variable| variable.name.clone())
+                    .zip(closure_timings),
+            );
+        }
+
+        if let (Some(report), Some(glr_conflicts)) =
+            (dynamic_precedence_report, self.glr_conflicts.take())
+        {
+            report.record(self.syntax_grammar, &glr_conflicts);
+        }
+
         Ok((self.parse_table, self.parse_state_info_by_id))
     }
 
+    /// Fail if the parse table has more states than allowed, reporting the rules whose
+    /// items appear in the most states so far.
+    fn check_state_limit(&self) -> BuildTableResult<()> {
+        let Some(max_states) = self.max_states else {
+            return Ok(());
+        };
+        if self.parse_table.states.len() <= max_states {
+            return Ok(());
+        }
+        let state_counts_by_rule = get_state_indices_by_variable(
+            self.syntax_grammar,
+            self.parse_state_info_by_id
+                .iter()
+                .map(|(_, item_set)| item_set),
+        )
+        .into_iter()
+        .take(STATE_LIMIT_REPORTED_RULE_COUNT)
+        .map(|(symbol, state_indices)| (self.symbol_name(&symbol), state_indices.len()))
+        .collect();
+        Err(StateLimitError {
+            max_states,
+            state_counts_by_rule,
+        })?
+    }
+
     fn add_parse_state(
         &mut self,
         preceding_symbols: &SymbolSequence,
@@ -605,7 +825,7 @@ impl<'a> ParseTableBuilder<'a> {
                             None
                         }
                     })
-                    .collect::<HashSet<_>>();
+                    .collect::<BTreeSet<_>>();
                 let parent_symbol_names = parent_symbols
                     .iter()
                     .map(|&variable_index| {
@@ -655,18 +875,22 @@ impl<'a> ParseTableBuilder<'a> {
             }
         }
 
-        if let Some(keyword_capture_token) = self.syntax_grammar.word_token {
+        let word_tokens = &self.syntax_grammar.word_tokens;
+        if !word_tokens.is_empty() {
             let reserved_word_set_id = item_set
                 .entries
                 .iter()
                 .filter_map(|entry| {
                     if let Some(next_step) = entry.item.step() {
-                        if next_step.symbol == keyword_capture_token {
+                        if word_tokens.contains(&next_step.symbol) {
                             Some(next_step.reserved_word_set_id)
                         } else {
                             None
                         }
-                    } else if entry.lookaheads.contains(&keyword_capture_token) {
+                    } else if word_tokens
+                        .iter()
+                        .any(|word_token| entry.lookaheads.contains(word_token))
+                    {
                         Some(entry.following_reserved_word_set)
                     } else {
                         None
@@ -841,6 +1065,16 @@ impl<'a> ParseTableBuilder<'a> {
             .expected_conflicts
             .contains(&actual_conflict)
         {
+            let lookahead = self
+                .glr_conflicts
+                .is_some()
+                .then(|| self.symbol_name(&conflicting_lookahead));
+            if let (Some(glr_conflicts), Some(lookahead)) = (&mut self.glr_conflicts, lookahead) {
+                glr_conflicts.push(GlrConflict {
+                    symbols: actual_conflict.clone(),
+                    lookahead,
+                });
+            }
             self.actual_conflicts.remove(&actual_conflict);
             return Ok(());
         }
@@ -853,6 +1087,7 @@ impl<'a> ParseTableBuilder<'a> {
         }
         conflict_error.conflicting_lookahead = self.symbol_name(&conflicting_lookahead).to_string();
 
+        let item_sets = self.retrace_item_sets(preceding_symbols);
         let interpretations = conflicting_items
             .iter()
             .map(|item| {
@@ -889,6 +1124,7 @@ impl<'a> ParseTableBuilder<'a> {
                     conflicting_lookahead: self.symbol_name(&conflicting_lookahead).to_string(),
                     precedence,
                     associativity,
+                    derivations: self.example_derivations(&item_sets, item),
                 }
             })
             .collect::<Vec<_>>();
@@ -959,6 +1195,128 @@ impl<'a> ParseTableBuilder<'a> {
         Err(conflict_error)?
     }
 
+    /// Compute the transitive closures of the item sets that are reached by following the
+    /// given sequence of symbols from the start state.
+    fn retrace_item_sets(&self, symbols: &SymbolSequence) -> Vec<ParseItemSet<'a>> {
+        let mut item_sets = vec![self
+            .item_set_builder
+            .transitive_closure(&self.parse_state_info_by_id[1].1)];
+        for symbol in symbols {
+            let successor_set = ParseItemSet {
+                entries: item_sets
+                    .last()
+                    .unwrap()
+                    .entries
+                    .iter()
+                    .filter(|entry| entry.item.symbol() == Some(*symbol))
+                    .map(|entry| ParseItemSetEntry {
+                        item: entry.item.successor(),
+                        lookaheads: entry.lookaheads.clone(),
+                        following_reserved_word_set: entry.following_reserved_word_set,
+                    })
+                    .collect(),
+            };
+            item_sets.push(self.item_set_builder.transitive_closure(&successor_set));
+        }
+        item_sets
+    }
+
+    /// Find example derivations of a conflicting item, given the item sets that lead to the
+    /// conflicting state. Starting from the item, this repeatedly finds the item through
+    /// which its rule was added to the item set where it started, until reaching the start
+    /// rule.
+    fn example_derivations(
+        &self,
+        item_sets: &[ParseItemSet<'a>],
+        item: &ParseItem,
+    ) -> Vec<Vec<DerivationStep>> {
+        let mut derivations = Vec::new();
+        let Some(mut item_set_index) = item_sets.len().checked_sub(item.step_index as usize + 1)
+        else {
+            return derivations;
+        };
+        for mut parent_items in Self::find_parent_items(
+            &item_sets[item_set_index],
+            item.variable_index,
+            MAX_EXAMPLE_DERIVATIONS,
+        ) {
+            let mut steps = vec![self.derivation_step(item)];
+            loop {
+                let kernel_item = *parent_items.last().unwrap();
+                steps.extend(
+                    parent_items
+                        .iter()
+                        .filter(|item| item.variable_index != u32::MAX)
+                        .map(|item| self.derivation_step(item)),
+                );
+                if kernel_item.variable_index == u32::MAX {
+                    steps.reverse();
+                    derivations.push(steps);
+                    break;
+                }
+                let Some(index) = item_set_index.checked_sub(kernel_item.step_index as usize)
+                else {
+                    break;
+                };
+                item_set_index = index;
+                let Some(items) =
+                    Self::find_parent_items(&item_sets[index], kernel_item.variable_index, 1).pop()
+                else {
+                    break;
+                };
+                parent_items = items;
+            }
+        }
+        derivations
+    }
+
+    /// Find the shortest chains of items through which the given variable was added to an
+    /// item set by the transitive closure. Each chain starts with an item whose next symbol
+    /// is the variable, and ends with a kernel item of the item set.
+    fn find_parent_items<'b>(
+        item_set: &ParseItemSet<'b>,
+        variable_index: u32,
+        limit: usize,
+    ) -> Vec<Vec<ParseItem<'b>>> {
+        let mut result = Vec::new();
+        let mut visited_variables = HashSet::from([variable_index]);
+        let mut queue = VecDeque::from([(variable_index, Vec::new())]);
+        while let Some((variable_index, chain)) = queue.pop_front() {
+            let symbol = Symbol::non_terminal(variable_index as usize);
+            for entry in &item_set.entries {
+                if entry.item.symbol() != Some(symbol) {
+                    continue;
+                }
+                let mut chain = chain.clone();
+                chain.push(entry.item);
+                if entry.item.step_index > 0 || entry.item.variable_index == u32::MAX {
+                    result.push(chain);
+                    if result.len() == limit {
+                        return result;
+                    }
+                } else if visited_variables.insert(entry.item.variable_index) {
+                    queue.push_back((entry.item.variable_index, chain));
+                }
+            }
+        }
+        result
+    }
+
+    fn derivation_step(&self, item: &ParseItem) -> DerivationStep {
+        DerivationStep {
+            variable_name: self.syntax_grammar.variables[item.variable_index as usize]
+                .name
+                .clone(),
+            production_step_symbols: item
+                .production
+                .steps
+                .iter()
+                .map(|step| self.symbol_name(&step.symbol))
+                .collect(),
+            step_index: item.step_index,
+        }
+    }
+
     fn compare_precedence(
         grammar: &SyntaxGrammar,
         left: &Precedence,
@@ -1125,12 +1483,21 @@ pub fn build_parse_table<'a>(
     lexical_grammar: &'a LexicalGrammar,
     item_set_builder: ParseItemSetBuilder<'a>,
     variable_info: &'a [VariableInfo],
+    max_states: Option<usize>,
+    timings: &mut TimingReport,
+    dynamic_precedence_report: Option<&mut DynamicPrecedenceReport>,
 ) -> BuildTableResult<(ParseTable, Vec<ParseStateInfo<'a>>)> {
-    ParseTableBuilder::new(
+    let start = Instant::now();
+    let result = ParseTableBuilder::new(
         syntax_grammar,
         lexical_grammar,
         item_set_builder,
         variable_info,
+        max_states,
+        timings.is_enabled(),
+        dynamic_precedence_report.is_some(),
     )
-    .build()
+    .build(timings, dynamic_precedence_report);
+    timings.record("build_parse_table", start.elapsed());
+    result
 }
@@ -1,8 +1,11 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt,
+    time::{Duration, Instant},
 };
 
+use rayon::prelude::*;
+
 use super::item::{ParseItem, ParseItemDisplay, ParseItemSet, ParseItemSetEntry, TokenSetDisplay};
 use crate::{
     grammars::{InlinedProductionMap, LexicalGrammar, ReservedWordSetId, SyntaxGrammar},
@@ -51,7 +54,7 @@ impl<'a> ParseItemSetBuilder<'a> {
             reserved_first_sets: HashMap::new(),
             last_sets: HashMap::new(),
             inlines,
-            transitive_closure_additions: vec![Vec::new(); syntax_grammar.variables.len()],
+            transitive_closure_additions: Vec::new(),
         };
 
         // For each grammar symbol, populate the FIRST and LAST sets: the set of
@@ -157,110 +160,148 @@ impl<'a> ParseItemSetBuilder<'a> {
         //     occur after `item`.
         //
         // Rather than computing these additions recursively, we use an explicit stack.
+        //
+        // The additions for each non-terminal are independent of each other, so they are
+        // computed in parallel.
+        result.transitive_closure_additions = (0..syntax_grammar.variables.len())
+            .into_par_iter()
+            .map(|i| result.compute_transitive_closure_additions(i))
+            .collect();
+
+        result
+    }
+
+    /// Compute the items that must be added to an item set when the non-terminal
+    /// with the given index occurs as the next symbol in one of its core items.
+    fn compute_transitive_closure_additions(&self, i: usize) -> Vec<TransitiveClosureAddition<'a>> {
+        // First, build up a map whose keys are all of the non-terminals that can
+        // appear at the beginning of non-terminal `i`, and whose values store
+        // information about the tokens that can follow those non-terminals.
         let empty_lookaheads = TokenSet::new();
-        let mut stack = Vec::new();
-        let mut follow_set_info_by_non_terminal = HashMap::<usize, FollowSetInfo>::new();
-        for i in 0..syntax_grammar.variables.len() {
-            // First, build up a map whose keys are all of the non-terminals that can
-            // appear at the beginning of non-terminal `i`, and whose values store
-            // information about the tokens that can follow those non-terminals.
-            stack.clear();
-            stack.push((i, &empty_lookaheads, ReservedWordSetId::default(), true));
-            follow_set_info_by_non_terminal.clear();
-            while let Some((sym_ix, lookaheads, reserved_word_set_id, propagates_lookaheads)) =
-                stack.pop()
-            {
-                let mut did_add = false;
-                let info = follow_set_info_by_non_terminal.entry(sym_ix).or_default();
-                did_add |= info.lookaheads.insert_all(lookaheads);
-                if reserved_word_set_id > info.reserved_lookaheads {
-                    info.reserved_lookaheads = reserved_word_set_id;
-                    did_add = true;
-                }
-                did_add |= propagates_lookaheads && !info.propagates_lookaheads;
-                info.propagates_lookaheads |= propagates_lookaheads;
-                if !did_add {
-                    continue;
-                }
+        let mut stack = vec![(i, &empty_lookaheads, ReservedWordSetId::default(), true)];
+        let mut follow_set_info_by_non_terminal = BTreeMap::<usize, FollowSetInfo>::new();
+        while let Some((sym_ix, lookaheads, reserved_word_set_id, propagates_lookaheads)) =
+            stack.pop()
+        {
+            let mut did_add = false;
+            let info = follow_set_info_by_non_terminal.entry(sym_ix).or_default();
+            did_add |= info.lookaheads.insert_all(lookaheads);
+            if reserved_word_set_id > info.reserved_lookaheads {
+                info.reserved_lookaheads = reserved_word_set_id;
+                did_add = true;
+            }
+            did_add |= propagates_lookaheads && !info.propagates_lookaheads;
+            info.propagates_lookaheads |= propagates_lookaheads;
+            if !did_add {
+                continue;
+            }
 
-                for production in &syntax_grammar.variables[sym_ix].productions {
-                    if let Some(symbol) = production.first_symbol() {
-                        if symbol.is_non_terminal() {
-                            if let Some(next_step) = production.steps.get(1) {
-                                stack.push((
-                                    symbol.index,
-                                    &result.first_sets[&next_step.symbol],
-                                    result.reserved_first_sets[&next_step.symbol],
-                                    false,
-                                ));
-                            } else {
-                                stack.push((
-                                    symbol.index,
-                                    lookaheads,
-                                    reserved_word_set_id,
-                                    propagates_lookaheads,
-                                ));
-                            }
+            for production in &self.syntax_grammar.variables[sym_ix].productions {
+                if let Some(symbol) = production.first_symbol() {
+                    if symbol.is_non_terminal() {
+                        if let Some(next_step) = production.steps.get(1) {
+                            stack.push((
+                                symbol.index,
+                                &self.first_sets[&next_step.symbol],
+                                self.reserved_first_sets[&next_step.symbol],
+                                false,
+                            ));
+                        } else {
+                            stack.push((
+                                symbol.index,
+                                lookaheads,
+                                reserved_word_set_id,
+                                propagates_lookaheads,
+                            ));
                         }
                     }
                 }
             }
+        }
 
-            // Store all of those non-terminals' productions, along with their associated
-            // lookahead info, as *additions* associated with non-terminal `i`.
-            let additions_for_non_terminal = &mut result.transitive_closure_additions[i];
-            for (&variable_index, follow_set_info) in &follow_set_info_by_non_terminal {
-                let variable = &syntax_grammar.variables[variable_index];
-                let non_terminal = Symbol::non_terminal(variable_index);
-                let variable_index = variable_index as u32;
-                if syntax_grammar.variables_to_inline.contains(&non_terminal) {
-                    continue;
-                }
-                for production in &variable.productions {
-                    let item = ParseItem {
-                        variable_index,
-                        production,
-                        step_index: 0,
-                        has_preceding_inherited_fields: false,
-                    };
-
-                    if let Some(inlined_productions) =
-                        inlines.inlined_productions(item.production, item.step_index)
-                    {
-                        for production in inlined_productions {
-                            find_or_push(
-                                additions_for_non_terminal,
-                                TransitiveClosureAddition {
-                                    item: item.substitute_production(production),
-                                    info: follow_set_info.clone(),
-                                },
-                            );
-                        }
-                    } else {
+        // Store all of those non-terminals' productions, along with their associated
+        // lookahead info, as *additions* associated with non-terminal `i`.
+        let mut additions_for_non_terminal = Vec::new();
+        for (&variable_index, follow_set_info) in &follow_set_info_by_non_terminal {
+            let variable = &self.syntax_grammar.variables[variable_index];
+            let non_terminal = Symbol::non_terminal(variable_index);
+            let variable_index = variable_index as u32;
+            if self
+                .syntax_grammar
+                .variables_to_inline
+                .contains(&non_terminal)
+            {
+                continue;
+            }
+            for production in &variable.productions {
+                let item = ParseItem {
+                    variable_index,
+                    production,
+                    step_index: 0,
+                    has_preceding_inherited_fields: false,
+                };
+
+                if let Some(inlined_productions) = self
+                    .inlines
+                    .inlined_productions(item.production, item.step_index)
+                {
+                    for production in inlined_productions {
                         find_or_push(
-                            additions_for_non_terminal,
+                            &mut additions_for_non_terminal,
                             TransitiveClosureAddition {
-                                item,
+                                item: item.substitute_production(production),
                                 info: follow_set_info.clone(),
                             },
                         );
                     }
+                } else {
+                    find_or_push(
+                        &mut additions_for_non_terminal,
+                        TransitiveClosureAddition {
+                            item,
+                            info: follow_set_info.clone(),
+                        },
+                    );
                 }
             }
         }
 
-        result
+        additions_for_non_terminal
     }
 
     pub fn transitive_closure(&self, item_set: &ParseItemSet<'a>) -> ParseItemSet<'a> {
+        self.transitive_closure_with_timings(item_set, None)
+    }
+
+    /// Compute the transitive closure of an item set. If `timings` are given, the time
+    /// spent expanding each non-terminal and the number of times it was expanded are added
+    /// to the entry for that non-terminal.
+    pub fn transitive_closure_with_timings(
+        &self,
+        item_set: &ParseItemSet<'a>,
+        mut timings: Option<&mut [(Duration, usize)]>,
+    ) -> ParseItemSet<'a> {
         let mut result = ParseItemSet::default();
+        let mut add_item = |result: &mut ParseItemSet<'a>, entry: &ParseItemSetEntry<'a>| {
+            let Some(timings) = timings.as_deref_mut() else {
+                self.add_item(result, entry);
+                return;
+            };
+            let start = Instant::now();
+            self.add_item(result, entry);
+            if let Some(symbol) = entry.item.symbol().filter(Symbol::is_non_terminal) {
+                let timing = &mut timings[symbol.index];
+                timing.0 += start.elapsed();
+                timing.1 += 1;
+            }
+        };
         for entry in &item_set.entries {
             if let Some(productions) = self
                 .inlines
                 .inlined_productions(entry.item.production, entry.item.step_index)
             {
                 for production in productions {
-                    self.add_item(
+                    add_item(
                         &mut result,
                         &ParseItemSetEntry {
                             item: entry.item.substitute_production(production),
@@ -270,7 +311,7 @@ impl<'a> ParseItemSetBuilder<'a> {
                     );
                 }
             } else {
-                self.add_item(&mut result, entry);
+                add_item(&mut result, entry);
             }
         }
         result
@@ -310,23 +351,29 @@ impl<'a> ParseItemSetBuilder<'a> {
                     let entry = set.insert(addition.item);
                     entry.lookaheads.insert_all(&addition.info.lookaheads);
 
-                    if let Some(word_token) = self.syntax_grammar.word_token {
-                        if addition.info.lookaheads.contains(&word_token) {
-                            entry.following_reserved_word_set = entry
-                                .following_reserved_word_set
-                                .max(addition.info.reserved_lookaheads);
-                        }
+                    if self
+                        .syntax_grammar
+                        .word_tokens
+                        .iter()
+                        .any(|word_token| addition.info.lookaheads.contains(word_token))
+                    {
+                        entry.following_reserved_word_set = entry
+                            .following_reserved_word_set
+                            .max(addition.info.reserved_lookaheads);
                     }
 
                     if addition.info.propagates_lookaheads {
                         entry.lookaheads.insert_all(following_tokens);
 
-                        if let Some(word_token) = self.syntax_grammar.word_token {
-                            if following_tokens.contains(&word_token) {
-                                entry.following_reserved_word_set = entry
-                                    .following_reserved_word_set
-                                    .max(following_reserved_tokens);
-                            }
+                        if self
+                            .syntax_grammar
+                            .word_tokens
+                            .iter()
+                            .any(|word_token| following_tokens.contains(word_token))
+                        {
+                            entry.following_reserved_word_set = entry
+                                .following_reserved_word_set
+                                .max(following_reserved_tokens);
                         }
                     }
                 }
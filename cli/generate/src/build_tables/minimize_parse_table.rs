@@ -19,7 +19,7 @@ pub fn minimize_parse_table(
     lexical_grammar: &LexicalGrammar,
     simple_aliases: &AliasMap,
     token_conflict_map: &TokenConflictMap,
-    keywords: &TokenSet,
+    keywords: &[TokenSet],
 ) {
     let mut minimizer = Minimizer {
         parse_table,
@@ -40,7 +40,7 @@ struct Minimizer<'a> {
     syntax_grammar: &'a SyntaxGrammar,
     lexical_grammar: &'a LexicalGrammar,
     token_conflict_map: &'a TokenConflictMap<'a>,
-    keywords: &'a TokenSet,
+    keywords: &'a [TokenSet],
     simple_aliases: &'a AliasMap,
 }
 
@@ -385,10 +385,7 @@ impl Minimizer<'_> {
             if !token.is_terminal() {
                 continue;
             }
-            if self.syntax_grammar.word_token == Some(token) && self.keywords.contains(&new_token) {
-                continue;
-            }
-            if self.syntax_grammar.word_token == Some(new_token) && self.keywords.contains(&token) {
+            if self.is_keyword_of(new_token, token) || self.is_keyword_of(token, new_token) {
                 continue;
             }
 
@@ -413,6 +410,15 @@ impl Minimizer<'_> {
         false
     }
 
+    /// Whether the given token is one of the keywords captured by the given word token.
+    fn is_keyword_of(&self, token: Symbol, word_token: Symbol) -> bool {
+        self.syntax_grammar
+            .word_tokens
+            .iter()
+            .zip(self.keywords)
+            .any(|(t, keywords)| *t == word_token && keywords.contains(&token))
+    }
+
     fn symbol_name(&self, symbol: &Symbol) -> &String {
         if symbol.is_non_terminal() {
             &self.syntax_grammar.variables[symbol.index].name
@@ -12,30 +12,39 @@ pub use build_lex_table::LARGE_CHARACTER_RANGE_COUNT;
 use build_parse_table::BuildTableResult;
 pub use build_parse_table::ParseTableBuilderError;
 use log::info;
+use serde::{Deserialize, Serialize};
 
 use self::{
     build_lex_table::build_lex_table,
     build_parse_table::{build_parse_table, ParseStateInfo},
     coincident_tokens::CoincidentTokenIndex,
+    item::ParseItemSet,
     item_set_builder::ParseItemSetBuilder,
     minimize_parse_table::minimize_parse_table,
     token_conflicts::TokenConflictMap,
 };
 use crate::{
+    dynamic_precedence_report::DynamicPrecedenceReport,
     grammars::{InlinedProductionMap, LexicalGrammar, SyntaxGrammar},
     nfa::{CharacterSet, NfaCursor},
     node_types::VariableInfo,
     rules::{AliasMap, Symbol, SymbolType, TokenSet},
     tables::{LexTable, ParseAction, ParseTable, ParseTableEntry},
+    timing::TimingReport,
 };
 
+#[derive(Serialize, Deserialize)]
 pub struct Tables {
     pub parse_table: ParseTable,
     pub main_lex_table: LexTable,
     pub keyword_lex_table: LexTable,
     pub large_character_sets: Vec<(Option<Symbol>, CharacterSet)>,
+    /// The tokens that are lexed as one of the grammar's word tokens, and then checked against
+    /// the word's text.
+    pub keywords: TokenSet,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn build_tables(
     syntax_grammar: &SyntaxGrammar,
     lexical_grammar: &LexicalGrammar,
@@ -43,8 +52,15 @@ pub fn build_tables(
     variable_info: &[VariableInfo],
     inlines: &InlinedProductionMap,
     report_symbol_name: Option<&str>,
+    report_shadowed_tokens: bool,
+    max_states: Option<usize>,
+    minimize_lexer: bool,
+    timings: &mut TimingReport,
+    dynamic_precedence_report: Option<&mut DynamicPrecedenceReport>,
 ) -> BuildTableResult<Tables> {
-    let item_set_builder = ParseItemSetBuilder::new(syntax_grammar, lexical_grammar, inlines);
+    let item_set_builder = timings.time("build_item_set_builder", || {
+        ParseItemSetBuilder::new(syntax_grammar, lexical_grammar, inlines)
+    });
     let following_tokens =
         get_following_tokens(syntax_grammar, lexical_grammar, inlines, &item_set_builder);
     let (mut parse_table, parse_state_info) = build_parse_table(
@@ -52,41 +68,62 @@ pub fn build_tables(
         lexical_grammar,
         item_set_builder,
         variable_info,
+        max_states,
+        timings,
+        dynamic_precedence_report,
     )?;
-    let token_conflict_map = TokenConflictMap::new(lexical_grammar, following_tokens);
+    let token_conflict_map = timings.time("token_conflicts", || {
+        TokenConflictMap::new(lexical_grammar, following_tokens)
+    });
     let coincident_token_index = CoincidentTokenIndex::new(&parse_table, lexical_grammar);
-    let keywords = identify_keywords(
-        lexical_grammar,
-        &parse_table,
-        syntax_grammar.word_token,
-        &token_conflict_map,
-        &coincident_token_index,
-    );
+    let keywords = timings.time("identify_keywords", || {
+        identify_keywords(
+            lexical_grammar,
+            &parse_table,
+            &syntax_grammar.word_tokens,
+            &token_conflict_map,
+            &coincident_token_index,
+        )
+    });
+    let all_keywords = keywords.iter().flat_map(TokenSet::iter).collect();
+    if report_shadowed_tokens {
+        report_token_shadowing(
+            lexical_grammar,
+            &token_conflict_map,
+            &coincident_token_index,
+            &all_keywords,
+        );
+    }
     populate_error_state(
         &mut parse_table,
         syntax_grammar,
         lexical_grammar,
         &coincident_token_index,
         &token_conflict_map,
-        &keywords,
+        &all_keywords,
     );
     populate_used_symbols(&mut parse_table, syntax_grammar, lexical_grammar);
-    minimize_parse_table(
-        &mut parse_table,
-        syntax_grammar,
-        lexical_grammar,
-        simple_aliases,
-        &token_conflict_map,
-        &keywords,
-    );
-    let lex_tables = build_lex_table(
-        &mut parse_table,
-        syntax_grammar,
-        lexical_grammar,
-        &keywords,
-        &coincident_token_index,
-        &token_conflict_map,
-    );
+    timings.time("minimize_parse_table", || {
+        minimize_parse_table(
+            &mut parse_table,
+            syntax_grammar,
+            lexical_grammar,
+            simple_aliases,
+            &token_conflict_map,
+            &keywords,
+        );
+    });
+    let lex_tables = timings.time("build_lex_table", || {
+        build_lex_table(
+            &mut parse_table,
+            syntax_grammar,
+            lexical_grammar,
+            &keywords,
+            &coincident_token_index,
+            &token_conflict_map,
+            minimize_lexer,
+        )
+    });
     populate_external_lex_states(&mut parse_table, syntax_grammar);
     mark_fragile_tokens(&mut parse_table, lexical_grammar, &token_conflict_map);
 
@@ -105,6 +142,7 @@ pub fn build_tables(
         main_lex_table: lex_tables.main_lex_table,
         keyword_lex_table: lex_tables.keyword_lex_table,
         large_character_sets: lex_tables.large_character_sets,
+        keywords: all_keywords,
     })
 }
 
@@ -192,7 +230,7 @@ fn populate_error_state(
         let symbol = Symbol::terminal(i);
         if !conflict_free_tokens.contains(&symbol)
             && !keywords.contains(&symbol)
-            && syntax_grammar.word_token != Some(symbol)
+            && !syntax_grammar.word_tokens.contains(&symbol)
         {
             if let Some(t) = conflict_free_tokens.iter().find(|t| {
                 !coincident_token_index.contains(symbol, *t)
@@ -248,16 +286,20 @@ fn populate_used_symbols(
         }
     }
     parse_table.symbols.push(Symbol::end());
+    let mut word_token_count = 0;
     for (i, value) in terminal_usages.into_iter().enumerate() {
         if value {
-            // Assign the grammar's word token a low numerical index. This ensures that
-            // it can be stored in a subtree with no heap allocations, even for grammars with
+            // Assign the grammar's word tokens low numerical indices. This ensures that
+            // they can be stored in a subtree with no heap allocations, even for grammars with
             // very large numbers of tokens. This is an optimization, but it's also important to
-            // ensure that a subtree's symbol can be successfully reassigned to the word token
+            // ensure that a subtree's symbol can be successfully reassigned to a word token
             // without having to move the subtree to the heap.
             // See https://github.com/tree-sitter/tree-sitter/issues/258
-            if syntax_grammar.word_token.is_some_and(|t| t.index == i) {
-                parse_table.symbols.insert(1, Symbol::terminal(i));
+            if syntax_grammar.word_tokens.contains(&Symbol::terminal(i)) {
+                word_token_count += 1;
+                parse_table
+                    .symbols
+                    .insert(word_token_count, Symbol::terminal(i));
             } else {
                 parse_table.symbols.push(Symbol::terminal(i));
             }
@@ -312,18 +354,46 @@ fn populate_external_lex_states(parse_table: &mut ParseTable, syntax_grammar: &S
     }
 }
 
+/// Identify the keywords that can be captured by each of the grammar's word tokens.
+/// A keyword that could be captured by several word tokens is assigned to the first
+/// of them, and a word token is never treated as a keyword of another word token.
 fn identify_keywords(
     lexical_grammar: &LexicalGrammar,
     parse_table: &ParseTable,
-    word_token: Option<Symbol>,
+    word_tokens: &[Symbol],
     token_conflict_map: &TokenConflictMap,
     coincident_token_index: &CoincidentTokenIndex,
-) -> TokenSet {
-    if word_token.is_none() {
-        return TokenSet::new();
-    }
+) -> Vec<TokenSet> {
+    let mut claimed_tokens = TokenSet::new();
+    word_tokens
+        .iter()
+        .map(|word_token| {
+            let keywords = identify_keywords_for_word_token(
+                lexical_grammar,
+                parse_table,
+                *word_token,
+                token_conflict_map,
+                coincident_token_index,
+            )
+            .iter()
+            .filter(|keyword| {
+                !claimed_tokens.contains(keyword)
+                    && (keyword == word_token || !word_tokens.contains(keyword))
+            })
+            .collect();
+            claimed_tokens.insert_all(&keywords);
+            keywords
+        })
+        .collect()
+}
 
-    let word_token = word_token.unwrap();
+fn identify_keywords_for_word_token(
+    lexical_grammar: &LexicalGrammar,
+    parse_table: &ParseTable,
+    word_token: Symbol,
+    token_conflict_map: &TokenConflictMap,
+    coincident_token_index: &CoincidentTokenIndex,
+) -> TokenSet {
     let mut cursor = NfaCursor::new(&lexical_grammar.nfa, Vec::new());
 
     // First find all of the candidate keyword tokens: tokens that start with
@@ -447,6 +517,31 @@ fn mark_fragile_tokens(
     }
 }
 
+/// Report each pair of tokens that are valid in the same parse state, where one token matches
+/// every string that the other token matches and always wins. Keywords are skipped, since
+/// keyword extraction decides between them and their word token.
+fn report_token_shadowing(
+    lexical_grammar: &LexicalGrammar,
+    token_conflict_map: &TokenConflictMap,
+    coincident_token_index: &CoincidentTokenIndex,
+    keywords: &TokenSet,
+) {
+    let n = lexical_grammar.variables.len();
+    for j in (0..n).filter(|j| !keywords.contains(&Symbol::terminal(*j))) {
+        for i in 0..n {
+            if i != j
+                && coincident_token_index.contains(Symbol::terminal(i), Symbol::terminal(j))
+                && token_conflict_map.does_shadow(i, j)
+            {
+                eprintln!(
+                    "token '{}' can never match because token '{}' always wins",
+                    lexical_grammar.variables[j].name, lexical_grammar.variables[i].name
+                );
+            }
+        }
+    }
+}
+
 fn report_state_info<'a>(
     syntax_grammar: &SyntaxGrammar,
     lexical_grammar: &LexicalGrammar,
@@ -454,24 +549,14 @@ fn report_state_info<'a>(
     parse_state_info: &[ParseStateInfo<'a>],
     report_symbol_name: &'a str,
 ) {
-    let mut all_state_indices = BTreeSet::new();
-    let mut symbols_with_state_indices = (0..syntax_grammar.variables.len())
-        .map(|i| (Symbol::non_terminal(i), BTreeSet::new()))
-        .collect::<Vec<_>>();
-
-    for (i, state) in parse_table.states.iter().enumerate() {
-        all_state_indices.insert(i);
-        let item_set = &parse_state_info[state.id];
-        for entry in &item_set.1.entries {
-            if !entry.item.is_augmented() {
-                symbols_with_state_indices[entry.item.variable_index as usize]
-                    .1
-                    .insert(i);
-            }
-        }
-    }
-
-    symbols_with_state_indices.sort_unstable_by_key(|(_, states)| -(states.len() as i32));
+    let all_state_indices = (0..parse_table.states.len()).collect::<BTreeSet<_>>();
+    let symbols_with_state_indices = get_state_indices_by_variable(
+        syntax_grammar,
+        parse_table
+            .states
+            .iter()
+            .map(|state| &parse_state_info[state.id].1),
+    );
 
     let max_symbol_name_length = syntax_grammar
         .variables
@@ -531,6 +616,30 @@ fn report_state_info<'a>(
     }
 }
 
+/// Find the indices of the given parse states whose item sets contain items for each
+/// non-terminal, ordered by descending number of states.
+fn get_state_indices_by_variable<'a, 'b: 'a>(
+    syntax_grammar: &SyntaxGrammar,
+    item_sets: impl Iterator<Item = &'a ParseItemSet<'b>>,
+) -> Vec<(Symbol, BTreeSet<usize>)> {
+    let mut symbols_with_state_indices = (0..syntax_grammar.variables.len())
+        .map(|i| (Symbol::non_terminal(i), BTreeSet::new()))
+        .collect::<Vec<_>>();
+
+    for (i, item_set) in item_sets.enumerate() {
+        for entry in &item_set.entries {
+            if !entry.item.is_augmented() {
+                symbols_with_state_indices[entry.item.variable_index as usize]
+                    .1
+                    .insert(i);
+            }
+        }
+    }
+
+    symbols_with_state_indices.sort_by_key(|(_, states)| std::cmp::Reverse(states.len()));
+    symbols_with_state_indices
+}
+
 fn all_chars_are_alphabetical(cursor: &NfaCursor) -> bool {
     cursor.transition_chars().all(|(chars, is_sep)| {
         if is_sep {
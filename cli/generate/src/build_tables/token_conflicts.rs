@@ -1,5 +1,7 @@
 use std::{cmp::Ordering, collections::HashSet, fmt};
 
+use rayon::prelude::*;
+
 use crate::{
     build_tables::item::TokenSetDisplay,
     grammars::{LexicalGrammar, SyntaxGrammar},
@@ -38,11 +40,22 @@ impl<'a> TokenConflictMap<'a> {
         let starting_chars = get_starting_chars(&mut cursor, grammar);
         let following_chars = get_following_chars(&starting_chars, &following_tokens);
 
+        // Each pair of tokens is analyzed independently, so the rows of the matrix are
+        // computed in parallel, each with its own NFA cursor.
         let n = grammar.variables.len();
+        let rows = (0..n)
+            .into_par_iter()
+            .map(|i| {
+                let mut cursor = NfaCursor::new(&grammar.nfa, Vec::new());
+                (0..i)
+                    .map(|j| compute_conflict_status(&mut cursor, grammar, &following_chars, i, j))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
         let mut status_matrix = vec![TokenConflictStatus::default(); n * n];
-        for i in 0..grammar.variables.len() {
-            for j in 0..i {
-                let status = compute_conflict_status(&mut cursor, grammar, &following_chars, i, j);
+        for (i, row) in rows.into_iter().enumerate() {
+            for (j, status) in row.into_iter().enumerate() {
                 status_matrix[matrix_index(n, i, j)] = status.0;
                 status_matrix[matrix_index(n, j, i)] = status.1;
             }
@@ -77,6 +90,64 @@ impl<'a> TokenConflictMap<'a> {
         self.status_matrix[matrix_index(self.n, i, j)].matches_same_string
     }
 
+    /// Does token `i` match every string that token `j` matches, such that token `i` is
+    /// always preferred over token `j`? If so, a lexer that is looking for both tokens
+    /// can never produce token `j`.
+    pub fn does_shadow(&self, i: usize, j: usize) -> bool {
+        if !self.does_match_same_string(i, j) || self.does_match_different_string(j, i) {
+            return false;
+        }
+
+        // The conflict status doesn't record strings that token `j` completes on while
+        // token `i` is still in progress, so walk both tokens' NFAs together, following
+        // every string that token `j` can match.
+        let grammar = self.grammar;
+        let mut cursor = NfaCursor::new(&grammar.nfa, Vec::new());
+        let mut visited_state_sets = HashSet::new();
+        let mut state_set_queue = vec![vec![
+            grammar.variables[i].start_state,
+            grammar.variables[j].start_state,
+        ]];
+        while let Some(state_set) = state_set_queue.pop() {
+            cursor.reset(state_set);
+
+            let mut completion_i = None;
+            let mut completion_j = None;
+            for (id, precedence) in cursor.completions() {
+                if id == i {
+                    completion_i = Some(precedence);
+                } else if id == j {
+                    completion_j = Some(precedence);
+                }
+            }
+            if let Some(precedence_j) = completion_j {
+                match completion_i {
+                    Some(precedence_i)
+                        if Self::prefer_token(grammar, (precedence_i, i), (precedence_j, j)) => {}
+                    _ => return false,
+                }
+            }
+
+            for transition in cursor.transitions() {
+                let mut contains_i = false;
+                let mut contains_j = false;
+                for variable_id in grammar.variable_indices_for_nfa_states(&transition.states) {
+                    contains_i |= variable_id == i;
+                    contains_j |= variable_id == j;
+                }
+                if contains_j {
+                    if !contains_i {
+                        return false;
+                    }
+                    if visited_state_sets.insert(transition.states.clone()) {
+                        state_set_queue.push(transition.states);
+                    }
+                }
+            }
+        }
+        true
+    }
+
     pub fn does_conflict(&self, i: usize, j: usize) -> bool {
         let entry = &self.status_matrix[matrix_index(self.n, i, j)];
         entry.does_match_valid_continuation
@@ -505,7 +576,7 @@ mod tests {
                 Variable {
                     name: "anything".to_string(),
                     kind: VariableType::Named,
-                    rule: Rule::prec(Precedence::Integer(-1), Rule::pattern(".*", "")),
+                    rule: Rule::prec(Precedence::Integer(-1), Rule::pattern(".+", "")),
                 },
             ],
         })
@@ -519,6 +590,99 @@ mod tests {
         assert!(!token_map.does_match_shorter_or_longer(var("x"), var("anything")));
     }
 
+    #[test]
+    fn test_token_shadowing_keywords() {
+        let grammar = expand_tokens(ExtractedLexicalGrammar {
+            separators: Vec::new(),
+            variables: vec![
+                Variable {
+                    name: "if".to_string(),
+                    kind: VariableType::Anonymous,
+                    rule: Rule::string("if"),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+                Variable {
+                    name: "in".to_string(),
+                    kind: VariableType::Anonymous,
+                    rule: Rule::string("in"),
+                },
+                Variable {
+                    name: "name".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::prec(Precedence::Integer(1), Rule::pattern("[a-z]+", "")),
+                },
+            ],
+        })
+        .unwrap();
+
+        let var = |name| index_of_var(&grammar, name);
+
+        let token_map = TokenConflictMap::new(&grammar, vec![TokenSet::new(); 4]);
+
+        // String literals are preferred over patterns, so keywords win over an ordinary
+        // identifier, and the identifier still matches other words.
+        assert!(!token_map.does_shadow(var("identifier"), var("if")));
+        assert!(!token_map.does_shadow(var("if"), var("identifier")));
+
+        // An identifier with a higher precedence always wins over the keywords.
+        assert!(token_map.does_shadow(var("name"), var("if")));
+        assert!(token_map.does_shadow(var("name"), var("in")));
+        assert!(!token_map.does_shadow(var("in"), var("if")));
+
+        // The two identifier tokens match the same strings, and the one with the higher
+        // precedence always wins.
+        assert!(token_map.does_shadow(var("name"), var("identifier")));
+        assert!(!token_map.does_shadow(var("identifier"), var("name")));
+    }
+
+    #[test]
+    fn test_token_shadowing_numeric_literals() {
+        let grammar = expand_tokens(ExtractedLexicalGrammar {
+            separators: Vec::new(),
+            variables: vec![
+                Variable {
+                    name: "number".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("\\d+(\\.\\d+)?", ""),
+                },
+                Variable {
+                    name: "integer".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("\\d+", ""),
+                },
+                Variable {
+                    name: "hex_integer".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::prec(Precedence::Integer(1), Rule::pattern("0x[0-9a-f]+", "")),
+                },
+                Variable {
+                    name: "version".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("\\d+\\.\\d+\\.\\d+", ""),
+                },
+            ],
+        })
+        .unwrap();
+
+        let var = |name| index_of_var(&grammar, name);
+
+        let token_map = TokenConflictMap::new(&grammar, vec![TokenSet::new(); 4]);
+
+        // Every integer is also a number, and the number token is declared first.
+        assert!(token_map.does_shadow(var("number"), var("integer")));
+        assert!(!token_map.does_shadow(var("integer"), var("number")));
+
+        // Hex integers and versions overlap with numbers, but they also match strings that
+        // numbers don't, so each of them can still win on the longer string.
+        assert!(!token_map.does_shadow(var("number"), var("hex_integer")));
+        assert!(!token_map.does_shadow(var("number"), var("version")));
+        assert!(!token_map.does_shadow(var("hex_integer"), var("number")));
+    }
+
     fn index_of_var(grammar: &LexicalGrammar, name: &str) -> usize {
         grammar
             .variables
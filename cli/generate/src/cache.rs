@@ -0,0 +1,159 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+
+use super::ParserTables;
+
+const CACHE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The number of grammars whose tables are kept in the cache by default.
+const DEFAULT_MAX_ENTRIES: usize = 16;
+
+/// An on-disk cache of prepared grammars and the parse tables built from them.
+///
+/// Building the tables is deterministic, so they only depend on the contents of
+/// `grammar.json`, the ABI version, whether the lexer is minimized, and the version of the
+/// generator itself. The grammar's semantic version and the number of files that the output
+/// is split into only affect the rendering of the parser, which is always redone. Entries
+/// are stored in a subdirectory named after the generator's version, and entries written by
+/// other versions are removed whenever the cache is written to. Only a limited number of
+/// entries are kept, and the least recently used ones are removed first.
+pub struct ParserCache {
+    path: PathBuf,
+    max_entries: usize,
+}
+
+impl ParserCache {
+    #[must_use]
+    pub fn new(cache_dir: &Path) -> Self {
+        Self {
+            path: cache_dir.join(CACHE_VERSION),
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+
+    /// Compute the key of the tables built from the given grammar with the given options.
+    #[must_use]
+    pub fn key(grammar_json: &str, abi_version: usize, minimize_lexer: bool) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(abi_version.to_le_bytes());
+        hasher.update([u8::from(minimize_lexer)]);
+        hasher.update(grammar_json.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Look up previously built tables. Entries that cannot be read are treated as missing.
+    pub(super) fn get(&self, key: &str) -> Option<ParserTables> {
+        let path = self.entry_path(key);
+        let contents = fs::read_to_string(&path).ok()?;
+        let tables = serde_json::from_str(&contents).ok()?;
+        // Mark the entry as recently used, so that it's the last one to be evicted.
+        if let Ok(file) = fs::File::options().append(true).open(&path) {
+            file.set_modified(SystemTime::now()).ok();
+        }
+        Some(tables)
+    }
+
+    pub(super) fn insert(&self, key: &str, tables: &ParserTables) -> io::Result<()> {
+        if let Some(cache_dir) = self.path.parent() {
+            if let Ok(entries) = fs::read_dir(cache_dir) {
+                for entry in entries.flatten() {
+                    if entry.file_name() != CACHE_VERSION && entry.path().is_dir() {
+                        fs::remove_dir_all(entry.path())?;
+                    }
+                }
+            }
+        }
+        fs::create_dir_all(&self.path)?;
+        fs::write(
+            self.entry_path(key),
+            serde_json::to_string(tables).map_err(io::Error::other)?,
+        )?;
+        self.evict()
+    }
+
+    /// Remove the least recently used entries, until there are at most `max_entries` left.
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = fs::read_dir(&self.path)?
+            .flatten()
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .collect::<Vec<_>>();
+        if entries.len() <= self.max_entries {
+            return Ok(());
+        }
+        entries.sort_unstable();
+        for (_, path) in &entries[..entries.len() - self.max_entries] {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.path.join(format!("{key}.json"))
+    }
+}
+
+/// Remove every cached entry from the given cache directory.
+pub fn clear_parser_cache(cache_dir: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(cache_dir) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        build_tables::Tables,
+        grammars::{LexicalGrammar, SyntaxGrammar},
+        rules::{AliasMap, Symbol, TokenSet},
+        tables::{LexTable, ParseTable},
+    };
+
+    #[test]
+    fn test_evict_least_recently_used_tables() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut cache = ParserCache::new(cache_dir.path());
+        cache.max_entries = 2;
+        let tables = |word_token: usize| ParserTables {
+            syntax_grammar: SyntaxGrammar {
+                word_tokens: vec![Symbol::terminal(word_token)],
+                ..SyntaxGrammar::default()
+            },
+            lexical_grammar: LexicalGrammar::default(),
+            simple_aliases: AliasMap::default(),
+            tables: Tables {
+                parse_table: ParseTable::default(),
+                main_lex_table: LexTable::default(),
+                keyword_lex_table: LexTable::default(),
+                large_character_sets: Vec::new(),
+                keywords: TokenSet::new(),
+            },
+            warnings: Vec::new(),
+        };
+        // Give each entry a distinct modification time.
+        let tick = || std::thread::sleep(Duration::from_millis(20));
+
+        cache.insert("a", &tables(1)).unwrap();
+        tick();
+        cache.insert("b", &tables(2)).unwrap();
+        tick();
+        assert_eq!(
+            cache.get("a").unwrap().syntax_grammar.word_tokens,
+            vec![Symbol::terminal(1)]
+        );
+        tick();
+        cache.insert("c", &tables(3)).unwrap();
+
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+    }
+}
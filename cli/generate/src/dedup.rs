@@ -1,9 +1,16 @@
-pub fn split_state_id_groups<S>(
+use rayon::prelude::*;
+
+// Groups with fewer remaining states than this are compared on the current thread,
+// because dispatching such small amounts of work to other threads costs more than
+// it saves.
+const MIN_PARALLEL_COMPARISON_COUNT: usize = 64;
+
+pub fn split_state_id_groups<S: Sync>(
     states: &[S],
     state_ids_by_group_id: &mut Vec<Vec<usize>>,
     group_ids_by_state_id: &mut [usize],
     start_group_id: usize,
-    mut should_split: impl FnMut(&S, &S, &[usize]) -> bool,
+    should_split: impl Fn(&S, &S, &[usize]) -> bool + Sync,
 ) -> bool {
     let mut result = false;
 
@@ -23,22 +30,28 @@ pub fn split_state_id_groups<S>(
             let left_state = &states[left_state_id];
 
             // Identify all of the other states in the group that are incompatible with
-            // this state.
-            let mut j = i + 1;
-            while j < state_ids.len() {
-                let right_state_id = state_ids[j];
-                if split_state_ids.contains(&right_state_id) {
-                    j += 1;
-                    continue;
-                }
-                let right_state = &states[right_state_id];
-
-                if should_split(left_state, right_state, group_ids_by_state_id) {
-                    split_state_ids.push(right_state_id);
-                }
-
-                j += 1;
-            }
+            // this state. The group assignments don't change until the whole group has
+            // been examined, so these comparisons are independent of each other.
+            let group_ids: &[usize] = group_ids_by_state_id;
+            let should_split_right_state = |right_state_id: &usize| {
+                !split_state_ids.contains(right_state_id)
+                    && should_split(left_state, &states[*right_state_id], group_ids)
+            };
+            let right_state_ids = &state_ids[i + 1..];
+            let newly_split_state_ids = if right_state_ids.len() < MIN_PARALLEL_COMPARISON_COUNT {
+                right_state_ids
+                    .iter()
+                    .copied()
+                    .filter(|id| should_split_right_state(id))
+                    .collect::<Vec<_>>()
+            } else {
+                right_state_ids
+                    .par_iter()
+                    .copied()
+                    .filter(|id| should_split_right_state(id))
+                    .collect::<Vec<_>>()
+            };
+            split_state_ids.extend(newly_split_state_ids);
 
             i += 1;
         }
@@ -0,0 +1,74 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{GenerateError, PrepareGrammarWarning};
+
+/// A problem found while generating a parser, in a form that is stable enough to be
+/// consumed by other tools. Errors and warnings share this representation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// A short, kebab-case identifier for the kind of problem, such as
+    /// `undeclared-precedence` or `undefined-symbol`.
+    pub code: String,
+    pub severity: Severity,
+    pub message: String,
+    /// The name of the rule that the problem is about, if there is a single such rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// The error or warning itself, with every detail that was recorded about it.
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub details: serde_json::Value,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Diagnostic {
+    pub(crate) fn warning(code: &str, message: String) -> Self {
+        Self {
+            code: code.to_string(),
+            severity: Severity::Warning,
+            message,
+            rule: None,
+            details: serde_json::Value::Null,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "Error: {}", self.message),
+            Severity::Warning => write!(f, "Warning: {}", self.message),
+        }
+    }
+}
+
+impl From<&GenerateError> for Diagnostic {
+    fn from(error: &GenerateError) -> Self {
+        Self {
+            code: error.code().to_string(),
+            severity: Severity::Error,
+            message: error.to_string(),
+            rule: error.rule().map(ToString::to_string),
+            details: serde_json::to_value(error).unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&PrepareGrammarWarning> for Diagnostic {
+    fn from(warning: &PrepareGrammarWarning) -> Self {
+        Self {
+            code: warning.code().to_string(),
+            severity: Severity::Warning,
+            message: warning.to_string(),
+            rule: warning.rule().map(ToString::to_string),
+            details: serde_json::to_value(warning).unwrap_or_default(),
+        }
+    }
+}
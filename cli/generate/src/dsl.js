@@ -33,6 +33,16 @@ function blank() {
   };
 }
 
+function caseInsensitive(value) {
+  if (typeof value !== 'string') {
+    throw new Error(`Invalid case-insensitive string ${value}`);
+  }
+  return {
+    type: "CASE_INSENSITIVE",
+    value
+  };
+}
+
 function field(name, rule) {
   return {
     type: "FIELD",
@@ -288,8 +298,9 @@ function grammar(baseGrammar, options) {
     ruleMap[key] = true;
   }
   for (const external of externals) {
-    if (typeof external.name === 'string') {
-      ruleMap[external.name] = true;
+    const symbol = external.type === 'ALIAS' ? external.content : external;
+    if (typeof symbol.name === 'string') {
+      ruleMap[symbol.name] = true;
     }
   }
 
@@ -371,14 +382,19 @@ function grammar(baseGrammar, options) {
 
   let word = baseGrammar.word;
   if (options.word) {
-    word = options.word.call(ruleBuilder, ruleBuilder).name;
-    if (typeof word != 'string') {
-      throw new Error("Grammar's 'word' property must be a named rule.");
-    }
+    const wordRules = options.word.call(ruleBuilder, ruleBuilder);
+    const words = (Array.isArray(wordRules) ? wordRules : [wordRules]).map(rule => rule.name);
+    for (const name of words) {
+      if (typeof name != 'string') {
+        throw new Error("Grammar's 'word' property must be a named rule or an array of named rules.");
+      }
 
-    if (word === 'ReferenceError') {
-      throw new Error("Grammar's 'word' property must be a valid rule name.");
+      if (name === 'ReferenceError') {
+        throw new Error("Grammar's 'word' property must be a valid rule name.");
+      }
     }
+
+    word = Array.isArray(wordRules) ? words : words[0];
   }
 
   let conflicts = baseGrammar.conflicts;
@@ -524,6 +540,7 @@ function getEnv(name) {
 
 globalThis.alias = alias;
 globalThis.blank = blank;
+globalThis.caseInsensitive = caseInsensitive;
 globalThis.choice = choice;
 globalThis.optional = optional;
 globalThis.prec = prec;
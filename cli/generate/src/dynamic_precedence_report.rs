@@ -0,0 +1,253 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use serde::Serialize;
+
+use crate::{grammars::SyntaxGrammar, rules::Symbol};
+
+/// A summary of how the grammar's dynamic precedences take part in the conflicts that are
+/// resolved at runtime.
+#[derive(Debug, Default, Serialize)]
+pub struct DynamicPrecedenceReport {
+    pub annotations: Vec<DynamicPrecedenceAnnotation>,
+    /// Declared conflicts in which every interpretation has the same dynamic precedence
+    /// for at least one lookahead, so dynamic precedence can't choose between them.
+    pub tied_conflicts: Vec<TiedConflict>,
+}
+
+/// The productions of a rule that have the same dynamic precedence, which usually come
+/// from a single `prec.dynamic` call.
+#[derive(Debug, Serialize)]
+pub struct DynamicPrecedenceAnnotation {
+    pub rule: String,
+    pub dynamic_precedence: i32,
+    pub production_count: usize,
+    /// The number of conflicts, for a given parse state and lookahead, in which one of
+    /// the productions can be part of a competing interpretation. When this is zero, the
+    /// annotation has no effect.
+    pub conflict_count: usize,
+}
+
+/// A declared conflict in which every interpretation can only contain the same dynamic
+/// precedences, so the interpretation that is chosen at runtime is arbitrary.
+#[derive(Debug, Serialize)]
+pub struct TiedConflict {
+    pub rules: Vec<String>,
+    /// The number of times that the conflict occurs, for a given parse state and lookahead.
+    pub conflict_count: usize,
+    pub lookaheads: Vec<String>,
+}
+
+/// An occurrence of a conflict that was left in the parse table because it is listed in
+/// the grammar's `conflicts`.
+pub struct GlrConflict {
+    pub symbols: Vec<Symbol>,
+    pub lookahead: String,
+}
+
+impl DynamicPrecedenceReport {
+    /// Record the grammar's dynamic precedences, and how they relate to the conflicts that
+    /// were found while building the parse table. Rules that are inlined don't have
+    /// productions of their own in the parse table, so they aren't included.
+    pub(crate) fn record(&mut self, syntax_grammar: &SyntaxGrammar, conflicts: &[GlrConflict]) {
+        let mut annotations = BTreeMap::<(usize, i32), (usize, usize)>::new();
+        for (index, variable) in syntax_grammar.variables.iter().enumerate() {
+            if syntax_grammar
+                .variables_to_inline
+                .contains(&Symbol::non_terminal(index))
+            {
+                continue;
+            }
+            for production in &variable.productions {
+                if production.dynamic_precedence != 0 {
+                    annotations
+                        .entry((index, production.dynamic_precedence))
+                        .or_default()
+                        .0 += 1;
+                }
+            }
+        }
+
+        let mut interpretations = HashMap::new();
+        let mut tied_conflicts = BTreeMap::<&[Symbol], (usize, BTreeSet<&str>)>::new();
+        for conflict in conflicts {
+            for symbol in &conflict.symbols {
+                interpretations
+                    .entry(*symbol)
+                    .or_insert_with(|| interpretation_precedences(syntax_grammar, *symbol));
+            }
+            let precedences_by_symbol = conflict
+                .symbols
+                .iter()
+                .map(|symbol| &interpretations[symbol])
+                .collect::<Vec<_>>();
+
+            let mut reachable_precedences = BTreeSet::new();
+            for precedences in &precedences_by_symbol {
+                reachable_precedences.extend(precedences.iter());
+            }
+            for key in reachable_precedences {
+                if let Some((_, conflict_count)) = annotations.get_mut(key) {
+                    *conflict_count += 1;
+                }
+            }
+
+            let mut values_by_symbol = precedences_by_symbol.iter().map(|precedences| {
+                precedences
+                    .iter()
+                    .map(|(_, value)| *value)
+                    .collect::<BTreeSet<_>>()
+            });
+            let first_values = values_by_symbol.next();
+            if values_by_symbol.all(|values| Some(&values) == first_values.as_ref()) {
+                let (conflict_count, lookaheads) =
+                    tied_conflicts.entry(&conflict.symbols).or_default();
+                *conflict_count += 1;
+                lookaheads.insert(&conflict.lookahead);
+            }
+        }
+
+        let rule_name = |index: usize| syntax_grammar.variables[index].name.clone();
+        self.annotations = annotations
+            .into_iter()
+            .map(
+                |((index, dynamic_precedence), (production_count, conflict_count))| {
+                    DynamicPrecedenceAnnotation {
+                        rule: rule_name(index),
+                        dynamic_precedence,
+                        production_count,
+                        conflict_count,
+                    }
+                },
+            )
+            .collect();
+        self.tied_conflicts = tied_conflicts
+            .into_iter()
+            .map(|(symbols, (conflict_count, lookaheads))| TiedConflict {
+                rules: symbols
+                    .iter()
+                    .map(|symbol| rule_name(symbol.index))
+                    .collect(),
+                conflict_count,
+                lookaheads: lookaheads.into_iter().map(ToString::to_string).collect(),
+            })
+            .collect();
+    }
+}
+
+/// Find the dynamic precedences that can contribute to the interpretation of a conflict in
+/// which the given rule is chosen. Dynamic precedences are summed over the subtrees that
+/// are built while the parse stack is split, which include the rule itself, the productions
+/// that contain it, and the rules that can follow it in those productions.
+fn interpretation_precedences(
+    syntax_grammar: &SyntaxGrammar,
+    symbol: Symbol,
+) -> BTreeSet<(usize, i32)> {
+    let mut result = BTreeSet::new();
+    let mut stack = vec![symbol.index];
+    for (index, variable) in syntax_grammar.variables.iter().enumerate() {
+        for production in &variable.productions {
+            for (step_index, step) in production.steps.iter().enumerate() {
+                if step.symbol == symbol {
+                    if production.dynamic_precedence != 0 {
+                        result.insert((index, production.dynamic_precedence));
+                    }
+                    stack.extend(
+                        production.steps[step_index + 1..]
+                            .iter()
+                            .filter(|step| step.symbol.is_non_terminal())
+                            .map(|step| step.symbol.index),
+                    );
+                }
+            }
+        }
+    }
+
+    let mut visited = HashSet::new();
+    while let Some(index) = stack.pop() {
+        if !visited.insert(index) {
+            continue;
+        }
+        for production in &syntax_grammar.variables[index].productions {
+            if production.dynamic_precedence != 0 {
+                result.insert((index, production.dynamic_precedence));
+            }
+            stack.extend(
+                production
+                    .steps
+                    .iter()
+                    .filter(|step| step.symbol.is_non_terminal())
+                    .map(|step| step.symbol.index),
+            );
+        }
+    }
+    result
+}
+
+impl fmt::Display for DynamicPrecedenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const HEADERS: [&str; 4] = ["Rule", "Dynamic precedence", "Productions", "Conflicts"];
+
+        if self.annotations.is_empty() {
+            writeln!(f, "The grammar doesn't use dynamic precedence.")?;
+        } else {
+            let name_width = self
+                .annotations
+                .iter()
+                .map(|annotation| annotation.rule.chars().count())
+                .chain([HEADERS[0].len()])
+                .max()
+                .unwrap();
+
+            writeln!(f, "Dynamic precedences:\n")?;
+            writeln!(
+                f,
+                "  {:name_width$}  {:>18}  {:>11}  {:>9}",
+                HEADERS[0], HEADERS[1], HEADERS[2], HEADERS[3]
+            )?;
+            for annotation in &self.annotations {
+                write!(
+                    f,
+                    "  {:name_width$}  {:>18}  {:>11}  {:>9}",
+                    annotation.rule,
+                    annotation.dynamic_precedence,
+                    annotation.production_count,
+                    annotation.conflict_count
+                )?;
+                if annotation.conflict_count == 0 {
+                    write!(f, "  (never used in a conflict)")?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        if !self.tied_conflicts.is_empty() {
+            writeln!(
+                f,
+                "\nConflicts in which every interpretation has the same dynamic precedence:\n"
+            )?;
+            for conflict in &self.tied_conflicts {
+                writeln!(
+                    f,
+                    "  [{}]  {} occurrences, before {}",
+                    conflict
+                        .rules
+                        .iter()
+                        .map(|rule| format!("`{rule}`"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    conflict.conflict_count,
+                    conflict
+                        .lookaheads
+                        .iter()
+                        .map(|lookahead| format!("`{lookahead}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,13 @@
 use std::{collections::HashMap, fmt};
 
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+
 use super::{
     nfa::Nfa,
     rules::{Alias, Associativity, Precedence, Rule, Symbol, TokenSet},
 };
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum VariableType {
     Hidden,
     Auxiliary,
@@ -15,14 +17,14 @@ pub enum VariableType {
 
 // Input grammar
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct Variable {
     pub name: String,
     pub kind: VariableType,
     pub rule: Rule,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum PrecedenceEntry {
     Name(String),
     Symbol(String),
@@ -38,11 +40,11 @@ pub struct InputGrammar {
     pub external_tokens: Vec<Rule>,
     pub variables_to_inline: Vec<String>,
     pub supertype_symbols: Vec<String>,
-    pub word_token: Option<String>,
+    pub word_tokens: Vec<String>,
     pub reserved_words: Vec<ReservedWordContext<Rule>>,
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
 pub struct ReservedWordContext<T> {
     pub name: String,
     pub reserved_words: Vec<T>,
@@ -50,7 +52,7 @@ pub struct ReservedWordContext<T> {
 
 // Extracted lexical grammar
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LexicalVariable {
     pub name: String,
     pub kind: VariableType,
@@ -58,7 +60,7 @@ pub struct LexicalVariable {
     pub start_state: u32,
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LexicalGrammar {
     pub nfa: Nfa,
     pub variables: Vec<LexicalVariable>,
@@ -66,7 +68,7 @@ pub struct LexicalGrammar {
 
 // Extracted syntax grammar
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct ProductionStep {
     pub symbol: Symbol,
     pub precedence: Precedence,
@@ -76,7 +78,9 @@ pub struct ProductionStep {
     pub reserved_word_set_id: ReservedWordSetId,
 }
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
 pub struct ReservedWordSetId(pub usize);
 
 impl fmt::Display for ReservedWordSetId {
@@ -87,7 +91,7 @@ impl fmt::Display for ReservedWordSetId {
 
 pub const NO_RESERVED_WORDS: ReservedWordSetId = ReservedWordSetId(usize::MAX);
 
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Production {
     pub steps: Vec<ProductionStep>,
     pub dynamic_precedence: i32,
@@ -96,24 +100,47 @@ pub struct Production {
 #[derive(Default)]
 pub struct InlinedProductionMap {
     pub productions: Vec<Production>,
-    pub production_map: HashMap<(*const Production, u32), Vec<usize>>,
+    /// Keyed by the address of a production and a step index. Addresses are stored as
+    /// integers, rather than raw pointers, so that the map can be shared between threads.
+    pub production_map: HashMap<(usize, u32), Vec<usize>>,
+    /// How each inlined rule was used. Rules that were never referenced have no entry.
+    pub usage_by_symbol: HashMap<Symbol, InlineUsage>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InlineUsage {
+    /// The number of productions that the rule was substituted into.
+    pub inlined_into_count: usize,
+    /// The number of productions that were produced by those substitutions.
+    pub production_count: usize,
+}
+
+// The production map is keyed by the addresses of the productions that were inlined, so only
+// the inlined productions themselves are serialized.
+impl Serialize for InlinedProductionMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("InlinedProductionMap", 1)?;
+        state.serialize_field("productions", &self.productions)?;
+        state.end()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SyntaxVariable {
     pub name: String,
     pub kind: VariableType,
     pub productions: Vec<Production>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ExternalToken {
     pub name: String,
     pub kind: VariableType,
     pub corresponding_internal_token: Option<Symbol>,
+    pub alias: Option<Alias>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct SyntaxGrammar {
     pub variables: Vec<SyntaxVariable>,
     pub extra_symbols: Vec<Symbol>,
@@ -121,7 +148,7 @@ pub struct SyntaxGrammar {
     pub external_tokens: Vec<ExternalToken>,
     pub supertype_symbols: Vec<Symbol>,
     pub variables_to_inline: Vec<Symbol>,
-    pub word_token: Option<Symbol>,
+    pub word_tokens: Vec<Symbol>,
     pub precedence_orderings: Vec<Vec<PrecedenceEntry>>,
     pub reserved_word_sets: Vec<TokenSet>,
 }
@@ -228,6 +255,7 @@ impl LexicalGrammar {
         })
     }
 
+    #[must_use]
     pub fn variable_index_for_nfa_state(&self, state_id: u32) -> usize {
         self.variables
             .iter()
@@ -247,13 +275,17 @@ impl SyntaxVariable {
 }
 
 impl InlinedProductionMap {
+    #[must_use]
     pub fn inlined_productions<'a>(
         &'a self,
         production: &Production,
         step_index: u32,
     ) -> Option<impl Iterator<Item = &'a Production> + 'a> {
         self.production_map
-            .get(&(std::ptr::from_ref::<Production>(production), step_index))
+            .get(&(
+                std::ptr::from_ref::<Production>(production) as usize,
+                step_index,
+            ))
             .map(|production_indices| {
                 production_indices
                     .iter()
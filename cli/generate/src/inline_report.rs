@@ -0,0 +1,98 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::grammars::{InlinedProductionMap, InputGrammar, SyntaxGrammar};
+
+/// A summary of how the rules in a grammar's `inline` list were inlined, for finding
+/// rules whose inlining adds many productions to the grammar.
+#[derive(Debug, Default, Serialize)]
+pub struct InlineReport {
+    pub rules: Vec<InlinedRule>,
+    /// The number of distinct productions that were added to the grammar by inlining.
+    pub added_production_count: usize,
+    /// Rules that are listed in `inline` but were never inlined into another rule, because
+    /// they aren't referenced or they became tokens, so listing them had no effect.
+    pub unused_rules: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InlinedRule {
+    pub name: String,
+    /// The number of productions that the rule was inlined into.
+    pub inlined_into_count: usize,
+    /// The number of productions that inlining the rule produced, before identical
+    /// productions were merged.
+    pub production_count: usize,
+}
+
+impl InlineReport {
+    /// Record how each of the grammar's inlined rules was used.
+    pub(crate) fn record(
+        &mut self,
+        input_grammar: &InputGrammar,
+        syntax_grammar: &SyntaxGrammar,
+        inlines: &InlinedProductionMap,
+    ) {
+        self.added_production_count = inlines.productions.len();
+        for name in &input_grammar.variables_to_inline {
+            let usage = syntax_grammar
+                .variables_to_inline
+                .iter()
+                .find(|symbol| syntax_grammar.variables[symbol.index].name == *name)
+                .and_then(|symbol| inlines.usage_by_symbol.get(symbol));
+            if let Some(usage) = usage {
+                self.rules.push(InlinedRule {
+                    name: name.clone(),
+                    inlined_into_count: usage.inlined_into_count,
+                    production_count: usage.production_count,
+                });
+            } else {
+                self.unused_rules.push(name.clone());
+            }
+        }
+        self.rules
+            .sort_by_key(|rule| std::cmp::Reverse(rule.production_count));
+    }
+}
+
+impl fmt::Display for InlineReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const HEADERS: [&str; 3] = ["Rule", "Inlined into", "Productions"];
+
+        let name_width = self
+            .rules
+            .iter()
+            .map(|rule| rule.name.chars().count())
+            .chain([HEADERS[0].len()])
+            .max()
+            .unwrap();
+
+        writeln!(f, "Inlined rules:\n")?;
+        writeln!(
+            f,
+            "  {:name_width$}  {:>12}  {:>12}",
+            HEADERS[0], HEADERS[1], HEADERS[2]
+        )?;
+        for rule in &self.rules {
+            writeln!(
+                f,
+                "  {:name_width$}  {:>12}  {:>12}",
+                rule.name, rule.inlined_into_count, rule.production_count
+            )?;
+        }
+        writeln!(
+            f,
+            "\nProductions added by inlining: {}",
+            self.added_production_count
+        )?;
+
+        if !self.unused_rules.is_empty() {
+            writeln!(f, "\nRules in `inline` that were never inlined:\n")?;
+            for name in &self.unused_rules {
+                writeln!(f, "  {name}")?;
+            }
+        }
+        Ok(())
+    }
+}
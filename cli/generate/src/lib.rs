@@ -13,27 +13,48 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 mod build_tables;
+mod cache;
 mod dedup;
+mod diagnostics;
+mod dynamic_precedence_report;
 mod grammar_files;
 mod grammars;
+mod inline_report;
 mod nfa;
 mod node_types;
 pub mod parse_grammar;
 mod prepare_grammar;
 mod render;
 mod rules;
+mod stats;
 mod tables;
+mod timing;
 
-use build_tables::build_tables;
 pub use build_tables::ParseTableBuilderError;
-use grammars::InputGrammar;
+use build_tables::{build_tables, Tables};
+pub use cache::{clear_parser_cache, ParserCache};
+pub use diagnostics::{Diagnostic, Severity};
+pub use dynamic_precedence_report::{
+    DynamicPrecedenceAnnotation, DynamicPrecedenceReport, TiedConflict,
+};
+pub use grammars::{InlinedProductionMap, InputGrammar, LexicalGrammar, SyntaxGrammar};
+pub use inline_report::{InlineReport, InlinedRule};
 pub use node_types::VariableInfoError;
 use parse_grammar::parse_grammar;
 pub use parse_grammar::ParseGrammarError;
-use prepare_grammar::prepare_grammar;
-pub use prepare_grammar::PrepareGrammarError;
-use render::render_c_code;
-pub use render::{ABI_VERSION_MAX, ABI_VERSION_MIN};
+use prepare_grammar::{prepare_grammar, prepare_grammar_with_timing, IntermediateGrammars};
+pub use prepare_grammar::{
+    prepare_grammar_with_intermediates, ExtractedLexicalGrammar, ExtractedSyntaxGrammar,
+    IntermediateGrammar, InternedGrammar, PrepareGrammarError, PrepareGrammarWarning,
+    PreparedGrammarArtifacts,
+};
+use render::{check_abi_compatibility, render_c_code, render_external_scanner_header};
+pub use render::{
+    AbiFeature, IncompatibleAbiError, ABI_VERSION_MAX, ABI_VERSION_MIN, ABI_VERSION_WITH_METADATA,
+};
+use rules::AliasMap;
+pub use stats::{GrammarStats, RuleStats};
+pub use timing::{RuleTiming, StageTiming, TimingReport};
 
 static JSON_COMMENT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
     RegexBuilder::new("^\\s*//.*")
@@ -44,12 +65,33 @@ static JSON_COMMENT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
 
 struct GeneratedParser {
     c_code: String,
+    parser_tables: Vec<String>,
     node_types_json: String,
+    node_types_typescript: String,
+    external_scanner_header: Option<String>,
+}
+
+/// The prepared grammar and the tables built from it, which is everything that's needed to
+/// render the parser. This is what the [`ParserCache`] stores.
+#[derive(Serialize, Deserialize)]
+struct ParserTables {
+    syntax_grammar: SyntaxGrammar,
+    lexical_grammar: LexicalGrammar,
+    #[serde(with = "rules::alias_map")]
+    simple_aliases: AliasMap,
+    tables: Tables,
+    /// The warnings that were reported while preparing the grammar, so that they're reported
+    /// again when the tables are reused.
+    warnings: Vec<Diagnostic>,
 }
 
 pub const ALLOC_HEADER: &str = include_str!("templates/alloc.h");
 pub const ARRAY_HEADER: &str = include_str!("templates/array.h");
 
+/// The name of the header, in `src/tree_sitter`, that enumerates the grammar's external
+/// tokens for use in an external scanner.
+pub const EXTERNAL_SCANNER_HEADER_NAME: &str = "external-scanner-symbols.h";
+
 pub type GenerateResult<T> = Result<T, GenerateError>;
 
 #[derive(Debug, Error, Serialize)]
@@ -70,6 +112,10 @@ pub enum GenerateError {
     BuildTables(#[from] ParseTableBuilderError),
     #[error(transparent)]
     ParseVersion(#[from] ParseVersionError),
+    #[error("This version of Tree-sitter can only generate parsers with ABI version {ABI_VERSION_MIN} - {ABI_VERSION_MAX}, not {0}")]
+    UnsupportedAbiVersion(usize),
+    #[error(transparent)]
+    IncompatibleAbi(#[from] IncompatibleAbiError),
 }
 
 impl From<std::io::Error> for GenerateError {
@@ -78,6 +124,37 @@ impl From<std::io::Error> for GenerateError {
     }
 }
 
+impl GenerateError {
+    /// A stable identifier for the kind of error, used in [`Diagnostic`]s.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::GrammarPath(_) => "invalid-grammar-path",
+            Self::IO(_) => "io",
+            Self::LoadGrammarFile(e) => e.code(),
+            Self::ParseGrammar(e) => e.code(),
+            Self::Prepare(e) => e.code(),
+            Self::VariableInfo(e) => e.code(),
+            Self::BuildTables(e) => e.code(),
+            Self::ParseVersion(_) => "invalid-grammar-version",
+            Self::UnsupportedAbiVersion(_) => "unsupported-abi-version",
+            Self::IncompatibleAbi(_) => "incompatible-abi-version",
+        }
+    }
+
+    /// The name of the rule that the error is about, if there is a single such rule.
+    #[must_use]
+    pub fn rule(&self) -> Option<&str> {
+        match self {
+            Self::ParseGrammar(e) => e.rule(),
+            Self::Prepare(e) => e.rule(),
+            Self::VariableInfo(e) => e.rule(),
+            Self::BuildTables(e) => e.rule(),
+            _ => None,
+        }
+    }
+}
+
 pub type LoadGrammarFileResult<T> = Result<T, LoadGrammarError>;
 
 #[derive(Debug, Error, Serialize)]
@@ -98,6 +175,17 @@ impl From<std::io::Error> for LoadGrammarError {
     }
 }
 
+impl LoadGrammarError {
+    const fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidPath => "invalid-grammar-path",
+            Self::LoadJSGrammarFile(_) => "load-grammar-js",
+            Self::IO(_) => "io",
+            Self::FileExtension(_) => "unknown-grammar-extension",
+        }
+    }
+}
+
 #[derive(Debug, Error, Serialize)]
 pub enum ParseVersionError {
     #[error("{0}")]
@@ -144,16 +232,82 @@ impl From<semver::Error> for JSError {
     }
 }
 
+/// Options for generating a parser with [`generate_parser_in_directory`].
+///
+/// The default options generate a parser with the latest ABI version, in the same way as
+/// `tree-sitter generate` without any flags.
+pub struct GenerateOptions<'a> {
+    /// The directory to write the generated files to, instead of the grammar's `src` folder.
+    pub out_path: Option<&'a str>,
+    /// The grammar file to generate the parser from, instead of the directory's `grammar.js`.
+    pub grammar_path: Option<&'a str>,
+    pub abi_version: usize,
+    /// The name of a rule to report the parse states of, or `-` to report every rule.
+    pub report_symbol_name: Option<&'a str>,
+    /// Report tokens that can never match, because another token always wins.
+    pub report_shadowed_tokens: bool,
+    /// The JavaScript runtime that evaluates `grammar.js`, which is `node` by default.
+    pub js_runtime: Option<&'a str>,
+    /// Treat warnings as errors.
+    pub strict: bool,
+    /// A directory to write the grammar produced by each stage of grammar preparation to.
+    pub intermediates_path: Option<&'a Path>,
+    /// A directory to cache the prepared grammar and its parse tables in, keyed by the grammar
+    /// and the options that affect the tables.
+    pub cache_dir: Option<&'a Path>,
+    /// Fail if the parse table has more than this many states.
+    pub max_states: Option<usize>,
+    /// Move the parse and lex tables into at most this many other C files.
+    pub split_output: Option<usize>,
+    pub minimize_lexer: bool,
+    /// Also write `node-types.d.ts`.
+    pub emit_typescript: bool,
+    pub timings: Option<&'a mut TimingReport>,
+    pub stats: Option<&'a mut GrammarStats>,
+    pub inline_report: Option<&'a mut InlineReport>,
+    pub dynamic_precedence_report: Option<&'a mut DynamicPrecedenceReport>,
+}
+
+impl Default for GenerateOptions<'_> {
+    fn default() -> Self {
+        Self {
+            out_path: None,
+            grammar_path: None,
+            abi_version: tree_sitter::LANGUAGE_VERSION,
+            report_symbol_name: None,
+            report_shadowed_tokens: false,
+            js_runtime: None,
+            strict: false,
+            intermediates_path: None,
+            cache_dir: None,
+            max_states: None,
+            split_output: None,
+            minimize_lexer: true,
+            emit_typescript: false,
+            timings: None,
+            stats: None,
+            inline_report: None,
+            dynamic_precedence_report: None,
+        }
+    }
+}
+
+/// Generate a parser from the grammar in the given directory, and write it to the
+/// directory's `src` folder.
+///
+/// Any warnings that are found along the way are added to `warnings`, even when
+/// generating the parser fails.
 pub fn generate_parser_in_directory(
     repo_path: &Path,
-    out_path: Option<&str>,
-    grammar_path: Option<&str>,
-    mut abi_version: usize,
-    report_symbol_name: Option<&str>,
-    js_runtime: Option<&str>,
+    mut options: GenerateOptions,
+    warnings: &mut Vec<Diagnostic>,
 ) -> GenerateResult<()> {
+    if !(ABI_VERSION_MIN..=ABI_VERSION_MAX).contains(&options.abi_version) {
+        Err(GenerateError::UnsupportedAbiVersion(options.abi_version))?;
+    }
+
     let mut repo_path = repo_path.to_owned();
-    let mut grammar_path = grammar_path;
+    let mut grammar_path = options.grammar_path;
 
     // Populate a new empty grammar directory.
     if let Some(path) = grammar_path {
@@ -171,9 +325,11 @@ pub fn generate_parser_in_directory(
     let grammar_path = grammar_path.map_or_else(|| repo_path.join("grammar.js"), PathBuf::from);
 
     // Read the grammar file.
-    let grammar_json = load_grammar_file(&grammar_path, js_runtime)?;
+    let grammar_json = load_grammar_file(&grammar_path, options.js_runtime)?;
 
-    let src_path = out_path.map_or_else(|| repo_path.join("src"), PathBuf::from);
+    let src_path = options
+        .out_path
+        .map_or_else(|| repo_path.join("src"), PathBuf::from);
     let header_path = src_path.join("tree_sitter");
 
     // Ensure that the output directories exist.
@@ -193,87 +349,339 @@ pub fn generate_parser_in_directory(
 
     let semantic_version = read_grammar_version(&repo_path)?;
 
-    if semantic_version.is_none() && abi_version > ABI_VERSION_MIN {
-        println!("Warning: No `tree-sitter.json` file found in your grammar, this file is required to generate with ABI {abi_version}. Using ABI version {ABI_VERSION_MIN} instead.");
-        println!("This file can be set up with `tree-sitter init`. For more information, see https://tree-sitter.github.io/tree-sitter/cli/init.");
-        abi_version = ABI_VERSION_MIN;
+    if semantic_version.is_none() && options.abi_version >= ABI_VERSION_WITH_METADATA {
+        let fallback_abi_version = ABI_VERSION_WITH_METADATA - 1;
+        warnings.push(Diagnostic::warning(
+            "missing-tree-sitter-json",
+            format!("No `tree-sitter.json` file found in your grammar, this file is required to generate with ABI {}. Using ABI version {fallback_abi_version} instead.\nThis file can be set up with `tree-sitter init`. For more information, see https://tree-sitter.github.io/tree-sitter/cli/init.", options.abi_version),
+        ));
+        options.abi_version = fallback_abi_version;
     }
 
+    let semantic_version = semantic_version.map(|v| (v.major as u8, v.minor as u8, v.patch as u8));
+
+    // Reuse the tables built from the same grammar, if there are any, so that only the
+    // rendering of the parser is left to do. Reports, intermediate grammars, the state
+    // limit, timings and stats are produced while building the tables, so they always
+    // bypass the cache.
+    let cache = options
+        .cache_dir
+        .filter(|_| {
+            options.report_symbol_name.is_none()
+                && !options.report_shadowed_tokens
+                && options.intermediates_path.is_none()
+                && options.max_states.is_none()
+                && options.timings.is_none()
+                && options.stats.is_none()
+                && options.inline_report.is_none()
+                && options.dynamic_precedence_report.is_none()
+        })
+        .map(|cache_dir| {
+            let key = ParserCache::key(&grammar_json, options.abi_version, options.minimize_lexer);
+            (ParserCache::new(cache_dir), key)
+        });
+    let cached_tables = cache
+        .as_ref()
+        .and_then(|(cache, key)| cache.get(key))
+        .filter(|tables| !options.strict || tables.warnings.is_empty());
+
+    let parser_tables = if let Some(parser_tables) = cached_tables {
+        warnings.extend(parser_tables.warnings.iter().cloned());
+        parser_tables
+    } else {
+        let parser_tables = build_parser_tables(&input_grammar, &mut options, warnings)?;
+        if let Some((cache, key)) = &cache {
+            if let Err(e) = cache.insert(key, &parser_tables) {
+                eprintln!("Warning: Failed to write the parse tables to the cache -- {e}");
+            }
+        }
+        parser_tables
+    };
+
     // Generate the parser and related files.
     let GeneratedParser {
         c_code,
+        parser_tables,
         node_types_json,
-    } = generate_parser_for_grammar_with_opts(
-        &input_grammar,
-        abi_version,
-        semantic_version.map(|v| (v.major as u8, v.minor as u8, v.patch as u8)),
-        report_symbol_name,
-    )?;
+        node_types_typescript,
+        external_scanner_header,
+    } = render_parser(
+        &input_grammar.name,
+        parser_tables,
+        semantic_version,
+        &mut options,
+    );
 
     write_file(&src_path.join("parser.c"), c_code)?;
+    for (index, code) in parser_tables.iter().enumerate() {
+        write_file(&src_path.join(format!("parser_tables_{index}.c")), code)?;
+    }
+    remove_stale_parser_tables(&src_path, parser_tables.len())?;
     write_file(&src_path.join("node-types.json"), node_types_json)?;
+    if options.emit_typescript {
+        write_file(&src_path.join("node-types.d.ts"), node_types_typescript)?;
+    }
     write_file(&header_path.join("alloc.h"), ALLOC_HEADER)?;
     write_file(&header_path.join("array.h"), ARRAY_HEADER)?;
     write_file(&header_path.join("parser.h"), tree_sitter::PARSER_HEADER)?;
 
+    // Write the external scanner's header, or remove one that was generated before the
+    // grammar's external tokens were removed, so that a scanner can't keep using it.
+    let external_scanner_header_path = header_path.join(EXTERNAL_SCANNER_HEADER_NAME);
+    if let Some(external_scanner_header) = external_scanner_header {
+        write_file(&external_scanner_header_path, external_scanner_header)?;
+    } else if external_scanner_header_path.exists() {
+        fs::remove_file(&external_scanner_header_path)?;
+    }
+
     Ok(())
 }
 
+/// Check that the grammar in the given directory is valid by preparing it, without
+/// building its parse table or writing any files. On success, any warnings that were
+/// found are returned.
+pub fn check_grammar_in_directory(
+    repo_path: &Path,
+    grammar_path: Option<&str>,
+    js_runtime: Option<&str>,
+) -> GenerateResult<Vec<Diagnostic>> {
+    let grammar_path = grammar_path.map_or_else(|| repo_path.join("grammar.js"), PathBuf::from);
+    let grammar_json = load_grammar_file(&grammar_path, js_runtime)?;
+    let input_grammar = parse_grammar(&grammar_json)?;
+    let (.., warnings) = prepare_grammar(&input_grammar)?;
+    Ok(warnings.iter().map(Diagnostic::from).collect())
+}
+
 pub fn generate_parser_for_grammar(
     grammar_json: &str,
     semantic_version: Option<(u8, u8, u8)>,
 ) -> GenerateResult<(String, String)> {
     let grammar_json = JSON_COMMENT_REGEX.replace_all(grammar_json, "\n");
     let input_grammar = parse_grammar(&grammar_json)?;
+    let mut warnings = Vec::new();
     let parser = generate_parser_for_grammar_with_opts(
         &input_grammar,
-        tree_sitter::LANGUAGE_VERSION,
         semantic_version,
-        None,
-    )?;
-    Ok((input_grammar.name, parser.c_code))
+        &mut GenerateOptions::default(),
+        &mut warnings,
+    );
+    for warning in &warnings {
+        eprintln!("{warning}");
+    }
+    Ok((input_grammar.name, parser?.c_code))
 }
 
 fn generate_parser_for_grammar_with_opts(
     input_grammar: &InputGrammar,
-    abi_version: usize,
     semantic_version: Option<(u8, u8, u8)>,
-    report_symbol_name: Option<&str>,
+    options: &mut GenerateOptions,
+    warnings: &mut Vec<Diagnostic>,
 ) -> GenerateResult<GeneratedParser> {
-    let (syntax_grammar, lexical_grammar, inlines, simple_aliases) =
-        prepare_grammar(input_grammar)?;
+    let parser_tables = build_parser_tables(input_grammar, options, warnings)?;
+    Ok(render_parser(
+        &input_grammar.name,
+        parser_tables,
+        semantic_version,
+        options,
+    ))
+}
+
+/// Prepare the grammar and build its parse and lex tables.
+fn build_parser_tables(
+    input_grammar: &InputGrammar,
+    options: &mut GenerateOptions,
+    warnings: &mut Vec<Diagnostic>,
+) -> GenerateResult<ParserTables> {
+    let mut disabled_timings = TimingReport::disabled();
+    let timings = options
+        .timings
+        .as_deref_mut()
+        .unwrap_or(&mut disabled_timings);
+    let mut default_stats = GrammarStats::default();
+    let stats = options.stats.as_deref_mut().unwrap_or(&mut default_stats);
+    let mut default_inline_report = InlineReport::default();
+    let inline_report = options
+        .inline_report
+        .as_deref_mut()
+        .unwrap_or(&mut default_inline_report);
+    let mut intermediates = options
+        .intermediates_path
+        .map(|_| IntermediateGrammars::default());
+    let prepared =
+        prepare_grammar_with_timing(input_grammar, timings, stats, intermediates.as_mut())?;
+    let (syntax_grammar, lexical_grammar, inlines, simple_aliases, grammar_warnings) =
+        if let (Some(intermediates_path), Some(intermediates)) =
+            (options.intermediates_path, intermediates)
+        {
+            let artifacts = PreparedGrammarArtifacts::new(intermediates, prepared);
+            write_grammar_intermediates(&artifacts, intermediates_path)?;
+            let PreparedGrammarArtifacts {
+                syntax_grammar,
+                lexical_grammar,
+                inlines,
+                default_aliases,
+                warnings,
+                ..
+            } = artifacts;
+            (
+                syntax_grammar,
+                lexical_grammar,
+                inlines,
+                default_aliases,
+                warnings,
+            )
+        } else {
+            prepared
+        };
+    stats.record_grammar(&syntax_grammar, &lexical_grammar, &inlines);
+    inline_report.record(input_grammar, &syntax_grammar, &inlines);
+    let first_warning = warnings.len();
+    report_warnings(grammar_warnings, options.strict, warnings)?;
+    let grammar_warnings = warnings[first_warning..].to_vec();
+    check_abi_compatibility(&syntax_grammar, options.abi_version)?;
     let variable_info =
         node_types::get_variable_info(&syntax_grammar, &lexical_grammar, &simple_aliases)?;
-    let node_types_json = node_types::generate_node_types_json(
-        &syntax_grammar,
-        &lexical_grammar,
-        &simple_aliases,
-        &variable_info,
-    );
-    let supertype_symbol_map =
-        node_types::get_supertype_symbol_map(&syntax_grammar, &simple_aliases, &variable_info);
     let tables = build_tables(
         &syntax_grammar,
         &lexical_grammar,
         &simple_aliases,
         &variable_info,
         &inlines,
-        report_symbol_name,
+        options.report_symbol_name,
+        options.report_shadowed_tokens,
+        options.max_states,
+        options.minimize_lexer,
+        timings,
+        options.dynamic_precedence_report.as_deref_mut(),
     )?;
-    let c_code = render_c_code(
-        &input_grammar.name,
+    stats.parse_state_count = tables.parse_table.states.len();
+    stats.lex_state_count =
+        tables.main_lex_table.states.len() + tables.keyword_lex_table.states.len();
+    Ok(ParserTables {
+        syntax_grammar,
+        lexical_grammar,
+        simple_aliases,
         tables,
+        warnings: grammar_warnings,
+    })
+}
+
+/// Render the parser and its node types from the tables built by [`build_parser_tables`].
+fn render_parser(
+    name: &str,
+    parser_tables: ParserTables,
+    semantic_version: Option<(u8, u8, u8)>,
+    options: &mut GenerateOptions,
+) -> GeneratedParser {
+    let ParserTables {
         syntax_grammar,
         lexical_grammar,
         simple_aliases,
-        abi_version,
-        semantic_version,
-        supertype_symbol_map,
+        tables,
+        ..
+    } = parser_tables;
+    let mut disabled_timings = TimingReport::disabled();
+    let timings = options
+        .timings
+        .as_deref_mut()
+        .unwrap_or(&mut disabled_timings);
+    let mut default_stats = GrammarStats::default();
+    let stats = options.stats.as_deref_mut().unwrap_or(&mut default_stats);
+    let (mut node_types_json, supertype_symbol_map) = timings.time("node_types", || {
+        let variable_info =
+            node_types::get_variable_info(&syntax_grammar, &lexical_grammar, &simple_aliases)
+                .expect("variable info was computed while building the tables");
+        let node_types_json = node_types::generate_node_types_json(
+            &syntax_grammar,
+            &lexical_grammar,
+            &simple_aliases,
+            &variable_info,
+        );
+        let supertype_symbol_map =
+            node_types::get_supertype_symbol_map(&syntax_grammar, &simple_aliases, &variable_info);
+        (node_types_json, supertype_symbol_map)
+    });
+    node_types::mark_keyword_node_types(
+        &mut node_types_json,
+        &lexical_grammar,
+        &simple_aliases,
+        &tables.keywords,
     );
-    Ok(GeneratedParser {
+    let external_scanner_header = render_external_scanner_header(&syntax_grammar);
+    let (c_code, parser_tables) = timings.time("render_c_code", || {
+        render_c_code(
+            name,
+            tables,
+            syntax_grammar,
+            lexical_grammar,
+            simple_aliases,
+            options.abi_version,
+            semantic_version,
+            supertype_symbol_map,
+            options.split_output,
+            stats,
+        )
+    });
+    GeneratedParser {
         c_code,
+        parser_tables,
         node_types_json: serde_json::to_string_pretty(&node_types_json).unwrap(),
-    })
+        node_types_typescript: node_types::generate_node_types_typescript(&node_types_json),
+        external_scanner_header,
+    }
+}
+
+/// Remove the `parser_tables_<N>.c` files, starting from the given index, that were written
+/// by a previous run whose output was split into more files.
+fn remove_stale_parser_tables(src_path: &Path, first_stale_index: usize) -> GenerateResult<()> {
+    for entry in fs::read_dir(src_path)? {
+        let path = entry?.path();
+        let index = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("parser_tables_"))
+            .and_then(|name| name.strip_suffix(".c"))
+            .and_then(|index| index.parse::<usize>().ok());
+        if index.is_some_and(|index| index >= first_stale_index) {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write each of the grammars produced while preparing the grammar to its own JSON file
+/// in the given directory, so that the transformations can be inspected.
+fn write_grammar_intermediates(
+    artifacts: &PreparedGrammarArtifacts,
+    intermediates_path: &Path,
+) -> GenerateResult<()> {
+    fs::create_dir_all(intermediates_path)?;
+    let serde_json::Value::Object(stages) = serde_json::to_value(artifacts).unwrap() else {
+        unreachable!("grammar artifacts are serialized as a JSON object");
+    };
+    for (name, stage) in stages {
+        write_file(
+            &intermediates_path.join(format!("{name}.json")),
+            serde_json::to_string_pretty(&stage).unwrap(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Add the warnings that were found while preparing the grammar to `diagnostics`. In
+/// strict mode, the first warning is returned as an error instead.
+fn report_warnings(
+    warnings: Vec<PrepareGrammarWarning>,
+    strict: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> GenerateResult<()> {
+    let mut warnings = warnings.into_iter();
+    let first = if strict { warnings.next() } else { None };
+    diagnostics.extend(warnings.map(|warning| Diagnostic::from(&warning)));
+    if let Some(warning) = first {
+        Err(PrepareGrammarError::from(warning))?;
+    }
+    Ok(())
 }
 
 /// This will read the `tree-sitter.json` config file and attempt to extract the version.
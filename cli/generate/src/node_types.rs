@@ -1,6 +1,6 @@
 use std::{
-    cmp::Ordering,
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Write,
 };
 
 use anyhow::Result;
@@ -9,7 +9,7 @@ use thiserror::Error;
 
 use super::{
     grammars::{LexicalGrammar, SyntaxGrammar, VariableType},
-    rules::{Alias, AliasMap, Symbol, SymbolType},
+    rules::{Alias, AliasMap, Symbol, SymbolType, TokenSet},
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -41,6 +41,8 @@ pub struct NodeInfoJSON {
     root: bool,
     #[serde(skip_serializing_if = "std::ops::Not::not")]
     extra: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    keyword: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     fields: Option<BTreeMap<String, FieldInfoJSON>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -143,6 +145,20 @@ pub enum VariableInfoError {
     InvalidSupertype(String),
 }
 
+impl VariableInfoError {
+    pub(crate) const fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidSupertype(_) => "invalid-supertype",
+        }
+    }
+
+    pub(crate) fn rule(&self) -> Option<&str> {
+        match self {
+            Self::InvalidSupertype(rule) => Some(rule),
+        }
+    }
+}
+
 /// Compute a summary of the public-facing structure of each variable in the
 /// grammar. Each variable in the grammar corresponds to a distinct public-facing
 /// node type.
@@ -377,11 +393,11 @@ pub fn get_variable_info(
 fn get_aliases_by_symbol(
     syntax_grammar: &SyntaxGrammar,
     default_aliases: &AliasMap,
-) -> HashMap<Symbol, HashSet<Option<Alias>>> {
+) -> HashMap<Symbol, BTreeSet<Option<Alias>>> {
     let mut aliases_by_symbol = HashMap::new();
     for (symbol, alias) in default_aliases {
         aliases_by_symbol.insert(*symbol, {
-            let mut aliases = HashSet::new();
+            let mut aliases = BTreeSet::new();
             aliases.insert(Some(alias.clone()));
             aliases
         });
@@ -390,7 +406,7 @@ fn get_aliases_by_symbol(
         if !default_aliases.contains_key(extra_symbol) {
             aliases_by_symbol
                 .entry(*extra_symbol)
-                .or_insert_with(HashSet::new)
+                .or_insert_with(BTreeSet::new)
                 .insert(None);
         }
     }
@@ -399,7 +415,7 @@ fn get_aliases_by_symbol(
             for step in &production.steps {
                 aliases_by_symbol
                     .entry(step.symbol)
-                    .or_insert_with(HashSet::new)
+                    .or_insert_with(BTreeSet::new)
                     .insert(
                         step.alias
                             .as_ref()
@@ -520,6 +536,7 @@ pub fn generate_node_types_json(
                         named: true,
                         root: false,
                         extra: false,
+                        keyword: false,
                         fields: None,
                         children: None,
                         subtypes: None,
@@ -541,7 +558,7 @@ pub fn generate_node_types_json(
         } else if !syntax_grammar.variables_to_inline.contains(&symbol) {
             // If a rule is aliased under multiple names, then its information
             // contributes to multiple entries in the final JSON.
-            for alias in aliases_by_symbol.get(&symbol).unwrap_or(&HashSet::new()) {
+            for alias in aliases_by_symbol.get(&symbol).unwrap_or(&BTreeSet::new()) {
                 let kind;
                 let is_named;
                 if let Some(alias) = alias {
@@ -564,6 +581,7 @@ pub fn generate_node_types_json(
                         named: is_named,
                         root: i == 0,
                         extra: false,
+                        keyword: false,
                         fields: Some(BTreeMap::new()),
                         children: None,
                         subtypes: None,
@@ -602,16 +620,10 @@ pub fn generate_node_types_json(
         }
     }
 
-    // Sort the subtype map so that subtypes are listed before their supertypes.
-    subtype_map.sort_by(|a, b| {
-        if b.1.contains(&a.0) {
-            Ordering::Less
-        } else if a.1.contains(&b.0) {
-            Ordering::Greater
-        } else {
-            Ordering::Equal
-        }
-    });
+    // Sort the subtype map so that subtypes are listed before their supertypes. Supertypes
+    // can be nested, so this has to account for subtypes of subtypes.
+    let supertype_depths = get_supertype_depths(&subtype_map);
+    subtype_map.sort_by_key(|(supertype, _)| supertype_depths[supertype.kind.as_str()]);
 
     for node_type_json in node_types_json.values_mut() {
         if node_type_json
@@ -634,7 +646,7 @@ pub fn generate_node_types_json(
 
     let mut anonymous_node_types = Vec::new();
 
-    let empty = HashSet::new();
+    let empty = BTreeSet::new();
     let regular_tokens = lexical_grammar
         .variables
         .iter()
@@ -703,6 +715,7 @@ pub fn generate_node_types_json(
                             named: true,
                             root: false,
                             extra: extra_names.contains(&name),
+                            keyword: false,
                             fields: None,
                             children: None,
                             subtypes: None,
@@ -721,6 +734,7 @@ pub fn generate_node_types_json(
                 named: false,
                 root: false,
                 extra: extra_names.contains(&name),
+                keyword: false,
                 fields: None,
                 children: None,
                 subtypes: None,
@@ -731,10 +745,17 @@ pub fn generate_node_types_json(
 
     let mut result = node_types_json.into_iter().map(|e| e.1).collect::<Vec<_>>();
     result.extend(anonymous_node_types);
+    // Supertypes are listed first, and each supertype is listed before any supertypes
+    // that contain it.
     result.sort_unstable_by(|a, b| {
         b.subtypes
             .is_some()
             .cmp(&a.subtypes.is_some())
+            .then_with(|| {
+                let a_depth = supertype_depths.get(a.kind.as_str()).filter(|_| a.named);
+                let b_depth = supertype_depths.get(b.kind.as_str()).filter(|_| b.named);
+                a_depth.cmp(&b_depth)
+            })
             .then_with(|| {
                 let a_is_leaf = a.children.is_none() && a.fields.is_none();
                 let b_is_leaf = b.children.is_none() && b.fields.is_none();
@@ -746,6 +767,217 @@ pub fn generate_node_types_json(
     result
 }
 
+/// Mark the anonymous node types whose tokens are keywords. The keywords are only known once the
+/// parse table has been built, which happens after the node types have been generated.
+pub fn mark_keyword_node_types(
+    node_types_json: &mut [NodeInfoJSON],
+    lexical_grammar: &LexicalGrammar,
+    default_aliases: &AliasMap,
+    keywords: &TokenSet,
+) {
+    let keyword_names = keywords
+        .iter()
+        .filter(Symbol::is_terminal)
+        .filter_map(|symbol| {
+            if let Some(alias) = default_aliases.get(&symbol) {
+                (!alias.is_named).then_some(alias.value.as_str())
+            } else {
+                let variable = &lexical_grammar.variables[symbol.index];
+                (variable.kind == VariableType::Anonymous).then_some(variable.name.as_str())
+            }
+        })
+        .collect::<HashSet<_>>();
+    for node_type in node_types_json {
+        if !node_type.named && keyword_names.contains(node_type.kind.as_str()) {
+            node_type.keyword = true;
+        }
+    }
+}
+
+/// Render the node types as TypeScript declarations, with an interface for each named node
+/// type and a union type for each supertype. Anonymous node types are represented by
+/// `UnnamedNode`, whose `type` is a string literal type.
+pub fn generate_node_types_typescript(node_types: &[NodeInfoJSON]) -> String {
+    let mut used_names = HashSet::new();
+    let mut names = HashMap::new();
+    for node_type in node_types.iter().filter(|node_type| node_type.named) {
+        let base_name = typescript_type_name(&node_type.kind);
+        let mut name = base_name.clone();
+        let mut suffix = 1;
+        while !used_names.insert(name.clone()) {
+            suffix += 1;
+            name = format!("{base_name}{suffix}");
+        }
+        names.insert(node_type.kind.as_str(), name);
+    }
+
+    let type_reference = |node_type: &NodeTypeJSON| {
+        let kind = serde_json::to_string(&node_type.kind).unwrap();
+        if !node_type.named {
+            format!("UnnamedNode<{kind}>")
+        } else if let Some(name) = names.get(node_type.kind.as_str()) {
+            name.clone()
+        } else {
+            format!("{{ type: {kind}; named: true }}")
+        }
+    };
+    let field_type = |field: &FieldInfoJSON| {
+        let types = field.types.iter().map(type_reference).collect::<Vec<_>>();
+        if field.multiple {
+            if types.len() == 1 {
+                format!("{}[]", types[0])
+            } else {
+                format!("({})[]", types.join(" | "))
+            }
+        } else if field.required {
+            types.join(" | ")
+        } else {
+            format!("{} | null", types.join(" | "))
+        }
+    };
+
+    let mut result = String::new();
+    result += "// Automatically generated by tree-sitter from node-types.json. Do not edit.\n\n";
+    result += "export interface UnnamedNode<T extends string> {\n";
+    result += "  type: T;\n";
+    result += "  named: false;\n";
+    result += "}\n";
+
+    let mut concrete_names = Vec::new();
+    let mut unnamed_kinds = Vec::new();
+    for node_type in node_types {
+        let kind = serde_json::to_string(&node_type.kind).unwrap();
+        if !node_type.named {
+            unnamed_kinds.push(kind);
+            continue;
+        }
+
+        let name = &names[node_type.kind.as_str()];
+        result += "\n";
+        if let Some(subtypes) = &node_type.subtypes {
+            let subtypes = subtypes.iter().map(type_reference).collect::<Vec<_>>();
+            writeln!(result, "/** The `{}` supertype. */", node_type.kind).unwrap();
+            writeln!(result, "export type {name} = {};", subtypes.join(" | ")).unwrap();
+            continue;
+        }
+
+        concrete_names.push(name.as_str());
+        writeln!(result, "export interface {name} {{").unwrap();
+        writeln!(result, "  type: {kind};").unwrap();
+        writeln!(result, "  named: true;").unwrap();
+        if let Some(fields) = &node_type.fields {
+            if !fields.is_empty() {
+                writeln!(result, "  fields: {{").unwrap();
+                for (field_name, field) in fields {
+                    writeln!(
+                        result,
+                        "    {}: {};",
+                        typescript_property_name(field_name),
+                        field_type(field)
+                    )
+                    .unwrap();
+                }
+                writeln!(result, "  }};").unwrap();
+            }
+        }
+        if let Some(children) = &node_type.children {
+            writeln!(result, "  children: {};", field_type(children)).unwrap();
+        }
+        writeln!(result, "}}").unwrap();
+    }
+
+    result += "\n";
+    writeln!(
+        result,
+        "export type NamedNode ={};",
+        typescript_union(&concrete_names)
+    )
+    .unwrap();
+    writeln!(
+        result,
+        "export type UnnamedNodeType ={};",
+        typescript_union(&unnamed_kinds)
+    )
+    .unwrap();
+    result += "export type Node = NamedNode | UnnamedNode<UnnamedNodeType>;\n";
+    result
+}
+
+/// Render a union type with one member per line, or `never` if there are no members.
+fn typescript_union(members: &[impl AsRef<str>]) -> String {
+    if members.is_empty() {
+        return " never".to_string();
+    }
+    members.iter().fold(String::new(), |mut result, member| {
+        write!(result, "\n  | {}", member.as_ref()).unwrap();
+        result
+    })
+}
+
+/// Convert a node type to a TypeScript type name, such as `BinaryExpressionNode` for
+/// `binary_expression`.
+fn typescript_type_name(kind: &str) -> String {
+    let mut result = String::new();
+    for word in kind.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            result.push(first.to_ascii_uppercase());
+            result.extend(chars);
+        }
+    }
+    if result.is_empty() || result.starts_with(|c: char| c.is_ascii_digit()) {
+        result.insert(0, '_');
+    }
+    result + "Node"
+}
+
+fn typescript_property_name(name: &str) -> String {
+    let is_identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if is_identifier {
+        name.to_string()
+    } else {
+        serde_json::to_string(name).unwrap()
+    }
+}
+
+/// Compute how deeply each supertype nests other supertypes. A supertype whose subtypes
+/// are all concrete node types has a depth of zero.
+fn get_supertype_depths(
+    subtype_map: &[(NodeTypeJSON, Vec<NodeTypeJSON>)],
+) -> HashMap<String, usize> {
+    let mut depths = subtype_map
+        .iter()
+        .map(|(supertype, _)| (supertype.kind.clone(), 0))
+        .collect::<HashMap<_, _>>();
+
+    // Each pass raises the depth of a supertype above the depths of its subtypes. The
+    // number of passes is bounded so that supertypes which contain each other can't
+    // cause an infinite loop.
+    for _ in 0..subtype_map.len() {
+        let mut did_change = false;
+        for (supertype, subtypes) in subtype_map {
+            let depth = subtypes
+                .iter()
+                .filter(|subtype| subtype.named)
+                .filter_map(|subtype| depths.get(subtype.kind.as_str()))
+                .map(|depth| depth + 1)
+                .max()
+                .unwrap_or(0);
+            if depth > depths[supertype.kind.as_str()] {
+                depths.insert(supertype.kind.clone(), depth);
+                did_change = true;
+            }
+        }
+        if !did_change {
+            break;
+        }
+    }
+    depths
+}
+
 fn process_supertypes(info: &mut FieldInfoJSON, subtype_map: &[(NodeTypeJSON, Vec<NodeTypeJSON>)]) {
     for (supertype, subtypes) in subtype_map {
         if info.types.contains(supertype) {
@@ -840,6 +1072,7 @@ mod tests {
                 named: true,
                 root: true,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: None,
                 fields: Some(
@@ -879,6 +1112,7 @@ mod tests {
                 named: false,
                 root: false,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: None,
                 fields: None
@@ -891,6 +1125,7 @@ mod tests {
                 named: true,
                 root: false,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: None,
                 fields: None
@@ -937,6 +1172,7 @@ mod tests {
                 named: true,
                 root: true,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: None,
                 fields: Some(
@@ -976,6 +1212,7 @@ mod tests {
                 named: false,
                 root: false,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: None,
                 fields: None
@@ -988,6 +1225,7 @@ mod tests {
                 named: true,
                 root: false,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: None,
                 fields: None
@@ -1000,6 +1238,7 @@ mod tests {
                 named: true,
                 root: false,
                 extra: true,
+                keyword: false,
                 subtypes: None,
                 children: None,
                 fields: None
@@ -1047,6 +1286,7 @@ mod tests {
                 named: true,
                 root: false,
                 extra: false,
+                keyword: false,
                 fields: None,
                 children: None,
                 subtypes: Some(vec![
@@ -1072,6 +1312,7 @@ mod tests {
                 named: true,
                 root: true,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: None,
                 fields: Some(
@@ -1093,6 +1334,103 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_node_types_with_nested_supertypes() {
+        let node_types = get_node_types(&InputGrammar {
+            supertype_symbols: vec!["_v2".to_string(), "_v3".to_string()],
+            variables: vec![
+                Variable {
+                    name: "v1".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::field("f1".to_string(), Rule::named("_v2")),
+                        Rule::field("f2".to_string(), Rule::named("_v3")),
+                    ]),
+                },
+                Variable {
+                    name: "_v2".to_string(),
+                    kind: VariableType::Hidden,
+                    rule: Rule::choice(vec![Rule::named("_v3"), Rule::named("v4")]),
+                },
+                Variable {
+                    name: "_v3".to_string(),
+                    kind: VariableType::Hidden,
+                    rule: Rule::choice(vec![Rule::named("v5"), Rule::named("v6")]),
+                },
+                Variable {
+                    name: "v4".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::string("x"),
+                },
+                Variable {
+                    name: "v5".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::string("y"),
+                },
+                Variable {
+                    name: "v6".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::string("z"),
+                },
+            ],
+            ..Default::default()
+        });
+
+        // The nested supertype is listed before the supertype that contains it, and is
+        // not expanded into its own subtypes.
+        assert_eq!(
+            node_types[0],
+            NodeInfoJSON {
+                kind: "_v3".to_string(),
+                named: true,
+                root: false,
+                extra: false,
+                keyword: false,
+                fields: None,
+                children: None,
+                subtypes: Some(vec![
+                    NodeTypeJSON {
+                        kind: "v5".to_string(),
+                        named: true,
+                    },
+                    NodeTypeJSON {
+                        kind: "v6".to_string(),
+                        named: true,
+                    },
+                ]),
+            }
+        );
+        assert_eq!(
+            node_types[1],
+            NodeInfoJSON {
+                kind: "_v2".to_string(),
+                named: true,
+                root: false,
+                extra: false,
+                keyword: false,
+                fields: None,
+                children: None,
+                subtypes: Some(vec![
+                    NodeTypeJSON {
+                        kind: "_v3".to_string(),
+                        named: true,
+                    },
+                    NodeTypeJSON {
+                        kind: "v4".to_string(),
+                        named: true,
+                    },
+                ]),
+            }
+        );
+        assert_eq!(
+            node_types[2].fields.as_ref().unwrap()["f2"].types,
+            vec![NodeTypeJSON {
+                kind: "_v3".to_string(),
+                named: true,
+            }]
+        );
+    }
+
     #[test]
     fn test_node_types_for_children_without_fields() {
         let node_types = get_node_types(&InputGrammar {
@@ -1136,6 +1474,7 @@ mod tests {
                 named: true,
                 root: true,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: Some(FieldInfoJSON {
                     multiple: true,
@@ -1175,6 +1514,7 @@ mod tests {
                 named: true,
                 root: false,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: Some(FieldInfoJSON {
                     multiple: false,
@@ -1221,6 +1561,7 @@ mod tests {
                 named: true,
                 root: true,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: Some(FieldInfoJSON {
                     multiple: true,
@@ -1296,6 +1637,7 @@ mod tests {
                 named: true,
                 root: false,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: None,
                 fields: None,
@@ -1308,6 +1650,7 @@ mod tests {
                 named: true,
                 root: false,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: None,
                 fields: None,
@@ -1351,6 +1694,7 @@ mod tests {
                 named: true,
                 root: true,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: Some(FieldInfoJSON {
                     multiple: true,
@@ -1400,6 +1744,7 @@ mod tests {
                 named: true,
                 root: true,
                 extra: false,
+                keyword: false,
                 fields: Some(BTreeMap::new()),
                 children: None,
                 subtypes: None
@@ -1458,6 +1803,7 @@ mod tests {
                     named: true,
                     root: false,
                     extra: false,
+                    keyword: false,
                     subtypes: None,
                     children: None,
                     fields: Some(
@@ -1515,6 +1861,7 @@ mod tests {
                     named: true,
                     root: true,
                     extra: false,
+                    keyword: false,
                     subtypes: None,
                     // Only one node
                     children: Some(FieldInfoJSON {
@@ -1571,6 +1918,7 @@ mod tests {
                 named: true,
                 root: false,
                 extra: false,
+                keyword: false,
                 subtypes: None,
                 children: Some(FieldInfoJSON {
                     multiple: true,
@@ -1885,8 +2233,166 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_node_types_typescript() {
+        let node_types = get_node_types(&InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::named("pair")),
+                },
+                Variable {
+                    name: "pair".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::field("key".to_string(), Rule::named("key")),
+                        Rule::field(
+                            "operator".to_string(),
+                            Rule::choice(vec![Rule::string("="), Rule::string(":")]),
+                        ),
+                        Rule::choice(vec![
+                            Rule::field(
+                                "value".to_string(),
+                                Rule::alias(Rule::named("value"), "key_".to_string(), true),
+                            ),
+                            Rule::Blank,
+                        ]),
+                    ]),
+                },
+                Variable {
+                    name: "key".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+                Variable {
+                    name: "value".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[0-9]+", ""),
+                },
+            ],
+            ..Default::default()
+        });
+
+        assert_eq!(
+            generate_node_types_typescript(&node_types),
+            r#"// Automatically generated by tree-sitter from node-types.json. Do not edit.
+
+export interface UnnamedNode<T extends string> {
+  type: T;
+  named: false;
+}
+
+export interface PairNode {
+  type: "pair";
+  named: true;
+  fields: {
+    key: KeyNode;
+    operator: UnnamedNode<":"> | UnnamedNode<"=">;
+    value: KeyNode2 | null;
+  };
+}
+
+export interface ProgramNode {
+  type: "program";
+  named: true;
+  children: PairNode[];
+}
+
+export interface KeyNode {
+  type: "key";
+  named: true;
+}
+
+export interface KeyNode2 {
+  type: "key_";
+  named: true;
+}
+
+export type NamedNode =
+  | PairNode
+  | ProgramNode
+  | KeyNode
+  | KeyNode2;
+export type UnnamedNodeType =
+  | ":"
+  | "=";
+export type Node = NamedNode | UnnamedNode<UnnamedNodeType>;
+"#
+        );
+    }
+
+    #[test]
+    fn test_node_types_keywords() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "v1".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::string("if"),
+                        Rule::named("identifier"),
+                        Rule::string(";"),
+                    ]),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, _, default_aliases, _) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let mut node_types = generate_node_types_json(
+            &syntax_grammar,
+            &lexical_grammar,
+            &default_aliases,
+            &variable_info,
+        );
+
+        let mut keywords = TokenSet::new();
+        for (index, variable) in lexical_grammar.variables.iter().enumerate() {
+            if variable.name == "if" || variable.name == "identifier" {
+                keywords.insert(Symbol::terminal(index));
+            }
+        }
+        mark_keyword_node_types(
+            &mut node_types,
+            &lexical_grammar,
+            &default_aliases,
+            &keywords,
+        );
+
+        let keyword_kinds = node_types
+            .iter()
+            .filter(|node_type| node_type.keyword)
+            .map(|node_type| node_type.kind.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(keyword_kinds, ["if"]);
+
+        let json = serde_json::to_value(&node_types).unwrap();
+        let if_json = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|node_type| node_type["type"] == "if")
+            .unwrap();
+        assert_eq!(if_json["keyword"], true);
+        let semicolon_json = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|node_type| node_type["type"] == ";")
+            .unwrap();
+        assert!(semicolon_json.get("keyword").is_none());
+    }
+
     fn get_node_types(grammar: &InputGrammar) -> Vec<NodeInfoJSON> {
-        let (syntax_grammar, lexical_grammar, _, default_aliases) =
+        let (syntax_grammar, lexical_grammar, _, default_aliases, _) =
             prepare_grammar(grammar).unwrap();
         let variable_info =
             get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
@@ -1,7 +1,13 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 use serde_json::{Map, Value};
 use thiserror::Error;
 
@@ -25,6 +31,9 @@ enum RuleJSON {
     STRING {
         value: String,
     },
+    CASE_INSENSITIVE {
+        value: String,
+    },
     PATTERN {
         value: String,
         flags: Option<String>,
@@ -83,10 +92,27 @@ enum PrecedenceValueJSON {
     Name(String),
 }
 
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WordTokenJSON {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl WordTokenJSON {
+    fn into_names(self) -> Vec<String> {
+        match self {
+            Self::Single(name) => vec![name],
+            Self::Multiple(names) => names,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct GrammarJSON {
     pub name: String,
-    rules: Map<String, Value>,
+    #[serde(deserialize_with = "deserialize_rules")]
+    rules: Vec<(String, Value)>,
     #[serde(default)]
     precedences: Vec<Vec<RuleJSON>>,
     #[serde(default)]
@@ -100,7 +126,7 @@ pub struct GrammarJSON {
     #[serde(default)]
     supertypes: Vec<String>,
     #[serde(default)]
-    word: Option<String>,
+    word: Option<WordTokenJSON>,
     #[serde(default)]
     reserved: Map<String, Value>,
 }
@@ -119,6 +145,37 @@ pub enum ParseGrammarError {
     InvalidReservedWordSet,
     #[error("Grammar Error: Unexpected rule `{0}` in `token()` call")]
     UnexpectedRule(String),
+    #[error(transparent)]
+    DuplicateRule(#[from] DuplicateRuleError),
+}
+
+/// A rule that is defined more than once in a grammar's `rules` object, along with the
+/// positions of both definitions in the JSON, when they can be found.
+#[derive(Debug, Error, Serialize)]
+pub struct DuplicateRuleError {
+    pub name: String,
+    pub first_index: usize,
+    pub second_index: usize,
+    pub first_position: Option<(usize, usize)>,
+    pub second_position: Option<(usize, usize)>,
+}
+
+impl fmt::Display for DuplicateRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let location = |index: usize, position: Option<(usize, usize)>| {
+            position.map_or_else(
+                || format!("index {index}"),
+                |(line, column)| format!("index {index} (line {line}, column {column})"),
+            )
+        };
+        write!(
+            f,
+            "Rule `{}` is defined more than once in the grammar's rules, at {} and {}",
+            self.name,
+            location(self.first_index, self.first_position),
+            location(self.second_index, self.second_position),
+        )
+    }
 }
 
 impl From<serde_json::Error> for ParseGrammarError {
@@ -127,6 +184,113 @@ impl From<serde_json::Error> for ParseGrammarError {
     }
 }
 
+impl ParseGrammarError {
+    pub(crate) const fn code(&self) -> &'static str {
+        match self {
+            Self::Serialization(_) => "invalid-grammar-json",
+            Self::InvalidExtra => "empty-extra",
+            Self::Unexpected => "invalid-precedence-entry",
+            Self::InvalidReservedWordSet => "invalid-reserved-word-set",
+            Self::UnexpectedRule(_) => "non-terminal-in-token",
+            Self::DuplicateRule(_) => "duplicate-rule",
+        }
+    }
+
+    pub(crate) fn rule(&self) -> Option<&str> {
+        match self {
+            Self::UnexpectedRule(name) => Some(name),
+            Self::DuplicateRule(e) => Some(&e.name),
+            _ => None,
+        }
+    }
+}
+
+/// Deserialize the grammar's `rules` object as a list of entries, so that a rule which is
+/// defined more than once can be reported instead of silently replacing the earlier one.
+fn deserialize_rules<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(String, Value)>, D::Error> {
+    struct RulesVisitor;
+
+    impl<'de> Visitor<'de> for RulesVisitor {
+        type Value = Vec<(String, Value)>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an object mapping rule names to rules")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut rules = Vec::with_capacity(map.size_hint().unwrap_or_default());
+            while let Some(entry) = map.next_entry()? {
+                rules.push(entry);
+            }
+            Ok(rules)
+        }
+    }
+
+    deserializer.deserialize_map(RulesVisitor)
+}
+
+/// Find the line and column of each key in the grammar's `rules` object, in order. This
+/// only follows the nesting of the JSON, without validating it, which is enough to point
+/// at the definitions of a duplicate rule once the JSON has been parsed successfully.
+fn rule_key_positions(input: &str) -> Vec<(usize, usize)> {
+    let mut offsets = Vec::new();
+    let mut containers = Vec::new();
+    let mut expecting_key = false;
+    let mut top_level_key = String::new();
+    let mut chars = input.char_indices();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '{' => {
+                containers.push(c);
+                expecting_key = true;
+            }
+            '[' => {
+                containers.push(c);
+                expecting_key = false;
+            }
+            '}' | ']' => {
+                containers.pop();
+                expecting_key = false;
+            }
+            ',' => expecting_key = containers.last() == Some(&'{'),
+            '"' => {
+                let mut string = String::new();
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => string.push(c),
+                    }
+                }
+                if expecting_key {
+                    if containers.len() == 1 {
+                        top_level_key = string;
+                    } else if containers == ['{', '{'] && top_level_key == "rules" {
+                        offsets.push(offset);
+                    }
+                    expecting_key = false;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    offsets
+        .into_iter()
+        .map(|offset| {
+            let line_start = input[..offset].rfind('\n').map_or(0, |i| i + 1);
+            (
+                input[..offset].matches('\n').count() + 1,
+                input[line_start..offset].chars().count() + 1,
+            )
+        })
+        .collect()
+}
+
 /// Check if a rule is referenced by another rule.
 ///
 /// This function is used to determine if a variable is used in a given rule,
@@ -190,8 +354,12 @@ fn variable_is_used(
     result
 }
 
-pub(crate) fn parse_grammar(input: &str) -> ParseGrammarResult<InputGrammar> {
+pub fn parse_grammar(input: &str) -> ParseGrammarResult<InputGrammar> {
     let mut grammar_json = serde_json::from_str::<GrammarJSON>(input)?;
+    let word_tokens = grammar_json
+        .word
+        .take()
+        .map_or_else(Vec::new, WordTokenJSON::into_names);
 
     let mut extra_symbols =
         grammar_json
@@ -227,6 +395,20 @@ pub(crate) fn parse_grammar(input: &str) -> ParseGrammarResult<InputGrammar> {
         precedence_orderings.push(ordering);
     }
 
+    let mut rule_indices = HashMap::new();
+    for (second_index, (name, _)) in grammar_json.rules.iter().enumerate() {
+        if let Some(first_index) = rule_indices.insert(name.as_str(), second_index) {
+            let positions = rule_key_positions(input);
+            Err(DuplicateRuleError {
+                name: name.clone(),
+                first_index,
+                second_index,
+                first_position: positions.get(first_index).copied(),
+                second_position: positions.get(second_index).copied(),
+            })?;
+        }
+    }
+
     let mut variables = Vec::with_capacity(grammar_json.rules.len());
 
     let rules = grammar_json
@@ -244,11 +426,10 @@ pub(crate) fn parse_grammar(input: &str) -> ParseGrammarResult<InputGrammar> {
             &external_tokens,
             name,
             &mut in_progress,
-        ) && grammar_json.word.as_ref().is_none_or(|w| w != name)
+        ) && !word_tokens.contains(name)
         {
             grammar_json.conflicts.retain(|r| !r.contains(name));
             grammar_json.supertypes.retain(|r| r != name);
-            grammar_json.inline.retain(|r| r != name);
             extra_symbols.retain(|r| !rule_is_referenced(r, name, true));
             external_tokens.retain(|r| !rule_is_referenced(r, name, true));
             precedence_orderings.retain(|r| {
@@ -290,7 +471,7 @@ pub(crate) fn parse_grammar(input: &str) -> ParseGrammarResult<InputGrammar> {
 
     Ok(InputGrammar {
         name: grammar_json.name,
-        word_token: grammar_json.word,
+        word_tokens,
         expected_conflicts: grammar_json.conflicts,
         supertype_symbols: grammar_json.supertypes,
         variables_to_inline: grammar_json.inline,
@@ -311,6 +492,7 @@ fn parse_rule(json: RuleJSON, is_token: bool) -> ParseGrammarResult<Rule> {
         } => parse_rule(*content, is_token).map(|r| Rule::alias(r, value, named)),
         RuleJSON::BLANK => Ok(Rule::Blank),
         RuleJSON::STRING { value } => Ok(Rule::String(value)),
+        RuleJSON::CASE_INSENSITIVE { value } => Ok(Rule::case_insensitive(value)),
         RuleJSON::PATTERN { value, flags } => Ok(Rule::Pattern(
             value,
             flags.map_or(String::new(), |f| {
@@ -430,4 +612,71 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_parse_grammar_with_multiple_word_tokens() {
+        let grammar = parse_grammar(
+            r#"{
+            "name": "my_lang",
+            "word": ["identifier", "tag_name"],
+            "rules": {
+                "file": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "SYMBOL", "name": "tag_name"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+                "tag_name": {"type": "PATTERN", "value": "[A-Z][a-z]*"}
+            }
+        }"#,
+        )
+        .unwrap();
+        assert_eq!(grammar.word_tokens, vec!["identifier", "tag_name"]);
+
+        let grammar = parse_grammar(
+            r#"{
+            "name": "my_lang",
+            "word": "identifier",
+            "rules": {
+                "file": {"type": "SYMBOL", "name": "identifier"},
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#,
+        )
+        .unwrap();
+        assert_eq!(grammar.word_tokens, vec!["identifier"]);
+    }
+
+    #[test]
+    fn test_parse_grammar_with_duplicate_rules() {
+        let error = parse_grammar(
+            r#"{
+            "name": "my_lang",
+            "rules": {
+                "file": {"type": "SYMBOL", "name": "statement"},
+                "statement": {"type": "STRING", "value": "{\"rules\": ["},
+                "statement": {"type": "STRING", "value": "bar"}
+            }
+        }"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Rule `statement` is defined more than once in the grammar's rules, at index 1 (line 5, column 17) and index 2 (line 6, column 17)"
+        );
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "DuplicateRule": {
+                    "name": "statement",
+                    "first_index": 1,
+                    "second_index": 2,
+                    "first_position": [5, 17],
+                    "second_position": [6, 17],
+                }
+            })
+        );
+    }
 }
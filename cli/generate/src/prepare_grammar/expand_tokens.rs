@@ -1,6 +1,7 @@
 use anyhow::Result;
 use regex_syntax::{
-    hir::{Class, Hir, HirKind},
+    ast::{self, AssertionKind, Ast},
+    hir::{self, Class, ClassUnicode, ClassUnicodeRange, Hir, HirKind},
     ParserBuilder,
 };
 use serde::Serialize;
@@ -8,14 +9,15 @@ use thiserror::Error;
 
 use super::ExtractedLexicalGrammar;
 use crate::{
-    grammars::{LexicalGrammar, LexicalVariable},
-    nfa::{CharacterSet, Nfa, NfaState},
+    grammars::{LexicalGrammar, LexicalVariable, Variable, VariableType},
+    nfa::{CharacterSet, Nfa, NfaCursor, NfaState},
     rules::{Precedence, Rule},
 };
 
 struct NfaBuilder {
     nfa: Nfa,
     is_sep: bool,
+    is_case_insensitive: bool,
     precedence_stack: Vec<i32>,
 }
 
@@ -30,6 +32,22 @@ unless they are used only as the grammar's start rule.
         "
     )]
     EmptyString(String),
+    #[error(
+        "The token `{0}` can match the empty string.
+Tokens must consume at least one character. Consider removing the `optional`
+or `*` from the token, and making it optional in the rules that use it instead.
+        "
+    )]
+    NullableToken(String),
+    #[error(
+        "The token `{}` refers to itself: `{}`.
+Tokens cannot be recursive. Consider turning the rule into a syntactic rule by
+removing the `token` that contains it, or rewriting it with `repeat`.
+        ",
+        .0[0],
+        .0.join(" -> ")
+    )]
+    RecursiveToken(Vec<String>),
     #[error(transparent)]
     Processing(ExpandTokensProcessingError),
     #[error(transparent)]
@@ -42,13 +60,30 @@ pub struct ExpandTokensProcessingError {
     error: ExpandRuleError,
 }
 
+impl ExpandTokensError {
+    pub(super) const fn code(&self) -> &'static str {
+        match self {
+            Self::EmptyString(_) => "empty-string",
+            Self::NullableToken(_) => "nullable-token",
+            Self::RecursiveToken(_) => "recursive-token",
+            Self::Processing(e) => e.error.code(),
+            Self::ExpandRule(e) => e.code(),
+        }
+    }
+
+    pub(super) fn rule(&self) -> Option<&str> {
+        match self {
+            Self::EmptyString(rule) | Self::NullableToken(rule) => Some(rule),
+            Self::RecursiveToken(cycle) => cycle.first().map(String::as_str),
+            Self::Processing(e) => Some(&e.rule),
+            Self::ExpandRule(_) => None,
+        }
+    }
+}
+
 impl std::fmt::Display for ExpandTokensProcessingError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(
-            f,
-            "Error processing rule {}: Grammar error: Unexpected rule {:?}",
-            self.rule, self.error
-        )?;
+        writeln!(f, "Error processing rule {}: {}", self.rule, self.error)?;
         Ok(())
     }
 }
@@ -76,10 +111,83 @@ const fn get_completion_precedence(rule: &Rule) -> i32 {
     0
 }
 
+/// Find a cycle of tokens that refer to each other. The returned names start and end with
+/// the same token.
+fn find_recursive_token(variables: &[Variable]) -> Option<Vec<String>> {
+    fn add_references(rule: &Rule, references: &mut Vec<usize>) {
+        match rule {
+            Rule::Symbol(symbol) if symbol.is_terminal() => references.push(symbol.index),
+            Rule::Choice(elements) | Rule::Seq(elements) => {
+                for element in elements {
+                    add_references(element, references);
+                }
+            }
+            Rule::Metadata { rule, .. } | Rule::Reserved { rule, .. } | Rule::Repeat(rule) => {
+                add_references(rule, references);
+            }
+            _ => {}
+        }
+    }
+
+    fn visit(
+        index: usize,
+        references: &[Vec<usize>],
+        visited: &mut [bool],
+        path: &mut Vec<usize>,
+    ) -> Option<Vec<usize>> {
+        visited[index] = true;
+        path.push(index);
+        for &next in &references[index] {
+            if let Some(start) = path.iter().position(|i| *i == next) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(next);
+                return Some(cycle);
+            }
+            if !visited[next] {
+                if let Some(cycle) = visit(next, references, visited, path) {
+                    return Some(cycle);
+                }
+            }
+        }
+        path.pop();
+        None
+    }
+
+    let references = variables
+        .iter()
+        .map(|variable| {
+            let mut references = Vec::new();
+            add_references(&variable.rule, &mut references);
+            references.retain(|index| *index < variables.len());
+            references
+        })
+        .collect::<Vec<_>>();
+    let mut visited = vec![false; variables.len()];
+    (0..variables.len()).find_map(|index| {
+        if visited[index] {
+            return None;
+        }
+        let cycle = visit(index, &references, &mut visited, &mut Vec::new())?;
+        Some(
+            cycle
+                .into_iter()
+                .map(|i| variables[i].name.clone())
+                .collect(),
+        )
+    })
+}
+
 pub fn expand_tokens(mut grammar: ExtractedLexicalGrammar) -> ExpandTokensResult<LexicalGrammar> {
+    // Tokens are expanded into a single state machine, so they can't refer to themselves,
+    // either directly or through other tokens.
+    if let Some(cycle) = find_recursive_token(&grammar.variables) {
+        Err(ExpandTokensError::RecursiveToken(cycle))?;
+    }
+
     let mut builder = NfaBuilder {
         nfa: Nfa::new(),
         is_sep: true,
+        is_case_insensitive: false,
         precedence_stack: vec![0],
     };
 
@@ -116,6 +224,19 @@ pub fn expand_tokens(mut grammar: ExtractedLexicalGrammar) -> ExpandTokensResult
                 })
             })?;
 
+        // A token must not be able to complete without consuming any characters.
+        // Separators are exempt from this, since they are always optional, and so are
+        // auxiliary tokens, like the `/[^"]*/` in `seq('"', /[^"]*/, '"')` or in
+        // `token.immediate(/[^"]*/)`, because existing grammars commonly rely on them.
+        let token_start_state_id = builder.nfa.last_state_id();
+        if variable.kind != VariableType::Auxiliary
+            && NfaCursor::new(&builder.nfa, vec![token_start_state_id])
+                .completions()
+                .any(|(variable_index, _)| variable_index == i)
+        {
+            Err(ExpandTokensError::NullableToken(variable.name.clone()))?;
+        }
+
         if !is_immediate_token {
             builder.is_sep = true;
             let last_state_id = builder.nfa.last_state_id();
@@ -138,6 +259,92 @@ pub fn expand_tokens(mut grammar: ExtractedLexicalGrammar) -> ExpandTokensResult
     })
 }
 
+/// Find all of the characters that are equivalent to the given character under
+/// Unicode simple case folding. As with case-insensitive regexes, ASCII characters
+/// are only matched by other ASCII characters, so that `k` and `s` don't match the
+/// Kelvin sign or the long s.
+fn case_folded_chars(c: char) -> CharacterSet {
+    let mut class = ClassUnicode::new([ClassUnicodeRange::new(c, c)]);
+    class.case_fold_simple();
+    let mut chars = CharacterSet::empty();
+    for range in class.ranges() {
+        for folded in range.start()..=range.end() {
+            if !c.is_ascii() || folded.is_ascii() {
+                chars = chars.add_char(folded);
+            }
+        }
+    }
+    chars
+}
+
+fn regex_parse_error(pattern: &str, error: &regex_syntax::Error) -> ExpandRuleError {
+    match error {
+        regex_syntax::Error::Translate(error)
+            if matches!(
+                error.kind(),
+                hir::ErrorKind::UnicodePropertyNotFound
+                    | hir::ErrorKind::UnicodePropertyValueNotFound
+            ) =>
+        {
+            let span = error.span();
+            ExpandRuleError::UnknownUnicodeProperty(
+                pattern[span.start.offset..span.end.offset].to_string(),
+            )
+        }
+        _ => ExpandRuleError::Parse(error.to_string()),
+    }
+}
+
+/// Find syntax in a regex that the regex parser rejects, but that users commonly
+/// expect to work, like look-around and backreferences.
+fn find_unsupported_syntax(pattern: &str) -> Option<UnsupportedRegexError> {
+    let error = ast::parse::Parser::new().parse(pattern).err()?;
+    let span = error.span();
+    let text = &pattern[span.start.offset..span.end.offset];
+    let construct = match error.kind() {
+        ast::ErrorKind::UnsupportedBackreference => format!("backreference {text}"),
+        ast::ErrorKind::UnsupportedLookAround if text.starts_with("(?<") => {
+            "lookbehind assertion".to_string()
+        }
+        ast::ErrorKind::UnsupportedLookAround => "lookahead assertion".to_string(),
+        _ => return None,
+    };
+    Some(UnsupportedRegexError::new(pattern, span, construct))
+}
+
+/// Find the first assertion in a regex, since assertions can't be represented in
+/// the lexer's NFA.
+fn find_unsupported_assertion(pattern: &str) -> Option<UnsupportedRegexError> {
+    fn find_assertion(ast: &Ast) -> Option<&ast::Assertion> {
+        match ast {
+            Ast::Assertion(assertion) => Some(assertion),
+            Ast::Repetition(repetition) => find_assertion(&repetition.ast),
+            Ast::Group(group) => find_assertion(&group.ast),
+            Ast::Alternation(alternation) => alternation.asts.iter().find_map(find_assertion),
+            Ast::Concat(concat) => concat.asts.iter().find_map(find_assertion),
+            _ => None,
+        }
+    }
+
+    let ast = ast::parse::Parser::new().parse(pattern).ok()?;
+    let assertion = find_assertion(&ast)?;
+    let name = match assertion.kind {
+        AssertionKind::StartLine => "start-of-line anchor",
+        AssertionKind::EndLine => "end-of-line anchor",
+        AssertionKind::StartText => "start-of-text anchor",
+        AssertionKind::EndText => "end-of-text anchor",
+        AssertionKind::NotWordBoundary => "non-word-boundary assertion",
+        _ => "word boundary assertion",
+    };
+    let span = &assertion.span;
+    let text = &pattern[span.start.offset..span.end.offset];
+    Some(UnsupportedRegexError::new(
+        pattern,
+        span,
+        format!("{name} {text}"),
+    ))
+}
+
 pub type ExpandRuleResult<T> = Result<T, ExpandRuleError>;
 
 #[derive(Debug, Error, Serialize)]
@@ -146,10 +353,63 @@ pub enum ExpandRuleError {
     UnexpectedRule(Rule),
     #[error("{0}")]
     Parse(String),
+    #[error("Unknown Unicode property `{0}`")]
+    UnknownUnicodeProperty(String),
+    #[error(transparent)]
+    UnsupportedRegex(UnsupportedRegexError),
     #[error(transparent)]
     ExpandRegex(ExpandRegexError),
 }
 
+impl ExpandRuleError {
+    const fn code(&self) -> &'static str {
+        match self {
+            Self::UnexpectedRule(_) => "non-terminal-in-token",
+            Self::Parse(_) | Self::ExpandRegex(ExpandRegexError::Utf8(_)) => "invalid-regex",
+            Self::UnknownUnicodeProperty(_) => "unknown-unicode-property",
+            Self::UnsupportedRegex(_) | Self::ExpandRegex(ExpandRegexError::Assertion) => {
+                "unsupported-regex"
+            }
+        }
+    }
+}
+
+/// A construct within a token's regex that can't be expanded into the lexer's NFA.
+/// `start` and `end` are the byte offsets of the construct within `pattern`.
+#[derive(Debug, Error, Serialize)]
+pub struct UnsupportedRegexError {
+    pub pattern: String,
+    pub start: usize,
+    pub end: usize,
+    pub construct: String,
+}
+
+impl UnsupportedRegexError {
+    fn new(pattern: &str, span: &ast::Span, construct: String) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            start: span.start.offset,
+            end: span.end.offset,
+            construct,
+        }
+    }
+}
+
+impl std::fmt::Display for UnsupportedRegexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let column = self.pattern[..self.start].chars().count();
+        let width = self.pattern[self.start..self.end].chars().count().max(1);
+        writeln!(
+            f,
+            "Unsupported {} at offset {} in regex:",
+            self.construct, self.start
+        )?;
+        writeln!(f, "  /{}/", self.pattern)?;
+        write!(f, "   {}{}", " ".repeat(column), "^".repeat(width))?;
+        Ok(())
+    }
+}
+
 pub type ExpandRegexResult<T> = Result<T, ExpandRegexError>;
 
 #[derive(Debug, Error, Serialize)]
@@ -163,13 +423,13 @@ pub enum ExpandRegexError {
 impl NfaBuilder {
     fn expand_rule(&mut self, rule: &Rule, mut next_state_id: u32) -> ExpandRuleResult<bool> {
         match rule {
-            Rule::Pattern(s, f) => {
+            Rule::Pattern(pattern, f) => {
                 // With unicode enabled, `\w`, `\s` and `\d` expand to character sets that are much
                 // larger than intended, so we replace them with the actual
                 // character sets they should represent. If the full unicode range
                 // of `\w`, `\s` or `\d` are needed then `\p{L}`, `\p{Z}` and `\p{N}` should be
                 // used.
-                let s = s
+                let s = pattern
                     .replace(r"\w", r"[0-9A-Za-z_]")
                     .replace(r"\s", r"[\t-\r ]")
                     .replace(r"\d", r"[0-9]")
@@ -181,15 +441,30 @@ impl NfaBuilder {
                     .unicode(true)
                     .utf8(false)
                     .build();
-                let hir = parser
-                    .parse(&s)
-                    .map_err(|e| ExpandRuleError::Parse(e.to_string()))?;
-                self.expand_regex(&hir, next_state_id)
-                    .map_err(ExpandRuleError::ExpandRegex)
+
+                // The regex is rewritten above, so unsupported constructs are located
+                // within the original pattern, in order to report their offsets.
+                let hir = parser.parse(&s).map_err(|e| {
+                    find_unsupported_syntax(pattern).map_or_else(
+                        || regex_parse_error(&s, &e),
+                        ExpandRuleError::UnsupportedRegex,
+                    )
+                })?;
+                self.expand_regex(&hir, next_state_id).map_err(|e| {
+                    find_unsupported_assertion(pattern).map_or(
+                        ExpandRuleError::ExpandRegex(e),
+                        ExpandRuleError::UnsupportedRegex,
+                    )
+                })
             }
             Rule::String(s) => {
                 for c in s.chars().rev() {
-                    self.push_advance(CharacterSet::empty().add_char(c), next_state_id);
+                    let chars = if self.is_case_insensitive {
+                        case_folded_chars(c)
+                    } else {
+                        CharacterSet::empty().add_char(c)
+                    };
+                    self.push_advance(chars, next_state_id);
                     next_state_id = self.nfa.last_state_id();
                 }
                 Ok(!s.is_empty())
@@ -242,7 +517,10 @@ impl NfaBuilder {
                 } else {
                     false
                 };
+                let was_case_insensitive = self.is_case_insensitive;
+                self.is_case_insensitive |= params.is_case_insensitive;
                 let result = self.expand_rule(rule, next_state_id);
+                self.is_case_insensitive = was_case_insensitive;
                 if has_precedence {
                     self.precedence_stack.pop();
                 }
@@ -429,8 +707,8 @@ impl NfaBuilder {
 mod tests {
     use super::*;
     use crate::{
-        grammars::Variable,
         nfa::{NfaCursor, NfaTransition},
+        rules::Symbol,
     };
 
     fn simulate_nfa<'a>(grammar: &'a LexicalGrammar, s: &'a str) -> Option<(usize, &'a str)> {
@@ -499,9 +777,9 @@ mod tests {
             },
             // regex with repeats
             Row {
-                rules: vec![Rule::pattern("a*", "")],
+                rules: vec![Rule::pattern("ba*", "")],
                 separators: vec![],
-                examples: vec![("aaa1", Some((0, "aaa"))), ("b", Some((0, "")))],
+                examples: vec![("baaa1", Some((0, "baaa"))), ("b", Some((0, "b")))],
             },
             // regex with repeats in sequences
             Row {
@@ -532,6 +810,23 @@ mod tests {
                 separators: vec![],
                 examples: vec![("abcd", Some((0, "abc"))), ("ab", None)],
             },
+            // case-insensitive string
+            Row {
+                rules: vec![Rule::case_insensitive("select".to_string())],
+                separators: vec![],
+                examples: vec![
+                    ("select;", Some((0, "select"))),
+                    ("SeLeCt;", Some((0, "SeLeCt"))),
+                    ("ſelect;", None),
+                    ("selec", None),
+                ],
+            },
+            // case-insensitive string with non-ASCII characters
+            Row {
+                rules: vec![Rule::case_insensitive("für".to_string())],
+                separators: vec![],
+                examples: vec![("FÜR.", Some((0, "FÜR"))), ("FUR.", None)],
+            },
             // complex rule containing strings and regexes
             Row {
                 rules: vec![Rule::repeat(Rule::seq(vec![
@@ -694,6 +989,30 @@ mod tests {
                 separators: vec![],
                 examples: vec![("abΨ12٣٣, ok", Some((0, "abΨ12٣٣")))],
             },
+            // unicode identifier properties
+            Row {
+                rules: vec![Rule::pattern(r"\p{XID_Start}\p{XID_Continue}*", "")],
+                separators: vec![],
+                examples: vec![("ñame_2 ", Some((0, "ñame_2"))), ("2ñame", None)],
+            },
+            // unicode general categories and scripts in bracketed sets
+            Row {
+                rules: vec![Rule::pattern(r"[\p{Lu}\p{Greek}]+", "")],
+                separators: vec![],
+                examples: vec![("ABγδc", Some((0, "ABγδ"))), ("abc", None)],
+            },
+            // negated unicode properties
+            Row {
+                rules: vec![Rule::pattern(r"\P{XID_Continue}+", "")],
+                separators: vec![],
+                examples: vec![("+-*a", Some((0, "+-*"))), ("a+", None)],
+            },
+            // negated unicode properties in bracketed sets
+            Row {
+                rules: vec![Rule::pattern(r"[^\p{L}\p{Zs}]+", "")],
+                separators: vec![],
+                examples: vec![("123 a", Some((0, "123"))), ("abc", None)],
+            },
             // unicode character escapes
             Row {
                 rules: vec![
@@ -823,4 +1142,219 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_unknown_unicode_properties() {
+        for (pattern, property) in [
+            (r"\p{XID_Start}\p{XID_Continu}*", r"\p{XID_Continu}"),
+            (r"[^\P{Script=Gothc}]", r"\P{Script=Gothc}"),
+        ] {
+            let error = expand_tokens(ExtractedLexicalGrammar {
+                separators: vec![],
+                variables: vec![Variable::named("identifier", Rule::pattern(pattern, ""))],
+            })
+            .err()
+            .unwrap();
+            assert_eq!(
+                error.to_string().trim_end(),
+                format!("Error processing rule identifier: Unknown Unicode property `{property}`")
+            );
+        }
+    }
+
+    #[test]
+    fn test_unsupported_regex_constructs() {
+        let expand_pattern = |pattern: &'static str| {
+            let Err(ExpandTokensError::Processing(error)) =
+                expand_tokens(ExtractedLexicalGrammar {
+                    separators: vec![],
+                    variables: vec![Variable::named("token", Rule::pattern(pattern, ""))],
+                })
+            else {
+                panic!("Expected an error processing {pattern:?}");
+            };
+            error
+        };
+
+        for (pattern, construct, start, end) in [
+            (r"a(?=b)", "lookahead assertion", 1, 4),
+            (r"\w+(?!b)", "lookahead assertion", 3, 6),
+            (r"(?<!x)y", "lookbehind assertion", 0, 4),
+            (r"(a)b\1", r"backreference \1", 4, 6),
+            (r"^abc", "start-of-line anchor ^", 0, 1),
+            (r"\d+\b", r"word boundary assertion \b", 3, 5),
+        ] {
+            let error = expand_pattern(pattern);
+            let ExpandRuleError::UnsupportedRegex(unsupported) = &error.error else {
+                panic!("Expected an unsupported regex error for {pattern:?}, got {error:?}");
+            };
+            assert_eq!(unsupported.pattern, pattern);
+            assert_eq!(unsupported.construct, construct);
+            assert_eq!((unsupported.start, unsupported.end), (start, end));
+        }
+
+        let error = expand_pattern("[a-z]+(?=ü)");
+        assert_eq!(
+            error.to_string(),
+            [
+                "Error processing rule token: Unsupported lookahead assertion at offset 6 in regex:",
+                "  /[a-z]+(?=ü)/",
+                "         ^^^",
+                "",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "rule": "token",
+                "error": {
+                    "UnsupportedRegex": {
+                        "pattern": "[a-z]+(?=ü)",
+                        "start": 6,
+                        "end": 9,
+                        "construct": "lookahead assertion",
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_nullable_tokens() {
+        let rules = [
+            // regexes with `*` quantifiers
+            Rule::pattern("a*", ""),
+            Rule::pattern("(ab)*c?", ""),
+            Rule::pattern("a|b*", ""),
+            // repeats of rules that can match the empty string
+            Rule::repeat(Rule::choice(vec![Rule::Blank, Rule::string("a")])),
+            Rule::token(Rule::repeat(Rule::seq(vec![
+                Rule::pattern("x?", ""),
+                Rule::pattern("y*", ""),
+            ]))),
+            // nested choices with a blank branch
+            Rule::choice(vec![
+                Rule::string("a"),
+                Rule::choice(vec![Rule::string("b"), Rule::Blank]),
+            ]),
+            Rule::seq(vec![
+                Rule::choice(vec![Rule::Blank, Rule::string("a")]),
+                Rule::prec(
+                    Precedence::Integer(1),
+                    Rule::choice(vec![Rule::string("b"), Rule::Blank]),
+                ),
+            ]),
+        ];
+
+        for rule in rules {
+            let result = expand_tokens(ExtractedLexicalGrammar {
+                separators: vec![Rule::pattern("\\s", "")],
+                variables: vec![
+                    Variable::named("ok", Rule::string("ok")),
+                    Variable::named("empty_token", rule.clone()),
+                ],
+            });
+            assert!(
+                matches!(&result, Err(ExpandTokensError::NullableToken(name)) if name == "empty_token"),
+                "expected {rule:?} to be rejected, got {result:?}",
+            );
+        }
+    }
+
+    #[test]
+    fn test_nullable_auxiliary_tokens() {
+        for rule in [
+            Rule::pattern("[^\"]*", ""),
+            Rule::token(Rule::pattern("[^\"]*", "")),
+            Rule::immediate_token(Rule::pattern("[^\"]*", "")),
+        ] {
+            let grammar = expand_tokens(ExtractedLexicalGrammar {
+                separators: vec![],
+                variables: vec![
+                    Variable::auxiliary("string_token1", rule),
+                    Variable::named("quote", Rule::string("\"")),
+                ],
+            })
+            .unwrap();
+            assert_eq!(simulate_nfa(&grammar, "abc\""), Some((0, "abc")));
+        }
+    }
+
+    #[test]
+    fn test_non_nullable_tokens_and_optional_separators() {
+        let rules = [
+            Rule::pattern("a+", ""),
+            Rule::pattern("a*b", ""),
+            Rule::repeat(Rule::seq(vec![
+                Rule::choice(vec![Rule::Blank, Rule::string("a")]),
+                Rule::string("b"),
+            ])),
+            Rule::choice(vec![Rule::string("a"), Rule::pattern("b+", "")]),
+        ];
+
+        for rule in rules {
+            let result = expand_tokens(ExtractedLexicalGrammar {
+                separators: vec![Rule::pattern("\\s*", ""), Rule::string("\\\n")],
+                variables: vec![Variable::named("token", rule.clone())],
+            });
+            assert!(result.is_ok(), "expected {rule:?} to be accepted");
+        }
+    }
+
+    #[test]
+    fn test_recursive_tokens() {
+        // A token that refers to itself directly.
+        let result = expand_tokens(ExtractedLexicalGrammar {
+            separators: vec![],
+            variables: vec![
+                Variable::named("name", Rule::pattern("[a-z]+", "")),
+                Variable::hidden(
+                    "_ident_chain",
+                    Rule::seq(vec![
+                        Rule::Symbol(Symbol::terminal(1)),
+                        Rule::string("."),
+                        Rule::Symbol(Symbol::terminal(0)),
+                    ]),
+                ),
+            ],
+        });
+        let Err(error) = result else {
+            panic!("expected a recursive token error");
+        };
+        assert!(error.to_string().starts_with(
+            "The token `_ident_chain` refers to itself: `_ident_chain -> _ident_chain`."
+        ));
+        assert!(error.to_string().contains("rewriting it with `repeat`"));
+
+        // Tokens that refer to each other through several helper tokens.
+        let result = expand_tokens(ExtractedLexicalGrammar {
+            separators: vec![],
+            variables: vec![
+                Variable::named("name", Rule::pattern("[a-z]+", "")),
+                Variable::hidden(
+                    "_a",
+                    Rule::seq(vec![Rule::Symbol(Symbol::terminal(2)), Rule::string("a")]),
+                ),
+                Variable::hidden(
+                    "_b",
+                    Rule::choice(vec![
+                        Rule::string("b"),
+                        Rule::token(Rule::Symbol(Symbol::terminal(3))),
+                    ]),
+                ),
+                Variable::hidden(
+                    "_c",
+                    Rule::repeat(Rule::seq(vec![
+                        Rule::string("c"),
+                        Rule::Symbol(Symbol::terminal(1)),
+                    ])),
+                ),
+            ],
+        });
+        assert!(matches!(
+            result,
+            Err(ExpandTokensError::RecursiveToken(cycle)) if cycle == ["_a", "_b", "_c", "_a"]
+        ));
+    }
 }
@@ -1,8 +1,62 @@
+use serde::Serialize;
+use thiserror::Error;
+
 use crate::{
     grammars::{LexicalGrammar, SyntaxGrammar},
     rules::{Alias, AliasMap, Symbol, SymbolType},
 };
 
+/// A symbol that always appears aliased, but whose most common aliases are used
+/// equally often, so that the choice of its default alias depends on the order
+/// of the rules in the grammar.
+#[derive(Debug, Error, Serialize)]
+pub struct ConflictingDefaultAliasError {
+    pub symbol: String,
+    pub default_alias: String,
+    pub aliases: Vec<AliasUsage>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AliasUsage {
+    pub alias: String,
+    pub rules: Vec<String>,
+}
+
+impl std::fmt::Display for ConflictingDefaultAliasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Symbol `{}` is aliased as ", self.symbol)?;
+        for (i, usage) in self.aliases.iter().enumerate() {
+            if i > 0 {
+                write!(
+                    f,
+                    "{}",
+                    if i == self.aliases.len() - 1 {
+                        " and "
+                    } else {
+                        ", "
+                    }
+                )?;
+            }
+            write!(
+                f,
+                "`{}` in {}",
+                usage.alias,
+                usage
+                    .rules
+                    .iter()
+                    .map(|rule| format!("`{rule}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+        write!(
+            f,
+            " equally often, so `{}` was chosen as its default alias",
+            self.default_alias
+        )
+    }
+}
+
 #[derive(Clone, Default)]
 struct SymbolStatus {
     aliases: Vec<(Alias, usize)>,
@@ -18,10 +72,14 @@ struct SymbolStatus {
 // * Within an `ERROR` node, no context-specific aliases will be applied. This transformation
 //   ensures that the children of an `ERROR` node have symbols that are consistent with the way that
 //   they would appear in a valid syntax tree.
+//
+// When several aliases are tied for being the most common alias of a symbol, the first one
+// is chosen, and a warning is returned, because reordering the grammar's rules would change
+// the default alias.
 pub(super) fn extract_default_aliases(
     syntax_grammar: &mut SyntaxGrammar,
     lexical_grammar: &LexicalGrammar,
-) -> AliasMap {
+) -> (AliasMap, Vec<ConflictingDefaultAliasError>) {
     let mut terminal_status_list = vec![SymbolStatus::default(); lexical_grammar.variables.len()];
     let mut non_terminal_status_list =
         vec![SymbolStatus::default(); syntax_grammar.variables.len()];
@@ -97,8 +155,19 @@ pub(super) fn extract_default_aliases(
     // and designate that alias as the symbol's "default alias". Store all of these
     // default aliases in a map that will be returned.
     let mut result = AliasMap::new();
+    let mut conflicts = Vec::new();
     for (symbol, status) in symbols_with_statuses {
-        if status.appears_unaliased {
+        // An alias given to an external token in the grammar's `externals` list is always
+        // used as that token's default alias.
+        if let Some(alias) = symbol
+            .is_external()
+            .then(|| syntax_grammar.external_tokens[symbol.index].alias.as_ref())
+            .flatten()
+        {
+            status.aliases.clear();
+            status.aliases.push((alias.clone(), 0));
+            result.insert(symbol, alias.clone());
+        } else if status.appears_unaliased {
             status.aliases.clear();
         } else if let Some(default_entry) = status
             .aliases
@@ -107,6 +176,25 @@ pub(super) fn extract_default_aliases(
             .max_by_key(|(i, (_, count))| (count, -(*i as i64)))
             .map(|(_, entry)| entry.clone())
         {
+            let tied_aliases = status
+                .aliases
+                .iter()
+                .filter(|(_, count)| *count == default_entry.1)
+                .map(|(alias, _)| alias)
+                .collect::<Vec<_>>();
+            if tied_aliases.len() > 1 {
+                conflicts.push(ConflictingDefaultAliasError {
+                    symbol: symbol_name(symbol, syntax_grammar, lexical_grammar).to_string(),
+                    default_alias: default_entry.0.value.clone(),
+                    aliases: tied_aliases
+                        .into_iter()
+                        .map(|alias| AliasUsage {
+                            alias: alias.value.clone(),
+                            rules: rules_using_alias(syntax_grammar, symbol, alias),
+                        })
+                        .collect(),
+                });
+            }
             status.aliases.clear();
             status.aliases.push(default_entry.clone());
             result.insert(symbol, default_entry.0);
@@ -158,14 +246,47 @@ pub(super) fn extract_default_aliases(
         }
     }
 
-    result
+    (result, conflicts)
+}
+
+fn symbol_name<'a>(
+    symbol: Symbol,
+    syntax_grammar: &'a SyntaxGrammar,
+    lexical_grammar: &'a LexicalGrammar,
+) -> &'a str {
+    match symbol.kind {
+        SymbolType::External => &syntax_grammar.external_tokens[symbol.index].name,
+        SymbolType::NonTerminal => &syntax_grammar.variables[symbol.index].name,
+        SymbolType::Terminal => &lexical_grammar.variables[symbol.index].name,
+        SymbolType::End | SymbolType::EndOfNonTerminalExtra => "end",
+    }
+}
+
+/// Find the names of the rules in which the given symbol appears with the given alias.
+fn rules_using_alias(syntax_grammar: &SyntaxGrammar, symbol: Symbol, alias: &Alias) -> Vec<String> {
+    syntax_grammar
+        .variables
+        .iter()
+        .filter(|variable| {
+            variable.productions.iter().any(|production| {
+                production
+                    .steps
+                    .iter()
+                    .any(|step| step.symbol == symbol && step.alias.as_ref() == Some(alias))
+            })
+        })
+        .map(|variable| variable.name.clone())
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        grammars::{LexicalVariable, Production, ProductionStep, SyntaxVariable, VariableType},
+        grammars::{
+            ExternalToken, LexicalVariable, Production, ProductionStep, SyntaxVariable,
+            VariableType,
+        },
         nfa::Nfa,
     };
 
@@ -242,7 +363,8 @@ mod tests {
             ],
         };
 
-        let default_aliases = extract_default_aliases(&mut syntax_grammar, &lexical_grammar);
+        let (default_aliases, conflicts) =
+            extract_default_aliases(&mut syntax_grammar, &lexical_grammar);
         assert_eq!(default_aliases.len(), 3);
 
         assert_eq!(
@@ -268,6 +390,14 @@ mod tests {
         );
         assert_eq!(default_aliases.get(&Symbol::terminal(1)), None);
 
+        // Token 2 is aliased as "a3" and "a5" once each, so the choice of its default
+        // alias is reported.
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].to_string(),
+            "Symbol `t2` is aliased as `a3` in `v1` and `a5` in `v2` equally often, so `a3` was chosen as its default alias"
+        );
+
         assert_eq!(
             syntax_grammar.variables,
             vec![
@@ -301,4 +431,147 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_extract_conflicting_default_aliases() {
+        let aliased_production = |alias: &str, count: usize| Production {
+            dynamic_precedence: 0,
+            steps: vec![ProductionStep::new(Symbol::terminal(0)).with_alias(alias, true); count],
+        };
+        let variable = |name: &str, productions: Vec<Production>| SyntaxVariable {
+            name: name.to_owned(),
+            kind: VariableType::Named,
+            productions,
+        };
+        let lexical_grammar = LexicalGrammar {
+            nfa: Nfa::new(),
+            variables: vec![LexicalVariable {
+                name: "t0".to_string(),
+                kind: VariableType::Anonymous,
+                implicit_precedence: 0,
+                start_state: 0,
+            }],
+        };
+
+        // A three-way tie, where one of the aliases is used in two different rules.
+        let mut syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                variable("v1", vec![aliased_production("b", 1)]),
+                variable("v2", vec![aliased_production("a", 1)]),
+                variable("v3", vec![aliased_production("c", 1)]),
+                variable("v4", vec![aliased_production("b", 1)]),
+                variable("v5", vec![aliased_production("a", 1)]),
+                variable("v6", vec![aliased_production("c", 1)]),
+            ],
+            ..Default::default()
+        };
+        let (default_aliases, conflicts) =
+            extract_default_aliases(&mut syntax_grammar, &lexical_grammar);
+        assert_eq!(default_aliases[&Symbol::terminal(0)].value, "b");
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].default_alias, "b");
+        assert_eq!(
+            conflicts[0]
+                .aliases
+                .iter()
+                .map(|usage| (usage.alias.as_str(), usage.rules.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("b", vec!["v1".to_string(), "v4".to_string()]),
+                ("a", vec!["v2".to_string(), "v5".to_string()]),
+                ("c", vec!["v3".to_string(), "v6".to_string()]),
+            ]
+        );
+        assert_eq!(
+            conflicts[0].to_string(),
+            "Symbol `t0` is aliased as `b` in `v1`, `v4`, `a` in `v2`, `v5` and `c` in `v3`, `v6` equally often, so `b` was chosen as its default alias"
+        );
+
+        // When one alias is used more often than the others, there is no conflict.
+        let mut syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                variable("v1", vec![aliased_production("a", 1)]),
+                variable("v2", vec![aliased_production("b", 2)]),
+                variable("v3", vec![aliased_production("c", 1)]),
+            ],
+            ..Default::default()
+        };
+        let (default_aliases, conflicts) =
+            extract_default_aliases(&mut syntax_grammar, &lexical_grammar);
+        assert_eq!(default_aliases[&Symbol::terminal(0)].value, "b");
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_extract_aliases_declared_for_external_tokens() {
+        let mut syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                SyntaxVariable {
+                    name: "v1".to_owned(),
+                    kind: VariableType::Named,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: vec![ProductionStep::new(Symbol::external(0))],
+                    }],
+                },
+                SyntaxVariable {
+                    name: "v2".to_owned(),
+                    kind: VariableType::Named,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: vec![
+                            ProductionStep::new(Symbol::external(0)).with_alias("body", false),
+                            ProductionStep::new(Symbol::external(0)).with_alias("content", true),
+                        ],
+                    }],
+                },
+            ],
+            extra_symbols: vec![Symbol::external(0)],
+            external_tokens: vec![ExternalToken {
+                name: "_e0".to_string(),
+                kind: VariableType::Hidden,
+                corresponding_internal_token: None,
+                alias: Some(Alias {
+                    value: "content".to_string(),
+                    is_named: true,
+                }),
+            }],
+            ..Default::default()
+        };
+
+        let lexical_grammar = LexicalGrammar {
+            nfa: Nfa::new(),
+            variables: Vec::new(),
+        };
+
+        let (default_aliases, conflicts) =
+            extract_default_aliases(&mut syntax_grammar, &lexical_grammar);
+        assert!(conflicts.is_empty());
+        assert_eq!(default_aliases.len(), 1);
+        assert_eq!(
+            default_aliases[&Symbol::external(0)],
+            Alias {
+                value: "content".to_string(),
+                is_named: true,
+            }
+        );
+
+        // The declared alias is applied by default, but other aliases still apply in
+        // the rules where they are used.
+        assert_eq!(
+            syntax_grammar.variables[0].productions[0].steps[0].alias,
+            None
+        );
+        assert_eq!(
+            syntax_grammar.variables[1].productions[0].steps[0].alias,
+            Some(Alias {
+                value: "body".to_string(),
+                is_named: false,
+            })
+        );
+        assert_eq!(
+            syntax_grammar.variables[1].productions[0].steps[1].alias,
+            None
+        );
+    }
 }
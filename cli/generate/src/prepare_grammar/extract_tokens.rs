@@ -28,13 +28,54 @@ unless they are used only as the grammar's start rule.
     NonSymbolExternalToken,
     #[error("Non-terminal symbol '{0}' cannot be used as the word token, because its rule is duplicated in '{1}'")]
     NonTerminalWordToken(String, String),
-    #[error("Reserved words must be tokens")]
-    NonTokenReservedWord,
+    #[error("Non-token rule `{rule}` used in extras. Extras must be tokens or the names of rules, but this rule refers to `{symbol}`")]
+    NonTokenExtra { rule: String, symbol: String },
+    #[error(
+        "Reserved word {1} in context `{0}` must be a token that is used elsewhere in the grammar"
+    )]
+    NonTokenReservedWord(String, String),
+}
+
+impl ExtractTokensError {
+    pub(super) const fn code(&self) -> &'static str {
+        match self {
+            Self::EmptyString(_) => "empty-string",
+            Self::ExternalTokenNonTerminal(_) => "external-token-non-terminal",
+            Self::NonSymbolExternalToken => "non-symbol-external-token",
+            Self::NonTerminalWordToken(..) => "non-terminal-word-token",
+            Self::NonTokenExtra { .. } => "non-token-extra",
+            Self::NonTokenReservedWord(..) => "non-token-reserved-word",
+        }
+    }
+
+    pub(super) fn rule(&self) -> Option<&str> {
+        match self {
+            Self::EmptyString(rule)
+            | Self::ExternalTokenNonTerminal(rule)
+            | Self::NonTerminalWordToken(rule, _)
+            | Self::NonTokenExtra { rule, .. } => Some(rule),
+            Self::NonSymbolExternalToken | Self::NonTokenReservedWord(..) => None,
+        }
+    }
 }
 
 pub(super) fn extract_tokens(
     mut grammar: InternedGrammar,
 ) -> ExtractTokensResult<(ExtractedSyntaxGrammar, ExtractedLexicalGrammar)> {
+    // Extras that aren't simply the name of a rule must be made up entirely of
+    // lexical rules, so that they can be extracted as a single token.
+    for rule in &grammar.extra_symbols {
+        if matches!(rule, Rule::Symbol(_)) {
+            continue;
+        }
+        if let Some(symbol) = find_symbol(rule) {
+            Err(ExtractTokensError::NonTokenExtra {
+                rule: display_rule(rule, &grammar),
+                symbol: symbol_name(symbol, &grammar).to_string(),
+            })?;
+        }
+    }
+
     let mut extractor = TokenExtractor {
         current_variable_name: String::new(),
         current_variable_token_count: 0,
@@ -131,7 +172,20 @@ pub(super) fn extract_tokens(
 
     let mut external_tokens = Vec::new();
     for external_token in grammar.external_tokens {
-        let rule = symbol_replacer.replace_symbols_in_rule(&external_token.rule);
+        let mut rule = symbol_replacer.replace_symbols_in_rule(&external_token.rule);
+        let mut alias = None;
+        if let Rule::Metadata {
+            rule: inner,
+            params,
+        } = rule
+        {
+            if params.alias.is_none() {
+                Err(ExtractTokensError::NonSymbolExternalToken)?;
+            }
+            alias = params.alias;
+            rule = *inner;
+        }
+
         if let Rule::Symbol(symbol) = rule {
             if symbol.is_non_terminal() {
                 Err(ExtractTokensError::ExternalTokenNonTerminal(
@@ -144,12 +198,14 @@ pub(super) fn extract_tokens(
                     name: external_token.name,
                     kind: external_token.kind,
                     corresponding_internal_token: None,
+                    alias,
                 });
             } else {
                 external_tokens.push(ExternalToken {
                     name: lexical_variables[symbol.index].name.clone(),
                     kind: external_token.kind,
                     corresponding_internal_token: Some(symbol),
+                    alias,
                 });
             }
         } else {
@@ -157,8 +213,8 @@ pub(super) fn extract_tokens(
         }
     }
 
-    let mut word_token = None;
-    if let Some(token) = grammar.word_token {
+    let mut word_tokens = Vec::with_capacity(grammar.word_tokens.len());
+    for token in grammar.word_tokens {
         let token = symbol_replacer.replace_symbol(token);
         if token.is_non_terminal() {
             let word_token_variable = &variables[token.index];
@@ -173,7 +229,9 @@ pub(super) fn extract_tokens(
                 conflicting_variable.1.name.clone(),
             ))?;
         }
-        word_token = Some(token);
+        if !word_tokens.contains(&token) {
+            word_tokens.push(token);
+        }
     }
 
     let mut reserved_word_contexts = Vec::new();
@@ -188,7 +246,14 @@ pub(super) fn extract_tokens(
             {
                 reserved_words.push(Symbol::terminal(index));
             } else {
-                Err(ExtractTokensError::NonTokenReservedWord)?;
+                Err(ExtractTokensError::NonTokenReservedWord(
+                    reserved_word_context.name.clone(),
+                    match &reserved_rule {
+                        Rule::String(value) => format!("'{value}'"),
+                        Rule::Pattern(value, flags) => format!("/{value}/{flags}"),
+                        _ => format!("{reserved_rule:?}"),
+                    },
+                ))?;
             }
         }
         reserved_word_contexts.push(ReservedWordContext {
@@ -205,7 +270,7 @@ pub(super) fn extract_tokens(
             variables_to_inline,
             supertype_symbols,
             external_tokens,
-            word_token,
+            word_tokens,
             precedence_orderings: grammar.precedence_orderings,
             reserved_word_sets: reserved_word_contexts,
         },
@@ -216,6 +281,72 @@ pub(super) fn extract_tokens(
     ))
 }
 
+/// Find the first symbol that a rule refers to.
+fn find_symbol(rule: &Rule) -> Option<Symbol> {
+    match rule {
+        Rule::Symbol(symbol) => Some(*symbol),
+        Rule::Choice(elements) | Rule::Seq(elements) => elements.iter().find_map(find_symbol),
+        Rule::Metadata { rule, .. } | Rule::Reserved { rule, .. } | Rule::Repeat(rule) => {
+            find_symbol(rule)
+        }
+        _ => None,
+    }
+}
+
+fn symbol_name(symbol: Symbol, grammar: &InternedGrammar) -> &str {
+    match symbol.kind {
+        SymbolType::External => &grammar.external_tokens[symbol.index].name,
+        _ => &grammar.variables[symbol.index].name,
+    }
+}
+
+/// Render a rule in the syntax of the grammar DSL, for use in error messages.
+fn display_rule(rule: &Rule, grammar: &InternedGrammar) -> String {
+    let display_all = |elements: &[Rule]| {
+        elements
+            .iter()
+            .map(|element| display_rule(element, grammar))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match rule {
+        Rule::Blank => "blank()".to_string(),
+        Rule::String(value) => format!("{value:?}"),
+        Rule::Pattern(value, flags) => format!("/{value}/{flags}"),
+        Rule::NamedSymbol(name) => format!("$.{name}"),
+        Rule::Symbol(symbol) => format!("$.{}", symbol_name(*symbol, grammar)),
+        Rule::Choice(elements) => format!("choice({})", display_all(elements)),
+        Rule::Seq(elements) => format!("seq({})", display_all(elements)),
+        Rule::Repeat(rule) => format!("repeat1({})", display_rule(rule, grammar)),
+        Rule::Reserved { rule, context_name } => {
+            format!(
+                "reserved({context_name:?}, {})",
+                display_rule(rule, grammar)
+            )
+        }
+        Rule::Metadata { params, rule } => {
+            let content = display_rule(rule, grammar);
+            if let Some(alias) = &params.alias {
+                if alias.is_named {
+                    format!("alias({content}, $.{})", alias.value)
+                } else {
+                    format!("alias({content}, {:?})", alias.value)
+                }
+            } else if let Some(field_name) = &params.field_name {
+                format!("field({field_name:?}, {content})")
+            } else if params.is_case_insensitive {
+                format!("caseInsensitive({content})")
+            } else if params.is_main_token {
+                format!("token.immediate({content})")
+            } else if params.is_token {
+                format!("token({content})")
+            } else {
+                content
+            }
+        }
+    }
+}
+
 struct TokenExtractor {
     current_variable_name: String,
     current_variable_token_count: usize,
@@ -263,6 +394,34 @@ impl TokenExtractor {
                     };
 
                     Ok(self.extract_token(rule_to_extract, string_value)?.into())
+                } else if params.is_case_insensitive {
+                    // A case-insensitive string is extracted as a token named after
+                    // its canonical spelling, leaving any other metadata, such as an
+                    // alias or a field name, in the syntax grammar.
+                    let mut params = params.clone();
+                    params.is_case_insensitive = false;
+
+                    let mut string_value = None;
+                    if let Rule::String(value) = rule.as_ref() {
+                        string_value = Some(value);
+                    }
+
+                    let token = Rule::Metadata {
+                        params: MetadataParams {
+                            is_case_insensitive: true,
+                            ..Default::default()
+                        },
+                        rule: rule.clone(),
+                    };
+                    let symbol = self.extract_token(&token, string_value)?;
+                    if params == MetadataParams::default() {
+                        Ok(symbol.into())
+                    } else {
+                        Ok(Rule::Metadata {
+                            params,
+                            rule: Box::new(symbol.into()),
+                        })
+                    }
                 } else {
                     Ok(Rule::Metadata {
                         params: params.clone(),
@@ -387,6 +546,7 @@ impl SymbolReplacer {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::rules::Alias;
 
     #[test]
     fn test_extraction() {
@@ -495,6 +655,81 @@ mod test {
         assert_eq!(lexical_grammar.separators, vec![Rule::string(" "),]);
     }
 
+    #[test]
+    fn test_extract_case_insensitive_strings() {
+        let (syntax_grammar, lexical_grammar) =
+            extract_tokens(build_grammar(vec![Variable::named(
+                "rule_0",
+                Rule::seq(vec![
+                    Rule::case_insensitive("select".to_string()),
+                    Rule::field(
+                        "keyword".to_string(),
+                        Rule::case_insensitive("select".to_string()),
+                    ),
+                    Rule::string("select"),
+                ]),
+            )]))
+            .unwrap();
+
+        assert_eq!(
+            syntax_grammar.variables,
+            vec![Variable::named(
+                "rule_0",
+                Rule::seq(vec![
+                    Rule::terminal(0),
+                    Rule::field("keyword".to_string(), Rule::terminal(0)),
+                    Rule::terminal(1),
+                ])
+            )]
+        );
+        assert_eq!(
+            lexical_grammar.variables,
+            vec![
+                Variable::anonymous("select", Rule::case_insensitive("select".to_string())),
+                Variable::anonymous("select", Rule::string("select")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_on_non_token_extras() {
+        let grammar = |extra: Rule| {
+            let mut grammar = build_grammar(vec![
+                Variable::named("rule_0", Rule::repeat(Rule::non_terminal(1))),
+                Variable::named("rule_1", Rule::pattern("[a-z]+", "")),
+                Variable::hidden(
+                    "_rule_2",
+                    Rule::seq(vec![Rule::string("a"), Rule::non_terminal(1)]),
+                ),
+            ]);
+            grammar.extra_symbols = vec![Rule::string(" "), extra];
+            grammar
+        };
+
+        let error = extract_tokens(grammar(Rule::seq(vec![
+            Rule::string("#"),
+            Rule::non_terminal(1),
+        ])))
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Non-token rule `seq(\"#\", $.rule_1)` used in extras. Extras must be tokens or the names of rules, but this rule refers to `rule_1`"
+        );
+
+        // The error refers to the extras entry, not to the hidden rule that it contains.
+        let error = extract_tokens(grammar(Rule::choice(vec![
+            Rule::token(Rule::pattern("#.*", "")),
+            Rule::non_terminal(2),
+        ])))
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Non-token rule `choice(token(/#.*/), $._rule_2)` used in extras. Extras must be tokens or the names of rules, but this rule refers to `_rule_2`"
+        );
+    }
+
     #[test]
     fn test_extract_externals() {
         let mut grammar = build_grammar(vec![
@@ -525,16 +760,63 @@ mod test {
                     name: "external_0".to_string(),
                     kind: VariableType::Named,
                     corresponding_internal_token: None,
+                    alias: None,
                 },
                 ExternalToken {
                     name: "a".to_string(),
                     kind: VariableType::Anonymous,
                     corresponding_internal_token: Some(Symbol::terminal(0)),
+                    alias: None,
                 },
                 ExternalToken {
                     name: "rule_2".to_string(),
                     kind: VariableType::Named,
                     corresponding_internal_token: Some(Symbol::terminal(2)),
+                    alias: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_aliased_externals() {
+        let mut grammar = build_grammar(vec![Variable::named(
+            "rule_0",
+            Rule::seq(vec![Rule::external(0), Rule::external(1)]),
+        )]);
+        grammar.external_tokens = vec![
+            Variable::hidden(
+                "_external_0",
+                Rule::alias(Rule::external(0), "content".to_string(), true),
+            ),
+            Variable::hidden(
+                "_external_1",
+                Rule::alias(Rule::external(1), "body".to_string(), false),
+            ),
+        ];
+
+        let (syntax_grammar, _) = extract_tokens(grammar).unwrap();
+
+        assert_eq!(
+            syntax_grammar.external_tokens,
+            vec![
+                ExternalToken {
+                    name: "_external_0".to_string(),
+                    kind: VariableType::Hidden,
+                    corresponding_internal_token: None,
+                    alias: Some(Alias {
+                        value: "content".to_string(),
+                        is_named: true,
+                    }),
+                },
+                ExternalToken {
+                    name: "_external_1".to_string(),
+                    kind: VariableType::Hidden,
+                    corresponding_internal_token: None,
+                    alias: Some(Alias {
+                        value: "body".to_string(),
+                        is_named: false,
+                    }),
                 },
             ]
         );
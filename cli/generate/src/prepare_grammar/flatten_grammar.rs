@@ -8,6 +8,7 @@ use super::ExtractedSyntaxGrammar;
 use crate::{
     grammars::{
         Production, ProductionStep, ReservedWordSetId, SyntaxGrammar, SyntaxVariable, Variable,
+        VariableType,
     },
     rules::{Alias, Associativity, Precedence, Rule, Symbol, TokenSet},
 };
@@ -30,6 +31,46 @@ unless they are used only as the grammar's start rule.
     RecursiveInline(String),
 }
 
+impl FlattenGrammarError {
+    pub(super) const fn code(&self) -> &'static str {
+        match self {
+            Self::NoReservedWordSet(_) => "undefined-reserved-word-set",
+            Self::EmptyString(_) => "empty-string",
+            Self::RecursiveInline(_) => "recursive-inline",
+        }
+    }
+
+    pub(super) fn rule(&self) -> Option<&str> {
+        match self {
+            Self::NoReservedWordSet(_) => None,
+            Self::EmptyString(rule) | Self::RecursiveInline(rule) => Some(rule),
+        }
+    }
+}
+
+/// A cycle of rules that can never match, because each of their productions refers to
+/// another rule in the cycle. The names in `cycle` start and end with the same rule.
+#[derive(Debug, Error, Serialize)]
+pub struct InfiniteRecursionError {
+    pub cycle: Vec<String>,
+}
+
+impl std::fmt::Display for InfiniteRecursionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The rule `{}` can never match, because every alternative refers back to it: `{}`.
+
+Each rule needs at least one alternative that doesn't lead back to itself. Rewrite the rule
+with `repeat`, such as `seq($.name, repeat(seq('.', $.name)))` instead of
+`seq($._chain, '.', $.name)`, or add a non-recursive alternative using `choice`.
+",
+            self.cycle[0],
+            self.cycle.join(" -> ")
+        )
+    }
+}
+
 struct RuleFlattener {
     production: Production,
     reserved_word_set_ids: HashMap<String, ReservedWordSetId>,
@@ -232,6 +273,65 @@ fn extract_choices(rule: Rule) -> Vec<Rule> {
     }
 }
 
+/// Find a cycle of rules that can never match, because each of their productions refers
+/// to another rule in the cycle.
+///
+/// Such rules don't prevent a parser from being generated, so the cycle is returned so that
+/// it can be reported as a warning.
+pub(super) fn find_infinite_recursion(grammar: &SyntaxGrammar) -> Option<InfiniteRecursionError> {
+    let variables = &grammar.variables;
+    // A rule can match if one of its productions consists only of tokens and rules that
+    // can match.
+    let mut can_match = vec![false; variables.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (i, variable) in variables.iter().enumerate() {
+            if !can_match[i]
+                && variable.productions.iter().any(|production| {
+                    production
+                        .steps
+                        .iter()
+                        .all(|step| !step.symbol.is_non_terminal() || can_match[step.symbol.index])
+                })
+            {
+                can_match[i] = true;
+                changed = true;
+            }
+        }
+    }
+
+    // Start from a rule that was written in the grammar rather than an auxiliary rule, and
+    // follow references to other rules that can't match until one of them repeats.
+    let mut path = vec![(0..variables.len())
+        .find(|i| !can_match[*i] && variables[*i].kind != VariableType::Auxiliary)?];
+    loop {
+        let current = *path.last().unwrap();
+        let next = variables[current]
+            .productions
+            .iter()
+            .flat_map(|production| &production.steps)
+            .filter(|step| step.symbol.is_non_terminal() && !can_match[step.symbol.index])
+            .map(|step| step.symbol.index)
+            .min_by_key(|i| {
+                (
+                    !path.contains(i),
+                    variables[*i].kind == VariableType::Auxiliary,
+                )
+            })?;
+        if let Some(start) = path.iter().position(|i| *i == next) {
+            return Some(InfiniteRecursionError {
+                cycle: path[start..]
+                    .iter()
+                    .chain([&next])
+                    .map(|i| variables[*i].name.clone())
+                    .collect(),
+            });
+        }
+        path.push(next);
+    }
+}
+
 fn symbol_is_used(variables: &[SyntaxVariable], symbol: Symbol) -> bool {
     for variable in variables {
         for production in &variable.productions {
@@ -293,7 +393,7 @@ pub(super) fn flatten_grammar(
         precedence_orderings: grammar.precedence_orderings,
         external_tokens: grammar.external_tokens,
         supertype_symbols: grammar.supertype_symbols,
-        word_token: grammar.word_token,
+        word_tokens: grammar.word_tokens,
         reserved_word_sets,
         variables,
     })
@@ -518,7 +618,7 @@ mod tests {
             precedence_orderings: Vec::new(),
             external_tokens: Vec::new(),
             supertype_symbols: Vec::new(),
-            word_token: None,
+            word_tokens: Vec::new(),
             reserved_word_sets: Vec::new(),
             variables: vec![Variable {
                 name: "test".to_string(),
@@ -536,4 +636,84 @@ mod tests {
             "Rule `test` cannot be inlined because it contains a reference to itself.",
         );
     }
+
+    #[test]
+    fn test_find_infinite_recursion() {
+        let find = |variables: Vec<Variable>| {
+            let grammar = flatten_grammar(ExtractedSyntaxGrammar {
+                extra_symbols: Vec::new(),
+                expected_conflicts: Vec::new(),
+                variables_to_inline: Vec::new(),
+                precedence_orderings: Vec::new(),
+                external_tokens: Vec::new(),
+                supertype_symbols: Vec::new(),
+                word_tokens: Vec::new(),
+                reserved_word_sets: Vec::new(),
+                variables,
+            })
+            .unwrap();
+            find_infinite_recursion(&grammar)
+        };
+
+        // A rule that refers to itself directly, without any other alternative.
+        let error = find(vec![
+            Variable::named("program", Rule::non_terminal(1)),
+            Variable::hidden(
+                "_ident_chain",
+                Rule::seq(vec![
+                    Rule::non_terminal(1),
+                    Rule::terminal(0),
+                    Rule::terminal(1),
+                ]),
+            ),
+        ])
+        .unwrap();
+        assert!(error.to_string().starts_with(
+            "The rule `_ident_chain` can never match, because every alternative refers back to it: `_ident_chain -> _ident_chain`."
+        ));
+
+        // Rules that refer to each other through several helper rules.
+        let error = find(vec![
+            Variable::named(
+                "program",
+                Rule::choice(vec![Rule::non_terminal(1), Rule::terminal(0)]),
+            ),
+            Variable::hidden(
+                "_a",
+                Rule::seq(vec![Rule::non_terminal(2), Rule::terminal(0)]),
+            ),
+            Variable::hidden(
+                "_b",
+                Rule::choice(vec![
+                    Rule::non_terminal(3),
+                    Rule::seq(vec![Rule::terminal(1), Rule::non_terminal(3)]),
+                ]),
+            ),
+            Variable::hidden(
+                "_c",
+                Rule::seq(vec![Rule::terminal(1), Rule::non_terminal(1)]),
+            ),
+        ])
+        .unwrap();
+        assert!(error
+            .to_string()
+            .contains("refers back to it: `_a -> _b -> _c -> _a`."));
+
+        // Recursive rules are fine as long as one alternative doesn't lead back to the rule.
+        let error = find(vec![
+            Variable::named("program", Rule::non_terminal(1)),
+            Variable::hidden(
+                "_list",
+                Rule::choice(vec![
+                    Rule::seq(vec![
+                        Rule::non_terminal(1),
+                        Rule::terminal(0),
+                        Rule::terminal(1),
+                    ]),
+                    Rule::terminal(1),
+                ]),
+            ),
+        ]);
+        assert!(error.is_none());
+    }
 }
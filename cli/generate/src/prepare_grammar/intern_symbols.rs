@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use anyhow::Result;
 use serde::Serialize;
 use thiserror::Error;
@@ -14,19 +16,93 @@ pub type InternSymbolsResult<T> = Result<T, InternSymbolsError>;
 pub enum InternSymbolsError {
     #[error("A grammar's start rule must be visible.")]
     HiddenStartRule,
-    #[error("Undefined symbol `{0}`")]
-    Undefined(String),
-    #[error("Undefined symbol `{0}` in grammar's supertypes array")]
-    UndefinedSupertype(String),
-    #[error("Undefined symbol `{0}` in grammar's conflicts array")]
-    UndefinedConflict(String),
-    #[error("Undefined symbol `{0}` as grammar's word token")]
-    UndefinedWordToken(String),
+    #[error("Undefined symbol `{symbol}`{suggestions}")]
+    Undefined {
+        symbol: String,
+        suggestions: Suggestions,
+    },
+    #[error("Undefined symbol `{symbol}` in grammar's supertypes array{suggestions}")]
+    UndefinedSupertype {
+        symbol: String,
+        suggestions: Suggestions,
+    },
+    #[error("Undefined symbol `{symbol}` in grammar's conflicts array{suggestions}")]
+    UndefinedConflict {
+        symbol: String,
+        suggestions: Suggestions,
+    },
+    #[error("Undefined symbol `{symbol}` as grammar's word token{suggestions}")]
+    UndefinedWordToken {
+        symbol: String,
+        suggestions: Suggestions,
+    },
+    #[error("Rule `{name}` is defined more than once, at indices {first_index} and {second_index} of the grammar's rules")]
+    DuplicateVariable {
+        name: String,
+        first_index: usize,
+        second_index: usize,
+    },
+    #[error("External token `{name}` is declared more than once, at indices {first_index} and {second_index} of the grammar's externals array")]
+    DuplicateExternalToken {
+        name: String,
+        first_index: usize,
+        second_index: usize,
+    },
+    #[error("Reserved word set `{name}` has the same name as the rule at index {variable_index}. Reserved word sets must have names that are distinct from the grammar's rules")]
+    ReservedWordSetNamedAfterVariable { name: String, variable_index: usize },
+}
+
+impl InternSymbolsError {
+    pub(super) const fn code(&self) -> &'static str {
+        match self {
+            Self::HiddenStartRule => "hidden-start-rule",
+            Self::Undefined { .. } => "undefined-symbol",
+            Self::UndefinedSupertype { .. } => "undefined-supertype",
+            Self::UndefinedConflict { .. } => "undefined-conflict-symbol",
+            Self::UndefinedWordToken { .. } => "undefined-word-token",
+            Self::DuplicateVariable { .. } => "duplicate-rule",
+            Self::DuplicateExternalToken { .. } => "duplicate-external-token",
+            Self::ReservedWordSetNamedAfterVariable { .. } => "reserved-word-set-named-after-rule",
+        }
+    }
+
+    pub(super) fn rule(&self) -> Option<&str> {
+        match self {
+            Self::DuplicateVariable { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// The defined names that are closest to an undefined symbol, ordered
+/// from the most to the least similar.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct Suggestions(pub Vec<String>);
+
+impl std::fmt::Display for Suggestions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        write!(f, ". Did you mean ")?;
+        for (i, name) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", if i == self.0.len() - 1 { " or " } else { ", " })?;
+            }
+            write!(f, "`{name}`")?;
+        }
+        write!(f, "?")
+    }
 }
 
+const MAX_SUGGESTIONS: usize = 3;
+
 pub(super) fn intern_symbols(grammar: &InputGrammar) -> InternSymbolsResult<InternedGrammar> {
     let interner = Interner { grammar };
 
+    check_duplicate_names(grammar)?;
+
     if variable_type_for_name(&grammar.variables[0].name) == VariableType::Hidden {
         Err(InternSymbolsError::HiddenStartRule)?;
     }
@@ -43,8 +119,8 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> InternSymbolsResult<Inte
     let mut external_tokens = Vec::with_capacity(grammar.external_tokens.len());
     for external_token in &grammar.external_tokens {
         let rule = interner.intern_rule(external_token, None)?;
-        let (name, kind) = if let Rule::NamedSymbol(name) = external_token {
-            (name.clone(), variable_type_for_name(name))
+        let (name, kind) = if let Some(name) = external_token_name(external_token) {
+            (name.to_string(), variable_type_for_name(name))
         } else {
             (String::new(), VariableType::Anonymous)
         };
@@ -59,7 +135,10 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> InternSymbolsResult<Inte
     let mut supertype_symbols = Vec::with_capacity(grammar.supertype_symbols.len());
     for supertype_symbol_name in &grammar.supertype_symbols {
         supertype_symbols.push(interner.intern_name(supertype_symbol_name).ok_or_else(|| {
-            InternSymbolsError::UndefinedSupertype(supertype_symbol_name.clone())
+            InternSymbolsError::UndefinedSupertype {
+                symbol: supertype_symbol_name.clone(),
+                suggestions: interner.suggestions(supertype_symbol_name),
+            }
         })?);
     }
 
@@ -79,11 +158,12 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> InternSymbolsResult<Inte
     for conflict in &grammar.expected_conflicts {
         let mut interned_conflict = Vec::with_capacity(conflict.len());
         for name in conflict {
-            interned_conflict.push(
-                interner
-                    .intern_name(name)
-                    .ok_or_else(|| InternSymbolsError::UndefinedConflict(name.clone()))?,
-            );
+            interned_conflict.push(interner.intern_name(name).ok_or_else(|| {
+                InternSymbolsError::UndefinedConflict {
+                    symbol: name.clone(),
+                    suggestions: interner.suggestions(name),
+                }
+            })?);
         }
         expected_conflicts.push(interned_conflict);
     }
@@ -95,13 +175,14 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> InternSymbolsResult<Inte
         }
     }
 
-    let mut word_token = None;
-    if let Some(name) = grammar.word_token.as_ref() {
-        word_token = Some(
-            interner
-                .intern_name(name)
-                .ok_or_else(|| InternSymbolsError::UndefinedWordToken(name.clone()))?,
-        );
+    let mut word_tokens = Vec::with_capacity(grammar.word_tokens.len());
+    for name in &grammar.word_tokens {
+        word_tokens.push(interner.intern_name(name).ok_or_else(|| {
+            InternSymbolsError::UndefinedWordToken {
+                symbol: name.clone(),
+                suggestions: interner.suggestions(name),
+            }
+        })?);
     }
 
     for (i, variable) in variables.iter_mut().enumerate() {
@@ -117,12 +198,53 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> InternSymbolsResult<Inte
         expected_conflicts,
         variables_to_inline,
         supertype_symbols,
-        word_token,
+        word_tokens,
         precedence_orderings: grammar.precedence_orderings.clone(),
         reserved_word_sets: reserved_words,
     })
 }
 
+/// Check that each rule and each external token is defined only once, and that no
+/// reserved word set shares its name with a rule. An external token may have the same
+/// name as a rule, since that is how an external scanner falls back to an internal token.
+fn check_duplicate_names(grammar: &InputGrammar) -> InternSymbolsResult<()> {
+    let mut variable_indices = HashMap::new();
+    for (second_index, variable) in grammar.variables.iter().enumerate() {
+        if let Some(first_index) = variable_indices.insert(variable.name.as_str(), second_index) {
+            Err(InternSymbolsError::DuplicateVariable {
+                name: variable.name.clone(),
+                first_index,
+                second_index,
+            })?;
+        }
+    }
+
+    let mut external_token_indices = HashMap::new();
+    for (second_index, external_token) in grammar.external_tokens.iter().enumerate() {
+        let Some(name) = external_token_name(external_token) else {
+            continue;
+        };
+        if let Some(first_index) = external_token_indices.insert(name, second_index) {
+            Err(InternSymbolsError::DuplicateExternalToken {
+                name: name.to_string(),
+                first_index,
+                second_index,
+            })?;
+        }
+    }
+
+    for reserved_word_set in &grammar.reserved_words {
+        if let Some(variable_index) = variable_indices.get(reserved_word_set.name.as_str()) {
+            Err(InternSymbolsError::ReservedWordSetNamedAfterVariable {
+                name: reserved_word_set.name.clone(),
+                variable_index: *variable_index,
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
 struct Interner<'a> {
     grammar: &'a InputGrammar,
 }
@@ -156,7 +278,12 @@ impl Interner<'_> {
                 context_name: context_name.clone(),
             }),
             Rule::NamedSymbol(name) => self.intern_name(name).map_or_else(
-                || Err(InternSymbolsError::Undefined(name.clone())),
+                || {
+                    Err(InternSymbolsError::Undefined {
+                        symbol: name.clone(),
+                        suggestions: self.suggestions(name),
+                    })
+                },
                 |symbol| Ok(Rule::Symbol(symbol)),
             ),
             _ => Ok(rule.clone()),
@@ -171,16 +298,52 @@ impl Interner<'_> {
         }
 
         for (i, external_token) in self.grammar.external_tokens.iter().enumerate() {
-            if let Rule::NamedSymbol(name) = external_token {
-                if name == symbol {
-                    return Some(Symbol::external(i));
-                }
+            if external_token_name(external_token) == Some(symbol) {
+                return Some(Symbol::external(i));
             }
         }
 
         None
     }
 
+    /// Find the names of the variables, external tokens, and reserved word
+    /// contexts that are most similar to the given undefined symbol.
+    fn suggestions(&self, symbol: &str) -> Suggestions {
+        let length = symbol.chars().count();
+        let max_distance = (length / 3).max(1);
+        let mut candidates = self
+            .grammar
+            .variables
+            .iter()
+            .map(|variable| variable.name.as_str())
+            .chain(
+                self.grammar
+                    .external_tokens
+                    .iter()
+                    .filter_map(external_token_name),
+            )
+            .chain(
+                self.grammar
+                    .reserved_words
+                    .iter()
+                    .map(|context| context.name.as_str()),
+            )
+            .filter_map(|name| {
+                let distance = edit_distance(symbol, name);
+                (distance <= max_distance && distance < length).then_some((distance, name))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+        candidates.dedup_by_key(|(_, name)| *name);
+        Suggestions(
+            candidates
+                .into_iter()
+                .take(MAX_SUGGESTIONS)
+                .map(|(_, name)| name.to_string())
+                .collect(),
+        )
+    }
+
     // In the case of a seq or choice rule of 1 element in a hidden rule, weird
     // inconsistent behavior with queries can occur. So we should warn the user about it.
     fn check_single(&self, elements: &[Rule], name: Option<&str>) {
@@ -193,6 +356,32 @@ impl Interner<'_> {
     }
 }
 
+/// Compute the Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut previous_diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous_diagonal + usize::from(a_char != *b_char);
+            previous_diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Get the name of an external token, looking through an alias that is applied
+/// to it in the grammar's `externals` list.
+fn external_token_name(rule: &Rule) -> Option<&str> {
+    match rule {
+        Rule::NamedSymbol(name) => Some(name),
+        Rule::Metadata { rule, params } if params.alias.is_some() => external_token_name(rule),
+        _ => None,
+    }
+}
+
 fn variable_type_for_name(name: &str) -> VariableType {
     if name.starts_with('_') {
         VariableType::Hidden
@@ -283,6 +472,107 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_grammar_with_misspelled_symbols() {
+        let mut input_grammar = build_grammar(vec![
+            Variable::named("program", Rule::repeat(Rule::named("statment"))),
+            Variable::named(
+                "statement",
+                Rule::choice(vec![Rule::named("statements"), Rule::named("expression")]),
+            ),
+            Variable::named("statements", Rule::string("a")),
+            Variable::named("expression", Rule::string("b")),
+        ]);
+        input_grammar.external_tokens.push(Rule::named("_statment"));
+        input_grammar.reserved_words.push(ReservedWordContext {
+            name: "statmnt".to_string(),
+            reserved_words: Vec::new(),
+        });
+
+        let error = intern_symbols(&input_grammar).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Undefined symbol `statment`. Did you mean `_statment`, `statement` or `statmnt`?"
+        );
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "Undefined": {
+                    "symbol": "statment",
+                    "suggestions": ["_statment", "statement", "statmnt"],
+                }
+            })
+        );
+
+        input_grammar.variables[0].rule = Rule::named("statement");
+        input_grammar.expected_conflicts = vec![vec!["expresion".to_string()]];
+        let error = intern_symbols(&input_grammar).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Undefined symbol `expresion` in grammar's conflicts array. Did you mean `expression`?"
+        );
+
+        input_grammar.expected_conflicts = vec![vec!["completely_unrelated".to_string()]];
+        let error = intern_symbols(&input_grammar).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Undefined symbol `completely_unrelated` in grammar's conflicts array"
+        );
+    }
+
+    #[test]
+    fn test_grammar_with_duplicate_names() {
+        let mut input_grammar = build_grammar(vec![
+            Variable::named("program", Rule::repeat(Rule::named("statement"))),
+            Variable::named("statement", Rule::string("a")),
+            Variable::named("statement", Rule::string("b")),
+        ]);
+        let error = intern_symbols(&input_grammar).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Rule `statement` is defined more than once, at indices 1 and 2 of the grammar's rules"
+        );
+
+        input_grammar.variables.pop();
+        input_grammar.external_tokens = vec![
+            Rule::named("statement"),
+            Rule::named("_heredoc_body"),
+            Rule::alias(Rule::named("_heredoc_body"), "body".to_string(), true),
+        ];
+        let error = intern_symbols(&input_grammar).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "External token `_heredoc_body` is declared more than once, at indices 1 and 2 of the grammar's externals array"
+        );
+
+        input_grammar.external_tokens.pop();
+        input_grammar.reserved_words.push(ReservedWordContext {
+            name: "statement".to_string(),
+            reserved_words: Vec::new(),
+        });
+        let error = intern_symbols(&input_grammar).err().unwrap();
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "ReservedWordSetNamedAfterVariable": {
+                    "name": "statement",
+                    "variable_index": 1,
+                }
+            })
+        );
+
+        input_grammar.reserved_words[0].name = "global".to_string();
+        intern_symbols(&input_grammar).unwrap();
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("stament", "statement"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
     fn build_grammar(variables: Vec<Variable>) -> InputGrammar {
         InputGrammar {
             variables,
@@ -14,8 +14,9 @@ use std::{
 
 use anyhow::Result;
 pub use expand_tokens::ExpandTokensError;
+pub use extract_default_aliases::ConflictingDefaultAliasError;
 pub use extract_tokens::ExtractTokensError;
-pub use flatten_grammar::FlattenGrammarError;
+pub use flatten_grammar::{FlattenGrammarError, InfiniteRecursionError};
 pub use intern_symbols::InternSymbolsError;
 pub use process_inlines::ProcessInlinesError;
 use serde::Serialize;
@@ -23,36 +24,40 @@ use thiserror::Error;
 
 pub use self::expand_tokens::expand_tokens;
 use self::{
-    expand_repeats::expand_repeats, extract_default_aliases::extract_default_aliases,
-    extract_tokens::extract_tokens, flatten_grammar::flatten_grammar,
-    intern_symbols::intern_symbols, process_inlines::process_inlines,
+    expand_repeats::expand_repeats,
+    extract_default_aliases::extract_default_aliases,
+    extract_tokens::extract_tokens,
+    flatten_grammar::{find_infinite_recursion, flatten_grammar},
+    intern_symbols::intern_symbols,
+    process_inlines::process_inlines,
 };
 use super::{
     grammars::{
         ExternalToken, InlinedProductionMap, InputGrammar, LexicalGrammar, PrecedenceEntry,
-        SyntaxGrammar, Variable,
+        SyntaxGrammar, Variable, VariableType,
     },
-    rules::{AliasMap, Precedence, Rule, Symbol},
+    rules::{alias_map, AliasMap, Precedence, Rule, Symbol, SymbolType},
 };
-use crate::grammars::ReservedWordContext;
+use crate::{grammars::ReservedWordContext, stats::GrammarStats, timing::TimingReport};
 
+#[derive(Clone, Serialize)]
 pub struct IntermediateGrammar<T, U> {
-    variables: Vec<Variable>,
-    extra_symbols: Vec<T>,
-    expected_conflicts: Vec<Vec<Symbol>>,
-    precedence_orderings: Vec<Vec<PrecedenceEntry>>,
-    external_tokens: Vec<U>,
-    variables_to_inline: Vec<Symbol>,
-    supertype_symbols: Vec<Symbol>,
-    word_token: Option<Symbol>,
-    reserved_word_sets: Vec<ReservedWordContext<T>>,
+    pub variables: Vec<Variable>,
+    pub extra_symbols: Vec<T>,
+    pub expected_conflicts: Vec<Vec<Symbol>>,
+    pub precedence_orderings: Vec<Vec<PrecedenceEntry>>,
+    pub external_tokens: Vec<U>,
+    pub variables_to_inline: Vec<Symbol>,
+    pub supertype_symbols: Vec<Symbol>,
+    pub word_tokens: Vec<Symbol>,
+    pub reserved_word_sets: Vec<ReservedWordContext<T>>,
 }
 
 pub type InternedGrammar = IntermediateGrammar<Rule, Variable>;
 
 pub type ExtractedSyntaxGrammar = IntermediateGrammar<Symbol, ExternalToken>;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
 pub struct ExtractedLexicalGrammar {
     pub variables: Vec<Variable>,
     pub separators: Vec<Rule>,
@@ -68,7 +73,7 @@ impl<T, U> Default for IntermediateGrammar<T, U> {
             external_tokens: Vec::default(),
             variables_to_inline: Vec::default(),
             supertype_symbols: Vec::default(),
-            word_token: Option::default(),
+            word_tokens: Vec::default(),
             reserved_word_sets: Vec::default(),
         }
     }
@@ -83,8 +88,107 @@ pub enum PrepareGrammarError {
     InternSymbols(#[from] InternSymbolsError),
     ExtractTokens(#[from] ExtractTokensError),
     FlattenGrammar(#[from] FlattenGrammarError),
+    InfiniteRecursion(#[from] InfiniteRecursionError),
     ExpandTokens(#[from] ExpandTokensError),
     ProcessInlines(#[from] ProcessInlinesError),
+    UnreachableRules(#[from] UnreachableRulesError),
+    DefaultAliases(#[from] ConflictingDefaultAliasError),
+    InvalidSupertype(#[from] InvalidSupertypeError),
+    InvalidReservedWord(#[from] InvalidReservedWordError),
+    DuplicateReservedWords(#[from] DuplicateReservedWordsError),
+}
+
+/// Problems that don't prevent a parser from being generated, but that are
+/// reported to the user, and treated as errors when generating in strict mode.
+#[derive(Debug, Error, Serialize)]
+#[error(transparent)]
+pub enum PrepareGrammarWarning {
+    UnusedPrecedences(#[from] UnusedPrecedenceError),
+    UnreachableRules(#[from] UnreachableRulesError),
+    DefaultAliases(#[from] ConflictingDefaultAliasError),
+    DuplicateReservedWords(#[from] DuplicateReservedWordsError),
+    InfiniteRecursion(#[from] InfiniteRecursionError),
+}
+
+impl From<PrepareGrammarWarning> for PrepareGrammarError {
+    fn from(value: PrepareGrammarWarning) -> Self {
+        match value {
+            PrepareGrammarWarning::UnusedPrecedences(e) => Self::ValidatePrecedences(e.into()),
+            PrepareGrammarWarning::UnreachableRules(e) => Self::UnreachableRules(e),
+            PrepareGrammarWarning::DefaultAliases(e) => Self::DefaultAliases(e),
+            PrepareGrammarWarning::DuplicateReservedWords(e) => Self::DuplicateReservedWords(e),
+            PrepareGrammarWarning::InfiniteRecursion(e) => Self::InfiniteRecursion(e),
+        }
+    }
+}
+
+impl PrepareGrammarError {
+    pub(crate) const fn code(&self) -> &'static str {
+        match self {
+            Self::ValidatePrecedences(e) => e.code(),
+            Self::InternSymbols(e) => e.code(),
+            Self::ExtractTokens(e) => e.code(),
+            Self::FlattenGrammar(e) => e.code(),
+            Self::InfiniteRecursion(_) => "infinite-recursion",
+            Self::ExpandTokens(e) => e.code(),
+            Self::ProcessInlines(e) => e.code(),
+            Self::UnreachableRules(_) => "unreachable-rules",
+            Self::DefaultAliases(_) => "conflicting-default-alias",
+            Self::InvalidSupertype(_) => "invalid-supertype",
+            Self::InvalidReservedWord(_) => "non-token-reserved-word",
+            Self::DuplicateReservedWords(_) => "duplicate-reserved-words",
+        }
+    }
+
+    pub(crate) fn rule(&self) -> Option<&str> {
+        match self {
+            Self::ValidatePrecedences(e) => e.rule(),
+            Self::InternSymbols(e) => e.rule(),
+            Self::ExtractTokens(e) => e.rule(),
+            Self::FlattenGrammar(e) => e.rule(),
+            Self::InfiniteRecursion(e) => e.cycle.first().map(String::as_str),
+            Self::ExpandTokens(e) => e.rule(),
+            Self::ProcessInlines(e) => e.rule(),
+            Self::UnreachableRules(e) => single_rule(&e.rules),
+            Self::DefaultAliases(e) => Some(&e.symbol),
+            Self::InvalidSupertype(e) => Some(&e.supertype),
+            Self::InvalidReservedWord(e) => Some(&e.rule),
+            Self::DuplicateReservedWords(_) => None,
+        }
+    }
+}
+
+impl PrepareGrammarWarning {
+    /// A stable identifier for the kind of warning, which is the same as the code of the
+    /// error that it becomes in strict mode.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::UnusedPrecedences(_) => "unused-precedence",
+            Self::UnreachableRules(_) => "unreachable-rules",
+            Self::DefaultAliases(_) => "conflicting-default-alias",
+            Self::DuplicateReservedWords(_) => "duplicate-reserved-words",
+            Self::InfiniteRecursion(_) => "infinite-recursion",
+        }
+    }
+
+    /// The name of the rule that the warning is about, if there is a single such rule.
+    #[must_use]
+    pub fn rule(&self) -> Option<&str> {
+        match self {
+            Self::UnreachableRules(e) => single_rule(&e.rules),
+            Self::DefaultAliases(e) => Some(&e.symbol),
+            Self::InfiniteRecursion(e) => e.cycle.first().map(String::as_str),
+            Self::UnusedPrecedences(_) | Self::DuplicateReservedWords(_) => None,
+        }
+    }
+}
+
+fn single_rule(rules: &[String]) -> Option<&str> {
+    match rules {
+        [rule] => Some(rule),
+        _ => None,
+    }
 }
 
 pub type ValidatePrecedenceResult<T> = Result<T, ValidatePrecedenceError>;
@@ -94,6 +198,26 @@ pub type ValidatePrecedenceResult<T> = Result<T, ValidatePrecedenceError>;
 pub enum ValidatePrecedenceError {
     Undeclared(#[from] UndeclaredPrecedenceError),
     Ordering(#[from] ConflictingPrecedenceOrderingError),
+    Cycle(#[from] PrecedenceCycleError),
+    Unused(#[from] UnusedPrecedenceError),
+}
+
+impl ValidatePrecedenceError {
+    const fn code(&self) -> &'static str {
+        match self {
+            Self::Undeclared(_) => "undeclared-precedence",
+            Self::Ordering(_) => "conflicting-precedence-ordering",
+            Self::Cycle(_) => "precedence-cycle",
+            Self::Unused(_) => "unused-precedence",
+        }
+    }
+
+    fn rule(&self) -> Option<&str> {
+        match self {
+            Self::Undeclared(e) => Some(&e.rule),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Error, Serialize)]
@@ -113,61 +237,360 @@ impl std::fmt::Display for UndeclaredPrecedenceError {
     }
 }
 
+/// Two precedence lists that order the same pair of precedences differently.
+/// `precedence_1` comes before `precedence_2` in the list at index `list_1`,
+/// and after it in the list at index `list_2`.
 #[derive(Debug, Error, Serialize)]
 pub struct ConflictingPrecedenceOrderingError {
     pub precedence_1: String,
     pub precedence_2: String,
+    pub list_1: usize,
+    pub list_1_entries: Vec<String>,
+    pub list_2: usize,
+    pub list_2_entries: Vec<String>,
 }
 
 impl std::fmt::Display for ConflictingPrecedenceOrderingError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Conflicting orderings for precedences {} and {}",
-            self.precedence_1, self.precedence_2
+            "Conflicting orderings for precedences {} and {} -- list {} [{}] places {} before {}, but list {} [{}] places {} before {}",
+            self.precedence_1,
+            self.precedence_2,
+            self.list_1,
+            self.list_1_entries.join(", "),
+            self.precedence_1,
+            self.precedence_2,
+            self.list_2,
+            self.list_2_entries.join(", "),
+            self.precedence_2,
+            self.precedence_1,
         )?;
         Ok(())
     }
 }
 
-/// Transform an input grammar into separate components that are ready
-/// for parse table construction.
-pub fn prepare_grammar(
-    input_grammar: &InputGrammar,
-) -> PrepareGrammarResult<(
+/// A chain of precedences in which each one comes before the next in some
+/// list, and the last one is the same as the first. `lists` contains the
+/// index of the list that orders each consecutive pair in `cycle`.
+#[derive(Debug, Error, Serialize)]
+pub struct PrecedenceCycleError {
+    pub cycle: Vec<String>,
+    pub lists: Vec<usize>,
+}
+
+impl std::fmt::Display for PrecedenceCycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Cyclic precedence ordering {} (from lists {})",
+            self.cycle.join(" before "),
+            self.lists
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error, Serialize)]
+pub struct UnusedPrecedenceError {
+    pub precedences: Vec<UnusedPrecedence>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnusedPrecedence {
+    pub precedence: String,
+    pub lists: Vec<usize>,
+}
+
+impl std::fmt::Display for UnusedPrecedenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unused precedences ")?;
+        for (i, unused) in self.precedences.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            let lists = unused
+                .lists
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let plural = if unused.lists.len() == 1 { "" } else { "s" };
+            write!(f, "'{}' (list{plural} {lists})", unused.precedence)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rules that can't be reached from the grammar's start rule, nor from any of
+/// its extras, external tokens, supertypes, conflicts, word token, or reserved
+/// words.
+#[derive(Debug, Error, Serialize)]
+pub struct UnreachableRulesError {
+    pub rules: Vec<String>,
+}
+
+impl std::fmt::Display for UnreachableRulesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unreachable rules {}",
+            self.rules
+                .iter()
+                .map(|rule| format!("`{rule}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+        Ok(())
+    }
+}
+
+/// A supertype that, once tokens have been extracted, doesn't have any named
+/// subtypes, either because it became a token itself or because all of its
+/// alternatives are unnamed tokens. `alternatives` lists the tokens that it
+/// consists of.
+#[derive(Debug, Error, Serialize)]
+pub struct InvalidSupertypeError {
+    pub supertype: String,
+    pub alternatives: Vec<String>,
+}
+
+impl std::fmt::Display for InvalidSupertypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Supertype `{}` must be a non-terminal rule with named subtypes, but it only consists of the tokens {}",
+            self.supertype,
+            self.alternatives.join(", ")
+        )?;
+        Ok(())
+    }
+}
+
+/// A reserved word that, once tokens have been extracted, refers to a non-terminal
+/// rule instead of a token.
+#[derive(Debug, Error, Serialize)]
+pub struct InvalidReservedWordError {
+    pub context_name: String,
+    pub rule: String,
+}
+
+impl std::fmt::Display for InvalidReservedWordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Reserved word `{}` in context `{}` must be a token, but it is a non-terminal rule",
+            self.rule, self.context_name
+        )?;
+        Ok(())
+    }
+}
+
+/// Reserved words that are listed more than once in the same reserved word
+/// context. The duplicate entries are removed.
+#[derive(Debug, Error, Serialize)]
+pub struct DuplicateReservedWordsError {
+    pub context_name: String,
+    pub words: Vec<String>,
+}
+
+impl std::fmt::Display for DuplicateReservedWordsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Duplicate reserved words {} in context `{}`",
+            self.words.join(", "),
+            self.context_name
+        )?;
+        Ok(())
+    }
+}
+
+/// The components of a prepared grammar that are used to build the parse table.
+pub type PreparedGrammar = (
     SyntaxGrammar,
     LexicalGrammar,
     InlinedProductionMap,
     AliasMap,
-)> {
-    validate_precedences(input_grammar)?;
+    Vec<PrepareGrammarWarning>,
+);
+
+/// The grammars produced by the intermediate stages of [`prepare_grammar`], which
+/// [`prepare_grammar_with_timing`] keeps a copy of when it's given somewhere to put them.
+#[derive(Default)]
+pub struct IntermediateGrammars {
+    pub interned_grammar: InternedGrammar,
+    pub extracted_syntax_grammar: ExtractedSyntaxGrammar,
+    pub extracted_lexical_grammar: ExtractedLexicalGrammar,
+    pub expanded_syntax_grammar: ExtractedSyntaxGrammar,
+    pub flattened_syntax_grammar: SyntaxGrammar,
+}
+
+/// The grammar produced by each stage of [`prepare_grammar`], for tools that
+/// want to inspect how an input grammar is transformed.
+#[derive(Serialize)]
+pub struct PreparedGrammarArtifacts {
+    /// The input grammar, with all rule names resolved to symbols.
+    pub interned_grammar: InternedGrammar,
+    /// The syntax grammar, after tokens have been extracted into the lexical grammar.
+    pub extracted_syntax_grammar: ExtractedSyntaxGrammar,
+    /// The lexical grammar, before its rules have been converted into an NFA.
+    pub extracted_lexical_grammar: ExtractedLexicalGrammar,
+    /// The syntax grammar, after repetitions have been replaced with auxiliary rules.
+    pub expanded_syntax_grammar: ExtractedSyntaxGrammar,
+    /// The syntax grammar, after every rule has been flattened into a list of productions.
+    pub flattened_syntax_grammar: SyntaxGrammar,
+    /// The final syntax grammar, without the aliases that became default aliases.
+    pub syntax_grammar: SyntaxGrammar,
+    pub lexical_grammar: LexicalGrammar,
+    pub inlines: InlinedProductionMap,
+    #[serde(with = "alias_map")]
+    pub default_aliases: AliasMap,
+    pub warnings: Vec<PrepareGrammarWarning>,
+}
+
+impl PreparedGrammarArtifacts {
+    pub(crate) fn new(intermediates: IntermediateGrammars, prepared: PreparedGrammar) -> Self {
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases, warnings) = prepared;
+        Self {
+            interned_grammar: intermediates.interned_grammar,
+            extracted_syntax_grammar: intermediates.extracted_syntax_grammar,
+            extracted_lexical_grammar: intermediates.extracted_lexical_grammar,
+            expanded_syntax_grammar: intermediates.expanded_syntax_grammar,
+            flattened_syntax_grammar: intermediates.flattened_syntax_grammar,
+            syntax_grammar,
+            lexical_grammar,
+            inlines,
+            default_aliases,
+            warnings,
+        }
+    }
+}
 
-    let interned_grammar = intern_symbols(input_grammar)?;
-    let (syntax_grammar, lexical_grammar) = extract_tokens(interned_grammar)?;
-    let syntax_grammar = expand_repeats(syntax_grammar);
-    let mut syntax_grammar = flatten_grammar(syntax_grammar)?;
-    let lexical_grammar = expand_tokens(lexical_grammar)?;
-    let default_aliases = extract_default_aliases(&mut syntax_grammar, &lexical_grammar);
-    let inlines = process_inlines(&syntax_grammar, &lexical_grammar)?;
-    Ok((syntax_grammar, lexical_grammar, inlines, default_aliases))
+/// Transform an input grammar into separate components that are ready
+/// for parse table construction.
+pub fn prepare_grammar(input_grammar: &InputGrammar) -> PrepareGrammarResult<PreparedGrammar> {
+    prepare_grammar_with_timing(
+        input_grammar,
+        &mut TimingReport::disabled(),
+        &mut GrammarStats::default(),
+        None,
+    )
+}
+
+/// Transform an input grammar in the same way as [`prepare_grammar`], recording how
+/// long each stage took, and keeping a copy of the grammar produced by each of the
+/// intermediate stages if `intermediates` is given.
+pub fn prepare_grammar_with_timing(
+    input_grammar: &InputGrammar,
+    timings: &mut TimingReport,
+    stats: &mut GrammarStats,
+    mut intermediates: Option<&mut IntermediateGrammars>,
+) -> PrepareGrammarResult<PreparedGrammar> {
+    let mut warnings = Vec::new();
+    if let Some(unused) = timings.time("validate_precedences", || {
+        validate_precedences(input_grammar)
+    })? {
+        warnings.push(unused.into());
+    }
+
+    let interned_grammar = timings.time("intern_symbols", || intern_symbols(input_grammar))?;
+    if let Some(unreachable) = timings.time("find_unreachable_rules", || {
+        find_unreachable_rules(&interned_grammar)
+    }) {
+        warnings.push(unreachable.into());
+    }
+    if let Some(intermediates) = intermediates.as_deref_mut() {
+        intermediates.interned_grammar = interned_grammar.clone();
+    }
+    let (mut syntax_grammar, lexical_grammar) =
+        timings.time("extract_tokens", || extract_tokens(interned_grammar))?;
+    let duplicate_reserved_words = timings.time("validate_reserved_words", || {
+        validate_reserved_words(&mut syntax_grammar, &lexical_grammar)
+    })?;
+    warnings.extend(duplicate_reserved_words.into_iter().map(Into::into));
+    timings.time("validate_supertypes", || {
+        validate_supertypes(&syntax_grammar, &lexical_grammar)
+    })?;
+    if let Some(intermediates) = intermediates.as_deref_mut() {
+        intermediates.extracted_syntax_grammar = syntax_grammar.clone();
+        intermediates.extracted_lexical_grammar = lexical_grammar.clone();
+    }
+    stats.variable_count_before_expand_repeats = syntax_grammar.variables.len();
+    let syntax_grammar = timings.time("expand_repeats", || expand_repeats(syntax_grammar));
+    stats.variable_count_after_expand_repeats = syntax_grammar.variables.len();
+    if let Some(intermediates) = intermediates.as_deref_mut() {
+        intermediates.expanded_syntax_grammar = syntax_grammar.clone();
+    }
+    let mut syntax_grammar = timings.time("flatten_grammar", || flatten_grammar(syntax_grammar))?;
+    if let Some(recursion) = timings.time("find_infinite_recursion", || {
+        find_infinite_recursion(&syntax_grammar)
+    }) {
+        warnings.push(recursion.into());
+    }
+    if let Some(intermediates) = intermediates {
+        intermediates.flattened_syntax_grammar = syntax_grammar.clone();
+    }
+    let lexical_grammar = timings.time("expand_tokens", || expand_tokens(lexical_grammar))?;
+    let (default_aliases, alias_conflicts) = timings.time("extract_default_aliases", || {
+        extract_default_aliases(&mut syntax_grammar, &lexical_grammar)
+    });
+    warnings.extend(alias_conflicts.into_iter().map(Into::into));
+    let inlines = timings.time("process_inlines", || {
+        process_inlines(&syntax_grammar, &lexical_grammar)
+    })?;
+    Ok((
+        syntax_grammar,
+        lexical_grammar,
+        inlines,
+        default_aliases,
+        warnings,
+    ))
+}
+
+/// Transform an input grammar in the same way as [`prepare_grammar`], but also
+/// keep a copy of the grammar produced by each of the intermediate stages.
+pub fn prepare_grammar_with_intermediates(
+    input_grammar: &InputGrammar,
+) -> PrepareGrammarResult<PreparedGrammarArtifacts> {
+    let mut intermediates = IntermediateGrammars::default();
+    let prepared = prepare_grammar_with_timing(
+        input_grammar,
+        &mut TimingReport::disabled(),
+        &mut GrammarStats::default(),
+        Some(&mut intermediates),
+    )?;
+    Ok(PreparedGrammarArtifacts::new(intermediates, prepared))
 }
 
 /// Check that all of the named precedences used in the grammar are declared
 /// within the `precedences` lists, and also that there are no conflicting
 /// precedence orderings declared in those lists.
-fn validate_precedences(grammar: &InputGrammar) -> ValidatePrecedenceResult<()> {
+///
+/// Named precedences that are declared but never used by any rule are not
+/// an error, and are returned so that they can be reported as a warning.
+fn validate_precedences(
+    grammar: &InputGrammar,
+) -> ValidatePrecedenceResult<Option<UnusedPrecedenceError>> {
     // Check that no rule contains a named precedence that is not present in
-    // any of the `precedences` lists.
-    fn validate(
+    // any of the `precedences` lists, and record which names are used.
+    fn validate<'a>(
         rule_name: &str,
-        rule: &Rule,
+        rule: &'a Rule,
         names: &HashSet<&String>,
+        used_names: &mut HashSet<&'a String>,
     ) -> ValidatePrecedenceResult<()> {
         match rule {
-            Rule::Repeat(rule) => validate(rule_name, rule, names),
+            Rule::Repeat(rule) | Rule::Reserved { rule, .. } => {
+                validate(rule_name, rule, names, used_names)
+            }
             Rule::Seq(elements) | Rule::Choice(elements) => elements
                 .iter()
-                .try_for_each(|e| validate(rule_name, e, names)),
+                .try_for_each(|e| validate(rule_name, e, names, used_names)),
             Rule::Metadata { rule, params } => {
                 if let Precedence::Name(n) = &params.precedence {
                     if !names.contains(n) {
@@ -176,8 +599,9 @@ fn validate_precedences(grammar: &InputGrammar) -> ValidatePrecedenceResult<()>
                             rule: rule_name.to_string(),
                         })?;
                     }
+                    used_names.insert(n);
                 }
-                validate(rule_name, rule, names)?;
+                validate(rule_name, rule, names, used_names)?;
                 Ok(())
             }
             _ => Ok(()),
@@ -186,8 +610,14 @@ fn validate_precedences(grammar: &InputGrammar) -> ValidatePrecedenceResult<()>
 
     // For any two precedence names `a` and `b`, if `a` comes before `b`
     // in some list, then it cannot come *after* `b` in any list.
+    let list_entries = |index: usize| {
+        grammar.precedence_orderings[index]
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    };
     let mut pairs = HashMap::new();
-    for list in &grammar.precedence_orderings {
+    for (list_index, list) in grammar.precedence_orderings.iter().enumerate() {
         for (i, mut entry1) in list.iter().enumerate() {
             for mut entry2 in list.iter().skip(i + 1) {
                 if entry2 == entry1 {
@@ -200,13 +630,23 @@ fn validate_precedences(grammar: &InputGrammar) -> ValidatePrecedenceResult<()>
                 }
                 match pairs.entry((entry1, entry2)) {
                     hash_map::Entry::Vacant(e) => {
-                        e.insert(ordering);
+                        e.insert((ordering, list_index));
                     }
                     hash_map::Entry::Occupied(e) => {
-                        if e.get() != &ordering {
+                        let (previous_ordering, previous_list_index) = *e.get();
+                        if previous_ordering != ordering {
+                            let (first, second) = if previous_ordering == Ordering::Greater {
+                                (entry1, entry2)
+                            } else {
+                                (entry2, entry1)
+                            };
                             Err(ConflictingPrecedenceOrderingError {
-                                precedence_1: entry1.to_string(),
-                                precedence_2: entry2.to_string(),
+                                precedence_1: first.to_string(),
+                                precedence_2: second.to_string(),
+                                list_1: previous_list_index,
+                                list_1_entries: list_entries(previous_list_index),
+                                list_2: list_index,
+                                list_2_entries: list_entries(list_index),
                             })?;
                         }
                     }
@@ -215,6 +655,8 @@ fn validate_precedences(grammar: &InputGrammar) -> ValidatePrecedenceResult<()>
         }
     }
 
+    validate_precedence_cycles(grammar)?;
+
     let precedence_names = grammar
         .precedence_orderings
         .iter()
@@ -227,17 +669,355 @@ fn validate_precedences(grammar: &InputGrammar) -> ValidatePrecedenceResult<()>
             }
         })
         .collect::<HashSet<&String>>();
+    let mut used_names = HashSet::new();
     for variable in &grammar.variables {
-        validate(&variable.name, &variable.rule, &precedence_names)?;
+        validate(
+            &variable.name,
+            &variable.rule,
+            &precedence_names,
+            &mut used_names,
+        )?;
+    }
+
+    // Report every declared name that isn't used by any rule, along with the
+    // indices of the lists in which it is declared.
+    let mut unused = Vec::<UnusedPrecedence>::new();
+    for (list_index, list) in grammar.precedence_orderings.iter().enumerate() {
+        for entry in list {
+            let PrecedenceEntry::Name(name) = entry else {
+                continue;
+            };
+            if used_names.contains(name) {
+                continue;
+            }
+            if let Some(unused) = unused.iter_mut().find(|u| &u.precedence == name) {
+                if !unused.lists.contains(&list_index) {
+                    unused.lists.push(list_index);
+                }
+            } else {
+                unused.push(UnusedPrecedence {
+                    precedence: name.clone(),
+                    lists: vec![list_index],
+                });
+            }
+        }
+    }
+
+    Ok((!unused.is_empty()).then_some(UnusedPrecedenceError {
+        precedences: unused,
+    }))
+}
+
+/// Check that the orderings declared across all of the `precedences` lists
+/// are consistent with each other, i.e. that the graph of precedences, with
+/// an edge from each entry to the one that follows it, has no cycles.
+fn validate_precedence_cycles(grammar: &InputGrammar) -> ValidatePrecedenceResult<()> {
+    fn visit(
+        node: usize,
+        entries: &[&PrecedenceEntry],
+        edges: &[Vec<(usize, usize)>],
+        states: &mut [VisitState],
+        path: &mut Vec<(usize, usize)>,
+    ) -> ValidatePrecedenceResult<()> {
+        states[node] = VisitState::InProgress;
+        for &(target, list_index) in &edges[node] {
+            match states[target] {
+                VisitState::Done => {}
+                VisitState::InProgress => {
+                    path.push((node, list_index));
+                    let start = path.iter().position(|(n, _)| *n == target).unwrap();
+                    let cycle = &path[start..];
+                    Err(PrecedenceCycleError {
+                        cycle: cycle
+                            .iter()
+                            .map(|(n, _)| entries[*n].to_string())
+                            .chain(std::iter::once(entries[target].to_string()))
+                            .collect(),
+                        lists: cycle.iter().map(|(_, l)| *l).collect(),
+                    })?;
+                }
+                VisitState::NotVisited => {
+                    path.push((node, list_index));
+                    visit(target, entries, edges, states, path)?;
+                    path.pop();
+                }
+            }
+        }
+        states[node] = VisitState::Done;
+        Ok(())
+    }
+
+    #[derive(Clone, Copy)]
+    enum VisitState {
+        NotVisited,
+        InProgress,
+        Done,
+    }
+
+    let mut entries = Vec::<&PrecedenceEntry>::new();
+    for entry in grammar.precedence_orderings.iter().flatten() {
+        if !entries.contains(&entry) {
+            entries.push(entry);
+        }
+    }
+    let mut edges = vec![Vec::<(usize, usize)>::new(); entries.len()];
+    for (list_index, list) in grammar.precedence_orderings.iter().enumerate() {
+        for pair in list.windows(2) {
+            if pair[0] != pair[1] {
+                let from = entries.iter().position(|e| *e == &pair[0]).unwrap();
+                let to = entries.iter().position(|e| *e == &pair[1]).unwrap();
+                if !edges[from].contains(&(to, list_index)) {
+                    edges[from].push((to, list_index));
+                }
+            }
+        }
+    }
+
+    let mut states = vec![VisitState::NotVisited; entries.len()];
+    let mut path = Vec::new();
+    for node in 0..entries.len() {
+        if matches!(states[node], VisitState::NotVisited) {
+            visit(node, &entries, &edges, &mut states, &mut path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Find the variables that can't be reached by following symbol references
+/// from the start rule. Anything that the grammar refers to outside of the
+/// rules themselves, such as extras, external tokens, supertypes, and
+/// conflicts, is also considered a starting point. Inlined variables are
+/// followed like any other rule, so they are only reachable if something
+/// refers to them.
+fn find_unreachable_rules(grammar: &InternedGrammar) -> Option<UnreachableRulesError> {
+    fn add_symbols(rule: &Rule, symbols: &mut Vec<Symbol>) {
+        match rule {
+            Rule::Symbol(symbol) => symbols.push(*symbol),
+            Rule::Seq(elements) | Rule::Choice(elements) => {
+                for element in elements {
+                    add_symbols(element, symbols);
+                }
+            }
+            Rule::Metadata { rule, .. } | Rule::Repeat(rule) | Rule::Reserved { rule, .. } => {
+                add_symbols(rule, symbols);
+            }
+            _ => {}
+        }
+    }
+
+    if grammar.variables.is_empty() {
+        return None;
+    }
+
+    let mut stack = vec![Symbol::non_terminal(0)];
+    stack.extend(grammar.supertype_symbols.iter());
+    stack.extend(grammar.expected_conflicts.iter().flatten());
+    stack.extend(grammar.word_tokens.iter());
+    for rule in grammar
+        .extra_symbols
+        .iter()
+        .chain(grammar.external_tokens.iter().map(|token| &token.rule))
+        .chain(
+            grammar
+                .reserved_word_sets
+                .iter()
+                .flat_map(|set| &set.reserved_words),
+        )
+    {
+        add_symbols(rule, &mut stack);
+    }
+
+    let mut reachable = vec![false; grammar.variables.len()];
+    while let Some(symbol) = stack.pop() {
+        if symbol.is_non_terminal() && !reachable[symbol.index] {
+            reachable[symbol.index] = true;
+            add_symbols(&grammar.variables[symbol.index].rule, &mut stack);
+        }
+    }
+
+    let rules = grammar
+        .variables
+        .iter()
+        .zip(reachable)
+        .filter(|(_, reachable)| !reachable)
+        .map(|(variable, _)| variable.name.clone())
+        .collect::<Vec<_>>();
+    (!rules.is_empty()).then_some(UnreachableRulesError { rules })
+}
+
+/// Check that every reserved word refers to a token after tokens have been extracted,
+/// and remove the words that are listed more than once within the same context. The
+/// removed duplicates are returned so that they can be reported as a warning.
+fn validate_reserved_words(
+    syntax_grammar: &mut ExtractedSyntaxGrammar,
+    lexical_grammar: &ExtractedLexicalGrammar,
+) -> Result<Vec<DuplicateReservedWordsError>, InvalidReservedWordError> {
+    let mut duplicates = Vec::new();
+    for context in &mut syntax_grammar.reserved_word_sets {
+        let symbol_name = |symbol: &Symbol| match symbol.kind {
+            SymbolType::NonTerminal => &syntax_grammar.variables[symbol.index].name,
+            SymbolType::Terminal => &lexical_grammar.variables[symbol.index].name,
+            SymbolType::External => &syntax_grammar.external_tokens[symbol.index].name,
+            SymbolType::End | SymbolType::EndOfNonTerminalExtra => {
+                panic!("Unexpected end token")
+            }
+        };
+
+        let mut seen = HashSet::new();
+        let mut duplicate_words = Vec::new();
+        for symbol in &context.reserved_words {
+            if symbol.is_non_terminal() {
+                Err(InvalidReservedWordError {
+                    context_name: context.name.clone(),
+                    rule: symbol_name(symbol).clone(),
+                })?;
+            }
+            if !seen.insert(*symbol) {
+                let name = format!("`{}`", symbol_name(symbol));
+                if !duplicate_words.contains(&name) {
+                    duplicate_words.push(name);
+                }
+            }
+        }
+
+        if !duplicate_words.is_empty() {
+            let mut seen = HashSet::new();
+            context.reserved_words.retain(|symbol| seen.insert(*symbol));
+            duplicates.push(DuplicateReservedWordsError {
+                context_name: context.name.clone(),
+                words: duplicate_words,
+            });
+        }
+    }
+    Ok(duplicates)
+}
+
+/// Check that every supertype still refers to a non-terminal rule with at least one
+/// named subtype after tokens have been extracted. Hidden rules within a supertype are
+/// followed to find their subtypes, while other supertypes count as named subtypes.
+fn validate_supertypes(
+    syntax_grammar: &ExtractedSyntaxGrammar,
+    lexical_grammar: &ExtractedLexicalGrammar,
+) -> Result<(), InvalidSupertypeError> {
+    fn token_name(name: &str, kind: VariableType) -> String {
+        if kind == VariableType::Anonymous {
+            format!("'{name}'")
+        } else {
+            format!("`{name}`")
+        }
     }
 
+    // Find the subtypes within a rule, returning whether any of them are named, and
+    // recording the ones that aren't.
+    fn find_subtypes(
+        rule: &Rule,
+        syntax_grammar: &ExtractedSyntaxGrammar,
+        lexical_grammar: &ExtractedLexicalGrammar,
+        visited: &mut HashSet<Symbol>,
+        alternatives: &mut Vec<String>,
+    ) -> bool {
+        match rule {
+            Rule::Seq(elements) | Rule::Choice(elements) => {
+                let mut has_named_subtype = false;
+                for element in elements {
+                    has_named_subtype |= find_subtypes(
+                        element,
+                        syntax_grammar,
+                        lexical_grammar,
+                        visited,
+                        alternatives,
+                    );
+                }
+                has_named_subtype
+            }
+            Rule::Metadata { rule, params } => match &params.alias {
+                Some(alias) if alias.is_named => true,
+                Some(alias) => {
+                    alternatives.push(format!("'{}'", alias.value));
+                    false
+                }
+                None => find_subtypes(rule, syntax_grammar, lexical_grammar, visited, alternatives),
+            },
+            Rule::Repeat(rule) | Rule::Reserved { rule, .. } => {
+                find_subtypes(rule, syntax_grammar, lexical_grammar, visited, alternatives)
+            }
+            Rule::Symbol(symbol) => {
+                let (name, kind) = match symbol.kind {
+                    SymbolType::NonTerminal => {
+                        // Nested supertypes are subtypes in their own right.
+                        if syntax_grammar.supertype_symbols.contains(symbol) {
+                            return true;
+                        }
+                        let variable = &syntax_grammar.variables[symbol.index];
+                        if matches!(
+                            variable.kind,
+                            VariableType::Hidden | VariableType::Auxiliary
+                        ) {
+                            return visited.insert(*symbol)
+                                && find_subtypes(
+                                    &variable.rule,
+                                    syntax_grammar,
+                                    lexical_grammar,
+                                    visited,
+                                    alternatives,
+                                );
+                        }
+                        (&variable.name, variable.kind)
+                    }
+                    SymbolType::Terminal => {
+                        let variable = &lexical_grammar.variables[symbol.index];
+                        (&variable.name, variable.kind)
+                    }
+                    SymbolType::External => {
+                        let token = &syntax_grammar.external_tokens[symbol.index];
+                        (&token.name, token.kind)
+                    }
+                    _ => return false,
+                };
+                if kind == VariableType::Named {
+                    true
+                } else {
+                    alternatives.push(token_name(name, kind));
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+
+    for supertype in &syntax_grammar.supertype_symbols {
+        let mut alternatives = Vec::new();
+        let supertype_name = if supertype.is_non_terminal() {
+            let variable = &syntax_grammar.variables[supertype.index];
+            let mut visited = HashSet::from([*supertype]);
+            if find_subtypes(
+                &variable.rule,
+                syntax_grammar,
+                lexical_grammar,
+                &mut visited,
+                &mut alternatives,
+            ) {
+                continue;
+            }
+            &variable.name
+        } else if supertype.is_terminal() {
+            let variable = &lexical_grammar.variables[supertype.index];
+            alternatives.push(token_name(&variable.name, variable.kind));
+            &variable.name
+        } else {
+            continue;
+        };
+        Err(InvalidSupertypeError {
+            supertype: supertype_name.clone(),
+            alternatives,
+        })?;
+    }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::grammars::VariableType;
 
     #[test]
     fn test_validate_precedences_with_undeclared_precedence() {
@@ -319,7 +1099,437 @@ mod tests {
         let result = validate_precedences(&grammar);
         assert_eq!(
             result.unwrap_err().to_string(),
-            "Conflicting orderings for precedences 'a' and 'b'",
+            "Conflicting orderings for precedences 'a' and 'b' -- list 0 ['a', 'b'] places 'a' before 'b', but list 1 ['b', 'c', 'a'] places 'b' before 'a'",
+        );
+    }
+
+    #[test]
+    fn test_validate_precedences_with_conflicting_order_reports_lists() {
+        let grammar = InputGrammar {
+            precedence_orderings: vec![
+                vec![
+                    PrecedenceEntry::Name("x".to_string()),
+                    PrecedenceEntry::Name("y".to_string()),
+                ],
+                vec![
+                    PrecedenceEntry::Name("z".to_string()),
+                    PrecedenceEntry::Symbol("v1".to_string()),
+                    PrecedenceEntry::Name("x".to_string()),
+                ],
+                vec![
+                    PrecedenceEntry::Name("x".to_string()),
+                    PrecedenceEntry::Symbol("v1".to_string()),
+                ],
+            ],
+            variables: vec![Variable {
+                name: "v1".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::Seq(vec![
+                    Rule::prec(Precedence::Name("x".to_string()), Rule::string("w")),
+                    Rule::prec(Precedence::Name("y".to_string()), Rule::string("x")),
+                    Rule::prec(Precedence::Name("z".to_string()), Rule::string("y")),
+                ]),
+            }],
+            ..Default::default()
+        };
+
+        let Err(ValidatePrecedenceError::Ordering(error)) = validate_precedences(&grammar) else {
+            panic!("expected a conflicting ordering error");
+        };
+        assert_eq!(error.precedence_1, "$.v1");
+        assert_eq!(error.precedence_2, "'x'");
+        assert_eq!(error.list_1, 1);
+        assert_eq!(error.list_1_entries, vec!["'z'", "$.v1", "'x'"]);
+        assert_eq!(error.list_2, 2);
+        assert_eq!(error.list_2_entries, vec!["'x'", "$.v1"]);
+    }
+
+    #[test]
+    fn test_validate_precedences_with_unused_precedence() {
+        let grammar = InputGrammar {
+            precedence_orderings: vec![
+                vec![
+                    PrecedenceEntry::Name("a".to_string()),
+                    PrecedenceEntry::Name("b".to_string()),
+                ],
+                vec![
+                    PrecedenceEntry::Name("b".to_string()),
+                    PrecedenceEntry::Symbol("v1".to_string()),
+                    PrecedenceEntry::Name("c".to_string()),
+                ],
+                vec![
+                    PrecedenceEntry::Name("c".to_string()),
+                    PrecedenceEntry::Name("d".to_string()),
+                ],
+            ],
+            variables: vec![Variable {
+                name: "v1".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::Seq(vec![
+                    Rule::prec_left(Precedence::Name("b".to_string()), Rule::string("w")),
+                    Rule::prec(Precedence::Name("d".to_string()), Rule::string("x")),
+                ]),
+            }],
+            ..Default::default()
+        };
+
+        let unused = validate_precedences(&grammar).unwrap().unwrap();
+        assert_eq!(
+            unused
+                .precedences
+                .iter()
+                .map(|u| (u.precedence.as_str(), u.lists.clone()))
+                .collect::<Vec<_>>(),
+            vec![("a", vec![0]), ("c", vec![1, 2])],
+        );
+        assert_eq!(
+            unused.to_string(),
+            "Unused precedences 'a' (list 0), 'c' (lists 1, 2)",
+        );
+    }
+
+    #[test]
+    fn test_validate_precedences_with_all_precedences_used() {
+        let grammar = InputGrammar {
+            precedence_orderings: vec![vec![
+                PrecedenceEntry::Name("a".to_string()),
+                PrecedenceEntry::Name("b".to_string()),
+            ]],
+            variables: vec![Variable {
+                name: "v1".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::choice(vec![
+                    Rule::prec(Precedence::Name("a".to_string()), Rule::string("w")),
+                    Rule::repeat(Rule::prec(
+                        Precedence::Name("b".to_string()),
+                        Rule::string("x"),
+                    )),
+                ]),
+            }],
+            ..Default::default()
+        };
+
+        assert!(validate_precedences(&grammar).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_validate_precedences_with_cycle_across_lists() {
+        let grammar = InputGrammar {
+            precedence_orderings: vec![
+                vec![
+                    PrecedenceEntry::Name("a".to_string()),
+                    PrecedenceEntry::Name("b".to_string()),
+                ],
+                vec![
+                    PrecedenceEntry::Name("b".to_string()),
+                    PrecedenceEntry::Name("c".to_string()),
+                ],
+                vec![
+                    PrecedenceEntry::Name("c".to_string()),
+                    PrecedenceEntry::Name("a".to_string()),
+                ],
+            ],
+            variables: vec![Variable {
+                name: "v1".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::Seq(vec![
+                    Rule::prec(Precedence::Name("a".to_string()), Rule::string("w")),
+                    Rule::prec(Precedence::Name("b".to_string()), Rule::string("x")),
+                    Rule::prec(Precedence::Name("c".to_string()), Rule::string("y")),
+                ]),
+            }],
+            ..Default::default()
+        };
+
+        let result = validate_precedences(&grammar);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Cyclic precedence ordering 'a' before 'b' before 'c' before 'a' (from lists 0, 1, 2)",
+        );
+    }
+
+    #[test]
+    fn test_validate_precedences_with_cycle_through_symbols() {
+        let grammar = InputGrammar {
+            precedence_orderings: vec![
+                vec![
+                    PrecedenceEntry::Symbol("v2".to_string()),
+                    PrecedenceEntry::Name("a".to_string()),
+                    PrecedenceEntry::Symbol("v1".to_string()),
+                ],
+                vec![
+                    PrecedenceEntry::Symbol("v1".to_string()),
+                    PrecedenceEntry::Name("b".to_string()),
+                ],
+                vec![
+                    PrecedenceEntry::Name("b".to_string()),
+                    PrecedenceEntry::Symbol("v2".to_string()),
+                ],
+            ],
+            variables: vec![
+                Variable {
+                    name: "v1".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::prec(Precedence::Name("a".to_string()), Rule::string("w")),
+                },
+                Variable {
+                    name: "v2".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::prec(Precedence::Name("b".to_string()), Rule::string("x")),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let Err(ValidatePrecedenceError::Cycle(error)) = validate_precedences(&grammar) else {
+            panic!("expected a precedence cycle error");
+        };
+        assert_eq!(error.cycle, vec!["$.v2", "'a'", "$.v1", "'b'", "$.v2"]);
+        assert_eq!(error.lists, vec![0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_find_unreachable_rules() {
+        let grammar = intern_symbols(&InputGrammar {
+            variables: vec![
+                Variable::named("program", Rule::repeat(Rule::named("_statement"))),
+                Variable::hidden("_statement", Rule::named("_inlined")),
+                Variable::hidden("_inlined", Rule::named("expression")),
+                Variable::named("expression", Rule::string("x")),
+                Variable::hidden("_unused", Rule::named("expression")),
+                Variable::named("unused", Rule::named("_unused")),
+                Variable::named("comment", Rule::string("#")),
+                Variable::hidden("_supertype", Rule::named("expression")),
+                Variable::named("in_conflict", Rule::string("y")),
+            ],
+            extra_symbols: vec![Rule::named("comment")],
+            variables_to_inline: vec!["_inlined".to_string()],
+            supertype_symbols: vec!["_supertype".to_string()],
+            expected_conflicts: vec![vec!["in_conflict".to_string(), "program".to_string()]],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let unreachable = find_unreachable_rules(&grammar).unwrap();
+        assert_eq!(unreachable.rules, vec!["_unused", "unused"]);
+        assert_eq!(
+            unreachable.to_string(),
+            "Unreachable rules `_unused`, `unused`"
+        );
+    }
+
+    #[test]
+    fn test_find_unreachable_rules_through_external_tokens() {
+        let grammar = intern_symbols(&InputGrammar {
+            variables: vec![
+                Variable::named("program", Rule::named("heredoc")),
+                Variable::named("heredoc", Rule::string("<<")),
+                Variable::named("keyword", Rule::string("if")),
+                Variable::named("identifier", Rule::pattern("[a-z]+", "")),
+            ],
+            external_tokens: vec![Rule::named("heredoc"), Rule::named("keyword")],
+            word_tokens: vec!["identifier".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(find_unreachable_rules(&grammar).is_none());
+    }
+
+    #[test]
+    fn test_find_unreachable_rules_through_inlined_variables() {
+        let grammar = intern_symbols(&InputGrammar {
+            variables: vec![
+                Variable::named("program", Rule::named("_inlined")),
+                Variable::hidden("_inlined", Rule::named("expression")),
+                Variable::named("expression", Rule::string("y")),
+                Variable::hidden("_unused_inlined", Rule::string("z")),
+            ],
+            variables_to_inline: vec!["_inlined".to_string(), "_unused_inlined".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
+        // `expression` is only reachable through the inlined `_inlined`, and an inlined
+        // variable that nothing refers to is still unreachable.
+        let unreachable = find_unreachable_rules(&grammar).unwrap();
+        assert_eq!(unreachable.rules, vec!["_unused_inlined"]);
+    }
+
+    #[test]
+    fn test_validate_supertypes() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable::named(
+                    "program",
+                    Rule::choice(vec![Rule::named("_literal"), Rule::named("_expression")]),
+                ),
+                Variable::hidden(
+                    "_literal",
+                    Rule::choice(vec![Rule::named("_boolean"), Rule::string("null")]),
+                ),
+                Variable::hidden(
+                    "_boolean",
+                    Rule::choice(vec![Rule::string("true"), Rule::string("false")]),
+                ),
+                Variable::hidden(
+                    "_expression",
+                    Rule::choice(vec![Rule::named("_primary"), Rule::string("this")]),
+                ),
+                Variable::hidden("_primary", Rule::named("identifier")),
+                Variable::named("identifier", Rule::pattern("[a-z]+", "")),
+            ],
+            supertype_symbols: vec!["_expression".to_string(), "_literal".to_string()],
+            ..Default::default()
+        };
+
+        let Err(PrepareGrammarError::InvalidSupertype(error)) = prepare_grammar(&grammar) else {
+            panic!("Expected an invalid supertype error");
+        };
+        assert_eq!(error.supertype, "_literal");
+        assert_eq!(error.alternatives, vec!["'true'", "'false'", "'null'"]);
+    }
+
+    #[test]
+    fn test_validate_reserved_words() {
+        let grammar = |reserved_words: Vec<Rule>| InputGrammar {
+            variables: vec![
+                Variable::named(
+                    "program",
+                    Rule::repeat(Rule::choice(vec![
+                        Rule::named("statement"),
+                        Rule::named("identifier"),
+                    ])),
+                ),
+                Variable::named(
+                    "statement",
+                    Rule::seq(vec![Rule::string("if"), Rule::named("identifier")]),
+                ),
+                Variable::named("identifier", Rule::pattern("[a-z]+", "")),
+            ],
+            word_tokens: vec!["identifier".to_string()],
+            reserved_words: vec![ReservedWordContext {
+                name: "global".to_string(),
+                reserved_words,
+            }],
+            ..Default::default()
+        };
+
+        // A reserved word that is a non-terminal rule.
+        let Err(PrepareGrammarError::InvalidReservedWord(error)) =
+            prepare_grammar(&grammar(vec![Rule::string("if"), Rule::named("statement")]))
+        else {
+            panic!("Expected an invalid reserved word error");
+        };
+        assert_eq!(error.context_name, "global");
+        assert_eq!(error.rule, "statement");
+        assert_eq!(
+            error.to_string(),
+            "Reserved word `statement` in context `global` must be a token, but it is a non-terminal rule"
+        );
+
+        // A reserved word that isn't used anywhere else in the grammar.
+        let Err(PrepareGrammarError::ExtractTokens(error)) =
+            prepare_grammar(&grammar(vec![Rule::string("if"), Rule::string("else")]))
+        else {
+            panic!("Expected an extract tokens error");
+        };
+        assert_eq!(
+            error.to_string(),
+            "Reserved word 'else' in context `global` must be a token that is used elsewhere in the grammar"
+        );
+
+        // Reserved words that are listed more than once are removed.
+        let artifacts = prepare_grammar_with_intermediates(&grammar(vec![
+            Rule::string("if"),
+            Rule::named("identifier"),
+            Rule::string("if"),
+        ]))
+        .unwrap();
+        let reserved_words = &artifacts.extracted_syntax_grammar.reserved_word_sets[0];
+        assert_eq!(
+            reserved_words
+                .reserved_words
+                .iter()
+                .map(
+                    |symbol| artifacts.extracted_lexical_grammar.variables[symbol.index]
+                        .name
+                        .as_str()
+                )
+                .collect::<Vec<_>>(),
+            &["if", "identifier"]
+        );
+        assert_eq!(artifacts.warnings.len(), 1);
+        assert_eq!(
+            artifacts.warnings[0].to_string(),
+            "Duplicate reserved words `if` in context `global`"
+        );
+    }
+
+    #[test]
+    fn test_prepare_grammar_with_intermediates() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable::named(
+                    "program",
+                    Rule::repeat(Rule::seq(vec![
+                        Rule::named("identifier"),
+                        Rule::string(";"),
+                    ])),
+                ),
+                Variable::named("identifier", Rule::pattern("[a-z]+", "")),
+            ],
+            ..Default::default()
+        };
+
+        let artifacts = prepare_grammar_with_intermediates(&grammar).unwrap();
+        assert_eq!(artifacts.interned_grammar.variables.len(), 2);
+        assert_eq!(artifacts.extracted_syntax_grammar.variables.len(), 1);
+        assert_eq!(
+            artifacts
+                .extracted_lexical_grammar
+                .variables
+                .iter()
+                .map(|v| v.name.as_str())
+                .collect::<Vec<_>>(),
+            &[";", "identifier"]
+        );
+        assert_eq!(
+            artifacts
+                .expanded_syntax_grammar
+                .variables
+                .iter()
+                .map(|v| (v.name.as_str(), v.kind))
+                .collect::<Vec<_>>(),
+            &[
+                ("program", VariableType::Named),
+                ("program_repeat1", VariableType::Auxiliary)
+            ]
+        );
+        assert_eq!(
+            artifacts.flattened_syntax_grammar.variables,
+            artifacts.syntax_grammar.variables
+        );
+
+        let (syntax_grammar, lexical_grammar, ..) = prepare_grammar(&grammar).unwrap();
+        assert_eq!(artifacts.syntax_grammar.variables, syntax_grammar.variables);
+        assert_eq!(artifacts.lexical_grammar, lexical_grammar);
+
+        let json = serde_json::to_value(&artifacts).unwrap();
+        assert_eq!(
+            json.as_object().unwrap().keys().collect::<Vec<_>>(),
+            &[
+                "interned_grammar",
+                "extracted_syntax_grammar",
+                "extracted_lexical_grammar",
+                "expanded_syntax_grammar",
+                "flattened_syntax_grammar",
+                "syntax_grammar",
+                "lexical_grammar",
+                "inlines",
+                "default_aliases",
+                "warnings",
+            ]
         );
     }
 }
@@ -5,8 +5,11 @@ use serde::Serialize;
 use thiserror::Error;
 
 use crate::{
-    grammars::{InlinedProductionMap, LexicalGrammar, Production, ProductionStep, SyntaxGrammar},
-    rules::SymbolType,
+    grammars::{
+        InlineUsage, InlinedProductionMap, LexicalGrammar, Production, ProductionStep,
+        SyntaxGrammar,
+    },
+    rules::{Symbol, SymbolType},
 };
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -22,6 +25,7 @@ struct ProductionStepId {
 struct InlinedProductionMapBuilder {
     production_indices_by_step_id: HashMap<ProductionStepId, Vec<usize>>,
     productions: Vec<Production>,
+    usage_by_symbol: HashMap<Symbol, InlineUsage>,
 }
 
 impl InlinedProductionMapBuilder {
@@ -76,7 +80,7 @@ impl InlinedProductionMapBuilder {
                     |variable_index| {
                         &grammar.variables[variable_index].productions[step_id.production_index]
                     },
-                ) as *const Production;
+                ) as *const Production as usize;
                 ((production, step_id.step_index as u32), production_indices)
             })
             .collect();
@@ -84,6 +88,7 @@ impl InlinedProductionMapBuilder {
         InlinedProductionMap {
             productions,
             production_map,
+            usage_by_symbol: self.usage_by_symbol,
         }
     }
 
@@ -92,7 +97,16 @@ impl InlinedProductionMapBuilder {
         step_id: ProductionStepId,
         grammar: &'a SyntaxGrammar,
     ) -> &'a [usize] {
-        // Build a list of productions produced by inlining rules.
+        // Build a list of productions produced by inlining rules. The same step can be
+        // reached more than once, so its inlined rules are only counted the first time.
+        // The productions of rules that are themselves inlined never appear in the parse
+        // table, so inlining rules into them isn't counted either.
+        let count_usage = !self.production_indices_by_step_id.contains_key(&step_id)
+            && !step_id.variable_index.is_some_and(|index| {
+                grammar
+                    .variables_to_inline
+                    .contains(&Symbol::non_terminal(index))
+            });
         let mut i = 0;
         let step_index = step_id.step_index;
         let mut productions_to_add = vec![self.production_for_id(step_id, grammar).clone()];
@@ -100,6 +114,13 @@ impl InlinedProductionMapBuilder {
             if let Some(step) = productions_to_add[i].steps.get(step_index) {
                 let symbol = step.symbol;
                 if grammar.variables_to_inline.contains(&symbol) {
+                    let inlined_productions = &grammar.variables[symbol.index].productions;
+                    if count_usage {
+                        let usage = self.usage_by_symbol.entry(symbol).or_default();
+                        usage.inlined_into_count += 1;
+                        usage.production_count += inlined_productions.len();
+                    }
+
                     // Remove the production from the vector, replacing it with a placeholder.
                     let production = productions_to_add
                         .splice(i..=i, std::iter::once(&Production::default()).cloned())
@@ -109,7 +130,7 @@ impl InlinedProductionMapBuilder {
                     // Replace the placeholder with the inlined productions.
                     productions_to_add.splice(
                         i..=i,
-                        grammar.variables[symbol.index].productions.iter().map(|p| {
+                        inlined_productions.iter().map(|p| {
                             let mut production = production.clone();
                             let removed_step = production
                                 .steps
@@ -199,6 +220,34 @@ pub enum ProcessInlinesError {
     Token(String),
     #[error("Rule `{0}` cannot be inlined because it is the first rule")]
     FirstRule(String),
+    #[error(
+        "Rule `{symbol}` cannot be inlined because it appears in the conflict [`{}`]. Inlined rules never appear in the parse table, so the conflict would never match",
+        .conflict.join("`, `")
+    )]
+    InlinedConflict {
+        symbol: String,
+        conflict: Vec<String>,
+    },
+}
+
+impl ProcessInlinesError {
+    pub(super) const fn code(&self) -> &'static str {
+        match self {
+            Self::ExternalToken(_) => "inlined-external-token",
+            Self::Token(_) => "inlined-token",
+            Self::FirstRule(_) => "inlined-start-rule",
+            Self::InlinedConflict { .. } => "inlined-conflict",
+        }
+    }
+
+    pub(super) fn rule(&self) -> Option<&str> {
+        match self {
+            Self::ExternalToken(rule)
+            | Self::Token(rule)
+            | Self::FirstRule(rule)
+            | Self::InlinedConflict { symbol: rule, .. } => Some(rule),
+        }
+    }
 }
 
 pub(super) fn process_inlines(
@@ -224,11 +273,27 @@ pub(super) fn process_inlines(
             }
             _ => {}
         }
+        if let Some(conflict) = grammar
+            .expected_conflicts
+            .iter()
+            .find(|conflict| conflict.contains(symbol))
+        {
+            let symbol_name = |symbol: &Symbol| match symbol.kind {
+                SymbolType::External => grammar.external_tokens[symbol.index].name.clone(),
+                SymbolType::Terminal => lexical_grammar.variables[symbol.index].name.clone(),
+                _ => grammar.variables[symbol.index].name.clone(),
+            };
+            Err(ProcessInlinesError::InlinedConflict {
+                symbol: symbol_name(symbol),
+                conflict: conflict.iter().map(symbol_name).collect(),
+            })?;
+        }
     }
 
     Ok(InlinedProductionMapBuilder {
         productions: Vec::new(),
         production_indices_by_step_id: HashMap::new(),
+        usage_by_symbol: HashMap::new(),
     }
     .build(grammar))
 }
@@ -238,7 +303,7 @@ mod tests {
     use super::*;
     use crate::{
         grammars::{LexicalVariable, SyntaxVariable, VariableType},
-        rules::{Associativity, Precedence, Symbol},
+        rules::{Associativity, Precedence},
     };
 
     #[test]
@@ -426,6 +491,26 @@ mod tests {
                 ],
             },]
         );
+
+        // Variable 2 is inlined into both of the productions produced by inlining variable 1,
+        // and variable 3 is only inlined into one of them.
+        assert_eq!(
+            [1, 2, 3].map(|index| inline_map.usage_by_symbol[&Symbol::non_terminal(index)]),
+            [
+                InlineUsage {
+                    inlined_into_count: 1,
+                    production_count: 2,
+                },
+                InlineUsage {
+                    inlined_into_count: 2,
+                    production_count: 2,
+                },
+                InlineUsage {
+                    inlined_into_count: 1,
+                    production_count: 1,
+                },
+            ]
+        );
     }
 
     #[test]
@@ -555,4 +640,73 @@ mod tests {
             panic!("expected an error, but got none");
         }
     }
+
+    #[test]
+    fn test_error_when_inlining_rules_in_conflicts() {
+        let mut grammar = SyntaxGrammar {
+            variables: vec![
+                SyntaxVariable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![
+                        Production {
+                            dynamic_precedence: 0,
+                            steps: vec![ProductionStep::new(Symbol::non_terminal(1))],
+                        },
+                        Production {
+                            dynamic_precedence: 0,
+                            steps: vec![ProductionStep::new(Symbol::non_terminal(2))],
+                        },
+                    ],
+                },
+                SyntaxVariable {
+                    name: "_call".to_string(),
+                    kind: VariableType::Hidden,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: vec![
+                            ProductionStep::new(Symbol::terminal(0)),
+                            ProductionStep::new(Symbol::terminal(1)),
+                        ],
+                    }],
+                },
+                SyntaxVariable {
+                    name: "member".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: vec![ProductionStep::new(Symbol::terminal(0))],
+                    }],
+                },
+            ],
+            expected_conflicts: vec![vec![Symbol::non_terminal(1), Symbol::non_terminal(2)]],
+            variables_to_inline: vec![Symbol::non_terminal(1)],
+            ..Default::default()
+        };
+
+        let error = process_inlines(&grammar, &LexicalGrammar::default())
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Rule `_call` cannot be inlined because it appears in the conflict [`_call`, `member`]. Inlined rules never appear in the parse table, so the conflict would never match"
+        );
+
+        // Inlining the other rule is still allowed once the conflict doesn't mention the
+        // inlined rule.
+        grammar.variables_to_inline = vec![Symbol::non_terminal(2)];
+        grammar.expected_conflicts = vec![vec![Symbol::non_terminal(0), Symbol::non_terminal(1)]];
+        let inline_map = process_inlines(&grammar, &LexicalGrammar::default()).unwrap();
+        assert_eq!(
+            inline_map
+                .inlined_productions(&grammar.variables[0].productions[1], 0)
+                .unwrap()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![Production {
+                dynamic_precedence: 0,
+                steps: vec![ProductionStep::new(Symbol::terminal(0))],
+            }]
+        );
+    }
 }
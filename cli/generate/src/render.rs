@@ -2,10 +2,12 @@ use std::{
     cmp,
     collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fmt::Write,
-    mem::swap,
+    mem::{self, swap},
+    ops::Range,
 };
 
 use indoc::indoc;
+use serde::Serialize;
 
 use super::{
     build_tables::Tables,
@@ -13,6 +15,7 @@ use super::{
     nfa::CharacterSet,
     node_types::ChildType,
     rules::{Alias, AliasMap, Symbol, SymbolType, TokenSet},
+    stats::GrammarStats,
     tables::{
         AdvanceAction, FieldLocation, GotoAction, LexState, LexTable, ParseAction, ParseTable,
         ParseTableEntry,
@@ -20,9 +23,16 @@ use super::{
 };
 
 const SMALL_STATE_THRESHOLD: usize = 64;
-pub const ABI_VERSION_MIN: usize = 14;
+pub const ABI_VERSION_MIN: usize = 13;
 pub const ABI_VERSION_MAX: usize = tree_sitter::LANGUAGE_VERSION;
+const ABI_VERSION_WITH_PRIMARY_STATES: usize = 14;
 const ABI_VERSION_WITH_RESERVED_WORDS: usize = 15;
+pub const ABI_VERSION_WITH_METADATA: usize = 15;
+pub const ABI_VERSION_WITH_MULTIPLE_WORD_TOKENS: usize = 16;
+// The sizes of the `TSParseActionEntry`, `TSLexMode` and `TSLexerMode` structs in `parser.h`.
+const PARSE_ACTION_ENTRY_SIZE: usize = 8;
+const LEX_MODE_SIZE: usize = 4;
+const LEXER_MODE_SIZE: usize = 6;
 const BUILD_VERSION: &str = env!("CARGO_PKG_VERSION");
 const BUILD_SHA: Option<&'static str> = option_env!("BUILD_SHA");
 
@@ -87,6 +97,10 @@ struct Generator {
     supertype_map: BTreeMap<String, Vec<ChildType>>,
     abi_version: usize,
     metadata: Option<Metadata>,
+    table_size: usize,
+    split_output: Option<usize>,
+    preamble_length: usize,
+    split_sections: Vec<SplitSection>,
 }
 
 struct LargeCharacterSetInfo {
@@ -100,14 +114,36 @@ struct Metadata {
     patch_version: u8,
 }
 
+/// A table that can be moved into a separate file when the output is split, along with the
+/// declarations that `parser.c` needs in order to refer to it.
+struct SplitSection {
+    range: Range<usize>,
+    declarations: String,
+}
+
+// The functions and tables that can be moved into separate files. When the output is split,
+// they are given names that are unique to the language, because they can't be `static`.
+const SPLIT_NAMES: [&str; 6] = [
+    "ts_lex",
+    "ts_lex_keywords",
+    "ts_parse_table",
+    "ts_small_parse_table",
+    "ts_small_parse_table_map",
+    "ts_parse_actions",
+];
+
 impl Generator {
-    fn generate(mut self) -> String {
+    fn generate(mut self, stats: &mut GrammarStats) -> (String, Vec<String>) {
         self.init();
         self.add_header();
         self.add_includes();
         self.add_pragmas();
         self.add_stats();
         self.add_symbol_enum();
+        if self.split_output.is_some() {
+            self.add_split_names();
+        }
+        self.preamble_length = self.buffer.len();
         self.add_symbol_names_list();
         self.add_unique_symbol_map();
         self.add_symbol_metadata_list();
@@ -123,7 +159,9 @@ impl Generator {
         }
 
         self.add_non_terminal_alias_map();
-        self.add_primary_state_id_list();
+        if self.abi_version >= ABI_VERSION_WITH_PRIMARY_STATES {
+            self.add_primary_state_id_list();
+        }
 
         if self.abi_version >= ABI_VERSION_WITH_RESERVED_WORDS && !self.supertype_map.is_empty() {
             self.add_supertype_map();
@@ -135,7 +173,7 @@ impl Generator {
         swap(&mut main_lex_table, &mut self.main_lex_table);
         self.add_lex_function("ts_lex", main_lex_table);
 
-        if self.syntax_grammar.word_token.is_some() {
+        if !self.syntax_grammar.word_tokens.is_empty() {
             let mut keyword_lex_table = LexTable::default();
             swap(&mut keyword_lex_table, &mut self.keyword_lex_table);
             self.add_lex_function("ts_lex_keywords", keyword_lex_table);
@@ -151,6 +189,21 @@ impl Generator {
         }
         self.buffer.push_str(&lex_functions);
 
+        let mut lex_declarations = "bool ts_lex(TSLexer *lexer, TSStateId state);\n".to_string();
+        if !self.syntax_grammar.word_tokens.is_empty() {
+            lex_declarations += "bool ts_lex_keywords(TSLexer *lexer, TSStateId state);\n";
+        }
+        self.split_sections.push(SplitSection {
+            range: buffer_offset_before_lex_functions..self.buffer.len(),
+            declarations: lex_declarations,
+        });
+
+        if self.abi_version >= ABI_VERSION_WITH_MULTIPLE_WORD_TOKENS
+            && self.syntax_grammar.word_tokens.len() > 1
+        {
+            self.add_keyword_capture_tokens();
+        }
+
         self.add_lex_modes();
 
         if self.abi_version >= ABI_VERSION_WITH_RESERVED_WORDS && self.reserved_word_sets.len() > 1
@@ -168,6 +221,149 @@ impl Generator {
 
         self.add_parser_export();
 
+        stats.table_size_bytes = self.table_size;
+        if let Some(file_count) = self.split_output {
+            self.split_into_files(file_count)
+        } else {
+            (self.buffer, Vec::new())
+        }
+    }
+
+    /// Move the tables that were recorded as split sections out of `parser.c`, into at most
+    /// `file_count` other files. Each table is kept whole, and the largest tables are placed
+    /// first, each in the file that is smallest so far.
+    ///
+    /// Builds that can only compile `parser.c`, like cgo, can define
+    /// `TREE_SITTER_INCLUDE_PARSER_TABLES` to include the other files at its end instead, which
+    /// then skip the preamble that `parser.c` already contains.
+    fn split_into_files(mut self, file_count: usize) -> (String, Vec<String>) {
+        let preamble = &self.buffer[..self.preamble_length];
+        let mut sections = self.split_sections.iter().collect::<Vec<_>>();
+        sections.sort_by_key(|section| cmp::Reverse(section.range.len()));
+        let mut files = vec![(0, Vec::new()); file_count.min(sections.len())];
+        for section in sections {
+            let (size, file_sections) = files.iter_mut().min_by_key(|(size, _)| *size).unwrap();
+            *size += section.range.len();
+            file_sections.push(section);
+        }
+
+        let table_files = files
+            .into_iter()
+            .map(|(_, mut file_sections)| {
+                file_sections.sort_by_key(|section| section.range.start);
+                let mut code = "#ifndef TREE_SITTER_INCLUDE_PARSER_TABLES\n".to_string();
+                code += preamble;
+                code += "#endif\n\n";
+                for section in file_sections {
+                    code += &section.declarations;
+                    code += "\n";
+                    code += &self.buffer[section.range.clone()];
+                }
+                code
+            })
+            .collect::<Vec<_>>();
+
+        let mut c_code = String::with_capacity(self.buffer.len());
+        let mut offset = 0;
+        for section in &self.split_sections {
+            c_code += &self.buffer[offset..section.range.start];
+            c_code += &section.declarations;
+            c_code += "\n";
+            offset = section.range.end;
+        }
+        c_code += &self.buffer[offset..];
+        c_code += "\n#ifdef TREE_SITTER_INCLUDE_PARSER_TABLES\n";
+        for index in 0..table_files.len() {
+            writeln!(c_code, "#include \"parser_tables_{index}.c\"").unwrap();
+        }
+        c_code += "#endif\n";
+        self.buffer = c_code;
+        (self.buffer, table_files)
+    }
+
+    fn add_split_names(&mut self) {
+        for name in SPLIT_NAMES {
+            add_line!(
+                self,
+                "#define {name} tree_sitter_{}_{}",
+                self.language_name,
+                name.trim_start_matches("ts_")
+            );
+        }
+        add_line!(self, "");
+    }
+
+    /// The storage class of the tables that can be moved into separate files.
+    const fn split_linkage(&self) -> &'static str {
+        if self.split_output.is_some() {
+            ""
+        } else {
+            "static "
+        }
+    }
+
+    fn generate_external_scanner_header(mut self) -> String {
+        self.add_header();
+        add_line!(self, "#ifndef TREE_SITTER_EXTERNAL_SCANNER_SYMBOLS_H_");
+        add_line!(self, "#define TREE_SITTER_EXTERNAL_SCANNER_SYMBOLS_H_");
+        add_line!(self, "");
+        add_line!(
+            self,
+            "#define TS_EXTERNAL_SYMBOL_COUNT {}",
+            self.syntax_grammar.external_tokens.len()
+        );
+        add_line!(self, "");
+
+        // Unlike the identifiers in `parser.c`, these must be unique, because the enum is
+        // compiled on its own.
+        let mut used_identifiers = HashSet::new();
+        let mut last_id = String::new();
+        add_line!(self, "enum ts_external_scanner_symbol {{");
+        indent!(self);
+        for (i, token) in self.syntax_grammar.external_tokens.iter().enumerate() {
+            let mut id = format!(
+                "ts_external_symbol_{}",
+                self.sanitize_identifier(&token.name)
+            );
+            let mut suffix_number = 1;
+            let mut suffix = String::new();
+            while used_identifiers.contains(&id) {
+                id.drain(id.len() - suffix.len()..);
+                suffix_number += 1;
+                suffix = suffix_number.to_string();
+                id += &suffix;
+            }
+            used_identifiers.insert(id.clone());
+
+            add_whitespace!(self);
+            add!(self, "{id} = {i},");
+            if token.kind == VariableType::Anonymous {
+                add!(self, " // \"{}\"", self.sanitize_string(&token.name));
+            }
+            self.buffer += "\n";
+            last_id = id;
+        }
+        dedent!(self);
+        add_line!(self, "}};");
+        add_line!(self, "");
+
+        let message = "the external scanner symbols are out of date, regenerate the parser";
+        add_line!(self, "#if defined(__cplusplus)");
+        add_line!(
+            self,
+            "static_assert({last_id} + 1 == TS_EXTERNAL_SYMBOL_COUNT, \"{message}\");"
+        );
+        add_line!(
+            self,
+            "#elif defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L"
+        );
+        add_line!(
+            self,
+            "_Static_assert({last_id} + 1 == TS_EXTERNAL_SYMBOL_COUNT, \"{message}\");"
+        );
+        add_line!(self, "#endif");
+        add_line!(self, "");
+        add_line!(self, "#endif  // TREE_SITTER_EXTERNAL_SCANNER_SYMBOLS_H_");
         self.buffer
     }
 
@@ -746,6 +942,20 @@ impl Generator {
         add_line!(self, "");
     }
 
+    fn add_keyword_capture_tokens(&mut self) {
+        add_line!(
+            self,
+            "static const TSSymbol ts_keyword_capture_tokens[] = {{"
+        );
+        indent!(self);
+        for word_token in &self.syntax_grammar.word_tokens {
+            add_line!(self, "{},", self.symbol_ids[word_token]);
+        }
+        dedent!(self);
+        add_line!(self, "}};");
+        add_line!(self, "");
+    }
+
     fn add_supertype_map(&mut self) {
         add_line!(
             self,
@@ -826,7 +1036,8 @@ impl Generator {
     fn add_lex_function(&mut self, name: &str, lex_table: LexTable) {
         add_line!(
             self,
-            "static bool {name}(TSLexer *lexer, TSStateId state) {{",
+            "{}bool {name}(TSLexer *lexer, TSStateId state) {{",
+            self.split_linkage()
         );
         indent!(self);
 
@@ -1155,6 +1366,12 @@ impl Generator {
             }
         );
         indent!(self);
+        self.table_size += self.parse_table.states.len()
+            * if self.abi_version >= ABI_VERSION_WITH_RESERVED_WORDS {
+                LEXER_MODE_SIZE
+            } else {
+                LEX_MODE_SIZE
+            };
         for (i, state) in self.parse_table.states.iter().enumerate() {
             add_whitespace!(self);
             add!(self, "[{i}] = {{");
@@ -1289,9 +1506,11 @@ impl Generator {
             &mut next_parse_action_list_index,
         );
 
+        let section_start = self.buffer.len();
         add_line!(
             self,
-            "static const uint16_t ts_parse_table[LARGE_STATE_COUNT][SYMBOL_COUNT] = {{",
+            "{}const uint16_t ts_parse_table[LARGE_STATE_COUNT][SYMBOL_COUNT] = {{",
+            self.split_linkage()
         );
         indent!(self);
 
@@ -1345,9 +1564,22 @@ impl Generator {
         dedent!(self);
         add_line!(self, "}};");
         add_line!(self, "");
+        self.split_sections.push(SplitSection {
+            range: section_start..self.buffer.len(),
+            declarations:
+                "extern const uint16_t ts_parse_table[LARGE_STATE_COUNT][SYMBOL_COUNT];\n"
+                    .to_string(),
+        });
+        self.table_size +=
+            self.large_state_count * self.parse_table.symbols.len() * mem::size_of::<u16>();
 
         if self.large_state_count < self.parse_table.states.len() {
-            add_line!(self, "static const uint16_t ts_small_parse_table[] = {{");
+            let section_start = self.buffer.len();
+            add_line!(
+                self,
+                "{}const uint16_t ts_small_parse_table[] = {{",
+                self.split_linkage()
+            );
             indent!(self);
 
             let mut next_table_index = 0;
@@ -1423,10 +1655,17 @@ impl Generator {
             dedent!(self);
             add_line!(self, "}};");
             add_line!(self, "");
+            self.split_sections.push(SplitSection {
+                range: section_start..self.buffer.len(),
+                declarations: "extern const uint16_t ts_small_parse_table[];\n".to_string(),
+            });
+            self.table_size += next_table_index * mem::size_of::<u16>();
 
+            let section_start = self.buffer.len();
             add_line!(
                 self,
-                "static const uint32_t ts_small_parse_table_map[] = {{"
+                "{}const uint32_t ts_small_parse_table_map[] = {{",
+                self.split_linkage()
             );
             indent!(self);
             for i in self.large_state_count..self.parse_table.states.len() {
@@ -1439,6 +1678,11 @@ impl Generator {
             dedent!(self);
             add_line!(self, "}};");
             add_line!(self, "");
+            self.split_sections.push(SplitSection {
+                range: section_start..self.buffer.len(),
+                declarations: "extern const uint32_t ts_small_parse_table_map[];\n".to_string(),
+            });
+            self.table_size += small_state_indices.len() * mem::size_of::<u32>();
         }
 
         let mut parse_table_entries = parse_table_entries
@@ -1450,12 +1694,15 @@ impl Generator {
     }
 
     fn add_parse_action_list(&mut self, parse_table_entries: Vec<(usize, ParseTableEntry)>) {
+        let section_start = self.buffer.len();
         add_line!(
             self,
-            "static const TSParseActionEntry ts_parse_actions[] = {{"
+            "{}const TSParseActionEntry ts_parse_actions[] = {{",
+            self.split_linkage()
         );
         indent!(self);
         for (i, entry) in parse_table_entries {
+            self.table_size += (1 + entry.actions.len()) * PARSE_ACTION_ENTRY_SIZE;
             add!(
                 self,
                 "  [{i}] = {{.entry = {{.count = {}, .reusable = {}}}}},",
@@ -1499,6 +1746,10 @@ impl Generator {
         dedent!(self);
         add_line!(self, "}};");
         add_line!(self, "");
+        self.split_sections.push(SplitSection {
+            range: section_start..self.buffer.len(),
+            declarations: "extern const TSParseActionEntry ts_parse_actions[];\n".to_string(),
+        });
     }
 
     fn add_parser_export(&mut self) {
@@ -1595,12 +1846,12 @@ impl Generator {
         // Lexing
         add_line!(self, ".lex_modes = (const void*)ts_lex_modes,");
         add_line!(self, ".lex_fn = ts_lex,");
-        if let Some(keyword_capture_token) = self.syntax_grammar.word_token {
+        if let Some(keyword_capture_token) = self.syntax_grammar.word_tokens.first() {
             add_line!(self, ".keyword_lex_fn = ts_lex_keywords,");
             add_line!(
                 self,
                 ".keyword_capture_token = {},",
-                self.symbol_ids[&keyword_capture_token]
+                self.symbol_ids[keyword_capture_token]
             );
         }
 
@@ -1618,7 +1869,9 @@ impl Generator {
             add_line!(self, "}},");
         }
 
-        add_line!(self, ".primary_state_ids = ts_primary_state_ids,");
+        if self.abi_version >= ABI_VERSION_WITH_PRIMARY_STATES {
+            add_line!(self, ".primary_state_ids = ts_primary_state_ids,");
+        }
 
         if self.abi_version >= ABI_VERSION_WITH_RESERVED_WORDS {
             add_line!(self, ".name = \"{}\",", self.language_name);
@@ -1656,6 +1909,17 @@ impl Generator {
             add_line!(self, "}},");
         }
 
+        if self.abi_version >= ABI_VERSION_WITH_MULTIPLE_WORD_TOKENS
+            && self.syntax_grammar.word_tokens.len() > 1
+        {
+            add_line!(self, ".keyword_capture_tokens = ts_keyword_capture_tokens,");
+            add_line!(
+                self,
+                ".keyword_capture_token_count = {},",
+                self.syntax_grammar.word_tokens.len()
+            );
+        }
+
         dedent!(self);
         add_line!(self, "}};");
         add_line!(self, "return &language;");
@@ -1914,7 +2178,93 @@ impl Generator {
     }
 }
 
-/// Returns a String of C code for the given components of a parser.
+#[derive(Debug, Serialize)]
+pub struct IncompatibleAbiError {
+    pub abi_version: usize,
+    pub features: Vec<AbiFeature>,
+}
+
+/// A feature of the grammar that requires a newer ABI version than the one that was requested.
+#[derive(Debug, Serialize)]
+pub struct AbiFeature {
+    pub description: String,
+    pub min_abi_version: usize,
+}
+
+impl std::fmt::Display for IncompatibleAbiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "The grammar uses features that can't be expressed in ABI version {}:",
+            self.abi_version
+        )?;
+        for feature in &self.features {
+            write!(
+                f,
+                "\n  {} (requires ABI version {})",
+                feature.description, feature.min_abi_version
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for IncompatibleAbiError {}
+
+/// Check that the grammar can be rendered with the given ABI version. Metadata that older ABI
+/// versions have no place for, such as the supertype map, is simply left out, but features
+/// that change how the grammar is parsed must be supported.
+pub fn check_abi_compatibility(
+    syntax_grammar: &SyntaxGrammar,
+    abi_version: usize,
+) -> Result<(), IncompatibleAbiError> {
+    let mut features = Vec::new();
+    if syntax_grammar.word_tokens.len() > 1 && abi_version < ABI_VERSION_WITH_MULTIPLE_WORD_TOKENS {
+        features.push(AbiFeature {
+            description: "multiple word tokens".to_string(),
+            min_abi_version: ABI_VERSION_WITH_MULTIPLE_WORD_TOKENS,
+        });
+    }
+    if syntax_grammar
+        .reserved_word_sets
+        .iter()
+        .any(|set| !set.is_empty())
+        && abi_version < ABI_VERSION_WITH_RESERVED_WORDS
+    {
+        features.push(AbiFeature {
+            description: "reserved words".to_string(),
+            min_abi_version: ABI_VERSION_WITH_RESERVED_WORDS,
+        });
+    }
+    if features.is_empty() {
+        Ok(())
+    } else {
+        Err(IncompatibleAbiError {
+            abi_version,
+            features,
+        })
+    }
+}
+
+/// Returns the contents of a C header that enumerates the grammar's external tokens, in
+/// the order in which they are passed to an external scanner, or `None` if the grammar
+/// doesn't have any external tokens.
+pub fn render_external_scanner_header(syntax_grammar: &SyntaxGrammar) -> Option<String> {
+    if syntax_grammar.external_tokens.is_empty() {
+        return None;
+    }
+    let generator = Generator {
+        syntax_grammar: SyntaxGrammar {
+            external_tokens: syntax_grammar.external_tokens.clone(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    Some(generator.generate_external_scanner_header())
+}
+
+/// Returns a String of C code for the given components of a parser, along with the files
+/// that its tables were moved into, if the output is split.
 ///
 /// # Arguments
 ///
@@ -1931,6 +2281,8 @@ impl Generator {
 /// * `abi_version` - The language ABI version that should be generated. Usually you want
 ///   Tree-sitter's current version, but right after making an ABI change, it may be useful to
 ///   generate code with the previous ABI.
+/// * `split_output` - The maximum number of separate files to move the parse and lex tables
+///   into, or `None` to generate a single file.
 #[allow(clippy::too_many_arguments)]
 pub fn render_c_code(
     name: &str,
@@ -1941,7 +2293,9 @@ pub fn render_c_code(
     abi_version: usize,
     semantic_version: Option<(u8, u8, u8)>,
     supertype_symbol_map: BTreeMap<Symbol, Vec<ChildType>>,
-) -> String {
+    split_output: Option<usize>,
+    stats: &mut GrammarStats,
+) -> (String, Vec<String>) {
     assert!(
         (ABI_VERSION_MIN..=ABI_VERSION_MAX).contains(&abi_version),
         "This version of Tree-sitter can only generate parsers with ABI version {ABI_VERSION_MIN} - {ABI_VERSION_MAX}, not {abi_version}",
@@ -1964,7 +2318,8 @@ pub fn render_c_code(
             patch_version,
         }),
         supertype_symbol_map,
+        split_output,
         ..Default::default()
     }
-    .generate()
+    .generate(stats)
 }
@@ -1,11 +1,11 @@
 use std::{collections::HashMap, fmt};
 
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use smallbitvec::SmallBitVec;
 
 use super::grammars::VariableType;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum SymbolType {
     External,
     End,
@@ -14,19 +14,19 @@ pub enum SymbolType {
     NonTerminal,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Associativity {
     Left,
     Right,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Alias {
     pub value: String,
     pub is_named: bool,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub enum Precedence {
     #[default]
     None,
@@ -36,6 +36,36 @@ pub enum Precedence {
 
 pub type AliasMap = HashMap<Symbol, Alias>;
 
+/// Symbols can't be used as JSON object keys, so an alias map is serialized as a list of
+/// entries, ordered by symbol.
+pub mod alias_map {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Alias, AliasMap, Symbol};
+
+    #[derive(Serialize, Deserialize)]
+    struct Entry<T, U> {
+        symbol: T,
+        alias: U,
+    }
+
+    pub fn serialize<S: Serializer>(aliases: &AliasMap, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries = aliases
+            .iter()
+            .map(|(symbol, alias)| Entry { symbol, alias })
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|entry| entry.symbol);
+        serializer.collect_seq(entries)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<AliasMap, D::Error> {
+        Ok(Vec::<Entry<Symbol, Alias>>::deserialize(deserializer)?
+            .into_iter()
+            .map(|entry| (entry.symbol, entry.alias))
+            .collect())
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize)]
 pub struct MetadataParams {
     pub precedence: Precedence,
@@ -43,11 +73,12 @@ pub struct MetadataParams {
     pub associativity: Option<Associativity>,
     pub is_token: bool,
     pub is_main_token: bool,
+    pub is_case_insensitive: bool,
     pub alias: Option<Alias>,
     pub field_name: Option<String>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Symbol {
     pub kind: SymbolType,
     pub index: usize,
@@ -91,6 +122,20 @@ impl fmt::Debug for TokenSet {
     }
 }
 
+impl Serialize for TokenSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for TokenSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Vec::<Symbol>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
 impl PartialOrd for TokenSet {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -137,6 +182,12 @@ impl Rule {
         })
     }
 
+    pub fn case_insensitive(value: String) -> Self {
+        add_metadata(Self::String(value), |params| {
+            params.is_case_insensitive = true;
+        })
+    }
+
     pub fn prec(value: Precedence, content: Self) -> Self {
         add_metadata(content, |params| {
             params.precedence = value;
@@ -0,0 +1,108 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::grammars::{InlinedProductionMap, LexicalGrammar, SyntaxGrammar};
+
+/// The number of rules that are included in the list of the largest rules.
+const LARGEST_RULE_COUNT: usize = 10;
+
+/// Metrics that describe the size and complexity of a grammar and of the parser that was
+/// generated from it.
+#[derive(Debug, Default, Serialize)]
+pub struct GrammarStats {
+    /// The number of syntax rules, before repetitions are replaced with auxiliary rules.
+    pub variable_count_before_expand_repeats: usize,
+    /// The number of syntax rules, after repetitions are replaced with auxiliary rules.
+    pub variable_count_after_expand_repeats: usize,
+    pub token_count: usize,
+    pub external_token_count: usize,
+    pub inlined_production_count: usize,
+    pub parse_state_count: usize,
+    /// The number of states in the main lex function and in the keyword lex function.
+    pub lex_state_count: usize,
+    /// The size of the parse table, parse actions and lex modes in the generated parser.
+    /// The lex functions are code rather than tables, so they aren't included.
+    pub table_size_bytes: usize,
+    pub largest_rules: Vec<RuleStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuleStats {
+    pub name: String,
+    pub production_count: usize,
+}
+
+impl GrammarStats {
+    /// Record the metrics of the prepared grammar.
+    pub(crate) fn record_grammar(
+        &mut self,
+        syntax_grammar: &SyntaxGrammar,
+        lexical_grammar: &LexicalGrammar,
+        inlines: &InlinedProductionMap,
+    ) {
+        self.token_count = lexical_grammar.variables.len();
+        self.external_token_count = syntax_grammar.external_tokens.len();
+        self.inlined_production_count = inlines.productions.len();
+
+        let mut largest_rules = syntax_grammar
+            .variables
+            .iter()
+            .map(|variable| RuleStats {
+                name: variable.name.clone(),
+                production_count: variable.productions.len(),
+            })
+            .collect::<Vec<_>>();
+        largest_rules.sort_by_key(|rule| std::cmp::Reverse(rule.production_count));
+        largest_rules.truncate(LARGEST_RULE_COUNT);
+        self.largest_rules = largest_rules;
+    }
+}
+
+impl fmt::Display for GrammarStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = [
+            (
+                "Rules (before expanding repeats)",
+                self.variable_count_before_expand_repeats,
+            ),
+            (
+                "Rules (after expanding repeats)",
+                self.variable_count_after_expand_repeats,
+            ),
+            ("Tokens", self.token_count),
+            ("External tokens", self.external_token_count),
+            ("Inlined productions", self.inlined_production_count),
+            ("Parse states", self.parse_state_count),
+            ("Lex states", self.lex_state_count),
+            ("Table size (bytes)", self.table_size_bytes),
+        ];
+        let name_width = rows
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain(
+                self.largest_rules
+                    .iter()
+                    .map(|rule| rule.name.chars().count()),
+            )
+            .max()
+            .unwrap();
+
+        writeln!(f, "Grammar statistics:\n")?;
+        for (name, value) in rows {
+            writeln!(f, "  {name:name_width$}  {value:>10}")?;
+        }
+
+        if !self.largest_rules.is_empty() {
+            writeln!(f, "\nLargest rules by production count:\n")?;
+            for rule in &self.largest_rules {
+                writeln!(
+                    f,
+                    "  {:name_width$}  {:>10}",
+                    rule.name, rule.production_count
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
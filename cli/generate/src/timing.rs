@@ -0,0 +1,159 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use serde::{Serialize, Serializer};
+
+/// The number of non-terminals that are included in the breakdown of the time spent
+/// computing item set closures.
+const CLOSURE_REPORT_RULE_COUNT: usize = 10;
+
+/// A record of how long each stage of parser generation took.
+#[derive(Debug, Serialize)]
+pub struct TimingReport {
+    pub stages: Vec<StageTiming>,
+    pub closure_rules: Vec<RuleTiming>,
+    #[serde(skip)]
+    enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StageTiming {
+    pub name: &'static str,
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
+    pub duration: Duration,
+}
+
+/// The total time spent expanding a non-terminal while computing the closures of the
+/// parse table's item sets, summed across all threads.
+#[derive(Debug, Serialize)]
+pub struct RuleTiming {
+    pub name: String,
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
+    pub duration: Duration,
+    pub expansion_count: usize,
+}
+
+impl Default for TimingReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimingReport {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            stages: Vec::new(),
+            closure_rules: Vec::new(),
+            enabled: true,
+        }
+    }
+
+    /// A report that doesn't record anything, for when no timing was requested.
+    pub(crate) const fn disabled() -> Self {
+        Self {
+            stages: Vec::new(),
+            closure_rules: Vec::new(),
+            enabled: false,
+        }
+    }
+
+    pub(crate) const fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Run one stage of parser generation, recording how long it took.
+    pub(crate) fn time<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        if !self.enabled {
+            return f();
+        }
+        let start = Instant::now();
+        let result = f();
+        self.record(name, start.elapsed());
+        result
+    }
+
+    /// Record how long a stage of parser generation took, for stages that need access to
+    /// the report themselves.
+    pub(crate) fn record(&mut self, name: &'static str, duration: Duration) {
+        if self.enabled {
+            self.stages.push(StageTiming { name, duration });
+        }
+    }
+
+    /// Record the time spent expanding each non-terminal, given the names of the
+    /// non-terminals and the time and number of expansions for each of them.
+    pub(crate) fn set_closure_timings(
+        &mut self,
+        timings: impl IntoIterator<Item = (String, (Duration, usize))>,
+    ) {
+        let mut closure_rules = timings
+            .into_iter()
+            .filter(|(_, (_, count))| *count > 0)
+            .map(|(name, (duration, expansion_count))| RuleTiming {
+                name,
+                duration,
+                expansion_count,
+            })
+            .collect::<Vec<_>>();
+        closure_rules.sort_by_key(|timing| std::cmp::Reverse(timing.duration));
+        closure_rules.truncate(CLOSURE_REPORT_RULE_COUNT);
+        self.closure_rules = closure_rules;
+    }
+}
+
+impl fmt::Display for TimingReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name_width = self
+            .stages
+            .iter()
+            .map(|stage| stage.name.len())
+            .chain(
+                self.closure_rules
+                    .iter()
+                    .map(|rule| rule.name.chars().count()),
+            )
+            .chain(["Total".len()])
+            .max()
+            .unwrap();
+
+        writeln!(f, "Time spent in each stage:\n")?;
+        for stage in &self.stages {
+            writeln!(
+                f,
+                "  {:name_width$}  {:>10.2} ms",
+                stage.name,
+                millis(stage.duration),
+            )?;
+        }
+        let total = self.stages.iter().map(|stage| stage.duration).sum();
+        writeln!(f, "  {:name_width$}  {:>10.2} ms", "Total", millis(total))?;
+
+        if !self.closure_rules.is_empty() {
+            writeln!(
+                f,
+                "\nTime spent expanding each rule in item set closures:\n"
+            )?;
+            for rule in &self.closure_rules {
+                writeln!(
+                    f,
+                    "  {:name_width$}  {:>10.2} ms  ({} expansions)",
+                    rule.name,
+                    millis(rule.duration),
+                    rule.expansion_count,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn serialize_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(millis(*duration))
+}
@@ -353,6 +353,7 @@ pub struct CompileConfig<'a> {
     pub header_paths: Vec<&'a Path>,
     pub parser_path: PathBuf,
     pub scanner_path: Option<PathBuf>,
+    pub parser_table_paths: Vec<PathBuf>,
     pub external_files: Option<&'a [PathBuf]>,
     pub output_path: Option<PathBuf>,
     pub flags: &'a [&'a str],
@@ -372,6 +373,7 @@ impl<'a> CompileConfig<'a> {
             header_paths: vec![src_path],
             parser_path: src_path.join("parser.c"),
             scanner_path: None,
+            parser_table_paths: Vec::new(),
             external_files: externals,
             output_path,
             flags: &[],
@@ -703,8 +705,10 @@ impl Loader {
 
         let parser_path = config.src_path.join("parser.c");
         config.scanner_path = self.get_scanner_path(config.src_path);
+        config.parser_table_paths = self.get_parser_table_paths(config.src_path)?;
 
         let mut paths_to_check = vec![parser_path];
+        paths_to_check.extend(config.parser_table_paths.iter().cloned());
 
         if let Some(scanner_path) = config.scanner_path.as_ref() {
             paths_to_check.push(scanner_path.clone());
@@ -807,6 +811,7 @@ impl Loader {
             language_fn()
         };
         mem::forget(library);
+        check_language_name(&language, &config.name, &output_path)?;
         Ok(language)
     }
 
@@ -830,6 +835,7 @@ impl Loader {
         if let Some(scanner_path) = config.scanner_path.as_ref() {
             cc_config.file(scanner_path);
         }
+        cc_config.files(&config.parser_table_paths);
 
         if self.debug_build {
             cc_config.opt_level(0).extra_warnings(true);
@@ -1095,6 +1101,9 @@ impl Loader {
         }
 
         command.arg("parser.c");
+        for path in self.get_parser_table_paths(src_path)? {
+            command.arg(path.file_name().unwrap());
+        }
         let status = command
             .spawn()
             .with_context(|| "Failed to run emcc command")?
@@ -1394,6 +1403,28 @@ impl Loader {
         let path = src_path.join("scanner.c");
         path.exists().then_some(path)
     }
+
+    /// Get the `parser_tables_<N>.c` files that a parser's tables were moved into when it was
+    /// generated with `--split-output`, in order.
+    pub fn get_parser_table_paths(&self, src_path: &Path) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(src_path)
+            .with_context(|| format!("Failed to read source directory {src_path:?}"))?
+        {
+            let path = entry?.path();
+            let index = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("parser_tables_"))
+                .and_then(|name| name.strip_suffix(".c"))
+                .and_then(|index| index.parse::<usize>().ok());
+            if let Some(index) = index {
+                paths.push((index, path));
+            }
+        }
+        paths.sort_unstable();
+        Ok(paths.into_iter().map(|(_, path)| path).collect())
+    }
 }
 
 impl LanguageConfiguration<'_> {
@@ -1626,6 +1657,24 @@ fn mtime(path: &Path) -> Result<SystemTime> {
     Ok(fs::metadata(path)?.modified()?)
 }
 
+/// Check that a language loaded from a library is the one that was asked for, using the name
+/// that newer parsers embed. Older parsers don't have a name, so they can't be checked.
+fn check_language_name(
+    language: &Language,
+    expected_name: &str,
+    library_path: &Path,
+) -> Result<()> {
+    let Some(name) = language.name() else {
+        return Ok(());
+    };
+    if replace_dashes_with_underscores(name) != replace_dashes_with_underscores(expected_name) {
+        return Err(anyhow!(
+            "The library {library_path:?} provides the language `{name}`, but `{expected_name}` was expected"
+        ));
+    }
+    Ok(())
+}
+
 fn replace_dashes_with_underscores(name: &str) -> String {
     let mut result = String::with_capacity(name.len());
     for c in name.chars() {
@@ -1,5 +1,6 @@
 type AliasRule = { type: 'ALIAS'; named: boolean; content: Rule; value: string };
 type BlankRule = { type: 'BLANK' };
+type CaseInsensitiveRule = { type: 'CASE_INSENSITIVE'; value: string };
 type ChoiceRule = { type: 'CHOICE'; members: Rule[] };
 type FieldRule = { type: 'FIELD'; name: string; content: Rule };
 type ImmediateTokenRule = { type: 'IMMEDIATE_TOKEN'; content: Rule };
@@ -18,6 +19,7 @@ type TokenRule = { type: 'TOKEN'; content: Rule };
 type Rule =
   | AliasRule
   | BlankRule
+  | CaseInsensitiveRule
   | ChoiceRule
   | FieldRule
   | ImmediateTokenRule
@@ -160,13 +162,17 @@ interface Grammar<
 
   /**
    * The name of a token that will match keywords for the purpose of the
-   * keyword extraction optimization.
+   * keyword extraction optimization. Grammars that need separate sets of
+   * keywords can return an array of tokens, each of which captures the
+   * keywords that it matches.
    *
    * @param $ grammar rules
    *
    * @see https://tree-sitter.github.io/tree-sitter/creating-parsers/3-writing-the-grammar#keyword-extraction
    */
-  word?: ($: GrammarSymbols<RuleName | BaseGrammarRuleName>) => RuleOrLiteral;
+  word?: (
+    $: GrammarSymbols<RuleName | BaseGrammarRuleName>,
+  ) => RuleOrLiteral | RuleOrLiteral[];
 }
 
 type GrammarSchema<RuleName extends string> = {
@@ -203,6 +209,15 @@ declare function alias(
  */
 declare function blank(): BlankRule;
 
+/**
+ * Creates a rule that matches a string literal regardless of case. The
+ * resulting token is named after the literal as it is written, and can
+ * be used as a keyword just like an ordinary string.
+ *
+ * @param value string to match
+ */
+declare function caseInsensitive(value: string): CaseInsensitiveRule;
+
 /**
  * Assigns a field name to the child node(s) matched by the given rule.
  * In the resulting syntax tree, you can then use that field name to
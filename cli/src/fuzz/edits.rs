@@ -1,3 +1,5 @@
+use std::fmt;
+
 use super::random::Rand;
 
 #[derive(Debug)]
@@ -7,6 +9,18 @@ pub struct Edit {
     pub inserted_text: Vec<u8>,
 }
 
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "at byte {}, delete {} bytes and insert {:?}",
+            self.position,
+            self.deleted_length,
+            String::from_utf8_lossy(&self.inserted_text)
+        )
+    }
+}
+
 #[must_use]
 pub fn invert_edit(input: &[u8], edit: &Edit) -> Edit {
     let position = edit.position;
@@ -59,3 +73,22 @@ pub fn get_random_edit(rand: &mut Rand, input: &[u8]) -> Edit {
         }
     }
 }
+
+/// Widens an edit so that neither of its ends falls inside of a UTF-8 character.
+#[must_use]
+pub fn align_edit_to_char_boundaries(input: &[u8], edit: Edit) -> Edit {
+    let is_continuation_byte = |i: usize| input.get(i).is_some_and(|b| b & 0xC0 == 0x80);
+    let mut start = edit.position;
+    let mut end = edit.position + edit.deleted_length;
+    while start > 0 && is_continuation_byte(start) {
+        start -= 1;
+    }
+    while is_continuation_byte(end) {
+        end += 1;
+    }
+    Edit {
+        position: start,
+        deleted_length: end - start,
+        inserted_text: edit.inserted_text,
+    }
+}
@@ -1,22 +1,30 @@
 use std::{
     collections::{HashMap, HashSet},
     fmt::Write,
-    fs,
-    io::{self, Write as _},
+    fs, io,
+    ops::Range,
     path::{self, Path, PathBuf},
     str,
-    sync::{atomic::AtomicUsize, Arc},
-    time::Instant,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 use ansi_colours::{ansi256_from_rgb, rgb_from_ansi256};
 use anstyle::{Ansi256Color, AnsiColor, Color, Effects, RgbColor};
 use anyhow::Result;
+use clap::ValueEnum;
 use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{json, Value};
+use tree_sitter::Point;
 use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter, HtmlRenderer};
 use tree_sitter_loader::Loader;
 
+use crate::parse::Encoding;
+
 pub const HTML_HEAD_HEADER: &str = "
 <!doctype HTML>
 <head>
@@ -63,10 +71,58 @@ pub struct ThemeConfig {
     pub theme: Theme,
 }
 
+/// How many colors the terminal can show, which decides whether a theme's hex colors are printed
+/// as they are, or approximated with the closest of the 256 ANSI colors
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// The 256 ANSI colors
+    #[value(name = "256")]
+    Ansi256,
+    /// 24-bit colors
+    Truecolor,
+}
+
+impl ColorDepth {
+    /// The color depth that the terminal reports with the `COLORTERM` environment variable
+    #[must_use]
+    pub fn detect() -> Self {
+        if terminal_supports_truecolor() {
+            Self::Truecolor
+        } else {
+            Self::Ansi256
+        }
+    }
+}
+
+impl Style {
+    /// Approximate the style's hex color with an ANSI color if the terminal can't show it.
+    pub fn set_color_depth(&mut self, depth: ColorDepth) {
+        if let Some(Color::Rgb(RgbColor(red, green, blue))) = self.ansi.get_fg_color() {
+            if depth == ColorDepth::Ansi256 {
+                let ansi256 = Color::Ansi256(Ansi256Color(ansi256_from_rgb((red, green, blue))));
+                self.ansi = self.ansi.fg_color(Some(ansi256));
+            }
+        }
+    }
+}
+
 impl Theme {
     pub fn load(path: &path::Path) -> io::Result<Self> {
-        let json = fs::read_to_string(path)?;
-        Ok(serde_json::from_str(&json).unwrap_or_default())
+        let json = serde_json::from_str::<Value>(&fs::read_to_string(path)?)?;
+        if !json.is_object() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "A theme must be an object with a style for each highlight name",
+            ));
+        }
+        Ok(serde_json::from_value(json)?)
+    }
+
+    /// Approximate the theme's hex colors with ANSI colors if the terminal can't show them.
+    pub fn set_color_depth(&mut self, depth: ColorDepth) {
+        for style in &mut self.styles {
+            style.set_color_depth(depth);
+        }
     }
 
     #[must_use]
@@ -86,6 +142,7 @@ impl<'de> Deserialize<'de> for Theme {
             highlight_names.reserve(colors.len());
             styles.reserve(colors.len());
             for (name, style_value) in colors {
+                warn_about_invalid_style(&name, &style_value);
                 let mut style = Style::default();
                 parse_style(&mut style, style_value);
                 highlight_names.push(name);
@@ -219,11 +276,42 @@ fn parse_style(style: &mut Style, json: Value) {
     } else {
         style.css = None;
     }
+}
 
-    if let Some(Color::Rgb(RgbColor(red, green, blue))) = style.ansi.get_fg_color() {
-        if !terminal_supports_truecolor() {
-            let ansi256 = Color::Ansi256(Ansi256Color(ansi256_from_rgb((red, green, blue))));
-            style.ansi = style.ansi.fg_color(Some(ansi256));
+/// Print a warning for the properties of a theme's style that aren't used, so that a typo in a
+/// theme doesn't just leave the text unstyled.
+fn warn_about_invalid_style(name: &str, json: &Value) {
+    match json {
+        Value::Null => {}
+        Value::Object(entries) => {
+            for (property_name, value) in entries {
+                match property_name.as_str() {
+                    "bold" | "italic" | "underline" => {
+                        if !value.is_boolean() {
+                            eprintln!(
+                                "Warning: The `{property_name}` property of the theme's style for \
+                                 `{name}` should be true or false, not {value}"
+                            );
+                        }
+                    }
+                    "color" => {
+                        if parse_color(value.clone()).is_none() {
+                            eprintln!(
+                                "Warning: Invalid color {value} in the theme's style for `{name}`"
+                            );
+                        }
+                    }
+                    _ => eprintln!(
+                        "Warning: Unknown property `{property_name}` in the theme's style for \
+                         `{name}`"
+                    ),
+                }
+            }
+        }
+        value => {
+            if parse_color(value.clone()).is_none() {
+                eprintln!("Warning: Invalid color {value} in the theme's style for `{name}`");
+            }
         }
     }
 }
@@ -307,6 +395,187 @@ fn write_color(buffer: &mut String, color: Color) {
     }
 }
 
+/// The class of the HTML elements for the highlight `name` with `--css-classes`, such as
+/// `ts-function-builtin` for `function.builtin`.
+#[must_use]
+pub fn css_class_name(name: &str) -> String {
+    format!("ts-{}", name.replace('.', "-"))
+}
+
+/// A stylesheet with a rule for the class of each highlight in the theme that has a style, so that
+/// HTML output with `--css-classes` looks the same as with inline styles.
+#[must_use]
+pub fn theme_stylesheet(theme: &Theme) -> String {
+    let mut rules = theme
+        .highlight_names
+        .iter()
+        .zip(&theme.styles)
+        .filter_map(|(name, style)| {
+            let css = style.css.as_deref()?.trim_end_matches(';');
+            Some(format!(".{} {{ {css}; }}\n", css_class_name(name)))
+        })
+        .collect::<Vec<_>>();
+    rules.sort();
+    rules.concat()
+}
+
+/// Print each highlight name that the queries in `config` can produce, along with the name in the
+/// theme that it is styled with, so that the names that a theme is missing stand out.
+pub fn print_theme_keys(theme: &Theme, config: &HighlightConfiguration) {
+    let mut names = config
+        .names()
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| {
+            !name.starts_with('_') && !name.starts_with("injection.") && !name.starts_with("local.")
+        })
+        .map(|(i, name)| {
+            let highlight = config.highlight_for_capture(i);
+            (
+                *name,
+                highlight.map(|h| theme.highlight_names[h.0].as_str()),
+            )
+        })
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+
+    let width = names.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, theme_name) in names {
+        println!("{name:width$}  {}", theme_name.unwrap_or("(missing)"));
+    }
+}
+
+/// A highlight that is active in the JSON output, with the language layer that it comes from
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+struct ActiveHighlight<'a> {
+    highlight: &'a str,
+    language: &'a str,
+    depth: usize,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum JsonEvent<'a> {
+    Source { start: usize, end: usize },
+    HighlightStart(ActiveHighlight<'a>),
+    HighlightEnd(ActiveHighlight<'a>),
+}
+
+#[derive(Serialize)]
+struct JsonEventRecord<'a> {
+    file: &'a str,
+    #[serde(flatten)]
+    event: JsonEvent<'a>,
+}
+
+/// Writes highlight events as JSON objects, one per line. With `merge_adjacent`, the `Source`
+/// events of adjacent text that has the same highlights are written as a single event, and
+/// highlights without any text are left out.
+struct JsonEventWriter<'a, W: io::Write> {
+    output: W,
+    file: &'a str,
+    merge_adjacent: bool,
+    stack: Vec<ActiveHighlight<'a>>,
+    /// The highlights that have been started in the output, when merging
+    written_stack: Vec<ActiveHighlight<'a>>,
+    /// The source text that hasn't been written yet when merging, with its highlights
+    pending_source: Option<(Range<usize>, Vec<ActiveHighlight<'a>>)>,
+}
+
+impl<'a, W: io::Write> JsonEventWriter<'a, W> {
+    const fn new(output: W, file: &'a str, merge_adjacent: bool) -> Self {
+        Self {
+            output,
+            file,
+            merge_adjacent,
+            stack: Vec::new(),
+            written_stack: Vec::new(),
+            pending_source: None,
+        }
+    }
+
+    fn start(&mut self, highlight: ActiveHighlight<'a>) -> Result<()> {
+        self.stack.push(highlight);
+        if !self.merge_adjacent {
+            self.write(JsonEvent::HighlightStart(highlight))?;
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        let highlight = self.stack.pop().unwrap();
+        if !self.merge_adjacent {
+            self.write(JsonEvent::HighlightEnd(highlight))?;
+        }
+        Ok(())
+    }
+
+    fn source(&mut self, range: Range<usize>) -> Result<()> {
+        if !self.merge_adjacent {
+            return self.write(JsonEvent::Source {
+                start: range.start,
+                end: range.end,
+            });
+        }
+        match &mut self.pending_source {
+            Some((pending_range, stack))
+                if pending_range.end == range.start && *stack == self.stack =>
+            {
+                pending_range.end = range.end;
+            }
+            _ => {
+                self.flush()?;
+                self.pending_source = Some((range, self.stack.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the pending source text, along with the events that change the highlights in the
+    /// output to its highlights.
+    fn flush(&mut self) -> Result<()> {
+        let Some((range, stack)) = self.pending_source.take() else {
+            return Ok(());
+        };
+        let common_len = self
+            .written_stack
+            .iter()
+            .zip(&stack)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while self.written_stack.len() > common_len {
+            let highlight = self.written_stack.pop().unwrap();
+            self.write(JsonEvent::HighlightEnd(highlight))?;
+        }
+        for highlight in &stack[common_len..] {
+            self.write(JsonEvent::HighlightStart(*highlight))?;
+        }
+        self.written_stack = stack;
+        self.write(JsonEvent::Source {
+            start: range.start,
+            end: range.end,
+        })
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.flush()?;
+        while let Some(highlight) = self.written_stack.pop() {
+            self.write(JsonEvent::HighlightEnd(highlight))?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, event: JsonEvent<'a>) -> Result<()> {
+        let record = JsonEventRecord {
+            file: self.file,
+            event,
+        };
+        writeln!(self.output, "{}", serde_json::to_string(&record)?)?;
+        Ok(())
+    }
+}
+
 fn terminal_supports_truecolor() -> bool {
     std::env::var("COLORTERM")
         .is_ok_and(|truecolor| truecolor == "truecolor" || truecolor == "24bit")
@@ -317,10 +586,28 @@ pub struct HighlightOptions {
     pub check: bool,
     pub captures_path: Option<PathBuf>,
     pub inline_styles: bool,
+    /// The stylesheet to link to from the HTML output with `--css-classes`, instead of including
+    /// the theme's rules in the document
+    pub css_file: Option<PathBuf>,
     pub html: bool,
     pub quiet: bool,
     pub print_time: bool,
     pub cancellation_flag: Arc<AtomicUsize>,
+    pub encoding: Encoding,
+    /// Only highlight these rows, as the highlighter would in an editor's viewport
+    pub row_range: Option<Range<usize>>,
+    /// Write the highlight events as JSON objects, one per line
+    pub json: bool,
+    /// Write adjacent text with the same highlights as a single event in the JSON output
+    pub merge_adjacent: bool,
+}
+
+/// The time that it took to highlight a file, returned by [`highlight_files_parallel`].
+pub struct HighlightSummary {
+    /// The name of the language that the file was highlighted as.
+    pub language_name: String,
+    pub byte_count: usize,
+    pub duration: Duration,
 }
 
 pub fn highlight(
@@ -332,66 +619,275 @@ pub fn highlight(
     opts: &HighlightOptions,
 ) -> Result<()> {
     if opts.check {
-        let names = if let Some(path) = opts.captures_path.as_deref() {
-            let file = fs::read_to_string(path)?;
-            let capture_names = file
-                .lines()
-                .filter_map(|line| {
-                    if line.trim().is_empty() || line.trim().starts_with(';') {
-                        return None;
+        check_captures(config, opts)?;
+    }
+    let summary = highlight_file(
+        &mut io::stdout().lock(),
+        path,
+        name,
+        config,
+        print_name,
+        opts,
+        |string| loader.highlight_config_for_injection_string(string),
+    )?;
+    if opts.print_time {
+        eprintln!("Time: {}ms", summary.duration.as_millis());
+    }
+    Ok(())
+}
+
+/// Highlight the given files using up to `thread_count` threads, each with its own highlighter.
+///
+/// The output of each file is written in one piece, in the order that the files were given. The
+/// configurations are shared between the threads. `injection_callback` is only called on
+/// the current thread, which looks up the injected languages for the other threads, so it can
+/// load them lazily, like [`Loader::highlight_config_for_injection_string`] does. With
+/// `--time`, the time that each file took is printed after its output, and the total time for
+/// each language is printed at the end.
+pub fn highlight_files_parallel<'a>(
+    files: &[(PathBuf, &'a HighlightConfiguration)],
+    mut injection_callback: impl FnMut(&str) -> Option<&'a HighlightConfiguration>,
+    thread_count: usize,
+    opts: &HighlightOptions,
+    out: &mut impl io::Write,
+) -> Result<Vec<HighlightSummary>> {
+    enum Message<'a> {
+        Injection(String, mpsc::Sender<Option<&'a HighlightConfiguration>>),
+        Done(usize, Vec<u8>, Result<HighlightSummary>),
+    }
+
+    let print_name = files.len() > 1;
+    let mut summaries = Vec::with_capacity(files.len());
+    let mut write_output = |index: usize, output: &[u8], result: Result<HighlightSummary>| {
+        if opts.check {
+            check_captures(files[index].1, opts)?;
+        }
+        out.write_all(output)?;
+        let summary = result?;
+        if opts.print_time {
+            eprintln!("Time: {}ms", summary.duration.as_millis());
+        }
+        Ok::<_, anyhow::Error>(summary)
+    };
+
+    if thread_count <= 1 || files.len() <= 1 {
+        for (path, config) in files {
+            let mut output = Vec::new();
+            let result = highlight_file(
+                &mut output,
+                path,
+                &path.display().to_string(),
+                config,
+                print_name,
+                opts,
+                &mut injection_callback,
+            );
+            summaries.push(write_output(summaries.len(), &output, result)?);
+        }
+    } else {
+        let next_index = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        thread::scope(|scope| -> Result<()> {
+            let (sender, receiver) = mpsc::channel();
+            for _ in 0..thread_count.min(files.len()) {
+                let sender = sender.clone();
+                let (next_index, stop) = (&next_index, &stop);
+                scope.spawn(move || {
+                    let mut injection_configs = HashMap::new();
+                    let mut injection_callback = |string: &str| {
+                        *injection_configs
+                            .entry(string.to_string())
+                            .or_insert_with(|| {
+                                let (config_sender, config_receiver) = mpsc::channel();
+                                let message = Message::Injection(string.to_string(), config_sender);
+                                sender.send(message).ok()?;
+                                config_receiver.recv().ok().flatten()
+                            })
+                    };
+                    while !stop.load(Ordering::SeqCst) {
+                        let index = next_index.fetch_add(1, Ordering::SeqCst);
+                        let Some((path, config)) = files.get(index) else {
+                            break;
+                        };
+                        let mut output = Vec::new();
+                        let result = highlight_file(
+                            &mut output,
+                            path,
+                            &path.display().to_string(),
+                            config,
+                            print_name,
+                            opts,
+                            &mut injection_callback,
+                        );
+                        if result.is_err() {
+                            stop.store(true, Ordering::SeqCst);
+                        }
+                        // The receiver is only dropped once the main thread has given up.
+                        if sender.send(Message::Done(index, output, result)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            // If the main thread gives up, dropping the receiver also drops the senders of the
+            // pending injection lookups, so that the threads waiting for them can finish.
+            let mut pending = HashMap::new();
+            for message in receiver {
+                match message {
+                    Message::Injection(string, config_sender) => {
+                        config_sender.send(injection_callback(&string)).ok();
+                    }
+                    Message::Done(index, output, result) => {
+                        pending.insert(index, (output, result));
+                        while let Some((output, result)) = pending.remove(&summaries.len()) {
+                            summaries.push(write_output(summaries.len(), &output, result)?);
+                        }
                     }
-                    line.split(';').next().map(|s| s.trim().trim_matches('"'))
-                })
-                .collect::<HashSet<_>>();
-            config.nonconformant_capture_names(&capture_names)
-        } else {
-            config.nonconformant_capture_names(&HashSet::new())
-        };
-        if names.is_empty() {
-            eprintln!("All highlight captures conform to standards.");
-        } else {
-            eprintln!(
-                "Non-standard highlight {} detected:",
-                if names.len() > 1 {
-                    "captures"
-                } else {
-                    "capture"
                 }
+            }
+            Ok(())
+        })?;
+    }
+
+    if opts.print_time && summaries.len() > 1 {
+        let mut totals = HashMap::<&str, (usize, usize, Duration)>::new();
+        for summary in &summaries {
+            let total = totals.entry(&summary.language_name).or_default();
+            total.0 += 1;
+            total.1 += summary.byte_count;
+            total.2 += summary.duration;
+        }
+        let mut totals = totals.into_iter().collect::<Vec<_>>();
+        totals.sort_unstable_by_key(|(language_name, _)| *language_name);
+        for (language_name, (file_count, byte_count, duration)) in totals {
+            eprintln!(
+                "{language_name}: {file_count} {}, {byte_count} bytes, {}ms",
+                if file_count == 1 { "file" } else { "files" },
+                duration.as_millis(),
             );
-            for name in names {
-                eprintln!("* {name}");
+        }
+    }
+
+    Ok(summaries)
+}
+
+/// Print the captures of a configuration that don't conform to the standard highlight names, or
+/// to the ones in the `--captures-path` file.
+fn check_captures(config: &HighlightConfiguration, opts: &HighlightOptions) -> Result<()> {
+    let file = opts
+        .captures_path
+        .as_deref()
+        .map(fs::read_to_string)
+        .transpose()?;
+    let captures = if let Some(file) = &file {
+        config.nonconformant_captures(&parse_capture_names(file))
+    } else {
+        config.nonconformant_captures(&HashSet::new())
+    };
+    if captures.is_empty() {
+        eprintln!("All highlight captures conform to standards.");
+    } else {
+        eprintln!(
+            "Non-standard highlight {} detected:",
+            if captures.len() > 1 {
+                "captures"
+            } else {
+                "capture"
+            }
+        );
+        for capture in captures {
+            if let Some(fallback) = capture.fallback {
+                eprintln!("* {} (falls back to {fallback})", capture.name);
+            } else {
+                eprintln!("* {}", capture.name);
             }
         }
     }
 
-    let source = fs::read(path)?;
-    let stdout = io::stdout();
-    let mut stdout = stdout.lock();
+    Ok(())
+}
+
+/// Parse the contents of a `--captures-path` file, which has a capture name on each line,
+/// optionally quoted, with `;` starting a comment.
+#[must_use]
+pub fn parse_capture_names(contents: &str) -> HashSet<&str> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            if line.trim().is_empty() || line.trim().starts_with(';') {
+                return None;
+            }
+            line.split(';').next().map(|s| s.trim().trim_matches('"'))
+        })
+        .collect()
+}
+
+fn highlight_file<'a>(
+    out: &mut impl io::Write,
+    path: &Path,
+    name: &str,
+    config: &'a HighlightConfiguration,
+    print_name: bool,
+    opts: &HighlightOptions,
+    mut injection_callback: impl FnMut(&str) -> Option<&'a HighlightConfiguration>,
+) -> Result<HighlightSummary> {
+    let mut source = fs::read(path)?;
+    // The highlighter only accepts UTF-8, so source code in other encodings is converted first.
+    if opts.encoding.detect(&source) != Encoding::Utf8 {
+        source = opts.encoding.decode(&source).into_owned().into_bytes();
+    }
     let time = Instant::now();
     let mut highlighter = Highlighter::new();
-    let events =
-        highlighter.highlight(config, &source, Some(&opts.cancellation_flag), |string| {
-            loader.highlight_config_for_injection_string(string)
-        })?;
+    let row_range = opts.row_range.clone().unwrap_or(0..usize::MAX);
+    let mut events = highlighter.highlight_point_range(
+        config,
+        &source,
+        Point::new(row_range.start, 0)..Point::new(row_range.end, 0),
+        Some(&opts.cancellation_flag),
+        |string| injection_callback(string),
+    )?;
     let theme = &opts.theme;
 
-    if !opts.quiet && print_name {
-        writeln!(&mut stdout, "{name}")?;
+    if !opts.quiet && !opts.json && print_name {
+        writeln!(&mut *out, "{name}")?;
     }
 
-    if opts.html {
+    if opts.json {
+        let mut writer = JsonEventWriter::new(&mut *out, name, opts.merge_adjacent);
+        while let Some(event) = events.next() {
+            match event? {
+                HighlightEvent::HighlightStart(highlight) => {
+                    let layer = events.highlight_layer().unwrap();
+                    writer.start(ActiveHighlight {
+                        highlight: &theme.highlight_names[highlight.0],
+                        language: layer.language_name,
+                        depth: layer.depth,
+                    })?;
+                }
+                HighlightEvent::HighlightEnd => writer.end()?,
+                HighlightEvent::Source { start, end } => writer.source(start..end)?,
+            }
+        }
+        writer.finish()?;
+    } else if opts.html {
         if !opts.quiet {
-            writeln!(&mut stdout, "{HTML_HEAD_HEADER}")?;
-            writeln!(&mut stdout, "  <style>")?;
-            let names = theme.highlight_names.iter();
-            let styles = theme.styles.iter();
-            for (name, style) in names.zip(styles) {
-                if let Some(css) = &style.css {
-                    writeln!(&mut stdout, "    .{name} {{ {css}; }}")?;
+            writeln!(&mut *out, "{HTML_HEAD_HEADER}")?;
+            if let Some(css_file) = opts.css_file.as_deref().filter(|_| !opts.inline_styles) {
+                writeln!(
+                    &mut *out,
+                    "  <link rel=\"stylesheet\" href=\"{}\">",
+                    css_file.display()
+                )?;
+            } else if !opts.inline_styles {
+                writeln!(&mut *out, "  <style>")?;
+                for rule in theme_stylesheet(theme).lines() {
+                    writeln!(&mut *out, "    {rule}")?;
                 }
+                writeln!(&mut *out, "  </style>")?;
             }
-            writeln!(&mut stdout, "  </style>")?;
-            writeln!(&mut stdout, "{HTML_BODY_HEADER}")?;
+            writeln!(&mut *out, "{HTML_BODY_HEADER}")?;
         }
 
         let mut renderer = HtmlRenderer::new();
@@ -406,29 +902,23 @@ pub fn highlight(
                 );
                 output.extend(b"'");
             } else {
-                output.extend(b"class='");
-                let mut parts = theme.highlight_names[highlight.0].split('.').peekable();
-                while let Some(part) = parts.next() {
-                    output.extend(part.as_bytes());
-                    if parts.peek().is_some() {
-                        output.extend(b" ");
-                    }
-                }
-                output.extend(b"'");
+                output.extend(b"class=\"");
+                output.extend(css_class_name(&theme.highlight_names[highlight.0]).as_bytes());
+                output.extend(b"\"");
             }
         })?;
 
         if !opts.quiet {
-            writeln!(&mut stdout, "<table>")?;
+            writeln!(&mut *out, "<table>")?;
             for (i, line) in renderer.lines().enumerate() {
                 writeln!(
-                    &mut stdout,
+                    &mut *out,
                     "<tr><td class=line-number>{}</td><td class=line>{line}</td></tr>",
-                    i + 1,
+                    row_range.start + i + 1,
                 )?;
             }
-            writeln!(&mut stdout, "</table>")?;
-            writeln!(&mut stdout, "{HTML_FOOTER}")?;
+            writeln!(&mut *out, "</table>")?;
+            writeln!(&mut *out, "{HTML_FOOTER}")?;
         }
     } else {
         let mut style_stack = vec![theme.default_style().ansi];
@@ -442,19 +932,19 @@ pub fn highlight(
                 }
                 HighlightEvent::Source { start, end } => {
                     let style = style_stack.last().unwrap();
-                    write!(&mut stdout, "{style}").unwrap();
-                    stdout.write_all(&source[start..end])?;
-                    write!(&mut stdout, "{style:#}").unwrap();
+                    write!(&mut *out, "{style}").unwrap();
+                    out.write_all(&source[start..end])?;
+                    write!(&mut *out, "{style:#}").unwrap();
                 }
             }
         }
     }
 
-    if opts.print_time {
-        eprintln!("Time: {}ms", time.elapsed().as_millis());
-    }
-
-    Ok(())
+    Ok(HighlightSummary {
+        language_name: config.language_name.clone(),
+        byte_count: source.len(),
+        duration: time.elapsed(),
+    })
 }
 
 #[cfg(test)]
@@ -477,6 +967,7 @@ mod tests {
         // darkcyan is an ANSI color and is preserved
         env::set_var("COLORTERM", "");
         parse_style(&mut style, Value::String(DARK_CYAN.to_string()));
+        style.set_color_depth(ColorDepth::detect());
         assert_eq!(
             style.ansi.get_fg_color(),
             Some(Color::Ansi256(Ansi256Color(36)))
@@ -486,6 +977,7 @@ mod tests {
         // junglegreen is not an ANSI color and is preserved when the terminal supports it
         env::set_var("COLORTERM", "truecolor");
         parse_style(&mut style, Value::String(JUNGLE_GREEN.to_string()));
+        style.set_color_depth(ColorDepth::detect());
         assert_eq!(
             style.ansi.get_fg_color(),
             Some(Color::Rgb(RgbColor(38, 166, 154)))
@@ -495,6 +987,7 @@ mod tests {
         // junglegreen gets approximated as cadetblue when the terminal does not support it
         env::set_var("COLORTERM", "");
         parse_style(&mut style, Value::String(JUNGLE_GREEN.to_string()));
+        style.set_color_depth(ColorDepth::detect());
         assert_eq!(
             style.ansi.get_fg_color(),
             Some(Color::Ansi256(Ansi256Color(72)))
@@ -507,4 +1000,116 @@ mod tests {
             env::remove_var("COLORTERM");
         }
     }
+
+    #[test]
+    fn test_color_depth() {
+        let mut style = Style::default();
+        parse_style(&mut style, Value::String(JUNGLE_GREEN.to_string()));
+        style.set_color_depth(ColorDepth::Truecolor);
+        assert_eq!(
+            style.ansi.get_fg_color(),
+            Some(Color::Rgb(RgbColor(38, 166, 154)))
+        );
+        style.set_color_depth(ColorDepth::Ansi256);
+        assert_eq!(
+            style.ansi.get_fg_color(),
+            Some(Color::Ansi256(Ansi256Color(72)))
+        );
+    }
+
+    #[test]
+    fn test_theme_stylesheet() {
+        assert_eq!(css_class_name("keyword"), "ts-keyword");
+        assert_eq!(
+            css_class_name("punctuation.bracket"),
+            "ts-punctuation-bracket"
+        );
+
+        let theme: Theme = serde_json::from_value(json!({
+            "function": "#26a69a",
+            "function.builtin": {"color": "#26a69a", "bold": true},
+            "keyword": {"italic": true},
+            "embedded": null,
+        }))
+        .unwrap();
+        assert_eq!(
+            theme_stylesheet(&theme),
+            concat!(
+                ".ts-function { color: #26a69a; }\n",
+                ".ts-function-builtin { font-weight: bold;color: #26a69a; }\n",
+                ".ts-keyword { font-style: italic; }\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_json_events() {
+        let keyword = ActiveHighlight {
+            highlight: "keyword",
+            language: "javascript",
+            depth: 0,
+        };
+        let tag = ActiveHighlight {
+            highlight: "tag",
+            language: "html",
+            depth: 1,
+        };
+        let write_events = |merge_adjacent| {
+            let mut output = Vec::new();
+            let mut writer = JsonEventWriter::new(&mut output, "a.js", merge_adjacent);
+            writer.start(keyword).unwrap();
+            writer.source(0..1).unwrap();
+            writer.end().unwrap();
+            writer.start(keyword).unwrap();
+            writer.source(1..2).unwrap();
+            writer.start(tag).unwrap();
+            writer.end().unwrap();
+            writer.end().unwrap();
+            writer.source(2..3).unwrap();
+            writer.start(tag).unwrap();
+            writer.source(3..4).unwrap();
+            writer.finish().unwrap();
+            String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .map(|line| {
+                    let event = serde_json::from_str::<Value>(line).unwrap();
+                    assert_eq!(event["file"], "a.js");
+                    match event["event"].as_str().unwrap() {
+                        "source" => format!("{}..{}", event["start"], event["end"]),
+                        kind => format!("{kind} {} {}", event["highlight"], event["depth"]),
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            write_events(false),
+            [
+                "highlight_start \"keyword\" 0",
+                "0..1",
+                "highlight_end \"keyword\" 0",
+                "highlight_start \"keyword\" 0",
+                "1..2",
+                "highlight_start \"tag\" 1",
+                "highlight_end \"tag\" 1",
+                "highlight_end \"keyword\" 0",
+                "2..3",
+                "highlight_start \"tag\" 1",
+                "3..4",
+            ]
+        );
+        assert_eq!(
+            write_events(true),
+            [
+                "highlight_start \"keyword\" 0",
+                "0..2",
+                "highlight_end \"keyword\" 0",
+                "2..3",
+                "highlight_start \"tag\" 1",
+                "3..4",
+                "highlight_end \"tag\" 1",
+            ]
+        );
+    }
 }
@@ -23,6 +23,27 @@ pub enum CliInput {
     Stdin(Vec<u8>),
 }
 
+/// Read the paths in a paths file, or in stdin if the path is `-`. There is one path on each
+/// line, and blank lines and lines starting with `#` are skipped.
+fn read_paths_file(paths_file: &Path) -> Result<Vec<PathBuf>> {
+    let contents = if paths_file == Path::new("-") {
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .context("Failed to read paths from stdin")?;
+        contents
+    } else {
+        fs::read_to_string(paths_file)
+            .with_context(|| format!("Failed to read paths file {}", paths_file.display()))?
+    };
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect())
+}
+
 pub fn get_input(
     paths_file: Option<&Path>,
     paths: Option<Vec<PathBuf>>,
@@ -30,14 +51,7 @@ pub fn get_input(
     cancellation_flag: &Arc<AtomicUsize>,
 ) -> Result<CliInput> {
     if let Some(paths_file) = paths_file {
-        return Ok(CliInput::Paths(
-            fs::read_to_string(paths_file)
-                .with_context(|| format!("Failed to read paths file {}", paths_file.display()))?
-                .trim()
-                .lines()
-                .map(PathBuf::from)
-                .collect::<Vec<_>>(),
-        ));
+        return Ok(CliInput::Paths(read_paths_file(paths_file)?));
     }
 
     if let Some(test_number) = test_number {
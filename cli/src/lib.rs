@@ -8,7 +8,9 @@ pub mod logger;
 pub mod parse;
 pub mod playground;
 pub mod query;
+pub mod query_analysis;
 pub mod query_testing;
+pub mod sexp_diff;
 pub mod tags;
 pub mod test;
 pub mod test_highlight;
@@ -16,6 +18,7 @@ pub mod test_tags;
 pub mod util;
 pub mod version;
 pub mod wasm;
+pub mod watch;
 
 #[cfg(test)]
 mod tests;
@@ -1,6 +1,10 @@
 use std::{
     collections::HashSet,
     env, fs,
+    io::{self, IsTerminal, Write},
+    iter,
+    num::NonZeroUsize,
+    ops::Range,
     path::{Path, PathBuf},
 };
 
@@ -12,24 +16,28 @@ use dialoguer::{theme::ColorfulTheme, Confirm, FuzzySelect, Input};
 use heck::ToUpperCamelCase;
 use regex::Regex;
 use semver::Version as SemverVersion;
-use tree_sitter::{ffi, Parser, Point};
+use tree_sitter::{Parser, Point};
 use tree_sitter_cli::{
     fuzz::{
-        fuzz_language_corpus, FuzzOptions, EDIT_COUNT, ITERATION_COUNT, LOG_ENABLED,
+        fuzz_language_corpus, new_seed, FuzzOptions, EDIT_COUNT, ITERATION_COUNT, LOG_ENABLED,
         LOG_GRAPH_ENABLED, START_SEED,
     },
-    highlight::{self, HighlightOptions},
+    highlight::{self, ColorDepth, HighlightOptions, Theme, ThemeConfig},
     init::{generate_grammar_files, get_root_path, JsonConfigOpts},
     input::{get_input, get_tmp_source_file, CliInput},
     logger,
-    parse::{self, ParseDebugType, ParseFileOptions, ParseOutput, ParseTheme},
-    playground, query,
-    tags::{self, TagsOptions},
-    test::{self, TestOptions, TestStats},
-    test_highlight, test_tags, util, version, wasm,
+    parse::{
+        self, Encoding, ParseDebugType, ParseFileOptions, ParseOutput, ParseTheme, RangeFormat,
+    },
+    playground,
+    query::{self, AssertionCounts},
+    query_analysis,
+    tags::{self, TagsOptions, TagsOutputFormat},
+    test::{self, TestOptions, TestStats, UpdateFields},
+    test_highlight, test_tags, util, version, wasm, watch,
 };
 use tree_sitter_config::Config;
-use tree_sitter_highlight::Highlighter;
+use tree_sitter_highlight::{standard_capture_names, Highlighter};
 use tree_sitter_loader::{self as loader, TreeSitterJSON};
 use tree_sitter_tags::TagsContext;
 use url::Url;
@@ -59,6 +67,8 @@ enum Commands {
     Fuzz(Fuzz),
     /// Search files using a syntax tree query
     Query(Query),
+    /// Check queries for unknown node types, unrecognized captures and shadowed patterns
+    LintQueries(LintQueries),
     /// Highlight a file
     Highlight(Highlight),
     /// Generate a list of tags
@@ -99,10 +109,12 @@ struct Generate {
         value_name = "VERSION",
         help = format!(concat!(
                     "Select the language ABI version to generate (default {}).\n",
-                    "Use --abi=latest to generate the newest supported version ({}).",
+                    "Use --abi=latest to generate the newest supported version ({}).\n",
+                    "Versions as old as {} can be generated for older runtimes.",
                     ),
                 DEFAULT_GENERATE_ABI_VERSION,
                 tree_sitter::LANGUAGE_VERSION,
+                tree_sitter_generate::ABI_VERSION_MIN,
                 )
     )]
     pub abi_version: Option<String>,
@@ -121,9 +133,81 @@ struct Generate {
     /// Produce a report of the states for the given rule, use `-` to report every rule
     #[arg(long)]
     pub report_states_for_rule: Option<String>,
-    /// Report conflicts in a JSON format
+    /// Report tokens that can never match, because another token that is valid in the same
+    /// state matches all of the same strings and always wins
+    #[arg(long)]
+    pub report_shadowed_tokens: bool,
+    /// Report errors and warnings as a JSON array of diagnostics
     #[arg(long)]
     pub json: bool,
+    /// Only check that the grammar is valid, without building the parse table or writing any
+    /// files
+    #[arg(long)]
+    #[clap(
+        conflicts_with = "build",
+        conflicts_with = "output",
+        conflicts_with = "report_states_for_rule",
+        conflicts_with = "report_shadowed_tokens",
+        conflicts_with = "dump_intermediates",
+        conflicts_with = "dump_grammar_stats",
+        conflicts_with = "inline_report",
+        conflicts_with = "report_dynamic_precedence",
+        conflicts_with = "split_output",
+        conflicts_with = "emit",
+        conflicts_with = "no_minimize_lexer"
+    )]
+    pub check: bool,
+    /// Treat warnings, such as unused precedences, unreachable rules, or conflicting default
+    /// aliases, as errors
+    #[arg(long)]
+    pub strict: bool,
+    /// Write the grammar produced by each stage of grammar preparation to a JSON file in this
+    /// directory
+    #[arg(long, value_name = "DIR")]
+    pub dump_intermediates: Option<PathBuf>,
+    /// The number of threads to use when building the parse tables (default: the number of
+    /// available CPUs)
+    #[arg(long, value_name = "N")]
+    pub threads: Option<usize>,
+    /// Fail if the parse table has more than this many states, reporting the rules that appear
+    /// in the most states
+    #[arg(long, value_name = "N")]
+    pub max_states: Option<usize>,
+    /// Move the parse and lex tables out of `parser.c` into at most this many other C files,
+    /// so that very large parsers can be compiled in smaller pieces
+    #[arg(long, value_name = "N")]
+    pub split_output: Option<NonZeroUsize>,
+    /// Write additional files derived from the grammar to the output directory, such as
+    /// `node-types.d.ts` for `typescript`
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub emit: Vec<EmitFormat>,
+    /// Don't merge the lex states that are equivalent, which makes the generated lexer larger,
+    /// but can help when debugging the lexer
+    #[arg(long)]
+    pub no_minimize_lexer: bool,
+    /// Report how long each stage of generation took, and which rules took the longest to
+    /// expand while building the parse table
+    #[arg(long)]
+    pub report_timing: bool,
+    /// Print metrics about the grammar and the generated parser, such as the number of rules,
+    /// tokens, and parse states, and the size of the parse table
+    #[arg(long)]
+    pub dump_grammar_stats: bool,
+    /// Report how many productions each rule in the grammar's `inline` list was inlined into
+    /// and how many productions that added, and which of those rules are never referenced
+    #[arg(long)]
+    pub inline_report: bool,
+    /// Report each dynamic precedence in the grammar and whether it takes part in any conflict,
+    /// and the conflicts in which every interpretation has the same dynamic precedence
+    #[arg(long)]
+    pub report_dynamic_precedence: bool,
+    /// Always build the parse tables, instead of reusing the ones cached for the same grammar
+    #[arg(long)]
+    pub no_cache: bool,
+    /// Remove all cached parse tables and exit
+    #[arg(long)]
+    #[clap(conflicts_with = "check", conflicts_with = "build")]
+    pub clear_cache: bool,
     /// The name or path of the JavaScript runtime to use for generating parsers
     #[arg(
         long,
@@ -132,6 +216,10 @@ struct Generate {
         default_value = "node"
     )]
     pub js_runtime: Option<String>,
+    /// Generate the parser again whenever the grammar or the files it imports change
+    #[arg(long, short)]
+    #[clap(conflicts_with = "json", conflicts_with = "clear_cache")]
+    pub watch: bool,
 }
 
 #[derive(Args)]
@@ -161,8 +249,9 @@ struct Build {
 #[derive(Args)]
 #[command(alias = "p")]
 struct Parse {
-    /// The path to a file with paths to source file(s)
-    #[arg(long = "paths")]
+    /// The path to a file with paths to source file(s), one per line, or `-` to read them from
+    /// stdin
+    #[arg(long = "paths", visible_alias = "paths-file")]
     pub paths_file: Option<PathBuf>,
     /// The source file(s) to use
     #[arg(num_args=1..)]
@@ -192,9 +281,28 @@ struct Parse {
     /// Output the parse data in a pretty-printed CST format
     #[arg(long = "cst", short = 'c')]
     pub output_cst: bool,
+    /// Output the parse data in the given format
+    #[arg(
+        long = "output",
+        value_name = "FORMAT",
+        value_enum,
+        conflicts_with_all = ["output_dot", "output_xml", "output_cst", "quiet"]
+    )]
+    pub output_format: Option<ParseOutputFormat>,
     /// Show parsing statistic
     #[arg(long, short)]
     pub stat: bool,
+    /// Only print the `ERROR` and `MISSING` nodes of each file, along with the source code around
+    /// them. With `--json`, print them as a JSON array
+    #[arg(
+        long,
+        conflicts_with_all = ["output_dot", "output_xml", "output_cst", "output_format"]
+    )]
+    pub errors_only: bool,
+    /// Show the parser's counters for each file: time spent in the lexer and the external
+    /// scanner, parse states, stack splits and merges, reused nodes, and tree size
+    #[arg(long)]
+    pub stat_detail: bool,
     /// Interrupt the parsing process by timeout (µs)
     #[arg(long)]
     pub timeout: Option<u64>,
@@ -205,15 +313,21 @@ struct Parse {
     #[arg(long, short)]
     pub quiet: bool,
     #[allow(clippy::doc_markdown)]
-    /// Apply edits in the format: \"row, col delcount insert_text\"
+    /// Apply edits in the format: \"START_BYTE DELETED_LEN INSERTED_TEXT\", re-parsing
+    /// incrementally after each one. The start can also be a \"row,column\" position, or `$`
+    /// for the end of the file
     #[arg(
         long,
+        visible_alias = "edit",
         num_args = 1..,
     )]
     pub edits: Option<Vec<String>>,
+    /// Print the ranges whose syntactic structure changed after each edit
+    #[arg(long, requires = "edits")]
+    pub print_changed_ranges: bool,
     /// The encoding of the input files
-    #[arg(long)]
-    pub encoding: Option<Encoding>,
+    #[arg(long, value_enum, default_value_t)]
+    pub encoding: Encoding,
     /// Open `log.html` in the default browser, if `--debug-graph` is supplied
     #[arg(long)]
     pub open_log: bool,
@@ -231,18 +345,62 @@ struct Parse {
     #[arg(short, long)]
     pub rebuild: bool,
     /// Omit ranges in the output
-    #[arg(long)]
+    #[arg(long, conflicts_with = "ranges")]
     pub no_ranges: bool,
+    /// The kind of range to show for each node in the default and CST outputs
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub ranges: Option<RangeFormat>,
+    /// Show field names in the default and CST outputs, which is the default
+    #[arg(long, overrides_with = "no_fields")]
+    pub fields: bool,
+    /// Hide field names in the default and CST outputs
+    #[arg(long, overrides_with = "fields")]
+    pub no_fields: bool,
+    /// Show anonymous nodes, like keywords and punctuation, in the default output. The CST
+    /// output shows them unless `--no-anonymous` is passed
+    #[arg(long, overrides_with = "no_anonymous")]
+    pub anonymous: bool,
+    /// Hide anonymous nodes in the default and CST outputs
+    #[arg(long, overrides_with = "anonymous")]
+    pub no_anonymous: bool,
+    /// Leave out the nodes that are nested more than this many levels below the root in the
+    /// default and CST outputs
+    #[arg(long, value_name = "N")]
+    pub depth: Option<usize>,
+    /// Only include the nodes that overlap this range of bytes, in the format `START:END`, in
+    /// the dot tree output
+    #[arg(long)]
+    pub byte_range: Option<String>,
+    /// The number of files to parse at the same time, keeping each file's output in memory until
+    /// it can be printed in order (default: 1)
+    #[arg(long, value_name = "N")]
+    pub threads: Option<usize>,
+    /// Stop parsing after the first file that fails to parse or contains errors
+    #[arg(long)]
+    pub fail_fast: bool,
 }
 
-#[derive(ValueEnum, Clone)]
-pub enum Encoding {
-    Utf8,
-    Utf16LE,
-    Utf16BE,
+#[derive(ValueEnum, Clone, Copy)]
+pub enum ParseOutputFormat {
+    /// The same as `--cst`
+    Cst,
+    /// The same as `--xml`
+    Xml,
+    /// The same as `--dot`
+    Dot,
+    /// A JSON object for each node, with its type, range, field name, and children
+    Json,
+    /// A graphviz dot graph of the syntax tree
+    DotTree,
 }
 
-#[derive(Args)]
+#[derive(ValueEnum, Clone, PartialEq, Eq)]
+pub enum EmitFormat {
+    /// TypeScript definitions of the grammar's node types
+    Typescript,
+}
+
+#[derive(Args, Clone)]
 #[command(alias = "t")]
 struct Test {
     /// Only run corpus test cases whose name matches the given regex
@@ -251,12 +409,15 @@ struct Test {
     /// Only run corpus test cases whose name does not match the given regex
     #[arg(long, short)]
     pub exclude: Option<Regex>,
-    /// Only run corpus test cases from from a given filename
-    #[arg(long)]
-    pub file_name: Option<String>,
+    /// Only run corpus test cases from the given corpus file, can be repeated
+    #[arg(long = "file", visible_alias = "file-name", value_name = "PATH")]
+    pub files: Vec<PathBuf>,
     /// Update all syntax trees in corpus files with current parser output
     #[arg(long, short)]
     pub update: bool,
+    /// Which field names to write in the updated syntax trees (default: existing)
+    #[arg(long, value_name = "POLICY", requires = "update")]
+    pub update_fields: Option<UpdateFields>,
     /// Show parsing debug log
     #[arg(long, short = 'd')]
     pub debug: bool,
@@ -287,6 +448,23 @@ struct Test {
     /// Show only the pass-fail overview tree
     #[arg(long)]
     pub overview_only: bool,
+    /// The number of tests to parse at the same time (default: the number of available CPUs)
+    #[arg(long, value_name = "N")]
+    pub threads: Option<usize>,
+    /// Show a line diff of the whole trees of failing tests, instead of only the nodes that differ
+    #[arg(long)]
+    pub full_diff: bool,
+    /// Perform this many random edits on each corpus example, checking that incremental
+    /// reparses match parsing from scratch
+    #[arg(long, value_name = "N")]
+    pub fuzz_edits: Option<usize>,
+    /// The seed for the random edits of `--fuzz-edits`
+    #[arg(long, requires = "fuzz_edits")]
+    pub seed: Option<usize>,
+    /// Generate the parser and run the tests again whenever the grammar, the external scanner,
+    /// the queries, or the tests change
+    #[arg(long, short)]
+    pub watch: bool,
 }
 
 #[derive(Args)]
@@ -336,22 +514,27 @@ struct Query {
     /// Path to a file with queries
     #[arg(index = 1, required = true)]
     query_path: PathBuf,
+    /// The path to another file with queries to run along with the first one, can be repeated
+    #[arg(long = "query", value_name = "PATH")]
+    pub extra_query_paths: Vec<PathBuf>,
     /// Measure execution time
     #[arg(long, short)]
     pub time: bool,
     /// Suppress main output
     #[arg(long, short)]
     pub quiet: bool,
-    /// The path to a file with paths to source file(s)
-    #[arg(long = "paths")]
+    /// The path to a file with paths to source file(s), one per line, or `-` to read them from
+    /// stdin
+    #[arg(long = "paths", visible_alias = "paths-file")]
     pub paths_file: Option<PathBuf>,
     /// The source file(s) to use
     #[arg(index = 2, num_args=1..)]
     pub paths: Option<Vec<PathBuf>>,
-    /// The range of byte offsets in which the query will be executed
+    /// Only return the matches that intersect this range of byte offsets, in the format
+    /// `START:END`
     #[arg(long)]
     pub byte_range: Option<String>,
-    /// The range of rows in which the query will be executed
+    /// Only return the matches that intersect this range of rows, in the format `START:END`
     #[arg(long)]
     pub row_range: Option<String>,
     /// Select a language by the scope instead of a file extension
@@ -370,6 +553,62 @@ struct Query {
     #[arg(long, short = 'n')]
     #[clap(conflicts_with = "paths", conflicts_with = "paths_file")]
     pub test_number: Option<u32>,
+    /// The encoding of the input files
+    #[arg(long, value_enum, default_value_t)]
+    pub encoding: Encoding,
+    /// Output the matches in the given format
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        conflicts_with_all = ["captures", "test", "time"]
+    )]
+    pub format: Option<QueryOutputFormat>,
+    /// Include the source text of each capture in the JSON output
+    #[arg(long, requires = "format")]
+    pub capture_text: bool,
+    /// Print how each pattern in the query compiles instead of running it
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "time",
+            "quiet",
+            "paths_file",
+            "byte_range",
+            "row_range",
+            "captures",
+            "test",
+            "test_number",
+            "capture_text",
+        ]
+    )]
+    pub analyze: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum QueryOutputFormat {
+    /// The captures of each match, with their positions and text
+    Text,
+    /// A JSON object on its own line for each match, with the range and node type of each
+    /// capture, and whether a text predicate rejected the match
+    Json,
+}
+
+#[derive(Args)]
+struct LintQueries {
+    /// The paths to the query files to check
+    #[arg(required = true, num_args = 1..)]
+    pub query_paths: Vec<PathBuf>,
+    /// Select a language by the scope instead of using the grammar in the current directory
+    #[arg(long)]
+    pub scope: Option<String>,
+    /// The path to a file with the recognized capture names, instead of the standard highlight
+    /// names
+    #[arg(long)]
+    pub captures_path: Option<PathBuf>,
+    /// The path to an alternative config.json file
+    #[arg(long)]
+    pub config_path: Option<PathBuf>,
 }
 
 #[derive(Args)]
@@ -381,6 +620,10 @@ struct Highlight {
     /// When generating HTML, use css classes rather than inline styles
     #[arg(long)]
     pub css_classes: bool,
+    /// Write a stylesheet for the css classes to this file, and link to it instead of including
+    /// it in the HTML
+    #[arg(long, value_name = "PATH", requires = "css_classes")]
+    pub css_file: Option<PathBuf>,
     /// Check that highlighting captures conform strictly to standards
     #[arg(long)]
     pub check: bool,
@@ -399,8 +642,9 @@ struct Highlight {
     /// Suppress main output
     #[arg(long, short)]
     pub quiet: bool,
-    /// The path to a file with paths to source file(s)
-    #[arg(long = "paths")]
+    /// The path to a file with paths to source file(s), one per line, or `-` to read them from
+    /// stdin
+    #[arg(long = "paths", visible_alias = "paths-file")]
     pub paths_file: Option<PathBuf>,
     /// The source file(s) to use
     #[arg(num_args = 1..)]
@@ -412,6 +656,49 @@ struct Highlight {
     #[arg(long, short = 'n')]
     #[clap(conflicts_with = "paths", conflicts_with = "paths_file")]
     pub test_number: Option<u32>,
+    /// The encoding of the input files
+    #[arg(long, value_enum, default_value_t)]
+    pub encoding: Encoding,
+    /// The path to a JSON file with a theme to use instead of the one in the config file
+    #[arg(long, value_name = "PATH")]
+    pub theme: Option<PathBuf>,
+    /// Print the highlight names that the language's queries can produce, and the name in the
+    /// theme that each one is styled with
+    #[arg(
+        long,
+        conflicts_with_all = ["html", "check", "time", "quiet", "paths_file", "test_number"]
+    )]
+    pub list_theme_keys: bool,
+    /// The number of colors to use for the terminal output, instead of detecting it from the
+    /// `COLORTERM` environment variable
+    #[arg(long, value_enum)]
+    pub color_depth: Option<ColorDepth>,
+    /// Only highlight this range of rows, in the format `START:END`
+    #[arg(long)]
+    pub row_range: Option<String>,
+    /// Output the highlighting in the given format
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        conflicts_with_all = ["html", "list_theme_keys"]
+    )]
+    pub format: Option<HighlightOutputFormat>,
+    /// In the JSON output, combine the source events of adjacent text with the same highlights
+    #[arg(long, requires = "format")]
+    pub merge_adjacent: bool,
+    /// The number of files to highlight at the same time (default: 1)
+    #[arg(long, value_name = "N")]
+    pub threads: Option<usize>,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightOutputFormat {
+    /// The source code, colored with the theme's styles
+    Ansi,
+    /// A JSON object on its own line for each highlight event, with the byte offsets of the
+    /// source text, and the name, language and injection depth of each highlight
+    Json,
 }
 
 #[derive(Args)]
@@ -425,8 +712,9 @@ struct Tags {
     /// Suppress main output
     #[arg(long, short)]
     pub quiet: bool,
-    /// The path to a file with paths to source file(s)
-    #[arg(long = "paths")]
+    /// The path to a file with paths to source file(s), one per line, or `-` to read them from
+    /// stdin
+    #[arg(long = "paths", visible_alias = "paths-file")]
     pub paths_file: Option<PathBuf>,
     /// The source file(s) to use
     #[arg(num_args = 1..)]
@@ -438,6 +726,15 @@ struct Tags {
     #[arg(long, short = 'n')]
     #[clap(conflicts_with = "paths", conflicts_with = "paths_file")]
     pub test_number: Option<u32>,
+    /// Output the tags in the given format
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t)]
+    pub format: TagsOutputFormat,
+    /// The ctags kind to use for each syntax type, in the format `SYNTAX_TYPE=KIND,...`
+    #[arg(long, value_name = "MAP")]
+    pub kind_map: Option<String>,
+    /// Sort the tags of all of the files by name, instead of listing them in file order
+    #[arg(long)]
+    pub sorted: bool,
 }
 
 #[derive(Args)]
@@ -487,7 +784,7 @@ impl InitConfig {
         }
         let mut config = Config::initial()?;
         config.add(tree_sitter_loader::Config::initial())?;
-        config.add(tree_sitter_cli::highlight::ThemeConfig::default())?;
+        config.add(ThemeConfig::default())?;
         config.save()?;
         println!(
             "Saved initial configuration to {}",
@@ -754,6 +1051,31 @@ impl Generate {
         if self.log {
             logger::init();
         }
+        if self.clear_cache {
+            let cache_dir = parser_cache_dir()?;
+            tree_sitter_generate::clear_parser_cache(&cache_dir)
+                .with_context(|| format!("Failed to remove {}", cache_dir.display()))?;
+            return Ok(());
+        }
+        if let Some(threads) = self.threads {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build_global()?;
+        }
+        if self.watch {
+            let color = env::var("NO_COLOR").map_or(true, |v| v != "1");
+            // A fresh loader for each run, so that `--build` compiles the new parser.
+            return watch::watch(current_dir, color, || {
+                self.generate(&mut loader::Loader::new()?, current_dir)
+            });
+        }
+        self.generate(&mut loader, current_dir)
+    }
+
+    fn generate(&self, loader: &mut loader::Loader, current_dir: &Path) -> Result<()> {
+        if self.check {
+            return self.check(current_dir);
+        }
         let abi_version =
             self.abi_version
                 .as_ref()
@@ -764,32 +1086,201 @@ impl Generate {
                         version.parse().expect("invalid abi version flag")
                     }
                 });
-        if let Err(err) = tree_sitter_generate::generate_parser_in_directory(
+        let cache_dir = if self.no_cache {
+            None
+        } else {
+            Some(parser_cache_dir()?)
+        };
+        let mut timings = self
+            .report_timing
+            .then(tree_sitter_generate::TimingReport::new);
+        let mut stats = self
+            .dump_grammar_stats
+            .then(tree_sitter_generate::GrammarStats::default);
+        let mut inline_report = self
+            .inline_report
+            .then(tree_sitter_generate::InlineReport::default);
+        let mut dynamic_precedence_report = self
+            .report_dynamic_precedence
+            .then(tree_sitter_generate::DynamicPrecedenceReport::default);
+        let mut warnings = Vec::new();
+        let result = tree_sitter_generate::generate_parser_in_directory(
             current_dir,
-            self.output.as_deref(),
-            self.grammar_path.as_deref(),
-            abi_version,
-            self.report_states_for_rule.as_deref(),
-            self.js_runtime.as_deref(),
-        ) {
-            if self.json {
-                eprintln!("{}", serde_json::to_string_pretty(&err)?);
+            tree_sitter_generate::GenerateOptions {
+                out_path: self.output.as_deref(),
+                grammar_path: self.grammar_path.as_deref(),
+                abi_version,
+                report_symbol_name: self.report_states_for_rule.as_deref(),
+                report_shadowed_tokens: self.report_shadowed_tokens,
+                js_runtime: self.js_runtime.as_deref(),
+                strict: self.strict,
+                intermediates_path: self.dump_intermediates.as_deref(),
+                cache_dir: cache_dir.as_deref(),
+                max_states: self.max_states,
+                split_output: self.split_output.map(NonZeroUsize::get),
+                minimize_lexer: !self.no_minimize_lexer,
+                emit_typescript: self.emit.contains(&EmitFormat::Typescript),
+                timings: timings.as_mut(),
+                stats: stats.as_mut(),
+                inline_report: inline_report.as_mut(),
+                dynamic_precedence_report: dynamic_precedence_report.as_mut(),
+            },
+            &mut warnings,
+        );
+        if self.json {
+            let mut diagnostics = warnings;
+            diagnostics.extend(
+                result
+                    .as_ref()
+                    .err()
+                    .map(tree_sitter_generate::Diagnostic::from),
+            );
+            if !diagnostics.is_empty() {
+                eprintln!("{}", serde_json::to_string_pretty(&diagnostics)?);
+            }
+            if result.is_err() {
                 // Exit early to prevent errors from being printed a second time in the caller
                 std::process::exit(1);
-            } else {
+            }
+        } else {
+            for warning in &warnings {
+                eprintln!("{warning}");
+            }
+            if let Err(err) = result {
                 // Removes extra context associated with the error
                 Err(anyhow!(err.to_string())).with_context(|| "Error when generating parser")?;
             }
         }
+        if let Some(timings) = timings {
+            if self.json {
+                eprintln!("{}", serde_json::to_string_pretty(&timings)?);
+            } else {
+                eprint!("{timings}");
+            }
+        }
+        if let Some(stats) = stats {
+            if self.json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                print!("{stats}");
+            }
+        }
+        if let Some(inline_report) = inline_report {
+            if self.json {
+                println!("{}", serde_json::to_string_pretty(&inline_report)?);
+            } else {
+                print!("{inline_report}");
+            }
+        }
+        if let Some(dynamic_precedence_report) = dynamic_precedence_report {
+            if self.json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&dynamic_precedence_report)?
+                );
+            } else {
+                print!("{dynamic_precedence_report}");
+            }
+        }
         if self.build {
-            if let Some(path) = self.libdir {
-                loader = loader::Loader::with_parser_lib_path(PathBuf::from(path));
+            if let Some(path) = &self.libdir {
+                *loader = loader::Loader::with_parser_lib_path(PathBuf::from(path));
             }
             loader.debug_build(self.debug_build);
             loader.languages_at_path(current_dir)?;
         }
         Ok(())
     }
+
+    /// Prepare the grammar without generating a parser. Errors exit with status 1, and in
+    /// strict mode, warnings exit with status 2.
+    fn check(&self, current_dir: &Path) -> Result<()> {
+        match tree_sitter_generate::check_grammar_in_directory(
+            current_dir,
+            self.grammar_path.as_deref(),
+            self.js_runtime.as_deref(),
+        ) {
+            Ok(warnings) => {
+                if self.json {
+                    if !warnings.is_empty() {
+                        eprintln!("{}", serde_json::to_string_pretty(&warnings)?);
+                    }
+                } else {
+                    for warning in &warnings {
+                        eprintln!("{warning}");
+                    }
+                }
+                if self.strict && !warnings.is_empty() {
+                    if self.watch {
+                        return Err(anyhow!("Warnings are treated as errors in strict mode"));
+                    }
+                    std::process::exit(2);
+                }
+                Ok(())
+            }
+            Err(err) => {
+                if self.json {
+                    eprintln!(
+                        "{}",
+                        serde_json::to_string_pretty(&[tree_sitter_generate::Diagnostic::from(
+                            &err
+                        )])?
+                    );
+                    std::process::exit(1);
+                }
+                Err(anyhow!(err.to_string())).with_context(|| "Error when checking grammar")
+            }
+        }
+    }
+}
+
+/// Generate the parser in `current_dir` the way `tree-sitter generate` does without any options.
+fn generate_parser_with_defaults(current_dir: &Path) -> Result<()> {
+    let js_runtime = env::var("TREE_SITTER_JS_RUNTIME").ok();
+    let cache_dir = parser_cache_dir()?;
+    let mut warnings = Vec::new();
+    let result = tree_sitter_generate::generate_parser_in_directory(
+        current_dir,
+        tree_sitter_generate::GenerateOptions {
+            abi_version: DEFAULT_GENERATE_ABI_VERSION,
+            js_runtime: js_runtime.as_deref(),
+            cache_dir: Some(&cache_dir),
+            ..Default::default()
+        },
+        &mut warnings,
+    );
+    for warning in &warnings {
+        eprintln!("{warning}");
+    }
+    if let Err(err) = result {
+        Err(anyhow!(err.to_string())).with_context(|| "Error when generating parser")?;
+    }
+    Ok(())
+}
+
+/// Parse a range given on the command line in the format `START:END`, where the start can't be
+/// after the end.
+fn parse_range(range: &str, description: &str) -> Result<Range<usize>> {
+    let (start, end) = range
+        .split_once(':')
+        .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)))
+        .ok_or_else(|| anyhow!("Invalid {description} {range:?}, expected START:END"))?;
+    if start > end {
+        return Err(anyhow!(
+            "Invalid {description} {range:?}, the start is after the end"
+        ));
+    }
+    Ok(start..end)
+}
+
+/// The directory where parse tables are cached, alongside the compiled parser libraries.
+fn parser_cache_dir() -> Result<PathBuf> {
+    use etcetera::BaseStrategy as _;
+
+    Ok(etcetera::choose_base_strategy()?
+        .cache_dir()
+        .join("tree-sitter")
+        .join("generate"))
 }
 
 impl Build {
@@ -852,13 +1343,24 @@ impl Build {
 impl Parse {
     fn run(self, mut loader: loader::Loader, current_dir: &Path) -> Result<()> {
         let config = Config::load(self.config_path)?;
-        let color = env::var("NO_COLOR").map_or(true, |v| v != "1");
-        let output = if self.output_dot {
+        let color =
+            env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && io::stdout().is_terminal();
+        let output = if let Some(format) = self.output_format {
+            match format {
+                ParseOutputFormat::Cst => ParseOutput::Cst,
+                ParseOutputFormat::Xml => ParseOutput::Xml,
+                ParseOutputFormat::Dot => ParseOutput::Dot,
+                ParseOutputFormat::Json => ParseOutput::Json,
+                ParseOutputFormat::DotTree => ParseOutput::DotTree,
+            }
+        } else if self.output_dot {
             ParseOutput::Dot
         } else if self.output_xml {
             ParseOutput::Xml
         } else if self.output_cst {
             ParseOutput::Cst
+        } else if self.errors_only {
+            ParseOutput::Errors
         } else if self.quiet || self.json {
             ParseOutput::Quiet
         } else {
@@ -876,11 +1378,11 @@ impl Parse {
             ParseTheme::empty()
         };
 
-        let encoding = self.encoding.map(|e| match e {
-            Encoding::Utf8 => ffi::TSInputEncodingUTF8,
-            Encoding::Utf16LE => ffi::TSInputEncodingUTF16LE,
-            Encoding::Utf16BE => ffi::TSInputEncodingUTF16BE,
-        });
+        let byte_range = self
+            .byte_range
+            .as_deref()
+            .map(|range| parse_range(range, "byte range"))
+            .transpose()?;
 
         let time = self.time;
         let edits = self.edits.unwrap_or_default();
@@ -905,7 +1407,8 @@ impl Parse {
         let loader_config = config.get()?;
         loader.find_all_languages(&loader_config)?;
 
-        let should_track_stats = self.stat;
+        let should_track_stats = self.stat || self.stat_detail;
+        let fail_fast = self.fail_fast;
         let mut stats = parse::ParseStats::default();
         let debug: ParseDebugType = match self.debug {
             None => ParseDebugType::Quiet,
@@ -913,26 +1416,41 @@ impl Parse {
             Some(Some(specifier)) => specifier,
         };
 
-        let mut options = ParseFileOptions {
+        let options = ParseFileOptions {
             edits: &edits
                 .iter()
                 .map(std::string::String::as_str)
                 .collect::<Vec<&str>>(),
+            print_changed_ranges: self.print_changed_ranges,
             output,
             print_time: time,
+            stat_detail: self.stat_detail,
             timeout,
-            stats: &mut stats,
             debug,
             debug_graph: self.debug_graph,
             cancellation_flag: Some(&cancellation_flag),
-            encoding,
+            encoding: self.encoding,
             open_log: self.open_log,
-            no_ranges: self.no_ranges,
+            ranges: if self.no_ranges {
+                RangeFormat::None
+            } else {
+                self.ranges.unwrap_or_default()
+            },
+            fields: !self.no_fields,
+            anonymous: if self.anonymous {
+                Some(true)
+            } else if self.no_anonymous {
+                Some(false)
+            } else {
+                None
+            },
+            depth: self.depth,
+            byte_range,
             parse_theme: &parse_theme,
         };
 
-        let mut update_stats = |stats: &mut parse::ParseStats| {
-            let parse_result = stats.parse_summaries.last().unwrap();
+        let mut update_stats = |stats: &mut parse::ParseStats,
+                                parse_result: parse::ParseSummary| {
             if should_track_stats {
                 stats.cumulative_stats.total_parses += 1;
                 if parse_result.successful {
@@ -942,9 +1460,30 @@ impl Parse {
                     stats.cumulative_stats.total_bytes += bytes;
                     stats.cumulative_stats.total_duration += duration;
                 }
+                if let Some(detail) = &parse_result.detail {
+                    stats
+                        .cumulative_stats
+                        .detail
+                        .get_or_insert_with(Default::default)
+                        .add(detail, false);
+                }
             }
 
-            has_error |= !parse_result.successful;
+            // With `--fail-fast`, files that contain errors also count as failures.
+            has_error |= if fail_fast {
+                parse_result.has_errors()
+            } else {
+                !parse_result.successful
+            };
+            stats.parse_summaries.push(parse_result);
+        };
+
+        // With `--errors-only --json`, the errors are only printed as JSON once every file has
+        // been parsed.
+        let mut out: Box<dyn Write> = if self.errors_only && self.json {
+            Box::new(io::sink())
+        } else {
+            Box::new(io::stdout().lock())
         };
 
         let input = get_input(
@@ -955,26 +1494,51 @@ impl Parse {
         )?;
         match input {
             CliInput::Paths(paths) => {
+                // Files that don't exist are reported once the others have been parsed.
+                let (paths, missing_paths) = paths
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|path| path.is_file());
+                stats.missing_files = missing_paths;
+
                 let max_path_length = paths
                     .iter()
                     .map(|p| p.to_string_lossy().chars().count())
                     .max()
                     .unwrap_or(0);
 
-                for path in &paths {
-                    let path = Path::new(&path);
-                    let language =
-                        loader.select_language(path, current_dir, self.scope.as_deref())?;
+                let files = paths
+                    .into_iter()
+                    .map(|path| {
+                        let language =
+                            loader.select_language(&path, current_dir, self.scope.as_deref())?;
+                        Ok((path, language))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                // Debug logs, debug graphs, and dot output can't be attributed to a single
+                // file when several files are parsed at once, and wasm languages belong to
+                // the main parser's store.
+                let thread_count = if options.debug != ParseDebugType::Quiet
+                    || self.debug_graph
+                    || options.output == ParseOutput::Dot
+                    || self.wasm
+                {
+                    1
+                } else {
+                    self.threads.unwrap_or(1)
+                };
 
-                    parse::parse_file_at_path(
-                        &mut parser,
-                        &language,
-                        path,
-                        &path.display().to_string(),
-                        max_path_length,
-                        &mut options,
-                    )?;
-                    update_stats(options.stats);
+                let summaries = parse::parse_files_at_paths(
+                    &mut parser,
+                    &files,
+                    max_path_length,
+                    thread_count,
+                    fail_fast,
+                    &options,
+                    &mut out,
+                )?;
+                for summary in summaries {
+                    update_stats(&mut stats, summary);
                 }
             }
 
@@ -992,15 +1556,16 @@ impl Parse {
                     .map(|(l, _)| l.clone())
                     .ok_or_else(|| anyhow!("No language found"))?;
 
-                parse::parse_file_at_path(
+                let summary = parse::parse_file_at_path(
                     &mut parser,
                     &language,
                     &path,
                     &name,
                     name.chars().count(),
-                    &mut options,
+                    &options,
+                    &mut out,
                 )?;
-                update_stats(&mut stats);
+                update_stats(&mut stats, summary);
                 fs::remove_file(path)?;
             }
 
@@ -1012,23 +1577,62 @@ impl Parse {
                 let name = "stdin";
                 let language = loader.select_language(&path, current_dir, None)?;
 
-                parse::parse_file_at_path(
+                let summary = parse::parse_file_at_path(
                     &mut parser,
                     &language,
                     &path,
                     name,
                     name.chars().count(),
-                    &mut options,
+                    &options,
+                    &mut out,
                 )?;
-                update_stats(&mut stats);
+                update_stats(&mut stats, summary);
                 fs::remove_file(path)?;
             }
         }
 
+        if should_track_stats {
+            let mut failed_summaries = stats
+                .parse_summaries
+                .iter()
+                .filter(|summary| summary.has_errors())
+                .peekable();
+            if failed_summaries.peek().is_some() {
+                println!("\nFiles with errors:");
+                for summary in failed_summaries {
+                    match &summary.first_error {
+                        Some(error) => println!(
+                            "  {}\t({} [{}, {}] - [{}, {}])",
+                            summary.file.display(),
+                            error.kind,
+                            error.start.row,
+                            error.start.column,
+                            error.end.row,
+                            error.end.column
+                        ),
+                        None => println!("  {}\t(timed out)", summary.file.display()),
+                    }
+                }
+            }
+        }
         if should_track_stats {
             println!("\n{}", stats.cumulative_stats);
         }
-        if self.json {
+        if !stats.missing_files.is_empty() {
+            eprintln!("\nFiles not found:");
+            for path in &stats.missing_files {
+                eprintln!("  {}", path.display());
+            }
+            has_error = true;
+        }
+        if self.json && self.errors_only {
+            let errors = stats
+                .parse_summaries
+                .iter()
+                .flat_map(|summary| &summary.errors)
+                .collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&errors)?);
+        } else if self.json {
             println!("{}", serde_json::to_string_pretty(&stats)?);
         }
 
@@ -1042,8 +1646,23 @@ impl Parse {
 
 impl Test {
     fn run(self, mut loader: loader::Loader, current_dir: &Path) -> Result<()> {
-        let config = Config::load(self.config_path)?;
         let color = env::var("NO_COLOR").map_or(true, |v| v != "1");
+        if self.watch {
+            return watch::watch(current_dir, color, || {
+                generate_parser_with_defaults(current_dir)?;
+                // Load the parser from a new directory each time. A library that has already been
+                // loaded from the same path would be reused instead of the one just compiled.
+                let lib_dir = tempfile::tempdir()?;
+                let loader = loader::Loader::with_parser_lib_path(lib_dir.path().to_path_buf());
+                Self {
+                    watch: false,
+                    ..self.clone()
+                }
+                .run(loader, current_dir)
+            });
+        }
+
+        let config = Config::load(self.config_path)?;
         let stat = self.stat.unwrap_or_default();
 
         loader.debug_build(self.debug_build);
@@ -1082,17 +1701,35 @@ impl Test {
                 debug_graph: self.debug_graph,
                 include: self.include,
                 exclude: self.exclude,
-                file_name: self.file_name,
+                files: self.files,
                 update: self.update,
+                update_fields: self.update_fields.unwrap_or_default(),
                 open_log: self.open_log,
                 languages: languages.iter().map(|(l, n)| (n.as_str(), l)).collect(),
                 color,
                 test_num: 1,
+                filtered_count: 0,
+                skipped_count: 0,
+                expected_failure_count: 0,
+                unexpected_passes: Vec::new(),
+                fuzz_edits: self.fuzz_edits.unwrap_or_default(),
+                fuzz_seed: self.seed.unwrap_or_else(new_seed),
+                edit_fuzz_failures: Vec::new(),
                 parse_rates: &mut rates,
                 stat_display: stat,
                 stats: &mut stats,
                 show_fields: self.show_fields,
                 overview_only: self.overview_only,
+                // Debug logs and debug graphs can't be attributed to a single test when several
+                // tests are parsed at once, and wasm languages belong to the main parser's store.
+                thread_count: if self.debug || self.debug_graph || self.wasm {
+                    1
+                } else {
+                    self.threads.unwrap_or_else(|| {
+                        std::thread::available_parallelism().map_or(1, usize::from)
+                    })
+                },
+                full_diff: self.full_diff,
             };
 
             test::run_tests_at_path(&mut parser, &mut opts)?;
@@ -1131,6 +1768,7 @@ impl Test {
         }
 
         // For the rest of the queries, find their tests and run them
+        let mut query_counts = AssertionCounts::default();
         for entry in walkdir::WalkDir::new(current_dir.join("queries"))
             .into_iter()
             .filter_map(|e| e.ok())
@@ -1157,24 +1795,35 @@ impl Test {
                     println!("{stem}:");
                 }
 
-                for entry in entries {
-                    let path = entry.path();
-                    query::query_file_at_path(
+                let query_paths = [entry.path().to_path_buf()];
+                for test_entry in entries {
+                    let path = test_entry.path();
+                    query_counts += query::query_file_at_path(
                         language,
                         path,
                         &path.display().to_string(),
-                        path,
+                        &query_paths,
                         false,
                         None,
                         None,
                         true,
                         false,
                         false,
+                        Encoding::Auto,
+                        false,
+                        false,
                         false,
                     )?;
                 }
             }
         }
+        if query_counts.failed > 0 {
+            return Err(anyhow!(
+                "{} of {} query assertions failed",
+                query_counts.failed,
+                query_counts.passed + query_counts.failed
+            ));
+        }
         Ok(())
     }
 }
@@ -1222,21 +1871,66 @@ impl Query {
         let config = Config::load(self.config_path)?;
         let loader_config = config.get()?;
         loader.find_all_languages(&loader_config)?;
-        let query_path = Path::new(&self.query_path);
-
-        let byte_range = self.byte_range.as_ref().and_then(|range| {
-            let mut parts = range.split(':');
-            let start = parts.next()?.parse().ok()?;
-            let end = parts.next().unwrap().parse().ok()?;
-            Some(start..end)
-        });
-        let point_range = self.row_range.as_ref().and_then(|range| {
-            let mut parts = range.split(':');
-            let start = parts.next()?.parse().ok()?;
-            let end = parts.next().unwrap().parse().ok()?;
-            Some(Point::new(start, 0)..Point::new(end, 0))
-        });
+        let query_paths = iter::once(self.query_path)
+            .chain(self.extra_query_paths)
+            .collect::<Vec<_>>();
+
+        if self.analyze {
+            let configuration = if let Some(scope) = self.scope.as_deref() {
+                Some(
+                    loader
+                        .language_configuration_for_scope(scope)?
+                        .ok_or_else(|| anyhow!("Unknown scope '{scope}'"))?,
+                )
+            } else if let Some(path) = self.paths.as_ref().and_then(|paths| paths.first()) {
+                loader.language_configuration_for_file_name(path)?
+            } else {
+                None
+            }
+            .map(|(language, config)| (language, config.root_path.clone()));
+            let (language, grammar_path) = if let Some(configuration) = configuration {
+                configuration
+            } else {
+                let (language, _) = loader
+                    .languages_at_path(current_dir)?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow!("No language found"))?;
+                (language, current_dir.to_path_buf())
+            };
+            let node_types = query_analysis::load_node_types(&grammar_path)?;
+            let json = self.format == Some(QueryOutputFormat::Json);
+
+            let mut error_count = 0;
+            for query_path in &query_paths {
+                error_count += query_analysis::analyze_query_at_path(
+                    &language,
+                    query_path,
+                    node_types.as_deref(),
+                    json,
+                )?;
+            }
+            if error_count > 0 {
+                return Err(anyhow!("{error_count} pattern(s) failed to compile"));
+            }
+            return Ok(());
+        }
 
+        let byte_range = self
+            .byte_range
+            .as_deref()
+            .map(|range| parse_range(range, "byte range"))
+            .transpose()?;
+        let point_range = self
+            .row_range
+            .as_deref()
+            .map(|range| {
+                let rows = parse_range(range, "row range")?;
+                Ok::<_, anyhow::Error>(Point::new(rows.start, 0)..Point::new(rows.end, 0))
+            })
+            .transpose()?;
+
+        let json = self.format == Some(QueryOutputFormat::Json);
         let cancellation_flag = util::cancel_on_signal();
 
         let input = get_input(
@@ -1246,6 +1940,7 @@ impl Query {
             &cancellation_flag,
         )?;
 
+        let mut counts = AssertionCounts::default();
         match input {
             CliInput::Paths(paths) => {
                 let language = loader.select_language(
@@ -1255,18 +1950,21 @@ impl Query {
                 )?;
 
                 for path in paths {
-                    query::query_file_at_path(
+                    counts += query::query_file_at_path(
                         &language,
                         &path,
                         &path.display().to_string(),
-                        query_path,
+                        &query_paths,
                         self.captures,
                         byte_range.clone(),
                         point_range.clone(),
                         self.test,
                         self.quiet,
                         self.time,
+                        self.encoding,
                         false,
+                        json,
+                        self.capture_text,
                     )?;
                 }
             }
@@ -1283,44 +1981,117 @@ impl Query {
                     .or_else(|| languages.first())
                     .map(|(l, _)| l.clone())
                     .ok_or_else(|| anyhow!("No language found"))?;
-                query::query_file_at_path(
+                counts += query::query_file_at_path(
                     &language,
                     &path,
                     &name,
-                    query_path,
+                    &query_paths,
                     self.captures,
                     byte_range,
                     point_range,
                     self.test,
                     self.quiet,
                     self.time,
+                    self.encoding,
                     true,
+                    json,
+                    self.capture_text,
                 )?;
                 fs::remove_file(path)?;
             }
             CliInput::Stdin(contents) => {
                 // Place user input and query output on separate lines
-                println!();
+                if !json {
+                    println!();
+                }
 
                 let path = get_tmp_source_file(&contents)?;
                 let language = loader.select_language(&path, current_dir, None)?;
-                query::query_file_at_path(
+                counts += query::query_file_at_path(
                     &language,
                     &path,
                     "stdin",
-                    query_path,
+                    &query_paths,
                     self.captures,
                     byte_range,
                     point_range,
                     self.test,
                     self.quiet,
                     self.time,
+                    self.encoding,
                     true,
+                    json,
+                    self.capture_text,
                 )?;
                 fs::remove_file(path)?;
             }
         }
 
+        if self.test {
+            println!(
+                "\n{} assertions passed, {} failed",
+                counts.passed, counts.failed
+            );
+            if counts.failed > 0 {
+                return Err(anyhow!(""));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl LintQueries {
+    fn run(self, mut loader: loader::Loader, current_dir: &Path) -> Result<()> {
+        let config = Config::load(self.config_path)?;
+        let loader_config = config.get()?;
+        loader.find_all_languages(&loader_config)?;
+
+        let (language, grammar_path) = if let Some(scope) = self.scope.as_deref() {
+            let (language, config) = loader
+                .language_configuration_for_scope(scope)?
+                .ok_or_else(|| anyhow!("Unknown scope '{scope}'"))?;
+            (language, config.root_path.clone())
+        } else {
+            let (language, _) = loader
+                .languages_at_path(current_dir)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow!("No language found"))?;
+            (language, current_dir.to_path_buf())
+        };
+        let node_types = query_analysis::load_node_types(&grammar_path)?;
+
+        let captures_file = self
+            .captures_path
+            .as_deref()
+            .map(fs::read_to_string)
+            .transpose()?;
+        let recognized_names = captures_file.as_deref().map_or_else(
+            || standard_capture_names().clone(),
+            highlight::parse_capture_names,
+        );
+
+        let mut problem_count = 0;
+        let mut error_count = 0;
+        for query_path in &self.query_paths {
+            let diagnostics = query_analysis::lint_query_at_path(
+                &language,
+                query_path,
+                node_types.as_deref(),
+                &recognized_names,
+            )?;
+            problem_count += diagnostics.len();
+            error_count += diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == query_analysis::Severity::Error)
+                .count();
+        }
+        if error_count > 0 {
+            return Err(anyhow!("{error_count} pattern(s) failed to compile"));
+        }
+        if problem_count == 0 {
+            eprintln!("No problems found.");
+        }
         Ok(())
     }
 }
@@ -1328,8 +2099,14 @@ impl Query {
 impl Highlight {
     fn run(self, mut loader: loader::Loader, current_dir: &Path) -> Result<()> {
         let config = Config::load(self.config_path)?;
-        let theme_config: tree_sitter_cli::highlight::ThemeConfig = config.get()?;
-        loader.configure_highlights(&theme_config.theme.highlight_names);
+        let mut theme = if let Some(theme_path) = &self.theme {
+            Theme::load(theme_path)
+                .with_context(|| format!("Failed to load theme {}", theme_path.display()))?
+        } else {
+            config.get::<ThemeConfig>()?.theme
+        };
+        theme.set_color_depth(self.color_depth.unwrap_or_else(ColorDepth::detect));
+        loader.configure_highlights(&theme.highlight_names);
         let loader_config = config.get()?;
         loader.find_all_languages(&loader_config)?;
 
@@ -1346,15 +2123,55 @@ impl Highlight {
             }
         }
 
+        if self.list_theme_keys {
+            let language_config = if let Some(language_config) = language_configuration {
+                language_config
+            } else if let Some(path) = self.paths.as_ref().and_then(|paths| paths.first()) {
+                loader
+                    .language_configuration_for_file_name(path)?
+                    .map(|(_, language_config)| language_config)
+                    .ok_or_else(|| {
+                        anyhow!("{}", util::lang_not_found_for_path(path, &loader_config))
+                    })?
+            } else {
+                loader.languages_at_path(current_dir)?;
+                loader
+                    .get_language_configuration_in_current_path()
+                    .ok_or_else(|| anyhow!("No language configuration found in current path"))?
+            };
+            let language = loader.language_for_configuration(language_config)?;
+            let highlight_config = language_config
+                .highlight_config(language, self.query_paths.as_deref())?
+                .ok_or_else(|| anyhow!("No syntax highlighting config found"))?;
+            highlight::print_theme_keys(&theme, highlight_config);
+            return Ok(());
+        }
+
+        if let Some(css_file) = &self.css_file {
+            fs::write(css_file, highlight::theme_stylesheet(&theme))
+                .with_context(|| format!("Failed to write stylesheet {}", css_file.display()))?;
+        }
+
+        let row_range = self
+            .row_range
+            .as_deref()
+            .map(|range| parse_range(range, "row range"))
+            .transpose()?;
+
         let options = HighlightOptions {
-            theme: theme_config.theme,
+            theme,
             check: self.check,
             captures_path: self.captures_path,
             inline_styles: !self.css_classes,
+            css_file: self.css_file,
             html: self.html,
             quiet: self.quiet,
             print_time: self.time,
             cancellation_flag: cancellation_flag.clone(),
+            encoding: self.encoding,
+            row_range,
+            json: self.format == Some(HighlightOutputFormat::Json),
+            merge_adjacent: self.merge_adjacent,
         };
 
         let input = get_input(
@@ -1365,7 +2182,7 @@ impl Highlight {
         )?;
         match input {
             CliInput::Paths(paths) => {
-                let print_name = paths.len() > 1;
+                let mut files = Vec::with_capacity(paths.len());
                 for path in paths {
                     let (language, language_config) =
                         match (language.clone(), language_configuration) {
@@ -1388,14 +2205,7 @@ impl Highlight {
                     if let Some(highlight_config) =
                         language_config.highlight_config(language, self.query_paths.as_deref())?
                     {
-                        highlight::highlight(
-                            &loader,
-                            &path,
-                            &path.display().to_string(),
-                            highlight_config,
-                            print_name,
-                            &options,
-                        )?;
+                        files.push((path, highlight_config));
                     } else {
                         eprintln!(
                             "No syntax highlighting config found for path {}",
@@ -1403,6 +2213,14 @@ impl Highlight {
                         );
                     }
                 }
+
+                highlight::highlight_files_parallel(
+                    &files,
+                    |string| loader.highlight_config_for_injection_string(string),
+                    self.threads.unwrap_or(1),
+                    &options,
+                    &mut io::stdout().lock(),
+                )?;
             }
 
             CliInput::Test {
@@ -1500,12 +2318,30 @@ impl Tags {
             }
         }
 
+        if self.kind_map.is_some() && self.format != TagsOutputFormat::Ctags {
+            return Err(anyhow!("--kind-map can only be used with --format ctags"));
+        }
+        if self.sorted && self.format == TagsOutputFormat::Text {
+            return Err(anyhow!(
+                "--sorted can only be used with --format ctags or --format json"
+            ));
+        }
+
         let options = TagsOptions {
             scope: self.scope,
             quiet: self.quiet,
             print_time: self.time,
             cancellation_flag: cancellation_flag.clone(),
+            format: self.format,
+            ctags_kinds: self
+                .kind_map
+                .as_deref()
+                .map(tags::parse_ctags_kinds)
+                .transpose()?
+                .unwrap_or_default(),
+            sorted: self.sorted,
         };
+        let mut sorted_tags = Vec::new();
 
         let input = get_input(
             self.paths_file.as_deref(),
@@ -1513,6 +2349,9 @@ impl Tags {
             self.test_number,
             &cancellation_flag,
         )?;
+        if self.format == TagsOutputFormat::Ctags && !self.quiet {
+            tags::write_ctags_header(self.sorted)?;
+        }
         match input {
             CliInput::Paths(paths) => {
                 let indent = paths.len() > 1;
@@ -1542,6 +2381,7 @@ impl Tags {
                             tags_config,
                             indent,
                             &options,
+                            &mut sorted_tags,
                         )?;
                     } else {
                         eprintln!("No tags config found for path {}", path.display());
@@ -1568,7 +2408,14 @@ impl Tags {
                     .ok_or_else(|| anyhow!("No language configuration found in current path"))?;
 
                 if let Some(tags_config) = language_config.tags_config(language)? {
-                    tags::generate_tags(&path, &name, tags_config, false, &options)?;
+                    tags::generate_tags(
+                        &path,
+                        &name,
+                        tags_config,
+                        false,
+                        &options,
+                        &mut sorted_tags,
+                    )?;
                 } else {
                     eprintln!("No tags config found for test {name}");
                 }
@@ -1577,7 +2424,9 @@ impl Tags {
 
             CliInput::Stdin(contents) => {
                 // Place user input and tags output on separate lines
-                println!();
+                if self.format == TagsOutputFormat::Text {
+                    println!();
+                }
 
                 let path = get_tmp_source_file(&contents)?;
 
@@ -1599,7 +2448,14 @@ impl Tags {
                     };
 
                 if let Some(tags_config) = language_config.tags_config(language)? {
-                    tags::generate_tags(&path, "stdin", tags_config, false, &options)?;
+                    tags::generate_tags(
+                        &path,
+                        "stdin",
+                        tags_config,
+                        false,
+                        &options,
+                        &mut sorted_tags,
+                    )?;
                 } else {
                     eprintln!("No tags config found for path {}", current_dir.display());
                 }
@@ -1607,6 +2463,9 @@ impl Tags {
             }
         }
 
+        if self.sorted && !self.quiet {
+            tags::write_sorted_tags(sorted_tags)?;
+        }
         Ok(())
     }
 }
@@ -1633,7 +2492,7 @@ impl DumpLanguages {
                     "highlights: {:?}\n",
                     "file_types: {:?}\n",
                     "content_regex: {:?}\n",
-                    "injection_regex: {:?}\n",
+                    "injection_regex: {:?}",
                 ),
                 configuration.scope.as_ref().unwrap_or(&String::new()),
                 language_path,
@@ -1642,6 +2501,33 @@ impl DumpLanguages {
                 configuration.content_regex,
                 configuration.injection_regex,
             );
+            match loader.language_for_configuration(configuration) {
+                Ok(language) => println!(
+                    concat!(
+                        "name: {:?}\n",
+                        "version: {:?}\n",
+                        "abi_version: {}\n",
+                        "parse_state_count: {}\n",
+                        "external_token_count: {}\n",
+                        "word_token: {:?}\n",
+                        "keyword_count: {}\n",
+                    ),
+                    language.name(),
+                    language
+                        .semantic_version()
+                        .map(|(major, minor, patch)| format!("{major}.{minor}.{patch}")),
+                    language.abi_version(),
+                    language.parse_state_count(),
+                    language.external_token_count(),
+                    language
+                        .word_token()
+                        .and_then(|symbol| language.node_kind_for_id(symbol)),
+                    (0..language.node_kind_count() as u16)
+                        .filter(|symbol| language.is_keyword(*symbol))
+                        .count(),
+                ),
+                Err(e) => println!("error: failed to load the language: {e}\n"),
+            }
         }
         Ok(())
     }
@@ -1720,6 +2606,7 @@ fn run() -> Result<()> {
         Commands::Version(version_options) => version_options.run(current_dir)?,
         Commands::Fuzz(fuzz_options) => fuzz_options.run(loader, &current_dir)?,
         Commands::Query(query_options) => query_options.run(loader, &current_dir)?,
+        Commands::LintQueries(lint_options) => lint_options.run(loader, &current_dir)?,
         Commands::Highlight(highlight_options) => highlight_options.run(loader, &current_dir)?,
         Commands::Tags(tags_options) => tags_options.run(loader, &current_dir)?,
         Commands::Playground(playground_options) => playground_options.run(&current_dir)?,
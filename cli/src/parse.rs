@@ -1,8 +1,15 @@
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     fmt, fs,
-    io::{self, StdoutLock, Write},
+    io::{self, Write},
+    ops,
     path::{Path, PathBuf},
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
     time::{Duration, Instant},
 };
 
@@ -11,8 +18,8 @@ use anyhow::{anyhow, Context, Result};
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use tree_sitter::{
-    ffi, InputEdit, Language, LogType, ParseOptions, ParseState, Parser, Point, Range, Tree,
-    TreeCursor,
+    Decode, InputEdit, Language, LogType, Node, ParseOptions, ParseState, Parser, Point, Range,
+    Tree, TreeCursor,
 };
 
 use super::util;
@@ -24,6 +31,9 @@ pub struct Stats {
     pub total_parses: usize,
     pub total_bytes: usize,
     pub total_duration: Duration,
+    /// The parser's counters, summed over every file, with `--stat-detail`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<ParseDetail>,
 }
 
 impl fmt::Display for Stats {
@@ -41,6 +51,84 @@ impl fmt::Display for Stats {
             } else {
                 0
             }
+        )?;
+        if let Some(detail) = &self.detail {
+            writeln!(f, "{detail}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The counters that the parser collects with `--stat-detail`, summed over the initial parse
+/// and any edits
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct ParseDetail {
+    pub lex_count: usize,
+    pub lex_duration: Duration,
+    pub external_scan_count: usize,
+    pub external_scan_duration: Duration,
+    pub state_count: usize,
+    pub stack_split_count: usize,
+    pub stack_merge_count: usize,
+    pub reused_node_count: usize,
+    pub reused_byte_count: usize,
+    /// The memory used by the final tree
+    pub tree_byte_count: usize,
+}
+
+impl ParseDetail {
+    /// Add the counters of another parse. Only the size of the latest tree is kept when
+    /// `replace_tree` is set, because an edited tree replaces the old one.
+    pub fn add(&mut self, other: &Self, replace_tree: bool) {
+        self.lex_count += other.lex_count;
+        self.lex_duration += other.lex_duration;
+        self.external_scan_count += other.external_scan_count;
+        self.external_scan_duration += other.external_scan_duration;
+        self.state_count += other.state_count;
+        self.stack_split_count += other.stack_split_count;
+        self.stack_merge_count += other.stack_merge_count;
+        self.reused_node_count += other.reused_node_count;
+        self.reused_byte_count += other.reused_byte_count;
+        if replace_tree {
+            self.tree_byte_count = other.tree_byte_count;
+        } else {
+            self.tree_byte_count += other.tree_byte_count;
+        }
+    }
+}
+
+impl From<tree_sitter::ParseStats> for ParseDetail {
+    fn from(stats: tree_sitter::ParseStats) -> Self {
+        Self {
+            lex_count: stats.lex_count,
+            lex_duration: stats.lex_duration,
+            external_scan_count: stats.external_scan_count,
+            external_scan_duration: stats.external_scan_duration,
+            state_count: stats.state_count,
+            stack_split_count: stats.stack_split_count,
+            stack_merge_count: stats.stack_merge_count,
+            reused_node_count: stats.reused_node_count,
+            reused_byte_count: stats.reused_byte_count,
+            tree_byte_count: stats.tree_byte_count,
+        }
+    }
+}
+
+impl fmt::Display for ParseDetail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Lex: {} calls, {:.2} ms; external scan: {} calls, {:.2} ms; states: {}; splits: {}; merges: {}; reused: {} nodes, {} bytes; tree: {} bytes",
+            self.lex_count,
+            self.lex_duration.as_micros() as f64 / 1e3,
+            self.external_scan_count,
+            self.external_scan_duration.as_micros() as f64 / 1e3,
+            self.state_count,
+            self.stack_split_count,
+            self.stack_merge_count,
+            self.reused_node_count,
+            self.reused_byte_count,
+            self.tree_byte_count,
         )
     }
 }
@@ -169,6 +257,42 @@ impl From<ParseThemeRaw> for ParseTheme {
     }
 }
 
+/// The kind of range to show for each node in the default and CST outputs
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RangeFormat {
+    /// Row and column positions
+    #[default]
+    Points,
+    /// Byte offsets
+    Bytes,
+    /// Both positions and byte offsets
+    Both,
+    /// No ranges
+    None,
+}
+
+impl RangeFormat {
+    /// Format a node's range for the default output, with a leading space.
+    fn format(self, range: Range) -> String {
+        let points = || {
+            format!(
+                " [{}, {}] - [{}, {}]",
+                range.start_point.row,
+                range.start_point.column,
+                range.end_point.row,
+                range.end_point.column
+            )
+        };
+        let bytes = || format!(" [{}..{}]", range.start_byte, range.end_byte);
+        match self {
+            Self::Points => points(),
+            Self::Bytes => bytes(),
+            Self::Both => points() + &bytes(),
+            Self::None => String::new(),
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum ParseOutput {
     Normal,
@@ -176,6 +300,11 @@ pub enum ParseOutput {
     Xml,
     Cst,
     Dot,
+    Json,
+    /// One line for each `ERROR` or `MISSING` node
+    Errors,
+    /// A graphviz dot graph of the syntax tree
+    DotTree,
 }
 
 /// A position in a multi-line text document, in terms of rows and columns.
@@ -206,6 +335,52 @@ pub struct ParseSummary {
     pub end: Option<ParsePoint>,
     pub duration: Option<Duration>,
     pub bytes: Option<usize>,
+    /// The first `ERROR` or `MISSING` node in the tree, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_error: Option<ParseErrorSummary>,
+    /// The parser's counters, with `--stat-detail`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<ParseDetail>,
+    /// Every `ERROR` and `MISSING` node in the tree, with `--errors-only`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ParseErrorRecord>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ParseErrorSummary {
+    pub kind: String,
+    pub start: ParsePoint,
+    pub end: ParsePoint,
+}
+
+/// An `ERROR` or `MISSING` node, along with the source code around it
+#[derive(Serialize, Debug, Clone)]
+pub struct ParseErrorRecord {
+    pub file: PathBuf,
+    pub kind: String,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start: ParsePoint,
+    pub end: ParsePoint,
+    /// The line on which the node starts, shortened to the text around the node
+    pub excerpt: String,
+}
+
+impl fmt::Display for ParseErrorRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "({} [{}, {}] - [{}, {}] [{}..{}])\t{}",
+            self.kind,
+            self.start.row,
+            self.start.column,
+            self.end.row,
+            self.end.column,
+            self.start_byte,
+            self.end_byte,
+            self.excerpt
+        )
+    }
 }
 
 impl ParseSummary {
@@ -217,12 +392,21 @@ impl ParseSummary {
             ..Default::default()
         }
     }
+
+    /// Whether the file failed to parse, or was parsed into a tree that contains errors
+    #[must_use]
+    pub const fn has_errors(&self) -> bool {
+        !self.successful || self.first_error.is_some()
+    }
 }
 
 #[derive(Serialize, Debug, Default)]
 pub struct ParseStats {
     pub parse_summaries: Vec<ParseSummary>,
     pub cumulative_stats: Stats,
+    /// The given paths that don't refer to a file
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing_files: Vec<PathBuf>,
 }
 
 #[derive(Serialize, ValueEnum, Debug, Clone, Default, Eq, PartialEq)]
@@ -233,21 +417,144 @@ pub enum ParseDebugType {
     Pretty,
 }
 
+/// The encoding of a source file
+#[derive(ValueEnum, Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Encoding {
+    /// UTF-16 if the file starts with a UTF-16 byte order mark, and UTF-8 otherwise
+    #[default]
+    Auto,
+    Utf8,
+    #[value(name = "utf16-le", alias = "utf16le")]
+    Utf16LE,
+    #[value(name = "utf16-be", alias = "utf16be")]
+    Utf16BE,
+    /// ISO-8859-1, in which every byte is a character
+    Latin1,
+}
+
+struct Latin1Decoder;
+
+impl Decode for Latin1Decoder {
+    fn decode(bytes: &[u8]) -> (i32, u32) {
+        bytes.first().map_or((0, 0), |byte| (i32::from(*byte), 1))
+    }
+}
+
+impl Encoding {
+    /// Replace `Auto` with the encoding indicated by the source code's byte order mark.
+    #[must_use]
+    pub fn detect(self, source_code: &[u8]) -> Self {
+        match self {
+            Self::Auto if source_code.starts_with(&[0xFF, 0xFE]) => Self::Utf16LE,
+            Self::Auto if source_code.starts_with(&[0xFE, 0xFF]) => Self::Utf16BE,
+            Self::Auto => Self::Utf8,
+            _ => self,
+        }
+    }
+
+    /// Decode some source code, replacing any invalid sequences, so that it can be printed.
+    /// When decoding part of a file, `Auto` should be resolved with [`Self::detect`] first.
+    #[must_use]
+    pub fn decode(self, source_code: &[u8]) -> Cow<'_, str> {
+        match self.detect(source_code) {
+            Self::Utf16LE => {
+                String::from_utf16_lossy(&to_utf16(source_code, u16::from_le_bytes)).into()
+            }
+            Self::Utf16BE => {
+                String::from_utf16_lossy(&to_utf16(source_code, u16::from_be_bytes)).into()
+            }
+            Self::Latin1 => source_code
+                .iter()
+                .copied()
+                .map(char::from)
+                .collect::<String>()
+                .into(),
+            Self::Auto | Self::Utf8 => String::from_utf8_lossy(source_code),
+        }
+    }
+
+    /// Parse some source code in this encoding. The positions in the resulting tree are byte
+    /// offsets into the original source code, whatever its encoding.
+    pub fn parse(
+        self,
+        parser: &mut Parser,
+        source_code: &[u8],
+        old_tree: Option<&Tree>,
+        options: Option<ParseOptions>,
+    ) -> Option<Tree> {
+        match self.detect(source_code) {
+            // The parser reads the code units in the byte order of the encoding, so the bytes
+            // are kept in the order that they appear in the file.
+            Self::Utf16LE => {
+                let source_code_utf16 = to_utf16(source_code, u16::from_ne_bytes);
+                parser.parse_utf16_le_with_options(
+                    &mut |i, _| source_code_utf16.get(i..).unwrap_or_default(),
+                    old_tree,
+                    options,
+                )
+            }
+            Self::Utf16BE => {
+                let source_code_utf16 = to_utf16(source_code, u16::from_ne_bytes);
+                parser.parse_utf16_be_with_options(
+                    &mut |i, _| source_code_utf16.get(i..).unwrap_or_default(),
+                    old_tree,
+                    options,
+                )
+            }
+            Self::Latin1 => parser.parse_custom_encoding::<Latin1Decoder, _, _>(
+                &mut |i, _| source_code.get(i..).unwrap_or_default(),
+                old_tree,
+                options,
+            ),
+            Self::Auto | Self::Utf8 => parser.parse_with_options(
+                &mut |i, _| source_code.get(i..).unwrap_or_default(),
+                old_tree,
+                options,
+            ),
+        }
+    }
+}
+
+fn to_utf16(source_code: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Vec<u16> {
+    source_code
+        .chunks_exact(2)
+        .map(|chunk| from_bytes([chunk[0], chunk[1]]))
+        .collect()
+}
+
 pub struct ParseFileOptions<'a> {
     pub edits: &'a [&'a str],
+    pub print_changed_ranges: bool,
     pub output: ParseOutput,
-    pub stats: &'a mut ParseStats,
     pub print_time: bool,
+    pub stat_detail: bool,
     pub timeout: u64,
     pub debug: ParseDebugType,
     pub debug_graph: bool,
     pub cancellation_flag: Option<&'a AtomicUsize>,
-    pub encoding: Option<u32>,
+    pub encoding: Encoding,
     pub open_log: bool,
-    pub no_ranges: bool,
+    pub ranges: RangeFormat,
+    /// Show field names in the default and CST outputs
+    pub fields: bool,
+    /// Show anonymous nodes in the default and CST outputs, or `None` to use the output's
+    /// default: the CST output shows them, and the default output doesn't
+    pub anonymous: Option<bool>,
+    /// The depth below which nodes are left out of the default and CST outputs
+    pub depth: Option<usize>,
+    /// The range of bytes whose nodes are included in the dot tree output
+    pub byte_range: Option<ops::Range<usize>>,
     pub parse_theme: &'a ParseTheme,
 }
 
+impl ParseFileOptions<'_> {
+    /// Whether the cursor's node is at the maximum depth, so its children shouldn't be shown
+    fn is_at_max_depth(&self, cursor: &TreeCursor) -> bool {
+        self.depth
+            .is_some_and(|depth| cursor.depth() as usize >= depth)
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct ParseResult {
     pub successful: bool,
@@ -261,8 +568,9 @@ pub fn parse_file_at_path(
     path: &Path,
     name: &str,
     max_path_length: usize,
-    opts: &mut ParseFileOptions,
-) -> Result<()> {
+    opts: &ParseFileOptions,
+    out: &mut impl Write,
+) -> Result<ParseSummary> {
     let mut _log_session = None;
     parser.set_language(language)?;
     let mut source_code = fs::read(path).with_context(|| format!("Error reading {name:?}"))?;
@@ -314,28 +622,7 @@ pub fn parse_file_at_path(
 
     let parse_time = Instant::now();
 
-    #[inline(always)]
-    fn is_utf16_le_bom(bom_bytes: &[u8]) -> bool {
-        bom_bytes == [0xFF, 0xFE]
-    }
-
-    #[inline(always)]
-    fn is_utf16_be_bom(bom_bytes: &[u8]) -> bool {
-        bom_bytes == [0xFE, 0xFF]
-    }
-
-    let encoding = match opts.encoding {
-        None if source_code.len() >= 2 => {
-            if is_utf16_le_bom(&source_code[0..2]) {
-                Some(ffi::TSInputEncodingUTF16LE)
-            } else if is_utf16_be_bom(&source_code[0..2]) {
-                Some(ffi::TSInputEncodingUTF16BE)
-            } else {
-                None
-            }
-        }
-        _ => opts.encoding,
-    };
+    let encoding = opts.encoding.detect(&source_code);
 
     // If the `--cancel` flag was passed, then cancel the parse
     // when the user types a newline.
@@ -359,71 +646,50 @@ pub fn parse_file_at_path(
 
     let parse_opts = ParseOptions::new().progress_callback(progress_callback);
 
-    let tree = match encoding {
-        Some(encoding) if encoding == ffi::TSInputEncodingUTF16LE => {
-            let source_code_utf16 = source_code
-                .chunks_exact(2)
-                .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
-                .collect::<Vec<_>>();
-            parser.parse_utf16_le_with_options(
-                &mut |i, _| {
-                    if i < source_code_utf16.len() {
-                        &source_code_utf16[i..]
-                    } else {
-                        &[]
-                    }
-                },
-                None,
-                Some(parse_opts),
-            )
-        }
-        Some(encoding) if encoding == ffi::TSInputEncodingUTF16BE => {
-            let source_code_utf16 = source_code
-                .chunks_exact(2)
-                .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
-                .collect::<Vec<_>>();
-            parser.parse_utf16_be_with_options(
-                &mut |i, _| {
-                    if i < source_code_utf16.len() {
-                        &source_code_utf16[i..]
-                    } else {
-                        &[]
-                    }
-                },
-                None,
-                Some(parse_opts),
-            )
-        }
-        _ => parser.parse_with_options(
-            &mut |i, _| {
-                if i < source_code.len() {
-                    &source_code[i..]
-                } else {
-                    &[]
-                }
-            },
-            None,
-            Some(parse_opts),
-        ),
-    };
+    parser.set_record_stats(opts.stat_detail);
+    let tree = encoding.parse(parser, &source_code, None, Some(parse_opts));
     let parse_duration = parse_time.elapsed();
-
-    let stdout = io::stdout();
-    let mut stdout = stdout.lock();
+    let mut detail = ParseDetail::from(parser.parse_stats());
 
     if let Some(mut tree) = tree {
         if opts.debug_graph && !opts.edits.is_empty() {
-            println!("BEFORE:\n{}", String::from_utf8_lossy(&source_code));
+            writeln!(out, "BEFORE:\n{}", encoding.decode(&source_code))?;
         }
 
+        // Byte offsets can only split code points in UTF-8 input.
+        let is_utf8 = encoding == Encoding::Utf8;
         let edit_time = Instant::now();
         for (i, edit) in opts.edits.iter().enumerate() {
-            let edit = parse_edit_flag(&source_code, edit)?;
+            let edit = parse_edit_flag(&source_code, edit, is_utf8)?;
             perform_edit(&mut tree, &mut source_code, &edit)?;
-            tree = parser.parse(&source_code, Some(&tree)).unwrap();
+            let new_tree = encoding
+                .parse(parser, &source_code, Some(&tree), None)
+                .unwrap();
+            detail.add(&parser.parse_stats().into(), true);
+
+            if opts.print_changed_ranges {
+                writeln!(out, "Changed ranges after edit {i}:")?;
+                let mut changed_ranges = tree.changed_ranges(&new_tree).peekable();
+                if changed_ranges.peek().is_none() {
+                    writeln!(out, "  none")?;
+                }
+                for range in changed_ranges {
+                    writeln!(
+                        out,
+                        "  [{}, {}] - [{}, {}] (bytes {} - {})",
+                        range.start_point.row,
+                        range.start_point.column,
+                        range.end_point.row,
+                        range.end_point.column,
+                        range.start_byte,
+                        range.end_byte
+                    )?;
+                }
+            }
+            tree = new_tree;
 
             if opts.debug_graph {
-                println!("AFTER {i}:\n{}", String::from_utf8_lossy(&source_code));
+                writeln!(out, "AFTER {i}:\n{}", encoding.decode(&source_code))?;
             }
         }
         let edit_duration = edit_time.elapsed();
@@ -435,15 +701,16 @@ pub fn parse_file_at_path(
         let mut cursor = tree.walk();
 
         if opts.output == ParseOutput::Normal {
+            let show_anonymous = opts.anonymous.unwrap_or(false);
             let mut needs_newline = false;
             let mut indent_level = 0;
             let mut did_visit_children = false;
             loop {
                 let node = cursor.node();
-                let is_named = node.is_named();
+                let is_visible = node.is_named() || show_anonymous;
                 if did_visit_children {
-                    if is_named {
-                        stdout.write_all(b")")?;
+                    if is_visible {
+                        out.write_all(b")")?;
                         needs_newline = true;
                     }
                     if cursor.goto_next_sibling() {
@@ -455,29 +722,33 @@ pub fn parse_file_at_path(
                         break;
                     }
                 } else {
-                    if is_named {
+                    let is_at_max_depth = opts.is_at_max_depth(&cursor);
+                    if is_visible {
                         if needs_newline {
-                            stdout.write_all(b"\n")?;
+                            out.write_all(b"\n")?;
                         }
                         for _ in 0..indent_level {
-                            stdout.write_all(b"  ")?;
+                            out.write_all(b"  ")?;
                         }
-                        let start = node.start_position();
-                        let end = node.end_position();
-                        if let Some(field_name) = cursor.field_name() {
-                            write!(&mut stdout, "{field_name}: ")?;
+                        if opts.fields {
+                            if let Some(field_name) = cursor.field_name() {
+                                write!(out, "{field_name}: ")?;
+                            }
+                        }
+                        if node.is_named() {
+                            write!(out, "({}", node.kind())?;
+                        } else {
+                            write!(out, "(\"{}\"", render_node_text(node.kind()))?;
                         }
-                        write!(&mut stdout, "({}", node.kind())?;
-                        if !opts.no_ranges {
-                            write!(
-                                &mut stdout,
-                                " [{}, {}] - [{}, {}]",
-                                start.row, start.column, end.row, end.column
-                            )?;
+                        write!(out, "{}", opts.ranges.format(node.range()))?;
+                        if is_at_max_depth && node.child_count() > 0 {
+                            write!(out, " ...")?;
                         }
                         needs_newline = true;
                     }
-                    if cursor.goto_first_child() {
+                    if is_at_max_depth {
+                        did_visit_children = true;
+                    } else if cursor.goto_first_child() {
                         did_visit_children = false;
                         indent_level += 1;
                     } else {
@@ -486,11 +757,11 @@ pub fn parse_file_at_path(
                 }
             }
             cursor.reset(tree.root_node());
-            println!();
+            writeln!(out)?;
         }
 
         if opts.output == ParseOutput::Cst {
-            let lossy_source_code = String::from_utf8_lossy(&source_code);
+            let lossy_source_code = encoding.decode(&source_code);
             let total_width = lossy_source_code
                 .lines()
                 .enumerate()
@@ -499,6 +770,11 @@ pub fn parse_file_at_path(
                 })
                 .max()
                 .unwrap_or(1);
+            let widths = RangeWidths {
+                points: total_width,
+                bytes: (source_code.len().max(1) as f64).log10() as usize + 1,
+            };
+            let show_anonymous = opts.anonymous.unwrap_or(true);
             let mut indent_level = 1;
             let mut did_visit_children = false;
             let mut in_error = false;
@@ -516,16 +792,22 @@ pub fn parse_file_at_path(
                         break;
                     }
                 } else {
-                    cst_render_node(
-                        opts,
-                        &mut cursor,
-                        &source_code,
-                        &mut stdout,
-                        total_width,
-                        indent_level,
-                        in_error,
-                    )?;
-                    if cursor.goto_first_child() {
+                    let node = cursor.node();
+                    if node.is_named() || node.is_missing() || show_anonymous {
+                        cst_render_node(
+                            opts,
+                            &mut cursor,
+                            &source_code,
+                            encoding,
+                            out,
+                            widths,
+                            indent_level,
+                            in_error,
+                        )?;
+                    }
+                    if opts.is_at_max_depth(&cursor) {
+                        did_visit_children = true;
+                    } else if cursor.goto_first_child() {
                         did_visit_children = false;
                         indent_level += 1;
                         if cursor.node().has_error() {
@@ -537,7 +819,7 @@ pub fn parse_file_at_path(
                 }
             }
             cursor.reset(tree.root_node());
-            println!();
+            writeln!(out)?;
         }
 
         if opts.output == ParseOutput::Xml {
@@ -546,7 +828,7 @@ pub fn parse_file_at_path(
             let mut did_visit_children = false;
             let mut had_named_children = false;
             let mut tags = Vec::<&str>::new();
-            writeln!(&mut stdout, "<?xml version=\"1.0\"?>")?;
+            writeln!(out, "<?xml version=\"1.0\"?>")?;
             loop {
                 let node = cursor.node();
                 let is_named = node.is_named();
@@ -555,14 +837,14 @@ pub fn parse_file_at_path(
                         let tag = tags.pop();
                         if had_named_children {
                             for _ in 0..indent_level {
-                                stdout.write_all(b"  ")?;
+                                out.write_all(b"  ")?;
                             }
                         }
-                        write!(&mut stdout, "</{}>", tag.expect("there is a tag"))?;
+                        write!(out, "</{}>", tag.expect("there is a tag"))?;
                         // we only write a line in the case where it's the last sibling
                         if let Some(parent) = node.parent() {
                             if parent.child(parent.child_count() - 1).unwrap() == node {
-                                stdout.write_all(b"\n")?;
+                                out.write_all(b"\n")?;
                             }
                         }
                         needs_newline = true;
@@ -575,9 +857,9 @@ pub fn parse_file_at_path(
                         had_named_children = is_named;
                         indent_level -= 1;
                         if !is_named && needs_newline {
-                            stdout.write_all(b"\n")?;
+                            out.write_all(b"\n")?;
                             for _ in 0..indent_level {
-                                stdout.write_all(b"  ")?;
+                                out.write_all(b"  ")?;
                             }
                         }
                     } else {
@@ -586,22 +868,22 @@ pub fn parse_file_at_path(
                 } else {
                     if is_named {
                         if needs_newline {
-                            stdout.write_all(b"\n")?;
+                            out.write_all(b"\n")?;
                         }
                         for _ in 0..indent_level {
-                            stdout.write_all(b"  ")?;
+                            out.write_all(b"  ")?;
                         }
-                        write!(&mut stdout, "<{}", node.kind())?;
+                        write!(out, "<{}", node.kind())?;
                         if let Some(field_name) = cursor.field_name() {
-                            write!(&mut stdout, " field=\"{field_name}\"")?;
+                            write!(out, " field=\"{field_name}\"")?;
                         }
                         let start = node.start_position();
                         let end = node.end_position();
-                        write!(&mut stdout, " srow=\"{}\"", start.row)?;
-                        write!(&mut stdout, " scol=\"{}\"", start.column)?;
-                        write!(&mut stdout, " erow=\"{}\"", end.row)?;
-                        write!(&mut stdout, " ecol=\"{}\"", end.column)?;
-                        write!(&mut stdout, ">")?;
+                        write!(out, " srow=\"{}\"", start.row)?;
+                        write!(out, " scol=\"{}\"", start.column)?;
+                        write!(out, " erow=\"{}\"", end.row)?;
+                        write!(out, " ecol=\"{}\"", end.column)?;
+                        write!(out, ">")?;
                         tags.push(node.kind());
                         needs_newline = true;
                     }
@@ -613,26 +895,51 @@ pub fn parse_file_at_path(
                         did_visit_children = true;
                         let start = node.start_byte();
                         let end = node.end_byte();
-                        let value =
-                            std::str::from_utf8(&source_code[start..end]).expect("has a string");
+                        let value = encoding.decode(&source_code[start..end]);
                         if !is_named && needs_newline {
-                            stdout.write_all(b"\n")?;
+                            out.write_all(b"\n")?;
                             for _ in 0..indent_level {
-                                stdout.write_all(b"  ")?;
+                                out.write_all(b"  ")?;
                             }
                         }
-                        write!(&mut stdout, "{}", html_escape::encode_text(value))?;
+                        write!(out, "{}", html_escape::encode_text(&value))?;
                     }
                 }
             }
             cursor.reset(tree.root_node());
-            println!();
+            writeln!(out)?;
         }
 
         if opts.output == ParseOutput::Dot {
             util::print_tree_graph(&tree, "log.html", opts.open_log).unwrap();
         }
 
+        if opts.output == ParseOutput::Json {
+            write_json_tree(
+                &mut io::BufWriter::new(&mut *out),
+                &tree,
+                opts.ranges == RangeFormat::None,
+            )?;
+        }
+
+        if opts.output == ParseOutput::DotTree {
+            write_dot_tree(
+                &mut io::BufWriter::new(&mut *out),
+                &tree,
+                opts.byte_range.as_ref(),
+                opts.ranges,
+            )?;
+        }
+
+        let errors = if opts.output == ParseOutput::Errors {
+            error_records(&tree, path, &source_code, encoding)
+        } else {
+            Vec::new()
+        };
+        for error in &errors {
+            writeln!(out, "{name}\t{error}")?;
+        }
+
         let mut first_error = None;
         let mut earliest_node_with_error = None;
         'outer: loop {
@@ -674,10 +981,10 @@ pub fn parse_file_at_path(
             }
         }
 
-        if first_error.is_some() || opts.print_time {
+        if (first_error.is_some() && opts.output != ParseOutput::Errors) || opts.print_time {
             let path = path.to_string_lossy();
             write!(
-                &mut stdout,
+                out,
                 "{:width$}\tParse: {parse_duration_ms:>7.2} ms\t{:>6} bytes/ms",
                 name,
                 (source_code.len() as u128 * 1_000_000) / parse_duration.as_nanos(),
@@ -686,71 +993,262 @@ pub fn parse_file_at_path(
             if let Some(node) = first_error {
                 let start = node.start_position();
                 let end = node.end_position();
-                write!(&mut stdout, "\t(")?;
-                if node.is_missing() {
-                    if node.is_named() {
-                        write!(&mut stdout, "MISSING {}", node.kind())?;
-                    } else {
-                        write!(
-                            &mut stdout,
-                            "MISSING \"{}\"",
-                            node.kind().replace('\n', "\\n")
-                        )?;
-                    }
-                } else {
-                    write!(&mut stdout, "{}", node.kind())?;
-                }
                 write!(
-                    &mut stdout,
-                    " [{}, {}] - [{}, {}])",
-                    start.row, start.column, end.row, end.column
+                    out,
+                    "\t({} [{}, {}] - [{}, {}])",
+                    error_node_description(node),
+                    start.row,
+                    start.column,
+                    end.row,
+                    end.column
                 )?;
             }
             if !opts.edits.is_empty() {
                 write!(
-                    &mut stdout,
+                    out,
                     "\n{:width$}\tEdit:  {edit_duration_ms:>7.2} ms",
                     " ".repeat(path.len()),
                     width = max_path_length,
                 )?;
             }
-            writeln!(&mut stdout)?;
+            writeln!(out)?;
+        }
+        if opts.stat_detail {
+            writeln!(out, "{name:max_path_length$}\t{detail}")?;
         }
 
-        opts.stats.parse_summaries.push(ParseSummary {
+        return Ok(ParseSummary {
             file: path.to_path_buf(),
             successful: true,
             start: Some(tree.root_node().start_position().into()),
             end: Some(tree.root_node().end_position().into()),
             duration: Some(parse_duration),
             bytes: Some(source_code.len()),
+            first_error: first_error.map(|node| ParseErrorSummary {
+                kind: error_node_description(node),
+                start: node.start_position().into(),
+                end: node.end_position().into(),
+            }),
+            detail: opts.stat_detail.then_some(detail),
+            errors,
         });
-
-        return Ok(());
     }
     parser.stop_printing_dot_graphs();
 
-    if opts.print_time {
+    if opts.print_time || opts.output == ParseOutput::Errors {
         let duration = parse_time.elapsed();
         let duration_ms = duration.as_micros() as f64 / 1e3;
         writeln!(
-            &mut stdout,
+            out,
             "{:width$}\tParse: {duration_ms:>7.2} ms\t(timed out)",
             path.to_str().unwrap(),
             width = max_path_length
         )?;
     }
 
-    opts.stats.parse_summaries.push(ParseSummary {
+    Ok(ParseSummary {
         file: path.to_path_buf(),
         successful: false,
         start: None,
         end: None,
         duration: None,
         bytes: Some(source_code.len()),
-    });
+        first_error: None,
+        detail: None,
+        errors: Vec::new(),
+    })
+}
 
-    Ok(())
+/// Parse the given files using up to `thread_count` threads, each with its own parser, and
+/// write their output in the order that the files were given.
+///
+/// With a single thread, each file's output is written as it is produced. Otherwise, it is
+/// buffered in memory until the output of the files before it has been written.
+///
+/// When `fail_fast` is set, no more files are parsed after one fails to parse or contains
+/// errors, and only the output of the files up to that one is written.
+pub fn parse_files_at_paths(
+    parser: &mut Parser,
+    files: &[(PathBuf, Language)],
+    max_path_length: usize,
+    thread_count: usize,
+    fail_fast: bool,
+    opts: &ParseFileOptions,
+    out: &mut impl Write,
+) -> Result<Vec<ParseSummary>> {
+    let mut summaries = Vec::with_capacity(files.len());
+
+    if thread_count <= 1 || files.len() <= 1 {
+        for (path, language) in files {
+            let summary = parse_file_at_path(
+                parser,
+                language,
+                path,
+                &path.display().to_string(),
+                max_path_length,
+                opts,
+                out,
+            )?;
+            let has_errors = summary.has_errors();
+            summaries.push(summary);
+            if fail_fast && has_errors {
+                break;
+            }
+        }
+        return Ok(summaries);
+    }
+
+    let next_index = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..thread_count.min(files.len()) {
+            let sender = sender.clone();
+            let (next_index, stop) = (&next_index, &stop);
+            scope.spawn(move || {
+                let mut parser = Parser::new();
+                while !stop.load(Ordering::SeqCst) {
+                    let index = next_index.fetch_add(1, Ordering::SeqCst);
+                    let Some((path, language)) = files.get(index) else {
+                        break;
+                    };
+                    let mut output = Vec::new();
+                    let result = parse_file_at_path(
+                        &mut parser,
+                        language,
+                        path,
+                        &path.display().to_string(),
+                        max_path_length,
+                        opts,
+                        &mut output,
+                    );
+                    if result
+                        .as_ref()
+                        .map_or(true, |summary| fail_fast && summary.has_errors())
+                    {
+                        stop.store(true, Ordering::SeqCst);
+                    }
+                    // The receiver is only dropped once the main thread has given up.
+                    if sender.send((index, output, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Files are claimed in order, so every file before a failing one has been claimed
+        // by the time that `stop` is set, and its output will still arrive.
+        let mut pending = HashMap::new();
+        for (index, output, result) in receiver {
+            pending.insert(index, (output, result));
+            while let Some((output, result)) = pending.remove(&summaries.len()) {
+                out.write_all(&output)?;
+                let summary = result?;
+                let has_errors = summary.has_errors();
+                summaries.push(summary);
+                if fail_fast && has_errors {
+                    return Ok(summaries);
+                }
+            }
+        }
+        Ok(summaries)
+    })
+}
+
+fn error_node_description(node: Node) -> String {
+    if node.is_missing() {
+        if node.is_named() {
+            format!("MISSING {}", node.kind())
+        } else {
+            format!("MISSING \"{}\"", node.kind().replace('\n', "\\n"))
+        }
+    } else {
+        node.kind().to_string()
+    }
+}
+
+/// Find every `ERROR` and `MISSING` node in a tree, without descending into `ERROR` nodes.
+fn error_records(
+    tree: &Tree,
+    path: &Path,
+    source_code: &[u8],
+    encoding: Encoding,
+) -> Vec<ParseErrorRecord> {
+    let mut records = Vec::new();
+    let mut cursor = tree.walk();
+    let mut did_visit_children = false;
+    loop {
+        if did_visit_children {
+            if cursor.goto_next_sibling() {
+                did_visit_children = false;
+            } else if !cursor.goto_parent() {
+                break;
+            }
+            continue;
+        }
+
+        let node = cursor.node();
+        if node.is_error() || node.is_missing() {
+            records.push(ParseErrorRecord {
+                file: path.to_path_buf(),
+                kind: error_node_description(node),
+                start_byte: node.start_byte(),
+                end_byte: node.end_byte(),
+                start: node.start_position().into(),
+                end: node.end_position().into(),
+                excerpt: error_excerpt(source_code, encoding, node),
+            });
+            did_visit_children = true;
+        } else {
+            did_visit_children = !(node.has_error() && cursor.goto_first_child());
+        }
+    }
+    records
+}
+
+/// The number of characters of source code shown for each error with `--errors-only`
+const ERROR_EXCERPT_LENGTH: usize = 60;
+
+/// Extract the text around the start of a node from the line that it starts on, replacing
+/// the parts of a long line that don't fit with ellipses.
+fn error_excerpt(source_code: &[u8], encoding: Encoding, node: Node) -> String {
+    // A character takes up at most four bytes, so these windows contain enough text without
+    // decoding the whole line, which could be very long in minified code.
+    let window_length = ERROR_EXCERPT_LENGTH * 4;
+    let start_byte = node.start_byte();
+    let line_start = start_byte - node.start_position().column;
+    let mut window_start = line_start.max(start_byte.saturating_sub(window_length / 2));
+    // Don't start decoding in the middle of a character.
+    match encoding {
+        Encoding::Utf16LE | Encoding::Utf16BE => window_start += (window_start - line_start) % 2,
+        Encoding::Auto | Encoding::Utf8 => {
+            while window_start < start_byte && source_code[window_start] & 0xC0 == 0x80 {
+                window_start += 1;
+            }
+        }
+        Encoding::Latin1 => {}
+    }
+    let window_end = source_code.len().min(start_byte + window_length);
+
+    let before = encoding.decode(&source_code[window_start..start_byte]);
+    let before = before.chars().collect::<Vec<_>>();
+    let after = encoding.decode(&source_code[start_byte..window_end]);
+    let after = after.split('\n').next().unwrap_or_default();
+    let after = after.strip_suffix('\r').unwrap_or(after);
+
+    let before_length = before.len().min(ERROR_EXCERPT_LENGTH / 3);
+    let after_length = ERROR_EXCERPT_LENGTH - before_length;
+    let mut excerpt = String::new();
+    if before_length < before.len() || window_start > line_start {
+        excerpt.push('…');
+    }
+    excerpt.extend(&before[before.len() - before_length..]);
+    excerpt.extend(after.chars().take(after_length));
+    if after.chars().nth(after_length).is_some() {
+        excerpt.push('…');
+    }
+    render_node_text(excerpt.trim_start_matches('\u{feff}').trim_start())
 }
 
 const fn escape_invisible(c: char) -> Option<&'static str> {
@@ -781,14 +1279,14 @@ fn render_node_text(source: &str) -> String {
 
 fn write_node_text(
     opts: &ParseFileOptions,
-    stdout: &mut StdoutLock<'static>,
+    out: &mut impl Write,
     cursor: &TreeCursor,
     is_named: bool,
     source: &str,
     color: Option<impl Into<Color> + Copy>,
-    text_info: (usize, usize),
+    text_info: (RangeWidths, usize),
 ) -> Result<()> {
-    let (total_width, indent_level) = text_info;
+    let (widths, indent_level) = text_info;
     let (quote, quote_color) = if is_named {
         ('`', opts.parse_theme.backtick)
     } else {
@@ -797,7 +1295,7 @@ fn write_node_text(
 
     if !is_named {
         write!(
-            stdout,
+            out,
             "{}{}{}",
             paint(quote_color, &String::from(quote)),
             paint(color, &render_node_text(source)),
@@ -805,6 +1303,7 @@ fn write_node_text(
         )?;
     } else {
         let multiline = source.contains('\n');
+        let mut line_start_byte = cursor.node().start_byte();
         for (i, line) in source.split_inclusive('\n').enumerate() {
             if line.is_empty() {
                 break;
@@ -820,14 +1319,17 @@ fn write_node_text(
                 } else {
                     0
                 };
+            node_range.start_byte = line_start_byte;
+            node_range.end_byte = line_start_byte + line.len();
+            line_start_byte = node_range.end_byte;
             let formatted_line = render_line_feed(line, opts);
-            if !opts.no_ranges {
+            if opts.ranges != RangeFormat::None {
                 write!(
-                    stdout,
+                    out,
                     "{}{}{}{}{}{}",
                     if multiline { "\n" } else { "" },
                     if multiline {
-                        render_node_range(opts, cursor, is_named, true, total_width, node_range)
+                        render_node_range(opts, cursor, is_named, true, widths, node_range)
                     } else {
                         String::new()
                     },
@@ -842,7 +1344,7 @@ fn write_node_text(
                 )?;
             } else {
                 write!(
-                    stdout,
+                    out,
                     "\n{}{}{}{}",
                     "  ".repeat(indent_level + 1),
                     paint(quote_color, &String::from(quote)),
@@ -864,12 +1366,19 @@ fn render_line_feed(source: &str, opts: &ParseFileOptions) -> String {
     }
 }
 
+/// The widths of the range columns in the CST output, which keep the node kinds aligned
+#[derive(Clone, Copy)]
+struct RangeWidths {
+    points: usize,
+    bytes: usize,
+}
+
 fn render_node_range(
     opts: &ParseFileOptions,
     cursor: &TreeCursor,
     is_named: bool,
     is_multiline: bool,
-    total_width: usize,
+    widths: RangeWidths,
     range: Range,
 ) -> String {
     let has_field_name = cursor.field_name().is_some();
@@ -879,17 +1388,16 @@ fn render_node_range(
         opts.parse_theme.row_color
     };
 
-    let remaining_width_start = (total_width
-        - (range.start_point.row as f64).log10() as usize
-        - (range.start_point.column as f64).log10() as usize)
-        .max(1);
-    let remaining_width_end = (total_width
-        - (range.end_point.row as f64).log10() as usize
-        - (range.end_point.column as f64).log10() as usize)
-        .max(1);
-    paint(
-        range_color,
-        &format!(
+    let points = || {
+        let remaining_width_start = (widths.points
+            - (range.start_point.row as f64).log10() as usize
+            - (range.start_point.column as f64).log10() as usize)
+            .max(1);
+        let remaining_width_end = (widths.points
+            - (range.end_point.row as f64).log10() as usize
+            - (range.end_point.column as f64).log10() as usize)
+            .max(1);
+        format!(
             "{}:{}{:remaining_width_start$}- {}:{}{:remaining_width_end$}",
             range.start_point.row,
             range.start_point.column,
@@ -897,30 +1405,43 @@ fn render_node_range(
             range.end_point.row,
             range.end_point.column,
             ' ',
-        ),
-    )
+        )
+    };
+    let bytes = || {
+        let offsets = format!("{}..{}", range.start_byte, range.end_byte);
+        format!("{offsets:<width$} ", width = widths.bytes * 2 + 2)
+    };
+    let text = match opts.ranges {
+        RangeFormat::Points => points(),
+        RangeFormat::Bytes => bytes(),
+        RangeFormat::Both => points() + &bytes(),
+        RangeFormat::None => String::new(),
+    };
+    paint(range_color, &text)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn cst_render_node(
     opts: &ParseFileOptions,
     cursor: &mut TreeCursor,
     source_code: &[u8],
-    stdout: &mut StdoutLock<'static>,
-    total_width: usize,
+    encoding: Encoding,
+    out: &mut impl Write,
+    widths: RangeWidths,
     indent_level: usize,
     in_error: bool,
 ) -> Result<()> {
     let node = cursor.node();
     let is_named = node.is_named();
-    if !opts.no_ranges {
+    if opts.ranges != RangeFormat::None {
         write!(
-            stdout,
+            out,
             "{}",
-            render_node_range(opts, cursor, is_named, false, total_width, node.range())
+            render_node_range(opts, cursor, is_named, false, widths, node.range())
         )?;
     }
     write!(
-        stdout,
+        out,
         "{}{}",
         "  ".repeat(indent_level),
         if in_error && !node.has_error() {
@@ -930,16 +1451,16 @@ fn cst_render_node(
         }
     )?;
     if is_named {
-        if let Some(field_name) = cursor.field_name() {
+        if let Some(field_name) = cursor.field_name().filter(|_| opts.fields) {
             write!(
-                stdout,
+                out,
                 "{}",
                 paint(opts.parse_theme.field, &format!("{field_name}: "))
             )?;
         }
 
         if node.has_error() || node.is_error() {
-            write!(stdout, "{}", paint(opts.parse_theme.error, "•"))?;
+            write!(out, "{}", paint(opts.parse_theme.error, "•"))?;
         }
 
         let kind_color = if node.is_error() {
@@ -949,44 +1470,201 @@ fn cst_render_node(
         } else {
             opts.parse_theme.node_kind
         };
-        write!(stdout, "{} ", paint(kind_color, node.kind()))?;
+        write!(out, "{} ", paint(kind_color, node.kind()))?;
 
-        if node.child_count() == 0 {
+        if node.child_count() > 0 && opts.is_at_max_depth(cursor) {
+            write!(out, "{}", paint(opts.parse_theme.node_text, "..."))?;
+        } else if node.child_count() == 0 {
             // Node text from a pattern or external scanner
             write_node_text(
                 opts,
-                stdout,
+                out,
                 cursor,
                 is_named,
-                &String::from_utf8_lossy(&source_code[node.start_byte()..node.end_byte()]),
+                &encoding.decode(&source_code[node.start_byte()..node.end_byte()]),
                 opts.parse_theme.node_text,
-                (total_width, indent_level),
+                (widths, indent_level),
             )?;
         }
     } else if node.is_missing() {
-        write!(stdout, "{}: ", paint(opts.parse_theme.missing, "MISSING"))?;
-        write!(
-            stdout,
-            "\"{}\"",
-            paint(opts.parse_theme.missing, node.kind())
-        )?;
+        write!(out, "{}: ", paint(opts.parse_theme.missing, "MISSING"))?;
+        write!(out, "\"{}\"", paint(opts.parse_theme.missing, node.kind()))?;
     } else {
         // Terminal literals, like "fn"
         write_node_text(
             opts,
-            stdout,
+            out,
             cursor,
             is_named,
             node.kind(),
             opts.parse_theme.literal,
-            (total_width, indent_level),
+            (widths, indent_level),
         )?;
     }
-    writeln!(stdout)?;
+    writeln!(out)?;
 
     Ok(())
 }
 
+/// Write a syntax tree as a JSON object, with a nested object for each child node.
+///
+/// The nodes are written one at a time, so that a tree for a large file can be written
+/// without building the whole JSON document in memory.
+pub fn write_json_tree(out: &mut impl Write, tree: &Tree, no_ranges: bool) -> io::Result<()> {
+    let mut cursor = tree.walk();
+    let mut did_visit_children = false;
+    loop {
+        if did_visit_children {
+            if cursor.goto_next_sibling() {
+                out.write_all(b",")?;
+                did_visit_children = false;
+            } else if cursor.goto_parent() {
+                out.write_all(b"]}")?;
+            } else {
+                break;
+            }
+        } else {
+            let node = cursor.node();
+            write!(out, "{{\"type\":")?;
+            serde_json::to_writer(&mut *out, node.kind())?;
+            write!(out, ",\"named\":{}", node.is_named())?;
+            if let Some(field_name) = cursor.field_name() {
+                write!(out, ",\"field\":")?;
+                serde_json::to_writer(&mut *out, field_name)?;
+            }
+            if node.is_error() {
+                write!(out, ",\"isError\":true")?;
+            }
+            if node.is_missing() {
+                write!(out, ",\"isMissing\":true")?;
+            }
+            if !no_ranges {
+                let start = node.start_position();
+                let end = node.end_position();
+                write!(
+                    out,
+                    ",\"startByte\":{},\"endByte\":{},\"startPosition\":{{\"row\":{},\"column\":{}}},\"endPosition\":{{\"row\":{},\"column\":{}}}",
+                    node.start_byte(),
+                    node.end_byte(),
+                    start.row,
+                    start.column,
+                    end.row,
+                    end.column
+                )?;
+            }
+            if cursor.goto_first_child() {
+                write!(out, ",\"children\":[")?;
+            } else {
+                write!(out, "}}")?;
+                did_visit_children = true;
+            }
+        }
+    }
+    writeln!(out)?;
+    out.flush()
+}
+
+/// Write a syntax tree as a graphviz dot graph, with field names on the edges. Anonymous nodes
+/// are drawn as boxes, and `ERROR` and `MISSING` nodes are filled in red.
+///
+/// If a byte range is given, only the nodes that overlap it are included.
+pub fn write_dot_tree(
+    out: &mut impl Write,
+    tree: &Tree,
+    byte_range: Option<&ops::Range<usize>>,
+    ranges: RangeFormat,
+) -> io::Result<()> {
+    let is_in_range = |node: Node| {
+        byte_range.is_none_or(|range| {
+            if node.start_byte() == node.end_byte() {
+                range.start <= node.start_byte() && node.start_byte() <= range.end
+            } else {
+                node.start_byte() < range.end && node.end_byte() > range.start
+            }
+        })
+    };
+
+    writeln!(out, "digraph tree {{")?;
+    writeln!(out, "  node [fontname=\"monospace\"];")?;
+    writeln!(out, "  edge [fontname=\"monospace\"];")?;
+    let mut cursor = tree.walk();
+    // The ids of the current node's ancestors
+    let mut parent_ids = Vec::new();
+    let mut next_id = 0;
+    let mut did_visit_children = false;
+    loop {
+        if did_visit_children {
+            if cursor.goto_next_sibling() {
+                did_visit_children = false;
+            } else if cursor.goto_parent() {
+                parent_ids.pop();
+            } else {
+                break;
+            }
+            continue;
+        }
+
+        let node = cursor.node();
+        if !is_in_range(node) {
+            did_visit_children = true;
+            continue;
+        }
+
+        let id = next_id;
+        next_id += 1;
+        let kind = if node.is_missing() {
+            error_node_description(node)
+        } else if node.is_named() {
+            node.kind().to_string()
+        } else {
+            format!("\"{}\"", node.kind())
+        };
+        let mut label = escape_dot(&kind);
+        let range = ranges.format(node.range());
+        if !range.is_empty() {
+            label.push_str("\\n");
+            label.push_str(range.trim_start());
+        }
+        write!(out, "  n{id} [label=\"{label}\"")?;
+        if !node.is_named() {
+            write!(out, ", shape=box")?;
+        }
+        if node.is_error() || node.is_missing() {
+            write!(out, ", style=filled, fillcolor=red")?;
+        }
+        writeln!(out, "];")?;
+        if let Some(parent_id) = parent_ids.last() {
+            write!(out, "  n{parent_id} -> n{id}")?;
+            if let Some(field_name) = cursor.field_name() {
+                write!(out, " [label=\"{field_name}\"]")?;
+            }
+            writeln!(out, ";")?;
+        }
+
+        if cursor.goto_first_child() {
+            parent_ids.push(id);
+        } else {
+            did_visit_children = true;
+        }
+    }
+    writeln!(out, "}}")?;
+    out.flush()
+}
+
+/// Escape text so that it can be used in a quoted dot string.
+fn escape_dot(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
 pub fn perform_edit(tree: &mut Tree, input: &mut Vec<u8>, edit: &Edit) -> Result<InputEdit> {
     let start_byte = edit.position;
     let old_end_byte = edit.position + edit.deleted_length;
@@ -1007,7 +1685,7 @@ pub fn perform_edit(tree: &mut Tree, input: &mut Vec<u8>, edit: &Edit) -> Result
     Ok(edit)
 }
 
-fn parse_edit_flag(source_code: &[u8], flag: &str) -> Result<Edit> {
+fn parse_edit_flag(source_code: &[u8], flag: &str, is_utf8: bool) -> Result<Edit> {
     let error = || {
         anyhow!(concat!(
             "Invalid edit string '{}'. ",
@@ -1041,6 +1719,32 @@ fn parse_edit_flag(source_code: &[u8], flag: &str) -> Result<Edit> {
     // Deleted length must be a byte count.
     let deleted_length = deleted_length.parse::<usize>().map_err(|_| error())?;
 
+    // The edit must be within the source code, and must not split a UTF-8 code point.
+    if position > source_code.len() {
+        return Err(anyhow!(
+            "Invalid edit string '{flag}'. The start byte {position} is past the end of the source code, which is {} bytes long",
+            source_code.len()
+        ));
+    }
+    if deleted_length > source_code.len() - position {
+        return Err(anyhow!(
+            "Invalid edit string '{flag}'. Removing {deleted_length} bytes from byte {position} goes past the end of the source code, which is {} bytes long",
+            source_code.len()
+        ));
+    }
+    if is_utf8 {
+        for offset in [position, position + deleted_length] {
+            if source_code
+                .get(offset)
+                .is_some_and(|byte| byte & 0xC0 == 0x80)
+            {
+                return Err(anyhow!(
+                    "Invalid edit string '{flag}'. Byte {offset} is in the middle of a multi-byte UTF-8 character"
+                ));
+            }
+        }
+    }
+
     Ok(Edit {
         position,
         deleted_length,
@@ -1093,3 +1797,62 @@ pub fn position_for_offset(input: &[u8], offset: usize) -> Result<Point> {
     };
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_edit_flag() {
+        let source_code = "let x = 1;\nlet y = 2;\n".as_bytes();
+
+        let edit = parse_edit_flag(source_code, "4 1 foo bar", true).unwrap();
+        assert_eq!(edit.position, 4);
+        assert_eq!(edit.deleted_length, 1);
+        assert_eq!(edit.inserted_text, b"foo bar");
+
+        let edit = parse_edit_flag(source_code, "1,4 1 z", true).unwrap();
+        assert_eq!(edit.position, 15);
+
+        let edit = parse_edit_flag(source_code, "$ 0 ;", true).unwrap();
+        assert_eq!(edit.position, source_code.len());
+
+        assert_eq!(
+            parse_edit_flag(source_code, "30 0 x", true)
+                .unwrap_err()
+                .to_string(),
+            "Invalid edit string '30 0 x'. The start byte 30 is past the end of the source code, which is 22 bytes long"
+        );
+        assert_eq!(
+            parse_edit_flag(source_code, "20 5 x", true)
+                .unwrap_err()
+                .to_string(),
+            "Invalid edit string '20 5 x'. Removing 5 bytes from byte 20 goes past the end of the source code, which is 22 bytes long"
+        );
+        assert!(parse_edit_flag(source_code, "4", true).is_err());
+        assert!(parse_edit_flag(source_code, "x 1 y", true).is_err());
+    }
+
+    #[test]
+    fn test_parse_edit_flag_with_multi_byte_characters() {
+        // `é` is encoded as two bytes, at offsets 3 and 4.
+        let source_code = "café!".as_bytes();
+
+        assert!(parse_edit_flag(source_code, "3 2 e", true).is_ok());
+        assert_eq!(
+            parse_edit_flag(source_code, "4 1 e", true)
+                .unwrap_err()
+                .to_string(),
+            "Invalid edit string '4 1 e'. Byte 4 is in the middle of a multi-byte UTF-8 character"
+        );
+        assert_eq!(
+            parse_edit_flag(source_code, "3 1 e", true)
+                .unwrap_err()
+                .to_string(),
+            "Invalid edit string '3 1 e'. Byte 4 is in the middle of a multi-byte UTF-8 character"
+        );
+
+        // Other encodings are edited byte by byte.
+        assert!(parse_edit_flag(source_code, "4 1 e", false).is_ok());
+    }
+}
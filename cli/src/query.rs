@@ -1,110 +1,165 @@
 use std::{
     fs,
     io::{self, Write},
+    iter,
+    ops::AddAssign,
     ops::Range,
-    path::Path,
+    path::{Path, PathBuf},
     time::Instant,
 };
 
 use anstyle::AnsiColor;
 use anyhow::{Context, Result};
+use serde::Serialize;
 use streaming_iterator::StreamingIterator;
-use tree_sitter::{Language, Parser, Point, Query, QueryCursor};
+use tree_sitter::{Language, Node, Parser, Point, Query, QueryCursor};
 
 use crate::{
+    parse::{Encoding, ParsePoint},
     query_testing::{self, to_utf8_point},
     test::paint,
 };
 
+/// A match of a query, as printed on its own line with `--format json`
+#[derive(Serialize)]
+struct QueryMatchRecord<'a> {
+    file: &'a str,
+    query: &'a Path,
+    pattern_index: usize,
+    /// Whether the match was rejected by a text predicate, such as `#eq?` or `#match?`
+    suppressed: bool,
+    captures: Vec<QueryCaptureRecord<'a>>,
+}
+
+#[derive(Serialize)]
+struct QueryCaptureRecord<'a> {
+    name: &'a str,
+    kind: &'a str,
+    start_byte: usize,
+    end_byte: usize,
+    start: ParsePoint,
+    end: ParsePoint,
+    /// The source text of the captured node, with `--capture-text`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+}
+
+/// The number of assertions that held and that failed in the files checked with `--test`
+#[derive(Default, Clone, Copy)]
+pub struct AssertionCounts {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+impl AddAssign for AssertionCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.passed += other.passed;
+        self.failed += other.failed;
+    }
+}
+
+/// Run the queries in `query_paths` on the file at `path`, printing their matches, or with
+/// `should_test`, checking the file's assertion comments against their captures.
 #[allow(clippy::too_many_arguments)]
 pub fn query_file_at_path(
     language: &Language,
     path: &Path,
     name: &str,
-    query_path: &Path,
+    query_paths: &[PathBuf],
     ordered_captures: bool,
     byte_range: Option<Range<usize>>,
     point_range: Option<Range<Point>>,
     should_test: bool,
     quiet: bool,
     print_time: bool,
+    encoding: Encoding,
     stdin: bool,
-) -> Result<()> {
+    json: bool,
+    capture_text: bool,
+) -> Result<AssertionCounts> {
     let stdout = io::stdout();
     let mut stdout = stdout.lock();
 
-    let query_source = fs::read_to_string(query_path)
-        .with_context(|| format!("Error reading query file {query_path:?}"))?;
-    let query = Query::new(language, &query_source).with_context(|| "Query compilation failed")?;
+    let queries = query_paths
+        .iter()
+        .map(|query_path| {
+            let query_source = fs::read_to_string(query_path)
+                .with_context(|| format!("Error reading query file {query_path:?}"))?;
+            Query::new(language, &query_source)
+                .with_context(|| format!("Query compilation failed for {query_path:?}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
 
     let mut query_cursor = QueryCursor::new();
-    if let Some(range) = byte_range {
-        query_cursor.set_byte_range(range);
-    }
-    if let Some(range) = point_range {
-        query_cursor.set_point_range(range);
-    }
 
     let mut parser = Parser::new();
     parser.set_language(language)?;
 
     let mut results = Vec::new();
 
-    if !should_test && !stdin {
+    if !should_test && !stdin && !json {
         writeln!(&mut stdout, "{name}")?;
     }
 
     let source_code =
         fs::read(path).with_context(|| format!("Error reading source file {path:?}"))?;
-    let tree = parser.parse(&source_code, None).unwrap();
+    let encoding = encoding.detect(&source_code);
+    let tree = encoding
+        .parse(&mut parser, &source_code, None, None)
+        .unwrap();
+
+    // Ranges that extend past the end of the file are clamped to it, so that the same range can
+    // be used for files of different lengths.
+    if let Some(mut range) = byte_range {
+        if range.end > source_code.len() {
+            eprintln!(
+                "Warning: The byte range {}:{} extends past the end of {name}, which is {} bytes long",
+                range.start,
+                range.end,
+                source_code.len()
+            );
+            range.end = source_code.len();
+            range.start = range.start.min(range.end);
+        }
+        query_cursor.set_byte_range(range);
+    }
+    if let Some(mut range) = point_range {
+        let row_count = tree.root_node().end_position().row + 1;
+        if range.end.row > row_count {
+            eprintln!(
+                "Warning: The row range {}:{} extends past the end of {name}, which has {row_count} rows",
+                range.start.row, range.end.row
+            );
+            range.end = Point::new(row_count, 0);
+            range.start = range.start.min(range.end);
+        }
+        query_cursor.set_point_range(range);
+    }
+
+    let node_text = |node: Node| encoding.decode(&source_code[node.byte_range()]);
+    // Predicates compare the decoded text of nodes, so that they work in any encoding.
+    let text_provider = |node: Node| iter::once(node_text(node).into_owned().into_bytes());
 
     let start = Instant::now();
-    if ordered_captures {
-        let mut captures = query_cursor.captures(&query, tree.root_node(), source_code.as_slice());
-        while let Some((mat, capture_index)) = captures.next() {
-            let capture = mat.captures[*capture_index];
-            let capture_name = &query.capture_names()[capture.index as usize];
-            if !quiet && !should_test {
-                writeln!(
-                        &mut stdout,
-                        "    pattern: {:>2}, capture: {} - {capture_name}, start: {}, end: {}, text: `{}`",
-                        mat.pattern_index,
-                        capture.index,
-                        capture.node.start_position(),
-                        capture.node.end_position(),
-                        capture.node.utf8_text(&source_code).unwrap_or("")
-                    )?;
-            }
-            results.push(query_testing::CaptureInfo {
-                name: (*capture_name).to_string(),
-                start: to_utf8_point(capture.node.start_position(), source_code.as_slice()),
-                end: to_utf8_point(capture.node.end_position(), source_code.as_slice()),
-            });
+    for (query_path, query) in query_paths.iter().zip(&queries) {
+        if queries.len() > 1 && !quiet && !should_test && !json {
+            writeln!(&mut stdout, "  query: {}", query_path.display())?;
         }
-    } else {
-        let mut matches = query_cursor.matches(&query, tree.root_node(), source_code.as_slice());
-        while let Some(m) = matches.next() {
-            if !quiet && !should_test {
-                writeln!(&mut stdout, "  pattern: {}", m.pattern_index)?;
-            }
-            for capture in m.captures {
-                let start = capture.node.start_position();
-                let end = capture.node.end_position();
+        if ordered_captures {
+            let mut captures = query_cursor.captures(query, tree.root_node(), text_provider);
+            while let Some((mat, capture_index)) = captures.next() {
+                let capture = mat.captures[*capture_index];
                 let capture_name = &query.capture_names()[capture.index as usize];
                 if !quiet && !should_test {
-                    if end.row == start.row {
-                        writeln!(
-                                &mut stdout,
-                                "    capture: {} - {capture_name}, start: {start}, end: {end}, text: `{}`",
-                                capture.index,
-                                capture.node.utf8_text(&source_code).unwrap_or("")
-                            )?;
-                    } else {
-                        writeln!(
+                    writeln!(
                             &mut stdout,
-                            "    capture: {capture_name}, start: {start}, end: {end}",
+                            "    pattern: {:>2}, capture: {} - {capture_name}, start: {}, end: {}, text: `{}`",
+                            mat.pattern_index,
+                            capture.index,
+                            capture.node.start_position(),
+                            capture.node.end_position(),
+                            node_text(capture.node)
                         )?;
-                    }
                 }
                 results.push(query_testing::CaptureInfo {
                     name: (*capture_name).to_string(),
@@ -112,31 +167,128 @@ pub fn query_file_at_path(
                     end: to_utf8_point(capture.node.end_position(), source_code.as_slice()),
                 });
             }
+        } else {
+            let mut matches = query_cursor.matches(query, tree.root_node(), text_provider);
+            // Matches rejected by a text predicate are included in the JSON output, marked as
+            // suppressed, to help debug the predicates.
+            matches.set_include_unsatisfied_matches(json);
+            let mut predicate_text_provider = text_provider;
+            let (mut buffer1, mut buffer2) = (Vec::new(), Vec::new());
+            while let Some(m) = matches.next() {
+                if json {
+                    if !quiet {
+                        let record = QueryMatchRecord {
+                            file: name,
+                            query: query_path,
+                            pattern_index: m.pattern_index,
+                            suppressed: !m.satisfies_text_predicates(
+                                query,
+                                &mut buffer1,
+                                &mut buffer2,
+                                &mut predicate_text_provider,
+                            ),
+                            captures: m
+                                .captures
+                                .iter()
+                                .map(|capture| QueryCaptureRecord {
+                                    name: query.capture_names()[capture.index as usize],
+                                    kind: capture.node.kind(),
+                                    start_byte: capture.node.start_byte(),
+                                    end_byte: capture.node.end_byte(),
+                                    start: capture.node.start_position().into(),
+                                    end: capture.node.end_position().into(),
+                                    text: capture_text
+                                        .then(|| node_text(capture.node).into_owned()),
+                                })
+                                .collect(),
+                        };
+                        writeln!(&mut stdout, "{}", serde_json::to_string(&record)?)?;
+                    }
+                    continue;
+                }
+                if !quiet && !should_test {
+                    writeln!(&mut stdout, "  pattern: {}", m.pattern_index)?;
+                }
+                for capture in m.captures {
+                    let start = capture.node.start_position();
+                    let end = capture.node.end_position();
+                    let capture_name = &query.capture_names()[capture.index as usize];
+                    if !quiet && !should_test {
+                        if end.row == start.row {
+                            writeln!(
+                                    &mut stdout,
+                                    "    capture: {} - {capture_name}, start: {start}, end: {end}, text: `{}`",
+                                    capture.index,
+                                    node_text(capture.node)
+                                )?;
+                        } else {
+                            writeln!(
+                                &mut stdout,
+                                "    capture: {capture_name}, start: {start}, end: {end}",
+                            )?;
+                        }
+                    }
+                    results.push(query_testing::CaptureInfo {
+                        name: (*capture_name).to_string(),
+                        start: to_utf8_point(capture.node.start_position(), source_code.as_slice()),
+                        end: to_utf8_point(capture.node.end_position(), source_code.as_slice()),
+                    });
+                }
+            }
         }
     }
     if query_cursor.did_exceed_match_limit() {
-        writeln!(
-            &mut stdout,
-            "  WARNING: Query exceeded maximum number of in-progress captures!"
-        )?;
+        if json {
+            eprintln!("WARNING: Query exceeded maximum number of in-progress captures in {name}!");
+        } else {
+            writeln!(
+                &mut stdout,
+                "  WARNING: Query exceeded maximum number of in-progress captures!"
+            )?;
+        }
     }
+    let mut counts = AssertionCounts::default();
     if should_test {
         let path_name = if stdin {
             "stdin"
         } else {
             Path::new(&path).file_name().unwrap().to_str().unwrap()
         };
-        match query_testing::assert_expected_captures(&results, path, &mut parser, language) {
-            Ok(assertion_count) => {
-                println!(
-                    "  ✓ {} ({} assertions)",
-                    paint(Some(AnsiColor::Green), path_name),
-                    assertion_count
-                );
-            }
-            Err(e) => {
-                println!("  ✗ {}", paint(Some(AnsiColor::Red), path_name));
-                return Err(e);
+        let outcomes =
+            query_testing::check_expected_captures(&results, path, &mut parser, language)?;
+        counts.failed = outcomes
+            .iter()
+            .filter(|(_, outcome)| outcome.is_err())
+            .count();
+        counts.passed = outcomes.len() - counts.failed;
+        if counts.failed == 0 {
+            println!(
+                "  ✓ {} ({} assertions)",
+                paint(Some(AnsiColor::Green), path_name),
+                outcomes.len()
+            );
+        } else {
+            println!(
+                "  ✗ {} ({} of {} assertions failed)",
+                paint(Some(AnsiColor::Red), path_name),
+                counts.failed,
+                outcomes.len()
+            );
+            for (assertion, outcome) in &outcomes {
+                match outcome {
+                    Ok(()) => println!(
+                        "    ✓ row: {}, column: {}, {} capture '{}'",
+                        assertion.position.row,
+                        assertion.position.column,
+                        if assertion.negative {
+                            "unexpected"
+                        } else {
+                            "expected"
+                        },
+                        assertion.expected_capture_name
+                    ),
+                    Err(failure) => println!("    ✗ {failure}"),
+                }
             }
         }
     }
@@ -144,5 +296,5 @@ pub fn query_file_at_path(
         writeln!(&mut stdout, "{:?}", start.elapsed())?;
     }
 
-    Ok(())
+    Ok(counts)
 }
@@ -0,0 +1,1243 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, fs,
+    hash::BuildHasher,
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Language, Query, QueryError, QueryErrorKind};
+
+use crate::parse::ParsePoint;
+
+/// What the query compiler concluded about one pattern, as printed by `query --analyze`
+#[derive(Serialize)]
+pub struct PatternAnalysis<'a> {
+    query: &'a Path,
+    pattern_index: usize,
+    start_byte: usize,
+    end_byte: usize,
+    start: ParsePoint,
+    /// Whether every match of the pattern starts at the same node, missing when the pattern
+    /// doesn't compile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rooted: Option<bool>,
+    /// Whether the pattern can match across more than one parent node, like a top-level
+    /// sequence of siblings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    non_local: Option<bool>,
+    node_types: Vec<NodeTypeReference>,
+    fields: Vec<FieldReference>,
+    steps: Vec<PatternStep>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<PatternError>,
+}
+
+#[derive(Serialize)]
+struct NodeTypeReference {
+    name: String,
+    named: bool,
+    valid: bool,
+}
+
+#[derive(Serialize)]
+struct FieldReference {
+    name: String,
+    valid: bool,
+}
+
+/// A node, anonymous node, or wildcard in a pattern, each of which the query compiles into
+/// one step that a match has to pass through
+#[derive(Serialize)]
+struct PatternStep {
+    start_byte: usize,
+    start: ParsePoint,
+    depth: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
+    kind: String,
+    named: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    supertype: Option<String>,
+    /// Whether the step always matches once the pattern's root node has matched, missing for the
+    /// root itself and when the pattern doesn't compile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guaranteed: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct PatternError {
+    kind: &'static str,
+    start_byte: usize,
+    start: ParsePoint,
+    message: String,
+    /// For an impossible pattern, the combination of nodes that can never occur
+    #[serde(skip_serializing_if = "Option::is_none")]
+    impossible_child: Option<ImpossibleChild>,
+}
+
+#[derive(Serialize)]
+struct ImpossibleChild {
+    parent: NodeType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
+    child: NodeType,
+    /// The node types that `node-types.json` allows in this position, when it is available
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allowed: Option<Vec<NodeType>>,
+}
+
+/// The parts of a grammar's `node-types.json` that describe which children a node can have
+#[derive(Deserialize)]
+pub struct NodeInfo {
+    #[serde(rename = "type")]
+    kind: String,
+    named: bool,
+    #[serde(default)]
+    fields: BTreeMap<String, ChildTypes>,
+    children: Option<ChildTypes>,
+    #[serde(default)]
+    subtypes: Vec<NodeType>,
+}
+
+#[derive(Deserialize)]
+struct ChildTypes {
+    types: Vec<NodeType>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct NodeType {
+    #[serde(rename = "type")]
+    kind: String,
+    named: bool,
+}
+
+/// Read the `src/node-types.json` of the grammar in `grammar_dir`, if it has been generated.
+pub fn load_node_types(grammar_dir: &Path) -> Result<Option<Vec<NodeInfo>>> {
+    let path = grammar_dir.join("src").join("node-types.json");
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read node types from {}", path.display()))?;
+    let node_types = serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse node types from {}", path.display()))?;
+    Ok(Some(node_types))
+}
+
+/// Compile each pattern in the query at `query_path` on its own, and print what the compiler
+/// concluded about it. Returns the number of patterns that failed to compile.
+pub fn analyze_query_at_path(
+    language: &Language,
+    query_path: &Path,
+    node_types: Option<&[NodeInfo]>,
+    json: bool,
+) -> Result<usize> {
+    let source = fs::read_to_string(query_path)
+        .with_context(|| format!("Error reading query file {}", query_path.display()))?;
+
+    let mut error_count = 0;
+    for (pattern_index, pattern) in scan_patterns(&source).into_iter().enumerate() {
+        let analysis = analyze_pattern(
+            language,
+            query_path,
+            &source,
+            pattern_index,
+            pattern,
+            node_types,
+        )?;
+        if analysis.error.is_some() {
+            error_count += 1;
+        }
+        if json {
+            println!("{}", serde_json::to_string(&analysis)?);
+        } else {
+            print_analysis(&analysis, &source);
+        }
+    }
+    Ok(error_count)
+}
+
+fn analyze_pattern<'a>(
+    language: &Language,
+    query_path: &'a Path,
+    source: &str,
+    pattern_index: usize,
+    pattern: ScannedPattern,
+    node_types: Option<&[NodeInfo]>,
+) -> Result<PatternAnalysis<'a>> {
+    let start_byte = pattern.range.start;
+    let mut steps = pattern
+        .steps
+        .into_iter()
+        .map(|step| PatternStep {
+            start_byte: step.start_byte,
+            start: point_for_offset(source, step.start_byte),
+            depth: step.depth,
+            field: step.field,
+            kind: step.kind,
+            named: step.named,
+            supertype: step.supertype,
+            guaranteed: None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut analysis = PatternAnalysis {
+        query: query_path,
+        pattern_index,
+        start_byte,
+        end_byte: pattern.range.end,
+        start: point_for_offset(source, start_byte),
+        rooted: None,
+        non_local: None,
+        node_types: node_type_references(language, &steps),
+        fields: field_references(language, &steps, &pattern.negated_fields),
+        steps: Vec::new(),
+        error: None,
+    };
+
+    match Query::new(language, &source[pattern.range]) {
+        Ok(query) => {
+            analysis.rooted = Some(query.is_pattern_rooted(0));
+            analysis.non_local = Some(query.is_pattern_non_local(0));
+            // Matches start at the steps at the root of the pattern, so only the steps below
+            // them can be guaranteed.
+            for step in steps.iter_mut().filter(|step| step.depth > 0) {
+                step.guaranteed =
+                    Some(query.is_pattern_guaranteed_at_step(step.start_byte - start_byte));
+            }
+        }
+        Err(error) if error.kind == QueryErrorKind::Language => {
+            return Err(anyhow!(error.message));
+        }
+        Err(error) => {
+            analysis.error = Some(pattern_error(
+                source, start_byte, &error, &steps, node_types,
+            ));
+        }
+    }
+    analysis.steps = steps;
+    Ok(analysis)
+}
+
+fn node_type_references(language: &Language, steps: &[PatternStep]) -> Vec<NodeTypeReference> {
+    let mut seen = HashSet::new();
+    steps
+        .iter()
+        .flat_map(|step| {
+            step.supertype
+                .iter()
+                .map(|supertype| (supertype.as_str(), true))
+                .chain([(step.kind.as_str(), step.named)])
+        })
+        .filter(|(kind, _)| *kind != "_")
+        .filter(|reference| seen.insert(*reference))
+        .map(|(kind, named)| NodeTypeReference {
+            name: kind.to_string(),
+            named,
+            valid: node_kind_exists(language, kind, named),
+        })
+        .collect()
+}
+
+fn node_kind_exists(language: &Language, kind: &str, named: bool) -> bool {
+    (named && matches!(kind, "ERROR" | "MISSING")) || language.id_for_node_kind(kind, named) != 0
+}
+
+fn field_references(
+    language: &Language,
+    steps: &[PatternStep],
+    negated_fields: &[String],
+) -> Vec<FieldReference> {
+    let mut seen = HashSet::new();
+    steps
+        .iter()
+        .filter_map(|step| step.field.as_deref())
+        .chain(negated_fields.iter().map(String::as_str))
+        .filter(|name| seen.insert(*name))
+        .map(|name| FieldReference {
+            name: name.to_string(),
+            valid: language.field_id_for_name(name).is_some(),
+        })
+        .collect()
+}
+
+fn pattern_error(
+    source: &str,
+    pattern_start: usize,
+    error: &QueryError,
+    steps: &[PatternStep],
+    node_types: Option<&[NodeInfo]>,
+) -> PatternError {
+    let start_byte = pattern_start + error.offset;
+    let (kind, message, impossible_child) = match error.kind {
+        QueryErrorKind::Syntax => ("syntax", "Invalid syntax".to_string(), None),
+        QueryErrorKind::NodeType => (
+            "node_type",
+            format!("Invalid node type `{}`", error.message),
+            None,
+        ),
+        QueryErrorKind::Field => (
+            "field",
+            format!("Invalid field name `{}`", error.message),
+            None,
+        ),
+        QueryErrorKind::Capture => (
+            "capture",
+            format!("Invalid capture name `{}`", error.message),
+            None,
+        ),
+        QueryErrorKind::Predicate => (
+            "predicate",
+            format!("Invalid predicate: {}", error.message),
+            None,
+        ),
+        QueryErrorKind::Structure | QueryErrorKind::Language => {
+            let (message, impossible_child) = explain_impossible_step(
+                steps,
+                steps.iter().position(|step| step.start_byte >= start_byte),
+                node_types,
+            );
+            ("structure", message, impossible_child)
+        }
+    };
+    PatternError {
+        kind,
+        start_byte,
+        start: point_for_offset(source, start_byte),
+        message,
+        impossible_child,
+    }
+}
+
+/// Describe why the step at `step_index`, where the compiler found that no match can continue,
+/// can't occur inside its parent, using `node-types.json` to list the node types that can.
+fn explain_impossible_step(
+    steps: &[PatternStep],
+    step_index: Option<usize>,
+    node_types: Option<&[NodeInfo]>,
+) -> (String, Option<ImpossibleChild>) {
+    let Some((child, parent)) = step_index.and_then(|index| {
+        let child = &steps[index];
+        let parent = steps[..index]
+            .iter()
+            .rev()
+            .find(|step| step.depth + 1 == child.depth)?;
+        Some((child, parent))
+    }) else {
+        return ("Impossible pattern".to_string(), None);
+    };
+
+    let parent_name = display_kind(&parent.kind, parent.named);
+    let child_name = display_kind(&child.kind, child.named);
+    let mut impossible_child = ImpossibleChild {
+        parent: NodeType {
+            kind: parent.kind.clone(),
+            named: parent.named,
+        },
+        field: child.field.clone(),
+        child: NodeType {
+            kind: child.kind.clone(),
+            named: child.named,
+        },
+        allowed: None,
+    };
+
+    let parent_info = node_types.and_then(|node_types| {
+        node_types
+            .iter()
+            .find(|info| info.kind == parent.kind && info.named == parent.named)
+    });
+    let allowed = parent_info.map(|info| {
+        if let Some(field) = &child.field {
+            info.fields
+                .get(field)
+                .map(|types| types.types.clone())
+                .unwrap_or_default()
+        } else {
+            info.children
+                .iter()
+                .chain(info.fields.values())
+                .flat_map(|types| types.types.iter().cloned())
+                .collect()
+        }
+    });
+
+    let message = match (&allowed, &child.field) {
+        (Some(allowed), None) if allowed.is_empty() => {
+            format!("Impossible pattern: {parent_name} nodes can't have any named children")
+        }
+        (Some(allowed), Some(field)) if allowed.is_empty() => {
+            format!("Impossible pattern: {parent_name} nodes don't have a `{field}` field")
+        }
+        (Some(allowed), field)
+            if child.kind != "_"
+                && (child.named || field.is_some())
+                && !allowed
+                    .iter()
+                    .any(|t| can_be(node_types.unwrap_or_default(), t, child)) =>
+        {
+            let names = allowed
+                .iter()
+                .map(|t| display_kind(&t.kind, t.named))
+                .collect::<Vec<_>>();
+            let message = if let Some(field) = field {
+                format!(
+                    "Impossible pattern: the `{field}` field of {parent_name} nodes can't \
+                     contain {child_name} nodes, only {}",
+                    names.join(", ")
+                )
+            } else {
+                format!(
+                    "Impossible pattern: {parent_name} nodes can't contain {child_name} nodes, \
+                     only {}",
+                    names.join(", ")
+                )
+            };
+            impossible_child.allowed = Some(allowed.clone());
+            message
+        }
+        (Some(_), _) => format!(
+            "Impossible pattern: {parent_name} nodes can contain {child_name} nodes, but not in \
+             this position relative to the pattern's other children"
+        ),
+        (None, Some(field)) => format!(
+            "Impossible pattern: {child_name} nodes can't occur in the `{field}` field of \
+             {parent_name} nodes in this position"
+        ),
+        (None, None) => format!(
+            "Impossible pattern: {child_name} nodes can't occur inside {parent_name} nodes in \
+             this position"
+        ),
+    };
+    (message, Some(impossible_child))
+}
+
+/// Whether a child of type `node_type` can match `step`, including through the subtypes of a
+/// supertype.
+fn can_be(node_types: &[NodeInfo], node_type: &NodeType, step: &PatternStep) -> bool {
+    if node_type.kind == step.kind && node_type.named == step.named {
+        return true;
+    }
+    node_types
+        .iter()
+        .find(|info| info.kind == node_type.kind && info.named == node_type.named)
+        .is_some_and(|info| {
+            info.subtypes
+                .iter()
+                .any(|subtype| can_be(node_types, subtype, step))
+        })
+}
+
+fn display_kind(kind: &str, named: bool) -> String {
+    if named {
+        format!("`{kind}`")
+    } else {
+        format!("`\"{kind}\"`")
+    }
+}
+
+fn print_analysis(analysis: &PatternAnalysis, source: &str) {
+    println!(
+        "{}:{}:{}: pattern {}",
+        analysis.query.display(),
+        analysis.start.row + 1,
+        analysis.start.column + 1,
+        analysis.pattern_index
+    );
+    for line in source[analysis.start_byte..analysis.end_byte].lines() {
+        println!("    {line}");
+    }
+
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    if let Some(rooted) = analysis.rooted {
+        println!("  rooted: {}", yes_no(rooted));
+    }
+    if let Some(non_local) = analysis.non_local {
+        println!("  non-local: {}", yes_no(non_local));
+    }
+    let references = analysis
+        .node_types
+        .iter()
+        .map(|r| (display_kind(&r.name, r.named), r.valid))
+        .collect::<Vec<_>>();
+    print_references("node types", &references);
+    let references = analysis
+        .fields
+        .iter()
+        .map(|r| (format!("`{}`", r.name), r.valid))
+        .collect::<Vec<_>>();
+    print_references("fields", &references);
+
+    println!("  steps:");
+    for step in &analysis.steps {
+        let field = step
+            .field
+            .as_ref()
+            .map_or(String::new(), |field| format!("{field}: "));
+        let supertype = step
+            .supertype
+            .as_ref()
+            .map_or(String::new(), |supertype| format!("{supertype}/"));
+        let kind = if step.named {
+            step.kind.clone()
+        } else {
+            format!("\"{}\"", step.kind)
+        };
+        let description = format!("{}{field}{supertype}{kind}", "  ".repeat(step.depth));
+        match step.guaranteed {
+            Some(true) => println!("    {description:<40} guaranteed"),
+            Some(false) => println!("    {description:<40} can fail"),
+            None => println!("    {description}"),
+        }
+    }
+
+    if let Some(error) = &analysis.error {
+        println!(
+            "  error at {}:{}: {}",
+            error.start.row + 1,
+            error.start.column + 1,
+            error.message
+        );
+    }
+    println!();
+}
+
+fn print_references(label: &str, references: &[(String, bool)]) {
+    if references.is_empty() {
+        println!("  {label}: none");
+        return;
+    }
+    let references = references
+        .iter()
+        .map(|(name, valid)| {
+            if *valid {
+                name.clone()
+            } else {
+                format!("{name} (invalid)")
+            }
+        })
+        .collect::<Vec<_>>();
+    println!("  {label}: {}", references.join(", "));
+}
+
+/// How serious a problem that `lint-queries` found is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The query compiles, but probably doesn't do what was intended
+    Warning,
+    /// The pattern doesn't compile
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem that `lint-queries` found at a position in a query file
+#[derive(Debug, PartialEq, Eq)]
+pub struct QueryDiagnostic {
+    pub severity: Severity,
+    pub start: ParsePoint,
+    pub message: String,
+}
+
+/// Check the query at `query_path` for problems, print them as diagnostics, and return them.
+pub fn lint_query_at_path<S: BuildHasher>(
+    language: &Language,
+    query_path: &Path,
+    node_types: Option<&[NodeInfo]>,
+    recognized_names: &HashSet<&str, S>,
+) -> Result<Vec<QueryDiagnostic>> {
+    let source = fs::read_to_string(query_path)
+        .with_context(|| format!("Error reading query file {}", query_path.display()))?;
+    let diagnostics = lint_query(language, &source, node_types, recognized_names)?;
+    for diagnostic in &diagnostics {
+        println!(
+            "{}:{}:{}: {}: {}",
+            query_path.display(),
+            diagnostic.start.row + 1,
+            diagnostic.start.column + 1,
+            diagnostic.severity,
+            diagnostic.message
+        );
+    }
+    Ok(diagnostics)
+}
+
+/// Check a query for problems, sorted by their position in the query.
+///
+/// Node types and fields that the language doesn't have, captures that aren't in
+/// `recognized_names`, and patterns that can never match because an identical pattern before
+/// them takes precedence are reported as warnings. Patterns that don't compile for any other
+/// reason are reported as errors.
+pub fn lint_query<S: BuildHasher>(
+    language: &Language,
+    source: &str,
+    node_types: Option<&[NodeInfo]>,
+    recognized_names: &HashSet<&str, S>,
+) -> Result<Vec<QueryDiagnostic>> {
+    let mut diagnostics = Vec::new();
+    let mut warn = |offset: usize, message: String| {
+        diagnostics.push(QueryDiagnostic {
+            severity: Severity::Warning,
+            start: point_for_offset(source, offset),
+            message,
+        });
+    };
+
+    let mut shapes = Vec::<(usize, PatternShape)>::new();
+    let mut errors = Vec::new();
+    for pattern in scan_patterns(source) {
+        let start_byte = pattern.range.start;
+        let text = &source[pattern.range.clone()];
+
+        for step in &pattern.steps {
+            let kinds = step
+                .supertype
+                .iter()
+                .map(|supertype| (supertype.as_str(), true))
+                .chain([(step.kind.as_str(), step.named)]);
+            for (kind, named) in kinds {
+                if kind != "_" && !node_kind_exists(language, kind, named) {
+                    warn(
+                        step.start_byte,
+                        format!("Unknown node type {}", display_kind(kind, named)),
+                    );
+                }
+            }
+            if let Some(field) = &step.field {
+                if language.field_id_for_name(field).is_none() {
+                    warn(step.start_byte, format!("Unknown field `{field}`"));
+                }
+            }
+        }
+        for field in &pattern.negated_fields {
+            if language.field_id_for_name(field).is_none() {
+                let offset = text.find(&format!("!{field}")).unwrap_or(0);
+                warn(start_byte + offset, format!("Unknown field `{field}`"));
+            }
+        }
+
+        let shape = PatternShape::new(text);
+        for (offset, name) in &shape.captures {
+            if !(recognized_names.contains(name)
+                || name.starts_with('_')
+                || name.starts_with("injection.")
+                || name.starts_with("local."))
+            {
+                warn(
+                    start_byte + offset,
+                    format!("Capture `@{name}` is not a recognized highlight name"),
+                );
+            }
+        }
+
+        match Query::new(language, text) {
+            Ok(_) => {
+                if let Some((earlier_start, _)) =
+                    shapes.iter().find(|(_, earlier)| earlier.shadows(&shape))
+                {
+                    let earlier = point_for_offset(source, *earlier_start);
+                    warn(
+                        start_byte,
+                        format!(
+                            "Pattern can never match, because the identical pattern at {}:{} \
+                             takes precedence",
+                            earlier.row + 1,
+                            earlier.column + 1
+                        ),
+                    );
+                }
+                shapes.push((start_byte, shape));
+            }
+            Err(error) if error.kind == QueryErrorKind::Language => {
+                return Err(anyhow!(error.message));
+            }
+            // These are already reported as warnings.
+            Err(error)
+                if matches!(error.kind, QueryErrorKind::NodeType | QueryErrorKind::Field) => {}
+            Err(error) => {
+                let steps = pattern
+                    .steps
+                    .into_iter()
+                    .map(|step| PatternStep {
+                        start_byte: step.start_byte,
+                        start: point_for_offset(source, step.start_byte),
+                        depth: step.depth,
+                        field: step.field,
+                        kind: step.kind,
+                        named: step.named,
+                        supertype: step.supertype,
+                        guaranteed: None,
+                    })
+                    .collect::<Vec<_>>();
+                errors.push(pattern_error(
+                    source, start_byte, &error, &steps, node_types,
+                ));
+            }
+        }
+    }
+
+    diagnostics.extend(errors.into_iter().map(|error| QueryDiagnostic {
+        severity: Severity::Error,
+        start: error.start,
+        message: error.message,
+    }));
+    diagnostics.sort_by_key(|diagnostic| diagnostic.start);
+    Ok(diagnostics)
+}
+
+/// A token of a pattern, without the whitespace and comments around it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PatternToken<'a> {
+    Punctuation(char),
+    Capture(&'a str),
+    Text(&'a str),
+}
+
+/// The parts of a pattern that decide which nodes it captures, for comparing patterns
+/// regardless of their formatting and capture names
+struct PatternShape<'a> {
+    /// The pattern's tokens outside of its predicates, with the capture names left out, and
+    /// without any group around the whole pattern
+    structure: Vec<PatternToken<'a>>,
+    predicates: Vec<PatternToken<'a>>,
+    /// The offset and name of each capture outside of the predicates
+    captures: Vec<(usize, &'a str)>,
+}
+
+impl<'a> PatternShape<'a> {
+    fn new(text: &'a str) -> Self {
+        let tokens = tokenize_pattern(text);
+        let mut shape = PatternShape {
+            structure: Vec::new(),
+            predicates: Vec::new(),
+            captures: Vec::new(),
+        };
+        let mut i = 0;
+        while i < tokens.len() {
+            let (offset, token) = tokens[i];
+            let is_predicate = token == PatternToken::Punctuation('(')
+                && matches!(tokens.get(i + 1), Some((_, PatternToken::Text(t))) if t.starts_with('#'));
+            if is_predicate {
+                let end = matching_close(tokens.iter().map(|(_, token)| token), i)
+                    .unwrap_or(tokens.len() - 1);
+                shape
+                    .predicates
+                    .extend(tokens[i..=end].iter().map(|(_, token)| *token));
+                i = end + 1;
+                continue;
+            }
+            if let PatternToken::Capture(name) = token {
+                shape.captures.push((offset, name));
+                shape.structure.push(PatternToken::Capture(""));
+            } else {
+                shape.structure.push(token);
+            }
+            i += 1;
+        }
+
+        // A group of a single node matches the same nodes as the node itself.
+        while shape.structure.len() > 2
+            && shape.structure[..2] == [PatternToken::Punctuation('('); 2]
+            && matching_close(&shape.structure, 0) == Some(shape.structure.len() - 1)
+        {
+            let Some(inner_end) = matching_close(&shape.structure, 1) else {
+                break;
+            };
+            let last = shape.structure.len() - 1;
+            if !shape.structure[inner_end + 1..last].iter().all(|token| {
+                matches!(
+                    token,
+                    PatternToken::Capture(_) | PatternToken::Text("*" | "+" | "?")
+                )
+            }) {
+                break;
+            }
+            shape.structure = shape.structure[1..last].to_vec();
+        }
+        shape
+    }
+
+    /// Whether this pattern takes precedence over a later pattern in every match of the later
+    /// one, because it matches the same nodes, captures the same nodes, and has no predicates
+    /// that the later one doesn't have.
+    fn shadows(&self, later: &Self) -> bool {
+        !self.captures.is_empty()
+            && self.structure == later.structure
+            && (self.predicates.is_empty() || self.predicates == later.predicates)
+    }
+}
+
+fn tokenize_pattern(text: &str) -> Vec<(usize, PatternToken<'_>)> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            ';' => while chars.next_if(|(_, c)| *c != '\n').is_some() {},
+            '(' | ')' | '[' | ']' | ':' => tokens.push((start, PatternToken::Punctuation(c))),
+            '"' => {
+                let mut end = text.len();
+                while let Some((i, c)) = chars.next() {
+                    if c == '\\' {
+                        chars.next();
+                    } else if c == '"' {
+                        end = i + 1;
+                        break;
+                    }
+                }
+                tokens.push((start, PatternToken::Text(&text[start..end])));
+            }
+            c if c.is_whitespace() => {}
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) =
+                    chars.next_if(|(_, c)| !c.is_whitespace() && !"()[]:\";".contains(*c))
+                {
+                    end = i + c.len_utf8();
+                }
+                let word = &text[start..end];
+                let token = word
+                    .strip_prefix('@')
+                    .map_or(PatternToken::Text(word), PatternToken::Capture);
+                tokens.push((start, token));
+            }
+        }
+    }
+    tokens
+}
+
+/// Find the index of the token that closes the parenthesis or bracket at `open`.
+fn matching_close<'a, 'b: 'a>(
+    tokens: impl IntoIterator<Item = &'a PatternToken<'b>>,
+    open: usize,
+) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.into_iter().enumerate().skip(open) {
+        match token {
+            PatternToken::Punctuation('(' | '[') => depth += 1,
+            PatternToken::Punctuation(')' | ']') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn point_for_offset(source: &str, offset: usize) -> ParsePoint {
+    let before = &source[..offset];
+    let row = before.matches('\n').count();
+    let column = offset - before.rfind('\n').map_or(0, |i| i + 1);
+    ParsePoint { row, column }
+}
+
+/// A top-level pattern, found by scanning the query's source without compiling it, so that the
+/// patterns of a query that doesn't compile can still be analyzed one at a time
+#[derive(Debug, PartialEq, Eq)]
+struct ScannedPattern {
+    range: std::ops::Range<usize>,
+    steps: Vec<ScannedStep>,
+    negated_fields: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ScannedStep {
+    /// The offset of the step's field name, if it has one, or of the node itself, which is the
+    /// offset that the query compiler records for the step
+    start_byte: usize,
+    depth: usize,
+    field: Option<String>,
+    kind: String,
+    named: bool,
+    supertype: Option<String>,
+}
+
+fn scan_patterns(source: &str) -> Vec<ScannedPattern> {
+    let mut scanner = Scanner {
+        source,
+        offset: 0,
+        steps: Vec::new(),
+        negated_fields: Vec::new(),
+    };
+    let mut patterns = Vec::<ScannedPattern>::new();
+    loop {
+        scanner.skip_trivia();
+        if scanner.peek().is_none() {
+            break;
+        }
+        let start = scanner.offset;
+        scanner.scan_element(0, None);
+        let steps = std::mem::take(&mut scanner.steps);
+        let negated_fields = std::mem::take(&mut scanner.negated_fields);
+
+        // A predicate outside of any pattern applies to the pattern before it.
+        match patterns.last_mut() {
+            Some(pattern) if steps.is_empty() => pattern.range.end = scanner.offset,
+            _ => patterns.push(ScannedPattern {
+                range: start..scanner.offset,
+                steps,
+                negated_fields,
+            }),
+        }
+    }
+    patterns
+}
+
+struct Scanner<'a> {
+    source: &'a str,
+    offset: usize,
+    steps: Vec<ScannedStep>,
+    negated_fields: Vec<String>,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.offset..].chars().next()
+    }
+
+    fn advance(&mut self) {
+        if let Some(c) = self.peek() {
+            self.offset += c.len_utf8();
+        }
+    }
+
+    fn skip_trivia(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == ';' {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.advance();
+                }
+            } else if c.is_whitespace() {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn scan_identifier(&mut self) -> String {
+        let start = self.offset;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '?' | '!'))
+        {
+            self.advance();
+        }
+        self.source[start..self.offset].to_string()
+    }
+
+    fn scan_string(&mut self) -> String {
+        let mut value = String::new();
+        self.advance();
+        while let Some(c) = self.peek() {
+            self.advance();
+            match c {
+                '"' => break,
+                '\\' => {
+                    match self.peek() {
+                        Some('n') => value.push('\n'),
+                        Some('r') => value.push('\r'),
+                        Some('t') => value.push('\t'),
+                        Some('0') => value.push('\0'),
+                        Some(c) => value.push(c),
+                        None => break,
+                    }
+                    self.advance();
+                }
+                c => value.push(c),
+            }
+        }
+        value
+    }
+
+    fn push_named_step(
+        &mut self,
+        start_byte: usize,
+        depth: usize,
+        field: Option<String>,
+        kind: String,
+    ) {
+        let (supertype, kind) = match kind.split_once('/') {
+            Some((supertype, subtype)) => (Some(supertype.to_string()), subtype.to_string()),
+            None => (None, kind),
+        };
+        self.steps.push(ScannedStep {
+            start_byte,
+            depth,
+            field,
+            kind,
+            named: true,
+            supertype,
+        });
+    }
+
+    /// Scan one node, anonymous node, wildcard, alternation or group, along with its quantifier
+    /// and captures.
+    fn scan_element(&mut self, depth: usize, field: Option<(String, usize)>) {
+        let start_byte = field.as_ref().map_or(self.offset, |(_, start)| *start);
+        let field = field.map(|(name, _)| name);
+        match self.peek() {
+            Some('[') => {
+                self.advance();
+                self.scan_children(depth, ']');
+            }
+            Some('(') => {
+                self.advance();
+                self.skip_trivia();
+                match self.peek() {
+                    Some('#') => self.skip_predicate(),
+                    Some('(' | '[' | '"') => self.scan_children(depth, ')'),
+                    _ => {
+                        let mut kind = self.scan_identifier();
+                        if self.peek() == Some('/') {
+                            self.advance();
+                            kind = format!("{kind}/{}", self.scan_identifier());
+                        }
+                        if kind == "MISSING" {
+                            self.skip_trivia();
+                            match self.peek() {
+                                Some('"') => {
+                                    let kind = self.scan_string();
+                                    self.steps.push(ScannedStep {
+                                        start_byte,
+                                        depth,
+                                        field,
+                                        kind,
+                                        named: false,
+                                        supertype: None,
+                                    });
+                                    self.scan_children(depth + 1, ')');
+                                    self.scan_suffixes();
+                                    return;
+                                }
+                                Some(c) if c.is_alphanumeric() || c == '_' => {
+                                    kind = self.scan_identifier();
+                                }
+                                _ => {}
+                            }
+                        }
+                        self.push_named_step(start_byte, depth, field, kind);
+                        self.scan_children(depth + 1, ')');
+                    }
+                }
+            }
+            Some('"') => {
+                let kind = self.scan_string();
+                self.steps.push(ScannedStep {
+                    start_byte,
+                    depth,
+                    field,
+                    kind,
+                    named: false,
+                    supertype: None,
+                });
+            }
+            Some('_') => {
+                self.advance();
+                self.steps.push(ScannedStep {
+                    start_byte,
+                    depth,
+                    field,
+                    kind: "_".to_string(),
+                    named: false,
+                    supertype: None,
+                });
+            }
+            Some(c) if c.is_alphanumeric() => {
+                let start = self.offset;
+                let name = self.scan_identifier();
+                self.skip_trivia();
+                if self.peek() == Some(':') {
+                    self.advance();
+                    self.skip_trivia();
+                    self.scan_element(depth, Some((name, start)));
+                    return;
+                }
+            }
+            Some(_) => self.advance(),
+            None => {}
+        }
+        self.scan_suffixes();
+    }
+
+    /// Scan the children of a node, or the branches of an alternation or a group, up to the
+    /// closing delimiter.
+    fn scan_children(&mut self, depth: usize, close: char) {
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                Some(c) if c == close => {
+                    self.advance();
+                    return;
+                }
+                None | Some(')' | ']') => return,
+                Some('.') => self.advance(),
+                Some('!') => {
+                    self.advance();
+                    let name = self.scan_identifier();
+                    self.negated_fields.push(name);
+                }
+                Some(_) => self.scan_element(depth, None),
+            }
+        }
+    }
+
+    fn skip_predicate(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                '"' => {
+                    self.scan_string();
+                }
+                '(' => {
+                    self.advance();
+                    self.skip_predicate();
+                }
+                ')' => {
+                    self.advance();
+                    return;
+                }
+                _ => self.advance(),
+            }
+        }
+    }
+
+    fn scan_suffixes(&mut self) {
+        loop {
+            let end = self.offset;
+            self.skip_trivia();
+            match self.peek() {
+                Some('*' | '+' | '?') => self.advance(),
+                Some('@') => {
+                    self.advance();
+                    self.scan_identifier();
+                }
+                _ => {
+                    self.offset = end;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_patterns() {
+        let source = r#"
+; Calls
+(call_expression
+  function: (identifier) @function
+  !type_arguments
+  arguments: (arguments "(" (_)* ")")) @call
+
+[
+  (true)
+  (false)
+] @boolean
+
+((identifier) @constant
+ (#match? @constant "^[A-Z]"))
+(#set! priority 90)
+
+(expression/identifier) _
+"#;
+        let patterns = scan_patterns(source);
+        let texts = patterns
+            .iter()
+            .map(|pattern| &source[pattern.range.clone()])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                "(call_expression\n  function: (identifier) @function\n  !type_arguments\n  \
+                 arguments: (arguments \"(\" (_)* \")\")) @call",
+                "[\n  (true)\n  (false)\n] @boolean",
+                "((identifier) @constant\n (#match? @constant \"^[A-Z]\"))\n(#set! priority 90)",
+                "(expression/identifier)",
+                "_",
+            ]
+        );
+
+        let steps = patterns[0]
+            .steps
+            .iter()
+            .map(|step| {
+                (
+                    &source[step.start_byte..step.start_byte + 1],
+                    step.depth,
+                    step.field.as_deref(),
+                    step.kind.as_str(),
+                    step.named,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            [
+                ("(", 0, None, "call_expression", true),
+                ("f", 1, Some("function"), "identifier", true),
+                ("a", 1, Some("arguments"), "arguments", true),
+                ("\"", 2, None, "(", false),
+                ("(", 2, None, "_", true),
+                ("\"", 2, None, ")", false),
+            ]
+        );
+        assert_eq!(patterns[0].negated_fields, ["type_arguments"]);
+
+        let step = &patterns[3].steps[0];
+        assert_eq!(step.supertype.as_deref(), Some("expression"));
+        assert_eq!(step.kind, "identifier");
+        assert!(!patterns[4].steps[0].named);
+    }
+
+    #[test]
+    fn test_pattern_shadowing() {
+        let shadows = |earlier: &str, later: &str| {
+            PatternShape::new(earlier).shadows(&PatternShape::new(later))
+        };
+
+        // Formatting, comments, capture names and groups around the pattern don't matter.
+        assert!(shadows(
+            "(call function: (identifier) @function)",
+            "(call\n  ; the callee\n  function: (identifier) @function.call)",
+        ));
+        assert!(shadows(
+            "(identifier) @variable",
+            "((identifier) @constant (#match? @constant \"^[A-Z]\"))",
+        ));
+        assert!(shadows(
+            "((identifier) @a (#eq? @a \"self\"))",
+            "((identifier) @a (#eq? @a \"self\"))",
+        ));
+
+        // The captured nodes, the structure and the earlier pattern's predicates do.
+        assert!(!shadows(
+            "(call function: (identifier) @function)",
+            "(call function: (identifier)) @call",
+        ));
+        assert!(!shadows(
+            "(identifier) @variable",
+            "(identifier)* @variable"
+        ));
+        assert!(!shadows(
+            "((identifier) @constant (#match? @constant \"^[A-Z]\"))",
+            "(identifier) @variable",
+        ));
+        assert!(!shadows("((a) @a (b))", "(a) @a"));
+        assert!(!shadows("(identifier)", "(identifier) @variable"));
+    }
+}
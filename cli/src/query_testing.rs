@@ -219,36 +219,116 @@ pub fn parse_position_comments(
     Ok(result)
 }
 
-pub fn assert_expected_captures(
+/// An assertion that did not hold, along with the names of the captures that were
+/// actually found at the position where it failed.
+#[derive(Debug)]
+pub struct AssertionFailure {
+    pub position: Utf8Point,
+    pub negative: bool,
+    pub kind: &'static str,
+    pub expected_capture_name: String,
+    pub actual_capture_names: Vec<String>,
+}
+
+impl std::error::Error for AssertionFailure {}
+
+impl std::fmt::Display for AssertionFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Failure - row: {}, column: {}, {} {} '{}', actual {}s: ",
+            self.position.row,
+            self.position.column,
+            if self.negative {
+                "unexpected"
+            } else {
+                "expected"
+            },
+            self.kind,
+            self.expected_capture_name,
+            self.kind,
+        )?;
+        if self.actual_capture_names.is_empty() {
+            write!(f, "none.")?;
+        } else {
+            for (i, name) in self.actual_capture_names.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "'{name}'")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check each assertion against the given `(start, end, name)` captures, stopping at the first
+/// one that fails.
+pub fn check_assertions<S: AsRef<str>>(
+    assertions: &[Assertion],
+    captures: &[(Utf8Point, Utf8Point, S)],
+    kind: &'static str,
+) -> Result<usize, AssertionFailure> {
+    for assertion in assertions {
+        check_assertion(assertion, captures, kind)?;
+    }
+    Ok(assertions.len())
+}
+
+/// Check an assertion against the given `(start, end, name)` captures.
+///
+/// An assertion with several carets must hold at every column it covers: a positive assertion
+/// needs a capture with the expected name at each column, and a negative one must not find one
+/// at any of them.
+pub fn check_assertion<S: AsRef<str>>(
+    assertion: &Assertion,
+    captures: &[(Utf8Point, Utf8Point, S)],
+    kind: &'static str,
+) -> Result<(), AssertionFailure> {
+    let start_column = assertion.position.column;
+    for column in start_column..start_column + assertion.length {
+        let position = Utf8Point::new(assertion.position.row, column);
+        let actual_capture_names = captures
+            .iter()
+            .filter(|(start, end, _)| *start <= position && position < *end)
+            .map(|(_, _, name)| name.as_ref())
+            .collect::<Vec<_>>();
+        let found = actual_capture_names.contains(&assertion.expected_capture_name.as_str());
+        if found == assertion.negative {
+            return Err(AssertionFailure {
+                position,
+                negative: assertion.negative,
+                kind,
+                expected_capture_name: assertion.expected_capture_name.clone(),
+                actual_capture_names: actual_capture_names
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check every assertion in the file at `path` against the captures of the queries that were run
+/// on it, returning each assertion along with whether it held.
+pub fn check_expected_captures(
     infos: &[CaptureInfo],
     path: &Path,
     parser: &mut Parser,
     language: &Language,
-) -> Result<usize> {
+) -> Result<Vec<(Assertion, Result<(), AssertionFailure>)>> {
     let contents = fs::read_to_string(path)?;
-    let pairs = parse_position_comments(parser, language, contents.as_bytes())?;
-    for assertion in &pairs {
-        if let Some(found) = &infos.iter().find(|p| {
-            assertion.position >= p.start
-                && (assertion.position.row < p.end.row
-                    || assertion.position.column + assertion.length - 1 < p.end.column)
-        }) {
-            if assertion.expected_capture_name != found.name && found.name != "name" {
-                return Err(anyhow!(
-                    "Assertion failed: at {}, found {}, expected {}",
-                    found.start,
-                    assertion.expected_capture_name,
-                    found.name
-                ));
-            }
-        } else {
-            return Err(anyhow!(
-                "Assertion failed: could not match {} at row {}, column {}",
-                assertion.expected_capture_name,
-                assertion.position.row,
-                assertion.position.column + assertion.length - 1,
-            ));
-        }
-    }
-    Ok(pairs.len())
+    let assertions = parse_position_comments(parser, language, contents.as_bytes())?;
+    let captures = infos
+        .iter()
+        .map(|info| (info.start, info.end, info.name.as_str()))
+        .collect::<Vec<_>>();
+    Ok(assertions
+        .into_iter()
+        .map(|assertion| {
+            let outcome = check_assertion(&assertion, &captures, "capture");
+            (assertion, outcome)
+        })
+        .collect())
 }
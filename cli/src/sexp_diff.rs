@@ -0,0 +1,489 @@
+use std::{fmt::Write, ops::Range};
+
+use anstyle::AnsiColor;
+
+use crate::test::paint;
+
+/// The number of unchanged lines that are shown before and after each change.
+const CONTEXT_LINES: usize = 2;
+
+/// A node of a parsed S-expression, as produced by `Node::to_sexp`.
+#[derive(Debug, PartialEq, Eq)]
+struct SexpNode<'a> {
+    field: Option<&'a str>,
+    kind: String,
+    children: Vec<Self>,
+    /// The index of this node in a pre-order traversal of the whole tree, or `usize::MAX` for
+    /// text assertions
+    index: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Change {
+    Equal,
+    /// The node is only in the actual tree
+    Removed,
+    /// The node is only in the expected tree
+    Added,
+}
+
+#[derive(Debug)]
+struct DiffLine {
+    change: Change,
+    depth: usize,
+    text: String,
+    parent: Option<usize>,
+}
+
+/// Prints the structural difference between the actual and expected S-expressions of a test.
+///
+/// The two trees are aligned node by node, and only the differing subtrees are printed, along
+/// with their ancestors and a few lines of context. Nodes that are only in the actual tree are
+/// colored red, and nodes that are only in the expected tree are colored green. `actual_ranges`
+/// holds the byte range of every node of the actual tree in pre-order, which is attached to
+/// each line of the actual tree.
+///
+/// Returns `false` without printing anything if either S-expression can't be parsed, or if they
+/// don't differ structurally.
+#[must_use]
+pub fn print_sexp_diff(
+    actual: &str,
+    expected: &str,
+    actual_ranges: &[Range<usize>],
+    use_color: bool,
+) -> bool {
+    match sexp_diff(actual, expected, actual_ranges, use_color) {
+        Some(diff) => {
+            print!("{diff}");
+            true
+        }
+        None => false,
+    }
+}
+
+fn sexp_diff(
+    actual: &str,
+    expected: &str,
+    actual_ranges: &[Range<usize>],
+    use_color: bool,
+) -> Option<String> {
+    let actual = parse_sexp(actual)?;
+    let expected = parse_sexp(expected)?;
+
+    // The ranges can't be attached reliably if they don't line up with the actual tree's nodes.
+    let actual_ranges = if actual_ranges.len() == node_count(&actual) {
+        actual_ranges
+    } else {
+        &[]
+    };
+
+    let mut lines = Vec::new();
+    diff_nodes(&actual, &expected, 0, None, actual_ranges, &mut lines);
+    if lines.iter().all(|line| line.change == Change::Equal) {
+        return None;
+    }
+
+    let mut shown = vec![false; lines.len()];
+    for (i, line) in lines.iter().enumerate() {
+        if line.change == Change::Equal {
+            continue;
+        }
+        let context = i.saturating_sub(CONTEXT_LINES)..(i + CONTEXT_LINES + 1).min(lines.len());
+        for is_shown in &mut shown[context] {
+            *is_shown = true;
+        }
+        let mut parent = line.parent;
+        while let Some(i) = parent {
+            shown[i] = true;
+            parent = lines[i].parent;
+        }
+    }
+
+    let mut result = String::new();
+    let mut previous = None;
+    for (i, line) in lines.iter().enumerate().filter(|(i, _)| shown[*i]) {
+        if previous.is_some_and(|previous| previous + 1 < i) {
+            result += "    ...\n";
+        }
+        previous = Some(i);
+
+        let indent = "  ".repeat(line.depth);
+        let text = match line.change {
+            Change::Equal => format!("    {indent}{}", line.text),
+            Change::Removed => paint(
+                use_color.then_some(AnsiColor::Red),
+                &format!("  - {indent}{}", line.text),
+            ),
+            Change::Added => paint(
+                use_color.then_some(AnsiColor::Green),
+                &format!("  + {indent}{}", line.text),
+            ),
+        };
+        result += &text;
+        result.push('\n');
+    }
+    if previous.is_some_and(|previous| previous + 1 < lines.len()) {
+        result += "    ...\n";
+    }
+    Some(result)
+}
+
+impl SexpNode<'_> {
+    fn is_text_assertion(&self) -> bool {
+        self.kind.starts_with("#text")
+    }
+}
+
+/// Counts the nodes of a tree, not including text assertions.
+fn node_count(node: &SexpNode) -> usize {
+    usize::from(!node.is_text_assertion()) + node.children.iter().map(node_count).sum::<usize>()
+}
+
+fn is_same_node(a: &SexpNode, b: &SexpNode) -> bool {
+    a.field == b.field && a.kind == b.kind
+}
+
+fn line_text(node: &SexpNode, ranges: &[Range<usize>]) -> String {
+    let mut text = String::new();
+    if let Some(field) = node.field {
+        text += field;
+        text += ": ";
+    }
+    text += &node.kind;
+    if let Some(range) = ranges.get(node.index) {
+        write!(text, " [{}..{}]", range.start, range.end).unwrap();
+    }
+    text
+}
+
+fn push_subtree(
+    node: &SexpNode,
+    change: Change,
+    depth: usize,
+    parent: Option<usize>,
+    ranges: &[Range<usize>],
+    lines: &mut Vec<DiffLine>,
+) {
+    let index = lines.len();
+    lines.push(DiffLine {
+        change,
+        depth,
+        text: line_text(node, ranges),
+        parent,
+    });
+    for child in &node.children {
+        push_subtree(child, change, depth + 1, Some(index), ranges, lines);
+    }
+}
+
+/// Diffs two nodes, recursing into their children if they have the same field and kind.
+///
+/// The children are aligned by a longest common subsequence of their fields and kinds, so that a
+/// single inserted or deleted node doesn't shift the alignment of all of its siblings.
+fn diff_nodes(
+    actual: &SexpNode,
+    expected: &SexpNode,
+    depth: usize,
+    parent: Option<usize>,
+    ranges: &[Range<usize>],
+    lines: &mut Vec<DiffLine>,
+) {
+    if !is_same_node(actual, expected) {
+        push_subtree(actual, Change::Removed, depth, parent, ranges, lines);
+        push_subtree(expected, Change::Added, depth, parent, &[], lines);
+        return;
+    }
+
+    let index = lines.len();
+    lines.push(DiffLine {
+        change: Change::Equal,
+        depth,
+        text: line_text(actual, ranges),
+        parent,
+    });
+
+    let (actual, expected) = (&actual.children, &expected.children);
+    let mut lcs = vec![vec![0usize; expected.len() + 1]; actual.len() + 1];
+    for i in (0..actual.len()).rev() {
+        for j in (0..expected.len()).rev() {
+            lcs[i][j] = if is_same_node(&actual[i], &expected[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let flush =
+        |removed: &mut Vec<&SexpNode>, added: &mut Vec<&SexpNode>, lines: &mut Vec<DiffLine>| {
+            for node in removed.drain(..) {
+                push_subtree(node, Change::Removed, depth + 1, Some(index), ranges, lines);
+            }
+            for node in added.drain(..) {
+                push_subtree(node, Change::Added, depth + 1, Some(index), &[], lines);
+            }
+        };
+    while i < actual.len() || j < expected.len() {
+        if i < actual.len()
+            && j < expected.len()
+            && is_same_node(&actual[i], &expected[j])
+            && lcs[i][j] == lcs[i + 1][j + 1] + 1
+        {
+            flush(&mut removed, &mut added, lines);
+            diff_nodes(
+                &actual[i],
+                &expected[j],
+                depth + 1,
+                Some(index),
+                ranges,
+                lines,
+            );
+            i += 1;
+            j += 1;
+        } else if j == expected.len() || (i < actual.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            removed.push(&actual[i]);
+            i += 1;
+        } else {
+            added.push(&expected[j]);
+            j += 1;
+        }
+    }
+    flush(&mut removed, &mut added, lines);
+}
+
+/// Parses an S-expression of the form `(kind field: (kind) (MISSING "x"))`.
+fn parse_sexp(sexp: &str) -> Option<SexpNode<'_>> {
+    let tokens = tokenize(sexp)
+        .into_iter()
+        .map(|(_, token)| token)
+        .collect::<Vec<_>>();
+    let mut index = 0;
+    let mut position = 0;
+    let node = parse_node(&tokens, &mut position, None, &mut index)?;
+    (position == tokens.len()).then_some(node)
+}
+
+fn parse_node<'a>(
+    tokens: &[&'a str],
+    position: &mut usize,
+    field: Option<&'a str>,
+    index: &mut usize,
+) -> Option<SexpNode<'a>> {
+    if tokens.get(*position) != Some(&"(") {
+        return None;
+    }
+    *position += 1;
+
+    let is_text_assertion = tokens.get(*position) == Some(&"#text");
+    let mut node = SexpNode {
+        field,
+        kind: String::new(),
+        children: Vec::new(),
+        index: if is_text_assertion {
+            usize::MAX
+        } else {
+            *index
+        },
+    };
+    if !is_text_assertion {
+        *index += 1;
+    }
+
+    let mut field = None;
+    loop {
+        let token = *tokens.get(*position)?;
+        match token {
+            ")" => {
+                *position += 1;
+                break;
+            }
+            "(" => {
+                node.children
+                    .push(parse_node(tokens, position, field.take(), index)?);
+            }
+            _ if node.children.is_empty() && field.is_none() && !is_field(token) => {
+                if !node.kind.is_empty() {
+                    node.kind.push(' ');
+                }
+                node.kind += token;
+                *position += 1;
+            }
+            _ => {
+                field = Some(token.strip_suffix(':')?);
+                *position += 1;
+            }
+        }
+    }
+    Some(node)
+}
+
+fn is_field(token: &str) -> bool {
+    token.ends_with(':') && !token.starts_with(['"', '\''])
+}
+
+/// Splits an S-expression into parentheses, `field:`s, node names, and quoted strings, along
+/// with their byte offsets. Like `format_sexp`, a quoted string only ends at a matching quote that
+/// isn't escaped and is followed by whitespace or a parenthesis, so that `(MISSING ")")` and
+/// `(UNEXPECTED ''')` are split correctly.
+fn tokenize(sexp: &str) -> Vec<(usize, &str)> {
+    let bytes = sexp.as_bytes();
+    let is_delimiter = |i: usize| {
+        bytes
+            .get(i)
+            .is_none_or(|b| b.is_ascii_whitespace() || *b == b'(' || *b == b')')
+    };
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'(' | b')' => i += 1,
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() {
+                    if bytes[i] == b'\\' {
+                        i += 2;
+                    } else if bytes[i] == quote && is_delimiter(i + 1) {
+                        i += 1;
+                        break;
+                    } else {
+                        i += 1;
+                    }
+                }
+                i = i.min(bytes.len());
+            }
+            _ => {
+                while !is_delimiter(i) {
+                    i += 1;
+                }
+            }
+        }
+        tokens.push((start, &sexp[start..i]));
+    }
+    tokens
+}
+
+/// Returns the pre-order indices of the nodes that have a `(#text "...")` assertion in an
+/// expected output. Text assertions aren't counted as nodes themselves.
+#[must_use]
+pub fn text_assertion_indices(sexp: &str) -> Vec<usize> {
+    fn collect(node: &SexpNode, indices: &mut Vec<usize>) {
+        if node.children.iter().any(SexpNode::is_text_assertion) {
+            indices.push(node.index);
+        }
+        for child in &node.children {
+            collect(child, indices);
+        }
+    }
+
+    let mut indices = Vec::new();
+    if let Some(node) = parse_sexp(sexp) {
+        collect(&node, &mut indices);
+    }
+    indices
+}
+
+/// Inserts a `(#text "...")` assertion after the name of each node with the given pre-order
+/// index. The texts must already be escaped.
+#[must_use]
+pub fn insert_text_assertions(sexp: &str, texts: &[(usize, String)]) -> String {
+    let tokens = tokenize(sexp);
+    let mut result = String::with_capacity(sexp.len());
+    let mut copied = 0;
+    let mut index = 0;
+    for (i, (_, token)) in tokens.iter().enumerate() {
+        if *token != "(" {
+            continue;
+        }
+        if let Some((_, text)) = texts.iter().find(|(node_index, _)| *node_index == index) {
+            // The name of a node is followed by a symbol in `(MISSING x)` and `(UNEXPECTED 'x')`
+            let name_len = match tokens.get(i + 1) {
+                Some((_, "MISSING" | "UNEXPECTED")) => 2,
+                _ => 1,
+            };
+            if let Some((offset, token)) = tokens.get(i + name_len) {
+                let end = offset + token.len();
+                result += &sexp[copied..end];
+                write!(result, " (#text \"{text}\")").unwrap();
+                copied = end;
+            }
+        }
+        index += 1;
+    }
+    result += &sexp[copied..];
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sexp() {
+        let node = parse_sexp(r#"(a b: (c) (MISSING ")") (d (e)))"#).unwrap();
+        assert_eq!(node.kind, "a");
+        assert_eq!(node_count(&node), 5);
+        assert_eq!(node.children[0].field, Some("b"));
+        assert_eq!(node.children[0].kind, "c");
+        assert_eq!(node.children[1].kind, r#"MISSING ")""#);
+        assert_eq!(node.children[2].children[0].index, 4);
+
+        assert!(parse_sexp("(a (b)").is_none());
+        assert!(parse_sexp("(a) (b)").is_none());
+    }
+
+    #[test]
+    fn test_sexp_diff() {
+        // The missing `(c)` shouldn't misalign all of the following siblings
+        let actual = "(a (b) (d) (e (f)) (g) (h) (i) (j) (k))";
+        let expected = "(a (b) (c) (d) (e (x)) (g) (h) (i) (j) (k))";
+        let ranges = (0..10).map(|i| i..i + 1).collect::<Vec<_>>();
+        assert_eq!(
+            sexp_diff(actual, expected, &ranges, false).unwrap(),
+            [
+                "    a [0..1]",
+                "      b [1..2]",
+                "  +   c",
+                "      d [2..3]",
+                "      e [3..4]",
+                "  -     f [4..5]",
+                "  +     x",
+                "      g [5..6]",
+                "      h [6..7]",
+                "    ...",
+                ""
+            ]
+            .join("\n")
+        );
+
+        // The ranges are left out if they don't match the actual tree
+        assert_eq!(
+            sexp_diff("(a f: (b))", "(a g: (b))", &[0..1, 1..2, 2..3], false).unwrap(),
+            "    a\n  -   f: b\n  +   g: b\n"
+        );
+
+        assert!(sexp_diff("(a (b))", "(a  (b) )", &[], false).is_none());
+    }
+
+    #[test]
+    fn test_text_assertions() {
+        let expected = r#"(a (b (#text "x")) f: (c) (MISSING d (#text "")) (e (#text "\")")))"#;
+        assert_eq!(text_assertion_indices(expected), vec![1, 3, 4]);
+
+        let actual = "(a (b) f: (c) (MISSING d) (e))";
+        assert_eq!(
+            insert_text_assertions(
+                actual,
+                &[(1, "x".into()), (3, String::new()), (4, "\\\")".into())]
+            ),
+            expected
+        );
+    }
+}
@@ -1,20 +1,158 @@
 use std::{
+    collections::HashMap,
     fs,
     io::{self, Write},
+    ops::Range,
     path::Path,
     str,
     sync::{atomic::AtomicUsize, Arc},
     time::Instant,
 };
 
-use anyhow::Result;
-use tree_sitter_tags::{TagsConfiguration, TagsContext};
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use tree_sitter_tags::{Tag, TagsConfiguration, TagsContext};
+
+use crate::parse::ParsePoint;
+
+/// The ctags kind letters of the syntax types that the tags queries of most languages use, which
+/// are the ones that Universal ctags uses for these kinds of definitions
+const DEFAULT_CTAGS_KINDS: &[(&str, &str)] = &[
+    ("class", "c"),
+    ("constant", "C"),
+    ("enum", "g"),
+    ("field", "m"),
+    ("function", "f"),
+    ("interface", "i"),
+    ("macro", "d"),
+    ("method", "m"),
+    ("module", "n"),
+    ("namespace", "n"),
+    ("struct", "s"),
+    ("type", "t"),
+    ("variable", "v"),
+];
+
+#[derive(ValueEnum, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TagsOutputFormat {
+    /// A line for each tag, with its name, syntax type, position and line of code
+    #[default]
+    Text,
+    /// The extended format of Exuberant and Universal ctags, with a line for each definition
+    Ctags,
+    /// A JSON object on its own line for each tag
+    Json,
+}
 
 pub struct TagsOptions {
     pub scope: Option<String>,
     pub quiet: bool,
     pub print_time: bool,
     pub cancellation_flag: Arc<AtomicUsize>,
+    pub format: TagsOutputFormat,
+    /// The ctags kind of each syntax type, for syntax types other than the default ones
+    pub ctags_kinds: HashMap<String, String>,
+    /// Collect the tags of all of the files, and write them sorted by name once all of the files
+    /// are tagged, instead of in the order of the files
+    pub sorted: bool,
+}
+
+#[derive(Serialize)]
+struct TagRecord<'a> {
+    file: &'a str,
+    name: &'a str,
+    kind: &'static str,
+    syntax_type: &'a str,
+    /// The range of the whole definition or reference
+    range: Range<usize>,
+    name_range: Range<usize>,
+    start: ParsePoint,
+    end: ParsePoint,
+    /// The line that the name is on, trimmed, and shortened if it's very long
+    line: &'a str,
+    docs: Option<&'a str>,
+}
+
+/// Parse a list of `--kind-map` entries, in the format `SYNTAX_TYPE=KIND,...`
+pub fn parse_ctags_kinds(kind_map: &str) -> Result<HashMap<String, String>> {
+    kind_map
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (syntax_type, kind) = entry
+                .split_once('=')
+                .map(|(syntax_type, kind)| (syntax_type.trim(), kind.trim()))
+                .filter(|(syntax_type, kind)| !syntax_type.is_empty() && !kind.is_empty())
+                .ok_or_else(|| {
+                    anyhow!("Invalid kind map entry `{entry}`, expected `SYNTAX_TYPE=KIND`")
+                })?;
+            Ok((syntax_type.to_string(), kind.to_string()))
+        })
+        .collect()
+}
+
+/// Write the pseudo-tags that ctags files start with, which tell editors whether they can use a
+/// binary search to find a tag.
+pub fn write_ctags_header(sorted: bool) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(
+        &mut stdout,
+        "!_TAG_FILE_FORMAT\t2\t/extended format; --format=1 will not append ;\" to lines/"
+    )?;
+    writeln!(
+        &mut stdout,
+        "!_TAG_FILE_SORTED\t{}\t/0=unsorted, 1=sorted, 2=foldcase/",
+        u8::from(sorted)
+    )?;
+    Ok(())
+}
+
+/// Write the lines of the tags that were collected with `--sorted`, ordered by the tags' names.
+/// Tags with the same name stay in the order of the files.
+pub fn write_sorted_tags(mut tags: Vec<(String, String)>) -> Result<()> {
+    tags.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+    let mut stdout = io::stdout().lock();
+    for (_, line) in tags {
+        writeln!(&mut stdout, "{line}")?;
+    }
+    Ok(())
+}
+
+/// A line in the extended ctags format for a definition, with an ex command that searches for the
+/// whole line that the definition's name is on.
+fn ctags_line(
+    source: &[u8],
+    file: &str,
+    name: &str,
+    syntax_type: &str,
+    tag: &Tag,
+    ctags_kinds: &HashMap<String, String>,
+) -> String {
+    let line_start = tag.name_range.start - tag.span.start.column;
+    let line_end = source[line_start..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(source.len(), |i| line_start + i);
+    let line = String::from_utf8_lossy(&source[line_start..line_end]);
+    let pattern = line
+        .trim_end_matches('\r')
+        .replace('\\', "\\\\")
+        .replace('/', "\\/");
+    let kind = ctags_kinds
+        .get(syntax_type)
+        .map(String::as_str)
+        .or_else(|| {
+            DEFAULT_CTAGS_KINDS
+                .iter()
+                .find(|(default_syntax_type, _)| *default_syntax_type == syntax_type)
+                .map(|(_, kind)| *kind)
+        })
+        .unwrap_or(syntax_type);
+    format!(
+        "{name}\t{file}\t/^{pattern}$/;\"\t{kind}\tline:{}",
+        tag.span.start.row + 1
+    )
 }
 
 pub fn generate_tags(
@@ -23,12 +161,13 @@ pub fn generate_tags(
     config: &TagsConfiguration,
     indent: bool,
     opts: &TagsOptions,
+    sorted_tags: &mut Vec<(String, String)>,
 ) -> Result<()> {
     let mut context = TagsContext::new();
     let stdout = io::stdout();
     let mut stdout = stdout.lock();
 
-    let indent_str = if indent {
+    let indent_str = if indent && opts.format == TagsOutputFormat::Text {
         if !opts.quiet {
             writeln!(&mut stdout, "{name}")?;
         }
@@ -44,7 +183,45 @@ pub fn generate_tags(
         .0
     {
         let tag = tag?;
-        if !opts.quiet {
+        if opts.format != TagsOutputFormat::Text {
+            let tag_name = String::from_utf8_lossy(&source[tag.name_range.clone()]);
+            let syntax_type = config.syntax_type_name(tag.syntax_type_id);
+            let line = if opts.format == TagsOutputFormat::Ctags {
+                if !tag.is_definition {
+                    continue;
+                }
+                ctags_line(
+                    &source,
+                    name,
+                    &tag_name,
+                    syntax_type,
+                    &tag,
+                    &opts.ctags_kinds,
+                )
+            } else {
+                serde_json::to_string(&TagRecord {
+                    file: name,
+                    name: &tag_name,
+                    kind: if tag.is_definition {
+                        "definition"
+                    } else {
+                        "reference"
+                    },
+                    syntax_type,
+                    range: tag.range.clone(),
+                    name_range: tag.name_range.clone(),
+                    start: tag.span.start.into(),
+                    end: tag.span.end.into(),
+                    line: str::from_utf8(&source[tag.line_range.clone()]).unwrap_or(""),
+                    docs: tag.docs.as_deref(),
+                })?
+            };
+            if opts.sorted {
+                sorted_tags.push((tag_name.into_owned(), line));
+            } else if !opts.quiet {
+                writeln!(&mut stdout, "{line}")?;
+            }
+        } else if !opts.quiet {
             write!(
                 &mut stdout,
                 "{indent_str}{:<10}\t | {:<8}\t{} {} - {} `{}`",
@@ -66,7 +243,9 @@ pub fn generate_tags(
         }
     }
 
-    if opts.print_time {
+    if opts.print_time && opts.format != TagsOutputFormat::Text {
+        eprintln!("{name}: time: {}ms", start.elapsed().as_millis());
+    } else if opts.print_time {
         writeln!(
             &mut stdout,
             "{indent_str}time: {}ms",
@@ -76,3 +255,41 @@ pub fn generate_tags(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use super::*;
+
+    #[test]
+    fn test_ctags_line() {
+        let source = b"x = 1\r\n  def a\\b/c():\r\n";
+        let tag = Tag {
+            range: 9..22,
+            name_range: 13..18,
+            line_range: 9..21,
+            span: Point::new(1, 6)..Point::new(1, 11),
+            utf16_column_range: 6..11,
+            docs: None,
+            is_definition: true,
+            syntax_type_id: 0,
+        };
+
+        let ctags_kinds = parse_ctags_kinds("method=M, macro = x,").unwrap();
+        assert_eq!(
+            ctags_line(source, "a.py", "a\\b/c", "function", &tag, &ctags_kinds),
+            "a\\b/c\ta.py\t/^  def a\\\\b\\/c():$/;\"\tf\tline:2"
+        );
+        assert_eq!(
+            ctags_line(source, "a.py", "a\\b/c", "method", &tag, &ctags_kinds),
+            "a\\b/c\ta.py\t/^  def a\\\\b\\/c():$/;\"\tM\tline:2"
+        );
+        assert_eq!(
+            ctags_line(source, "a.py", "a\\b/c", "var", &tag, &ctags_kinds),
+            "a\\b/c\ta.py\t/^  def a\\\\b\\/c():$/;\"\tvar\tline:2"
+        );
+        assert!(parse_ctags_kinds("method").is_err());
+        assert!(parse_ctags_kinds("=m").is_err());
+    }
+}
@@ -1,6 +1,7 @@
 package tree_sitter_LOWER_PARSER_NAME
 
 // #cgo CFLAGS: -std=c11 -fPIC
+// #define TREE_SITTER_INCLUDE_PARSER_TABLES
 // #include "../../src/parser.c"
 // #if __has_include("../../src/scanner.c")
 // #include "../../src/scanner.c"
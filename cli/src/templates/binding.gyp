@@ -11,6 +11,7 @@
       "sources": [
         "bindings/node/binding.cc",
         "src/parser.c",
+        "<!@(node -p \"fs.readdirSync('src').filter(f => f.startsWith('parser_tables_') && f.endsWith('.c')).map(f => 'src/' + f).join(' ')\")",
       ],
       "variables": {
         "has_scanner": "<!(node -p \"fs.existsSync('src/scanner.c')\")"
@@ -11,6 +11,15 @@ fn main() {
     c_config.file(&parser_path);
     println!("cargo:rerun-if-changed={}", parser_path.to_str().unwrap());
 
+    for entry in std::fs::read_dir(src_dir).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        if name.starts_with("parser_tables_") && name.ends_with(".c") {
+            c_config.file(&path);
+            println!("cargo:rerun-if-changed={}", path.to_str().unwrap());
+        }
+    }
+
     let scanner_path = src_dir.join("scanner.c");
     if scanner_path.exists() {
         c_config.file(&scanner_path);
@@ -24,6 +24,20 @@ pub fn build(b: *std.Build) !void {
         .file = b.path("src/parser.c"),
         .flags = &.{"-std=c11"},
     });
+    var src_dir = try b.build_root.handle.openDir("src", .{ .iterate = true });
+    defer src_dir.close();
+    var src_files = src_dir.iterate();
+    while (try src_files.next()) |entry| {
+        if (entry.kind == .file and
+            std.mem.startsWith(u8, entry.name, "parser_tables_") and
+            std.mem.endsWith(u8, entry.name, ".c"))
+        {
+            lib.addCSourceFile(.{
+                .file = b.path(b.pathJoin(&.{ "src", entry.name })),
+                .flags = &.{"-std=c11"},
+            });
+        }
+    }
     if (hasScanner(b.build_root.handle)) {
         lib.addCSourceFile(.{
             .file = b.path("src/scanner.c"),
@@ -25,6 +25,10 @@ add_custom_command(OUTPUT "${CMAKE_CURRENT_SOURCE_DIR}/src/parser.c"
                    COMMENT "Generating parser.c")
 
 add_library(tree-sitter-KEBAB_PARSER_NAME src/parser.c)
+file(GLOB PARSER_TABLES src/parser_tables_*.c)
+if(PARSER_TABLES)
+  target_sources(tree-sitter-KEBAB_PARSER_NAME PRIVATE ${PARSER_TABLES})
+endif()
 if(EXISTS ${CMAKE_CURRENT_SOURCE_DIR}/src/scanner.c)
   target_sources(tree-sitter-KEBAB_PARSER_NAME PRIVATE src/scanner.c)
 endif()
@@ -4,6 +4,11 @@ import Foundation
 import PackageDescription
 
 var sources = ["src/parser.c"]
+let srcFiles = (try? FileManager.default.contentsOfDirectory(atPath: "src")) ?? []
+sources += srcFiles
+    .filter { $0.hasPrefix("parser_tables_") && $0.hasSuffix(".c") }
+    .sorted()
+    .map { "src/" + $0 }
 if FileManager.default.fileExists(atPath: "src/scanner.c") {
     sources.append("src/scanner.c")
 }
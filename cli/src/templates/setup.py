@@ -1,3 +1,4 @@
+from glob import glob
 from os import path
 from platform import system
 from sysconfig import get_config_var
@@ -10,6 +11,7 @@ from wheel.bdist_wheel import bdist_wheel
 sources = [
     "bindings/python/tree_sitter_LOWER_PARSER_NAME/binding.c",
     "src/parser.c",
+    *sorted(glob("src/parser_tables_*.c")),
 ]
 if path.exists("src/scanner.c"):
     sources.append("src/scanner.c")
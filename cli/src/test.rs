@@ -1,13 +1,18 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap},
     ffi::OsStr,
     fmt::Write as _,
     fs,
     io::{self, Write},
-    path::{Path, PathBuf},
+    ops,
+    path::{Component, Path, PathBuf},
     str,
-    sync::LazyLock,
-    time::Duration,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, LazyLock,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 use anstyle::{AnsiColor, Color, Style};
@@ -23,7 +28,14 @@ use tree_sitter::{format_sexp, Language, LogType, Parser, Query, Tree};
 use walkdir::WalkDir;
 
 use super::util;
-use crate::parse::Stats;
+use crate::{
+    fuzz::{
+        edits::{align_edit_to_char_boundaries, get_random_edit, Edit},
+        random::Rand,
+    },
+    parse::{perform_edit, Stats},
+    sexp_diff::{insert_text_assertions, print_sexp_diff, text_assertion_indices},
+};
 
 static HEADER_REGEX: LazyLock<ByteRegex> = LazyLock::new(|| {
     ByteRegexBuilder::new(
@@ -72,7 +84,6 @@ pub enum TestEntry {
         has_fields: bool,
         attributes_str: String,
         attributes: TestAttributes,
-        file_name: Option<String>,
     },
 }
 
@@ -81,6 +92,7 @@ pub struct TestAttributes {
     pub skip: bool,
     pub platform: bool,
     pub fail_fast: bool,
+    pub fail: bool,
     pub error: bool,
     pub languages: Vec<Box<str>>,
 }
@@ -101,12 +113,25 @@ impl Default for TestAttributes {
             skip: false,
             platform: true,
             fail_fast: false,
+            fail: false,
             error: false,
             languages: vec!["".into()],
         }
     }
 }
 
+/// Which field names `--update` writes in the expected output of the tests that it rewrites.
+#[derive(ValueEnum, Default, Copy, Clone, PartialEq, Eq)]
+pub enum UpdateFields {
+    /// Always write field names
+    Always,
+    /// Never write field names
+    Never,
+    /// Only write field names in tests whose expected output already has them
+    #[default]
+    Existing,
+}
+
 #[derive(ValueEnum, Default, Copy, Clone, PartialEq, Eq)]
 pub enum TestStats {
     All,
@@ -115,6 +140,26 @@ pub enum TestStats {
     TotalOnly,
 }
 
+/// An example whose tree, after a sequence of random edits and incremental reparses, differed
+/// from the tree produced by parsing the edited text from scratch.
+pub struct EditFuzzFailure {
+    pub name: String,
+    pub edits: Vec<Edit>,
+    pub incremental: String,
+    pub from_scratch: String,
+}
+
+/// A test's name, input, expected output, attributes, and header and divider lengths, as they
+/// will be written back to its corpus file by `--update`.
+type CorrectedEntry = (String, String, String, String, usize, usize);
+
+/// The result of parsing a test's input with one of its languages.
+struct ExampleParse {
+    tree: Tree,
+    parse_time: Duration,
+    edit_fuzz_failure: Option<(Vec<Edit>, String, String)>,
+}
+
 pub struct TestOptions<'a> {
     pub output: &'a mut String,
     pub path: PathBuf,
@@ -122,12 +167,26 @@ pub struct TestOptions<'a> {
     pub debug_graph: bool,
     pub include: Option<Regex>,
     pub exclude: Option<Regex>,
-    pub file_name: Option<String>,
+    pub files: Vec<PathBuf>,
     pub update: bool,
+    pub update_fields: UpdateFields,
     pub open_log: bool,
     pub languages: BTreeMap<&'a str, &'a Language>,
     pub color: bool,
     pub test_num: usize,
+    /// The number of tests that were skipped because of `include`, `exclude`, or `files`
+    pub filtered_count: usize,
+    /// The number of tests that were skipped because of their `:skip` or `:platform` attributes
+    pub skipped_count: usize,
+    /// The number of tests marked with `:fail` that failed, as expected
+    pub expected_failure_count: usize,
+    /// The names of tests marked with `:fail` that passed
+    pub unexpected_passes: Vec<String>,
+    /// The number of random edits to perform on each example, checking that reparsing
+    /// incrementally produces the same tree as parsing from scratch
+    pub fuzz_edits: usize,
+    pub fuzz_seed: usize,
+    pub edit_fuzz_failures: Vec<EditFuzzFailure>,
     /// Whether a test ran for the nth line in `output`, the true parse rate, and the adjusted
     /// parse rate
     pub parse_rates: &'a mut Vec<(bool, Option<(f64, f64)>)>,
@@ -135,6 +194,10 @@ pub struct TestOptions<'a> {
     pub stats: &'a mut Stats,
     pub show_fields: bool,
     pub overview_only: bool,
+    /// The number of tests to parse at the same time
+    pub thread_count: usize,
+    /// Show a line diff of the whole trees of failing tests, rather than only the differing nodes
+    pub full_diff: bool,
 }
 
 pub fn run_tests_at_path(parser: &mut Parser, opts: &mut TestOptions) -> Result<()> {
@@ -152,19 +215,32 @@ pub fn run_tests_at_path(parser: &mut Parser, opts: &mut TestOptions) -> Result<
         })));
     }
 
+    let mut parses = if opts.thread_count > 1 {
+        parse_examples_in_parallel(parser, &test_entry, opts)
+    } else {
+        HashMap::new()
+    };
+
     let mut failures = Vec::new();
     let mut corrected_entries = Vec::new();
+    let mut file_updates = Vec::new();
     let mut has_parse_errors = false;
     run_tests(
         parser,
         test_entry,
         opts,
         0,
+        &mut parses,
         &mut failures,
         &mut corrected_entries,
+        &mut file_updates,
         &mut has_parse_errors,
     )?;
 
+    for (file_path, entries) in file_updates {
+        write_tests(&file_path, &entries)?;
+    }
+
     let (count, total_adj_parse_time) = opts
         .parse_rates
         .iter()
@@ -214,8 +290,80 @@ pub fn run_tests_at_path(parser: &mut Parser, opts: &mut TestOptions) -> Result<
 
     parser.stop_printing_dot_graphs();
 
+    let counts = [
+        (opts.skipped_count, "skipped by attributes"),
+        (opts.filtered_count, "skipped by filters"),
+        (opts.expected_failure_count, "failed as expected"),
+    ];
+    if counts.iter().any(|(count, _)| *count > 0) {
+        println!();
+    }
+    for (count, description) in counts {
+        match count {
+            0 => {}
+            1 => println!("1 test {description}"),
+            _ => println!("{count} tests {description}"),
+        }
+    }
+
+    if !opts.unexpected_passes.is_empty() {
+        println!();
+        let message = if opts.unexpected_passes.len() == 1 {
+            "1 test marked with `:fail` passed:".to_string()
+        } else {
+            format!(
+                "{} tests marked with `:fail` passed:",
+                opts.unexpected_passes.len()
+            )
+        };
+        println!("{}", paint(opts.color.then_some(AnsiColor::Red), &message));
+        for (i, name) in opts.unexpected_passes.iter().enumerate() {
+            println!("  {}. {name}", i + 1);
+        }
+    }
+
+    if !opts.edit_fuzz_failures.is_empty() {
+        println!();
+        let message = if opts.edit_fuzz_failures.len() == 1 {
+            "1 test reparsed differently after random edits:".to_string()
+        } else {
+            format!(
+                "{} tests reparsed differently after random edits:",
+                opts.edit_fuzz_failures.len()
+            )
+        };
+        println!("{}", paint(opts.color.then_some(AnsiColor::Red), &message));
+        for (i, failure) in opts.edit_fuzz_failures.iter().enumerate() {
+            println!("\n  {}. {}:", i + 1, failure.name);
+            println!("  Edits:");
+            for edit in &failure.edits {
+                println!("    {edit}");
+            }
+            let from_scratch = format_sexp(&failure.from_scratch, 2);
+            let incremental = format_sexp(&failure.incremental, 2);
+            let diff = TextDiff::from_lines(&from_scratch, &incremental);
+            print!(
+                "{}",
+                diff.unified_diff()
+                    .context_radius(3)
+                    .header("from scratch", "incremental")
+            );
+        }
+        println!("\nRerun with `--seed {}` to reproduce.", opts.fuzz_seed);
+    }
+
     if failures.is_empty() {
-        Ok(())
+        if !opts.unexpected_passes.is_empty() {
+            Err(anyhow!(
+                "Tests marked with `:fail` are now passing, remove the attribute from them"
+            ))
+        } else if !opts.edit_fuzz_failures.is_empty() {
+            Err(anyhow!(
+                "Incremental reparses did not match parses from scratch"
+            ))
+        } else {
+            Ok(())
+        }
     } else {
         println!();
 
@@ -246,7 +394,7 @@ pub fn run_tests_at_path(parser: &mut Parser, opts: &mut TestOptions) -> Result<
                 if opts.color {
                     print_diff_key();
                 }
-                for (i, (name, actual, expected)) in failures.iter().enumerate() {
+                for (i, (name, actual, expected, actual_ranges)) in failures.iter().enumerate() {
                     if expected == "NO ERROR" {
                         println!("\n  {}. {name}:\n", i + 1);
                         println!("  Expected an ERROR node, but got:");
@@ -259,9 +407,15 @@ pub fn run_tests_at_path(parser: &mut Parser, opts: &mut TestOptions) -> Result<
                         );
                     } else {
                         println!("\n  {}. {name}:", i + 1);
-                        let actual = format_sexp(actual, 2);
-                        let expected = format_sexp(expected, 2);
-                        print_diff(&actual, &expected, opts.color);
+                        if opts.full_diff
+                            || !print_sexp_diff(actual, expected, actual_ranges, opts.color)
+                        {
+                            let actual = format_sexp(actual, 2);
+                            let expected = format_sexp(expected, 2);
+                            print_diff(&actual, &expected, opts.color);
+                        } else {
+                            println!();
+                        }
                     }
                 }
             }
@@ -355,8 +509,10 @@ fn run_tests(
     test_entry: TestEntry,
     opts: &mut TestOptions,
     mut indent_level: u32,
-    failures: &mut Vec<(String, String, String)>,
-    corrected_entries: &mut Vec<(String, String, String, String, usize, usize)>,
+    parses: &mut HashMap<usize, Vec<ExampleParse>>,
+    failures: &mut Vec<(String, String, String, Vec<ops::Range<usize>>)>,
+    corrected_entries: &mut Vec<CorrectedEntry>,
+    file_updates: &mut Vec<(PathBuf, Vec<CorrectedEntry>)>,
     has_parse_errors: &mut bool,
 ) -> Result<bool> {
     match test_entry {
@@ -382,6 +538,7 @@ fn run_tests(
                 )?;
                 opts.parse_rates.push((true, None));
                 opts.test_num += 1;
+                opts.skipped_count += 1;
                 return Ok(true);
             }
 
@@ -394,21 +551,34 @@ fn run_tests(
                 )?;
                 opts.parse_rates.push((true, None));
                 opts.test_num += 1;
+                opts.skipped_count += 1;
                 return Ok(true);
             }
 
+            let default_language = parser.language().map(|language| language.clone());
+            let fuzz_seed = opts.fuzz_seed.wrapping_add(opts.test_num);
+            let mut example_parses = parses
+                .remove(&opts.test_num)
+                .unwrap_or_default()
+                .into_iter();
             for (i, language_name) in attributes.languages.iter().enumerate() {
-                if !language_name.is_empty() {
-                    let language = opts
-                        .languages
-                        .get(language_name.as_ref())
-                        .ok_or_else(|| anyhow!("Language not found: {language_name}"))?;
-                    parser.set_language(language)?;
-                }
-                let start = std::time::Instant::now();
-                let tree = parser.parse(&input, None).unwrap();
+                let ExampleParse {
+                    tree,
+                    parse_time,
+                    edit_fuzz_failure,
+                } = if let Some(example_parse) = example_parses.next() {
+                    example_parse
+                } else {
+                    if !language_name.is_empty() {
+                        let language = opts
+                            .languages
+                            .get(language_name.as_ref())
+                            .ok_or_else(|| anyhow!("Language not found: {language_name}"))?;
+                        parser.set_language(language)?;
+                    }
+                    parse_example(parser, &input, opts.fuzz_edits, fuzz_seed)?
+                };
                 {
-                    let parse_time = start.elapsed();
                     let true_parse_rate = tree.root_node().byte_range().len() as f64
                         / (parse_time.as_nanos() as f64 / 1_000_000.0);
                     let adj_parse_rate = adjusted_parse_rate(&tree, parse_time);
@@ -420,7 +590,59 @@ fn run_tests(
                     opts.stats.total_bytes += tree.root_node().byte_range().len();
                 }
 
-                if attributes.error {
+                if attributes.fail {
+                    let passed = if attributes.error {
+                        tree.root_node().has_error()
+                    } else {
+                        let mut actual = tree.root_node().to_sexp();
+                        if !(opts.show_fields || has_fields) {
+                            actual = strip_sexp_fields(&actual);
+                        }
+                        add_text_assertions(&actual, &output, &tree, &input) == output
+                    };
+
+                    if passed {
+                        writeln!(
+                            opts.output,
+                            "{:>3}. ! {}{}",
+                            opts.test_num,
+                            paint(opts.color.then_some(AnsiColor::Red), &name),
+                            paint(
+                                opts.color.then_some(AnsiColor::Red),
+                                " -- Warning: Expected to fail, but passed",
+                            ),
+                        )?;
+                        opts.stats.successful_parses += 1;
+                        opts.unexpected_passes.push(name.clone());
+
+                        if attributes.fail_fast {
+                            return Ok(false);
+                        }
+                    } else {
+                        writeln!(
+                            opts.output,
+                            "{:>3}. ✗ {} (expected failure)",
+                            opts.test_num,
+                            paint(opts.color.then_some(AnsiColor::Yellow), &name),
+                        )?;
+                        opts.expected_failure_count += 1;
+                    }
+
+                    // Known failures keep their expected output, since it documents the intended
+                    // parse rather than the current one
+                    if opts.update {
+                        let input = String::from_utf8(input.clone()).unwrap();
+                        let output = format_sexp(&output, 0);
+                        corrected_entries.push((
+                            name.clone(),
+                            input,
+                            output,
+                            attributes_str.clone(),
+                            header_delim_len,
+                            divider_delim_len,
+                        ));
+                    }
+                } else if attributes.error {
                     if tree.root_node().has_error() {
                         writeln!(
                             opts.output,
@@ -465,6 +687,7 @@ fn run_tests(
                             name.clone(),
                             tree.root_node().to_sexp(),
                             "NO ERROR".to_string(),
+                            Vec::new(),
                         ));
                     }
 
@@ -472,12 +695,30 @@ fn run_tests(
                         return Ok(false);
                     }
                 } else {
-                    let mut actual = tree.root_node().to_sexp();
-                    if !(opts.show_fields || has_fields) {
-                        actual = strip_sexp_fields(&actual);
-                    }
+                    let full_actual = tree.root_node().to_sexp();
+                    let actual = if opts.show_fields || has_fields {
+                        full_actual.clone()
+                    } else {
+                        strip_sexp_fields(&full_actual)
+                    };
+                    let actual = add_text_assertions(&actual, &output, &tree, &input);
+
+                    // The output that `--update` would write, which can differ from `actual` in
+                    // its field names, in which case the test is rewritten even if it passes.
+                    let update_output = match opts.update_fields {
+                        UpdateFields::Existing => actual.clone(),
+                        UpdateFields::Always => {
+                            add_text_assertions(&full_actual, &output, &tree, &input)
+                        }
+                        UpdateFields::Never => add_text_assertions(
+                            &strip_sexp_fields(&full_actual),
+                            &output,
+                            &tree,
+                            &input,
+                        ),
+                    };
 
-                    if actual == output {
+                    if actual == output && !(opts.update && update_output != output) {
                         writeln!(
                             opts.output,
                             "{:>3}. ✓ {}",
@@ -501,13 +742,15 @@ fn run_tests(
                         if opts.update {
                             let input = String::from_utf8(input.clone()).unwrap();
                             let expected_output = format_sexp(&output, 0);
-                            let actual_output = format_sexp(&actual, 0);
+                            let actual_output = format_sexp(&update_output, 0);
 
                             // Only bail early before updating if the actual is not the output,
                             // sometimes users want to test cases that
                             // are intended to have errors, hence why this
                             // check isn't shown above
-                            if actual.contains("ERROR") || actual.contains("MISSING") {
+                            if actual != output
+                                && (actual.contains("ERROR") || actual.contains("MISSING"))
+                            {
                                 *has_parse_errors = true;
 
                                 // keep the original `expected` output if the actual output has an
@@ -544,7 +787,12 @@ fn run_tests(
                                 paint(opts.color.then_some(AnsiColor::Red), &name),
                             )?;
                         }
-                        failures.push((name.clone(), actual, output.clone()));
+                        failures.push((
+                            name.clone(),
+                            actual,
+                            output.clone(),
+                            sexp_node_ranges(&tree),
+                        ));
 
                         if attributes.fail_fast {
                             return Ok(false);
@@ -552,9 +800,20 @@ fn run_tests(
                     }
                 }
 
+                if let Some((edits, incremental, from_scratch)) = edit_fuzz_failure {
+                    opts.edit_fuzz_failures.push(EditFuzzFailure {
+                        name: name.clone(),
+                        edits,
+                        incremental,
+                        from_scratch,
+                    });
+                }
+
                 if i == attributes.languages.len() - 1 {
                     // reset to the first language
-                    parser.set_language(opts.languages.values().next().unwrap())?;
+                    if let Some(language) = &default_language {
+                        parser.set_language(language)?;
+                    }
                 }
             }
             opts.test_num += 1;
@@ -572,26 +831,10 @@ fn run_tests(
             let failure_count = failures.len();
             let mut has_printed = false;
 
-            let matches_filter = |name: &str, file_name: &Option<String>, opts: &TestOptions| {
-                if let (Some(test_file_path), Some(filter_file_name)) = (file_name, &opts.file_name)
-                {
-                    if !filter_file_name.eq(test_file_path) {
-                        return false;
-                    }
-                }
-                if let Some(include) = &opts.include {
-                    include.is_match(name)
-                } else if let Some(exclude) = &opts.exclude {
-                    !exclude.is_match(name)
-                } else {
-                    true
-                }
-            };
-
             let should_skip = |entry: &TestEntry, opts: &TestOptions| match entry {
-                TestEntry::Example {
-                    name, file_name, ..
-                } => !matches_filter(name, file_name, opts),
+                TestEntry::Example { name, .. } => {
+                    is_filtered_out(name, file_path.as_deref(), opts)
+                }
                 TestEntry::Group { .. } => false,
             };
 
@@ -619,6 +862,7 @@ fn run_tests(
                         ));
 
                         opts.test_num += 1;
+                        opts.filtered_count += 1;
 
                         continue;
                     }
@@ -637,8 +881,10 @@ fn run_tests(
                     child,
                     opts,
                     indent_level,
+                    parses,
                     failures,
                     corrected_entries,
+                    file_updates,
                     has_parse_errors,
                 )? {
                     // fail fast
@@ -648,7 +894,7 @@ fn run_tests(
 
             if let Some(file_path) = file_path {
                 if opts.update && failures.len() - failure_count > 0 {
-                    write_tests(&file_path, corrected_entries)?;
+                    file_updates.push((file_path, std::mem::take(corrected_entries)));
                 }
                 corrected_entries.clear();
             }
@@ -657,6 +903,258 @@ fn run_tests(
     Ok(true)
 }
 
+/// Normalizes the whitespace of an S-expression, like the expected output of a test.
+fn normalize_sexp(sexp: &str) -> String {
+    let sexp = WHITESPACE_REGEX.replace_all(sexp.trim(), " ");
+    sexp.replace(" )", ")")
+}
+
+/// Adds a `(#text "...")` assertion with the text of the actual node to every node whose
+/// expected output has one, so that the texts are compared along with the rest of the tree.
+fn add_text_assertions(actual: &str, expected: &str, tree: &Tree, input: &[u8]) -> String {
+    let indices = text_assertion_indices(expected);
+    if indices.is_empty() {
+        return actual.to_string();
+    }
+
+    let ranges = sexp_node_ranges(tree);
+    let texts = indices
+        .into_iter()
+        .filter_map(|index| {
+            let text = String::from_utf8_lossy(&input[ranges.get(index)?.clone()]);
+            let mut escaped = String::with_capacity(text.len());
+            for c in text.chars() {
+                match c {
+                    '\\' => escaped += "\\\\",
+                    '"' => escaped += "\\\"",
+                    '\n' => escaped += "\\n",
+                    '\r' => escaped += "\\r",
+                    '\t' => escaped += "\\t",
+                    c => escaped.push(c),
+                }
+            }
+            Some((index, escaped))
+        })
+        .collect::<Vec<_>>();
+    normalize_sexp(&insert_text_assertions(actual, &texts))
+}
+
+/// Whether a test is skipped because of the `include`, `exclude`, or `files` filters.
+fn is_filtered_out(name: &str, file_path: Option<&Path>, opts: &TestOptions) -> bool {
+    file_path.is_some_and(|file_path| !matches_file_filter(file_path, &opts.files))
+        || opts
+            .include
+            .as_ref()
+            .is_some_and(|include| !include.is_match(name))
+        || opts
+            .exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.is_match(name))
+}
+
+/// Parses the input of every test that will be run, spread across `opts.thread_count` threads
+/// with one parser each. The results are keyed by test number, and are picked up by `run_tests`,
+/// which still checks and prints the tests one at a time, in corpus order. Tests that aren't
+/// parsed here, like those with an unknown language, are parsed by `run_tests` itself.
+fn parse_examples_in_parallel(
+    parser: &Parser,
+    test_entry: &TestEntry,
+    opts: &TestOptions,
+) -> HashMap<usize, Vec<ExampleParse>> {
+    fn collect_jobs<'a>(
+        entry: &'a TestEntry,
+        file_path: Option<&Path>,
+        default_language: &Language,
+        opts: &TestOptions,
+        test_num: &mut usize,
+        jobs: &mut Vec<(usize, &'a [u8], Vec<Language>)>,
+    ) {
+        match entry {
+            TestEntry::Example {
+                name,
+                input,
+                attributes,
+                ..
+            } => {
+                if !is_filtered_out(name, file_path, opts)
+                    && !attributes.skip
+                    && attributes.platform
+                {
+                    let languages = attributes
+                        .languages
+                        .iter()
+                        .map(|name| {
+                            if name.is_empty() {
+                                Some(default_language.clone())
+                            } else {
+                                opts.languages
+                                    .get(name.as_ref())
+                                    .map(|language| (*language).clone())
+                            }
+                        })
+                        .collect::<Option<Vec<_>>>();
+                    if let Some(languages) = languages {
+                        jobs.push((*test_num, input, languages));
+                    }
+                }
+                *test_num += 1;
+            }
+            TestEntry::Group {
+                children,
+                file_path,
+                ..
+            } => {
+                for child in children {
+                    collect_jobs(
+                        child,
+                        file_path.as_deref(),
+                        default_language,
+                        opts,
+                        test_num,
+                        jobs,
+                    );
+                }
+            }
+        }
+    }
+
+    let Some(default_language) = parser.language().map(|language| language.clone()) else {
+        return HashMap::new();
+    };
+    let mut jobs = Vec::new();
+    let mut test_num = opts.test_num;
+    collect_jobs(
+        test_entry,
+        None,
+        &default_language,
+        opts,
+        &mut test_num,
+        &mut jobs,
+    );
+
+    let next_index = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..opts.thread_count.min(jobs.len()) {
+            let sender = sender.clone();
+            let (next_index, jobs) = (&next_index, &jobs);
+            scope.spawn(move || {
+                let mut parser = Parser::new();
+                loop {
+                    let index = next_index.fetch_add(1, Ordering::SeqCst);
+                    let Some((test_num, input, languages)) = jobs.get(index) else {
+                        break;
+                    };
+                    let fuzz_seed = opts.fuzz_seed.wrapping_add(*test_num);
+                    let example_parses = languages
+                        .iter()
+                        .map(|language| {
+                            parser.set_language(language)?;
+                            parse_example(&mut parser, input, opts.fuzz_edits, fuzz_seed)
+                        })
+                        .collect::<Result<Vec<_>>>();
+                    if let Ok(example_parses) = example_parses {
+                        sender.send((*test_num, example_parses)).unwrap();
+                    }
+                }
+            });
+        }
+        drop(sender);
+        receiver.into_iter().collect()
+    })
+}
+
+/// Parses a test's input with the parser's current language, timing the parse, and then
+/// performs `fuzz_edits` random edits on it if requested.
+fn parse_example(
+    parser: &mut Parser,
+    input: &[u8],
+    fuzz_edits: usize,
+    fuzz_seed: usize,
+) -> Result<ExampleParse> {
+    let start = Instant::now();
+    let tree = parser.parse(input, None).unwrap();
+    let parse_time = start.elapsed();
+
+    let edit_fuzz_failure = if fuzz_edits > 0 {
+        // Seed each test by its number, so that a failure can be reproduced with the same seed
+        // while other tests are filtered out
+        let mut rand = Rand::new(fuzz_seed);
+        fuzz_edits_for_input(parser, input, tree.clone(), fuzz_edits, &mut rand)?
+    } else {
+        None
+    };
+
+    Ok(ExampleParse {
+        tree,
+        parse_time,
+        edit_fuzz_failure,
+    })
+}
+
+/// A corpus file matches the `files` filter if its path ends with one of the filter paths, so
+/// `expressions.txt`, `corpus/expressions.txt`, and a full path all select the same file.
+fn matches_file_filter(file_path: &Path, files: &[PathBuf]) -> bool {
+    files.is_empty()
+        || files.iter().any(|filter| {
+            let filter = filter
+                .components()
+                .filter(|c| !matches!(c, Component::CurDir))
+                .collect::<PathBuf>();
+            file_path.ends_with(filter)
+        })
+}
+
+/// Returns the byte ranges of the nodes that are included in the tree's S-expression, in
+/// pre-order.
+fn sexp_node_ranges(tree: &Tree) -> Vec<ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut cursor = tree.walk();
+    loop {
+        let node = cursor.node();
+        if node.is_named() || node.is_missing() {
+            ranges.push(node.byte_range());
+        }
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return ranges;
+            }
+        }
+    }
+}
+
+/// Performs random edits on `input`, reparsing incrementally after each one and comparing the
+/// result with a parse from scratch. Returns the edits that were performed along with both trees
+/// as soon as they differ.
+fn fuzz_edits_for_input(
+    parser: &mut Parser,
+    input: &[u8],
+    mut tree: Tree,
+    edit_count: usize,
+    rand: &mut Rand,
+) -> Result<Option<(Vec<Edit>, String, String)>> {
+    let mut input = input.to_vec();
+    let mut edits = Vec::with_capacity(edit_count);
+    for _ in 0..edit_count {
+        let edit = align_edit_to_char_boundaries(&input, get_random_edit(rand, &input));
+        perform_edit(&mut tree, &mut input, &edit)?;
+        edits.push(edit);
+
+        tree = parser.parse(&input, Some(&tree)).unwrap();
+        let from_scratch = parser.parse(&input, None).unwrap();
+
+        let incremental = tree.root_node().to_sexp();
+        let from_scratch = from_scratch.root_node().to_sexp();
+        if incremental != from_scratch {
+            return Ok(Some((edits, incremental, from_scratch)));
+        }
+    }
+    Ok(None)
+}
+
 // Parse time is interpreted in ns before converting to ms to avoid truncation issues
 // Parse rates often have several outliers, leading to a large standard deviation. Taking
 // the log of these rates serves to "flatten" out the distribution, yielding a more
@@ -669,17 +1167,14 @@ pub fn adjusted_parse_rate(tree: &Tree, parse_time: Duration) -> f64 {
     )
 }
 
-fn write_tests(
-    file_path: &Path,
-    corrected_entries: &[(String, String, String, String, usize, usize)],
-) -> Result<()> {
+fn write_tests(file_path: &Path, corrected_entries: &[CorrectedEntry]) -> Result<()> {
     let mut buffer = fs::File::create(file_path)?;
     write_tests_to_buffer(&mut buffer, corrected_entries)
 }
 
 fn write_tests_to_buffer(
     buffer: &mut impl Write,
-    corrected_entries: &[(String, String, String, String, usize, usize)],
+    corrected_entries: &[CorrectedEntry],
 ) -> Result<()> {
     for (i, (name, input, output, attributes_str, header_delim_len, divider_delim_len)) in
         corrected_entries.iter().enumerate()
@@ -776,8 +1271,8 @@ fn parse_test_content(name: String, content: &str, file_path: Option<PathBuf>) -
             .name("suffix2")
             .map(|m| String::from_utf8_lossy(m.as_bytes()));
 
-        let (mut skip, mut platform, mut fail_fast, mut error, mut languages) =
-            (false, None, false, false, vec![]);
+        let (mut skip, mut platform, mut fail_fast, mut fail, mut error, mut languages) =
+            (false, None, false, false, false, vec![]);
 
         let test_name_and_markers = c
             .name("test_name_and_markers")
@@ -808,6 +1303,7 @@ fn parse_test_content(name: String, content: &str, file_path: Option<PathBuf>) -
                     }
                 }
                 ":fail-fast" => (seen_marker, fail_fast) = (true, true),
+                ":fail" => (seen_marker, fail) = (true, true),
                 ":error" => (seen_marker, error) = (true, true),
                 ":language" => {
                     if let Some(lang) = trimmed_line.strip_prefix(':').and_then(|s| {
@@ -857,6 +1353,7 @@ fn parse_test_content(name: String, content: &str, file_path: Option<PathBuf>) -
                     skip,
                     platform: platform.unwrap_or(true),
                     fail_fast,
+                    fail,
                     error,
                     languages,
                 },
@@ -914,19 +1411,12 @@ fn parse_test_content(name: String, content: &str, file_path: Option<PathBuf>) -
                     let output = COMMENT_REGEX.replace_all(output, "").to_string();
 
                     // Normalize the whitespace in the expected output.
-                    let output = WHITESPACE_REGEX.replace_all(output.trim(), " ");
-                    let output = output.replace(" )", ")");
+                    let output = normalize_sexp(&output);
 
                     // Identify if the expected output has fields indicated. If not, then
                     // fields will not be checked.
                     let has_fields = SEXP_FIELD_REGEX.is_match(&output);
 
-                    let file_name = if let Some(ref path) = file_path {
-                        path.file_name().map(|n| n.to_string_lossy().to_string())
-                    } else {
-                        None
-                    };
-
                     let t = TestEntry::Example {
                         name: prev_name,
                         input,
@@ -936,7 +1426,6 @@ fn parse_test_content(name: String, content: &str, file_path: Option<PathBuf>) -
                         has_fields,
                         attributes_str: prev_attributes_str,
                         attributes: prev_attributes,
-                        file_name,
                     };
 
                     children.push(t);
@@ -1001,7 +1490,6 @@ d
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     },
                     TestEntry::Example {
                         name: "The second test".to_string(),
@@ -1012,7 +1500,6 @@ d
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     },
                 ],
                 file_path: None,
@@ -1064,7 +1551,6 @@ abc
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     },
                     TestEntry::Example {
                         name: "Code ending with dashes".to_string(),
@@ -1075,7 +1561,6 @@ abc
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     },
                 ],
                 file_path: None,
@@ -1130,6 +1615,16 @@ abc
 "
             .trim()
         );
+        assert_eq!(
+            format_sexp(r#"(a (MISSING "x") (b (#text "y (z)")))"#, 0),
+            r#"
+(a
+  (MISSING "x")
+  (b
+    (#text "y (z)")))
+"#
+            .trim()
+        );
     }
 
     #[test]
@@ -1231,7 +1726,6 @@ code
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     },
                     TestEntry::Example {
                         name: "sexp with comment between".to_string(),
@@ -1242,7 +1736,6 @@ code
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     },
                     TestEntry::Example {
                         name: "sexp with ';'".to_string(),
@@ -1253,7 +1746,6 @@ code
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     }
                 ],
                 file_path: None,
@@ -1348,7 +1840,6 @@ Subsequent test containing equals
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     },
                     TestEntry::Example {
                         name: "Second test".to_string(),
@@ -1359,7 +1850,6 @@ Subsequent test containing equals
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     },
                     TestEntry::Example {
                         name: "Test name with = symbol".to_string(),
@@ -1370,7 +1860,6 @@ Subsequent test containing equals
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     },
                     TestEntry::Example {
                         name: "Test containing equals".to_string(),
@@ -1381,7 +1870,6 @@ Subsequent test containing equals
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     },
                     TestEntry::Example {
                         name: "Subsequent test containing equals".to_string(),
@@ -1392,7 +1880,6 @@ Subsequent test containing equals
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     }
                 ],
                 file_path: None,
@@ -1439,7 +1926,6 @@ code with ----
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     },
                     TestEntry::Example {
                         name: "name with === signs".to_string(),
@@ -1450,7 +1936,6 @@ code with ----
                         has_fields: false,
                         attributes_str: String::new(),
                         attributes: TestAttributes::default(),
-                        file_name: None,
                     }
                 ]
             }
@@ -1492,10 +1977,50 @@ a
                         skip: true,
                         platform: true,
                         fail_fast: false,
+                        fail: false,
+                        error: false,
+                        languages: vec!["".into()]
+                    },
+                }]
+            }
+        );
+
+        let entry = parse_test_content(
+            "the-filename".to_string(),
+            r"
+=====================
+Test with fail marker
+:fail
+:fail-fast
+=====================
+a
+---
+(b)
+",
+            None,
+        );
+
+        assert_eq!(
+            entry,
+            TestEntry::Group {
+                name: "the-filename".to_string(),
+                file_path: None,
+                children: vec![TestEntry::Example {
+                    name: "Test with fail marker".to_string(),
+                    input: b"a".to_vec(),
+                    output: "(b)".to_string(),
+                    header_delim_len: 21,
+                    divider_delim_len: 3,
+                    has_fields: false,
+                    attributes_str: ":fail\n:fail-fast".to_string(),
+                    attributes: TestAttributes {
+                        skip: false,
+                        platform: true,
+                        fail_fast: true,
+                        fail: true,
                         error: false,
                         languages: vec!["".into()]
                     },
-                    file_name: None,
                 }]
             }
         );
@@ -1551,10 +2076,10 @@ a
                             skip: false,
                             platform: true,
                             fail_fast: true,
+                            fail: false,
                             error: false,
                             languages: vec!["".into()]
                         },
-                        file_name: None,
                     },
                     TestEntry::Example {
                         name: "Test with bad platform marker".to_string(),
@@ -1572,13 +2097,47 @@ a
                             skip: false,
                             platform: false,
                             fail_fast: false,
+                            fail: false,
                             error: false,
                             languages: vec!["foo".into()]
                         },
-                        file_name: None,
                     }
                 ]
             }
         );
     }
+
+    #[test]
+    fn test_matches_file_filter() {
+        let file_path = Path::new("/grammar/test/corpus/expressions.txt");
+        assert!(matches_file_filter(file_path, &[]));
+        for filter in [
+            "expressions.txt",
+            "corpus/expressions.txt",
+            "./test/corpus/expressions.txt",
+            "/grammar/test/corpus/expressions.txt",
+        ] {
+            assert!(
+                matches_file_filter(file_path, &[PathBuf::from(filter)]),
+                "{filter}"
+            );
+        }
+        for filter in [
+            "statements.txt",
+            "sions.txt",
+            "other/corpus/expressions.txt",
+        ] {
+            assert!(
+                !matches_file_filter(file_path, &[PathBuf::from(filter)]),
+                "{filter}"
+            );
+        }
+        assert!(matches_file_filter(
+            file_path,
+            &[
+                PathBuf::from("statements.txt"),
+                PathBuf::from("expressions.txt")
+            ]
+        ));
+    }
 }
@@ -7,42 +7,11 @@ use tree_sitter_highlight::{Highlight, HighlightConfiguration, HighlightEvent, H
 use tree_sitter_loader::{Config, Loader};
 
 use super::{
-    query_testing::{parse_position_comments, to_utf8_point, Assertion, Utf8Point},
+    query_testing::{check_assertions, parse_position_comments, to_utf8_point, Utf8Point},
     test::paint,
     util,
 };
 
-#[derive(Debug)]
-pub struct Failure {
-    row: usize,
-    column: usize,
-    expected_highlight: String,
-    actual_highlights: Vec<String>,
-}
-
-impl std::error::Error for Failure {}
-
-impl std::fmt::Display for Failure {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "Failure - row: {}, column: {}, expected highlight '{}', actual highlights: ",
-            self.row, self.column, self.expected_highlight
-        )?;
-        if self.actual_highlights.is_empty() {
-            write!(f, "none.")?;
-        } else {
-            for (i, actual_highlight) in self.actual_highlights.iter().enumerate() {
-                if i > 0 {
-                    write!(f, ", ")?;
-                }
-                write!(f, "'{actual_highlight}'")?;
-            }
-        }
-        Ok(())
-    }
-}
-
 pub fn test_highlights(
     loader: &Loader,
     loader_config: &Config,
@@ -139,74 +108,6 @@ fn test_highlights_indented(
         Ok(())
     }
 }
-pub fn iterate_assertions(
-    assertions: &[Assertion],
-    highlights: &[(Utf8Point, Utf8Point, Highlight)],
-    highlight_names: &[String],
-) -> Result<usize> {
-    // Iterate through all of the highlighting assertions, checking each one against the
-    // actual highlights.
-    let mut i = 0;
-    let mut actual_highlights = Vec::new();
-    for Assertion {
-        position,
-        length,
-        negative,
-        expected_capture_name: expected_highlight,
-    } in assertions
-    {
-        let mut passed = false;
-        let mut end_column = position.column + length - 1;
-        actual_highlights.clear();
-
-        // The assertions are ordered by position, so skip past all of the highlights that
-        // end at or before this assertion's position.
-        'highlight_loop: while let Some(highlight) = highlights.get(i) {
-            if highlight.1 <= *position {
-                i += 1;
-                continue;
-            }
-
-            // Iterate through all of the highlights that start at or before this assertion's
-            // position, looking for one that matches the assertion.
-            let mut j = i;
-            while let (false, Some(highlight)) = (passed, highlights.get(j)) {
-                end_column = position.column + length - 1;
-                if highlight.0.column > end_column {
-                    break 'highlight_loop;
-                }
-
-                // If the highlight matches the assertion, or if the highlight doesn't
-                // match the assertion but it's negative, this test passes. Otherwise,
-                // add this highlight to the list of actual highlights that span the
-                // assertion's position, in order to generate an error message in the event
-                // of a failure.
-                let highlight_name = &highlight_names[(highlight.2).0];
-                if (*highlight_name == *expected_highlight) == *negative {
-                    actual_highlights.push(highlight_name);
-                } else {
-                    passed = true;
-                    break 'highlight_loop;
-                }
-
-                j += 1;
-            }
-        }
-
-        if !passed {
-            return Err(Failure {
-                row: position.row,
-                column: end_column,
-                expected_highlight: expected_highlight.clone(),
-                actual_highlights: actual_highlights.into_iter().cloned().collect(),
-            }
-            .into());
-        }
-    }
-
-    Ok(assertions.len())
-}
-
 pub fn test_highlight(
     loader: &Loader,
     highlighter: &mut Highlighter,
@@ -219,7 +120,11 @@ pub fn test_highlight(
     let assertions =
         parse_position_comments(highlighter.parser(), &highlight_config.language, source)?;
 
-    iterate_assertions(&assertions, &highlights, &highlight_names)
+    let highlights = highlights
+        .iter()
+        .map(|(start, end, highlight)| (*start, *end, highlight_names[highlight.0].as_str()))
+        .collect::<Vec<_>>();
+    Ok(check_assertions(&assertions, &highlights, "highlight")?)
 }
 
 pub fn get_highlight_positions(
@@ -6,42 +6,11 @@ use tree_sitter_loader::{Config, Loader};
 use tree_sitter_tags::{TagsConfiguration, TagsContext};
 
 use super::{
-    query_testing::{parse_position_comments, to_utf8_point, Assertion, Utf8Point},
+    query_testing::{check_assertions, parse_position_comments, to_utf8_point, Utf8Point},
     test::paint,
     util,
 };
 
-#[derive(Debug)]
-pub struct Failure {
-    row: usize,
-    column: usize,
-    expected_tag: String,
-    actual_tags: Vec<String>,
-}
-
-impl std::error::Error for Failure {}
-
-impl std::fmt::Display for Failure {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "Failure - row: {}, column: {}, expected tag: '{}', actual tag: ",
-            self.row, self.column, self.expected_tag
-        )?;
-        if self.actual_tags.is_empty() {
-            write!(f, "none.")?;
-        } else {
-            for (i, actual_tag) in self.actual_tags.iter().enumerate() {
-                if i > 0 {
-                    write!(f, ", ")?;
-                }
-                write!(f, "'{actual_tag}'")?;
-            }
-        }
-        Ok(())
-    }
-}
-
 pub fn test_tags(
     loader: &Loader,
     loader_config: &Config,
@@ -146,61 +115,7 @@ pub fn test_tag(
     let tags = get_tag_positions(tags_context, tags_config, source)?;
     let assertions = parse_position_comments(tags_context.parser(), &tags_config.language, source)?;
 
-    // Iterate through all of the assertions, checking against the actual tags.
-    let mut i = 0;
-    let mut actual_tags = Vec::<&String>::new();
-    for Assertion {
-        position,
-        length,
-        negative,
-        expected_capture_name: expected_tag,
-    } in &assertions
-    {
-        let mut passed = false;
-        let mut end_column = position.column + length - 1;
-
-        'tag_loop: while let Some(tag) = tags.get(i) {
-            if tag.1 <= *position {
-                i += 1;
-                continue;
-            }
-
-            // Iterate through all of the tags that start at or before this assertion's
-            // position, looking for one that matches the assertion
-            let mut j = i;
-            while let (false, Some(tag)) = (passed, tags.get(j)) {
-                end_column = position.column + length - 1;
-                if tag.0.column > end_column {
-                    break 'tag_loop;
-                }
-
-                let tag_name = &tag.2;
-                if (*tag_name == *expected_tag) == *negative {
-                    actual_tags.push(tag_name);
-                } else {
-                    passed = true;
-                    break 'tag_loop;
-                }
-
-                j += 1;
-                if tag == tags.last().unwrap() {
-                    break 'tag_loop;
-                }
-            }
-        }
-
-        if !passed {
-            return Err(Failure {
-                row: position.row,
-                column: end_column,
-                expected_tag: expected_tag.clone(),
-                actual_tags: actual_tags.into_iter().cloned().collect(),
-            }
-            .into());
-        }
-    }
-
-    Ok(assertions.len())
+    Ok(check_assertions(&assertions, &tags, "tag")?)
 }
 
 pub fn get_tag_positions(
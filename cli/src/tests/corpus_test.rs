@@ -18,7 +18,9 @@ use crate::{
     test::{parse_tests, print_diff, print_diff_key, strip_sexp_fields},
     tests::{
         allocations,
-        helpers::fixtures::{fixtures_dir, get_language, get_test_language, SCRATCH_BASE_DIR},
+        helpers::fixtures::{
+            fixtures_dir, get_language, get_test_language, test_loader, SCRATCH_BASE_DIR,
+        },
     },
 };
 
@@ -333,6 +335,701 @@ pub fn test_language_corpus(
     }
 }
 
+#[test]
+fn test_generate_is_deterministic() {
+    for language_name in [
+        "aliased_rules",
+        "external_tokens",
+        "inlined_aliased_rules",
+        "readme_grammar",
+        "reserved_words",
+    ] {
+        let grammar_path = fixtures_dir()
+            .join("test_grammars")
+            .join(language_name)
+            .join("grammar.js");
+        let grammar_json = tree_sitter_generate::load_grammar_file(&grammar_path, None).unwrap();
+        let generate = |thread_count: usize| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(thread_count)
+                .build()
+                .unwrap()
+                .install(|| {
+                    tree_sitter_generate::generate_parser_for_grammar(
+                        &grammar_json,
+                        Some((0, 0, 0)),
+                    )
+                })
+                .unwrap()
+                .1
+        };
+
+        let first_c_code = generate(4);
+        assert!(
+            first_c_code == generate(4),
+            "Generating the parser for test grammar '{language_name}' twice produced different output"
+        );
+        assert!(
+            first_c_code == generate(1),
+            "Generating the parser for test grammar '{language_name}' with one thread produced different output"
+        );
+    }
+}
+
+#[test]
+fn test_generate_reuses_cached_parse_tables() {
+    let grammar_path = fixtures_dir()
+        .join("test_grammars")
+        .join("readme_grammar")
+        .join("grammar.js");
+    let grammar_json = tree_sitter_generate::load_grammar_file(&grammar_path, None).unwrap();
+    let cache_dir = tempfile::tempdir().unwrap();
+    let generate = || {
+        let repo_dir = tempfile::tempdir().unwrap();
+        let grammar_path = repo_dir.path().join("grammar.json");
+        fs::write(&grammar_path, &grammar_json).unwrap();
+        tree_sitter_generate::generate_parser_in_directory(
+            repo_dir.path(),
+            tree_sitter_generate::GenerateOptions {
+                grammar_path: grammar_path.to_str(),
+                cache_dir: Some(cache_dir.path()),
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        )
+        .unwrap();
+        fs::read_to_string(repo_dir.path().join("src").join("parser.c")).unwrap()
+    };
+
+    let c_code = generate();
+    let entries = fs::read_dir(cache_dir.path())
+        .unwrap()
+        .flat_map(|version_dir| fs::read_dir(version_dir.unwrap().path()).unwrap())
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    assert_eq!(entries.len(), 1);
+
+    // Rename a rule in the cached grammar, so that a cache hit can be told apart from
+    // building the tables again.
+    let mut entry =
+        serde_json::from_str::<serde_json::Value>(&fs::read_to_string(&entries[0]).unwrap())
+            .unwrap();
+    let rule_name = &mut entry["syntax_grammar"]["variables"][0]["name"];
+    assert!(c_code.contains(&format!("\"{}\"", rule_name.as_str().unwrap())));
+    *rule_name = "cached_rule".into();
+    fs::write(&entries[0], entry.to_string()).unwrap();
+    assert!(generate().contains("\"cached_rule\""));
+
+    tree_sitter_generate::clear_parser_cache(cache_dir.path()).unwrap();
+    assert_eq!(generate(), c_code);
+}
+
+#[test]
+fn test_generate_with_max_states() {
+    let grammar_path = fixtures_dir()
+        .join("test_grammars")
+        .join("readme_grammar")
+        .join("grammar.js");
+    let grammar_json = tree_sitter_generate::load_grammar_file(&grammar_path, None).unwrap();
+    let generate = |max_states: usize| {
+        let repo_dir = tempfile::tempdir().unwrap();
+        let grammar_path = repo_dir.path().join("grammar.json");
+        fs::write(&grammar_path, &grammar_json).unwrap();
+        tree_sitter_generate::generate_parser_in_directory(
+            repo_dir.path(),
+            tree_sitter_generate::GenerateOptions {
+                grammar_path: grammar_path.to_str(),
+                max_states: Some(max_states),
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        )
+    };
+
+    generate(1000).unwrap();
+    match generate(5) {
+        Err(tree_sitter_generate::GenerateError::BuildTables(
+            tree_sitter_generate::ParseTableBuilderError::StateLimit(error),
+        )) => {
+            assert_eq!(error.max_states, 5);
+            assert_eq!(error.state_counts_by_rule[0].0, "expression");
+            assert!(error
+                .state_counts_by_rule
+                .windows(2)
+                .all(|counts| counts[0].1 >= counts[1].1));
+        }
+        result => panic!("Expected the state limit to be exceeded, got {result:?}"),
+    }
+}
+
+#[test]
+fn test_generate_reports_diagnostics() {
+    let generate = |precedence: &str| {
+        let repo_dir = tempfile::tempdir().unwrap();
+        let grammar_path = repo_dir.path().join("grammar.json");
+        let grammar_json = serde_json::json!({
+            "name": "test",
+            "precedences": [[
+                {"type": "STRING", "value": "a"},
+                {"type": "STRING", "value": "b"},
+            ]],
+            "rules": {
+                "source_file": {
+                    "type": "PREC",
+                    "value": precedence,
+                    "content": {"type": "STRING", "value": "x"},
+                },
+            },
+        });
+        fs::write(&grammar_path, grammar_json.to_string()).unwrap();
+        let mut warnings = Vec::new();
+        let result = tree_sitter_generate::generate_parser_in_directory(
+            repo_dir.path(),
+            tree_sitter_generate::GenerateOptions {
+                grammar_path: grammar_path.to_str(),
+                ..Default::default()
+            },
+            &mut warnings,
+        );
+        (result, warnings)
+    };
+
+    let (result, warnings) = generate("a");
+    result.unwrap();
+    let warning = warnings
+        .iter()
+        .find(|warning| warning.code == "unused-precedence")
+        .unwrap();
+    assert_eq!(warning.severity, tree_sitter_generate::Severity::Warning);
+    assert_eq!(warning.message, "Unused precedences 'b' (list 0)");
+    assert_eq!(warning.rule, None);
+
+    let (result, _) = generate("c");
+    let error = tree_sitter_generate::Diagnostic::from(&result.unwrap_err());
+    assert_eq!(error.code, "undeclared-precedence");
+    assert_eq!(error.severity, tree_sitter_generate::Severity::Error);
+    assert_eq!(
+        error.message,
+        "Undeclared precedence 'c' in rule 'source_file'"
+    );
+    assert_eq!(error.rule.as_deref(), Some("source_file"));
+}
+
+#[test]
+fn test_generate_with_timing_report() {
+    let grammar_path = fixtures_dir()
+        .join("test_grammars")
+        .join("readme_grammar")
+        .join("grammar.js");
+    let grammar_json = tree_sitter_generate::load_grammar_file(&grammar_path, None).unwrap();
+    let repo_dir = tempfile::tempdir().unwrap();
+    let grammar_path = repo_dir.path().join("grammar.json");
+    fs::write(&grammar_path, &grammar_json).unwrap();
+
+    let mut timings = tree_sitter_generate::TimingReport::new();
+    tree_sitter_generate::generate_parser_in_directory(
+        repo_dir.path(),
+        tree_sitter_generate::GenerateOptions {
+            grammar_path: grammar_path.to_str(),
+            timings: Some(&mut timings),
+            ..Default::default()
+        },
+        &mut Vec::new(),
+    )
+    .unwrap();
+
+    let stage_names = timings
+        .stages
+        .iter()
+        .map(|stage| stage.name)
+        .collect::<Vec<_>>();
+    for name in [
+        "intern_symbols",
+        "extract_tokens",
+        "expand_repeats",
+        "flatten_grammar",
+        "expand_tokens",
+        "process_inlines",
+        "build_parse_table",
+        "build_lex_table",
+        "minimize_parse_table",
+        "render_c_code",
+    ] {
+        assert!(stage_names.contains(&name), "missing stage {name}");
+    }
+    // The rules are ordered by how long their closures took, which varies from run to run.
+    let expression = timings
+        .closure_rules
+        .iter()
+        .find(|rule| rule.name == "expression")
+        .expect("missing closure timing for `expression`");
+    assert!(expression.expansion_count > 0);
+}
+
+#[test]
+fn test_generate_with_grammar_stats() {
+    let grammar_path = fixtures_dir()
+        .join("test_grammars")
+        .join("readme_grammar")
+        .join("grammar.js");
+    let grammar_json = tree_sitter_generate::load_grammar_file(&grammar_path, None).unwrap();
+    let repo_dir = tempfile::tempdir().unwrap();
+    let grammar_path = repo_dir.path().join("grammar.json");
+    fs::write(&grammar_path, &grammar_json).unwrap();
+
+    let mut stats = tree_sitter_generate::GrammarStats::default();
+    tree_sitter_generate::generate_parser_in_directory(
+        repo_dir.path(),
+        tree_sitter_generate::GenerateOptions {
+            grammar_path: grammar_path.to_str(),
+            stats: Some(&mut stats),
+            ..Default::default()
+        },
+        &mut Vec::new(),
+    )
+    .unwrap();
+
+    assert_eq!(stats.variable_count_before_expand_repeats, 3);
+    assert_eq!(stats.variable_count_after_expand_repeats, 3);
+    assert_eq!(stats.token_count, 7);
+    assert_eq!(stats.external_token_count, 0);
+    assert_eq!(stats.inlined_production_count, 0);
+    assert_eq!(stats.largest_rules[0].name, "expression");
+    assert_eq!(stats.largest_rules[0].production_count, 5);
+    assert!(stats.lex_state_count > 0);
+    assert!(stats.table_size_bytes > 0);
+
+    let c_code = fs::read_to_string(repo_dir.path().join("src").join("parser.c")).unwrap();
+    assert!(c_code.contains(&format!(
+        "#define STATE_COUNT {}\n",
+        stats.parse_state_count
+    )));
+}
+
+#[test]
+fn test_generate_with_inline_report() {
+    let repo_dir = tempfile::tempdir().unwrap();
+    let grammar_path = repo_dir.path().join("grammar.json");
+    let grammar_json = serde_json::json!({
+        "name": "test",
+        "inline": ["_value", "_unused"],
+        "rules": {
+            "source_file": {
+                "type": "REPEAT",
+                "content": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "SYMBOL", "name": "_value"},
+                        {
+                            "type": "SEQ",
+                            "members": [
+                                {"type": "STRING", "value": "("},
+                                {"type": "SYMBOL", "name": "_value"},
+                                {"type": "STRING", "value": ")"},
+                            ],
+                        },
+                    ],
+                },
+            },
+            "_value": {
+                "type": "CHOICE",
+                "members": [
+                    {"type": "SYMBOL", "name": "number"},
+                    {"type": "SYMBOL", "name": "identifier"},
+                    {"type": "STRING", "value": "null"},
+                ],
+            },
+            "_unused": {
+                "type": "SEQ",
+                "members": [
+                    {"type": "STRING", "value": "unused"},
+                    {"type": "SYMBOL", "name": "identifier"},
+                ],
+            },
+            "number": {"type": "PATTERN", "value": "\\d+"},
+            "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+        },
+    });
+    fs::write(&grammar_path, grammar_json.to_string()).unwrap();
+
+    let mut inline_report = tree_sitter_generate::InlineReport::default();
+    tree_sitter_generate::generate_parser_in_directory(
+        repo_dir.path(),
+        tree_sitter_generate::GenerateOptions {
+            grammar_path: grammar_path.to_str(),
+            inline_report: Some(&mut inline_report),
+            ..Default::default()
+        },
+        &mut Vec::new(),
+    )
+    .unwrap();
+
+    assert_eq!(inline_report.rules.len(), 1);
+    assert_eq!(inline_report.rules[0].name, "_value");
+    assert_eq!(inline_report.rules[0].inlined_into_count, 2);
+    assert_eq!(inline_report.rules[0].production_count, 6);
+    assert_eq!(inline_report.added_production_count, 6);
+    assert_eq!(inline_report.unused_rules, ["_unused"]);
+}
+
+#[test]
+fn test_generate_with_dynamic_precedence_report() {
+    let grammar_path = fixtures_dir()
+        .join("test_grammars")
+        .join("dynamic_precedence")
+        .join("grammar.js");
+    let grammar_json = tree_sitter_generate::load_grammar_file(&grammar_path, None).unwrap();
+    let generate = |grammar_json: &str| {
+        let repo_dir = tempfile::tempdir().unwrap();
+        let grammar_path = repo_dir.path().join("grammar.json");
+        fs::write(&grammar_path, grammar_json).unwrap();
+        let mut report = tree_sitter_generate::DynamicPrecedenceReport::default();
+        tree_sitter_generate::generate_parser_in_directory(
+            repo_dir.path(),
+            tree_sitter_generate::GenerateOptions {
+                grammar_path: grammar_path.to_str(),
+                dynamic_precedence_report: Some(&mut report),
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        )
+        .unwrap();
+        report
+    };
+
+    // The dynamic precedence of `declarator` decides between the two interpretations of
+    // `a * b`, even though `declarator` comes after the conflicting rules.
+    let report = generate(&grammar_json);
+    assert_eq!(report.annotations.len(), 1);
+    assert_eq!(report.annotations[0].rule, "declarator");
+    assert_eq!(report.annotations[0].dynamic_precedence, 1);
+    assert!(report.annotations[0].conflict_count > 0);
+    assert!(report.tied_conflicts.is_empty());
+
+    // Without it, nothing decides between them.
+    let mut grammar = serde_json::from_str::<serde_json::Value>(&grammar_json).unwrap();
+    let declarator = &mut grammar["rules"]["declarator"]["members"][0];
+    assert_eq!(declarator["type"], "PREC_DYNAMIC");
+    *declarator = declarator["content"].take();
+    let report = generate(&grammar.to_string());
+    assert!(report.annotations.is_empty());
+    assert_eq!(report.tied_conflicts.len(), 1);
+    assert_eq!(report.tied_conflicts[0].rules, ["expression", "type"]);
+    assert_eq!(report.tied_conflicts[0].lookaheads, ["'*'"]);
+}
+
+#[test]
+fn test_generate_external_scanner_header() {
+    let repo_dir = tempfile::tempdir().unwrap();
+    let grammar_path = repo_dir.path().join("grammar.json");
+    let header_path = repo_dir
+        .path()
+        .join("src")
+        .join("tree_sitter")
+        .join(tree_sitter_generate::EXTERNAL_SCANNER_HEADER_NAME);
+    let generate = |externals: serde_json::Value| {
+        let grammar = serde_json::json!({
+            "name": "external_scanner_header",
+            "externals": externals,
+            "rules": {
+                "program": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "STRING", "value": "||"},
+                        {"type": "SYMBOL", "name": "PIPE_PIPE"},
+                    ],
+                },
+                "PIPE_PIPE": {"type": "STRING", "value": "&&"},
+            },
+        });
+        fs::write(&grammar_path, grammar.to_string()).unwrap();
+        tree_sitter_generate::generate_parser_in_directory(
+            repo_dir.path(),
+            tree_sitter_generate::GenerateOptions {
+                grammar_path: grammar_path.to_str(),
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        )
+        .unwrap();
+    };
+
+    // String externals are sanitized like the symbols in `parser.c`, and identifiers that
+    // would be the same are given a suffix.
+    generate(serde_json::json!([
+        {"type": "SYMBOL", "name": "indent"},
+        {"type": "STRING", "value": "||"},
+        {"type": "SYMBOL", "name": "PIPE_PIPE"},
+    ]));
+    let header = fs::read_to_string(&header_path).unwrap();
+    assert!(header.contains("#define TS_EXTERNAL_SYMBOL_COUNT 3\n"));
+    assert!(header.contains(
+        "  ts_external_symbol_indent = 0,\n  \
+           ts_external_symbol_PIPE_PIPE = 1, // \"||\"\n  \
+           ts_external_symbol_PIPE_PIPE2 = 2,\n"
+    ));
+    assert!(header.contains("ts_external_symbol_PIPE_PIPE2 + 1 == TS_EXTERNAL_SYMBOL_COUNT"));
+
+    // The header is removed once the grammar no longer has any external tokens.
+    generate(serde_json::json!([]));
+    assert!(!header_path.exists());
+}
+
+#[test]
+fn test_generate_with_older_abi_versions() {
+    let generate = |name: &str, abi_version: usize| {
+        let grammar_path = fixtures_dir()
+            .join("test_grammars")
+            .join(name)
+            .join("grammar.js");
+        let grammar_json = tree_sitter_generate::load_grammar_file(&grammar_path, None).unwrap();
+        let mut grammar = serde_json::from_str::<serde_json::Value>(&grammar_json).unwrap();
+        let name = format!("{name}_abi_{abi_version}");
+        grammar["name"] = name.clone().into();
+
+        let repo_dir = tempfile::tempdir().unwrap();
+        let grammar_path = repo_dir.path().join("grammar.json");
+        fs::write(&grammar_path, grammar.to_string()).unwrap();
+        tree_sitter_generate::generate_parser_in_directory(
+            repo_dir.path(),
+            tree_sitter_generate::GenerateOptions {
+                grammar_path: grammar_path.to_str(),
+                abi_version,
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        )
+        .map(|()| {
+            let parser_code = fs::read_to_string(repo_dir.path().join("src/parser.c")).unwrap();
+            (name, parser_code)
+        })
+    };
+
+    // Parsers generated with older ABI versions can still be loaded and used.
+    for abi_version in [tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION, 14] {
+        let (name, parser_code) = generate("associativity_left", abi_version).unwrap();
+        let language = get_test_language(&name, &parser_code, None);
+        assert_eq!(language.abi_version(), abi_version);
+
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse("x+y+z", None).unwrap();
+        assert_eq!(
+            tree.root_node().to_sexp(),
+            "(expression (math_operation \
+               (expression (math_operation (expression (identifier)) (expression (identifier)))) \
+               (expression (identifier))))"
+        );
+    }
+
+    // Features that change how the grammar is parsed can't be left out.
+    let error = generate("reserved_words", 14).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "The grammar uses features that can't be expressed in ABI version 14:\n  \
+         reserved words (requires ABI version 15)"
+    );
+    let error = generate("multiple_word_tokens", 14).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "The grammar uses features that can't be expressed in ABI version 14:\n  \
+         multiple word tokens (requires ABI version 16)"
+    );
+
+    let error = generate("associativity_left", 12).unwrap_err();
+    assert_eq!(error.code(), "unsupported-abi-version");
+}
+
+#[test]
+fn test_generate_with_and_without_lexer_minimization() {
+    let generate = |name: &str, minimize_lexer: bool| {
+        let test_path = fixtures_dir().join("test_grammars").join(name);
+        let grammar_json =
+            tree_sitter_generate::load_grammar_file(&test_path.join("grammar.js"), None).unwrap();
+        let mut grammar = serde_json::from_str::<serde_json::Value>(&grammar_json).unwrap();
+        let name = if minimize_lexer {
+            format!("{name}_minimized")
+        } else {
+            format!("{name}_unminimized")
+        };
+        grammar["name"] = name.clone().into();
+
+        let repo_dir = tempfile::tempdir().unwrap();
+        let grammar_path = repo_dir.path().join("grammar.json");
+        fs::write(&grammar_path, grammar.to_string()).unwrap();
+        let mut stats = tree_sitter_generate::GrammarStats::default();
+        tree_sitter_generate::generate_parser_in_directory(
+            repo_dir.path(),
+            tree_sitter_generate::GenerateOptions {
+                grammar_path: grammar_path.to_str(),
+                minimize_lexer,
+                stats: Some(&mut stats),
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        )
+        .unwrap();
+        let parser_code = fs::read_to_string(repo_dir.path().join("src/parser.c")).unwrap();
+        (
+            get_test_language(&name, &parser_code, None),
+            stats.lex_state_count,
+        )
+    };
+
+    // Merging equivalent lex states doesn't change how any of the corpus examples are parsed.
+    for name in [
+        "tokens_with_shared_suffixes",
+        "precedence_on_token",
+        "case_insensitive_keywords",
+        "anonymous_tokens_with_escaped_chars",
+        "unicode_classes",
+    ] {
+        let (minimized_language, minimized_lex_state_count) = generate(name, true);
+        let (unminimized_language, unminimized_lex_state_count) = generate(name, false);
+        assert!(minimized_lex_state_count <= unminimized_lex_state_count);
+
+        let corpus_path = fixtures_dir()
+            .join("test_grammars")
+            .join(name)
+            .join("corpus.txt");
+        let tests = flatten_tests(parse_tests(&corpus_path).unwrap(), None, None);
+        assert!(!tests.is_empty());
+        let mut parser = Parser::new();
+        for test in tests {
+            parser.set_language(&minimized_language).unwrap();
+            let minimized_tree = parser.parse(&test.input, None).unwrap();
+            parser.set_language(&unminimized_language).unwrap();
+            let unminimized_tree = parser.parse(&test.input, None).unwrap();
+            assert_eq!(
+                minimized_tree.root_node().to_sexp(),
+                unminimized_tree.root_node().to_sexp(),
+                "{name}: {}",
+                test.name
+            );
+        }
+
+        // The lexer states that read the suffixes shared by the `type` token's alternatives
+        // are merged.
+        if name == "tokens_with_shared_suffixes" {
+            assert!(minimized_lex_state_count * 3 < unminimized_lex_state_count * 2);
+        }
+    }
+}
+
+#[test]
+fn test_generate_with_split_output() {
+    let grammar_path = fixtures_dir()
+        .join("test_grammars")
+        .join("case_insensitive_keywords")
+        .join("grammar.js");
+    let grammar_json = tree_sitter_generate::load_grammar_file(&grammar_path, None).unwrap();
+    let mut grammar = serde_json::from_str::<serde_json::Value>(&grammar_json).unwrap();
+    grammar["name"] = "case_insensitive_keywords_split".into();
+
+    let repo_dir = tempfile::tempdir().unwrap();
+    let src_dir = repo_dir.path().join("src");
+    let grammar_path = repo_dir.path().join("grammar.json");
+    fs::write(&grammar_path, grammar.to_string()).unwrap();
+    let generate = |split_output: Option<usize>| {
+        tree_sitter_generate::generate_parser_in_directory(
+            repo_dir.path(),
+            tree_sitter_generate::GenerateOptions {
+                grammar_path: grammar_path.to_str(),
+                split_output,
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        )
+        .unwrap();
+        let mut table_files = fs::read_dir(&src_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.starts_with("parser_tables_"))
+            .collect::<Vec<_>>();
+        table_files.sort();
+        table_files
+    };
+
+    // The tables are moved out of `parser.c`, and the loader compiles every file.
+    assert_eq!(
+        generate(Some(3)),
+        [
+            "parser_tables_0.c",
+            "parser_tables_1.c",
+            "parser_tables_2.c"
+        ]
+    );
+    let parser_code = fs::read_to_string(src_dir.join("parser.c")).unwrap();
+    assert!(parser_code.contains("extern const TSParseActionEntry ts_parse_actions[];"));
+    assert!(!parser_code.contains("ts_parse_actions[] = {"));
+    assert!(parser_code.contains("bool ts_lex_keywords(TSLexer *lexer, TSStateId state);"));
+    assert!(parser_code.ends_with(concat!(
+        "#ifdef TREE_SITTER_INCLUDE_PARSER_TABLES\n",
+        "#include \"parser_tables_0.c\"\n",
+        "#include \"parser_tables_1.c\"\n",
+        "#include \"parser_tables_2.c\"\n",
+        "#endif\n",
+    )));
+    let table_code = fs::read_to_string(src_dir.join("parser_tables_0.c")).unwrap();
+    assert!(table_code.starts_with("#ifndef TREE_SITTER_INCLUDE_PARSER_TABLES\n"));
+
+    let mut config = tree_sitter_loader::CompileConfig::new(&src_dir, None, None);
+    config.name = "case_insensitive_keywords_split".to_string();
+    let language = test_loader()
+        .load_language_at_path_with_name(config)
+        .unwrap();
+    let mut parser = Parser::new();
+    parser.set_language(&language).unwrap();
+    let tree = parser.parse("SELECT a, b FROM c;", None).unwrap();
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        "(program (statement (identifier) (identifier) (identifier)))"
+    );
+
+    // Files left over from splitting the output into more files are removed.
+    assert_eq!(generate(Some(1)), ["parser_tables_0.c"]);
+    assert!(generate(None).is_empty());
+    let parser_code = fs::read_to_string(src_dir.join("parser.c")).unwrap();
+    assert!(parser_code.contains("static const TSParseActionEntry ts_parse_actions[] = {"));
+}
+
+#[test]
+fn test_generate_node_types_with_nested_supertypes() {
+    let test_path = fixtures_dir()
+        .join("test_grammars")
+        .join("nested_supertypes");
+    let grammar_json =
+        tree_sitter_generate::load_grammar_file(&test_path.join("grammar.js"), None).unwrap();
+    let repo_dir = tempfile::tempdir().unwrap();
+    let grammar_path = repo_dir.path().join("grammar.json");
+    fs::write(&grammar_path, &grammar_json).unwrap();
+
+    tree_sitter_generate::generate_parser_in_directory(
+        repo_dir.path(),
+        tree_sitter_generate::GenerateOptions {
+            grammar_path: grammar_path.to_str(),
+            emit_typescript: true,
+            ..Default::default()
+        },
+        &mut Vec::new(),
+    )
+    .unwrap();
+
+    let expected_node_types = fs::read_to_string(test_path.join("node-types.json"))
+        .unwrap()
+        .replace("\r\n", "\n");
+    let actual_node_types =
+        fs::read_to_string(repo_dir.path().join("src").join("node-types.json")).unwrap();
+    assert_eq!(actual_node_types, expected_node_types);
+
+    let expected_definitions = fs::read_to_string(test_path.join("node-types.d.ts"))
+        .unwrap()
+        .replace("\r\n", "\n");
+    let actual_definitions =
+        fs::read_to_string(repo_dir.path().join("src").join("node-types.d.ts")).unwrap();
+    assert_eq!(actual_definitions, expected_definitions);
+}
+
 #[test]
 fn test_feature_corpus_files() {
     let test_grammars_dir = fixtures_dir().join("test_grammars");
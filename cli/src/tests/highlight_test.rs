@@ -1,19 +1,39 @@
 use std::{
+    collections::HashSet,
     ffi::CString,
     fs,
     os::raw::c_char,
     ptr, slice, str,
     sync::{
         atomic::{AtomicUsize, Ordering},
-        LazyLock,
+        Arc, LazyLock,
     },
+    thread,
 };
 
+use indoc::indoc;
+use tree_sitter::{edits_between, InputEdit, Parser, Point, QueryErrorKind};
 use tree_sitter_highlight::{
-    c, Error, Highlight, HighlightConfiguration, HighlightEvent, Highlighter, HtmlRenderer,
+    c,
+    locals::{analyze_locals, LocalScopeAnalysis},
+    semantic_tokens::{
+        encode_semantic_tokens, semantic_tokens_edits, PositionEncoding, SemanticTokensEdit,
+        SemanticTokensLegend,
+    },
+    Error, Highlight, HighlightConfiguration, HighlightEvent, HighlightSession, Highlighter,
+    HtmlRenderer, NonconformantCapture,
 };
 
-use super::helpers::fixtures::{get_highlight_config, get_language, get_language_queries_path};
+use crate::{
+    highlight::{highlight_files_parallel, HighlightOptions, Style, Theme},
+    parse::Encoding,
+    tests::{
+        generate_parser,
+        helpers::fixtures::{
+            get_highlight_config, get_language, get_language_queries_path, get_test_language,
+        },
+    },
+};
 
 static JS_HIGHLIGHT: LazyLock<HighlightConfiguration> =
     LazyLock::new(|| get_highlight_config("javascript", Some("injections.scm"), &HIGHLIGHT_NAMES));
@@ -464,6 +484,213 @@ fn test_highlighting_with_content_children_included() {
     );
 }
 
+#[test]
+fn test_highlighting_injection_with_line_prefix() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"
+        {
+            "name": "test_indented_quotes",
+            "extras": [],
+            "rules": {
+                "document": {
+                    "type": "REPEAT",
+                    "content": {
+                        "type": "CHOICE",
+                        "members": [
+                            {"type": "SYMBOL", "name": "quote"},
+                            {"type": "SYMBOL", "name": "paragraph"}
+                        ]
+                    }
+                },
+                "quote": {"type": "PATTERN", "value": "([ \\t]*>[^\\n]*\\n)+"},
+                "paragraph": {"type": "PATTERN", "value": "[^ \\t>\\n][^\\n]*\\n"}
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    let language = get_test_language(&parser_name, &parser_code, None);
+    let mut config = HighlightConfiguration::new(
+        language.clone(),
+        "indented-quotes",
+        "(paragraph) @comment",
+        r#"
+        ((quote) @injection.content
+         (#set! injection.language "javascript")
+         (#set! injection.line-prefix "[ \t]*> ?"))
+        "#,
+        "",
+    )
+    .unwrap();
+    config.configure(&HIGHLIGHT_NAMES);
+
+    let source = indoc! {"
+        Some text
+          > function a(b) {
+          >   return b;
+          > }
+        More text
+    "};
+
+    // The quote markers and the indentation before them are left out of the injected document,
+    // so it parses without errors.
+    let mut highlighter = Highlighter::new();
+    let session = HighlightSession::new(
+        &mut highlighter,
+        &config,
+        source,
+        None,
+        &test_language_for_injection_string,
+    )
+    .unwrap();
+    let (layer, tree) = session.layers().nth(1).unwrap();
+    assert_eq!(layer.language_name, "javascript");
+    assert!(!tree.root_node().has_error(), "{}", tree.root_node());
+    let included_text = tree
+        .included_ranges()
+        .iter()
+        .map(|range| &source[range.start_byte..range.end_byte])
+        .collect::<Vec<_>>();
+    assert_eq!(included_text, ["function a(b) {\n", "  return b;\n", "}\n"]);
+    for range in tree.included_ranges() {
+        let line = &source[..range.start_byte];
+        assert_eq!(range.start_point.row, line.matches('\n').count());
+        assert_eq!(
+            range.start_point.column,
+            range.start_byte - line.rfind('\n').unwrap() - 1
+        );
+    }
+
+    // The highlights of the injected document refer to the positions in the original text.
+    let tokens = to_token_vector(source, &config).unwrap();
+    assert_eq!(tokens[0], [("Some text", vec!["comment"])]);
+    assert_eq!(tokens[1][0], ("  > ", vec![]));
+    assert_eq!(tokens[1][1], ("function", vec!["keyword"]));
+    assert_eq!(tokens[2][0], ("  >   ", vec![]));
+    assert_eq!(tokens[2][1], ("return", vec!["keyword"]));
+    assert!(tokens
+        .iter()
+        .flatten()
+        .all(|(text, highlights)| !text.contains('>') || highlights.is_empty()));
+    assert_eq!(tokens[4], [("More text", vec!["comment"])]);
+
+    // The prefix has to be a valid regex.
+    let error = HighlightConfiguration::new(
+        language,
+        "indented-quotes",
+        "",
+        "((quote) @injection.content (#set! injection.line-prefix \"(\"))",
+        "",
+    )
+    .err()
+    .unwrap();
+    assert_eq!(error.kind, QueryErrorKind::Predicate);
+}
+
+#[test]
+fn test_highlighting_on_multiple_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<HighlightConfiguration>();
+
+    let sources = [
+        (&*JS_HIGHLIGHT, "function a(b) { return b + 1; }"),
+        (
+            &*HTML_HIGHLIGHT,
+            "<div><script>const c = d();</script></div>",
+        ),
+        (&*JS_HIGHLIGHT, "const s = html `<b>${e}</b>`;"),
+    ];
+    let expected_tokens = sources
+        .iter()
+        .map(|(config, source)| to_token_vector(source, config).unwrap())
+        .collect::<Vec<_>>();
+
+    // The configurations are shared between threads that each use their own highlighter.
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for ((config, source), expected_tokens) in sources.iter().zip(&expected_tokens) {
+                    assert_eq!(&to_token_vector(source, config).unwrap(), expected_tokens);
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn test_highlight_files_parallel() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = (0..8)
+        .map(|i| {
+            let (config, source) = if i % 2 == 0 {
+                (
+                    &*JS_HIGHLIGHT,
+                    format!("function f{i}(a) {{ return a + {i}; }}"),
+                )
+            } else {
+                (
+                    &*HTML_HIGHLIGHT,
+                    format!("<p>{i}</p><script>let x = {i};</script>"),
+                )
+            };
+            let path = dir.path().join(format!("file-{i}"));
+            fs::write(&path, source).unwrap();
+            (path, config)
+        })
+        .collect::<Vec<_>>();
+
+    let options = HighlightOptions {
+        theme: Theme {
+            styles: HIGHLIGHT_NAMES.iter().map(|_| Style::default()).collect(),
+            highlight_names: HIGHLIGHT_NAMES.clone(),
+        },
+        check: false,
+        captures_path: None,
+        inline_styles: true,
+        css_file: None,
+        html: false,
+        quiet: false,
+        print_time: false,
+        cancellation_flag: Arc::default(),
+        encoding: Encoding::Utf8,
+        row_range: None,
+        json: true,
+        merge_adjacent: false,
+    };
+    let highlight_files = |thread_count| {
+        let mut output = Vec::new();
+        let summaries = highlight_files_parallel(
+            &files,
+            test_language_for_injection_string,
+            thread_count,
+            &options,
+            &mut output,
+        )
+        .unwrap();
+        let language_names = summaries
+            .into_iter()
+            .map(|summary| summary.language_name)
+            .collect::<Vec<_>>();
+        (String::from_utf8(output).unwrap(), language_names)
+    };
+
+    // The output is the same as when the files are highlighted one at a time, and the output of
+    // each file is in one piece.
+    let (expected_output, expected_language_names) = highlight_files(1);
+    let (output, language_names) = highlight_files(4);
+    assert_eq!(output, expected_output);
+    assert_eq!(language_names, expected_language_names);
+    assert_eq!(language_names[..2], ["javascript", "html"]);
+
+    let file_names = output
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["file"].clone())
+        .collect::<Vec<_>>();
+    let mut expected_file_names = file_names.clone();
+    expected_file_names.dedup();
+    assert_eq!(expected_file_names.len(), files.len());
+}
+
 #[test]
 fn test_highlighting_cancellation() {
     // An HTML document with a large injected JavaScript document:
@@ -503,6 +730,343 @@ fn test_highlighting_cancellation() {
     panic!("Expected an error while iterating highlighter");
 }
 
+#[test]
+fn test_highlighting_existing_tree() {
+    let sources = [
+        (
+            &*HTML_HIGHLIGHT,
+            indoc! {"
+                <div>
+                  <script>
+                    const a = html`<b>${1 + 2}</b>`;
+                  </script>
+                </div>
+            "},
+        ),
+        (&*EJS_HIGHLIGHT, "<% if (x) { %>\n<b><%= y %></b>\n<% } %>"),
+        (
+            &*JS_HIGHLIGHT,
+            "function f(param) { const x = 1; return param + x; }",
+        ),
+    ];
+
+    let mut parser = Parser::new();
+    for (config, source) in sources {
+        let mut highlighter = Highlighter::new();
+        let expected_events = highlighter
+            .highlight(
+                config,
+                source.as_bytes(),
+                None,
+                &test_language_for_injection_string,
+            )
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        parser.set_language(&config.language).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let events = highlighter
+            .highlight_existing_tree(
+                config,
+                &tree,
+                source.as_bytes(),
+                None,
+                &test_language_for_injection_string,
+            )
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(events, expected_events, "{source:?}");
+    }
+}
+
+#[test]
+fn test_highlighting_existing_tree_after_edit() {
+    let old_source = "const s = html `<div>${a}</div>`;";
+    let new_source = "const s = html `<div>${a < b}</div>`; let t = 1;";
+
+    let mut parser = Parser::new();
+    parser.set_language(&JS_HIGHLIGHT.language).unwrap();
+    let mut tree = parser.parse(old_source, None).unwrap();
+    tree.edit(&InputEdit {
+        start_byte: 23,
+        old_end_byte: 33,
+        new_end_byte: 48,
+        start_position: Point::new(0, 23),
+        old_end_position: Point::new(0, 33),
+        new_end_position: Point::new(0, 48),
+    });
+    let tree = parser.parse(new_source, Some(&tree)).unwrap();
+
+    let mut highlighter = Highlighter::new();
+    let events = highlighter
+        .highlight_existing_tree(
+            &JS_HIGHLIGHT,
+            &tree,
+            new_source.as_bytes(),
+            None,
+            &test_language_for_injection_string,
+        )
+        .unwrap();
+    assert_eq!(
+        tokens_for_events(new_source, events).unwrap(),
+        to_token_vector(new_source, &JS_HIGHLIGHT).unwrap(),
+    );
+
+    // The tree has to be parsed with the configuration's language.
+    let tree = parser.parse(old_source, None).unwrap();
+    assert_eq!(
+        highlighter
+            .highlight_existing_tree(
+                &HTML_HIGHLIGHT,
+                &tree,
+                old_source.as_bytes(),
+                None,
+                &test_language_for_injection_string,
+            )
+            .err(),
+        Some(Error::InvalidLanguage),
+    );
+}
+
+#[test]
+fn test_highlight_session_edit_inside_injection() {
+    let old_source = indoc! {"
+        <div>
+          <script>
+            const a = 1;
+          </script>
+        </div>
+    "};
+    let new_source = old_source.replace("const a = 1;", "const a = f(b);");
+
+    let mut highlighter = Highlighter::new();
+    let mut session = HighlightSession::new(
+        &mut highlighter,
+        &HTML_HIGHLIGHT,
+        old_source,
+        None,
+        &test_language_for_injection_string,
+    )
+    .unwrap();
+    assert_eq!(
+        session
+            .layers()
+            .map(|(layer, _)| layer.language_name)
+            .collect::<Vec<_>>(),
+        ["html", "javascript"],
+    );
+
+    let changed_ranges = session
+        .edit(
+            &mut highlighter,
+            &edits_between(old_source, &new_source),
+            new_source.as_str(),
+            None,
+            &test_language_for_injection_string,
+        )
+        .unwrap();
+    assert_eq!(session.source(), new_source.as_bytes());
+    assert_eq!(session.layers().count(), 2);
+
+    // Only the script's contents changed, so the tags around it don't need to be highlighted
+    // again.
+    let script_start = new_source.find("<script>").unwrap() + "<script>".len();
+    let script_end = new_source.find("</script>").unwrap();
+    assert!(!changed_ranges.is_empty());
+    for range in &changed_ranges {
+        assert!(
+            script_start <= range.start && range.end <= script_end,
+            "{range:?}",
+        );
+    }
+    assert_session_highlights(&mut highlighter, &session, &HTML_HIGHLIGHT, &changed_ranges);
+}
+
+#[test]
+fn test_highlight_session_edit_removing_injection() {
+    let old_source = indoc! {"
+        <div>
+          <script>
+            const a = 1;
+          </script>
+          <b>c</b>
+        </div>
+    "};
+    let new_source = old_source
+        .replace("<script>", "<b>")
+        .replace("</script>", "</b>");
+
+    let mut highlighter = Highlighter::new();
+    let mut session = HighlightSession::new(
+        &mut highlighter,
+        &HTML_HIGHLIGHT,
+        old_source,
+        None,
+        &test_language_for_injection_string,
+    )
+    .unwrap();
+    assert_eq!(session.layers().count(), 2);
+
+    let changed_ranges = session
+        .edit(
+            &mut highlighter,
+            &edits_between(old_source, &new_source),
+            new_source.as_str(),
+            None,
+            &test_language_for_injection_string,
+        )
+        .unwrap();
+    assert_eq!(
+        session
+            .layers()
+            .map(|(layer, _)| layer.language_name)
+            .collect::<Vec<_>>(),
+        ["html"],
+    );
+
+    // The text that used to be highlighted as JavaScript has to be highlighted again.
+    let text_start = new_source.find("const").unwrap();
+    let text_end = new_source.find(';').unwrap() + 1;
+    assert!(
+        changed_ranges
+            .iter()
+            .any(|range| range.start <= text_start && text_end <= range.end),
+        "{changed_ranges:?}",
+    );
+    assert_session_highlights(&mut highlighter, &session, &HTML_HIGHLIGHT, &changed_ranges);
+}
+
+#[test]
+fn test_analyze_locals() {
+    let source = [
+        "module.exports = function a(b) {",
+        "  const module = c;",
+        "  console.log(module, b);",
+        "}",
+    ]
+    .join("\n");
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("javascript")).unwrap();
+    let tree = parser.parse(&source, None).unwrap();
+    let analysis = analyze_locals(&JS_HIGHLIGHT, &tree, source.as_bytes());
+
+    // The references are resolved in the same way as when highlighting.
+    let references = resolved_references(&analysis);
+    for reference in [
+        ("module", Point::new(0, 0), None),
+        ("c", Point::new(1, 17), None),
+        ("module", Point::new(2, 14), Some(Point::new(1, 8))),
+        ("b", Point::new(2, 22), Some(Point::new(0, 28))),
+    ] {
+        assert!(references.contains(&reference), "{reference:?}");
+    }
+
+    let b = analysis
+        .definitions
+        .iter()
+        .position(|definition| definition.name == "b")
+        .unwrap();
+    assert_eq!(
+        analysis
+            .references_to(b)
+            .map(|reference| reference.range.start_point)
+            .collect::<Vec<_>>(),
+        [Point::new(2, 22)]
+    );
+    let scope = &analysis.scopes[analysis.definitions[b].scope];
+    assert!(scope.parent.is_some());
+    assert_eq!(scope.layer.language_name, "javascript");
+}
+
+#[test]
+fn test_analyze_locals_with_scopes_that_dont_inherit() {
+    let source = indoc! {"
+        const x = 1;
+        class A { m() { return x; } }
+        function f() { const x = x; return x; }
+    "};
+    let locals_query = indoc! {"
+        (function_declaration) @local.scope
+        ((class_body) @local.scope
+         (#set! local.scope-inherits false))
+        (variable_declarator
+          name: (identifier) @local.definition
+          value: (_) @local.definition-value)
+        (identifier) @local.reference
+    "};
+    let language = get_language("javascript");
+    let config = HighlightConfiguration::new(
+        language.clone(),
+        "javascript",
+        "(identifier) @variable",
+        "",
+        locals_query,
+    )
+    .unwrap();
+    let mut parser = Parser::new();
+    parser.set_language(&language).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let analysis = analyze_locals(&config, &tree, source.as_bytes());
+
+    assert_eq!(
+        resolved_references(&analysis),
+        [
+            ("A", Point::new(1, 6), None),
+            // The class body doesn't inherit the definitions around it.
+            ("x", Point::new(1, 23), None),
+            ("f", Point::new(2, 9), None),
+            // A definition's value doesn't refer to the definition itself.
+            ("x", Point::new(2, 25), Some(Point::new(0, 6))),
+            ("x", Point::new(2, 35), Some(Point::new(2, 21))),
+        ]
+    );
+    assert_eq!(
+        analysis
+            .scopes
+            .iter()
+            .map(|scope| (scope.range.start_point, scope.inherits, scope.parent))
+            .collect::<Vec<_>>(),
+        [
+            (Point::new(0, 0), false, None),
+            (Point::new(1, 8), false, Some(0)),
+            (Point::new(2, 0), true, Some(0)),
+        ]
+    );
+}
+
+#[test]
+fn test_analyze_locals_in_injections() {
+    let source = "<script>const a = 1; a;</script>\n<script>a;</script>\n";
+    let mut highlighter = Highlighter::new();
+    let session = HighlightSession::new(
+        &mut highlighter,
+        &HTML_HIGHLIGHT,
+        source,
+        None,
+        &test_language_for_injection_string,
+    )
+    .unwrap();
+    let analysis = session.analyze_locals();
+
+    // Each injected script is analyzed as a separate layer.
+    assert_eq!(
+        resolved_references(&analysis),
+        [
+            ("a", Point::new(0, 21), Some(Point::new(0, 14))),
+            ("a", Point::new(1, 8), None),
+        ]
+    );
+    let layers = analysis
+        .scopes
+        .iter()
+        .filter(|scope| scope.parent.is_none())
+        .map(|scope| (scope.layer.language_name, scope.layer.depth))
+        .collect::<Vec<_>>();
+    assert_eq!(layers, [("html", 0), ("javascript", 1), ("javascript", 1)]);
+}
+
 #[test]
 fn test_highlighting_via_c_api() {
     let highlights = [
@@ -682,6 +1246,288 @@ fn test_highlighting_with_all_captures_applied() {
     );
 }
 
+#[test]
+fn test_highlighting_with_added_highlight_names() {
+    let source = "let a = new B();";
+    let language = get_language("javascript");
+    let highlights_query = indoc::indoc! {"
+        \"let\" @keyword
+        \"new\" @keyword.operator.new
+        (identifier) @variable
+    "};
+    let mut config =
+        HighlightConfiguration::new(language, "javascript", highlights_query, "", "").unwrap();
+    config.configure(&["keyword", "variable"]);
+
+    let new_index = config
+        .query
+        .capture_index_for_name("keyword.operator.new")
+        .unwrap() as usize;
+    assert_eq!(config.highlight_for_capture(new_index), Some(Highlight(0)));
+
+    // Adding a more specific name changes the highlight of the captures that it matches.
+    let highlight = config.add_highlight_name("keyword.operator");
+    assert_eq!(highlight, Highlight(2));
+    assert_eq!(
+        config.highlight_names(),
+        ["keyword", "variable", "keyword.operator"]
+    );
+    assert_eq!(config.highlight_for_capture(new_index), Some(highlight));
+    let keyword_index = config.query.capture_index_for_name("keyword").unwrap() as usize;
+    assert_eq!(
+        config.highlight_for_capture(keyword_index),
+        Some(Highlight(0))
+    );
+
+    // Adding a less specific name, or one that is already recognized, doesn't.
+    assert_eq!(config.add_highlight_name("operator"), Highlight(3));
+    assert_eq!(config.highlight_for_capture(new_index), Some(highlight));
+    assert_eq!(config.add_highlight_name("keyword.operator"), highlight);
+    assert_eq!(config.highlight_names().len(), 4);
+
+    let events = Highlighter::new()
+        .highlight(&config, source.as_bytes(), None, |_| None)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let new_start = source.find("new").unwrap();
+    assert!(events.windows(2).any(|events| events
+        == [
+            HighlightEvent::HighlightStart(highlight),
+            HighlightEvent::Source {
+                start: new_start,
+                end: new_start + 3,
+            },
+        ]));
+
+    // The captures that the theme doesn't recognize are reported along with the patterns that
+    // use them, and the recognized name that they fall back to.
+    let theme_names = ["keyword", "variable"].into_iter().collect::<HashSet<_>>();
+    assert_eq!(
+        config.nonconformant_captures(&theme_names),
+        [NonconformantCapture {
+            name: "keyword.operator.new",
+            capture_index: new_index as u32,
+            pattern_indices: vec![1],
+            fallback: Some("keyword"),
+        }],
+    );
+    assert_eq!(
+        config.nonconformant_capture_names(&theme_names),
+        ["keyword.operator.new"],
+    );
+}
+
+#[test]
+fn test_highlighting_point_range() {
+    // The script starts before the range, and the parameter is defined before the range.
+    let sources = [
+        (
+            &*HTML_HIGHLIGHT,
+            indoc! {"
+                <div>
+                  <script>
+                    const a = 1;
+                    const f = function(b) { return a + b; };
+                  </script>
+                </div>
+            "},
+        ),
+        (
+            &*JS_HIGHLIGHT,
+            indoc! {"
+                function f(param) {
+                  const x = 1;
+                  return param + x;
+                }
+            "},
+        ),
+    ];
+    for (config, source) in sources {
+        let lines = to_token_vector(source, config).unwrap();
+        for rows in [0..1, 1..3, 2..4, 3..10] {
+            let mut highlighter = Highlighter::new();
+            let events = highlighter
+                .highlight_point_range(
+                    config,
+                    source.as_bytes(),
+                    Point::new(rows.start, 0)..Point::new(rows.end, 0),
+                    None,
+                    &test_language_for_injection_string,
+                )
+                .unwrap();
+            let range_lines = tokens_for_events(source, events).unwrap();
+            let end = rows.end.min(lines.len());
+            assert_eq!(range_lines, lines[rows.start..end], "rows {rows:?}");
+        }
+    }
+}
+
+#[test]
+fn test_semantic_tokens_for_multiline_strings_and_multibyte_characters() {
+    let source = "let s = `\u{fc}\u{1f600}\r\nb`; // \u{e9}\u{1f600}x\n";
+    let highlight = |name: &str| Highlight(HIGHLIGHT_NAMES.iter().position(|n| n == name).unwrap());
+    let events = [
+        HighlightEvent::HighlightStart(highlight("keyword")),
+        HighlightEvent::Source { start: 0, end: 3 },
+        HighlightEvent::HighlightEnd,
+        HighlightEvent::Source { start: 3, end: 4 },
+        HighlightEvent::HighlightStart(highlight("variable.builtin")),
+        HighlightEvent::Source { start: 4, end: 5 },
+        HighlightEvent::HighlightEnd,
+        HighlightEvent::Source { start: 5, end: 8 },
+        // The embedded highlight isn't a token type, so its text is part of the string's token.
+        HighlightEvent::HighlightStart(highlight("string")),
+        HighlightEvent::Source { start: 8, end: 9 },
+        HighlightEvent::HighlightStart(highlight("embedded")),
+        HighlightEvent::Source { start: 9, end: 11 },
+        HighlightEvent::HighlightEnd,
+        HighlightEvent::Source { start: 11, end: 19 },
+        HighlightEvent::HighlightEnd,
+        HighlightEvent::Source { start: 19, end: 21 },
+        HighlightEvent::HighlightStart(highlight("comment")),
+        HighlightEvent::Source { start: 21, end: 31 },
+        HighlightEvent::HighlightEnd,
+        HighlightEvent::Source { start: 31, end: 32 },
+    ];
+    let token_types = ["comment", "keyword", "string", "variable"];
+    let legend = SemanticTokensLegend::new(&HIGHLIGHT_NAMES, &token_types, &["builtin"]);
+
+    let data =
+        encode_semantic_tokens(events.iter().copied().map(Ok), source.as_bytes(), &legend).unwrap();
+    #[rustfmt::skip]
+    assert_eq!(
+        data,
+        [
+            0, 0, 3, 1, 0,
+            0, 4, 1, 3, 1,
+            0, 4, 4, 2, 0,
+            1, 0, 2, 2, 0,
+            0, 4, 7, 0, 0,
+        ]
+    );
+    assert_eq!(
+        decode_semantic_tokens(&data, source, &token_types),
+        [
+            (0, "let".to_string(), "keyword", 0),
+            (0, "s".to_string(), "variable", 1),
+            (0, "`\u{fc}\u{1f600}".to_string(), "string", 0),
+            (1, "b`".to_string(), "string", 0),
+            (1, "// \u{e9}\u{1f600}x".to_string(), "comment", 0),
+        ]
+    );
+
+    // The lengths depend on the position encoding.
+    let token_lengths = |encoding| {
+        let legend = legend.clone().with_position_encoding(encoding);
+        encode_semantic_tokens(events.iter().copied().map(Ok), source.as_bytes(), &legend)
+            .unwrap()
+            .chunks(5)
+            .map(|token| token[2])
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(token_lengths(PositionEncoding::Utf8), [3, 1, 7, 2, 10]);
+    assert_eq!(token_lengths(PositionEncoding::Utf32), [3, 1, 3, 2, 6]);
+}
+
+#[test]
+fn test_semantic_tokens_for_javascript() {
+    let source = indoc! {"
+        const s = `\u{fc}
+        \u{1f600} ${x}`; // \u{e9}\u{1f600}
+        function f(a) { return a; }
+    "};
+    let token_types = ["comment", "function", "keyword", "string", "variable"];
+    let legend = SemanticTokensLegend::new(&HIGHLIGHT_NAMES, &token_types, &["builtin"]);
+    let mut highlighter = Highlighter::new();
+    let events = highlighter
+        .highlight(
+            &JS_HIGHLIGHT,
+            source.as_bytes(),
+            None,
+            &test_language_for_injection_string,
+        )
+        .unwrap();
+    let data = encode_semantic_tokens(events, source.as_bytes(), &legend).unwrap();
+
+    // Each token is the text of the adjacent highlighted pieces of a line that have the same
+    // innermost highlight with a token type.
+    let mut expected = Vec::<(usize, String, &str, u32)>::new();
+    for (row, line) in to_token_vector(source, &JS_HIGHLIGHT)
+        .unwrap()
+        .into_iter()
+        .enumerate()
+    {
+        let mut previous = None;
+        for (text, highlights) in line {
+            let token = highlights.iter().rev().find_map(|name| {
+                let parts = name.split('.').collect::<Vec<_>>();
+                let token_type = token_types.iter().rev().find(|t| parts.contains(t))?;
+                Some((*token_type, u32::from(parts.contains(&"builtin"))))
+            });
+            match (token, expected.last_mut()) {
+                (Some(token), Some(last)) if previous == Some(token) => last.1 += text,
+                (Some((token_type, modifiers)), _) => {
+                    expected.push((row, text.to_string(), token_type, modifiers));
+                }
+                _ => {}
+            }
+            previous = token;
+        }
+    }
+    assert!(expected.iter().any(|token| token.2 == "string"));
+    assert_eq!(
+        decode_semantic_tokens(&data, source, &token_types),
+        expected
+    );
+}
+
+#[test]
+fn test_semantic_tokens_edits() {
+    let token = |line| [line, 0, 1, 0, 0];
+    let tokens = |lines: &[u32]| {
+        lines
+            .iter()
+            .flat_map(|line| token(*line))
+            .collect::<Vec<_>>()
+    };
+    let apply = |previous: &[u32], edits: Vec<SemanticTokensEdit>| {
+        let mut result = previous.to_vec();
+        for edit in edits.into_iter().rev() {
+            let start = edit.start as usize;
+            result.splice(start..start + edit.delete_count as usize, edit.data);
+        }
+        result
+    };
+
+    let previous = tokens(&[0, 1, 2, 3]);
+    assert_eq!(semantic_tokens_edits(&previous, &previous), []);
+
+    for current in [
+        tokens(&[0, 1, 5, 3]),
+        tokens(&[0, 1, 2, 3, 4]),
+        tokens(&[1, 2, 3]),
+        tokens(&[0, 1]),
+        tokens(&[0, 1, 1, 2, 3]),
+        tokens(&[]),
+    ] {
+        let edits = semantic_tokens_edits(&previous, &current);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].start % 5, 0);
+        assert_eq!(apply(&previous, edits), current);
+    }
+
+    // Only the tokens between the common prefix and suffix are replaced.
+    assert_eq!(
+        semantic_tokens_edits(&previous, &tokens(&[0, 1, 5, 3])),
+        [SemanticTokensEdit {
+            start: 10,
+            delete_count: 5,
+            data: token(5).to_vec(),
+        }]
+    );
+}
+
 #[test]
 fn test_decode_utf8_lossy() {
     use tree_sitter::LossyUtf8;
@@ -703,6 +1549,87 @@ fn c_string(s: &str) -> CString {
     CString::new(s.as_bytes().to_vec()).unwrap()
 }
 
+fn assert_session_highlights(
+    highlighter: &mut Highlighter,
+    session: &HighlightSession,
+    config: &'static HighlightConfiguration,
+    ranges: &[std::ops::Range<usize>],
+) {
+    let source = session.source().to_vec();
+    for range in ranges {
+        let expected_events = Highlighter::new()
+            .highlight_byte_range(
+                config,
+                &source,
+                range.clone(),
+                None,
+                &test_language_for_injection_string,
+            )
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let events = session
+            .highlight(
+                highlighter,
+                range.clone(),
+                None,
+                &test_language_for_injection_string,
+            )
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(events, expected_events, "{range:?}");
+    }
+}
+
+/// Decode semantic tokens into the line, text, token type and modifiers of each token, using
+/// UTF-16 to find the text of their positions.
+fn decode_semantic_tokens(
+    data: &[u32],
+    source: &str,
+    token_types: &[&'static str],
+) -> Vec<(usize, String, &'static str, u32)> {
+    let lines = source
+        .split('\n')
+        .map(|line| line.encode_utf16().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut line = 0;
+    let mut start = 0;
+    data.chunks(5)
+        .map(|token| {
+            if token[0] > 0 {
+                line += token[0] as usize;
+                start = token[1] as usize;
+            } else {
+                start += token[1] as usize;
+            }
+            let text = &lines[line][start..start + token[2] as usize];
+            (
+                line,
+                String::from_utf16(text).unwrap(),
+                token_types[token[3] as usize],
+                token[4],
+            )
+        })
+        .collect()
+}
+
+/// Get the name and position of each reference, and the position of its definition.
+fn resolved_references<'a>(
+    analysis: &LocalScopeAnalysis<'a>,
+) -> Vec<(&'a str, Point, Option<Point>)> {
+    analysis
+        .references
+        .iter()
+        .map(|reference| {
+            let definition = reference
+                .definition
+                .map(|definition| analysis.definitions[definition].range.start_point);
+            (reference.name, reference.range.start_point, definition)
+        })
+        .collect()
+}
+
 fn test_language_for_injection_string<'a>(string: &str) -> Option<&'a HighlightConfiguration> {
     match string {
         "javascript" => Some(&JS_HIGHLIGHT),
@@ -749,17 +1676,25 @@ fn to_token_vector<'a>(
     src: &'a str,
     language_config: &'a HighlightConfiguration,
 ) -> Result<Vec<Vec<(&'a str, Vec<&'static str>)>>, Error> {
-    let src = src.as_bytes();
     let mut highlighter = Highlighter::new();
-    let mut lines = Vec::new();
-    let mut highlights = Vec::new();
-    let mut line = Vec::new();
     let events = highlighter.highlight(
         language_config,
-        src,
+        src.as_bytes(),
         None,
         &test_language_for_injection_string,
     )?;
+    tokens_for_events(src, events)
+}
+
+#[allow(clippy::type_complexity)]
+fn tokens_for_events(
+    src: &str,
+    events: impl Iterator<Item = Result<HighlightEvent, Error>>,
+) -> Result<Vec<Vec<(&str, Vec<&'static str>)>>, Error> {
+    let src = src.as_bytes();
+    let mut lines = Vec::new();
+    let mut highlights = Vec::new();
+    let mut line = Vec::new();
     for event in events {
         match event? {
             HighlightEvent::HighlightStart(s) => highlights.push(HIGHLIGHT_NAMES[s.0].as_str()),
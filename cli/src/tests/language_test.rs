@@ -1,6 +1,9 @@
 use tree_sitter::{self, Parser};
 
-use super::helpers::fixtures::get_language;
+use super::{
+    generate_parser,
+    helpers::fixtures::{get_language, get_test_language},
+};
 
 #[test]
 fn test_lookahead_iterator() {
@@ -196,3 +199,249 @@ fn test_supertypes() {
         }
     }
 }
+
+#[test]
+fn test_language_metadata() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"
+        {
+            "name": "test_language_metadata",
+            "rules": {
+                "list": {
+                    "type": "REPEAT1",
+                    "content": {"type": "STRING", "value": "a"}
+                }
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    let language = get_test_language(&parser_name, &parser_code, None);
+
+    assert_eq!(language.name(), Some("test_language_metadata"));
+    assert_eq!(language.semantic_version(), Some((0, 0, 0)));
+    assert_eq!(language.external_token_count(), 0);
+    assert!(language.parse_state_count() > 1);
+}
+
+#[test]
+fn test_language_metadata_with_external_scanner() {
+    let language = get_language("python");
+    if language.abi_version() < 15 {
+        assert_eq!(language.name(), None);
+        assert_eq!(language.semantic_version(), None);
+    } else {
+        assert_eq!(language.name(), Some("python"));
+    }
+    assert!(language.external_token_count() > 0);
+}
+
+#[test]
+fn test_node_kind_is_subtype_of() {
+    let language = get_language("rust");
+    if language.abi_version() < 15 {
+        return;
+    }
+
+    let kind_id = |kind: &str, named: bool| language.id_for_node_kind(kind, named);
+    let pattern = kind_id("_pattern", true);
+    let literal_pattern = kind_id("_literal_pattern", true);
+    let expression = kind_id("_expression", true);
+
+    assert!(language.node_kind_is_subtype_of(literal_pattern, pattern));
+    assert!(language.node_kind_is_subtype_of(kind_id("tuple_pattern", true), pattern));
+    assert!(language.node_kind_is_subtype_of(kind_id("if_expression", true), expression));
+    assert!(!language.node_kind_is_subtype_of(kind_id("if_expression", true), pattern));
+    assert!(!language.node_kind_is_subtype_of(pattern, pattern));
+
+    // `_literal_pattern`'s subtypes are only subtypes of `_pattern` through it
+    for &subtype in language.subtypes_for_supertype(literal_pattern) {
+        assert!(language.node_kind_is_subtype_of(subtype, pattern));
+    }
+}
+
+#[test]
+fn test_node_kind_is_subtype_of_nested_supertypes() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"
+        {
+            "name": "test_nested_supertypes",
+            "supertypes": ["_statement", "_expression", "_literal"],
+            "extras": [{"type": "PATTERN", "value": "\\s"}],
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "_statement"}},
+                "_statement": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "SYMBOL", "name": "expression_statement"},
+                        {"type": "SYMBOL", "name": "empty_statement"}
+                    ]
+                },
+                "expression_statement": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "SYMBOL", "name": "_expression"},
+                        {"type": "STRING", "value": ";"}
+                    ]
+                },
+                "empty_statement": {"type": "STRING", "value": ";"},
+                "_expression": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "SYMBOL", "name": "_literal"}
+                    ]
+                },
+                "_literal": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "SYMBOL", "name": "number"},
+                        {"type": "SYMBOL", "name": "string"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+                "number": {"type": "PATTERN", "value": "\\d+"},
+                "string": {"type": "PATTERN", "value": "\"[a-z]*\""}
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    let language = get_test_language(&parser_name, &parser_code, None);
+    let kind_id = |kind: &str| language.id_for_node_kind(kind, true);
+
+    let expression = kind_id("_expression");
+    let literal = kind_id("_literal");
+    assert_eq!(
+        language
+            .subtypes_for_supertype(expression)
+            .iter()
+            .filter_map(|&id| language.node_kind_for_id(id))
+            .collect::<Vec<_>>(),
+        ["_literal", "identifier"],
+    );
+
+    for (kind, supertype, expected) in [
+        ("number", "_literal", true),
+        ("number", "_expression", true),
+        ("_literal", "_expression", true),
+        ("_expression", "_statement", false),
+        ("number", "_statement", false),
+        ("identifier", "_literal", false),
+        ("empty_statement", "_statement", true),
+        ("_literal", "_literal", false),
+        ("expression_statement", "_expression", false),
+    ] {
+        assert_eq!(
+            language.node_kind_is_subtype_of(kind_id(kind), kind_id(supertype)),
+            expected,
+            "{kind} is a subtype of {supertype}",
+        );
+    }
+    assert!(language.node_kind_is_subtype_of(kind_id("string"), expression));
+    assert!(!language.node_kind_is_subtype_of(expression, literal));
+}
+
+#[test]
+fn test_language_keywords() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"
+        {
+            "name": "test_language_keywords",
+            "word": "identifier",
+            "extras": [{"type": "PATTERN", "value": "\\s"}],
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "_statement"}},
+                "_statement": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "SYMBOL", "name": "if_statement"},
+                        {"type": "SYMBOL", "name": "while_statement"},
+                        {"type": "SYMBOL", "name": "expression_statement"}
+                    ]
+                },
+                "if_statement": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "STRING", "value": "if"},
+                        {"type": "SYMBOL", "name": "parenthesized_expression"},
+                        {"type": "SYMBOL", "name": "_statement"}
+                    ]
+                },
+                "while_statement": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "STRING", "value": "while"},
+                        {"type": "SYMBOL", "name": "parenthesized_expression"},
+                        {"type": "SYMBOL", "name": "_statement"}
+                    ]
+                },
+                "parenthesized_expression": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "STRING", "value": "("},
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "STRING", "value": ")"}
+                    ]
+                },
+                "expression_statement": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "STRING", "value": ";"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    let language = get_test_language(&parser_name, &parser_code, None);
+
+    let identifier = language.id_for_node_kind("identifier", true);
+    assert_eq!(language.word_token(), Some(identifier));
+    assert!(!language.is_keyword(identifier));
+
+    for (kind, expected) in [("if", true), ("while", true), ("(", false), (";", false)] {
+        let id = language.id_for_node_kind(kind, false);
+        assert_ne!(id, 0, "{kind} should be a node kind");
+        assert_eq!(language.is_keyword(id), expected, "{kind}");
+    }
+
+    // Named nodes, and symbols that don't exist, are never keywords.
+    assert!(!language.is_keyword(language.id_for_node_kind("program", true)));
+    assert!(!language.is_keyword(u16::MAX));
+
+    let mut parser = Parser::new();
+    parser.set_language(&language).unwrap();
+    let tree = parser.parse("while (x) if (y) z;", None).unwrap();
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        "(program (while_statement (parenthesized_expression (identifier)) \
+         (if_statement (parenthesized_expression (identifier)) \
+         (expression_statement (identifier)))))"
+    );
+}
+
+#[test]
+fn test_language_keywords_without_word_token() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"
+        {
+            "name": "test_language_keywords_without_word_token",
+            "rules": {
+                "list": {
+                    "type": "REPEAT1",
+                    "content": {"type": "STRING", "value": "a"}
+                }
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    let language = get_test_language(&parser_name, &parser_code, None);
+
+    assert_eq!(language.word_token(), None);
+    assert!(!language.is_keyword(language.id_for_node_kind("a", false)));
+}
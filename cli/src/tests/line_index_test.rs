@@ -0,0 +1,141 @@
+use tree_sitter::{edits_between, LineIndex, Parser, Point};
+
+use super::helpers::fixtures::get_language;
+
+#[test]
+fn test_line_index_byte_and_point_conversions() {
+    // CRLF and LF line endings, a multi-byte character, and a final row without a newline
+    let text = "ab\r\ncd\n\u{e9}f";
+    let index = LineIndex::new(text);
+    assert_eq!(index.row_count(), 3);
+
+    let points = [
+        Point::new(0, 0),
+        Point::new(0, 1),
+        Point::new(0, 2),
+        Point::new(0, 3),
+        Point::new(1, 0),
+        Point::new(1, 1),
+        Point::new(1, 2),
+        Point::new(2, 0),
+        Point::new(2, 1),
+        Point::new(2, 2),
+        Point::new(2, 3),
+    ];
+    for (byte, point) in points.into_iter().enumerate() {
+        assert_eq!(index.byte_to_point(byte), point, "byte {byte}");
+        assert_eq!(index.point_to_byte(point), byte, "point {point}");
+    }
+
+    // Positions past the end of the text, or past the end of a row, are clamped
+    assert_eq!(index.byte_to_point(100), Point::new(2, 3));
+    assert_eq!(index.point_to_byte(Point::new(5, 0)), text.len());
+    assert_eq!(index.point_to_byte(Point::new(0, 10)), 3);
+    assert_eq!(index.point_to_byte(Point::new(2, 10)), text.len());
+}
+
+#[test]
+fn test_line_index_utf16_conversions() {
+    let text = "a\u{1f600}b\r\n\u{e9}\n";
+    let index = LineIndex::new(text);
+
+    let points = [
+        (0, Point::new(0, 0)),
+        (1, Point::new(0, 1)),
+        // Within a character
+        (3, Point::new(0, 1)),
+        (5, Point::new(0, 3)),
+        (6, Point::new(0, 4)),
+        // Within the line ending
+        (7, Point::new(0, 4)),
+        (8, Point::new(1, 0)),
+        (10, Point::new(1, 1)),
+        (11, Point::new(2, 0)),
+        // Past the end of the text
+        (20, Point::new(2, 0)),
+    ];
+    for (byte, point) in points {
+        assert_eq!(index.byte_to_utf16_cu(byte), point, "byte {byte}");
+    }
+
+    let bytes = [
+        (Point::new(0, 0), 0),
+        (Point::new(0, 1), 1),
+        // Within a surrogate pair
+        (Point::new(0, 2), 1),
+        (Point::new(0, 3), 5),
+        (Point::new(0, 4), 6),
+        // Past the end of the row, or of the text
+        (Point::new(0, 10), 6),
+        (Point::new(1, 1), 10),
+        (Point::new(2, 5), 11),
+        (Point::new(3, 0), 11),
+    ];
+    for (point, byte) in bytes {
+        assert_eq!(index.utf16_cu_to_byte(point), byte, "point {point}");
+    }
+
+    assert_eq!(index.utf16_range(1..10), Point::new(0, 1)..Point::new(1, 1));
+}
+
+#[test]
+fn test_line_index_edit() {
+    let texts = [
+        "a\nb\nc\n",
+        "a\nB\r\nc\nd",
+        "a\nc\nd",
+        "",
+        "x\ny\n\u{e9}\n",
+        "\n\n\u{e8}x\n\n",
+        "one\r\ntwo\r\nthree",
+    ];
+
+    let mut index = LineIndex::new(texts[0]);
+    for window in texts.windows(2) {
+        let (old_text, new_text) = (window[0], window[1]);
+        let edits = edits_between(old_text, new_text);
+
+        // The edits' positions are relative to the old text, so the inserted text for each one
+        // is found by shifting them by the edits that come before it.
+        let mut offset = 0isize;
+        let mut inserted_texts = Vec::new();
+        for edit in &edits {
+            let start = edit.start_byte.checked_add_signed(offset).unwrap();
+            let end = edit.new_end_byte.checked_add_signed(offset).unwrap();
+            inserted_texts.push(&new_text[start..end]);
+            offset += edit.new_end_byte as isize - edit.old_end_byte as isize;
+        }
+        for (edit, inserted_text) in edits.iter().zip(inserted_texts).rev() {
+            index.edit(edit, inserted_text);
+        }
+
+        assert_eq!(
+            index,
+            LineIndex::new(new_text),
+            "{old_text:?} -> {new_text:?}"
+        );
+    }
+}
+
+#[test]
+fn test_node_lsp_range() {
+    let text = "let s = \"\u{1f600}\";\r\nlet t = '\u{e9}';";
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("javascript")).unwrap();
+    let tree = parser.parse(text, None).unwrap();
+    let index = LineIndex::new(text);
+
+    let root = tree.root_node();
+    let strings = [
+        root.named_descendant_for_byte_range(8, 8).unwrap(),
+        root.named_descendant_for_byte_range(25, 25).unwrap(),
+    ];
+    assert_eq!(strings.map(|node| node.kind()), ["string", "string"]);
+    assert_eq!(
+        strings.map(|node| node.lsp_range(&index)),
+        [
+            Point::new(0, 8)..Point::new(0, 12),
+            Point::new(1, 8)..Point::new(1, 11),
+        ]
+    );
+}
@@ -4,6 +4,7 @@ mod detect_language;
 mod helpers;
 mod highlight_test;
 mod language_test;
+mod line_index_test;
 mod node_test;
 mod parser_hang_test;
 mod parser_test;
@@ -1,4 +1,6 @@
-use tree_sitter::{Node, Parser, Point, Tree};
+use std::ops::Range;
+
+use tree_sitter::{Node, Parser, Point, Tree, TreeCursor};
 use tree_sitter_generate::load_grammar_file;
 
 use super::{
@@ -1027,6 +1029,441 @@ fn test_node_field_calls_in_language_without_fields() {
     assert_eq!(cursor.field_name(), None);
 }
 
+#[test]
+fn test_node_children_with_fields() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"
+        {
+            "name": "test_grammar_with_optional_and_repeated_fields",
+            "extras": [
+                {"type": "PATTERN", "value": "\\s+"}
+            ],
+            "rules": {
+                "program": {
+                    "type": "REPEAT",
+                    "content": {"type": "SYMBOL", "name": "call"}
+                },
+                "call": {
+                    "type": "SEQ",
+                    "members": [
+                        {
+                            "type": "FIELD",
+                            "name": "function",
+                            "content": {"type": "SYMBOL", "name": "identifier"}
+                        },
+                        {"type": "STRING", "value": "("},
+                        {
+                            "type": "CHOICE",
+                            "members": [
+                                {
+                                    "type": "SEQ",
+                                    "members": [
+                                        {
+                                            "type": "FIELD",
+                                            "name": "argument",
+                                            "content": {"type": "SYMBOL", "name": "identifier"}
+                                        },
+                                        {
+                                            "type": "REPEAT",
+                                            "content": {
+                                                "type": "SEQ",
+                                                "members": [
+                                                    {"type": "STRING", "value": ","},
+                                                    {
+                                                        "type": "FIELD",
+                                                        "name": "argument",
+                                                        "content": {"type": "SYMBOL", "name": "identifier"}
+                                                    }
+                                                ]
+                                            }
+                                        }
+                                    ]
+                                },
+                                {"type": "BLANK"}
+                            ]
+                        },
+                        {"type": "STRING", "value": ")"},
+                        {
+                            "type": "CHOICE",
+                            "members": [
+                                {
+                                    "type": "FIELD",
+                                    "name": "block",
+                                    "content": {"type": "SYMBOL", "name": "block"}
+                                },
+                                {"type": "BLANK"}
+                            ]
+                        }
+                    ]
+                },
+                "block": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "STRING", "value": "{"},
+                        {"type": "STRING", "value": "}"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }
+    "#,
+    )
+    .unwrap();
+
+    let mut parser = Parser::new();
+    let language = get_test_language(&parser_name, &parser_code, None);
+    parser.set_language(&language).unwrap();
+
+    let source = "f(a, b, c) g() {}";
+    let tree = parser.parse(source, None).unwrap();
+    let root_node = tree.root_node();
+    let call1 = root_node.child(0).unwrap();
+    let call2 = root_node.child(1).unwrap();
+
+    let mut cursor = root_node.walk();
+    let children = call1.children_with_fields(&mut cursor);
+    assert_eq!(children.len(), 8);
+    assert_eq!(
+        children
+            .map(|(field_name, child)| (field_name, child.utf8_text(source.as_bytes()).unwrap()))
+            .collect::<Vec<_>>(),
+        &[
+            (Some("function"), "f"),
+            (None, "("),
+            (Some("argument"), "a"),
+            (None, ","),
+            (Some("argument"), "b"),
+            (None, ","),
+            (Some("argument"), "c"),
+            (None, ")"),
+        ]
+    );
+
+    // Repeated fields yield each of their children in order, and missing optional fields yield
+    // nothing.
+    fn fields<'tree>(
+        node: Node<'tree>,
+        cursor: &mut TreeCursor<'tree>,
+    ) -> Vec<(&'static str, &'static str, Range<usize>)> {
+        node.fields(cursor)
+            .map(|(field_name, child)| (field_name, child.kind(), child.byte_range()))
+            .collect()
+    }
+    assert_eq!(
+        fields(call1, &mut cursor),
+        &[
+            ("function", "identifier", 0..1),
+            ("argument", "identifier", 2..3),
+            ("argument", "identifier", 5..6),
+            ("argument", "identifier", 8..9),
+        ]
+    );
+    assert_eq!(
+        fields(call2, &mut cursor),
+        &[
+            ("function", "identifier", 11..12),
+            ("block", "block", 15..17)
+        ]
+    );
+    assert_eq!(fields(root_node, &mut cursor), &[]);
+}
+
+#[test]
+fn test_node_child_by_field_path() {
+    let (parser_name, parser_code) = generate_parser(
+        r##"
+        {
+            "name": "test_grammar_with_nested_fields",
+            "extras": [
+                {"type": "PATTERN", "value": "\\s+"},
+                {"type": "SYMBOL", "name": "comment"}
+            ],
+            "rules": {
+                "program": {
+                    "type": "REPEAT",
+                    "content": {
+                        "type": "FIELD",
+                        "name": "call",
+                        "content": {"type": "SYMBOL", "name": "call"}
+                    }
+                },
+                "call": {
+                    "type": "SEQ",
+                    "members": [
+                        {
+                            "type": "FIELD",
+                            "name": "function",
+                            "content": {
+                                "type": "CHOICE",
+                                "members": [
+                                    {"type": "SYMBOL", "name": "member"},
+                                    {"type": "SYMBOL", "name": "identifier"}
+                                ]
+                            }
+                        },
+                        {
+                            "type": "FIELD",
+                            "name": "arguments",
+                            "content": {"type": "SYMBOL", "name": "arguments"}
+                        }
+                    ]
+                },
+                "member": {
+                    "type": "SEQ",
+                    "members": [
+                        {
+                            "type": "FIELD",
+                            "name": "object",
+                            "content": {"type": "SYMBOL", "name": "identifier"}
+                        },
+                        {"type": "STRING", "value": "."},
+                        {
+                            "type": "FIELD",
+                            "name": "property",
+                            "content": {"type": "SYMBOL", "name": "identifier"}
+                        }
+                    ]
+                },
+                "arguments": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "STRING", "value": "("},
+                        {
+                            "type": "REPEAT",
+                            "content": {
+                                "type": "FIELD",
+                                "name": "argument",
+                                "content": {
+                                    "type": "CHOICE",
+                                    "members": [
+                                        {"type": "SYMBOL", "name": "call"},
+                                        {"type": "SYMBOL", "name": "identifier"}
+                                    ]
+                                }
+                            }
+                        },
+                        {"type": "STRING", "value": ")"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+                "comment": {"type": "PATTERN", "value": "#[^\\n]*"}
+            }
+        }
+    "##,
+    )
+    .unwrap();
+
+    let mut parser = Parser::new();
+    let language = get_test_language(&parser_name, &parser_code, None);
+    parser.set_language(&language).unwrap();
+
+    let source = "a.b(x # comment\n y) f(g(z))";
+    let tree = parser.parse(source, None).unwrap();
+    let root_node = tree.root_node();
+    let text = |node: Node| node.utf8_text(source.as_bytes()).unwrap().to_string();
+    let call1 = root_node.child(0).unwrap();
+    let call2 = root_node.child(1).unwrap();
+
+    let child_text = |node: Node, path: &[&str]| node.child_by_field_path(path).map(text);
+    assert_eq!(child_text(call1, &["function", "object"]).unwrap(), "a");
+    assert_eq!(child_text(call1, &["function", "property"]).unwrap(), "b");
+    assert_eq!(child_text(call1, &["arguments", "argument"]).unwrap(), "x");
+    assert_eq!(
+        child_text(call2, &["arguments", "argument", "function"]).unwrap(),
+        "g"
+    );
+    assert_eq!(
+        child_text(root_node, &["call", "arguments", "argument", "function"]),
+        None
+    );
+    assert_eq!(child_text(call1, &[]).unwrap(), text(call1));
+    assert_eq!(child_text(call2, &["function", "object"]), None);
+    assert_eq!(child_text(call1, &["function", "nonexistent"]), None);
+
+    let field_id = |name: &str| language.field_id_for_name(name).unwrap();
+    assert_eq!(
+        call1
+            .child_by_field_id_path(&[field_id("function"), field_id("property")])
+            .map(text)
+            .unwrap(),
+        "b"
+    );
+    assert_eq!(
+        call2.child_by_field_id_path(&[field_id("function"), field_id("property")]),
+        None
+    );
+
+    // When intermediate fields repeat, every matching node is returned, in order.
+    let mut cursor = root_node.walk();
+    let children_text = |nodes: Vec<Node>| nodes.into_iter().map(text).collect::<Vec<_>>();
+    assert_eq!(
+        children_text(root_node.children_by_field_path(&["call", "function"], &mut cursor)),
+        &["a.b", "f"]
+    );
+    assert_eq!(
+        children_text(
+            root_node.children_by_field_path(&["call", "arguments", "argument"], &mut cursor)
+        ),
+        &["x", "y", "g(z)"]
+    );
+    assert_eq!(
+        children_text(root_node.children_by_field_id_path(
+            &[
+                field_id("call"),
+                field_id("arguments"),
+                field_id("argument"),
+                field_id("arguments"),
+                field_id("argument")
+            ],
+            &mut cursor
+        )),
+        &["z"]
+    );
+    assert_eq!(
+        root_node.children_by_field_path(&["call", "nonexistent"], &mut cursor),
+        &[]
+    );
+}
+
+#[test]
+fn test_node_expected_kinds() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"
+        {
+            "name": "test_grammar_with_calls",
+            "extras": [
+                {"type": "PATTERN", "value": "\\s+"}
+            ],
+            "rules": {
+                "program": {
+                    "type": "REPEAT",
+                    "content": {"type": "SYMBOL", "name": "statement"}
+                },
+                "statement": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "SYMBOL", "name": "_expression"},
+                        {"type": "STRING", "value": ";"}
+                    ]
+                },
+                "_expression": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "SYMBOL", "name": "number"},
+                        {"type": "SYMBOL", "name": "call"}
+                    ]
+                },
+                "call": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "STRING", "value": "("},
+                        {
+                            "type": "CHOICE",
+                            "members": [
+                                {
+                                    "type": "SEQ",
+                                    "members": [
+                                        {"type": "SYMBOL", "name": "_expression"},
+                                        {
+                                            "type": "REPEAT",
+                                            "content": {
+                                                "type": "SEQ",
+                                                "members": [
+                                                    {"type": "STRING", "value": ","},
+                                                    {"type": "SYMBOL", "name": "_expression"}
+                                                ]
+                                            }
+                                        }
+                                    ]
+                                },
+                                {"type": "BLANK"}
+                            ]
+                        },
+                        {"type": "STRING", "value": ")"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+                "number": {"type": "PATTERN", "value": "\\d+"}
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&parser_name, &parser_code, None))
+        .unwrap();
+
+    let tree = parser.parse("f(a;", None).unwrap();
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        "(program (statement (call (identifier) (identifier) (MISSING \")\"))))"
+    );
+    let call = tree.root_node().child(0).unwrap().child(0).unwrap();
+    let missing = call.child(3).unwrap();
+    assert!(missing.is_missing());
+    assert_eq!(missing.expected_kinds(), ["(", ")", ",", ";"]);
+    assert_eq!(
+        missing.expected_parse_state(),
+        Some(call.child(2).unwrap().next_parse_state())
+    );
+    assert_eq!(call.expected_parse_state(), None);
+    assert!(call.expected_kinds().is_empty());
+
+    let tree = parser.parse("f(a b);", None).unwrap();
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        "(program (statement (call (identifier) (identifier) (ERROR (identifier)))))"
+    );
+    let error = tree
+        .root_node()
+        .child(0)
+        .unwrap()
+        .child(0)
+        .unwrap()
+        .child(3)
+        .unwrap();
+    assert!(error.is_error());
+    assert_eq!(error.expected_kinds(), ["(", ")", ",", ";"]);
+
+    // An error at the start of the document expects whatever the document can start with.
+    let tree = parser.parse("); a;", None).unwrap();
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        "(program (ERROR) (statement (identifier)))"
+    );
+    let error = tree.root_node().child(0).unwrap();
+    assert_eq!(error.expected_parse_state(), Some(1));
+    let expected_kinds = error.expected_kinds();
+    for kind in ["call", "identifier", "number", "statement"] {
+        assert!(expected_kinds.contains(&kind));
+    }
+    assert!(!expected_kinds.contains(&")"));
+}
+
+#[test]
+fn test_node_expected_kinds_in_javascript() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("javascript")).unwrap();
+
+    let tree = parser.parse("const h = `i ${j(k} l`\n", None).unwrap();
+    let missing = tree.root_node().preorder().find(Node::is_missing).unwrap();
+    assert_eq!(missing.kind(), ")");
+    assert_eq!(missing.parent().unwrap().kind(), "arguments");
+    let expected_kinds = missing.expected_kinds();
+    assert!(expected_kinds.contains(&")"));
+    assert!(expected_kinds.contains(&","));
+
+    let tree = parser.parse("if (a b) {}\n", None).unwrap();
+    let error = tree.root_node().preorder().find(Node::is_error).unwrap();
+    assert_eq!(error.parent().unwrap().kind(), "parenthesized_expression");
+    let expected_kinds = error.expected_kinds();
+    assert!(expected_kinds.contains(&")"));
+    assert!(expected_kinds.contains(&"+"));
+}
+
 #[test]
 fn test_node_is_named_but_aliased_as_anonymous() {
     let grammar_json = load_grammar_file(
@@ -1165,3 +1602,42 @@ fn parse_json_example() -> Tree {
     parser.set_language(&get_language("json")).unwrap();
     parser.parse(JSON_EXAMPLE, None).unwrap()
 }
+
+#[test]
+fn test_node_to_dot() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("javascript")).unwrap();
+    let tree = parser.parse("x = \"a\"; // b", None).unwrap();
+
+    let graph = tree.to_dot();
+    assert!(graph.starts_with("digraph tree {\nedge [arrowhead=none]\n"));
+    assert!(graph.ends_with("}\n"));
+    assert!(graph.contains("node_0 [label=\"program\""));
+    assert_eq!(
+        graph
+            .lines()
+            .filter(|line| line.starts_with("node_") && !line.contains(" -> "))
+            .count(),
+        tree.root_node().descendant_count(),
+    );
+
+    // Leaves have no border, extras are gray, and names and quotes are escaped.
+    assert!(graph.contains("[label=\"comment\", shape=plaintext, fontcolor=gray, "));
+    assert!(graph.contains(
+        "[label=\"\\\"\", shape=plaintext, tooltip=\"range: 4 - 5\\nstart: (0, 4)\\nend: (0, 5)\"]"
+    ));
+
+    // Edges are labeled with field names.
+    assert!(graph.contains(" [label=\"left\"]\n"));
+    assert!(graph.contains(" [label=\"right\"]\n"));
+
+    let string_node = tree.root_node().descendant_for_byte_range(4, 7).unwrap();
+    assert_eq!(string_node.kind(), "string");
+    let graph = string_node.to_dot();
+    assert!(graph.starts_with("digraph tree {\nedge [arrowhead=none]\nnode_0 [label=\"string\", "));
+    assert!(!graph.contains("program"));
+    assert_eq!(
+        graph.matches(" -> ").count(),
+        string_node.descendant_count() - 1
+    );
+}
@@ -1,10 +1,13 @@
 use std::{
+    fs,
+    io::{self, Cursor, Read, Seek, SeekFrom},
     sync::atomic::{AtomicUsize, Ordering},
     thread, time,
 };
 
 use tree_sitter::{
-    Decode, IncludedRangesError, InputEdit, LogType, ParseOptions, ParseState, Parser, Point, Range,
+    Decode, IncludedRangesError, IncludedRangesErrorKind, InputEdit, LexFunction, LogCategory,
+    LogType, ParseOptions, ParseState, Parser, Point, Range,
 };
 use tree_sitter_generate::load_grammar_file;
 use tree_sitter_proc_macro::retry;
@@ -16,7 +19,10 @@ use super::helpers::{
 };
 use crate::{
     fuzz::edits::Edit,
-    parse::perform_edit,
+    parse::{
+        parse_file_at_path, parse_files_at_paths, perform_edit, Encoding, ParseDebugType,
+        ParseFileOptions, ParseOutput, ParseTheme, RangeFormat,
+    },
     tests::{generate_parser, helpers::fixtures::fixtures_dir, invert_edit},
 };
 
@@ -87,6 +93,130 @@ fn test_parsing_with_logging() {
     assert!(row_starts_from_0);
 }
 
+#[test]
+fn test_parsing_with_structured_logging() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("rust")).unwrap();
+
+    let mut messages = Vec::new();
+    parser.set_structured_logger(Some(Box::new(|message| {
+        messages.push((
+            message.category(),
+            message.name().to_string(),
+            message.symbol().map(str::to_string),
+            message.state(),
+            message.lex_function(),
+            message.lex_state(),
+            message.byte_offset(),
+        ));
+    })));
+
+    let source = "struct Stuff {}\nfn main() {}\n";
+    parser.parse(source, None).unwrap();
+    parser.set_structured_logger(None);
+
+    let reduction = messages
+        .iter()
+        .find(|message| message.1 == "reduce" && message.2.as_deref() == Some("struct_item"))
+        .unwrap();
+    assert_eq!(reduction.0, LogCategory::Reduce);
+    assert_eq!(reduction.4, None);
+    assert!(reduction
+        .6
+        .is_some_and(|offset| offset >= "struct Stuff {}".len()));
+
+    let shift = messages
+        .iter()
+        .find(|message| message.1 == "shift")
+        .unwrap();
+    assert_eq!(shift.0, LogCategory::Shift);
+    assert!(shift.3.is_some());
+
+    // Lex messages carry the lexing function and lex state that they were logged in
+    let skip = messages.iter().find(|message| message.1 == "skip").unwrap();
+    assert_eq!(skip.0, LogCategory::Lex);
+    assert_eq!(skip.4, Some(LexFunction::Main));
+    assert!(skip.5.is_some());
+
+    let keyword_lex = messages
+        .iter()
+        .find(|message| message.1 == "lex_keyword")
+        .unwrap();
+    assert_eq!(keyword_lex.0, LogCategory::Lookahead);
+    assert_eq!(keyword_lex.4, Some(LexFunction::Keyword));
+    assert_eq!(keyword_lex.5, keyword_lex.3);
+    assert!(messages.iter().any(|message| {
+        message.0 == LogCategory::Lex && message.4 == Some(LexFunction::Keyword)
+    }));
+}
+
+#[test]
+fn test_parsing_with_log_filter() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("rust")).unwrap();
+
+    let mut parse_with_filter = |source: &str, filter: Option<&[LogCategory]>| {
+        let mut messages = Vec::new();
+        parser.set_log_filter(filter);
+        parser.set_logger(Some(Box::new(|log_type, message| {
+            messages.push((log_type, message.to_string()));
+        })));
+        parser.parse(source, None).unwrap();
+        parser.set_logger(None);
+        messages
+    };
+
+    let messages = parse_with_filter("struct Stuff {}", Some(&[LogCategory::Reduce]));
+    assert!(!messages.is_empty());
+    assert!(messages
+        .iter()
+        .all(|(log_type, message)| *log_type == LogType::Parse && message.starts_with("reduce ")));
+
+    let messages = parse_with_filter("struct Stuff {", Some(&[LogCategory::ErrorRecovery]));
+    assert!(messages
+        .iter()
+        .any(|(_, message)| message == "detect_error"));
+    assert!(messages
+        .iter()
+        .all(|(_, message)| !message.starts_with("shift") && !message.starts_with("reduce")));
+
+    let messages = parse_with_filter("struct Stuff {}", None);
+    assert!(messages
+        .iter()
+        .any(|(_, message)| message.starts_with("shift")));
+    assert!(messages
+        .iter()
+        .any(|(log_type, _)| *log_type == LogType::Lex));
+}
+
+#[test]
+fn test_parsing_with_external_scanner_logging() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("python")).unwrap();
+
+    let mut messages = Vec::new();
+    parser.set_log_filter(Some(&[LogCategory::ExternalScanner]));
+    parser.set_structured_logger(Some(Box::new(|message| {
+        messages.push((
+            message.log_type(),
+            message.name().to_string(),
+            message.lex_function(),
+        ));
+    })));
+    parser.parse("if a:\n    b\n", None).unwrap();
+    parser.set_structured_logger(None);
+
+    assert!(messages
+        .iter()
+        .any(|(log_type, name, _)| *log_type == LogType::Parse && name == "lex_external"));
+    assert!(messages
+        .iter()
+        .any(|(log_type, _, _)| *log_type == LogType::Lex));
+    assert!(messages
+        .iter()
+        .all(|(_, _, lex_function)| *lex_function == Some(LexFunction::ExternalScanner)));
+}
+
 #[test]
 #[cfg(unix)]
 fn test_parsing_with_debug_graph_enabled() {
@@ -113,6 +243,57 @@ fn test_parsing_with_debug_graph_enabled() {
     }
 }
 
+#[test]
+fn test_parsing_with_debug_graph_writer() {
+    struct GraphWriter<'a>(&'a mut Vec<String>);
+
+    impl io::Write for GraphWriter<'_> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(String::from_utf8(buf.to_vec()).unwrap());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("javascript")).unwrap();
+
+    let mut graphs = Vec::new();
+    parser.print_dot_graphs_to(GraphWriter(&mut graphs));
+    parser.parse("const zero = 0", None).unwrap();
+    parser.stop_printing_dot_graphs();
+    parser.parse("function one() {}", None).unwrap();
+
+    // Each graph is passed to the writer on its own.
+    assert!(!graphs.is_empty());
+    for graph in &graphs {
+        assert!(
+            graph.starts_with("graph {\n") || graph.starts_with("digraph "),
+            "Unexpected start of graph: {graph}",
+        );
+        assert!(
+            graph.trim_end().ends_with('}'),
+            "Unexpected end of graph: {graph}"
+        );
+    }
+    assert!(graphs
+        .iter()
+        .any(|graph| graph.starts_with("digraph stack {")));
+    assert!(graphs
+        .iter()
+        .any(|graph| graph.contains("label=\"shift state:")));
+    let tree_graph = graphs.last().unwrap();
+    assert!(tree_graph.starts_with("digraph tree {"));
+    assert!(tree_graph.contains("[label=\"lexical_declaration\""));
+    assert!(tree_graph.contains("[label=\"number\""));
+    assert!(graphs
+        .iter()
+        .all(|graph| !graph.contains("function_declaration")));
+}
+
 #[test]
 fn test_parsing_with_custom_utf8_input() {
     let mut parser = Parser::new();
@@ -263,6 +444,59 @@ fn test_parsing_with_callback_returning_owned_strings() {
     );
 }
 
+#[test]
+fn test_parsing_with_io_reader() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("javascript")).unwrap();
+
+    // Text that spans several chunks, with multi-byte characters that cross the chunk boundaries,
+    // and bytes that aren't valid UTF8
+    let mut source = Vec::new();
+    for i in 0..10000 {
+        source.extend_from_slice(format!("let v{i} = \"\u{e9}\u{1f600}\";\n").as_bytes());
+        if i % 1000 == 999 {
+            source.extend_from_slice(b"x = \"\xff\xfe\";\n");
+        }
+    }
+    assert!(source.len() > 3 * 64 * 1024);
+
+    let expected_tree = parser.parse(&source, None).unwrap();
+    let mut reader = ShortReader::new(source.clone());
+    let tree = parser.parse_io(&mut reader, None).unwrap().unwrap();
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        expected_tree.root_node().to_sexp()
+    );
+    assert_eq!(tree.root_node().end_byte(), source.len());
+    assert!(reader.seek_count > 0);
+
+    // Reparse after an edit, which makes the parser jump over the nodes that it reuses
+    let position = source.len() / 2;
+    let edit = Edit {
+        position,
+        deleted_length: 0,
+        inserted_text: b"let inserted = 1;\n".to_vec(),
+    };
+    let mut edited_tree = tree.clone();
+    perform_edit(&mut edited_tree, &mut source, &edit).unwrap();
+    let expected_tree = parser.parse(&source, Some(&edited_tree)).unwrap();
+    let mut reader = ShortReader::new(source.clone());
+    let tree = parser
+        .parse_io(&mut reader, Some(&edited_tree))
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        expected_tree.root_node().to_sexp()
+    );
+
+    // Read errors are returned instead of a tree
+    let mut reader = ShortReader::new(source);
+    reader.fail_at = Some(100_000);
+    let error = parser.parse_io(&mut reader, None).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::Other);
+}
+
 #[test]
 fn test_parsing_text_with_byte_order_mark() {
     let mut parser = Parser::new();
@@ -420,6 +654,123 @@ fn test_parsing_after_editing_beginning_of_code() {
     assert_eq!(recorder.strings_read(), vec!["123 || 5 "]);
 }
 
+#[test]
+fn test_parsing_with_stats() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("javascript")).unwrap();
+
+    let mut code = b"const a = `${b}`;\nfunction c() { return a + 1; }\n".to_vec();
+    parser.parse(&code, None).unwrap();
+    let stats = parser.parse_stats();
+    assert!(stats.lex_count > 0);
+    assert!(stats.external_scan_count > 0);
+    assert!(stats.state_count > 0);
+    assert_eq!(stats.reused_node_count, 0);
+    // Durations and tree sizes are only measured when recording is enabled.
+    assert_eq!(stats.lex_duration, time::Duration::ZERO);
+    assert_eq!(stats.tree_byte_count, 0);
+
+    parser.set_record_stats(true);
+    let mut tree = parser.parse(&code, None).unwrap();
+    let initial_stats = parser.parse_stats();
+    assert_eq!(initial_stats.lex_count, stats.lex_count);
+    assert_eq!(initial_stats.state_count, stats.state_count);
+    assert!(initial_stats.tree_byte_count > 0);
+
+    // Replace the `1` at the end of the code
+    let position = code.len() - 5;
+    perform_edit(
+        &mut tree,
+        &mut code,
+        &Edit {
+            position,
+            deleted_length: 1,
+            inserted_text: b"2".to_vec(),
+        },
+    )
+    .unwrap();
+    parser.parse(&code, Some(&tree)).unwrap();
+    let stats = parser.parse_stats();
+    assert!(stats.reused_node_count > 0);
+    assert!(stats.reused_byte_count > 0);
+    assert!(stats.lex_count < initial_stats.lex_count);
+    assert!(stats.tree_byte_count > 0);
+}
+
+#[test]
+fn test_parsing_with_stack_split_and_merge_stats() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"{
+            "name": "test_unambiguous_stats",
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "statement"}},
+                "statement": {"type": "SEQ", "members": [
+                    {"type": "SYMBOL", "name": "identifier"},
+                    {"type": "SYMBOL", "name": "identifier"},
+                    {"type": "STRING", "value": ";"}
+                ]},
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+            },
+            "extras": [{"type": "PATTERN", "value": "\\s"}]
+        }"#,
+    )
+    .unwrap();
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&parser_name, &parser_code, None))
+        .unwrap();
+
+    // Recovering from errors creates and merges stack versions, but those aren't counted.
+    for code in ["a b; c d; e f;", "a b; c; e f;", "a b c d; ; e f g;"] {
+        let tree = parser.parse(code, None).unwrap();
+        let stats = parser.parse_stats();
+        assert_eq!(tree.root_node().has_error(), code != "a b; c d; e f;");
+        assert_eq!(stats.stack_split_count, 0, "{code}");
+        assert_eq!(stats.stack_merge_count, 0, "{code}");
+    }
+
+    let (parser_name, parser_code) = generate_parser(
+        r#"{
+            "name": "test_ambiguous_stats",
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "statement"}},
+                "statement": {"type": "SEQ", "members": [
+                    {"type": "CHOICE", "members": [
+                        {"type": "SYMBOL", "name": "type_pair"},
+                        {"type": "SYMBOL", "name": "value_pair"}
+                    ]},
+                    {"type": "STRING", "value": ";"}
+                ]},
+                "type_pair": {"type": "SEQ", "members": [
+                    {"type": "SYMBOL", "name": "type"},
+                    {"type": "STRING", "value": ","}
+                ]},
+                "value_pair": {"type": "SEQ", "members": [
+                    {"type": "SYMBOL", "name": "value"},
+                    {"type": "STRING", "value": ","}
+                ]},
+                "type": {"type": "SYMBOL", "name": "identifier"},
+                "value": {"type": "SYMBOL", "name": "identifier"},
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+            },
+            "conflicts": [["type", "value"], ["type_pair", "value_pair"]],
+            "extras": [{"type": "PATTERN", "value": "\\s"}]
+        }"#,
+    )
+    .unwrap();
+    parser
+        .set_language(&get_test_language(&parser_name, &parser_code, None))
+        .unwrap();
+
+    // Each statement is ambiguous, so the stack splits, and its versions are merged again once
+    // they have consumed the same tokens.
+    let tree = parser.parse("a,; b,;", None).unwrap();
+    assert!(!tree.root_node().has_error());
+    let stats = parser.parse_stats();
+    assert!(stats.stack_split_count > 0);
+    assert!(stats.stack_merge_count > 0);
+}
+
 #[test]
 fn test_parsing_after_editing_end_of_code() {
     let mut parser = Parser::new();
@@ -1058,6 +1409,134 @@ fn test_parsing_with_timeout_when_error_detected() {
     assert!(tree.is_none());
 }
 
+#[test]
+fn test_parsing_cancelled_during_a_long_external_token() {
+    let dir = fixtures_dir()
+        .join("test_grammars")
+        .join("aliased_external_tokens");
+    let grammar_json = load_grammar_file(&dir.join("grammar.js"), None).unwrap();
+    let (grammar_name, parser_code) = generate_parser(&grammar_json).unwrap();
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&grammar_name, &parser_code, Some(&dir)))
+        .unwrap();
+
+    // The external scanner consumes the string's contents in one call, so the progress callback
+    // is only called by the lexer, every 1024 characters after the opening quote.
+    let code = format!("\"{}\"", "a".repeat(5000));
+    let mut offsets = Vec::new();
+    let tree = parser.parse_with_options(
+        &mut |i, _| &code.as_bytes()[i.min(code.len())..],
+        None,
+        Some(ParseOptions::new().progress_callback(&mut |state| {
+            offsets.push(state.current_byte_offset());
+            state.current_byte_offset() >= 2048
+        })),
+    );
+    assert!(tree.is_none());
+    assert_eq!(offsets, [1025, 2049]);
+
+    // Parsing can be resumed from where it was cancelled.
+    let tree = parser
+        .parse_with_options(
+            &mut |i, _| &code.as_bytes()[i.min(code.len())..],
+            None,
+            None,
+        )
+        .unwrap();
+    assert_eq!(tree.root_node().to_sexp(), "(document (string (content)))");
+    assert_eq!(tree.root_node().end_byte(), code.len());
+}
+
+#[test]
+fn test_parsing_cancelled_during_a_long_internal_token() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"{
+            "name": "test_long_token",
+            "rules": {
+                "words": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "word"}},
+                "word": {"type": "PATTERN", "value": "[a-z]+"}
+            },
+            "extras": [{"type": "PATTERN", "value": "\\s"}]
+        }"#,
+    )
+    .unwrap();
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&parser_name, &parser_code, None))
+        .unwrap();
+
+    let code = "a".repeat(5000);
+    let mut offsets = Vec::new();
+    let tree = parser.parse_with_options(
+        &mut |i, _| &code.as_bytes()[i.min(code.len())..],
+        None,
+        Some(ParseOptions::new().progress_callback(&mut |state| {
+            offsets.push(state.current_byte_offset());
+            offsets.len() == 3
+        })),
+    );
+    assert!(tree.is_none());
+    assert_eq!(offsets, [1024, 2048, 3072]);
+
+    parser.reset();
+    let tree = parser.parse(&code, None).unwrap();
+    assert_eq!(tree.root_node().to_sexp(), "(words (word))");
+}
+
+#[test]
+fn test_parsing_with_a_deadline() {
+    let dir = fixtures_dir()
+        .join("test_grammars")
+        .join("aliased_external_tokens");
+    let grammar_json = load_grammar_file(&dir.join("grammar.js"), None).unwrap();
+    let (grammar_name, parser_code) = generate_parser(&grammar_json).unwrap();
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&grammar_name, &parser_code, Some(&dir)))
+        .unwrap();
+
+    let code = format!("\"{}\"", "a".repeat(5000));
+    let tree = parser.parse_with_options(
+        &mut |i, _| &code.as_bytes()[i.min(code.len())..],
+        None,
+        Some(ParseOptions::new().deadline(time::Instant::now())),
+    );
+    assert!(tree.is_none());
+
+    // The progress callback isn't called once the deadline has passed.
+    parser.reset();
+    let mut callback_count = 0;
+    let tree = parser.parse_with_options(
+        &mut |i, _| &code.as_bytes()[i.min(code.len())..],
+        None,
+        Some(
+            ParseOptions::new()
+                .deadline(time::Instant::now())
+                .progress_callback(&mut |_| {
+                    callback_count += 1;
+                    false
+                }),
+        ),
+    );
+    assert!(tree.is_none());
+    assert_eq!(callback_count, 0);
+
+    parser.reset();
+    let tree = parser.parse_with_options(
+        &mut |i, _| &code.as_bytes()[i.min(code.len())..],
+        None,
+        Some(ParseOptions::new().deadline(time::Instant::now() + time::Duration::from_secs(60))),
+    );
+    assert_eq!(
+        tree.unwrap().root_node().to_sexp(),
+        "(document (string (content)))"
+    );
+}
+
 // Included Ranges
 
 #[test]
@@ -1259,7 +1738,13 @@ fn test_parsing_error_in_invalid_included_ranges() {
             },
         ])
         .unwrap_err();
-    assert_eq!(error, IncludedRangesError(1));
+    assert_eq!(
+        error,
+        IncludedRangesError {
+            index: 1,
+            kind: IncludedRangesErrorKind::OverlapsPrevious,
+        }
+    );
 
     // Range ends before it starts
     let error = parser
@@ -1270,7 +1755,71 @@ fn test_parsing_error_in_invalid_included_ranges() {
             end_point: Point::new(0, 5),
         }])
         .unwrap_err();
-    assert_eq!(error, IncludedRangesError(0));
+    assert_eq!(
+        error,
+        IncludedRangesError {
+            index: 0,
+            kind: IncludedRangesErrorKind::EndBeforeStart,
+        }
+    );
+
+    // Range's end point is before its start point
+    let error = parser
+        .set_included_ranges(&[Range {
+            start_byte: 5,
+            end_byte: 10,
+            start_point: Point::new(1, 0),
+            end_point: Point::new(0, 10),
+        }])
+        .unwrap_err();
+    assert_eq!(
+        error,
+        IncludedRangesError {
+            index: 0,
+            kind: IncludedRangesErrorKind::PointsOutOfOrder,
+        }
+    );
+
+    // Range's start point is before the previous range's end point
+    let error = parser
+        .set_included_ranges(&[
+            Range {
+                start_byte: 0,
+                end_byte: 5,
+                start_point: Point::new(0, 0),
+                end_point: Point::new(2, 0),
+            },
+            Range {
+                start_byte: 10,
+                end_byte: 15,
+                start_point: Point::new(1, 0),
+                end_point: Point::new(3, 0),
+            },
+        ])
+        .unwrap_err();
+    assert_eq!(
+        error,
+        IncludedRangesError {
+            index: 1,
+            kind: IncludedRangesErrorKind::PointsOutOfOrder,
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "Incorrect range by index: 1, because its points are out of order with its byte offsets"
+    );
+
+    // After an error, the parser's included ranges are unchanged, and the default range spans
+    // the entire document.
+    assert_eq!(
+        parser.included_ranges(),
+        &[Range {
+            start_byte: 0,
+            end_byte: u32::MAX as usize,
+            start_point: Point::new(0, 0),
+            end_point: Point::new(u32::MAX as usize, u32::MAX as usize),
+        }]
+    );
 }
 
 #[test]
@@ -2023,6 +2572,433 @@ fn test_grammars_that_should_not_compile() {
     .is_err());
 }
 
+#[test]
+fn test_parsing_files_on_multiple_threads() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"{
+            "name": "test_parallel_parsing",
+            "extras": [{"type": "PATTERN", "value": "\\s"}],
+            "rules": {
+                "source_file": {
+                    "type": "REPEAT",
+                    "content": {"type": "SYMBOL", "name": "assignment"}
+                },
+                "assignment": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "STRING", "value": "="},
+                        {"type": "SYMBOL", "name": "identifier"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#,
+    )
+    .unwrap();
+    let language = get_test_language(&parser_name, &parser_code, None);
+
+    let dir = tempfile::tempdir().unwrap();
+    let files = (0..12)
+        .map(|i| {
+            let path = dir.path().join(format!("file{i}.txt"));
+            let source_code = if i == 7 || i == 9 {
+                "a = = b\n".to_string()
+            } else {
+                "a = b\n".repeat(i + 1)
+            };
+            fs::write(&path, source_code).unwrap();
+            (path, language.clone())
+        })
+        .collect::<Vec<_>>();
+
+    let parse_theme = ParseTheme::empty();
+    let options = ParseFileOptions {
+        edits: &[],
+        print_changed_ranges: false,
+        output: ParseOutput::Normal,
+        print_time: false,
+        stat_detail: false,
+        timeout: 0,
+        debug: ParseDebugType::Quiet,
+        debug_graph: false,
+        cancellation_flag: None,
+        encoding: Encoding::Auto,
+        open_log: false,
+        ranges: RangeFormat::None,
+        fields: true,
+        anonymous: None,
+        depth: None,
+        byte_range: None,
+        parse_theme: &parse_theme,
+    };
+    let parse_files = |thread_count: usize, fail_fast: bool| {
+        let mut output = Vec::new();
+        let summaries = parse_files_at_paths(
+            &mut Parser::new(),
+            &files,
+            0,
+            thread_count,
+            fail_fast,
+            &options,
+            &mut output,
+        )
+        .unwrap();
+
+        // The lines that report errors include the parse time, so only compare the trees.
+        let trees = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.contains("Parse:"))
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        (summaries, trees)
+    };
+
+    let (sequential_summaries, sequential_trees) = parse_files(1, false);
+    let (parallel_summaries, parallel_trees) = parse_files(4, false);
+    assert_eq!(parallel_trees, sequential_trees);
+    for summaries in [&sequential_summaries, &parallel_summaries] {
+        assert_eq!(
+            summaries
+                .iter()
+                .map(|summary| summary.file.clone())
+                .collect::<Vec<_>>(),
+            files
+                .iter()
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            summaries
+                .iter()
+                .enumerate()
+                .filter(|(_, summary)| summary.has_errors())
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>(),
+            [7, 9]
+        );
+    }
+
+    // No files are parsed after the first one with errors.
+    let (summaries, trees) = parse_files(4, true);
+    assert_eq!(summaries.len(), 8);
+    assert!(summaries[7].has_errors());
+    assert!(sequential_trees.starts_with(&trees));
+    assert!(trees.len() < sequential_trees.len());
+}
+
+#[test]
+fn test_parsing_files_in_each_encoding() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"{
+            "name": "test_encodings",
+            "extras": [{"type": "PATTERN", "value": "\\s"}],
+            "rules": {
+                "source_file": {
+                    "type": "REPEAT",
+                    "content": {"type": "SYMBOL", "name": "assignment"}
+                },
+                "assignment": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "STRING", "value": "="},
+                        {"type": "SYMBOL", "name": "identifier"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "\\p{L}+"}
+            }
+        }"#,
+    )
+    .unwrap();
+    let language = get_test_language(&parser_name, &parser_code, None);
+    let mut parser = Parser::new();
+    let parse_theme = ParseTheme::empty();
+
+    // Each file contains `café = crème`, and the UTF-16LE one starts with a byte order mark.
+    for (file_name, encoding, identifier_ranges) in [
+        ("utf8.txt", Encoding::Auto, [0..5, 8..14]),
+        ("utf16-le.txt", Encoding::Auto, [2..10, 16..26]),
+        ("utf16-be.txt", Encoding::Utf16BE, [0..8, 14..24]),
+        ("latin1.txt", Encoding::Latin1, [0..4, 7..12]),
+    ] {
+        let path = fixtures_dir().join("encodings").join(file_name);
+        let options = ParseFileOptions {
+            edits: &[],
+            print_changed_ranges: false,
+            output: ParseOutput::Json,
+            print_time: false,
+            stat_detail: false,
+            timeout: 0,
+            debug: ParseDebugType::Quiet,
+            debug_graph: false,
+            cancellation_flag: None,
+            encoding,
+            open_log: false,
+            ranges: RangeFormat::Points,
+            fields: true,
+            anonymous: None,
+            depth: None,
+            byte_range: None,
+            parse_theme: &parse_theme,
+        };
+        let mut output = Vec::new();
+        let summary = parse_file_at_path(
+            &mut parser,
+            &language,
+            &path,
+            file_name,
+            0,
+            &options,
+            &mut output,
+        )
+        .unwrap();
+        assert!(!summary.has_errors(), "{file_name}");
+
+        let tree = serde_json::from_slice::<serde_json::Value>(&output).unwrap();
+        let assignment = &tree["children"][0];
+        assert_eq!(tree["type"], "source_file", "{file_name}");
+        assert_eq!(assignment["type"], "assignment", "{file_name}");
+        let source_code = fs::read(&path).unwrap();
+        let encoding = encoding.detect(&source_code);
+        for (identifier, (range, text)) in [&assignment["children"][0], &assignment["children"][2]]
+            .into_iter()
+            .zip(identifier_ranges.into_iter().zip(["café", "crème"]))
+        {
+            assert_eq!(identifier["type"], "identifier", "{file_name}");
+            assert_eq!(identifier["startByte"], range.start, "{file_name}");
+            assert_eq!(identifier["endByte"], range.end, "{file_name}");
+            assert_eq!(
+                identifier["startPosition"]["column"], range.start,
+                "{file_name}"
+            );
+            assert_eq!(encoding.decode(&source_code[range]), text, "{file_name}");
+        }
+    }
+}
+
+#[test]
+fn test_parsing_with_tree_display_options() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"{
+            "name": "test_tree_display",
+            "extras": [{"type": "PATTERN", "value": "\\s"}],
+            "rules": {
+                "source_file": {
+                    "type": "REPEAT",
+                    "content": {"type": "SYMBOL", "name": "assignment"}
+                },
+                "assignment": {
+                    "type": "SEQ",
+                    "members": [
+                        {
+                            "type": "FIELD",
+                            "name": "left",
+                            "content": {"type": "SYMBOL", "name": "identifier"}
+                        },
+                        {"type": "STRING", "value": "="},
+                        {
+                            "type": "FIELD",
+                            "name": "right",
+                            "content": {"type": "SYMBOL", "name": "identifier"}
+                        }
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#,
+    )
+    .unwrap();
+    let language = get_test_language(&parser_name, &parser_code, None);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("input.txt");
+    fs::write(&path, "a = bc\n").unwrap();
+
+    let parse_theme = ParseTheme::empty();
+    let parse = |output, ranges, fields, anonymous, depth| {
+        let options = ParseFileOptions {
+            edits: &[],
+            print_changed_ranges: false,
+            output,
+            print_time: false,
+            stat_detail: false,
+            timeout: 0,
+            debug: ParseDebugType::Quiet,
+            debug_graph: false,
+            cancellation_flag: None,
+            encoding: Encoding::Auto,
+            open_log: false,
+            ranges,
+            fields,
+            anonymous,
+            depth,
+            byte_range: None,
+            parse_theme: &parse_theme,
+        };
+        let mut output = Vec::new();
+        parse_file_at_path(
+            &mut Parser::new(),
+            &language,
+            &path,
+            "input.txt",
+            0,
+            &options,
+            &mut output,
+        )
+        .unwrap();
+        String::from_utf8(output).unwrap()
+    };
+
+    assert_eq!(
+        parse(
+            ParseOutput::Normal,
+            RangeFormat::Bytes,
+            true,
+            Some(true),
+            None
+        ),
+        concat!(
+            "(source_file [0..7]\n",
+            "  (assignment [0..6]\n",
+            "    left: (identifier [0..1])\n",
+            "    (\"=\" [2..3])\n",
+            "    right: (identifier [4..6])))\n",
+        )
+    );
+    assert_eq!(
+        parse(ParseOutput::Normal, RangeFormat::None, false, None, None),
+        "(source_file\n  (assignment\n    (identifier)\n    (identifier)))\n"
+    );
+    assert_eq!(
+        parse(
+            ParseOutput::Normal,
+            RangeFormat::Points,
+            true,
+            None,
+            Some(1)
+        ),
+        "(source_file [0, 0] - [1, 0]\n  (assignment [0, 0] - [0, 6] ...))\n"
+    );
+    assert_eq!(
+        parse(
+            ParseOutput::Cst,
+            RangeFormat::Both,
+            false,
+            Some(false),
+            None
+        ),
+        concat!(
+            "0:0 - 1:0 0..7   source_file \n",
+            "0:0 - 0:6 0..6     assignment \n",
+            "0:0 - 0:1 0..1       identifier `a`\n",
+            "0:4 - 0:6 4..6       identifier `bc`\n",
+            "\n",
+        )
+    );
+    assert_eq!(
+        parse(ParseOutput::Cst, RangeFormat::None, true, None, Some(1)),
+        "  source_file \n    assignment ...\n\n",
+    );
+}
+
+#[test]
+fn test_parsing_with_errors_only() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"{
+            "name": "test_errors_only",
+            "extras": [{"type": "PATTERN", "value": "\\s"}],
+            "rules": {
+                "source_file": {
+                    "type": "REPEAT",
+                    "content": {"type": "SYMBOL", "name": "assignment"}
+                },
+                "assignment": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "STRING", "value": "="},
+                        {"type": "SYMBOL", "name": "identifier"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "\\p{L}+"}
+            }
+        }"#,
+    )
+    .unwrap();
+    let language = get_test_language(&parser_name, &parser_code, None);
+    let dir = tempfile::tempdir().unwrap();
+    let files = [
+        ("valid.txt", "a = b\n".to_string()),
+        ("short.txt", "a = b\n\tc = = d\n".to_string()),
+        // The error is in the middle of a long line of multi-byte characters.
+        (
+            "long.txt",
+            format!("{}é = = ü{}\n", "ä = ö ".repeat(30), " ä = ö".repeat(30)),
+        ),
+    ]
+    .map(|(name, source_code)| {
+        let path = dir.path().join(name);
+        fs::write(&path, source_code).unwrap();
+        (path, language.clone())
+    });
+
+    let parse_theme = ParseTheme::empty();
+    let options = ParseFileOptions {
+        edits: &[],
+        print_changed_ranges: false,
+        output: ParseOutput::Errors,
+        print_time: false,
+        stat_detail: false,
+        timeout: 0,
+        debug: ParseDebugType::Quiet,
+        debug_graph: false,
+        cancellation_flag: None,
+        encoding: Encoding::Auto,
+        open_log: false,
+        ranges: RangeFormat::Points,
+        fields: true,
+        anonymous: None,
+        depth: None,
+        byte_range: None,
+        parse_theme: &parse_theme,
+    };
+    let mut output = Vec::new();
+    let summaries = parse_files_at_paths(
+        &mut Parser::new(),
+        &files,
+        0,
+        1,
+        false,
+        &options,
+        &mut output,
+    )
+    .unwrap();
+
+    assert!(summaries[0].errors.is_empty());
+    let short_error = &summaries[1].errors[0];
+    assert_eq!(short_error.kind, "ERROR");
+    assert_eq!((short_error.start_byte, short_error.end_byte), (11, 12));
+    assert_eq!(short_error.excerpt, "c = = d");
+    let long_error = &summaries[2].errors[0];
+    assert_eq!(long_error.start.column, 30 * 8 + 5);
+    assert_eq!(
+        long_error.excerpt,
+        "…= ö ä = ö ä = ö é = = ü ä = ö ä = ö ä = ö ä = ö ä = ö ä = ö …"
+    );
+
+    let output = String::from_utf8(output).unwrap();
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[0],
+        format!(
+            "{}\t(ERROR [1, 5] - [1, 6] [11..12])\tc = = d",
+            files[1].0.display()
+        )
+    );
+}
+
 const fn simple_range(start: usize, end: usize) -> Range {
     Range {
         start_byte: start,
@@ -2035,3 +3011,40 @@ const fn simple_range(start: usize, end: usize) -> Range {
 fn chunked_input<'a>(text: &'a str, size: usize) -> impl FnMut(usize, Point) -> &'a [u8] {
     move |offset, _| text[offset..text.len().min(offset + size)].as_bytes()
 }
+
+/// A reader that returns fewer bytes than asked for, and that can fail at a given offset.
+struct ShortReader {
+    cursor: Cursor<Vec<u8>>,
+    seek_count: usize,
+    fail_at: Option<u64>,
+}
+
+impl ShortReader {
+    const fn new(text: Vec<u8>) -> Self {
+        Self {
+            cursor: Cursor::new(text),
+            seek_count: 0,
+            fail_at: None,
+        }
+    }
+}
+
+impl Read for ShortReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self
+            .fail_at
+            .is_some_and(|offset| self.cursor.position() >= offset)
+        {
+            return Err(io::Error::other("read failed"));
+        }
+        let len = buf.len().min(1000);
+        self.cursor.read(&mut buf[..len])
+    }
+}
+
+impl Seek for ShortReader {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.seek_count += 1;
+        self.cursor.seek(position)
+    }
+}
@@ -1,12 +1,12 @@
-use std::{env, fmt::Write, sync::LazyLock};
+use std::{collections::HashSet, env, fmt::Write, sync::LazyLock};
 
 use indoc::indoc;
 use rand::{prelude::StdRng, SeedableRng};
 use streaming_iterator::StreamingIterator;
 use tree_sitter::{
-    CaptureQuantifier, InputEdit, Language, Node, Parser, Point, Query, QueryCursor,
-    QueryCursorOptions, QueryError, QueryErrorKind, QueryPredicate, QueryPredicateArg,
-    QueryProperty, Range,
+    edits_between, CaptureQuantifier, InputEdit, Language, Node, Parser, Point, Query, QueryCursor,
+    QueryCursorOptions, QueryCursorStats, QueryDeserializeError, QueryError, QueryErrorKind,
+    QueryPredicate, QueryPredicateArg, QueryProperty, Range,
 };
 use unindent::Unindent;
 
@@ -15,10 +15,14 @@ use super::helpers::{
     fixtures::{get_language, get_test_language},
     query_helpers::{assert_query_matches, Match, Pattern},
 };
-use crate::tests::{
-    generate_parser,
-    helpers::query_helpers::{collect_captures, collect_matches},
-    ITERATION_COUNT,
+use crate::{
+    parse::ParsePoint,
+    query_analysis::{lint_query, QueryDiagnostic, Severity},
+    tests::{
+        generate_parser,
+        helpers::query_helpers::{collect_captures, collect_matches},
+        ITERATION_COUNT,
+    },
 };
 
 static EXAMPLE_FILTER: LazyLock<Option<String>> =
@@ -1911,6 +1915,206 @@ fn test_query_matches_with_too_many_permutations_to_track() {
     });
 }
 
+#[test]
+fn test_query_cursor_stats() {
+    allocations::record(|| {
+        let language = get_language("javascript");
+        let query = Query::new(&language, "(array (identifier) @pre (identifier) @post)").unwrap();
+
+        let mut source = "hello, ".repeat(50);
+        source.insert(0, '[');
+        source.push_str("];");
+
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(&source, None).unwrap();
+        let mut cursor = QueryCursor::new();
+        assert_eq!(cursor.stats(), QueryCursorStats::default());
+
+        // Without a match limit, every permutation is tracked at once.
+        let match_count = cursor
+            .matches(&query, tree.root_node(), source.as_bytes())
+            .count();
+        let stats = cursor.stats();
+        assert!(!cursor.did_exceed_match_limit());
+        assert_eq!(match_count, 50 * 49 / 2);
+        assert_eq!(stats.node_count, tree.root_node().descendant_count());
+        assert!(stats.state_count > stats.node_count);
+        assert!(stats.max_in_progress_match_count > 32);
+
+        // With a match limit, the number of in-progress matches reaches the limit, and doesn't
+        // exceed it, even though the cursor has already allocated room for more matches.
+        cursor.set_match_limit(32);
+        cursor
+            .matches(&query, tree.root_node(), source.as_bytes())
+            .count();
+        assert!(cursor.did_exceed_match_limit());
+        assert_eq!(cursor.stats().max_in_progress_match_count, 32);
+        assert_eq!(cursor.stats().node_count, stats.node_count);
+
+        // The counters are reset when the cursor is executed again.
+        let node = tree.root_node().child(0).unwrap().child(0).unwrap();
+        cursor.matches(&query, node, source.as_bytes()).count();
+        assert!(cursor.stats().node_count < stats.node_count);
+    });
+}
+
+#[test]
+fn test_query_cursor_step_limit() {
+    allocations::record(|| {
+        let language = get_language("javascript");
+        // Every combination of three identifiers in an array matches this query, so the number
+        // of steps grows with the cube of the array's length.
+        let query = Query::new(
+            &language,
+            "(array (identifier) @a (identifier) @b (identifier) @c)",
+        )
+        .unwrap();
+
+        let mut source = "hello, ".repeat(100);
+        source.insert(0, '[');
+        source.push_str("];");
+
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(&source, None).unwrap();
+        let mut cursor = QueryCursor::new();
+        assert_eq!(cursor.step_limit(), u64::MAX);
+
+        cursor.set_step_limit(1_000);
+        let matches = collect_matches(
+            cursor.matches(&query, tree.root_node(), source.as_bytes()),
+            &query,
+            source.as_str(),
+        );
+
+        // The cursor stops early, but still returns the matches that it finished.
+        assert!(!matches.is_empty());
+        assert!(matches.len() < 100 * 99 * 98 / 6);
+        assert_eq!(
+            matches[0],
+            (0, vec![("a", "hello"), ("b", "hello"), ("c", "hello")]),
+        );
+        assert!(cursor.did_exceed_step_limit());
+
+        // The cursor can only exceed the limit by the states that it checks against one node.
+        let stats = cursor.stats();
+        assert!(stats.state_count <= 1_000 + stats.max_in_progress_match_count);
+        assert!(stats.node_count < tree.root_node().descendant_count());
+
+        // Capturing stops at the same point.
+        let capture_count = cursor
+            .captures(&query, tree.root_node(), source.as_bytes())
+            .count();
+        assert!(cursor.did_exceed_step_limit());
+        assert!(capture_count > 0);
+
+        // The step count is reset when the cursor is executed again, so a smaller input doesn't
+        // exceed the same limit.
+        let source = "[a, b, c, d];";
+        let tree = parser.parse(source, None).unwrap();
+        let matches = collect_matches(
+            cursor.matches(&query, tree.root_node(), source.as_bytes()),
+            &query,
+            source,
+        );
+        assert_eq!(matches.len(), 4);
+        assert!(!cursor.did_exceed_step_limit());
+    });
+}
+
+#[test]
+fn test_query_cursor_reset() {
+    allocations::record(|| {
+        let examples = [
+            (
+                "javascript",
+                "(call_expression function: (identifier) @fn arguments: (arguments (_) @arg))",
+                "a(b, c); d(e(f), [g]); if (h) { i(j); }",
+            ),
+            (
+                "python",
+                "(function_definition name: (identifier) @name body: (block (_) @stmt))",
+                "def a():\n  b()\n  return c\n\nclass D:\n  def e(self):\n    pass\n",
+            ),
+            (
+                "rust",
+                "(struct_item name: (type_identifier) @name (field_declaration_list (field_declaration name: (_) @field)))",
+                "struct A { b: u8, c: u16 }\nfn d() { struct E { f: () } }\n",
+            ),
+        ];
+
+        let mut parser = Parser::new();
+        let mut reused_cursor = QueryCursor::new();
+        for (language_name, query_source, source) in examples {
+            let language = get_language(language_name);
+            let query = Query::new(&language, query_source).unwrap();
+            parser.set_language(&language).unwrap();
+            let tree = parser.parse(source, None).unwrap();
+
+            let mut matches_cursor = QueryCursor::new();
+            matches_cursor
+                .matches(&query, tree.root_node(), source.as_bytes())
+                .count();
+            let expected_stats = matches_cursor.stats();
+            let expected_matches = collect_matches(
+                matches_cursor.matches(&query, tree.root_node(), source.as_bytes()),
+                &query,
+                source,
+            );
+            let mut captures_cursor = QueryCursor::new();
+            let expected_captures = collect_captures(
+                captures_cursor.captures(&query, tree.root_node(), source.as_bytes()),
+                &query,
+                source,
+            );
+            assert!(!expected_matches.is_empty());
+
+            // Leave the reused cursor with settings that would change the results, and with
+            // matches that haven't been returned yet.
+            reused_cursor
+                .set_byte_range(1..2)
+                .set_point_range(Point::new(0, 1)..Point::new(0, 2))
+                .set_max_start_depth(Some(0))
+                .set_enabled_patterns(&[false]);
+            reused_cursor.set_match_limit(1);
+            reused_cursor.set_step_limit(1);
+            reused_cursor
+                .matches(&query, tree.root_node(), source.as_bytes())
+                .advance();
+
+            reused_cursor.reset();
+            assert_eq!(
+                reused_cursor.match_limit(),
+                QueryCursor::new().match_limit()
+            );
+            assert_eq!(reused_cursor.step_limit(), u64::MAX);
+            assert!(!reused_cursor.did_exceed_step_limit());
+            reused_cursor
+                .matches(&query, tree.root_node(), source.as_bytes())
+                .count();
+            assert_eq!(reused_cursor.stats(), expected_stats);
+            assert!(!reused_cursor.did_exceed_match_limit());
+            assert_eq!(
+                collect_matches(
+                    reused_cursor.matches(&query, tree.root_node(), source.as_bytes()),
+                    &query,
+                    source,
+                ),
+                expected_matches,
+            );
+            assert_eq!(
+                collect_captures(
+                    reused_cursor.captures(&query, tree.root_node(), source.as_bytes()),
+                    &query,
+                    source,
+                ),
+                expected_captures,
+            );
+        }
+    });
+}
+
 #[test]
 fn test_query_sibling_patterns_dont_match_children_of_an_error() {
     allocations::record(|| {
@@ -3244,6 +3448,141 @@ fn test_query_captures_with_predicates() {
     });
 }
 
+#[test]
+fn test_query_matches_including_unsatisfied_predicates() {
+    allocations::record(|| {
+        let language = get_language("javascript");
+        let query = Query::new(&language, r#"((identifier) @id (#eq? @id "a"))"#).unwrap();
+
+        let source = "a; b; a;";
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let mut cursor = QueryCursor::new();
+
+        let mut matches = cursor.matches(&query, tree.root_node(), source.as_bytes());
+        matches.set_include_unsatisfied_matches(true);
+        let (mut buffer1, mut buffer2) = (Vec::new(), Vec::new());
+        let mut results = Vec::new();
+        while let Some(m) = matches.next() {
+            results.push((
+                m.captures[0].node.utf8_text(source.as_bytes()).unwrap(),
+                m.satisfies_text_predicates(
+                    &query,
+                    &mut buffer1,
+                    &mut buffer2,
+                    &mut source.as_bytes(),
+                ),
+            ));
+        }
+        assert_eq!(results, &[("a", true), ("b", false), ("a", true)]);
+    });
+}
+
+#[test]
+fn test_query_matches_with_custom_capture_predicates() {
+    allocations::record(|| {
+        let language = get_language("javascript");
+        let query = Query::new(
+            &language,
+            r"
+            ((array (identifier) @all (identifier) @all)
+             (#is-snake-case? @all))
+            ((array (identifier) @some (identifier) @some)
+             (#any-is-snake-case? @some))
+            ((array (identifier) @none (identifier) @none)
+             (#not-is-snake-case? @none))
+            ((array (identifier) @not_all (identifier) @not_all)
+             (#any-not-is-snake-case? @not_all))
+            ",
+        )
+        .unwrap();
+
+        let source = "[a_b, c_d]; [e_f, gH]; [iJ, kL];";
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+
+        let mut cursor = QueryCursor::new();
+        cursor.set_predicate_handler("is-snake-case?", |m, args, text| {
+            let QueryPredicateArg::Capture(capture_ix) = args[0] else {
+                unreachable!()
+            };
+            let mut nodes = m.nodes_for_capture_index(capture_ix);
+            let node = nodes.next().unwrap();
+            assert!(nodes.next().is_none());
+            text.text(node)
+                .iter()
+                .all(|byte| byte.is_ascii_lowercase() || *byte == b'_')
+        });
+        let matches = cursor.matches(&query, tree.root_node(), source.as_bytes());
+
+        assert_eq!(
+            collect_matches(matches, &query, source),
+            &[
+                (0, vec![("all", "a_b"), ("all", "c_d")]),
+                (1, vec![("some", "a_b"), ("some", "c_d")]),
+                (1, vec![("some", "e_f"), ("some", "gH")]),
+                (3, vec![("not_all", "e_f"), ("not_all", "gH")]),
+                (2, vec![("none", "iJ"), ("none", "kL")]),
+                (3, vec![("not_all", "iJ"), ("not_all", "kL")]),
+            ]
+        );
+    });
+}
+
+#[test]
+fn test_query_captures_with_custom_string_predicates() {
+    allocations::record(|| {
+        let language = get_language("javascript");
+        let query = Query::new(
+            &language,
+            r#"
+            ((identifier) @argument
+             (#has-parent-kind? @argument "arguments"))
+            ((identifier) @variable
+             (#not-has-parent-kind? @variable "call_expression")
+             (#is-enabled? "variables"))
+            ((identifier) @disabled
+             (#is-enabled? "functions"))
+            "#,
+        )
+        .unwrap();
+
+        let source = "a(b); c; d(e);";
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+
+        let mut cursor = QueryCursor::new();
+        cursor
+            .set_predicate_handler("has-parent-kind?", |m, args, _| {
+                let (QueryPredicateArg::Capture(capture_ix), QueryPredicateArg::String(kind)) =
+                    (&args[0], &args[1])
+                else {
+                    unreachable!()
+                };
+                m.nodes_for_capture_index(*capture_ix)
+                    .all(|node| node.parent().unwrap().kind() == &**kind)
+            })
+            .set_predicate_handler("is-enabled?", |_, args, _| {
+                args == [QueryPredicateArg::String("variables".into())]
+            });
+        let captures = cursor.captures(&query, tree.root_node(), source.as_bytes());
+
+        assert_eq!(
+            collect_captures(captures, &query, source),
+            &[
+                ("argument", "b"),
+                ("variable", "b"),
+                ("variable", "c"),
+                ("argument", "e"),
+                ("variable", "e"),
+            ]
+        );
+    });
+}
+
 #[test]
 fn test_query_captures_with_quoted_predicate_args() {
     allocations::record(|| {
@@ -4046,6 +4385,216 @@ fn test_query_disable_pattern() {
     });
 }
 
+#[test]
+fn test_query_cursor_enabled_patterns() {
+    allocations::record(|| {
+        let language = get_language("javascript");
+        let query_source = "
+            (function_declaration
+                name: (identifier) @name)
+            (function_declaration
+                body: (statement_block) @body)
+            (class_declaration
+                name: (identifier) @name)
+            (class_declaration
+                body: (class_body) @body)
+            [\"(\" \")\" \"{\" \"}\"] @punctuation
+        ";
+        let query = Query::new(&language, query_source).unwrap();
+
+        assert_eq!(query.patterns_for_capture("name"), &[0, 2]);
+        assert_eq!(query.patterns_for_capture("body"), &[1, 3]);
+        assert_eq!(query.patterns_for_capture("punctuation"), &[4]);
+        assert!(query.patterns_for_capture("nonexistent").is_empty());
+
+        let source = "class A { constructor() {} } function b() { return 1; }";
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+
+        let pattern_mask = |disabled_capture: &str| {
+            let mut enabled_patterns = vec![true; query.pattern_count()];
+            for pattern_index in query.patterns_for_capture(disabled_capture) {
+                enabled_patterns[pattern_index] = false;
+            }
+            enabled_patterns
+        };
+        let mut names_cursor = QueryCursor::new();
+        names_cursor.set_enabled_patterns(&pattern_mask("name"));
+        let mut punctuation_cursor = QueryCursor::new();
+        punctuation_cursor.set_enabled_patterns(&pattern_mask("punctuation"));
+
+        // Run cursors with different masks over the same query at the same time.
+        let (names_result, punctuation_result) = std::thread::scope(|scope| {
+            let names = scope.spawn(|| {
+                let matches = names_cursor.matches(&query, tree.root_node(), source.as_bytes());
+                collect_matches(matches, &query, source)
+            });
+            let punctuation = scope.spawn(|| {
+                let matches =
+                    punctuation_cursor.matches(&query, tree.root_node(), source.as_bytes());
+                collect_matches(matches, &query, source)
+            });
+            (names.join().unwrap(), punctuation.join().unwrap())
+        });
+
+        // The results match those of queries whose patterns are disabled permanently.
+        let mut query_without_names = Query::new(&language, query_source).unwrap();
+        query_without_names.disable_pattern(0);
+        query_without_names.disable_pattern(2);
+        let mut query_without_punctuation = Query::new(&language, query_source).unwrap();
+        query_without_punctuation.disable_pattern(4);
+        let mut cursor = QueryCursor::new();
+        let matches = cursor.matches(&query_without_names, tree.root_node(), source.as_bytes());
+        assert_eq!(
+            names_result,
+            collect_matches(matches, &query_without_names, source)
+        );
+        let matches = cursor.matches(
+            &query_without_punctuation,
+            tree.root_node(),
+            source.as_bytes(),
+        );
+        assert_eq!(
+            punctuation_result,
+            collect_matches(matches, &query_without_punctuation, source)
+        );
+        assert!(names_result
+            .iter()
+            .all(|(pattern_index, _)| ![0, 2].contains(pattern_index)));
+        assert_eq!(
+            punctuation_result,
+            &[
+                (2, vec![("name", "A")]),
+                (3, vec![("body", "{ constructor() {} }")]),
+                (0, vec![("name", "b")]),
+                (1, vec![("body", "{ return 1; }")]),
+            ],
+        );
+
+        // The query itself is unchanged, and an empty mask enables every pattern.
+        cursor.set_enabled_patterns(&pattern_mask("name"));
+        cursor.set_enabled_patterns(&[]);
+        let matches = cursor.matches(&query, tree.root_node(), source.as_bytes());
+        assert_eq!(collect_matches(matches, &query, source).len(), 14);
+    });
+}
+
+#[test]
+fn test_query_serialization() {
+    allocations::record(|| {
+        let language = get_language("javascript");
+        let mut query = Query::new(
+            &language,
+            r#"
+            (function_declaration
+                name: (identifier) @name
+                !type_parameters
+                body: (statement_block . (_) @first_statement))
+            [(string) (template_string)] @string
+            ((identifier) @constant
+             (#match? @constant "^[A-Z_]+$")
+             (#set! kind "constant"))
+            (call_expression
+                function: (identifier) @function
+                arguments: (arguments (number)* @number)
+                (#not-eq? @function "require")
+                (#is-callback? @function))
+            (class_declaration name: (identifier) @class)
+            "#,
+        )
+        .unwrap();
+        query.disable_pattern(4);
+
+        let deserialized = Query::deserialize(&language, &query.serialize()).unwrap();
+        assert_eq!(deserialized.pattern_count(), query.pattern_count());
+        assert_eq!(deserialized.capture_names(), query.capture_names());
+        for i in 0..query.pattern_count() {
+            assert_eq!(
+                deserialized.capture_quantifiers(i),
+                query.capture_quantifiers(i)
+            );
+            assert_eq!(
+                deserialized.property_settings(i),
+                query.property_settings(i)
+            );
+            assert_eq!(
+                deserialized.general_predicates(i),
+                query.general_predicates(i)
+            );
+            assert_eq!(
+                deserialized.start_byte_for_pattern(i),
+                query.start_byte_for_pattern(i)
+            );
+            assert_eq!(
+                deserialized.is_pattern_rooted(i),
+                query.is_pattern_rooted(i)
+            );
+        }
+
+        let source = "
+            function a() { b(1, 2); return `c`; }
+            const D_E = require('f');
+            g(3);
+            class H {}
+        ";
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+
+        let mut cursor = QueryCursor::new();
+        let mut deserialized_cursor = QueryCursor::new();
+        let expected_matches = collect_matches(
+            cursor.matches(&query, tree.root_node(), source.as_bytes()),
+            &query,
+            source,
+        );
+        let matches = collect_matches(
+            deserialized_cursor.matches(&deserialized, tree.root_node(), source.as_bytes()),
+            &deserialized,
+            source,
+        );
+        assert!(expected_matches.iter().all(|(pattern, _)| *pattern != 4));
+        assert_eq!(matches, expected_matches);
+
+        let expected_captures = collect_captures(
+            cursor.captures(&query, tree.root_node(), source.as_bytes()),
+            &query,
+            source,
+        );
+        let captures = collect_captures(
+            deserialized_cursor.captures(&deserialized, tree.root_node(), source.as_bytes()),
+            &deserialized,
+            source,
+        );
+        assert_eq!(captures, expected_captures);
+    });
+}
+
+#[test]
+fn test_query_deserialization_errors() {
+    allocations::record(|| {
+        let language = get_language("javascript");
+        let query = Query::new(&language, "(identifier) @id").unwrap();
+        let bytes = query.serialize();
+
+        let error = Query::deserialize(&language, &bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(error, QueryDeserializeError::Format);
+        assert_eq!(error.to_string(), "The bytes are not a serialized query");
+
+        let error = Query::deserialize(&language, b"(identifier) @id").unwrap_err();
+        assert_eq!(error, QueryDeserializeError::Format);
+
+        let mut newer_bytes = bytes.clone();
+        newer_bytes[4] += 1;
+        let error = Query::deserialize(&language, &newer_bytes).unwrap_err();
+        assert_eq!(error, QueryDeserializeError::Version);
+
+        let error = Query::deserialize(&get_language("python"), &bytes).unwrap_err();
+        assert_eq!(error, QueryDeserializeError::Symbol);
+    });
+}
+
 #[test]
 fn test_query_alternative_predicate_prefix() {
     allocations::record(|| {
@@ -5203,6 +5752,243 @@ fn test_query_max_start_depth_more() {
     });
 }
 
+#[test]
+fn test_query_max_start_depth_and_containment_within_function() {
+    allocations::record(|| {
+        let language = get_language("javascript");
+        let query = Query::new(
+            &language,
+            r"
+            (function_declaration name: (identifier) @name) @function
+            (variable_declarator name: (identifier) @variable)
+            ",
+        )
+        .unwrap();
+
+        let source = indoc! {"
+            function one() {
+              let a = 1;
+              function nested() { return a; }
+            }
+            function two() {
+              let b = 2;
+            }
+            function three() {
+              let c = 3;
+            }
+        "};
+
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let functions = tree
+            .root_node()
+            .children(&mut tree.walk())
+            .collect::<Vec<_>>();
+        assert_eq!(functions.len(), 3);
+
+        // Without a max start depth, the matches for nested functions are included.
+        let mut cursor = QueryCursor::new();
+        let matches = cursor.matches(&query, functions[0], source.as_bytes());
+        assert_eq!(
+            collect_matches(matches, &query, source)
+                .into_iter()
+                .map(|(pattern_index, captures)| (pattern_index, captures.last().unwrap().1))
+                .collect::<Vec<_>>(),
+            &[(0, "one"), (1, "a"), (0, "nested")],
+        );
+
+        // With a max start depth of zero, only the patterns rooted at the node itself match.
+        cursor.set_max_start_depth(Some(0));
+        for (function, name) in functions.iter().zip(["one", "two", "three"]) {
+            let matches = cursor.matches(&query, *function, source.as_bytes());
+            assert_eq!(
+                collect_matches(matches, &query, source),
+                &[(
+                    0,
+                    vec![
+                        ("function", function.utf8_text(source.as_bytes()).unwrap()),
+                        ("name", name)
+                    ]
+                )],
+            );
+        }
+        cursor.set_max_start_depth(None);
+
+        // When the query is executed on the whole file, with a byte range that covers the body
+        // of one function, the function itself still matches, because it intersects the range.
+        let body = functions[1].child_by_field_name("body").unwrap();
+        cursor.set_byte_range(body.byte_range());
+        let matches = cursor.matches(&query, tree.root_node(), source.as_bytes());
+        assert_eq!(
+            collect_matches(matches, &query, source),
+            &[
+                (
+                    0,
+                    vec![
+                        (
+                            "function",
+                            functions[1].utf8_text(source.as_bytes()).unwrap()
+                        ),
+                        ("name", "two"),
+                    ]
+                ),
+                (1, vec![("variable", "b")]),
+            ],
+        );
+
+        // With containment, only the matches whose captures are within that range remain.
+        cursor.set_containment(true);
+        let matches = cursor.matches(&query, tree.root_node(), source.as_bytes());
+        assert_eq!(
+            collect_matches(matches, &query, source),
+            &[(1, vec![("variable", "b")])],
+        );
+        let captures = cursor.captures(&query, tree.root_node(), source.as_bytes());
+        assert_eq!(
+            collect_captures(captures, &query, source),
+            &[("variable", "b")],
+        );
+
+        // Resetting the cursor clears the byte range and the containment.
+        cursor.reset();
+        let matches = cursor.matches(&query, tree.root_node(), source.as_bytes());
+        assert_eq!(collect_matches(matches, &query, source).len(), 7);
+    });
+}
+
+#[test]
+fn test_query_matches_in_changed_ranges() {
+    // The pattern index of each match, and the capture index and byte range of each capture.
+    type Matches = Vec<(usize, Vec<(u32, usize, usize)>)>;
+
+    fn collect<'cursor, 'tree: 'cursor>(
+        mut matches: impl StreamingIterator<Item = tree_sitter::QueryMatch<'cursor, 'tree>>,
+    ) -> Matches {
+        let mut result = Vec::new();
+        while let Some(m) = matches.next() {
+            result.push((
+                m.pattern_index,
+                m.captures
+                    .iter()
+                    .map(|c| (c.index, c.node.start_byte(), c.node.end_byte()))
+                    .collect(),
+            ));
+        }
+        result.sort();
+        result
+    }
+
+    // The position that a byte offset in the old text moves to, after an edit.
+    fn adjust(offset: usize, edit: &InputEdit) -> usize {
+        if offset >= edit.old_end_byte {
+            offset + edit.new_end_byte - edit.old_end_byte
+        } else {
+            offset.min(edit.new_end_byte)
+        }
+    }
+
+    allocations::record(|| {
+        let language = get_language("javascript");
+        let query = Query::new(
+            &language,
+            r#"
+            (function_declaration name: (identifier) @function)
+            (variable_declarator name: (identifier) @variable value: (number) @value)
+            ((comment) @doc . (function_declaration name: (identifier) @documented))
+            ((identifier) @constant (#match? @constant "^[A-Z]+$"))
+            (call_expression function: (identifier) @callee)
+            (function_declaration
+                name: (identifier) @returning
+                body: (statement_block (return_statement)))
+            "#,
+        )
+        .unwrap();
+
+        let mut source = indoc! {"
+            // Add one to a number.
+            function increment() {
+              let result = 1;
+              return result;
+            }
+
+            function main() {
+              let MAX = 10;
+              log(increment());
+            }
+        "}
+        .to_string();
+        let replacements = [
+            ("result", "total"),
+            ("  return total;\n", ""),
+            ("MAX", "max"),
+            ("// Add one to a number.\n", ""),
+            ("log(increment());", "increment();\n  log(max);"),
+            (
+                "}\n\nfunction main",
+                "}\n\nfunction other() {\n  let LIMIT = 5;\n}\n\nfunction main",
+            ),
+        ];
+
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let mut tree = parser.parse(&source, None).unwrap();
+        let mut cursor = QueryCursor::new();
+        let mut partial_update_count = 0;
+
+        for (old, new) in replacements {
+            let new_source = source.replace(old, new);
+            let edits = edits_between(&source, &new_source);
+            let mut old_matches =
+                collect(cursor.matches(&query, tree.root_node(), source.as_bytes()));
+            for (_, captures) in &mut old_matches {
+                for (_, start, end) in captures {
+                    for edit in edits.iter().rev() {
+                        (*start, *end) = (adjust(*start, edit), adjust(*end, edit));
+                    }
+                }
+            }
+
+            tree.edit_all(&edits);
+            let new_tree = parser.parse(&new_source, Some(&tree)).unwrap();
+            let changed_matches =
+                cursor.matches_in_changed_ranges(&query, &tree, &new_tree, new_source.as_bytes());
+            let ranges = changed_matches.ranges().to_vec();
+            assert!(!ranges.is_empty());
+            let changed_len = ranges
+                .iter()
+                .map(|range| range.end_byte - range.start_byte)
+                .sum::<usize>();
+            if changed_len < new_source.len() {
+                partial_update_count += 1;
+            }
+
+            let mut merged_matches = old_matches
+                .into_iter()
+                .filter(|(_, captures)| {
+                    !captures
+                        .iter()
+                        .any(|(_, start, end)| changed_matches.intersects(*start..*end))
+                })
+                .collect::<Vec<_>>();
+            merged_matches.extend(collect(changed_matches));
+            merged_matches.sort();
+
+            cursor.reset();
+            let expected_matches =
+                collect(cursor.matches(&query, new_tree.root_node(), new_source.as_bytes()));
+            assert_eq!(
+                merged_matches, expected_matches,
+                "replacing {old:?} with {new:?}, in ranges {ranges:?}",
+            );
+
+            source = new_source;
+            tree = new_tree;
+        }
+        assert!(partial_update_count > 0);
+    });
+}
+
 #[test]
 fn test_grammar_with_aliased_literal_query() {
     // module.exports = grammar({
@@ -5621,3 +6407,60 @@ const foo = [
     assert_eq!(matches.len(), 1);
     assert_eq!(matches[0].1, captures);
 }
+
+#[test]
+fn test_lint_query() {
+    let language = get_language("javascript");
+    let source = indoc! {r#"
+        (identifier) @variable
+        ((identifier) @constant
+         (#match? @constant "^[A-Z]"))
+        (call_expression
+          function: (identifier) @function)
+        (call_expression function: (identifier) @function)
+        (identifer) @variable
+        (call_expression callee: (identifier)) @call
+        "const" @keyword.fancy
+        (identifier (identifier)) @variable
+    "#};
+    let recognized_names = ["constant", "function", "keyword", "variable"]
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let diagnostics = lint_query(&language, source, None, &recognized_names).unwrap();
+
+    let warning = |row, column, message: &str| QueryDiagnostic {
+        severity: Severity::Warning,
+        start: ParsePoint { row, column },
+        message: message.to_string(),
+    };
+    assert_eq!(
+        diagnostics,
+        [
+            warning(
+                1,
+                0,
+                "Pattern can never match, because the identical pattern at 1:1 takes precedence"
+            ),
+            warning(
+                5,
+                0,
+                "Pattern can never match, because the identical pattern at 4:1 takes precedence"
+            ),
+            warning(6, 0, "Unknown node type `identifer`"),
+            warning(7, 17, "Unknown field `callee`"),
+            warning(7, 39, "Capture `@call` is not a recognized highlight name"),
+            warning(
+                8,
+                8,
+                "Capture `@keyword.fancy` is not a recognized highlight name"
+            ),
+            QueryDiagnostic {
+                severity: Severity::Error,
+                start: ParsePoint { row: 9, column: 12 },
+                message: "Impossible pattern: `identifier` nodes can't occur inside `identifier` \
+                          nodes in this position"
+                    .to_string(),
+            },
+        ]
+    );
+}
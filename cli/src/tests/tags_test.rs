@@ -4,7 +4,7 @@ use std::{
 };
 
 use tree_sitter::Point;
-use tree_sitter_tags::{c_lib as c, Error, TagsConfiguration, TagsContext};
+use tree_sitter_tags::{c_lib as c, Error, Tag, TagsConfiguration, TagsContext};
 
 use super::helpers::{
     allocations,
@@ -293,6 +293,62 @@ fn test_tags_cancellation() {
     });
 }
 
+#[test]
+fn test_tags_resumed_after_cancellation() {
+    let language = get_language("python");
+    let tags_config = TagsConfiguration::new(language, PYTHON_TAG_QUERY, "").unwrap();
+    let mut tag_context = TagsContext::new();
+
+    let source = br#"
+class Customer:
+    """
+    Data about a customer
+    """
+
+    def age(self):
+        return compute_age(self.id)
+"#
+    .repeat(500);
+
+    let full_tags = tag_context
+        .generate_tags(&tags_config, &source, None)
+        .unwrap()
+        .0
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let first = tag_context
+        .generate_tags_from(&tags_config, &source, 0, &mut |progress| {
+            progress.tag_count >= full_tags.len() / 2
+        })
+        .unwrap();
+    let resume_byte = first.resume_byte.expect("expected tagging to stop midway");
+    assert!(first.tags.len() >= full_tags.len() / 2);
+    assert!(first.tags.len() < full_tags.len());
+
+    let second = tag_context
+        .generate_tags_from(&tags_config, &source, resume_byte, &mut |_| false)
+        .unwrap();
+    assert_eq!(second.resume_byte, None);
+
+    let summarize = |tags: &[Tag]| {
+        tags.iter()
+            .map(|tag| {
+                (
+                    tag.name_range.clone(),
+                    tag.syntax_type_id,
+                    tag.is_definition,
+                    tag.docs.clone(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        summarize(&[first.tags, second.tags].concat()),
+        summarize(&full_tags)
+    );
+}
+
 #[test]
 fn test_invalid_capture() {
     let language = get_language("python");
@@ -301,6 +357,162 @@ fn test_invalid_capture() {
     assert_eq!(e, Error::InvalidCapture("method".to_string()));
 }
 
+#[test]
+fn test_tags_custom_kinds() {
+    let language = get_language("python");
+    let tags_config = TagsConfiguration::new(
+        language,
+        r#"
+        (decorated_definition
+          (decorator)
+          definition: (function_definition name: (identifier) @name)) @definition.route
+
+        (function_definition name: (identifier) @name) @definition.function
+
+        (call function: (identifier) @name) @reference.call
+
+        (call
+          function: (attribute attribute: (identifier) @name)
+          (#eq? @name "redirect")) @reference.route.redirect
+        "#,
+        "",
+    )
+    .unwrap();
+
+    assert_eq!(
+        tags_config.syntax_type_names().collect::<Vec<_>>(),
+        ["route", "function", "call", "route.redirect"]
+    );
+    assert_eq!(tags_config.syntax_type_id("route"), Some(0));
+    assert_eq!(tags_config.syntax_type_id("route.redirect"), Some(3));
+    assert_eq!(tags_config.syntax_type_id("class"), None);
+
+    let source = br#"
+@app.get("/")
+def index():
+    return flask.redirect(home())
+"#;
+    let tags = TagsContext::new()
+        .generate_tags(&tags_config, source, None)
+        .unwrap()
+        .0
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        tags.iter()
+            .map(|t| (
+                substr(source, &t.name_range),
+                t.syntax_type_name(&tags_config),
+                t.is_definition,
+            ))
+            .collect::<Vec<_>>(),
+        &[
+            ("index", "route", true),
+            ("redirect", "route.redirect", false),
+            ("home", "call", false),
+        ]
+    );
+
+    let e = TagsConfiguration::new(get_language("python"), "(identifier) @definition.", "")
+        .expect_err("expected InvalidCapture error");
+    assert_eq!(e, Error::InvalidCapture("definition.".to_string()));
+}
+
+#[test]
+fn test_tags_docs_attachment() {
+    let language = get_language("javascript");
+    let tags_query = r#"
+    (
+      (comment)* @doc .
+      (function_declaration name: (identifier) @name) @definition.function
+      (#select-adjacent! @doc @definition.function)
+      (#strip-lines! @doc "^//\\s*")
+    )
+
+    (
+      (lexical_declaration (variable_declarator name: (identifier) @name)) @definition.constant .
+      (comment)* @doc
+      (#select-adjacent! @doc @definition.constant)
+      (#allow-trailing! @doc)
+      (#strip-lines! @doc "^//\\s*")
+      (#join! @doc " ")
+    )
+    "#;
+    let source = br"
+// Separated by a blank line
+
+function gap() {}
+
+// First line
+// second line
+function joined() {}
+
+const value = 1; // Trailing docs
+// and more
+
+
+// Not for `value`
+";
+
+    let docs = |tags_config: &TagsConfiguration| {
+        TagsContext::new()
+            .generate_tags(tags_config, source, None)
+            .unwrap()
+            .0
+            .map(|tag| {
+                let tag = tag.unwrap();
+                (substr(source, &tag.name_range), tag.docs)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut tags_config = TagsConfiguration::new(language.clone(), tags_query, "").unwrap();
+    assert_eq!(
+        docs(&tags_config),
+        &[
+            ("gap", None),
+            ("joined", Some("First line\nsecond line".to_string())),
+            ("value", Some("Trailing docs and more".to_string())),
+        ]
+    );
+
+    // The predicates take precedence over the configuration.
+    tags_config.docs.max_gap = 1;
+    tags_config.docs.separator = " ".to_string();
+    assert_eq!(
+        docs(&tags_config),
+        &[
+            ("gap", Some("Separated by a blank line".to_string())),
+            ("joined", Some("First line second line".to_string())),
+            ("value", Some("Trailing docs and more".to_string())),
+        ]
+    );
+
+    let tags_query = tags_query.replace("(#allow-trailing! @doc)", "").replace(
+        "(#select-adjacent! @doc @definition.function)",
+        "(#select-adjacent! @doc @definition.function) (#max-gap! @doc 1)",
+    );
+    let tags_config = TagsConfiguration::new(language.clone(), &tags_query, "").unwrap();
+    let tags = docs(&tags_config);
+    assert_eq!(
+        tags[0],
+        ("gap", Some("Separated by a blank line".to_string()))
+    );
+    assert_eq!(tags[2], ("value", None));
+
+    let e = TagsConfiguration::new(
+        language,
+        "((comment) @doc . (function_declaration name: (identifier) @name) @definition.function
+          (#max-gap! @doc many))",
+        "",
+    )
+    .expect_err("expected InvalidPredicate error");
+    assert_eq!(
+        e,
+        Error::InvalidPredicate("#max-gap! expects a number of lines, got `many`".to_string())
+    );
+}
+
 #[test]
 fn test_tags_with_parse_error() {
     let language = get_language("python");
@@ -3,7 +3,7 @@ use tree_sitter_highlight::{Highlight, Highlighter};
 
 use super::helpers::fixtures::{get_highlight_config, get_language, test_loader};
 use crate::{
-    query_testing::{parse_position_comments, Assertion, Utf8Point},
+    query_testing::{check_assertions, parse_position_comments, Assertion, Utf8Point},
     test_highlight::get_highlight_positions,
 };
 
@@ -67,4 +67,32 @@ fn test_highlight_test_with_basic_test() {
             (Utf8Point::new(8, 11), Utf8Point::new(8, 19), Highlight(2)), // "function"
         ]
     );
+    let highlight_names = ["function", "variable", "keyword"];
+    let captures = highlight_positions
+        .iter()
+        .map(|(start, end, highlight)| (*start, *end, highlight_names[highlight.0]))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        check_assertions(&assertions, &captures, "highlight").unwrap(),
+        6
+    );
+
+    // A span must be covered by the expected highlight at every column.
+    let failure = check_assertions(
+        &[Assertion::new(1, 5, 4, false, String::from("function"))],
+        &captures,
+        "highlight",
+    )
+    .unwrap_err();
+    assert_eq!(failure.position, Utf8Point::new(1, 7));
+    assert!(failure.actual_capture_names.is_empty());
+
+    let failure = check_assertions(
+        &[Assertion::new(4, 9, 1, true, String::from("variable"))],
+        &captures,
+        "highlight",
+    )
+    .unwrap_err();
+    assert_eq!(failure.position, Utf8Point::new(4, 9));
+    assert_eq!(failure.actual_capture_names, &["variable"]);
 }
@@ -3,7 +3,7 @@ use tree_sitter_tags::TagsContext;
 
 use super::helpers::fixtures::{get_language, get_tags_config};
 use crate::{
-    query_testing::{parse_position_comments, Assertion, Utf8Point},
+    query_testing::{check_assertions, parse_position_comments, Assertion, Utf8Point},
     test_tags::get_tag_positions,
 };
 
@@ -59,4 +59,26 @@ fn test_tags_test_with_basic_test() {
             ),
         ]
     );
+    assert_eq!(
+        check_assertions(&assertions, &tag_positions, "tag").unwrap(),
+        4
+    );
+
+    let failure = check_assertions(
+        &[Assertion::new(
+            3,
+            8,
+            1,
+            false,
+            String::from("definition.function"),
+        )],
+        &tag_positions,
+        "tag",
+    )
+    .unwrap_err();
+    assert_eq!(failure.actual_capture_names, &["reference.call"]);
+    assert_eq!(
+        failure.to_string(),
+        "Failure - row: 3, column: 8, expected tag 'definition.function', actual tags: 'reference.call'"
+    );
 }
@@ -1,7 +1,9 @@
 use std::{iter, sync::Arc};
 
 use streaming_iterator::StreamingIterator;
-use tree_sitter::{Language, Node, Parser, Point, Query, QueryCursor, TextProvider, Tree};
+use tree_sitter::{
+    ChunkProvider, Language, Node, Parser, Point, Query, QueryCursor, StrChunks, TextProvider, Tree,
+};
 
 use crate::tests::helpers::fixtures::get_language;
 
@@ -172,3 +174,112 @@ fn test_text_provider_callback_with_owned_arc_of_bytes_slice() {
         },
     );
 }
+
+/// Split the text into chunks of about `chunk_len` bytes, at character boundaries, the way that a
+/// rope stores it.
+fn rope_chunks(text: &str, chunk_len: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = chunk_len.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end += 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+fn node_text_with<'a>(node: Node, chunk_provider: impl ChunkProvider<'a>) -> String {
+    node.text_with(chunk_provider).map(Result::unwrap).collect()
+}
+
+fn all_nodes(tree: &Tree) -> Vec<Node<'_>> {
+    let mut nodes = Vec::new();
+    let mut cursor = tree.walk();
+    'outer: loop {
+        nodes.push(cursor.node());
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'outer;
+            }
+        }
+    }
+    nodes
+}
+
+#[test]
+fn test_node_text_with_rope_chunks() {
+    let text = "int main() {\n  // héllo, wörld ✓\n  return \"ünïcode\";\n}\n";
+    let (tree, _) = parse_text(text);
+    let nodes = all_nodes(&tree);
+
+    for chunk_len in [1, 2, 3, 5, 8, 1024] {
+        let chunks = StrChunks::new(rope_chunks(text, chunk_len));
+        for node in &nodes {
+            assert_eq!(
+                node_text_with(*node, &chunks),
+                node.utf8_text(text.as_bytes()).unwrap(),
+                "chunk length {chunk_len}, node {node:?}",
+            );
+        }
+    }
+
+    // The text of a node that spans several chunks is borrowed from each of them.
+    let root = tree.root_node();
+    assert_eq!(root.byte_range(), 0..text.len());
+    let chunks = rope_chunks(text, 4);
+    let pieces = root
+        .text_with(&StrChunks::new(chunks.iter().copied()))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(pieces, chunks);
+    assert!(pieces
+        .iter()
+        .zip(&chunks)
+        .all(|(piece, chunk)| piece.as_ptr() == chunk.as_ptr()));
+}
+
+#[test]
+fn test_node_text_with_byte_slice_and_callback() {
+    let text = "// comment\nint x = 1;\n";
+    let (tree, _) = parse_text(text);
+    let chunks = rope_chunks(text, 3);
+    let chunk_at_byte = |byte: usize| {
+        let mut start = 0;
+        for chunk in &chunks {
+            if byte < start + chunk.len() {
+                return Some((chunk.as_bytes(), start));
+            }
+            start += chunk.len();
+        }
+        None
+    };
+
+    for node in all_nodes(&tree) {
+        let expected = node.utf8_text(text.as_bytes()).unwrap();
+        assert_eq!(node_text_with(node, text.as_bytes()), expected);
+        assert_eq!(node_text_with(node, chunk_at_byte), expected);
+    }
+}
+
+#[test]
+fn test_node_text_with_zero_width_node() {
+    let text = "int x";
+    let (tree, _) = parse_text(text);
+    let missing_node = all_nodes(&tree).into_iter().find(Node::is_missing).unwrap();
+    assert_eq!(missing_node.byte_range(), text.len()..text.len());
+
+    assert_eq!(missing_node.text_with(text.as_bytes()).count(), 0);
+    assert_eq!(
+        missing_node
+            .text_with(&StrChunks::new(rope_chunks(text, 2)))
+            .count(),
+        0
+    );
+}
@@ -1,9 +1,22 @@
-use std::str;
-
-use tree_sitter::{InputEdit, Parser, Point, Range, Tree};
-
-use super::helpers::fixtures::get_language;
-use crate::{fuzz::edits::Edit, parse::perform_edit, tests::invert_edit};
+use std::{str, thread, time::Instant};
+
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{
+    edits_between, InputEdit, Node, Parser, Point, Query, QueryCursor, Range, SharedTree,
+    SyntaxError, SyntaxErrorKind, Traversal, Tree, TreeDeserializeError,
+};
+
+use serde_json::json;
+
+use super::helpers::fixtures::{fixtures_dir, get_language, get_test_language};
+use crate::{
+    fuzz::{
+        corpus_test::check_consistent_sizes,
+        edits::{align_edit_to_char_boundaries, Edit},
+    },
+    parse::{perform_edit, write_dot_tree, write_json_tree, RangeFormat},
+    tests::{generate_parser, get_random_edit, invert_edit, Rand},
+};
 
 #[test]
 fn test_tree_edit() {
@@ -298,6 +311,143 @@ fn test_tree_edit_with_included_ranges() {
     );
 }
 
+#[test]
+fn test_edits_between() {
+    assert_eq!(edits_between("a\nb\n", "a\nb\n"), []);
+
+    // Insertion at the end of the text
+    assert_eq!(
+        edits_between("a\nb", "a\nbc\n"),
+        [InputEdit {
+            start_byte: 3,
+            old_end_byte: 3,
+            new_end_byte: 5,
+            start_position: Point::new(1, 1),
+            old_end_position: Point::new(1, 1),
+            new_end_position: Point::new(2, 0),
+        }]
+    );
+
+    // Replacement within a line that ends with CRLF
+    assert_eq!(
+        edits_between("one\r\ntwo\r\nthree\r\n", "one\r\nTWO\r\nthree\r\n"),
+        [InputEdit {
+            start_byte: 5,
+            old_end_byte: 8,
+            new_end_byte: 8,
+            start_position: Point::new(1, 0),
+            old_end_position: Point::new(1, 3),
+            new_end_position: Point::new(1, 3),
+        }]
+    );
+
+    // Replacement of a multi-byte character that shares its first byte with the new one
+    assert_eq!(
+        edits_between("caf\u{e9}\n", "caf\u{e8}\n"),
+        [InputEdit {
+            start_byte: 3,
+            old_end_byte: 5,
+            new_end_byte: 5,
+            start_position: Point::new(0, 3),
+            old_end_position: Point::new(0, 5),
+            new_end_position: Point::new(0, 5),
+        }]
+    );
+
+    // Separate changes, where positions are relative to the old text
+    assert_eq!(
+        edits_between("a\nb\nc\nd\n", "b\nc\nD\ne\n"),
+        [
+            InputEdit {
+                start_byte: 0,
+                old_end_byte: 2,
+                new_end_byte: 0,
+                start_position: Point::new(0, 0),
+                old_end_position: Point::new(1, 0),
+                new_end_position: Point::new(0, 0),
+            },
+            InputEdit {
+                start_byte: 6,
+                old_end_byte: 7,
+                new_end_byte: 9,
+                start_position: Point::new(3, 0),
+                old_end_position: Point::new(3, 1),
+                new_end_position: Point::new(4, 1),
+            },
+        ]
+    );
+
+    // Deletion of the whole text
+    assert_eq!(
+        edits_between("ab\nc", ""),
+        [InputEdit {
+            start_byte: 0,
+            old_end_byte: 4,
+            new_end_byte: 0,
+            start_position: Point::new(0, 0),
+            old_end_position: Point::new(1, 1),
+            new_end_position: Point::new(0, 0),
+        }]
+    );
+}
+
+#[test]
+fn test_tree_edit_all_with_edits_between() {
+    let sources = [
+        (
+            "javascript",
+            "const greeting = \"h\u{e9}llo\";\n\nfunction greet(name) {\n  // \u{1f44b}\n  return `${greeting}, ${name}`;\n}\n\ngreet(\"w\u{f6}rld\");\n",
+        ),
+        (
+            "javascript",
+            "if (a) {\r\n  b();\r\n} else {\r\n  c(\"\u{e9}\");\r\n}\r\n",
+        ),
+        (
+            "python",
+            "def f(x):\n    y = x + 1\n    return y\n\nclass C:\n    \"\"\"D\u{f6}c.\"\"\"\n    z = [f(i) for i in range(3)]\n",
+        ),
+        (
+            "rust",
+            "fn main() {\r\n    let s = \"\u{4e2d}\u{6587}\";\r\n    println!(\"{s}\");\r\n}",
+        ),
+    ];
+
+    let mut parser = Parser::new();
+    let mut rand = Rand::new(0);
+    for (language_name, source) in sources {
+        parser.set_language(&get_language(language_name)).unwrap();
+        let old_tree = parser.parse(source, None).unwrap();
+
+        for _ in 0..50 {
+            let mut new_source = source.as_bytes().to_vec();
+            for _ in 0..=rand.unsigned(2) {
+                let edit = get_random_edit(&mut rand, &new_source);
+                let edit = align_edit_to_char_boundaries(&new_source, edit);
+                new_source.splice(
+                    edit.position..edit.position + edit.deleted_length,
+                    edit.inserted_text,
+                );
+            }
+            let new_source = String::from_utf8(new_source).unwrap();
+
+            let edits = edits_between(source, &new_source);
+            assert_eq!(apply_edits(source, &new_source, &edits), new_source);
+
+            let mut tree = old_tree.clone();
+            tree.edit_all(&edits);
+            check_consistent_sizes(&tree, new_source.as_bytes());
+
+            let new_tree = parser.parse(&new_source, Some(&tree)).unwrap();
+            let fresh_tree = parser.parse(&new_source, None).unwrap();
+            assert_eq!(
+                node_ranges(&new_tree),
+                node_ranges(&fresh_tree),
+                "language: {language_name}, new source: {new_source:?}",
+            );
+        }
+    }
+}
+
 #[test]
 fn test_tree_cursor() {
     let mut parser = Parser::new();
@@ -730,6 +880,789 @@ fn main() {
     assert_eq!(cursor.node().kind(), "block_comment");
 }
 
+#[test]
+fn test_tree_traversals() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("javascript")).unwrap();
+    let source = r#"
+function greet(name, { greeting = "hello" }) {
+  // Say hi
+  return `${greeting}, ${name}!`;
+}
+
+const people = ["a", 'b', `c${1 + 2}`];
+for (const person of people) greet(person, {});
+"#;
+    let tree = parser.parse(source, None).unwrap();
+    let is_string = |node: &Node| matches!(node.kind(), "string" | "template_string");
+
+    assert_eq!(
+        traversal_steps(tree.preorder()),
+        reference_steps(tree.root_node(), Order::Pre, |_| false)
+    );
+    assert_eq!(
+        traversal_steps(tree.postorder()),
+        reference_steps(tree.root_node(), Order::Post, |_| false)
+    );
+    assert_eq!(
+        traversal_steps(tree.preorder().named_only()),
+        reference_steps(tree.root_node(), Order::Pre, |_| false)
+            .into_iter()
+            .filter(|(node, ..)| node.is_named())
+            .collect::<Vec<_>>()
+    );
+
+    // Pruned nodes are returned, but their descendants aren't.
+    let pruned_preorder = traversal_steps(tree.preorder().prune(is_string));
+    assert_eq!(
+        pruned_preorder,
+        reference_steps(tree.root_node(), Order::Pre, is_string)
+    );
+    assert_eq!(
+        pruned_preorder
+            .iter()
+            .filter(|(node, ..)| is_string(node))
+            .count(),
+        5
+    );
+    assert!(pruned_preorder
+        .iter()
+        .all(|(node, ..)| !node.parent().is_some_and(|parent| is_string(&parent))));
+    assert_eq!(
+        traversal_steps(tree.postorder().prune(is_string).named_only()),
+        reference_steps(tree.root_node(), Order::Post, is_string)
+            .into_iter()
+            .filter(|(node, ..)| node.is_named())
+            .collect::<Vec<_>>()
+    );
+
+    // A traversal from a node stays within it, and reports depths relative to it.
+    let function = tree.root_node().child(0).unwrap();
+    assert_eq!(function.kind(), "function_declaration");
+    let function_preorder = traversal_steps(function.preorder());
+    assert_eq!(function_preorder.len(), function.descendant_count());
+    assert_eq!(
+        function_preorder,
+        reference_steps(function, Order::Pre, |_| false)
+    );
+    assert_eq!(
+        traversal_steps(function.postorder()),
+        reference_steps(function, Order::Post, |_| false)
+    );
+    assert_eq!(function_preorder[0], (function, 0, None));
+    assert_eq!(
+        function_preorder[1..4]
+            .iter()
+            .map(|(node, depth, field_name)| (node.kind(), *depth, *field_name))
+            .collect::<Vec<_>>(),
+        [
+            ("function", 1, None),
+            ("identifier", 1, Some("name")),
+            ("formal_parameters", 1, Some("parameters")),
+        ]
+    );
+}
+
+#[test]
+fn test_tree_errors() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("javascript")).unwrap();
+
+    let tree = parser.parse("if (a b) {\n  c d;\n}\ne f;\n", None).unwrap();
+    assert_eq!(
+        tree.errors()
+            .map(|error| (error.kind, error.context, error.node.start_position()))
+            .collect::<Vec<_>>(),
+        [
+            (
+                SyntaxErrorKind::Error,
+                Some("parenthesized_expression"),
+                Point::new(0, 6)
+            ),
+            (
+                SyntaxErrorKind::Error,
+                Some("statement_block"),
+                Point::new(1, 2)
+            ),
+            (
+                SyntaxErrorKind::Error,
+                Some("expression_statement"),
+                Point::new(3, 2)
+            ),
+        ]
+    );
+    assert_eq!(tree.errors().collect::<Vec<_>>(), all_errors(&tree));
+    assert_eq!(
+        tree.error_ranges(),
+        all_errors(&tree)
+            .iter()
+            .map(|error| error.node.range())
+            .collect::<Vec<_>>()
+    );
+
+    let tree = parser.parse("{\n  a: b,\n  c:\n}\n", None).unwrap();
+    let errors = tree.errors().collect::<Vec<_>>();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, SyntaxErrorKind::Missing("identifier"));
+    assert_eq!(errors[0].context, Some("pair"));
+    assert_eq!(errors, all_errors(&tree));
+
+    let tree = parser.parse("a(b, c);\n", None).unwrap();
+    assert_eq!(tree.errors().count(), 0);
+    assert!(tree.error_ranges().is_empty());
+}
+
+#[test]
+fn test_tree_errors_skip_valid_subtrees() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("javascript")).unwrap();
+
+    let statement = "function f(a, b) { return [a, b].map((x) => x * 2 + `${a}`); }\n";
+    let mut source = statement.repeat(2000);
+    let error_offset = source.len() / 2;
+    source.insert_str(error_offset, "if (a b) {}\n");
+    let tree = parser.parse(&source, None).unwrap();
+
+    let errors = tree.errors().collect::<Vec<_>>();
+    assert_eq!(errors, all_errors(&tree));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].node.start_byte(), error_offset + 6);
+
+    // Only the top-level statements and the nodes around the error are visited, so finding the
+    // errors is much faster than walking the whole tree.
+    let time = |f: &dyn Fn() -> usize| {
+        (0..3)
+            .map(|_| {
+                let start = Instant::now();
+                assert!(f() > 0);
+                start.elapsed()
+            })
+            .min()
+            .unwrap()
+    };
+    let full_walk_time = time(&|| tree.preorder().count());
+    let errors_time = time(&|| tree.errors().count());
+    assert!(
+        errors_time * 10 < full_walk_time,
+        "finding errors took {errors_time:?}, walking the whole tree took {full_walk_time:?}"
+    );
+}
+
+#[test]
+fn test_tree_json_output() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"{
+            "name": "test_json_output",
+            "extras": [{"type": "PATTERN", "value": "\\s"}],
+            "rules": {
+                "assignment": {
+                    "type": "SEQ",
+                    "members": [
+                        {
+                            "type": "FIELD",
+                            "name": "left",
+                            "content": {"type": "SYMBOL", "name": "identifier"}
+                        },
+                        {"type": "STRING", "value": "="},
+                        {"type": "SYMBOL", "name": "identifier"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#,
+    )
+    .unwrap();
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&parser_name, &parser_code, None))
+        .unwrap();
+    let mut json_output = |source_code: &str, no_ranges: bool| {
+        let tree = parser.parse(source_code, None).unwrap();
+        let mut output = Vec::new();
+        write_json_tree(&mut output, &tree, no_ranges).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.ends_with('\n'));
+        serde_json::from_str::<serde_json::Value>(&output).unwrap()
+    };
+
+    assert_eq!(
+        json_output("a = b", false),
+        json!({
+            "type": "assignment",
+            "named": true,
+            "startByte": 0,
+            "endByte": 5,
+            "startPosition": {"row": 0, "column": 0},
+            "endPosition": {"row": 0, "column": 5},
+            "children": [
+                {
+                    "type": "identifier",
+                    "named": true,
+                    "field": "left",
+                    "startByte": 0,
+                    "endByte": 1,
+                    "startPosition": {"row": 0, "column": 0},
+                    "endPosition": {"row": 0, "column": 1}
+                },
+                {
+                    "type": "=",
+                    "named": false,
+                    "startByte": 2,
+                    "endByte": 3,
+                    "startPosition": {"row": 0, "column": 2},
+                    "endPosition": {"row": 0, "column": 3}
+                },
+                {
+                    "type": "identifier",
+                    "named": true,
+                    "startByte": 4,
+                    "endByte": 5,
+                    "startPosition": {"row": 0, "column": 4},
+                    "endPosition": {"row": 0, "column": 5}
+                }
+            ]
+        })
+    );
+
+    assert_eq!(
+        json_output("a =", true),
+        json!({
+            "type": "assignment",
+            "named": true,
+            "children": [
+                {"type": "identifier", "named": true, "field": "left"},
+                {"type": "=", "named": false},
+                {"type": "identifier", "named": true, "isMissing": true}
+            ]
+        })
+    );
+
+    assert_eq!(
+        json_output("a = = b", true),
+        json!({
+            "type": "assignment",
+            "named": true,
+            "children": [
+                {"type": "identifier", "named": true, "field": "left"},
+                {"type": "=", "named": false},
+                {
+                    "type": "ERROR",
+                    "named": true,
+                    "isError": true,
+                    "children": [{"type": "=", "named": false}]
+                },
+                {"type": "identifier", "named": true}
+            ]
+        })
+    );
+}
+
+#[test]
+fn test_tree_dot_tree_output() {
+    let (parser_name, parser_code) = generate_parser(
+        r#"{
+            "name": "test_dot_tree_output",
+            "extras": [{"type": "PATTERN", "value": "\\s"}],
+            "rules": {
+                "source_file": {
+                    "type": "REPEAT",
+                    "content": {"type": "SYMBOL", "name": "assignment"}
+                },
+                "assignment": {
+                    "type": "SEQ",
+                    "members": [
+                        {
+                            "type": "FIELD",
+                            "name": "left",
+                            "content": {"type": "SYMBOL", "name": "identifier"}
+                        },
+                        {"type": "STRING", "value": "="},
+                        {
+                            "type": "FIELD",
+                            "name": "right",
+                            "content": {
+                                "type": "CHOICE",
+                                "members": [
+                                    {"type": "SYMBOL", "name": "identifier"},
+                                    {"type": "SYMBOL", "name": "string"}
+                                ]
+                            }
+                        }
+                    ]
+                },
+                "string": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "STRING", "value": "\""},
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "STRING", "value": "\""}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#,
+    )
+    .unwrap();
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&parser_name, &parser_code, None))
+        .unwrap();
+    let fixtures_dir = fixtures_dir().join("dot_tree");
+    let source_code = std::fs::read(fixtures_dir.join("input.txt")).unwrap();
+    let tree = parser.parse(&source_code, None).unwrap();
+
+    // The second line contains an `ERROR` node, and the third line a `MISSING` one.
+    for (file_name, byte_range) in [("full.dot", None), ("range.dot", Some(6..14))] {
+        let mut output = Vec::new();
+        write_dot_tree(&mut output, &tree, byte_range.as_ref(), RangeFormat::Points).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            std::fs::read_to_string(fixtures_dir.join(file_name)).unwrap(),
+            "{file_name}"
+        );
+    }
+}
+
+#[test]
+fn test_tree_serialization_round_trip() {
+    let mut parser = Parser::new();
+    for (language_name, source) in [
+        (
+            "javascript",
+            "const a = f(b, c);\n// comment\nclass A { m() { return `x${y}`; } }\n",
+        ),
+        ("python", "def f(x):\n    if x:\n        return [1,\n  2]\n"),
+        ("rust", "fn main() {\n    let x = r#\"raw\"#;\n}\n"),
+        ("c", "int main() { return 0 }\n"),
+        ("html", "<div><p>text</div>\n<script>let x</script>\n"),
+    ] {
+        let language = get_language(language_name);
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+
+        let bytes = tree.to_bytes();
+        let deserialized_tree = Tree::from_bytes(&language, source.len(), &bytes).unwrap();
+        assert_eq!(
+            node_summaries(&deserialized_tree),
+            node_summaries(&tree),
+            "{language_name}"
+        );
+        assert_eq!(deserialized_tree.included_ranges(), tree.included_ranges());
+        assert_eq!(deserialized_tree.to_bytes(), bytes, "{language_name}");
+
+        // The deserialized tree can be reused for incremental parsing, like the original one.
+        let edit = Edit {
+            position: source.find('\n').unwrap(),
+            deleted_length: 0,
+            inserted_text: b" x".to_vec(),
+        };
+        let mut source_code = source.as_bytes().to_vec();
+        let mut tree = tree;
+        perform_edit(&mut tree, &mut source_code, &edit).unwrap();
+        let mut deserialized_source_code = source.as_bytes().to_vec();
+        let mut deserialized_tree = deserialized_tree;
+        perform_edit(&mut deserialized_tree, &mut deserialized_source_code, &edit).unwrap();
+        let new_tree = parser.parse(&source_code, Some(&tree)).unwrap();
+        let new_deserialized_tree = parser
+            .parse(&deserialized_source_code, Some(&deserialized_tree))
+            .unwrap();
+        assert_eq!(
+            node_summaries(&new_deserialized_tree),
+            node_summaries(&new_tree),
+            "{language_name}"
+        );
+    }
+}
+
+#[test]
+fn test_tree_deserialization_errors() {
+    let mut parser = Parser::new();
+    let language = get_language("javascript");
+    parser.set_language(&language).unwrap();
+    let source = "function f(a, b) {\n  return a + b\n}\nf(1, `${2}`);\n";
+    let tree = parser.parse(source, None).unwrap();
+    let bytes = tree.to_bytes();
+
+    assert_eq!(
+        Tree::from_bytes(&language, source.len(), b"").unwrap_err(),
+        TreeDeserializeError::Format
+    );
+    assert_eq!(
+        Tree::from_bytes(&language, source.len(), &bytes[..bytes.len() - 1]).unwrap_err(),
+        TreeDeserializeError::Format
+    );
+    let mut other_version = bytes.clone();
+    other_version[4] += 1;
+    assert_eq!(
+        Tree::from_bytes(&language, source.len(), &other_version).unwrap_err(),
+        TreeDeserializeError::Version
+    );
+    assert_eq!(
+        Tree::from_bytes(&language, source.len() - 2, &bytes).unwrap_err(),
+        TreeDeserializeError::Range
+    );
+    assert!(Tree::from_bytes(&get_language("json"), source.len(), &bytes).is_err());
+
+    // Corrupted bytes are either rejected, or produce a tree that can be used like any other.
+    let mut rand = Rand::new(0);
+    for _ in 0..1000 {
+        let mut corrupted_bytes = bytes.clone();
+        match rand.unsigned(2) {
+            0 => {
+                for _ in 0..=rand.unsigned(3) {
+                    let index = rand.unsigned(bytes.len() - 1);
+                    corrupted_bytes[index] = rand.unsigned(u8::MAX as usize) as u8;
+                }
+            }
+            1 => corrupted_bytes.truncate(rand.unsigned(bytes.len() - 1)),
+            _ => {
+                let index = rand.unsigned(bytes.len());
+                corrupted_bytes.insert(index, rand.unsigned(u8::MAX as usize) as u8);
+            }
+        }
+        if let Ok(tree) = Tree::from_bytes(&language, source.len(), &corrupted_bytes) {
+            node_summaries(&tree);
+            let _ = tree.root_node().to_sexp();
+            parser.parse(source, Some(&tree)).unwrap();
+        }
+    }
+}
+
+#[test]
+fn test_tree_deserialization_of_leaves_with_non_terminal_symbols() {
+    let mut parser = Parser::new();
+    let language = get_language("javascript");
+    parser.set_language(&language).unwrap();
+    let source = "a;\n";
+    let tree = parser.parse(source, None).unwrap();
+    let mut bytes = tree.to_bytes();
+
+    // The subtrees come after the header and the included ranges, and the first one is the
+    // leaf at the start of the document.
+    let range_count = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+    let leaf_offset = 16 + 24 * range_count;
+    let program = language.id_for_node_kind("program", true);
+    bytes[leaf_offset..leaf_offset + 2].copy_from_slice(&program.to_le_bytes());
+    assert_eq!(
+        Tree::from_bytes(&language, source.len(), &bytes).unwrap_err(),
+        TreeDeserializeError::Symbol
+    );
+}
+
+#[test]
+fn test_tree_deserialization_of_parents_with_terminal_symbols() {
+    let mut parser = Parser::new();
+    let language = get_language("javascript");
+    parser.set_language(&language).unwrap();
+    let source = "a;\n";
+    let tree = parser.parse(source, None).unwrap();
+    let mut bytes = tree.to_bytes();
+
+    // The last subtree is the root, whose symbol, flags, child count, production id, parse
+    // state and dynamic precedence take 16 bytes.
+    let root_offset = bytes.len() - 16;
+    let identifier = language.id_for_node_kind("identifier", true);
+    bytes[root_offset..root_offset + 2].copy_from_slice(&identifier.to_le_bytes());
+    assert_eq!(
+        Tree::from_bytes(&language, source.len(), &bytes).unwrap_err(),
+        TreeDeserializeError::Symbol
+    );
+
+    // A parent can still have no children, like the root of an empty document.
+    let tree = parser.parse("", None).unwrap();
+    assert_eq!(tree.root_node().child_count(), 0);
+    let deserialized = Tree::from_bytes(&language, 0, &tree.to_bytes()).unwrap();
+    assert_eq!(deserialized.root_node().kind(), "program");
+    assert_eq!(deserialized.to_bytes(), tree.to_bytes());
+}
+
+#[test]
+fn test_tree_memory_usage() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("javascript")).unwrap();
+
+    let statement = "let a = f(b, c);\n";
+    let usages = [1, 10, 100].map(|count| {
+        let tree = parser.parse(statement.repeat(count), None).unwrap();
+        tree.memory_usage()
+    });
+    assert!(usages[0] > 0);
+    assert!(usages[1] > 5 * usages[0], "{usages:?}");
+    assert!(usages[2] > 5 * usages[1], "{usages:?}");
+
+    // Trees that share their nodes each count all of them
+    let source = statement.repeat(100);
+    let tree = parser.parse(&source, None).unwrap();
+    let tree_copy = tree.clone();
+    assert_eq!(tree_copy.memory_usage(), tree.memory_usage());
+
+    let new_source = source.replacen("let a", "let abc", 1);
+    let mut edited_tree = tree.clone();
+    edited_tree.edit_all(&edits_between(&source, &new_source));
+    let new_tree = parser.parse(&new_source, Some(&edited_tree)).unwrap();
+    drop((tree, tree_copy, edited_tree));
+
+    // The nodes that the new tree reused are still counted once the old trees are dropped
+    assert_eq!(
+        new_tree.memory_usage(),
+        parser.parse(&new_source, None).unwrap().memory_usage()
+    );
+}
+
+#[test]
+fn test_shared_tree_copy_on_edit() {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language("javascript")).unwrap();
+    let source = "let a = b;\n";
+    let mut tree = SharedTree::new(parser.parse(source, None).unwrap());
+
+    // A tree that isn't shared isn't copied
+    let address = std::ptr::from_ref::<Tree>(&tree);
+    assert!(std::ptr::eq(tree.make_mut(), address));
+
+    // A tree that is shared is copied, and the other clones don't see the edit
+    let original = tree.clone();
+    assert!(tree.ptr_eq(&original));
+    let new_source = "let abc = b;\n";
+    for edit in edits_between(source, new_source) {
+        tree.edit(&edit);
+    }
+    assert!(!tree.ptr_eq(&original));
+    assert!(tree.root_node().has_changes());
+    assert!(!original.root_node().has_changes());
+    assert_eq!(original.root_node().end_byte(), source.len());
+    assert_eq!(tree.root_node().end_byte(), new_source.len());
+
+    let new_tree = parser.parse(new_source, Some(&tree)).unwrap();
+    assert_eq!(
+        new_tree.root_node().to_sexp(),
+        original.root_node().to_sexp()
+    );
+    assert_eq!(
+        original.into_tree().root_node().to_sexp(),
+        new_tree.root_node().to_sexp()
+    );
+}
+
+#[test]
+fn test_shared_tree_queries_on_multiple_threads() {
+    let language = get_language("javascript");
+    let source = "function one() { return 1; }\nfunction two() { return two(); }\n".repeat(20);
+    let query = Query::new(&language, "(function_declaration name: (identifier) @name)").unwrap();
+
+    let mut parser = Parser::new();
+    parser.set_language(&language).unwrap();
+    let tree = SharedTree::new(parser.parse(&source, None).unwrap());
+
+    let function_names = |tree: &Tree, source: &str| {
+        let mut cursor = QueryCursor::new();
+        let mut names = Vec::new();
+        let mut captures = cursor.captures(&query, tree.root_node(), source.as_bytes());
+        while let Some((m, i)) = captures.next() {
+            let node = m.captures[*i].node;
+            names.push(node.utf8_text(source.as_bytes()).unwrap().to_string());
+        }
+        names
+    };
+    let expected_names = function_names(&tree, &source);
+    assert_eq!(expected_names.len(), 40);
+
+    // Run queries over clones of the shared tree on several threads, while the main thread
+    // repeatedly edits and reparses its own clone.
+    let new_tree = thread::scope(|scope| {
+        let readers = (0..8)
+            .map(|_| {
+                let tree = tree.clone();
+                let (source, expected_names) = (&source, &expected_names);
+                scope.spawn(move || {
+                    for _ in 0..50 {
+                        assert_eq!(function_names(&tree, source), *expected_names);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut writer = tree.clone();
+        let mut writer_source = source.clone();
+        while writer_source.contains("function one") {
+            let new_source = writer_source.replacen("function one", "function three", 1);
+            for edit in edits_between(&writer_source, &new_source) {
+                writer.edit(&edit);
+            }
+            assert!(!writer.ptr_eq(&tree));
+            assert!(writer.root_node().has_changes());
+
+            writer = parser.parse(&new_source, Some(&writer)).unwrap().into();
+            writer_source = new_source;
+        }
+
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(function_names(&writer, &writer_source).len(), 40);
+        writer.into_tree()
+    });
+
+    assert_eq!(function_names(&tree, &source), expected_names);
+    assert_eq!(
+        new_tree.root_node().to_sexp(),
+        parser
+            .parse(source.replace("function one", "function three"), None)
+            .unwrap()
+            .root_node()
+            .to_sexp()
+    );
+}
+
+/// The syntax errors in the tree, found by visiting every node.
+fn all_errors(tree: &Tree) -> Vec<SyntaxError<'_>> {
+    tree.preorder()
+        .prune(Node::is_error)
+        .filter(|node| node.is_error() || node.is_missing())
+        .map(|node| {
+            let mut context = node.parent();
+            while context.is_some_and(|context| !context.is_named()) {
+                context = context.unwrap().parent();
+            }
+            SyntaxError {
+                node,
+                kind: if node.is_error() {
+                    SyntaxErrorKind::Error
+                } else {
+                    SyntaxErrorKind::Missing(node.kind())
+                },
+                context: context.map(|context| context.kind()),
+            }
+        })
+        .collect()
+}
+
+/// Apply edits that are relative to `old_text`, taking the text that each one inserts from
+/// `new_text`.
+fn apply_edits(old_text: &str, new_text: &str, edits: &[InputEdit]) -> String {
+    let mut inserted_texts = Vec::new();
+    let (mut inserted_len, mut deleted_len) = (0, 0);
+    for edit in edits {
+        let start = edit.start_byte + inserted_len - deleted_len;
+        inserted_texts.push(&new_text[start..start + edit.new_end_byte - edit.start_byte]);
+        inserted_len += edit.new_end_byte - edit.start_byte;
+        deleted_len += edit.old_end_byte - edit.start_byte;
+    }
+
+    let mut result = old_text.to_string();
+    for (edit, inserted_text) in edits.iter().zip(inserted_texts).rev() {
+        result.replace_range(edit.start_byte..edit.old_end_byte, inserted_text);
+    }
+    result
+}
+
+/// The kind and range of each of the tree's nodes, in pre-order.
+fn node_ranges(tree: &Tree) -> Vec<(&'static str, Range)> {
+    tree.preorder()
+        .map(|node| (node.kind(), node.range()))
+        .collect()
+}
+
+/// Each node that a traversal returns, along with its depth and field name.
+fn traversal_steps<'tree, P: FnMut(&Node<'tree>) -> bool>(
+    mut traversal: Traversal<'tree, P>,
+) -> Vec<(Node<'tree>, u32, Option<&'static str>)> {
+    let mut steps = Vec::new();
+    while let Some(node) = traversal.next() {
+        steps.push((node, traversal.depth(), traversal.field_name()));
+    }
+    steps
+}
+
+#[derive(Clone, Copy)]
+enum Order {
+    Pre,
+    Post,
+}
+
+/// The steps that a traversal of the given node should take, computed recursively.
+fn reference_steps<'tree>(
+    node: Node<'tree>,
+    order: Order,
+    prune: impl Fn(&Node<'tree>) -> bool,
+) -> Vec<(Node<'tree>, u32, Option<&'static str>)> {
+    fn visit<'tree>(
+        node: Node<'tree>,
+        depth: u32,
+        field_name: Option<&'static str>,
+        order: Order,
+        prune: &dyn Fn(&Node<'tree>) -> bool,
+        steps: &mut Vec<(Node<'tree>, u32, Option<&'static str>)>,
+    ) {
+        if matches!(order, Order::Pre) {
+            steps.push((node, depth, field_name));
+        }
+        if !prune(&node) {
+            for i in 0..node.child_count() {
+                let field_name = node.field_name_for_child(i as u32);
+                visit(
+                    node.child(i).unwrap(),
+                    depth + 1,
+                    field_name,
+                    order,
+                    prune,
+                    steps,
+                );
+            }
+        }
+        if matches!(order, Order::Post) {
+            steps.push((node, depth, field_name));
+        }
+    }
+
+    let mut steps = Vec::new();
+    visit(node, 0, None, order, &prune, &mut steps);
+    steps
+}
+
+/// The properties of each of the tree's nodes, in the order of a depth-first traversal.
+#[allow(clippy::type_complexity)]
+fn node_summaries(
+    tree: &Tree,
+) -> Vec<(
+    &'static str,
+    Option<&'static str>,
+    Range,
+    [bool; 4],
+    (u16, u16),
+    usize,
+)> {
+    let mut summaries = Vec::new();
+    let mut cursor = tree.walk();
+    'outer: loop {
+        let node = cursor.node();
+        summaries.push((
+            node.kind(),
+            cursor.field_name(),
+            node.range(),
+            [
+                node.is_extra(),
+                node.is_missing(),
+                node.is_error(),
+                node.has_error(),
+            ],
+            (node.parse_state(), node.next_parse_state()),
+            node.child_count(),
+        ));
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'outer;
+            }
+        }
+    }
+    summaries
+}
+
 fn index_of(text: &[u8], substring: &str) -> usize {
     str::from_utf8(text).unwrap().find(substring).unwrap()
 }
@@ -0,0 +1,182 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    io::{self, IsTerminal, Write},
+    path::Path,
+    sync::mpsc,
+    time::Duration,
+};
+
+use anstyle::AnsiColor;
+use anyhow::Result;
+use notify_debouncer_full::{
+    new_debouncer,
+    notify::{event::ModifyKind, EventKind, RecursiveMode},
+    DebouncedEvent,
+};
+
+use crate::test::paint;
+
+/// How long to wait for the files to stop changing before running again, so that saving several
+/// files at once, or an editor that writes a file in more than one step, only causes one run.
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// The directories of a grammar that are generated or managed by other tools. Changes in them
+/// never cause a run, except for `src/scanner.c`.
+const IGNORED_DIRECTORIES: &[&str] = &["bindings", "build", "node_modules", "src", "target"];
+
+/// Call `run` once, and then again every time the grammar, the files it imports, the external
+/// scanner, the queries, or the tests in `grammar_dir` change, until the process is interrupted.
+///
+/// An error returned by `run` is reported in the summary after each run, and doesn't stop the
+/// watcher.
+pub fn watch(
+    grammar_dir: &Path,
+    use_color: bool,
+    mut run: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let mut debouncer = new_debouncer(DEBOUNCE_TIMEOUT, None, sender)?;
+
+    // Watch the top-level files and `src/` on their own, so that the large directories that
+    // are never relevant, like `node_modules`, don't need to be watched at all.
+    debouncer.watch(grammar_dir, RecursiveMode::NonRecursive)?;
+    let src_dir = grammar_dir.join("src");
+    if src_dir.is_dir() {
+        debouncer.watch(&src_dir, RecursiveMode::NonRecursive)?;
+    }
+    for entry in fs::read_dir(grammar_dir)? {
+        let path = entry?.path();
+        if path.is_dir() && !is_ignored_directory(&path) {
+            debouncer.watch(&path, RecursiveMode::Recursive)?;
+        }
+    }
+
+    loop {
+        if io::stdout().is_terminal() {
+            print!("\x1B[2J\x1B[1;1H");
+            io::stdout().flush()?;
+        }
+
+        let result = run();
+
+        println!();
+        match result {
+            Ok(()) => println!(
+                "{}",
+                paint(use_color.then_some(AnsiColor::Green), "✓ Passed")
+            ),
+            Err(e) => {
+                let message = format!("{e:#}");
+                if message.is_empty() {
+                    println!("{}", paint(use_color.then_some(AnsiColor::Red), "✗ Failed"));
+                } else {
+                    println!(
+                        "{}",
+                        paint(
+                            use_color.then_some(AnsiColor::Red),
+                            &format!("✗ Failed: {message}")
+                        )
+                    );
+                }
+            }
+        }
+        println!("Watching for changes, press Ctrl-C to stop");
+
+        loop {
+            match receiver.recv()? {
+                Ok(events) => {
+                    if events.iter().any(|event| is_relevant(grammar_dir, event)) {
+                        break;
+                    }
+                }
+                Err(errors) => {
+                    for error in errors {
+                        eprintln!("Failed to watch for changes: {error}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn is_ignored_directory(path: &Path) -> bool {
+    path.file_name()
+        .and_then(OsStr::to_str)
+        .is_none_or(|name| name.starts_with('.') || IGNORED_DIRECTORIES.contains(&name))
+}
+
+/// Whether the event changed the contents of a file that should cause a run. Reading files, which
+/// the runs themselves do, doesn't count.
+fn is_relevant(grammar_dir: &Path, event: &DebouncedEvent) -> bool {
+    let changed = matches!(
+        event.kind,
+        EventKind::Create(_)
+            | EventKind::Remove(_)
+            | EventKind::Modify(ModifyKind::Any | ModifyKind::Data(_) | ModifyKind::Name(_))
+    );
+    changed
+        && event
+            .paths
+            .iter()
+            .any(|path| is_watched_file(grammar_dir, path))
+}
+
+fn is_watched_file(grammar_dir: &Path, path: &Path) -> bool {
+    let Ok(relative_path) = path.strip_prefix(grammar_dir) else {
+        return false;
+    };
+    let mut components = relative_path.components().map(|c| c.as_os_str());
+    let Some(first) = components.next() else {
+        return false;
+    };
+    if first == "queries" || first == "test" {
+        return true;
+    }
+    if first == "src" {
+        return relative_path == Path::new("src").join("scanner.c");
+    }
+    if components.next().is_some() && is_ignored_directory(Path::new(first)) {
+        return false;
+    }
+
+    // The grammar, the files it imports, and the configuration in `tree-sitter.json`.
+    matches!(
+        path.extension().and_then(OsStr::to_str),
+        Some("js" | "cjs" | "mjs" | "ts" | "json")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_watched_file() {
+        let dir = Path::new("/grammar");
+        for path in [
+            "grammar.js",
+            "tree-sitter.json",
+            "rules/expressions.js",
+            "src/scanner.c",
+            "queries/highlights.scm",
+            "test/corpus/statements.txt",
+            "test/highlight/nested/example.js",
+        ] {
+            assert!(is_watched_file(dir, &dir.join(path)), "{path}");
+        }
+        for path in [
+            "README.md",
+            "log.html",
+            "src/parser.c",
+            "src/grammar.json",
+            "src/tree_sitter/parser.h",
+            "node_modules/tree-sitter-cli/package.json",
+            ".git/index",
+            "bindings/node/index.js",
+            "/elsewhere/grammar.js",
+        ] {
+            assert!(!is_watched_file(dir, &dir.join(path)), "{path}");
+        }
+    }
+}
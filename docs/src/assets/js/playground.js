@@ -290,15 +290,20 @@ window.initializePlayground = async (opts) => {
             fieldName = "";
           }
 
+          const isKeyword =
+            !cursor.nodeIsNamed && parser.language.nodeTypeIsKeyword(cursor.nodeTypeId);
           const nodeClass =
             displayName === 'ERROR' || displayName.startsWith('MISSING')
               ? 'node-link error'
               : cursor.nodeIsNamed
                 ? 'node-link named'
-                : 'node-link anonymous';
+                : isKeyword
+                  ? 'node-link anonymous keyword'
+                  : 'node-link anonymous';
+          const title = isKeyword ? ' title="keyword"' : '';
 
           row = `<div class="tree-row">${"  ".repeat(indentLevel)}${fieldName}` +
-            `<a class='${nodeClass}' href="#" data-id=${id} ` +
+            `<a class='${nodeClass}'${title} href="#" data-id=${id} ` +
             `data-range="${start.row},${start.column},${end.row},${end.column}">` +
             `${displayName}</a> <span class="position-info">` +
             `[${start.row}, ${start.column}] - [${end.row}, ${end.column}]</span>`;
@@ -1,13 +1,16 @@
 #![doc = include_str!("../README.md")]
 
 pub mod c_lib;
+pub mod locals;
+pub mod semantic_tokens;
+mod session;
 use core::slice;
 use std::{
     collections::HashSet,
     iter,
     marker::PhantomData,
     mem::{self, MaybeUninit},
-    ops, str,
+    ops, ptr, str,
     sync::{
         atomic::{AtomicUsize, Ordering},
         LazyLock,
@@ -15,11 +18,12 @@ use std::{
 };
 
 pub use c_lib as c;
+pub use session::HighlightSession;
 use streaming_iterator::StreamingIterator;
 use thiserror::Error;
 use tree_sitter::{
     ffi, Language, LossyUtf8, Node, ParseOptions, Parser, Point, Query, QueryCapture,
-    QueryCaptures, QueryCursor, QueryError, QueryMatch, Range, TextProvider, Tree,
+    QueryCaptures, QueryCursor, QueryError, QueryErrorKind, QueryMatch, Range, TextProvider, Tree,
 };
 
 const CANCELLATION_CHECK_INTERVAL: usize = 100;
@@ -85,6 +89,14 @@ static STANDARD_CAPTURE_NAMES: LazyLock<HashSet<&'static str>> = LazyLock::new(|
     .collect()
 });
 
+/// Get the standard highlight names, which
+/// [`HighlightConfiguration::nonconformant_captures`] checks captures against when it isn't
+/// given any names.
+#[must_use]
+pub fn standard_capture_names() -> &'static HashSet<&'static str> {
+    &STANDARD_CAPTURE_NAMES
+}
+
 /// Indicates which highlight should be applied to a region of source code.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Highlight(pub usize);
@@ -101,7 +113,7 @@ pub enum Error {
 }
 
 /// Represents a single step in rendering a syntax-highlighted document.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum HighlightEvent {
     Source { start: usize, end: usize },
     HighlightStart(Highlight),
@@ -110,7 +122,9 @@ pub enum HighlightEvent {
 
 /// Contains the data needed to highlight code written in a particular language.
 ///
-/// This struct is immutable and can be shared between threads.
+/// Once it has been configured, this struct is only used through shared references, and it is
+/// `Send` and `Sync`, so it can be shared between threads that each highlight with their own
+/// [`Highlighter`].
 pub struct HighlightConfiguration {
     pub language: Language,
     pub language_name: String,
@@ -118,8 +132,10 @@ pub struct HighlightConfiguration {
     combined_injections_query: Option<Query>,
     locals_pattern_index: usize,
     highlights_pattern_index: usize,
+    highlight_names: Vec<String>,
     highlight_indices: Vec<Option<Highlight>>,
     non_local_variable_patterns: Vec<bool>,
+    injection_line_prefixes: Vec<Option<regex::bytes::Regex>>,
     injection_content_capture_index: Option<u32>,
     injection_language_capture_index: Option<u32>,
     local_scope_capture_index: Option<u32>,
@@ -159,22 +175,53 @@ struct LocalScope<'a> {
     local_defs: Vec<LocalDef<'a>>,
 }
 
-struct HighlightIter<'a, F>
+/// The language layer of a document that a highlight comes from: the document's own language at
+/// depth 0, or a language injected into it, one level deeper than the layer it is injected into.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HighlightLayer<'a> {
+    pub language_name: &'a str,
+    pub depth: usize,
+}
+
+/// An iterator over the [`HighlightEvent`]s of a document, returned by
+/// [`Highlighter::highlight`] and [`Highlighter::highlight_existing_tree`].
+pub struct HighlightIter<'a, F>
 where
     F: FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a,
 {
     source: &'a [u8],
     language_name: &'a str,
     byte_offset: usize,
+    /// The range of the source that events are emitted for, clamped to the source's length
+    byte_range: ops::Range<usize>,
     highlighter: &'a mut Highlighter,
     injection_callback: F,
     cancellation_flag: Option<&'a AtomicUsize>,
+    layer_trees: Vec<LayerTree<'a>>,
     layers: Vec<HighlightIterLayer<'a>>,
     iter_count: usize,
     next_event: Option<HighlightEvent>,
     last_highlight_range: Option<(usize, usize, usize)>,
+    last_highlight_layer: Option<HighlightLayer<'a>>,
+}
+
+/// A syntax tree that has already been parsed for one of the layers of a document, which is
+/// used instead of parsing that layer again.
+#[derive(Clone)]
+struct LayerTree<'a> {
+    config: &'a HighlightConfiguration,
+    ranges: Vec<Range>,
+    tree: Tree,
 }
 
+/// The range of a layer that covers the whole document.
+const DOCUMENT_RANGE: Range = Range {
+    start_byte: 0,
+    end_byte: usize::MAX,
+    start_point: Point::new(0, 0),
+    end_point: Point::new(usize::MAX, usize::MAX),
+};
+
 struct HighlightIterLayer<'a> {
     _tree: Tree,
     cursor: QueryCursor,
@@ -193,6 +240,9 @@ pub struct _QueryCaptures<'query, 'tree: 'query, T: TextProvider<I>, I: AsRef<[u
     buffer1: Vec<u8>,
     buffer2: Vec<u8>,
     _current_match: Option<(QueryMatch<'query, 'tree>, usize)>,
+    _predicate_handlers: &'query [()],
+    _range: &'query mut Range,
+    _containing_node_range: Option<Range>,
     _options: Option<*mut ffi::TSQueryCursorOptions>,
     _phantom: PhantomData<(&'tree (), I)>,
 }
@@ -283,39 +333,158 @@ impl Highlighter {
         config: &'a HighlightConfiguration,
         source: &'a [u8],
         cancellation_flag: Option<&'a AtomicUsize>,
+        injection_callback: impl FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a,
+    ) -> Result<HighlightIter<'a, impl FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a>, Error>
+    {
+        self.highlight_byte_range(
+            config,
+            source,
+            0..usize::MAX,
+            cancellation_flag,
+            injection_callback,
+        )
+    }
+
+    /// Iterate over the highlighted regions of a given slice of source code that intersect a
+    /// range of byte offsets, such as the part of a document that is visible in an editor.
+    ///
+    /// The whole document is still parsed, along with the languages injected into it, so the
+    /// events are the same as the ones that [`highlight`](Self::highlight) returns for this part
+    /// of the document, including the highlights of nodes and injections that start before the
+    /// range. But `Source` events are only emitted for the text inside the range, and the
+    /// highlights of nodes outside of it are skipped.
+    pub fn highlight_byte_range<'a>(
+        &'a mut self,
+        config: &'a HighlightConfiguration,
+        source: &'a [u8],
+        range: ops::Range<usize>,
+        cancellation_flag: Option<&'a AtomicUsize>,
+        injection_callback: impl FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a,
+    ) -> Result<HighlightIter<'a, impl FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a>, Error>
+    {
+        self.highlight_layer_trees(
+            config,
+            Vec::new(),
+            source,
+            range,
+            cancellation_flag,
+            injection_callback,
+        )
+    }
+
+    /// Iterate over the highlighted regions for a given slice of source code, using a syntax
+    /// tree that has already been parsed from it instead of parsing it again.
+    ///
+    /// This is useful for editors that keep an up-to-date tree for each document, by editing
+    /// and reparsing it as the document changes. The tree must have been parsed with the
+    /// configuration's language, and must be in sync with `source`. The languages injected
+    /// into the document are still parsed, so the events are the same as the ones that
+    /// [`highlight`](Self::highlight) returns for the same source.
+    pub fn highlight_existing_tree<'a>(
+        &'a mut self,
+        config: &'a HighlightConfiguration,
+        tree: &Tree,
+        source: &'a [u8],
+        cancellation_flag: Option<&'a AtomicUsize>,
+        injection_callback: impl FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a,
+    ) -> Result<HighlightIter<'a, impl FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a>, Error>
+    {
+        if *tree.language() != config.language {
+            return Err(Error::InvalidLanguage);
+        }
+        let layer_trees = vec![LayerTree {
+            config,
+            ranges: vec![DOCUMENT_RANGE],
+            tree: tree.clone(),
+        }];
+        self.highlight_layer_trees(
+            config,
+            layer_trees,
+            source,
+            0..usize::MAX,
+            cancellation_flag,
+            injection_callback,
+        )
+    }
+
+    /// Iterate over the highlighted regions of a document, using the given trees for the
+    /// layers that they were parsed for, and parsing the other layers.
+    fn highlight_layer_trees<'a>(
+        &'a mut self,
+        config: &'a HighlightConfiguration,
+        layer_trees: Vec<LayerTree<'a>>,
+        source: &'a [u8],
+        range: ops::Range<usize>,
+        cancellation_flag: Option<&'a AtomicUsize>,
         mut injection_callback: impl FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a,
-    ) -> Result<impl Iterator<Item = Result<HighlightEvent, Error>> + 'a, Error> {
+    ) -> Result<HighlightIter<'a, impl FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a>, Error>
+    {
         let layers = HighlightIterLayer::new(
             source,
+            &layer_trees,
             None,
             self,
             cancellation_flag,
             &mut injection_callback,
             config,
             0,
-            vec![Range {
-                start_byte: 0,
-                end_byte: usize::MAX,
-                start_point: Point::new(0, 0),
-                end_point: Point::new(usize::MAX, usize::MAX),
-            }],
+            vec![DOCUMENT_RANGE],
+            &range,
         )?;
         assert_ne!(layers.len(), 0);
+        let byte_range = range.start.min(source.len())..range.end.min(source.len());
         let mut result = HighlightIter {
             source,
             language_name: &config.language_name,
-            byte_offset: 0,
+            byte_offset: byte_range.start,
+            byte_range,
             injection_callback,
             cancellation_flag,
             highlighter: self,
             iter_count: 0,
+            layer_trees,
             layers,
             next_event: None,
             last_highlight_range: None,
+            last_highlight_layer: None,
         };
         result.sort_layers();
         Ok(result)
     }
+
+    /// Iterate over the highlighted regions of a given slice of source code that intersect a
+    /// range of rows and columns. See [`highlight_byte_range`](Self::highlight_byte_range).
+    pub fn highlight_point_range<'a>(
+        &'a mut self,
+        config: &'a HighlightConfiguration,
+        source: &'a [u8],
+        range: ops::Range<Point>,
+        cancellation_flag: Option<&'a AtomicUsize>,
+        injection_callback: impl FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a,
+    ) -> Result<HighlightIter<'a, impl FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a>, Error>
+    {
+        let range =
+            byte_offset_for_point(source, range.start)..byte_offset_for_point(source, range.end);
+        self.highlight_byte_range(config, source, range, cancellation_flag, injection_callback)
+    }
+}
+
+/// The byte offset of a row and column in `source`, clamped to the end of the row. If the source
+/// has fewer rows, this is `usize::MAX`, so that a range that ends there includes everything up
+/// to the end of the source.
+fn byte_offset_for_point(source: &[u8], point: Point) -> usize {
+    let mut row_start = 0;
+    for _ in 0..point.row {
+        match source[row_start..].iter().position(|&b| b == b'\n') {
+            Some(i) => row_start += i + 1,
+            None => return usize::MAX,
+        }
+    }
+    let row_end = source[row_start..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(source.len(), |i| row_start + i);
+    row_start.saturating_add(point.column).min(row_end)
 }
 
 impl HighlightConfiguration {
@@ -384,6 +553,33 @@ impl HighlightConfiguration {
             None
         };
 
+        // Compile the regexes that match the prefixes that are stripped from each line of
+        // the injections' content, like the indentation of a code block.
+        let injection_line_prefixes = (0..locals_pattern_index)
+            .map(|pattern_index| {
+                let Some(prefix) = query
+                    .property_settings(pattern_index)
+                    .iter()
+                    .find(|s| &*s.key == "injection.line-prefix")
+                    .and_then(|s| s.value.as_deref())
+                else {
+                    return Ok(None);
+                };
+                regex::bytes::Regex::new(&format!("^(?:{prefix})"))
+                    .map(Some)
+                    .map_err(|_| {
+                        let offset = query.start_byte_for_pattern(pattern_index);
+                        QueryError {
+                            row: query_source[..offset].matches('\n').count(),
+                            column: 0,
+                            offset,
+                            message: format!("Invalid regex '{prefix}'"),
+                            kind: QueryErrorKind::Predicate,
+                        }
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+
         // Find all of the highlighting patterns that are disabled for nodes that
         // have been identified as local variables.
         let non_local_variable_patterns = (0..query.pattern_count())
@@ -423,8 +619,10 @@ impl HighlightConfiguration {
             combined_injections_query,
             locals_pattern_index,
             highlights_pattern_index,
+            highlight_names: Vec::new(),
             highlight_indices,
             non_local_variable_patterns,
+            injection_line_prefixes,
             injection_content_capture_index,
             injection_language_capture_index,
             local_def_capture_index,
@@ -451,34 +649,76 @@ impl HighlightConfiguration {
     /// When highlighting, results are returned as `Highlight` values, which contain the index
     /// of the matched highlight this list of highlight names.
     pub fn configure(&mut self, recognized_names: &[impl AsRef<str>]) {
-        let mut capture_parts = Vec::new();
+        self.highlight_names.clear();
+        self.highlight_names.extend(
+            recognized_names
+                .iter()
+                .map(|name| name.as_ref().to_string()),
+        );
         self.highlight_indices.clear();
         self.highlight_indices
-            .extend(self.query.capture_names().iter().map(move |capture_name| {
-                capture_parts.clear();
-                capture_parts.extend(capture_name.split('.'));
-
-                let mut best_index = None;
-                let mut best_match_len = 0;
-                for (i, recognized_name) in recognized_names.iter().enumerate() {
-                    let mut len = 0;
-                    let mut matches = true;
-                    for part in recognized_name.as_ref().split('.') {
-                        len += 1;
-                        if !capture_parts.contains(&part) {
-                            matches = false;
-                            break;
-                        }
-                    }
-                    if matches && len > best_match_len {
-                        best_index = Some(i);
-                        best_match_len = len;
-                    }
-                }
-                best_index.map(Highlight)
+            .extend(self.query.capture_names().iter().map(|capture_name| {
+                best_highlight_name(capture_name, &self.highlight_names).map(Highlight)
             }));
     }
 
+    /// Add a name to the end of the list of recognized highlight names, after the configuration
+    /// has been configured, and get the highlight for it.
+    ///
+    /// The captures that the new name matches more specifically than the name that they are
+    /// highlighted with are highlighted with the new name instead, just like if the name had
+    /// been in the list passed to [`configure`](Self::configure). This lets a theme introduce a
+    /// highlight like `keyword.operator.new` without rebuilding the configuration. If the name
+    /// is already recognized, its existing highlight is returned.
+    pub fn add_highlight_name(&mut self, name: &str) -> Highlight {
+        if let Some(index) = self.highlight_names.iter().position(|n| n == name) {
+            return Highlight(index);
+        }
+        let highlight = Highlight(self.highlight_names.len());
+        self.highlight_names.push(name.to_string());
+        for (capture_name, highlight_index) in self
+            .query
+            .capture_names()
+            .iter()
+            .zip(&mut self.highlight_indices)
+        {
+            if best_highlight_name(capture_name, &self.highlight_names) == Some(highlight.0) {
+                *highlight_index = Some(highlight);
+            }
+        }
+        highlight
+    }
+
+    /// Get the list of recognized highlight names, which [`Highlight`] values are indices into.
+    #[must_use]
+    pub fn highlight_names(&self) -> &[String] {
+        &self.highlight_names
+    }
+
+    /// Compute the ranges of an injection from its content nodes, leaving out the prefix of
+    /// each line if the injection pattern sets `injection.line-prefix`.
+    fn injection_ranges(
+        &self,
+        pattern_index: usize,
+        parent_ranges: &[Range],
+        nodes: &[Node],
+        includes_children: bool,
+        source: &[u8],
+    ) -> Vec<Range> {
+        let ranges = HighlightIterLayer::intersect_ranges(parent_ranges, nodes, includes_children);
+        match self.injection_line_prefixes.get(pattern_index) {
+            Some(Some(line_prefix)) => strip_line_prefixes(&ranges, line_prefix, source),
+            _ => ranges,
+        }
+    }
+
+    /// Get the recognized highlight name, chosen by [`configure`](Self::configure), that the
+    /// capture with the given index is highlighted with.
+    #[must_use]
+    pub fn highlight_for_capture(&self, capture_index: usize) -> Option<Highlight> {
+        self.highlight_indices.get(capture_index).copied().flatten()
+    }
+
     // Return the list of this configuration's capture names that are neither present in the
     // list of predefined 'canonical' names nor start with an underscore (denoting 'private'
     // captures used as part of capture internals).
@@ -495,6 +735,128 @@ impl HighlightConfiguration {
             .copied()
             .collect()
     }
+
+    /// Get the captures that [`nonconformant_capture_names`](Self::nonconformant_capture_names)
+    /// returns, along with the patterns that use each of them and the recognized name that
+    /// each of them falls back to, so that tools can lint highlight queries against a theme.
+    #[must_use]
+    pub fn nonconformant_captures<'a>(
+        &'a self,
+        capture_names: &HashSet<&'a str>,
+    ) -> Vec<NonconformantCapture<'a>> {
+        let capture_names = if capture_names.is_empty() {
+            &*STANDARD_CAPTURE_NAMES
+        } else {
+            capture_names
+        };
+        let mut recognized_names = capture_names.iter().copied().collect::<Vec<_>>();
+        recognized_names.sort_unstable();
+        self.names()
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !(n.starts_with('_') || capture_names.contains(*n)))
+            .map(|(index, &name)| NonconformantCapture {
+                name,
+                capture_index: index as u32,
+                pattern_indices: self.query.patterns_for_capture(name),
+                fallback: best_highlight_name(name, &recognized_names).map(|i| recognized_names[i]),
+            })
+            .collect()
+    }
+}
+
+/// A capture in a highlight query whose name isn't one of the recognized highlight names,
+/// returned by [`HighlightConfiguration::nonconformant_captures`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NonconformantCapture<'a> {
+    /// The name of the capture.
+    pub name: &'a str,
+    /// The index of the capture in the configuration's query.
+    pub capture_index: u32,
+    /// The indices of the query's patterns that use the capture.
+    pub pattern_indices: Vec<usize>,
+    /// The recognized name that matches the capture most specifically, and that the capture
+    /// would be highlighted with, if there is one.
+    pub fallback: Option<&'a str>,
+}
+
+/// Find the index of the recognized highlight name that matches a capture name most
+/// specifically. A recognized name matches if all of its dot-separated parts are parts of the
+/// capture name, and the earliest of the longest matching names is chosen.
+fn best_highlight_name(capture_name: &str, recognized_names: &[impl AsRef<str>]) -> Option<usize> {
+    let capture_parts = capture_name.split('.').collect::<Vec<_>>();
+    let mut best_index = None;
+    let mut best_match_len = 0;
+    for (i, recognized_name) in recognized_names.iter().enumerate() {
+        let mut len = 0;
+        let mut matches = true;
+        for part in recognized_name.as_ref().split('.') {
+            len += 1;
+            if !capture_parts.contains(&part) {
+                matches = false;
+                break;
+            }
+        }
+        if matches && len > best_match_len {
+            best_index = Some(i);
+            best_match_len = len;
+        }
+    }
+    best_index
+}
+
+impl LayerTree<'_> {
+    /// Get a copy of the tree that was parsed for a layer with the given configuration and
+    /// ranges, if there is one.
+    fn find(
+        layer_trees: &[Self],
+        config: &HighlightConfiguration,
+        ranges: &[Range],
+    ) -> Option<Tree> {
+        layer_trees
+            .iter()
+            .find(|layer_tree| ptr::eq(layer_tree.config, config) && layer_tree.ranges == ranges)
+            .map(|layer_tree| layer_tree.tree.clone())
+    }
+}
+
+/// Parse a layer of a document, reusing an old tree for the layer if one is given. Returns
+/// `None` if the layer's ranges can't be parsed, and an error if parsing is cancelled.
+fn parse_layer(
+    parser: &mut Parser,
+    config: &HighlightConfiguration,
+    source: &[u8],
+    ranges: &[Range],
+    old_tree: Option<&Tree>,
+    cancellation_flag: Option<&AtomicUsize>,
+) -> Result<Option<Tree>, Error> {
+    if parser.set_included_ranges(ranges).is_err() {
+        return Ok(None);
+    }
+    parser
+        .set_language(&config.language)
+        .map_err(|_| Error::InvalidLanguage)?;
+
+    let tree = parser
+        .parse_with_options(
+            &mut |i, _| {
+                if i < source.len() {
+                    &source[i..]
+                } else {
+                    &[]
+                }
+            },
+            old_tree,
+            Some(ParseOptions::new().progress_callback(&mut |_| {
+                if let Some(cancellation_flag) = cancellation_flag {
+                    cancellation_flag.load(Ordering::SeqCst) != 0
+                } else {
+                    false
+                }
+            })),
+        )
+        .ok_or(Error::Cancelled)?;
+    Ok(Some(tree))
 }
 
 impl<'a> HighlightIterLayer<'a> {
@@ -503,9 +865,13 @@ impl<'a> HighlightIterLayer<'a> {
     /// In the event that the new layer contains "combined injections" (injections where multiple
     /// disjoint ranges are parsed as one syntax tree), these will be eagerly processed and
     /// added to the returned vector.
+    ///
+    /// The layers that have a tree in `layer_trees`, parsed with the same configuration and
+    /// ranges, use that tree instead of parsing the source again.
     #[allow(clippy::too_many_arguments)]
     fn new<F: FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a>(
         source: &'a [u8],
+        layer_trees: &[LayerTree<'a>],
         parent_name: Option<&str>,
         highlighter: &mut Highlighter,
         cancellation_flag: Option<&'a AtomicUsize>,
@@ -513,106 +879,36 @@ impl<'a> HighlightIterLayer<'a> {
         mut config: &'a HighlightConfiguration,
         mut depth: usize,
         mut ranges: Vec<Range>,
+        byte_range: &ops::Range<usize>,
     ) -> Result<Vec<Self>, Error> {
         let mut result = Vec::with_capacity(1);
         let mut queue = Vec::new();
         loop {
-            if highlighter.parser.set_included_ranges(&ranges).is_ok() {
-                highlighter
-                    .parser
-                    .set_language(&config.language)
-                    .map_err(|_| Error::InvalidLanguage)?;
-
-                let tree = highlighter
-                    .parser
-                    .parse_with_options(
-                        &mut |i, _| {
-                            if i < source.len() {
-                                &source[i..]
-                            } else {
-                                &[]
-                            }
-                        },
-                        None,
-                        Some(ParseOptions::new().progress_callback(&mut |_| {
-                            if let Some(cancellation_flag) = cancellation_flag {
-                                cancellation_flag.load(Ordering::SeqCst) != 0
-                            } else {
-                                false
-                            }
-                        })),
-                    )
-                    .ok_or(Error::Cancelled)?;
-                let mut cursor = highlighter.cursors.pop().unwrap_or_default();
-
-                // Process combined injections.
-                if let Some(combined_injections_query) = &config.combined_injections_query {
-                    let mut injections_by_pattern_index =
-                        vec![(None, Vec::new(), false); combined_injections_query.pattern_count()];
-                    let mut matches =
-                        cursor.matches(combined_injections_query, tree.root_node(), source);
-                    while let Some(mat) = matches.next() {
-                        let entry = &mut injections_by_pattern_index[mat.pattern_index];
-                        let (language_name, content_node, include_children) = injection_for_match(
-                            config,
-                            parent_name,
-                            combined_injections_query,
-                            mat,
-                            source,
-                        );
-                        if language_name.is_some() {
-                            entry.0 = language_name;
-                        }
-                        if let Some(content_node) = content_node {
-                            entry.1.push(content_node);
-                        }
-                        entry.2 = include_children;
-                    }
-                    for (lang_name, content_nodes, includes_children) in injections_by_pattern_index
-                    {
-                        if let (Some(lang_name), false) = (lang_name, content_nodes.is_empty()) {
-                            if let Some(next_config) = (injection_callback)(lang_name) {
-                                let ranges = Self::intersect_ranges(
-                                    &ranges,
-                                    &content_nodes,
-                                    includes_children,
-                                );
-                                if !ranges.is_empty() {
-                                    queue.push((next_config, depth + 1, ranges));
-                                }
-                            }
-                        }
-                    }
-                }
-
-                // The `captures` iterator borrows the `Tree` and the `QueryCursor`, which
-                // prevents them from being moved. But both of these values are really just
-                // pointers, so it's actually ok to move them.
-                let tree_ref = unsafe { mem::transmute::<&Tree, &'static Tree>(&tree) };
-                let cursor_ref = unsafe {
-                    mem::transmute::<&mut QueryCursor, &'static mut QueryCursor>(&mut cursor)
-                };
-                let captures = unsafe {
-                    std::mem::transmute::<QueryCaptures<_, _>, _QueryCaptures<_, _>>(
-                        cursor_ref.captures(&config.query, tree_ref.root_node(), source),
-                    )
-                }
-                .peekable();
+            let layer_tree = match LayerTree::find(layer_trees, config, &ranges) {
+                Some(tree) => Some(tree),
+                None => parse_layer(
+                    &mut highlighter.parser,
+                    config,
+                    source,
+                    &ranges,
+                    None,
+                    cancellation_flag,
+                )?,
+            };
 
-                result.push(HighlightIterLayer {
-                    highlight_end_stack: Vec::new(),
-                    scope_stack: vec![LocalScope {
-                        inherits: false,
-                        range: 0..usize::MAX,
-                        local_defs: Vec::new(),
-                    }],
-                    cursor,
-                    depth,
-                    _tree: tree,
-                    captures,
+            if let Some(tree) = layer_tree {
+                result.push(Self::with_tree(
+                    source,
+                    tree,
+                    parent_name,
+                    highlighter,
+                    injection_callback,
                     config,
+                    depth,
                     ranges,
-                });
+                    byte_range,
+                    &mut queue,
+                ));
             }
 
             if queue.is_empty() {
@@ -628,6 +924,132 @@ impl<'a> HighlightIterLayer<'a> {
         Ok(result)
     }
 
+    /// Create a layer for a tree that has been parsed, queueing the combined injections that it
+    /// contains to be parsed as layers of their own.
+    #[allow(clippy::too_many_arguments)]
+    fn with_tree<F: FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a>(
+        source: &'a [u8],
+        tree: Tree,
+        parent_name: Option<&str>,
+        highlighter: &mut Highlighter,
+        injection_callback: &mut F,
+        config: &'a HighlightConfiguration,
+        depth: usize,
+        ranges: Vec<Range>,
+        byte_range: &ops::Range<usize>,
+        queue: &mut Vec<(&'a HighlightConfiguration, usize, Vec<Range>)>,
+    ) -> Self {
+        let mut cursor = highlighter.cursors.pop().unwrap_or_default();
+        cursor.set_byte_range(0..usize::MAX);
+
+        // Process combined injections.
+        queue.extend(
+            Self::combined_injections(
+                source,
+                &tree,
+                parent_name,
+                &mut cursor,
+                injection_callback,
+                config,
+                &ranges,
+            )
+            .into_iter()
+            .map(|(next_config, ranges)| (next_config, depth + 1, ranges)),
+        );
+
+        // Only the captures inside the range are needed, except that the definitions of
+        // local variables before the range are still needed to highlight their
+        // references inside of it.
+        let tracks_locals = config.locals_pattern_index < config.highlights_pattern_index;
+        let start_byte = if tracks_locals { 0 } else { byte_range.start };
+        cursor.set_byte_range(start_byte..byte_range.end);
+
+        // The `captures` iterator borrows the `Tree` and the `QueryCursor`, which
+        // prevents them from being moved. But both of these values are really just
+        // pointers, so it's actually ok to move them.
+        let tree_ref = unsafe { mem::transmute::<&Tree, &'static Tree>(&tree) };
+        let cursor_ref =
+            unsafe { mem::transmute::<&mut QueryCursor, &'static mut QueryCursor>(&mut cursor) };
+        let captures =
+            unsafe {
+                std::mem::transmute::<QueryCaptures<_, _>, _QueryCaptures<_, _>>(
+                    cursor_ref.captures(&config.query, tree_ref.root_node(), source),
+                )
+            }
+            .peekable();
+
+        Self {
+            highlight_end_stack: Vec::new(),
+            scope_stack: vec![LocalScope {
+                inherits: false,
+                range: 0..usize::MAX,
+                local_defs: Vec::new(),
+            }],
+            cursor,
+            depth,
+            _tree: tree,
+            captures,
+            config,
+            ranges,
+        }
+    }
+
+    /// Find the combined injections in a layer's tree. Each of the combined injections query's
+    /// patterns injects one document, made of the ranges of all of the content nodes that the
+    /// pattern matches.
+    fn combined_injections<F: FnMut(&str) -> Option<&'a HighlightConfiguration>>(
+        source: &[u8],
+        tree: &Tree,
+        parent_name: Option<&str>,
+        cursor: &mut QueryCursor,
+        injection_callback: &mut F,
+        config: &'a HighlightConfiguration,
+        ranges: &[Range],
+    ) -> Vec<(&'a HighlightConfiguration, Vec<Range>)> {
+        let mut result = Vec::new();
+        if let Some(combined_injections_query) = &config.combined_injections_query {
+            let mut injections_by_pattern_index =
+                vec![(None, Vec::new(), false); combined_injections_query.pattern_count()];
+            let mut matches = cursor.matches(combined_injections_query, tree.root_node(), source);
+            while let Some(mat) = matches.next() {
+                let entry = &mut injections_by_pattern_index[mat.pattern_index];
+                let (language_name, content_node, include_children) = injection_for_match(
+                    config,
+                    parent_name,
+                    combined_injections_query,
+                    mat,
+                    source,
+                );
+                if language_name.is_some() {
+                    entry.0 = language_name;
+                }
+                if let Some(content_node) = content_node {
+                    entry.1.push(content_node);
+                }
+                entry.2 = include_children;
+            }
+            for (pattern_index, (lang_name, content_nodes, includes_children)) in
+                injections_by_pattern_index.into_iter().enumerate()
+            {
+                if let (Some(lang_name), false) = (lang_name, content_nodes.is_empty()) {
+                    if let Some(next_config) = (injection_callback)(lang_name) {
+                        let ranges = config.injection_ranges(
+                            pattern_index,
+                            ranges,
+                            &content_nodes,
+                            includes_children,
+                            source,
+                        );
+                        if !ranges.is_empty() {
+                            result.push((next_config, ranges));
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
     // Compute the ranges that should be included when parsing an injection.
     // This takes into account three things:
     // * `parent_ranges` - The ranges must all fall within the *current* layer's ranges.
@@ -751,11 +1173,19 @@ impl<'a, F> HighlightIter<'a, F>
 where
     F: FnMut(&str) -> Option<&'a HighlightConfiguration> + 'a,
 {
+    /// The language layer that the last [`HighlightEvent::HighlightStart`] event came from, which
+    /// tells the highlights of injected languages apart from the ones of the document itself.
+    #[must_use]
+    pub const fn highlight_layer(&self) -> Option<HighlightLayer<'a>> {
+        self.last_highlight_layer
+    }
+
     fn emit_event(
         &mut self,
         offset: usize,
         event: Option<HighlightEvent>,
     ) -> Option<Result<HighlightEvent, Error>> {
+        let offset = offset.min(self.byte_range.end);
         let result;
         if self.byte_offset < offset {
             result = Some(Ok(HighlightEvent::Source {
@@ -840,12 +1270,12 @@ where
 
             // If none of the layers have any more highlight boundaries, terminate.
             if self.layers.is_empty() {
-                return if self.byte_offset < self.source.len() {
+                return if self.byte_offset < self.byte_range.end {
                     let result = Some(Ok(HighlightEvent::Source {
                         start: self.byte_offset,
-                        end: self.source.len(),
+                        end: self.byte_range.end,
                     }));
-                    self.byte_offset = self.source.len();
+                    self.byte_offset = self.byte_range.end;
                     result
                 } else {
                     None
@@ -876,7 +1306,7 @@ where
                     layer.highlight_end_stack.pop();
                     return self.emit_event(end_byte, Some(HighlightEvent::HighlightEnd));
                 }
-                return self.emit_event(self.source.len(), None);
+                return self.emit_event(self.byte_range.end, None);
             }
 
             let (mut match_, capture_index) = layer.captures.next().unwrap();
@@ -884,6 +1314,8 @@ where
 
             // If this capture represents an injection, then process the injection.
             if match_.pattern_index < layer.config.locals_pattern_index {
+                let pattern_index = match_.pattern_index;
+                let parent_config = layer.config;
                 let (language_name, content_node, include_children) = injection_for_match(
                     layer.config,
                     Some(self.language_name),
@@ -897,17 +1329,23 @@ where
                 match_.remove();
 
                 // If a language is found with the given name, then add a new language layer
-                // to the highlighted document.
+                // to the highlighted document, unless the injection ends before the range that
+                // is being highlighted.
+                let content_node =
+                    content_node.filter(|node| node.end_byte() > self.byte_range.start);
                 if let (Some(language_name), Some(content_node)) = (language_name, content_node) {
                     if let Some(config) = (self.injection_callback)(language_name) {
-                        let ranges = HighlightIterLayer::intersect_ranges(
+                        let ranges = parent_config.injection_ranges(
+                            pattern_index,
                             &self.layers[0].ranges,
                             &[content_node],
                             include_children,
+                            self.source,
                         );
                         if !ranges.is_empty() {
                             match HighlightIterLayer::new(
                                 self.source,
+                                &self.layer_trees,
                                 Some(self.language_name),
                                 self.highlighter,
                                 self.cancellation_flag,
@@ -915,6 +1353,7 @@ where
                                 config,
                                 self.layers[0].depth + 1,
                                 ranges,
+                                &self.byte_range,
                             ) {
                                 Ok(layers) => {
                                     for layer in layers {
@@ -1064,9 +1503,20 @@ where
                 *definition_highlight = current_highlight;
             }
 
-            // Emit a scope start event and push the node's end position to the stack.
+            // Emit a scope start event and push the node's end position to the stack, unless
+            // the node ends before the range that is being highlighted.
+            if range.end < self.byte_range.start
+                || (range.end == self.byte_range.start && !range.is_empty())
+            {
+                self.sort_layers();
+                continue 'main;
+            }
             if let Some(highlight) = reference_highlight.or(current_highlight) {
                 self.last_highlight_range = Some((range.start, range.end, layer.depth));
+                self.last_highlight_layer = Some(HighlightLayer {
+                    language_name: &layer.config.language_name,
+                    depth: layer.depth,
+                });
                 layer.highlight_end_stack.push(range.end);
                 return self
                     .emit_event(range.start, Some(HighlightEvent::HighlightStart(highlight)));
@@ -1226,6 +1676,66 @@ impl HtmlRenderer {
     }
 }
 
+/// Split the ranges of an injection into one range per line, leaving out the part at the start
+/// of each line that matches the prefix regex.
+fn strip_line_prefixes(
+    ranges: &[Range],
+    line_prefix: &regex::bytes::Regex,
+    source: &[u8],
+) -> Vec<Range> {
+    let mut result = Vec::<Range>::new();
+    for range in ranges {
+        let end_byte = range.end_byte.min(source.len());
+        let mut byte = range.start_byte;
+        let mut point = range.start_point;
+        while byte < end_byte {
+            let line = &source[byte..end_byte];
+            let line_len = line
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(line.len(), |i| i + 1);
+            let content_len = line[..line_len]
+                .strip_suffix(b"\n")
+                .map_or(line_len, <[u8]>::len);
+
+            // Only strip the prefix at the start of a line, not at the start of a range that
+            // begins in the middle of one.
+            let prefix_len = if byte == 0 || source[byte - 1] == b'\n' {
+                line_prefix
+                    .find(&line[..content_len])
+                    .map_or(0, |prefix| prefix.end())
+            } else {
+                0
+            };
+
+            let start_byte = byte + prefix_len;
+            let start_point = Point::new(point.row, point.column + prefix_len);
+            byte += line_len;
+            point = if line_len > content_len {
+                Point::new(point.row + 1, 0)
+            } else {
+                Point::new(point.row, point.column + line_len)
+            };
+            if start_byte == byte {
+                continue;
+            }
+            match result.last_mut() {
+                Some(last) if last.end_byte == start_byte => {
+                    last.end_byte = byte;
+                    last.end_point = point;
+                }
+                _ => result.push(Range {
+                    start_byte,
+                    start_point,
+                    end_byte: byte,
+                    end_point: point,
+                }),
+            }
+        }
+    }
+    result
+}
+
 fn injection_for_match<'a>(
     config: &'a HighlightConfiguration,
     parent_name: Option<&'a str>,
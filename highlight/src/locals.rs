@@ -0,0 +1,236 @@
+//! Analyze the local variables of a document with the locals query of a highlight configuration.
+//!
+//! While highlighting, the highlighter uses the `@local.scope`, `@local.definition` and
+//! `@local.reference` captures of the locals query to highlight each reference to a local
+//! variable like its definition. [`analyze_locals`] and [`HighlightSession::analyze_locals`]
+//! resolve the references in the same way, but return the scopes, definitions and references
+//! that they found, for features like renaming a variable or highlighting its other uses.
+//!
+//! Unlike the highlighter, the analysis also excludes the references inside of a definition's
+//! `@local.definition-value` when it comes after the definition's name.
+//!
+//! [`HighlightSession::analyze_locals`]: crate::HighlightSession::analyze_locals
+
+use std::str;
+
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Node, QueryCursor, Range, Tree};
+
+use crate::{HighlightConfiguration, HighlightLayer};
+
+/// The scopes, definitions and references of local variables in a document.
+///
+/// Scopes, definitions and references refer to each other by their index in the analysis's
+/// vectors, and each vector is ordered by the position of its items within each language layer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LocalScopeAnalysis<'a> {
+    pub scopes: Vec<Scope<'a>>,
+    pub definitions: Vec<Definition<'a>>,
+    pub references: Vec<Reference<'a>>,
+}
+
+/// A node captured with `@local.scope`, or the root node of a language layer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scope<'a> {
+    /// The language layer that the scope is in.
+    pub layer: HighlightLayer<'a>,
+    pub range: Range,
+    /// Whether the references in the scope can refer to the definitions in the scopes around
+    /// it. This is `false` for the root scope of each layer, and for scopes whose pattern sets
+    /// `local.scope-inherits` to `false`.
+    pub inherits: bool,
+    /// The index of the innermost scope that contains this one, which is `None` for the root
+    /// scope of each layer.
+    pub parent: Option<usize>,
+}
+
+/// A node captured with `@local.definition`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Definition<'a> {
+    /// The text of the node.
+    pub name: &'a str,
+    pub range: Range,
+    /// The range of the node captured with `@local.definition-value` in the same match. The
+    /// references inside of it don't refer to this definition.
+    pub value_range: Option<Range>,
+    /// The index of the innermost scope that contains the definition.
+    pub scope: usize,
+}
+
+/// A node captured with `@local.reference`, except for the ones that are also definitions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reference<'a> {
+    /// The text of the node.
+    pub name: &'a str,
+    pub range: Range,
+    /// The index of the innermost scope that contains the reference.
+    pub scope: usize,
+    /// The index of the definition that the reference refers to, or `None` if it isn't a local
+    /// variable.
+    pub definition: Option<usize>,
+}
+
+/// Analyze the local variables of a syntax tree that was parsed with a configuration's language.
+///
+/// Only the tree's own language is analyzed. To also analyze the languages that are injected
+/// into it, use [`HighlightSession::analyze_locals`](crate::HighlightSession::analyze_locals).
+#[must_use]
+pub fn analyze_locals<'a>(
+    config: &'a HighlightConfiguration,
+    tree: &Tree,
+    source: &'a [u8],
+) -> LocalScopeAnalysis<'a> {
+    let mut analysis = LocalScopeAnalysis::default();
+    analysis.add_layer(&mut QueryCursor::new(), config, 0, tree, source);
+    analysis
+}
+
+impl<'a> LocalScopeAnalysis<'a> {
+    /// Get the references that refer to a definition.
+    pub fn references_to(&self, definition: usize) -> impl Iterator<Item = &Reference<'a>> + '_ {
+        self.references
+            .iter()
+            .filter(move |reference| reference.definition == Some(definition))
+    }
+
+    /// Analyze a language layer of a document, in the same way that the highlighter tracks the
+    /// local variables of a layer.
+    pub(crate) fn add_layer(
+        &mut self,
+        cursor: &mut QueryCursor,
+        config: &'a HighlightConfiguration,
+        depth: usize,
+        tree: &Tree,
+        source: &'a [u8],
+    ) {
+        let layer = HighlightLayer {
+            language_name: &config.language_name,
+            depth,
+        };
+        let root = tree.root_node();
+        // The index of each scope that contains the current node, along with the indices of the
+        // definitions in it.
+        let mut scope_stack = vec![(self.scopes.len(), Vec::<usize>::new())];
+        self.scopes.push(Scope {
+            layer,
+            range: root.range(),
+            inherits: false,
+            parent: None,
+        });
+        if config.locals_pattern_index == config.highlights_pattern_index {
+            return;
+        }
+
+        let enabled_patterns = (0..config.query.pattern_count())
+            .map(|pattern_index| {
+                (config.locals_pattern_index..config.highlights_pattern_index)
+                    .contains(&pattern_index)
+            })
+            .collect::<Vec<_>>();
+        cursor.set_byte_range(0..usize::MAX);
+        cursor.set_enabled_patterns(&enabled_patterns);
+        let mut captures = cursor.captures(&config.query, root, source);
+        // The node of the last definition, so that it isn't also added as a reference.
+        let mut definition_node = None::<Node>;
+        // The id of the match of each definition whose value hasn't been captured yet, since a
+        // match's later captures aren't available until the cursor reaches them.
+        let mut pending_values = Vec::<(u32, usize)>::new();
+        while let Some((match_, capture_index)) = captures.next() {
+            let capture = match_.captures[*capture_index];
+            let node = capture.node;
+            while scope_stack.len() > 1
+                && node.start_byte() > self.scopes[scope_stack.last().unwrap().0].range.end_byte
+            {
+                scope_stack.pop();
+            }
+            let scope = scope_stack.last().unwrap().0;
+
+            if Some(capture.index) == config.local_scope_capture_index {
+                definition_node = None;
+                let mut inherits = true;
+                for prop in config.query.property_settings(match_.pattern_index) {
+                    if prop.key.as_ref() == "local.scope-inherits" {
+                        inherits = prop.value.as_ref().is_none_or(|r| r.as_ref() == "true");
+                    }
+                }
+                scope_stack.push((self.scopes.len(), Vec::new()));
+                self.scopes.push(Scope {
+                    layer,
+                    range: node.range(),
+                    inherits,
+                    parent: Some(scope),
+                });
+            } else if Some(capture.index) == config.local_def_capture_index {
+                definition_node = None;
+                let value_range = match_
+                    .captures
+                    .iter()
+                    .rev()
+                    .find(|capture| Some(capture.index) == config.local_def_value_capture_index)
+                    .map(|capture| capture.node.range());
+                if let Ok(name) = str::from_utf8(&source[node.byte_range()]) {
+                    if value_range.is_none() {
+                        pending_values.push((match_.id(), self.definitions.len()));
+                    }
+                    scope_stack
+                        .last_mut()
+                        .unwrap()
+                        .1
+                        .push(self.definitions.len());
+                    self.definitions.push(Definition {
+                        name,
+                        range: node.range(),
+                        value_range,
+                        scope,
+                    });
+                    definition_node = Some(node);
+                }
+            } else if Some(capture.index) == config.local_def_value_capture_index {
+                if let Some(i) = pending_values.iter().position(|(id, _)| *id == match_.id()) {
+                    let (_, definition) = pending_values.swap_remove(i);
+                    self.definitions[definition].value_range = Some(node.range());
+                }
+            } else if Some(capture.index) == config.local_ref_capture_index
+                && definition_node != Some(node)
+            {
+                if let Ok(name) = str::from_utf8(&source[node.byte_range()]) {
+                    let definition = self.resolve(&scope_stack, name, node.start_byte());
+                    self.references.push(Reference {
+                        name,
+                        range: node.range(),
+                        scope,
+                        definition,
+                    });
+                }
+            }
+        }
+        cursor.set_enabled_patterns(&[]);
+    }
+
+    /// Find the definition that a reference refers to, looking through the scopes that contain
+    /// it from the innermost one outwards, until a scope that doesn't inherit its parent's
+    /// definitions.
+    fn resolve(
+        &self,
+        scope_stack: &[(usize, Vec<usize>)],
+        name: &str,
+        start_byte: usize,
+    ) -> Option<usize> {
+        for (scope, definitions) in scope_stack.iter().rev() {
+            let definition = definitions.iter().rev().copied().find(|&definition| {
+                let definition = &self.definitions[definition];
+                definition.name == name
+                    && definition
+                        .value_range
+                        .is_none_or(|value_range| start_byte >= value_range.end_byte)
+            });
+            if definition.is_some() {
+                return definition;
+            }
+            if !self.scopes[*scope].inherits {
+                break;
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,275 @@
+//! Encode highlights as the semantic tokens of the Language Server Protocol.
+//!
+//! A language server returns the highlights of a document as an array of integers, with five
+//! integers for each token: the line of the token relative to the previous token, the character
+//! at which it starts, relative to the previous token if it's on the same line, its length, its
+//! token type, and the bits of its token modifiers. Positions are measured in UTF-16 code units
+//! unless the client and server agree to use another [`PositionEncoding`].
+//!
+//! [`encode_semantic_tokens`] converts the [`HighlightEvent`]s of a document into this format,
+//! using a [`SemanticTokensLegend`] that maps each highlight to a token type and modifiers, and
+//! [`semantic_tokens_edits`] computes the edits between two results, for
+//! `textDocument/semanticTokens/full/delta` requests.
+
+use crate::{Error, Highlight, HighlightEvent};
+
+/// The unit that the characters of positions are measured in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PositionEncoding {
+    /// Bytes of UTF-8.
+    Utf8,
+    /// UTF-16 code units, which is the default in the Language Server Protocol.
+    #[default]
+    Utf16,
+    /// Unicode code points.
+    Utf32,
+}
+
+/// Maps highlights to the token types and token modifiers that a language server declares in its
+/// semantic tokens legend.
+#[derive(Clone, Debug, Default)]
+pub struct SemanticTokensLegend {
+    tokens: Vec<Option<(u32, u32)>>,
+    position_encoding: PositionEncoding,
+}
+
+impl SemanticTokensLegend {
+    /// Map each of the highlight names that a configuration was configured with to a token type
+    /// and token modifiers.
+    ///
+    /// The dot-separated parts of each highlight name are looked up in the legend's token types
+    /// and modifiers. The token type is the last part that is a token type, so that the most
+    /// specific one is used, and the modifiers are all of the parts that are modifiers. For
+    /// example, with the standard token types and modifiers, `function.method` is a `method`,
+    /// and `variable.builtin.defaultLibrary` is a `variable` with the `defaultLibrary` modifier.
+    /// Highlights that don't contain a token type aren't encoded as tokens.
+    #[must_use]
+    pub fn new(
+        highlight_names: &[impl AsRef<str>],
+        token_types: &[impl AsRef<str>],
+        token_modifiers: &[impl AsRef<str>],
+    ) -> Self {
+        let tokens = highlight_names
+            .iter()
+            .map(|name| {
+                let mut token_type = None;
+                let mut modifiers = 0;
+                for part in name.as_ref().split('.') {
+                    if let Some(i) = token_types.iter().position(|t| t.as_ref() == part) {
+                        token_type = Some(i as u32);
+                    }
+                    if let Some(i) = token_modifiers.iter().position(|m| m.as_ref() == part) {
+                        modifiers |= 1 << i;
+                    }
+                }
+                token_type.map(|token_type| (token_type, modifiers))
+            })
+            .collect();
+        Self {
+            tokens,
+            position_encoding: PositionEncoding::default(),
+        }
+    }
+
+    /// Set the token type and the bits of the token modifiers that a highlight is encoded with,
+    /// or make it not be encoded as a token.
+    pub fn set_token(&mut self, highlight: Highlight, token: Option<(u32, u32)>) {
+        if self.tokens.len() <= highlight.0 {
+            self.tokens.resize(highlight.0 + 1, None);
+        }
+        self.tokens[highlight.0] = token;
+    }
+
+    /// Get the token type and the bits of the token modifiers that a highlight is encoded with.
+    #[must_use]
+    pub fn token(&self, highlight: Highlight) -> Option<(u32, u32)> {
+        self.tokens.get(highlight.0).copied().flatten()
+    }
+
+    /// Set the unit that the characters of positions are measured in.
+    #[must_use]
+    pub const fn with_position_encoding(mut self, position_encoding: PositionEncoding) -> Self {
+        self.position_encoding = position_encoding;
+        self
+    }
+
+    /// Get the unit that the characters of positions are measured in.
+    #[must_use]
+    pub const fn position_encoding(&self) -> PositionEncoding {
+        self.position_encoding
+    }
+}
+
+/// An edit that turns one array of semantic tokens into another, as returned in a
+/// `textDocument/semanticTokens/full/delta` response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SemanticTokensEdit {
+    /// The index of the first integer that is replaced.
+    pub start: u32,
+    /// The number of integers that are replaced.
+    pub delete_count: u32,
+    /// The integers that replace them.
+    pub data: Vec<u32>,
+}
+
+/// A token, with its start measured from the start of the document.
+#[derive(Clone, Copy)]
+struct Token {
+    line: u32,
+    character: u32,
+    length: u32,
+    token_type: u32,
+    modifiers: u32,
+}
+
+/// Encode the highlights of a document as semantic tokens.
+///
+/// Each piece of text is encoded with the innermost of its highlights that the legend maps to a
+/// token type. Tokens never span several lines, so multi-line highlights like block comments and
+/// template strings are split into a token for each line, leaving out the line breaks. Adjacent
+/// text on the same line with the same token type and modifiers is encoded as a single token.
+///
+/// # Errors
+///
+/// Returns the first error of the events, for example if highlighting was cancelled.
+pub fn encode_semantic_tokens(
+    events: impl IntoIterator<Item = Result<HighlightEvent, Error>>,
+    source: &[u8],
+    legend: &SemanticTokensLegend,
+) -> Result<Vec<u32>, Error> {
+    let mut tokens = Vec::<Token>::new();
+    let mut highlights = Vec::new();
+    let mut position = Position::default();
+    for event in events {
+        match event? {
+            HighlightEvent::HighlightStart(highlight) => highlights.push(highlight),
+            HighlightEvent::HighlightEnd => {
+                highlights.pop();
+            }
+            HighlightEvent::Source { start, end } => {
+                let Some((token_type, modifiers)) = highlights
+                    .iter()
+                    .rev()
+                    .find_map(|highlight| legend.token(*highlight))
+                else {
+                    continue;
+                };
+                position.advance(source, start, legend.position_encoding);
+                while position.byte < end {
+                    let line_start = position;
+                    let line_end = source[position.byte..end]
+                        .iter()
+                        .position(|&b| b == b'\n')
+                        .map_or(end, |i| position.byte + i);
+                    let content_end = if line_end < end && source[..line_end].ends_with(b"\r") {
+                        line_end - 1
+                    } else {
+                        line_end
+                    };
+                    position.advance(source, content_end, legend.position_encoding);
+                    let length = position.character - line_start.character;
+                    if length > 0 {
+                        let token = Token {
+                            line: line_start.line,
+                            character: line_start.character,
+                            length,
+                            token_type,
+                            modifiers,
+                        };
+                        match tokens.last_mut() {
+                            Some(last)
+                                if last.line == token.line
+                                    && last.character + last.length == token.character
+                                    && (last.token_type, last.modifiers)
+                                        == (token_type, modifiers) =>
+                            {
+                                last.length += length;
+                            }
+                            _ => tokens.push(token),
+                        }
+                    }
+                    position.advance(source, (line_end + 1).min(end), legend.position_encoding);
+                }
+            }
+        }
+    }
+
+    let mut result = Vec::with_capacity(tokens.len() * 5);
+    let mut previous = (0, 0);
+    for token in tokens {
+        let delta_line = token.line - previous.0;
+        let delta_start = if delta_line == 0 {
+            token.character - previous.1
+        } else {
+            token.character
+        };
+        result.extend([
+            delta_line,
+            delta_start,
+            token.length,
+            token.token_type,
+            token.modifiers,
+        ]);
+        previous = (token.line, token.character);
+    }
+    Ok(result)
+}
+
+/// Compute the edits that turn a previous result of [`encode_semantic_tokens`] into a new one.
+///
+/// The tokens that both results start and end with are kept, and the ones between them are
+/// replaced with a single edit, or no edit if the results are the same.
+#[must_use]
+pub fn semantic_tokens_edits(previous: &[u32], current: &[u32]) -> Vec<SemanticTokensEdit> {
+    let prefix_len = previous
+        .chunks(5)
+        .zip(current.chunks(5))
+        .take_while(|(a, b)| a == b)
+        .count()
+        * 5;
+    if prefix_len == previous.len() && prefix_len == current.len() {
+        return Vec::new();
+    }
+    let suffix_len = previous[prefix_len..]
+        .rchunks(5)
+        .zip(current[prefix_len..].rchunks(5))
+        .take_while(|(a, b)| a == b)
+        .count()
+        * 5;
+    vec![SemanticTokensEdit {
+        start: prefix_len as u32,
+        delete_count: (previous.len() - prefix_len - suffix_len) as u32,
+        data: current[prefix_len..current.len() - suffix_len].to_vec(),
+    }]
+}
+
+/// A position in a document, measured in both bytes and the characters of a position encoding.
+#[derive(Clone, Copy, Default)]
+struct Position {
+    byte: usize,
+    line: u32,
+    character: u32,
+}
+
+impl Position {
+    /// Move forward to a byte offset, counting the lines and characters on the way.
+    fn advance(&mut self, source: &[u8], byte: usize, encoding: PositionEncoding) {
+        let byte = byte.min(source.len());
+        for &b in &source[self.byte.min(byte)..byte] {
+            if b == b'\n' {
+                self.line += 1;
+                self.character = 0;
+                continue;
+            }
+            // Invalid UTF-8 is counted as one character for each byte that doesn't continue a
+            // sequence.
+            self.character += match encoding {
+                PositionEncoding::Utf8 => 1,
+                _ if b & 0xC0 == 0x80 => 0,
+                PositionEncoding::Utf16 if b >= 0xF0 => 2,
+                _ => 1,
+            };
+        }
+        self.byte = self.byte.max(byte);
+    }
+}
@@ -0,0 +1,447 @@
+use std::{collections::VecDeque, mem, ops, ptr, sync::atomic::AtomicUsize};
+
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{InputEdit, Point, QueryCursor, Range, Tree};
+
+use super::{
+    injection_for_match, locals::LocalScopeAnalysis, parse_layer, Error, HighlightConfiguration,
+    HighlightIter, HighlightIterLayer, HighlightLayer, Highlighter, LayerTree, DOCUMENT_RANGE,
+};
+
+/// A document that keeps the syntax trees of its layers between edits, so that it can be
+/// highlighted again after an edit without parsing the whole document again.
+///
+/// The session owns the document's text, the tree of the document's own language, and the trees
+/// of the languages that are injected into it. When the document is edited with
+/// [`HighlightSession::edit`], each layer is parsed incrementally, reusing its old tree, and the
+/// session returns the ranges of the document whose highlights may have changed. Those ranges can
+/// then be highlighted with [`HighlightSession::highlight`], which doesn't parse anything.
+///
+/// Each injected layer is matched with the layer that was injected into the same place before
+/// the edit, so an edit inside of an injected document only reparses that document
+/// incrementally. Layers whose injection no longer exists after an edit are discarded, and their
+/// ranges are included in the ranges that have changed.
+pub struct HighlightSession<'a> {
+    config: &'a HighlightConfiguration,
+    source: Vec<u8>,
+    layers: Vec<SessionLayer<'a>>,
+    cursor: QueryCursor,
+}
+
+#[derive(Clone)]
+struct SessionLayer<'a> {
+    layer_tree: LayerTree<'a>,
+    depth: usize,
+    /// The index of the layer that this layer is injected into.
+    parent: Option<usize>,
+}
+
+impl<'a> HighlightSession<'a> {
+    /// Parse a document, along with the languages that are injected into it.
+    ///
+    /// Like with [`Highlighter::highlight`], `injection_callback` provides the configurations of
+    /// the injected languages, and `cancellation_flag` can be set to stop parsing, in which case
+    /// this returns [`Error::Cancelled`].
+    pub fn new(
+        highlighter: &mut Highlighter,
+        config: &'a HighlightConfiguration,
+        source: impl Into<Vec<u8>>,
+        cancellation_flag: Option<&AtomicUsize>,
+        mut injection_callback: impl FnMut(&str) -> Option<&'a HighlightConfiguration>,
+    ) -> Result<Self, Error> {
+        let mut session = Self {
+            config,
+            source: source.into(),
+            layers: Vec::new(),
+            cursor: QueryCursor::new(),
+        };
+        let (layers, _) =
+            session.parse_layers(highlighter, &[], cancellation_flag, &mut injection_callback)?;
+        session.layers = layers;
+        Ok(session)
+    }
+
+    /// Get the text of the document.
+    #[must_use]
+    pub fn source(&self) -> &[u8] {
+        &self.source
+    }
+
+    /// Get the syntax tree of the document's own language.
+    #[must_use]
+    pub fn tree(&self) -> &Tree {
+        &self.layers[0].layer_tree.tree
+    }
+
+    /// Iterate over the layers of the document, starting with the document's own language, along
+    /// with the syntax tree of each layer.
+    pub fn layers(&self) -> impl Iterator<Item = (HighlightLayer<'a>, &Tree)> + '_ {
+        self.layers.iter().map(|layer| {
+            let highlight_layer = HighlightLayer {
+                language_name: &layer.layer_tree.config.language_name,
+                depth: layer.depth,
+            };
+            (highlight_layer, &layer.layer_tree.tree)
+        })
+    }
+
+    /// Analyze the local variables of each layer of the document, using the session's syntax
+    /// trees.
+    ///
+    /// The layers are analyzed separately, like [`analyze_locals`](crate::locals::analyze_locals)
+    /// analyzes a single tree, so a reference in an injected layer never refers to a definition in
+    /// another layer.
+    #[must_use]
+    pub fn analyze_locals(&self) -> LocalScopeAnalysis<'_> {
+        let mut analysis = LocalScopeAnalysis::default();
+        let mut cursor = QueryCursor::new();
+        for layer in &self.layers {
+            analysis.add_layer(
+                &mut cursor,
+                layer.layer_tree.config,
+                layer.depth,
+                &layer.layer_tree.tree,
+                &self.source,
+            );
+        }
+        analysis
+    }
+
+    /// Update the document after its text has been edited, and get the ranges of the new text
+    /// whose highlights may have changed.
+    ///
+    /// The edits must describe the changes from the old text to `new_source` like the ones that
+    /// [`Tree::edit_all`] accepts: in the order in which they appear in the document, without
+    /// overlapping, and relative to the old text. [`tree_sitter::edits_between`] computes them.
+    ///
+    /// The ranges that are returned are sorted and don't overlap. They include the text that was
+    /// inserted, the tokens that the edits are in, the ranges whose syntax changed in any layer,
+    /// and the ranges of the layers that were added or removed. For languages whose
+    /// configuration tracks local variables, they also include the whole top-level node that each
+    /// edit is in, because editing a definition can change the highlights of the references to
+    /// it.
+    ///
+    /// If parsing is cancelled, this returns [`Error::Cancelled`] and the session is left as it
+    /// was before the edit.
+    pub fn edit(
+        &mut self,
+        highlighter: &mut Highlighter,
+        edits: &[InputEdit],
+        new_source: impl Into<Vec<u8>>,
+        cancellation_flag: Option<&AtomicUsize>,
+        mut injection_callback: impl FnMut(&str) -> Option<&'a HighlightConfiguration>,
+    ) -> Result<Vec<ops::Range<usize>>, Error> {
+        let mut old_layers = self.layers.clone();
+        for layer in &mut old_layers {
+            layer.layer_tree.tree.edit_all(edits);
+            for edit in edits.iter().rev() {
+                for range in &mut layer.layer_tree.ranges {
+                    edit_range(range, edit);
+                }
+            }
+        }
+
+        let old_source = mem::replace(&mut self.source, new_source.into());
+        let (layers, mut changed_ranges) = match self.parse_layers(
+            highlighter,
+            &old_layers,
+            cancellation_flag,
+            &mut injection_callback,
+        ) {
+            Ok(result) => result,
+            Err(error) => {
+                self.source = old_source;
+                return Err(error);
+            }
+        };
+        self.layers = layers;
+
+        let mut inserted_bytes = 0;
+        let mut removed_bytes = 0;
+        for edit in edits {
+            let start_byte = edit.start_byte - removed_bytes + inserted_bytes;
+            let end_byte = start_byte + edit.new_end_byte - edit.start_byte;
+            inserted_bytes += edit.new_end_byte - edit.start_byte;
+            removed_bytes += edit.old_end_byte - edit.start_byte;
+            changed_ranges.push(start_byte..end_byte);
+
+            for layer in &self.layers {
+                if !layer
+                    .layer_tree
+                    .ranges
+                    .iter()
+                    .any(|range| range.start_byte <= start_byte && end_byte <= range.end_byte)
+                {
+                    continue;
+                }
+                let root = layer.layer_tree.tree.root_node();
+                for byte in [start_byte, end_byte] {
+                    if let Some(node) = root.descendant_for_byte_range(byte, byte) {
+                        if node.child_count() == 0 {
+                            changed_ranges.push(node.byte_range());
+                        }
+                    }
+                }
+                let config = layer.layer_tree.config;
+                if config.locals_pattern_index < config.highlights_pattern_index {
+                    if let Some(node) = root.first_child_for_byte(start_byte) {
+                        if node.start_byte() <= end_byte {
+                            changed_ranges.push(node.byte_range());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(merge_ranges(changed_ranges, self.source.len()))
+    }
+
+    /// Iterate over the highlighted regions of the document that intersect a range of byte
+    /// offsets, using the session's syntax trees instead of parsing the document.
+    ///
+    /// The events are the same as the ones that [`Highlighter::highlight_byte_range`] returns for
+    /// the document's text. This is usually called with each of the ranges that
+    /// [`HighlightSession::edit`] returns.
+    pub fn highlight<'s>(
+        &'s self,
+        highlighter: &'s mut Highlighter,
+        range: ops::Range<usize>,
+        cancellation_flag: Option<&'s AtomicUsize>,
+        injection_callback: impl FnMut(&str) -> Option<&'s HighlightConfiguration> + 's,
+    ) -> Result<HighlightIter<'s, impl FnMut(&str) -> Option<&'s HighlightConfiguration> + 's>, Error>
+    {
+        let layer_trees = self
+            .layers
+            .iter()
+            .map(|layer| layer.layer_tree.clone())
+            .collect();
+        highlighter.highlight_layer_trees(
+            self.config,
+            layer_trees,
+            &self.source,
+            range,
+            cancellation_flag,
+            injection_callback,
+        )
+    }
+
+    /// Parse the layers of the document, starting with the document's own language, reusing the
+    /// edited trees of the old layers. Returns the new layers, along with the ranges whose
+    /// syntax changed in them, and the ranges of the layers that were added or removed.
+    fn parse_layers(
+        &mut self,
+        highlighter: &mut Highlighter,
+        old_layers: &[SessionLayer<'a>],
+        cancellation_flag: Option<&AtomicUsize>,
+        injection_callback: &mut impl FnMut(&str) -> Option<&'a HighlightConfiguration>,
+    ) -> Result<(Vec<SessionLayer<'a>>, Vec<ops::Range<usize>>), Error> {
+        let mut layers = Vec::<SessionLayer>::new();
+        let mut changed_ranges = Vec::new();
+        // The index of the old layer that each new layer reuses.
+        let mut reused_layers = Vec::<Option<usize>>::new();
+        let mut is_reused = vec![false; old_layers.len()];
+
+        let mut queue = VecDeque::from([(self.config, 0, vec![DOCUMENT_RANGE], None)]);
+        while let Some((config, depth, ranges, parent)) = queue.pop_front() {
+            // An injected layer can only reuse a layer that was injected into the layer that its
+            // parent reuses. It reuses the layer with the same configuration and the same ranges,
+            // or if there is none, the first one whose ranges overlap with its ranges.
+            let old_parent = parent.map(|parent: usize| reused_layers[parent]);
+            let candidates = old_layers.iter().enumerate().filter(|(i, old_layer)| {
+                !is_reused[*i]
+                    && ptr::eq(old_layer.layer_tree.config, config)
+                    && match old_parent {
+                        None => old_layer.parent.is_none(),
+                        Some(old_parent) => old_parent.is_some() && old_layer.parent == old_parent,
+                    }
+            });
+            let reused_layer = candidates
+                .clone()
+                .find(|(_, old_layer)| old_layer.layer_tree.ranges == ranges)
+                .or_else(|| {
+                    candidates.clone().find(|(_, old_layer)| {
+                        ranges_overlap(&old_layer.layer_tree.ranges, &ranges)
+                    })
+                })
+                .map(|(i, _)| i);
+
+            let old_tree = reused_layer.map(|i| &old_layers[i].layer_tree.tree);
+            let Some(tree) = parse_layer(
+                &mut highlighter.parser,
+                config,
+                &self.source,
+                &ranges,
+                old_tree,
+                cancellation_flag,
+            )?
+            else {
+                continue;
+            };
+
+            match reused_layer {
+                Some(i) if old_layers[i].layer_tree.ranges == ranges => {
+                    let old_tree = &old_layers[i].layer_tree.tree;
+                    changed_ranges.extend(
+                        old_tree
+                            .changed_ranges(&tree)
+                            .map(|range| range.start_byte..range.end_byte),
+                    );
+                }
+                Some(i) => {
+                    changed_ranges.extend(byte_ranges(&old_layers[i].layer_tree.ranges));
+                    changed_ranges.extend(byte_ranges(&ranges));
+                }
+                None => changed_ranges.extend(byte_ranges(&ranges)),
+            }
+            if let Some(i) = reused_layer {
+                is_reused[i] = true;
+            }
+
+            let index = layers.len();
+            for (injection_config, injection_ranges) in
+                self.injections(config, &tree, &ranges, depth, injection_callback)
+            {
+                queue.push_back((injection_config, depth + 1, injection_ranges, Some(index)));
+            }
+            layers.push(SessionLayer {
+                layer_tree: LayerTree {
+                    config,
+                    ranges,
+                    tree,
+                },
+                depth,
+                parent,
+            });
+            reused_layers.push(reused_layer);
+        }
+
+        for (old_layer, is_reused) in old_layers.iter().zip(is_reused) {
+            if !is_reused {
+                changed_ranges.extend(byte_ranges(&old_layer.layer_tree.ranges));
+            }
+        }
+
+        Ok((layers, changed_ranges))
+    }
+
+    /// Find the languages that are injected into a layer, and the ranges of each of them, in the
+    /// same way that [`HighlightIter`] finds them while highlighting.
+    fn injections(
+        &mut self,
+        config: &'a HighlightConfiguration,
+        tree: &Tree,
+        ranges: &[Range],
+        depth: usize,
+        injection_callback: &mut impl FnMut(&str) -> Option<&'a HighlightConfiguration>,
+    ) -> Vec<(&'a HighlightConfiguration, Vec<Range>)> {
+        let language_name = self.config.language_name.as_str();
+        let source = self.source.as_slice();
+        self.cursor.set_byte_range(0..usize::MAX);
+        let mut result = HighlightIterLayer::combined_injections(
+            source,
+            tree,
+            (depth > 0).then_some(language_name),
+            &mut self.cursor,
+            injection_callback,
+            config,
+            ranges,
+        );
+
+        if config.locals_pattern_index > 0 {
+            let enabled_patterns = (0..config.query.pattern_count())
+                .map(|pattern_index| pattern_index < config.locals_pattern_index)
+                .collect::<Vec<_>>();
+            self.cursor.set_enabled_patterns(&enabled_patterns);
+            let mut matches = self.cursor.matches(&config.query, tree.root_node(), source);
+            while let Some(mat) = matches.next() {
+                let (injection_language, content_node, include_children) =
+                    injection_for_match(config, Some(language_name), &config.query, mat, source);
+                if let (Some(injection_language), Some(content_node)) =
+                    (injection_language, content_node)
+                {
+                    if let Some(injection_config) = injection_callback(injection_language) {
+                        let injection_ranges = config.injection_ranges(
+                            mat.pattern_index,
+                            ranges,
+                            &[content_node],
+                            include_children,
+                            source,
+                        );
+                        if !injection_ranges.is_empty() {
+                            result.push((injection_config, injection_ranges));
+                        }
+                    }
+                }
+            }
+            self.cursor.set_enabled_patterns(&[]);
+        }
+
+        result
+    }
+}
+
+/// Update a range to keep it in sync with an edit, in the same way that [`Tree::edit`] updates
+/// a tree's included ranges.
+fn edit_range(range: &mut Range, edit: &InputEdit) {
+    if range.end_byte >= edit.old_end_byte {
+        if range.end_byte != usize::MAX {
+            range.end_byte = edit.new_end_byte + (range.end_byte - edit.old_end_byte);
+            range.end_point = edit_point(range.end_point, edit);
+        }
+    } else if range.end_byte > edit.start_byte {
+        range.end_byte = edit.start_byte;
+        range.end_point = edit.start_position;
+    }
+    if range.start_byte >= edit.old_end_byte {
+        range.start_byte = edit.new_end_byte + (range.start_byte - edit.old_end_byte);
+        range.start_point = edit_point(range.start_point, edit);
+    } else if range.start_byte > edit.start_byte {
+        range.start_byte = edit.start_byte;
+        range.start_point = edit.start_position;
+    }
+}
+
+/// Move a position that comes after an edit by the amount that the edit moves the end of the
+/// text that it replaces.
+const fn edit_point(point: Point, edit: &InputEdit) -> Point {
+    if point.row > edit.old_end_position.row {
+        Point::new(
+            edit.new_end_position.row + point.row - edit.old_end_position.row,
+            point.column,
+        )
+    } else {
+        Point::new(
+            edit.new_end_position.row,
+            edit.new_end_position.column + point.column - edit.old_end_position.column,
+        )
+    }
+}
+
+fn ranges_overlap(a: &[Range], b: &[Range]) -> bool {
+    a.iter().any(|a| {
+        b.iter()
+            .any(|b| a.start_byte < b.end_byte && b.start_byte < a.end_byte)
+    })
+}
+
+fn byte_ranges(ranges: &[Range]) -> impl Iterator<Item = ops::Range<usize>> + '_ {
+    ranges.iter().map(|range| range.start_byte..range.end_byte)
+}
+
+/// Sort a list of ranges, clamping them to the end of the document, and merging the ones that
+/// overlap or touch. Empty ranges are removed.
+fn merge_ranges(mut ranges: Vec<ops::Range<usize>>, end: usize) -> Vec<ops::Range<usize>> {
+    ranges.sort_unstable_by_key(|range| range.start);
+    let mut result = Vec::<ops::Range<usize>>::new();
+    for range in ranges {
+        let range = range.start.min(end)..range.end.min(end);
+        if range.is_empty() {
+            continue;
+        }
+        match result.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => result.push(range),
+        }
+    }
+    result
+}
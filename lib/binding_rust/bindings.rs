@@ -1,6 +1,6 @@
 /* automatically generated by rust-bindgen 0.71.1 */
 
-pub const TREE_SITTER_LANGUAGE_VERSION: u32 = 15;
+pub const TREE_SITTER_LANGUAGE_VERSION: u32 = 16;
 pub const TREE_SITTER_MIN_COMPATIBLE_LANGUAGE_VERSION: u32 = 13;
 pub type TSStateId = u16;
 pub type TSSymbol = u16;
@@ -91,6 +91,20 @@ pub struct TSParseOptions {
     pub progress_callback:
         ::core::option::Option<unsafe extern "C" fn(state: *mut TSParseState) -> bool>,
 }
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct TSParseStats {
+    pub lex_count: u32,
+    pub external_scan_count: u32,
+    pub lex_nanos: u64,
+    pub external_scan_nanos: u64,
+    pub state_count: u32,
+    pub stack_split_count: u32,
+    pub stack_merge_count: u32,
+    pub reused_node_count: u32,
+    pub reused_byte_count: u32,
+    pub tree_byte_count: u64,
+}
 pub const TSLogTypeParse: TSLogType = 0;
 pub const TSLogTypeLex: TSLogType = 1;
 pub type TSLogType = ::core::ffi::c_uint;
@@ -108,6 +122,18 @@ pub struct TSLogger {
 }
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
+pub struct TSDotGraphWriter {
+    pub payload: *mut ::core::ffi::c_void,
+    pub write: ::core::option::Option<
+        unsafe extern "C" fn(
+            payload: *mut ::core::ffi::c_void,
+            data: *const ::core::ffi::c_char,
+            length: u32,
+        ),
+    >,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
 pub struct TSInputEdit {
     pub start_byte: u32,
     pub old_end_byte: u32,
@@ -168,6 +194,21 @@ pub const TSQueryErrorCapture: TSQueryError = 4;
 pub const TSQueryErrorStructure: TSQueryError = 5;
 pub const TSQueryErrorLanguage: TSQueryError = 6;
 pub type TSQueryError = ::core::ffi::c_uint;
+pub const TSTreeDeserializeErrorNone: TSTreeDeserializeError = 0;
+pub const TSTreeDeserializeErrorFormat: TSTreeDeserializeError = 1;
+pub const TSTreeDeserializeErrorVersion: TSTreeDeserializeError = 2;
+pub const TSTreeDeserializeErrorSymbol: TSTreeDeserializeError = 3;
+pub const TSTreeDeserializeErrorField: TSTreeDeserializeError = 4;
+pub const TSTreeDeserializeErrorParseState: TSTreeDeserializeError = 5;
+pub const TSTreeDeserializeErrorRange: TSTreeDeserializeError = 6;
+pub type TSTreeDeserializeError = ::core::ffi::c_uint;
+pub const TSQueryDeserializeErrorNone: TSQueryDeserializeError = 0;
+pub const TSQueryDeserializeErrorFormat: TSQueryDeserializeError = 1;
+pub const TSQueryDeserializeErrorVersion: TSQueryDeserializeError = 2;
+pub const TSQueryDeserializeErrorLanguage: TSQueryDeserializeError = 3;
+pub const TSQueryDeserializeErrorSymbol: TSQueryDeserializeError = 4;
+pub const TSQueryDeserializeErrorField: TSQueryDeserializeError = 5;
+pub type TSQueryDeserializeError = ::core::ffi::c_uint;
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct TSQueryCursorState {
@@ -181,6 +222,13 @@ pub struct TSQueryCursorOptions {
     pub progress_callback:
         ::core::option::Option<unsafe extern "C" fn(state: *mut TSQueryCursorState) -> bool>,
 }
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct TSQueryCursorStats {
+    pub node_count: u32,
+    pub state_count: u32,
+    pub max_in_progress_match_count: u32,
+}
 #[doc = " The metadata associated with a language.\n\n Currently, this metadata can be used to check the [Semantic Version](https://semver.org/)\n of the language. This version information should be used to signal if a given parser might\n be incompatible with existing queries when upgrading between major versions, or minor versions\n if it's in zerover."]
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -285,6 +333,22 @@ extern "C" {
     #[doc = " Set the file descriptor to which the parser should write debugging graphs\n during parsing. The graphs are formatted in the DOT language. You may want\n to pipe these graphs directly to a `dot(1)` process in order to generate\n SVG output. You can turn off this logging by passing a negative number."]
     pub fn ts_parser_print_dot_graphs(self_: *mut TSParser, fd: ::core::ffi::c_int);
 }
+extern "C" {
+    #[doc = " Set the writer to which the parser should pass debugging graphs during\n parsing, as an alternative to [`ts_parser_print_dot_graphs`] that doesn't\n require a file descriptor. Each graph is passed to the writer's `write`\n function in a single call, formatted in the DOT language. You can turn off\n this logging by passing a writer whose `write` function is `NULL`.\n\n The parser does not take ownership over the writer payload. If a writer was\n previously assigned, the caller is responsible for releasing any memory\n owned by the previous writer."]
+    pub fn ts_parser_set_dot_graph_writer(self_: *mut TSParser, writer: TSDotGraphWriter);
+}
+extern "C" {
+    #[doc = " Get the parser's current dot graph writer."]
+    pub fn ts_parser_dot_graph_writer(self_: *const TSParser) -> TSDotGraphWriter;
+}
+extern "C" {
+    #[doc = " Set whether the parser should measure the time spent in the lexer and the\n external scanner, and the memory used by the resulting tree. This slows\n parsing down, so it is off by default. See [`ts_parser_parse_stats`]."]
+    pub fn ts_parser_set_record_stats(self_: *mut TSParser, record_stats: bool);
+}
+extern "C" {
+    #[doc = " Get statistics about the parser's most recent parse, which are reset whenever\n a new parse is started:\n 1. [`lex_count`] and [`external_scan_count`]: The number of times that the\n    internal lexer and the external scanner were called.\n 2. [`lex_nanos`] and [`external_scan_nanos`]: The time spent in the internal\n    lexer and the external scanner, if recording was enabled with\n    [`ts_parser_set_record_stats`].\n 3. [`state_count`]: The number of times that the parser looked up the actions\n    for a parse state and a lookahead token.\n 4. [`stack_split_count`] and [`stack_merge_count`]: The number of times that\n    the parse stack was split because of an ambiguity, and the number of times\n    that two versions of the stack were merged back together.\n 5. [`reused_node_count`] and [`reused_byte_count`]: The number of nodes from\n    the old tree that were reused, and the number of bytes that they span.\n 6. [`tree_byte_count`]: The memory used by the nodes of the resulting tree,\n    including nodes that were reused from the old tree, if recording was\n    enabled with [`ts_parser_set_record_stats`].\n\n [`lex_count`]: TSParseStats::lex_count\n [`external_scan_count`]: TSParseStats::external_scan_count\n [`lex_nanos`]: TSParseStats::lex_nanos\n [`external_scan_nanos`]: TSParseStats::external_scan_nanos\n [`state_count`]: TSParseStats::state_count\n [`stack_split_count`]: TSParseStats::stack_split_count\n [`stack_merge_count`]: TSParseStats::stack_merge_count\n [`reused_node_count`]: TSParseStats::reused_node_count\n [`reused_byte_count`]: TSParseStats::reused_byte_count\n [`tree_byte_count`]: TSParseStats::tree_byte_count"]
+    pub fn ts_parser_parse_stats(self_: *const TSParser) -> TSParseStats;
+}
 extern "C" {
     #[doc = " Create a shallow copy of the syntax tree. This is very fast.\n\n You need to copy a syntax tree in order to use it on more than one thread at\n a time, as syntax trees are not thread safe."]
     pub fn ts_tree_copy(self_: *const TSTree) -> *mut TSTree;
@@ -313,6 +377,10 @@ extern "C" {
     #[doc = " Get the array of included ranges that was used to parse the syntax tree.\n\n The returned pointer must be freed by the caller."]
     pub fn ts_tree_included_ranges(self_: *const TSTree, length: *mut u32) -> *mut TSRange;
 }
+extern "C" {
+    #[doc = " Get the number of bytes of memory that the syntax tree uses.\n\n This includes the memory for all of the tree's nodes, even the ones that\n it shares with other trees, such as copies of it made with [`ts_tree_copy`]\n or trees that reused its nodes when they were parsed. So the memory that\n is freed when the tree is deleted can be less than this."]
+    pub fn ts_tree_memory_usage(self_: *const TSTree) -> usize;
+}
 extern "C" {
     #[doc = " Edit the syntax tree to keep it in sync with source code that has been\n edited.\n\n You must describe the edit both in terms of byte offsets and in terms of\n (row, column) coordinates."]
     pub fn ts_tree_edit(self_: *mut TSTree, edit: *const TSInputEdit);
@@ -329,6 +397,20 @@ extern "C" {
     #[doc = " Write a DOT graph describing the syntax tree to the given file."]
     pub fn ts_tree_print_dot_graph(self_: *const TSTree, file_descriptor: ::core::ffi::c_int);
 }
+extern "C" {
+    #[doc = " Serialize the syntax tree into a compact binary format, so that it can be\n stored or sent elsewhere and turned back into a tree using\n [`ts_tree_deserialize`], without parsing the source code again.\n\n The format stores each node's symbol, range, flags, and the production that\n determines its children's fields, but not the source code itself. Edits that\n haven't been followed by a new parse are not preserved.\n\n The returned buffer is allocated using `malloc` and the caller is responsible\n for freeing it using `free`. Its length will be written to the given `length`\n pointer."]
+    pub fn ts_tree_serialize(self_: *const TSTree, length: *mut u32) -> *mut ::core::ffi::c_char;
+}
+extern "C" {
+    #[doc = " Create a syntax tree from the bytes produced by [`ts_tree_serialize`], for a\n document that is `source_length` bytes long.\n\n The bytes are checked to have been serialized with the same version of the\n format, to only refer to symbols, fields, and parse states that exist in the\n given language, and to describe nodes that fit within the document. If they\n don't, this returns `NULL` and writes the reason to the `error` pointer."]
+    pub fn ts_tree_deserialize(
+        language: *const TSLanguage,
+        source_length: u32,
+        bytes: *const ::core::ffi::c_char,
+        length: u32,
+        error: *mut TSTreeDeserializeError,
+    ) -> *mut TSTree;
+}
 extern "C" {
     #[doc = " Get the node's type as a null-terminated string."]
     pub fn ts_node_type(self_: TSNode) -> *const ::core::ffi::c_char;
@@ -635,6 +717,9 @@ extern "C" {
 extern "C" {
     pub fn ts_query_is_pattern_non_local(self_: *const TSQuery, pattern_index: u32) -> bool;
 }
+extern "C" {
+    pub fn ts_query_pattern_depth(self_: *const TSQuery, pattern_index: u32) -> u32;
+}
 extern "C" {
     pub fn ts_query_is_pattern_guaranteed_at_step(self_: *const TSQuery, byte_offset: u32) -> bool;
 }
@@ -673,6 +758,19 @@ extern "C" {
     #[doc = " Disable a certain pattern within a query.\n\n This prevents the pattern from matching and removes most of the overhead\n associated with the pattern. Currently, there is no way to undo this."]
     pub fn ts_query_disable_pattern(self_: *mut TSQuery, pattern_index: u32);
 }
+extern "C" {
+    #[doc = " Serialize the compiled query into a binary format, so that it can be stored\n and turned back into a query using [`ts_query_deserialize`], without\n compiling its source code again.\n\n The format stores the query's pattern steps, capture names, predicates, and\n string values, along with a fingerprint of the query's language. Captures\n and patterns that have been disabled stay disabled.\n\n The returned buffer is allocated using `malloc` and the caller is responsible\n for freeing it using `free`. Its length will be written to the given `length`\n pointer."]
+    pub fn ts_query_serialize(self_: *const TSQuery, length: *mut u32) -> *mut ::core::ffi::c_char;
+}
+extern "C" {
+    #[doc = " Create a query from the bytes produced by [`ts_query_serialize`].\n\n The bytes are checked to have been serialized with the same version of the\n format, for a language with the same symbols and fields as the given one,\n and to only refer to symbols, fields, captures, and steps that exist. If\n they don't, this returns `NULL` and writes the reason to the `error`\n pointer, and the query should be compiled from its source code instead."]
+    pub fn ts_query_deserialize(
+        language: *const TSLanguage,
+        bytes: *const ::core::ffi::c_char,
+        length: u32,
+        error: *mut TSQueryDeserializeError,
+    ) -> *mut TSQuery;
+}
 extern "C" {
     #[doc = " Create a new cursor for executing a given query.\n\n The cursor stores the state that is needed to iteratively search\n for matches. To use the query cursor, first call [`ts_query_cursor_exec`]\n to start running a given query on a given syntax node. Then, there are\n two options for consuming the results of the query:\n 1. Repeatedly call [`ts_query_cursor_next_match`] to iterate over all of the\n    *matches* in the order that they were found. Each match contains the\n    index of the pattern that matched, and an array of captures. Because\n    multiple patterns can match the same set of nodes, one match may contain\n    captures that appear *before* some of the captures from a previous match.\n 2. Repeatedly call [`ts_query_cursor_next_capture`] to iterate over all of the\n    individual *captures* in the order that they appear. This is useful if\n    don't care about which pattern matched, and just want a single ordered\n    sequence of captures.\n\n If you don't care about consuming all of the results, you can stop calling\n [`ts_query_cursor_next_match`] or [`ts_query_cursor_next_capture`] at any point.\n  You can then start executing another query on another node by calling\n  [`ts_query_cursor_exec`] again."]
     pub fn ts_query_cursor_new() -> *mut TSQueryCursor;
@@ -704,6 +802,16 @@ extern "C" {
 extern "C" {
     pub fn ts_query_cursor_set_match_limit(self_: *mut TSQueryCursor, limit: u32);
 }
+extern "C" {
+    #[doc = " Manage the maximum number of steps that this query cursor can take while\n executing a query.\n\n A step is one check of an in-progress match against a node, which is the\n unit of work that grows when a query's patterns can match in many different\n ways, like patterns with nested or repeated wildcards. If the cursor reaches\n this limit, it halts: it stops looking for new matches and discards its\n in-progress ones, but the matches that it has already finished are still\n returned. The limit is checked before each node is processed, so the cursor\n can take slightly more steps than the limit, for the states that it checks\n against the last node.\n\n The step count is reset whenever [`ts_query_cursor_exec`] is called. By\n default, there is no limit, which is represented by `UINT64_MAX`."]
+    pub fn ts_query_cursor_did_exceed_step_limit(self_: *const TSQueryCursor) -> bool;
+}
+extern "C" {
+    pub fn ts_query_cursor_step_limit(self_: *const TSQueryCursor) -> u64;
+}
+extern "C" {
+    pub fn ts_query_cursor_set_step_limit(self_: *mut TSQueryCursor, limit: u64);
+}
 extern "C" {
     #[doc = " @deprecated use [`ts_query_cursor_exec_with_options`] and pass in a callback instead, this will be removed in 0.26.\n\n Set the maximum duration in microseconds that query execution should be allowed to\n take before halting.\n\n If query execution takes longer than this, it will halt early, returning NULL.\n See [`ts_query_cursor_next_match`] or [`ts_query_cursor_next_capture`] for more information."]
     pub fn ts_query_cursor_set_timeout_micros(self_: *mut TSQueryCursor, timeout_micros: u64);
@@ -748,6 +856,22 @@ extern "C" {
     #[doc = " Set the maximum start depth for a query cursor.\n\n This prevents cursors from exploring children nodes at a certain depth.\n Note if a pattern includes many children, then they will still be checked.\n\n The zero max start depth value can be used as a special behavior and\n it helps to destructure a subtree by staying on a node and using captures\n for interested parts. Note that the zero max start depth only limit a search\n depth for a pattern's root node but other nodes that are parts of the pattern\n may be searched at any depth what defined by the pattern structure.\n\n Set to `UINT32_MAX` to remove the maximum start depth."]
     pub fn ts_query_cursor_set_max_start_depth(self_: *mut TSQueryCursor, max_start_depth: u32);
 }
+extern "C" {
+    #[doc = " Set which of a query's patterns the query cursor should match, without\n changing the query itself. The `enabled_patterns` array contains a flag for\n each pattern index, and the patterns whose index is greater than or equal to\n `length` are enabled, so passing a `length` of zero enables every pattern.\n\n Unlike [`ts_query_disable_pattern`], this makes it possible for different\n cursors to match different subsets of the same query's patterns at the same\n time."]
+    pub fn ts_query_cursor_set_enabled_patterns(
+        self_: *mut TSQueryCursor,
+        enabled_patterns: *const bool,
+        length: u32,
+    );
+}
+extern "C" {
+    #[doc = " Restore all of the query cursor's settings to their defaults: its byte and\n point ranges, its match limit, its step limit, its maximum start depth, its\n timeout, and its enabled patterns.\n This also discards any in-progress or unreturned matches. The memory that\n the cursor has allocated is kept, so that a cursor can be reused for many\n queries more cheaply than creating a new one for each of them."]
+    pub fn ts_query_cursor_reset(self_: *mut TSQueryCursor);
+}
+extern "C" {
+    #[doc = " Get statistics about the query cursor's most recent execution, which are\n reset whenever [`ts_query_cursor_exec`] is called:\n 1. [`node_count`]: The number of nodes that the cursor visited.\n 2. [`state_count`]: The number of times that an in-progress state was\n    checked against a node.\n 3. [`max_in_progress_match_count`]: The largest number of in-progress\n    matches that were storing captures at the same time. This is the number\n    that the match limit bounds, so if it reaches the limit, then\n    [`ts_query_cursor_did_exceed_match_limit`] may return true.\n\n [`node_count`]: TSQueryCursorStats::node_count\n [`state_count`]: TSQueryCursorStats::state_count\n [`max_in_progress_match_count`]: TSQueryCursorStats::max_in_progress_match_count"]
+    pub fn ts_query_cursor_stats(self_: *const TSQueryCursor) -> TSQueryCursorStats;
+}
 extern "C" {
     #[doc = " Get another reference to the given language."]
     pub fn ts_language_copy(self_: *const TSLanguage) -> *const TSLanguage;
@@ -764,6 +888,18 @@ extern "C" {
     #[doc = " Get the number of valid states in this language."]
     pub fn ts_language_state_count(self_: *const TSLanguage) -> u32;
 }
+extern "C" {
+    #[doc = " Get the number of external tokens in this language."]
+    pub fn ts_language_external_token_count(self_: *const TSLanguage) -> u32;
+}
+extern "C" {
+    #[doc = " Get the numerical id of the language's word token, which is the token that\n keywords are lexed as before they are checked against the language's\n keywords, or zero if the language has no word token. If the language has\n several word tokens, this is the first of them."]
+    pub fn ts_language_word_token(self_: *const TSLanguage) -> TSSymbol;
+}
+extern "C" {
+    #[doc = " Check if the given token is one of the language's keywords: a string token\n that is lexed by first lexing a word token, and then checking whether the\n word's text is the keyword's. This is always false for languages that were\n loaded from WebAssembly with a [`TSWasmStore`]."]
+    pub fn ts_language_is_keyword(self_: *const TSLanguage, symbol: TSSymbol) -> bool;
+}
 extern "C" {
     #[doc = " Get the numerical id for the given node type string."]
     pub fn ts_language_symbol_for_name(
@@ -0,0 +1,236 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::{iter, ops::Range};
+
+use super::{InputEdit, Point};
+
+/// The largest number of inserted and deleted lines that the diff looks for before it gives up,
+/// and replaces all of the lines that differ with a single edit.
+const MAX_DIFF_DISTANCE: usize = 1024;
+
+/// Compute the edits that turn `old_text` into `new_text`, for use with [`Tree::edit_all`].
+///
+/// The lines of the two texts are compared with a diff, and each run of lines that differ is
+/// narrowed down to the characters that differ, so that each edit is as small as possible.
+///
+/// The edits are returned in the order in which they appear in the document, and they don't
+/// overlap. Each edit's positions are relative to `old_text`, so the edits have to be applied in
+/// reverse order, which is what [`Tree::edit_all`] does.
+///
+/// [`Tree::edit_all`]: super::Tree::edit_all
+#[must_use]
+pub fn edits_between(old_text: &str, new_text: &str) -> Vec<InputEdit> {
+    let old_lines = old_text.split_inclusive('\n').collect::<Vec<_>>();
+    let new_lines = new_text.split_inclusive('\n').collect::<Vec<_>>();
+    let old_line_starts = line_starts(&old_lines);
+    let new_line_starts = line_starts(&new_lines);
+
+    let mut edits = Vec::new();
+    let mut position = Point::default();
+    let mut position_byte = 0;
+    for (old_range, new_range) in diff_lines(&old_lines, &new_lines) {
+        let old_bytes = old_line_starts[old_range.start]..old_line_starts[old_range.end];
+        let new_bytes = new_line_starts[new_range.start]..new_line_starts[new_range.end];
+        let (old_bytes, new_bytes) = trim_common_affixes(
+            &old_text[old_bytes.clone()],
+            &new_text[new_bytes.clone()],
+            old_bytes.start,
+            new_bytes.start,
+        );
+        if old_bytes.is_empty() && new_bytes.is_empty() {
+            continue;
+        }
+
+        position = advance(position, &old_text[position_byte..old_bytes.start]);
+        position_byte = old_bytes.start;
+        let start_position = position;
+        let inserted_text = &new_text[new_bytes.clone()];
+        edits.push(InputEdit {
+            start_byte: old_bytes.start,
+            old_end_byte: old_bytes.end,
+            new_end_byte: old_bytes.start + inserted_text.len(),
+            start_position,
+            old_end_position: advance(start_position, &old_text[old_bytes]),
+            new_end_position: advance(start_position, inserted_text),
+        });
+    }
+    edits
+}
+
+/// The offsets at which each line starts, followed by the offset at which the last one ends.
+fn line_starts(lines: &[&str]) -> Vec<usize> {
+    iter::once(0)
+        .chain(lines.iter().scan(0, |offset, line| {
+            *offset += line.len();
+            Some(*offset)
+        }))
+        .collect()
+}
+
+/// The position that comes after the given text, if it starts at the given position.
+fn advance(mut position: Point, text: &str) -> Point {
+    match text.rfind('\n') {
+        Some(last_newline) => {
+            position.row += text.bytes().filter(|byte| *byte == b'\n').count();
+            position.column = text.len() - last_newline - 1;
+        }
+        None => position.column += text.len(),
+    }
+    position
+}
+
+/// Remove the longest prefix and suffix that the two texts share, without splitting any
+/// characters, and return the byte ranges of what remains of each text.
+fn trim_common_affixes(
+    old_text: &str,
+    new_text: &str,
+    old_offset: usize,
+    new_offset: usize,
+) -> (Range<usize>, Range<usize>) {
+    let is_boundary =
+        |index: usize| old_text.is_char_boundary(index) && new_text.is_char_boundary(index);
+
+    let mut prefix_len = iter::zip(old_text.bytes(), new_text.bytes())
+        .take_while(|(old_byte, new_byte)| old_byte == new_byte)
+        .count();
+    while !is_boundary(prefix_len) {
+        prefix_len -= 1;
+    }
+
+    let max_suffix_len = old_text.len().min(new_text.len()) - prefix_len;
+    let mut suffix_len = iter::zip(old_text.bytes().rev(), new_text.bytes().rev())
+        .take(max_suffix_len)
+        .take_while(|(old_byte, new_byte)| old_byte == new_byte)
+        .count();
+    while !old_text.is_char_boundary(old_text.len() - suffix_len)
+        || !new_text.is_char_boundary(new_text.len() - suffix_len)
+    {
+        suffix_len -= 1;
+    }
+
+    (
+        old_offset + prefix_len..old_offset + old_text.len() - suffix_len,
+        new_offset + prefix_len..new_offset + new_text.len() - suffix_len,
+    )
+}
+
+/// Find the runs of lines that differ between the two sequences of lines, using Myers' diff
+/// algorithm, and return the range of lines that each run spans in each sequence.
+fn diff_lines(old_lines: &[&str], new_lines: &[&str]) -> Vec<(Range<usize>, Range<usize>)> {
+    let prefix_len = iter::zip(old_lines, new_lines)
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let suffix_len = iter::zip(
+        old_lines[prefix_len..].iter().rev(),
+        new_lines[prefix_len..].iter().rev(),
+    )
+    .take_while(|(old_line, new_line)| old_line == new_line)
+    .count();
+    let old_lines = &old_lines[prefix_len..old_lines.len() - suffix_len];
+    let new_lines = &new_lines[prefix_len..new_lines.len() - suffix_len];
+    let shift = |(old_range, new_range): (Range<usize>, Range<usize>)| {
+        (
+            old_range.start + prefix_len..old_range.end + prefix_len,
+            new_range.start + prefix_len..new_range.end + prefix_len,
+        )
+    };
+
+    let Some(matching_runs) = matching_runs(old_lines, new_lines) else {
+        return Vec::from([shift((0..old_lines.len(), 0..new_lines.len()))]);
+    };
+
+    let mut runs = Vec::new();
+    let (mut old_index, mut new_index) = (0, 0);
+    for (old_start, new_start, len) in
+        matching_runs
+            .into_iter()
+            .chain(iter::once((old_lines.len(), new_lines.len(), 0)))
+    {
+        if old_start > old_index || new_start > new_index {
+            runs.push(shift((old_index..old_start, new_index..new_start)));
+        }
+        old_index = old_start + len;
+        new_index = new_start + len;
+    }
+    runs
+}
+
+/// Find the shortest sequence of insertions and deletions that turns `old_lines` into
+/// `new_lines`, and return the runs of lines that it leaves in place, as the index at which each
+/// run starts in each sequence, and its length.
+///
+/// This returns `None` if the sequences differ by more than `MAX_DIFF_DISTANCE` lines.
+fn matching_runs(old_lines: &[&str], new_lines: &[&str]) -> Option<Vec<(usize, usize, usize)>> {
+    let (old_len, new_len) = (old_lines.len() as isize, new_lines.len() as isize);
+    let max_distance = (old_len + new_len).min(MAX_DIFF_DISTANCE as isize);
+
+    // For each diagonal `k`, the furthest index into `old_lines` that a path with the current
+    // number of insertions and deletions reaches on that diagonal. Before each step, the part of
+    // it that the step reads from is saved, so that the path can be traced back afterwards.
+    let offset = max_distance + 1;
+    let mut furthest = iter::repeat(0)
+        .take(2 * offset as usize + 1)
+        .collect::<Vec<isize>>();
+    let mut history = Vec::new();
+    let index = |k: isize| (offset + k) as usize;
+    let mut distance = None;
+    'search: for d in 0..=max_distance {
+        history.push(furthest[index(-d - 1)..=index(d + 1)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && furthest[index(k - 1)] < furthest[index(k + 1)]) {
+                furthest[index(k + 1)]
+            } else {
+                furthest[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while old_lines
+                .get(x as usize)
+                .is_some_and(|old_line| new_lines.get(y as usize) == Some(old_line))
+            {
+                x += 1;
+                y += 1;
+            }
+            furthest[index(k)] = x;
+            if x >= old_len && y >= new_len {
+                distance = Some(d);
+                break 'search;
+            }
+        }
+    }
+
+    let mut runs = Vec::new();
+    let (mut x, mut y) = (old_len, new_len);
+    for d in (0..=distance?).rev() {
+        let furthest = &history[d as usize];
+        let index = |k: isize| (d + 1 + k) as usize;
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && furthest[index(k - 1)] < furthest[index(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = if d == 0 {
+            0
+        } else {
+            furthest[index(previous_k)]
+        };
+        let previous_y = if d == 0 { 0 } else { previous_x - previous_k };
+
+        // The path reaches `(x, y)` with an insertion or a deletion, followed by a run of
+        // matching lines.
+        let (run_x, run_y) = if d == 0 {
+            (previous_x, previous_y)
+        } else if previous_k == k + 1 {
+            (previous_x, previous_y + 1)
+        } else {
+            (previous_x + 1, previous_y)
+        };
+        if x > run_x {
+            runs.push((run_x as usize, run_y as usize, (x - run_x) as usize));
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    runs.reverse();
+    Some(runs)
+}
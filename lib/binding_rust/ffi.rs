@@ -20,11 +20,18 @@ extern "C" {
     pub(crate) fn _ts_dup(handle: *mut std::os::raw::c_void) -> std::os::raw::c_int;
 }
 
-use core::{marker::PhantomData, mem::ManuallyDrop, ptr::NonNull, str};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::{
+    marker::PhantomData,
+    mem::{self, ManuallyDrop},
+    ptr::NonNull,
+    str,
+};
 
 use crate::{
     Language, LookaheadIterator, Node, ParseState, Parser, Query, QueryCursor, QueryCursorState,
-    QueryError, Tree, TreeCursor,
+    QueryError, Tree, TreeCursor, QUERY_CURSOR_DEFAULT_RANGE,
 };
 
 impl Language {
@@ -168,13 +175,20 @@ impl QueryCursor {
     pub const unsafe fn from_raw(ptr: *mut TSQueryCursor) -> Self {
         Self {
             ptr: NonNull::new_unchecked(ptr),
+            predicate_handlers: Vec::new(),
+            range: QUERY_CURSOR_DEFAULT_RANGE,
+            containment: false,
         }
     }
 
     /// Consumes the [`QueryCursor`], returning a raw pointer to the underlying C structure.
+    ///
+    /// The cursor's predicate handlers are dropped.
     #[must_use]
     pub fn into_raw(self) -> *mut TSQueryCursor {
-        ManuallyDrop::new(self).ptr.as_ptr()
+        let mut cursor = ManuallyDrop::new(self);
+        drop(mem::take(&mut cursor.predicate_handlers));
+        cursor.ptr.as_ptr()
     }
 }
 
@@ -0,0 +1,113 @@
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    rc::Rc,
+};
+
+/// The number of bytes that [`Parser::parse_io`](super::Parser::parse_io) reads at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The number of bytes in the longest UTF-8 character. A chunk that ends in the middle of a
+/// character makes the parser ask for a new chunk that starts at that character, so a cached
+/// chunk is only used if at least this many bytes remain in it.
+const MAX_CHAR_LEN: usize = 4;
+
+/// A reader that provides the parser with chunks of text, reading and caching one chunk at a
+/// time so that the parser can jump back and forth within the text without reading all of it
+/// into memory.
+pub struct IoInput<R> {
+    reader: R,
+    /// The offset that the reader is at, or `None` if it's unknown and has to be sought to.
+    reader_offset: Option<u64>,
+    chunk: IoChunk,
+    /// Whether the cached chunk ends at the end of the text.
+    chunk_is_last: bool,
+    error: Option<io::Error>,
+}
+
+/// A chunk of text that is shared with the input's cache, starting at a given offset within it.
+pub struct IoChunk {
+    data: Rc<[u8]>,
+    start: usize,
+    offset: usize,
+}
+
+impl<R: Read + Seek> IoInput<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            reader_offset: None,
+            chunk: IoChunk::empty(),
+            chunk_is_last: false,
+            error: None,
+        }
+    }
+
+    /// Get the text that starts at the given offset, which is empty at the end of the text, or
+    /// once reading has failed.
+    pub fn read(&mut self, offset: usize) -> IoChunk {
+        if self.error.is_some() {
+            return IoChunk::empty();
+        }
+
+        let chunk_end = self.chunk.start + self.chunk.data.len();
+        let is_cached = offset >= self.chunk.start
+            && (offset + MAX_CHAR_LEN <= chunk_end || (self.chunk_is_last && offset <= chunk_end));
+        if !is_cached {
+            if let Err(error) = self.read_chunk(offset) {
+                self.error = Some(error);
+                return IoChunk::empty();
+            }
+        }
+
+        IoChunk {
+            data: self.chunk.data.clone(),
+            start: self.chunk.start,
+            offset: offset - self.chunk.start,
+        }
+    }
+
+    /// Get the error that stopped the input from being read, if any.
+    pub fn into_error(self) -> Option<io::Error> {
+        self.error
+    }
+
+    fn read_chunk(&mut self, start: usize) -> io::Result<()> {
+        if self.reader_offset != Some(start as u64) {
+            self.reader_offset = None;
+            self.reader.seek(SeekFrom::Start(start as u64))?;
+        }
+
+        // `read_to_end` keeps reading after short reads, and retries interrupted ones.
+        let mut data = Vec::with_capacity(CHUNK_SIZE);
+        self.reader_offset = None;
+        self.reader
+            .by_ref()
+            .take(CHUNK_SIZE as u64)
+            .read_to_end(&mut data)?;
+        self.reader_offset = Some((start + data.len()) as u64);
+
+        self.chunk_is_last = data.len() < CHUNK_SIZE;
+        self.chunk = IoChunk {
+            data: data.into(),
+            start,
+            offset: 0,
+        };
+        Ok(())
+    }
+}
+
+impl IoChunk {
+    fn empty() -> Self {
+        Self {
+            data: Rc::from([]),
+            start: 0,
+            offset: 0,
+        }
+    }
+}
+
+impl AsRef<[u8]> for IoChunk {
+    fn as_ref(&self) -> &[u8] {
+        &self.data[self.offset..]
+    }
+}
@@ -2,7 +2,13 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+mod diff;
 pub mod ffi;
+#[cfg(feature = "std")]
+mod io_input;
+mod line_index;
+mod logging;
+mod shared_tree;
 mod util;
 
 #[cfg(not(feature = "std"))]
@@ -20,14 +26,20 @@ use core::{
     ptr::{self, NonNull},
     slice, str,
     sync::atomic::AtomicUsize,
+    time::Duration,
 };
-#[cfg(feature = "std")]
-use std::error;
 #[cfg(all(unix, feature = "std"))]
 use std::os::fd::AsRawFd;
 #[cfg(all(windows, feature = "std"))]
 use std::os::windows::io::AsRawHandle;
+#[cfg(feature = "std")]
+use std::{error, io, time::Instant};
 
+pub use diff::edits_between;
+pub use line_index::LineIndex;
+use logging::LoggerPayload;
+pub use logging::{LexFunction, LogCategory, LogMessage};
+pub use shared_tree::SharedTree;
 pub use streaming_iterator::{StreamingIterator, StreamingIteratorMut};
 use tree_sitter_language::LanguageFn;
 
@@ -168,6 +180,8 @@ impl QueryCursorState {
 #[derive(Default)]
 pub struct ParseOptions<'a> {
     pub progress_callback: Option<ParseProgressCallback<'a>>,
+    #[cfg(feature = "std")]
+    deadline: Option<Instant>,
 }
 
 impl<'a> ParseOptions<'a> {
@@ -176,11 +190,133 @@ impl<'a> ParseOptions<'a> {
         Self::default()
     }
 
+    /// Set a callback that is called periodically during parsing, including while a long token
+    /// is being lexed by the lex function or the external scanner. Parsing is cancelled if the
+    /// callback returns `true`.
     #[must_use]
     pub fn progress_callback<F: FnMut(&ParseState) -> bool>(mut self, callback: &'a mut F) -> Self {
         self.progress_callback = Some(callback);
         self
     }
+
+    /// Set a point in time after which parsing is cancelled. The deadline is checked every time
+    /// that the progress callback would be called.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[must_use]
+    pub const fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Create the C options for a parse, which call back into these options. The options must
+    /// not be moved until the parse is done.
+    fn as_raw(&mut self) -> ffi::TSParseOptions {
+        // This C function is passed to Tree-sitter as the progress callback.
+        unsafe extern "C" fn progress(state: *mut ffi::TSParseState) -> bool {
+            let options = (*state).payload.cast::<ParseOptions>().as_mut().unwrap();
+            options.should_cancel(&ParseState::from_raw(state))
+        }
+
+        #[cfg(feature = "std")]
+        let has_deadline = self.deadline.is_some();
+        #[cfg(not(feature = "std"))]
+        let has_deadline = false;
+
+        if self.progress_callback.is_none() && !has_deadline {
+            return ffi::TSParseOptions {
+                payload: ptr::null_mut(),
+                progress_callback: None,
+            };
+        }
+        ffi::TSParseOptions {
+            payload: ptr::from_mut(self).cast::<c_void>(),
+            progress_callback: Some(progress),
+        }
+    }
+
+    fn should_cancel(&mut self, state: &ParseState) -> bool {
+        #[cfg(feature = "std")]
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return true;
+        }
+        self.progress_callback
+            .as_mut()
+            .is_some_and(|callback| callback(state))
+    }
+}
+
+/// Counters describing the work that a [`Parser`] did during its most recent parse.
+///
+/// The durations and the tree size are only measured when recording has been
+/// enabled with [`Parser::set_record_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParseStats {
+    /// The number of tokens requested from the internal lexer.
+    pub lex_count: usize,
+    /// The number of calls made to the external scanner.
+    pub external_scan_count: usize,
+    /// The time spent in the internal lexer.
+    pub lex_duration: Duration,
+    /// The time spent in the external scanner.
+    pub external_scan_duration: Duration,
+    /// The number of parse states that were processed.
+    pub state_count: usize,
+    /// The number of times the parse stack split because of a conflict.
+    pub stack_split_count: usize,
+    /// The number of times two stack versions were merged back together.
+    pub stack_merge_count: usize,
+    /// The number of nodes reused from the old tree.
+    pub reused_node_count: usize,
+    /// The number of bytes covered by the reused nodes.
+    pub reused_byte_count: usize,
+    /// The number of bytes allocated for the resulting tree.
+    pub tree_byte_count: usize,
+}
+
+impl From<ffi::TSParseStats> for ParseStats {
+    fn from(stats: ffi::TSParseStats) -> Self {
+        Self {
+            lex_count: stats.lex_count as usize,
+            external_scan_count: stats.external_scan_count as usize,
+            lex_duration: Duration::from_nanos(stats.lex_nanos),
+            external_scan_duration: Duration::from_nanos(stats.external_scan_nanos),
+            state_count: stats.state_count as usize,
+            stack_split_count: stats.stack_split_count as usize,
+            stack_merge_count: stats.stack_merge_count as usize,
+            reused_node_count: stats.reused_node_count as usize,
+            reused_byte_count: stats.reused_byte_count as usize,
+            tree_byte_count: stats.tree_byte_count as usize,
+        }
+    }
+}
+
+/// Counters describing the work that a [`QueryCursor`] did during its most recent execution.
+///
+/// These can be used to choose a match limit for a query with
+/// [`QueryCursor::set_match_limit`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryCursorStats {
+    /// The number of nodes that were visited.
+    pub node_count: usize,
+    /// The number of times an in-progress state was checked against a node.
+    pub state_count: usize,
+    /// The largest number of in-progress matches that were storing captures at the same time,
+    /// which is what the match limit bounds.
+    pub max_in_progress_match_count: usize,
+}
+
+impl From<ffi::TSQueryCursorStats> for QueryCursorStats {
+    fn from(stats: ffi::TSQueryCursorStats) -> Self {
+        Self {
+            node_count: stats.node_count as usize,
+            state_count: stats.state_count as usize,
+            max_in_progress_match_count: stats.max_in_progress_match_count as usize,
+        }
+    }
 }
 
 #[derive(Default)]
@@ -220,7 +356,7 @@ impl Drop for QueryCursorOptionsDrop {
 }
 
 /// A type of log message.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LogType {
     Parse,
     Lex,
@@ -231,12 +367,23 @@ type FieldId = NonZeroU16;
 /// A callback that receives log messages during parsing.
 type Logger<'a> = Box<dyn FnMut(LogType, &str) + 'a>;
 
+/// A callback that receives structured log messages during parsing.
+type StructuredLogger<'a> = Box<dyn FnMut(&LogMessage) + 'a>;
+
+/// A writer that receives debugging graphs during parsing.
+#[cfg(feature = "std")]
+type DotGraphWriter<'a> = Box<dyn io::Write + Send + 'a>;
+
 /// A callback that receives the parse state during parsing.
 type ParseProgressCallback<'a> = &'a mut dyn FnMut(&ParseState) -> bool;
 
 /// A callback that receives the query state during query execution.
 type QueryProgressCallback<'a> = &'a mut dyn FnMut(&QueryCursorState) -> bool;
 
+/// A callback that decides whether a match satisfies a custom query predicate.
+type QueryPredicateHandler =
+    Box<dyn Fn(&QueryMatch, &[QueryPredicateArg], &mut QueryPredicateText) -> bool + Send + Sync>;
+
 pub trait Decode {
     /// A callback that decodes the next code point from the input slice. It should return the code
     /// point, and how many bytes were decoded.
@@ -288,6 +435,9 @@ impl From<ffi::TSQuantifier> for CaptureQuantifier {
 #[doc(alias = "TSQueryCursor")]
 pub struct QueryCursor {
     ptr: NonNull<ffi::TSQueryCursor>,
+    predicate_handlers: Vec<(Box<str>, QueryPredicateHandler)>,
+    range: Range,
+    containment: bool,
 }
 
 /// A key-value pair associated with a particular pattern in a [`Query`].
@@ -311,6 +461,13 @@ pub struct QueryPredicate {
     pub args: Box<[QueryPredicateArg]>,
 }
 
+/// Reads the text of the nodes in a match, for a handler that is registered with
+/// [`QueryCursor::set_predicate_handler`].
+pub struct QueryPredicateText<'a> {
+    read: &'a mut dyn FnMut(Node, &mut Vec<u8>),
+    buffer: &'a mut Vec<u8>,
+}
+
 /// A match of a [`Query`] to a particular set of [`Node`]s.
 pub struct QueryMatch<'cursor, 'tree> {
     pub pattern_index: usize,
@@ -327,6 +484,10 @@ pub struct QueryMatches<'query, 'tree: 'query, T: TextProvider<I>, I: AsRef<[u8]
     buffer1: Vec<u8>,
     buffer2: Vec<u8>,
     current_match: Option<QueryMatch<'query, 'tree>>,
+    predicate_handlers: &'query [(Box<str>, QueryPredicateHandler)],
+    range: &'query mut Range,
+    containing_node_range: Option<Range>,
+    include_unsatisfied: bool,
     _options: Option<QueryCursorOptionsDrop>,
     _phantom: PhantomData<(&'tree (), I)>,
 }
@@ -342,10 +503,20 @@ pub struct QueryCaptures<'query, 'tree: 'query, T: TextProvider<I>, I: AsRef<[u8
     buffer1: Vec<u8>,
     buffer2: Vec<u8>,
     current_match: Option<(QueryMatch<'query, 'tree>, usize)>,
+    predicate_handlers: &'query [(Box<str>, QueryPredicateHandler)],
+    range: &'query mut Range,
+    containing_node_range: Option<Range>,
     _options: Option<QueryCursorOptionsDrop>,
     _phantom: PhantomData<(&'tree (), I)>,
 }
 
+/// A sequence of [`QueryMatch`]es in the parts of a [`Tree`] that changed after an edit,
+/// associated with a given [`QueryCursor`].
+pub struct QueryChangedMatches<'query, 'tree: 'query, T: TextProvider<I>, I: AsRef<[u8]>> {
+    matches: QueryMatches<'query, 'tree, T, I>,
+    ranges: Vec<Range>,
+}
+
 pub trait TextProvider<I>
 where
     I: AsRef<[u8]>,
@@ -354,6 +525,70 @@ where
     fn text(&mut self, node: Node) -> Self::I;
 }
 
+/// A source whose text is stored in chunks, like a rope, that the text of a [`Node`] can be
+/// read from with [`Node::text_with`], without copying the chunks into one contiguous buffer.
+pub trait ChunkProvider<'a> {
+    /// Return the chunk that contains the byte at the given offset, along with the offset at
+    /// which the chunk starts, or `None` if the offset is past the end of the text.
+    fn chunk_at_byte(&mut self, byte: usize) -> Option<(&'a [u8], usize)>;
+}
+
+/// A [`ChunkProvider`] over a sequence of string chunks, which finds the chunk that contains a
+/// given byte offset with a binary search.
+#[derive(Clone, Debug, Default)]
+pub struct StrChunks<'a> {
+    chunks: Vec<(usize, &'a str)>,
+}
+
+/// An iterator over the text of a [`Node`], in the pieces that it is stored in, which is
+/// returned by [`Node::text_with`].
+pub struct NodeTextChunks<'a, C: ChunkProvider<'a>> {
+    chunk_provider: C,
+    byte: usize,
+    end_byte: usize,
+    _phantom: PhantomData<&'a ()>,
+}
+
+/// An iterator over a [`Node`] and all of its descendants, in pre-order or post-order, which is
+/// returned by [`Node::preorder`] and [`Node::postorder`].
+///
+/// The iterator is driven by a [`TreeCursor`], so it doesn't allocate while it's advanced. The
+/// cursor stays on the node that was returned most recently, whose depth and field can be read
+/// with [`depth`](Traversal::depth) and [`field_name`](Traversal::field_name).
+pub struct Traversal<'tree, P = fn(&Node<'tree>) -> bool> {
+    cursor: TreeCursor<'tree>,
+    order: TraversalOrder,
+    named_only: bool,
+    prune: P,
+    started: bool,
+    done: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TraversalOrder {
+    Preorder,
+    Postorder,
+}
+
+/// A syntax error in a [`Tree`], which is returned by [`Tree::errors`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SyntaxError<'tree> {
+    /// The `ERROR` or `MISSING` node.
+    pub node: Node<'tree>,
+    pub kind: SyntaxErrorKind,
+    /// The kind of the closest named ancestor of the node, if there is one.
+    pub context: Option<&'static str>,
+}
+
+/// The kind of a [`SyntaxError`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyntaxErrorKind {
+    /// Text that the parser couldn't fit into the grammar, which it skipped over.
+    Error,
+    /// A token that the parser inserted to recover from an error, with the name of its kind.
+    Missing(&'static str),
+}
+
 /// A particular [`Node`] that has been captured with a particular name within a
 /// [`Query`].
 #[derive(Clone, Copy, Debug)]
@@ -372,7 +607,57 @@ pub struct LanguageError {
 
 /// An error that occurred in [`Parser::set_included_ranges`].
 #[derive(Debug, PartialEq, Eq)]
-pub struct IncludedRangesError(pub usize);
+pub struct IncludedRangesError {
+    /// The index of the first invalid range.
+    pub index: usize,
+    pub kind: IncludedRangesErrorKind,
+}
+
+/// The reason that a range passed to [`Parser::set_included_ranges`] is invalid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IncludedRangesErrorKind {
+    /// The range's end byte is before its start byte.
+    EndBeforeStart,
+    /// The range starts before the previous range ends.
+    OverlapsPrevious,
+    /// The range's end point is before its start point, or its start point is before the
+    /// previous range's end point, so its points can't match its byte offsets.
+    PointsOutOfOrder,
+}
+
+/// An error that occurred in [`Tree::from_bytes`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreeDeserializeError {
+    /// The bytes weren't produced by [`Tree::to_bytes`], or were truncated.
+    Format,
+    /// The bytes were produced by a version of the library that uses a different format.
+    Version,
+    /// A node's symbol doesn't exist in the language.
+    Symbol,
+    /// A node's production, which determines the fields of its children, doesn't exist in the
+    /// language.
+    Field,
+    /// A node's parse state doesn't exist in the language.
+    ParseState,
+    /// A node doesn't fit within the source code, or the included ranges overlap.
+    Range,
+}
+
+/// An error that occurred in [`Query::deserialize`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryDeserializeError {
+    /// The bytes weren't produced by [`Query::serialize`], or were truncated.
+    Format,
+    /// The bytes were produced by a version of the library that uses a different format.
+    Version,
+    /// The query was serialized for a different language, or the language's ABI version isn't
+    /// supported by this version of the library.
+    Language,
+    /// The query was serialized for a language with a different number of node types.
+    Symbol,
+    /// The query was serialized for a language with a different number of fields.
+    Field,
+}
 
 /// An error that occurred when trying to create a [`Query`].
 #[derive(Debug, PartialEq, Eq)]
@@ -460,6 +745,21 @@ impl Language {
         }
     }
 
+    /// Get the semantic version of this language as a `(major, minor, patch)` tuple.
+    ///
+    /// This returns `None` for languages that were generated without metadata, which includes
+    /// every language with an ABI version below 15. See also [`Language::metadata`].
+    #[must_use]
+    pub fn semantic_version(&self) -> Option<(u16, u16, u16)> {
+        self.metadata().map(|metadata| {
+            (
+                metadata.major_version.into(),
+                metadata.minor_version.into(),
+                metadata.patch_version.into(),
+            )
+        })
+    }
+
     /// Get the number of distinct node types in this language.
     #[doc(alias = "ts_language_symbol_count")]
     #[must_use]
@@ -474,6 +774,24 @@ impl Language {
         unsafe { ffi::ts_language_state_count(self.0) as usize }
     }
 
+    /// Get the number of external tokens in this language, which are the tokens that its
+    /// external scanner produces.
+    #[doc(alias = "ts_language_external_token_count")]
+    #[must_use]
+    pub fn external_token_count(&self) -> usize {
+        unsafe { ffi::ts_language_external_token_count(self.0) as usize }
+    }
+
+    /// Get the numerical id of the language's word token, which is the token that keywords are
+    /// lexed as before they are checked against the language's keywords. If the language has
+    /// several word tokens, this is the first of them.
+    #[doc(alias = "ts_language_word_token")]
+    #[must_use]
+    pub fn word_token(&self) -> Option<u16> {
+        let symbol = unsafe { ffi::ts_language_word_token(self.0) };
+        (symbol != 0).then_some(symbol)
+    }
+
     /// Get a list of all supertype symbols for the language.
     #[doc(alias = "ts_language_supertypes")]
     #[must_use]
@@ -504,6 +822,29 @@ impl Language {
         }
     }
 
+    /// Check if the node kind with the given id is a subtype of the given supertype, either
+    /// directly or through other supertypes that are themselves subtypes of it.
+    ///
+    /// A node kind isn't considered to be a subtype of itself. This always returns `false` for
+    /// languages with an ABI version below 15, which don't contain any supertype information.
+    #[must_use]
+    pub fn node_kind_is_subtype_of(&self, kind_id: u16, supertype_id: u16) -> bool {
+        let mut visited = Vec::new();
+        let mut stack = Vec::from([supertype_id]);
+        while let Some(supertype) = stack.pop() {
+            for &subtype in self.subtypes_for_supertype(supertype) {
+                if subtype == kind_id {
+                    return true;
+                }
+                if self.node_kind_is_supertype(subtype) && !visited.contains(&subtype) {
+                    visited.push(subtype);
+                    stack.push(subtype);
+                }
+            }
+        }
+        false
+    }
+
     /// Get the name of the node kind for the given numerical id.
     #[doc(alias = "ts_language_symbol_name")]
     #[must_use]
@@ -540,6 +881,17 @@ impl Language {
         unsafe { ffi::ts_language_symbol_type(self.0, id) <= ffi::TSSymbolTypeAnonymous }
     }
 
+    /// Check if the node type for the given numerical id is one of the language's keywords,
+    /// which are the string tokens that are lexed as the word token first, and then matched
+    /// against the word's text.
+    ///
+    /// This is always false for languages that were loaded from WebAssembly.
+    #[doc(alias = "ts_language_is_keyword")]
+    #[must_use]
+    pub fn is_keyword(&self, id: u16) -> bool {
+        unsafe { ffi::ts_language_is_keyword(self.0, id) }
+    }
+
     /// Check if the node type for the given numerical id is a supertype.
     #[must_use]
     pub fn node_kind_is_supertype(&self, id: u16) -> bool {
@@ -688,49 +1040,86 @@ impl Parser {
     #[must_use]
     pub fn logger(&self) -> Option<&Logger> {
         let logger = unsafe { ffi::ts_parser_logger(self.0.as_ptr()) };
-        unsafe { logger.payload.cast::<Logger>().as_ref() }
+        unsafe { logger.payload.cast::<LoggerPayload>().as_ref() }?
+            .logger
+            .as_ref()
     }
 
     /// Set the logging callback that a parser should use during parsing.
     #[doc(alias = "ts_parser_set_logger")]
     pub fn set_logger(&mut self, logger: Option<Logger>) {
-        let prev_logger = unsafe { ffi::ts_parser_logger(self.0.as_ptr()) };
-        if !prev_logger.payload.is_null() {
-            drop(unsafe { Box::from_raw(prev_logger.payload.cast::<Logger>()) });
-        }
-
-        let c_logger;
-        if let Some(logger) = logger {
-            let container = Box::new(logger);
-
-            unsafe extern "C" fn log(
-                payload: *mut c_void,
-                c_log_type: ffi::TSLogType,
-                c_message: *const c_char,
-            ) {
-                let callback = payload.cast::<Logger>().as_mut().unwrap();
-                if let Ok(message) = CStr::from_ptr(c_message).to_str() {
-                    let log_type = if c_log_type == ffi::TSLogTypeParse {
-                        LogType::Parse
-                    } else {
-                        LogType::Lex
-                    };
-                    callback(log_type, message);
-                }
-            }
+        self.update_logger_payload(|payload| payload.logger = logger);
+    }
+
+    /// Set a logging callback that receives each log message along with the structured
+    /// information that it contains, such as the parse state, the symbol, and the lex state.
+    ///
+    /// This callback is used alongside the one set with [`Parser::set_logger`], and it receives
+    /// the same messages. See also [`LogMessage`].
+    #[doc(alias = "ts_parser_set_logger")]
+    pub fn set_structured_logger(&mut self, logger: Option<StructuredLogger>) {
+        self.update_logger_payload(|payload| payload.structured_logger = logger);
+    }
 
-            let raw_container = Box::into_raw(container);
+    /// Set the categories of log messages that the parser's logging callbacks receive. Passing
+    /// `None` makes them receive every message.
+    ///
+    /// For example, passing `Some(&[LogCategory::Reduce])` makes the callbacks only receive the
+    /// messages about reductions.
+    pub fn set_log_filter(&mut self, categories: Option<&[LogCategory]>) {
+        self.update_logger_payload(|payload| payload.filter = categories.map(Box::from));
+    }
 
-            c_logger = ffi::TSLogger {
-                payload: raw_container.cast::<c_void>(),
-                log: Some(log),
-            };
+    /// Forward the parser's log messages to the [`tracing`](https://docs.rs/tracing) crate, as
+    /// events with the `tree_sitter` target. Parse messages are logged at the `DEBUG` level, and
+    /// lex messages at the `TRACE` level, with the fields of each [`LogMessage`].
+    ///
+    /// This replaces the callback set with [`Parser::set_structured_logger`], and respects the
+    /// filter set with [`Parser::set_log_filter`].
+    #[cfg(feature = "tracing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+    pub fn set_tracing_logger(&mut self) {
+        self.set_structured_logger(Some(Box::new(logging::trace)));
+    }
+
+    fn update_logger_payload<'a>(&mut self, update: impl FnOnce(&mut LoggerPayload<'a>)) {
+        // This C function is passed to Tree-sitter as the logging callback.
+        unsafe extern "C" fn log(
+            payload: *mut c_void,
+            c_log_type: ffi::TSLogType,
+            c_message: *const c_char,
+        ) {
+            let payload = payload.cast::<LoggerPayload>().as_mut().unwrap();
+            if let Ok(message) = CStr::from_ptr(c_message).to_str() {
+                let log_type = if c_log_type == ffi::TSLogTypeParse {
+                    LogType::Parse
+                } else {
+                    LogType::Lex
+                };
+                payload.log(log_type, message);
+            }
+        }
+
+        let prev_logger = unsafe { ffi::ts_parser_logger(self.0.as_ptr()) };
+        let mut payload = if prev_logger.payload.is_null() {
+            LoggerPayload::default()
         } else {
-            c_logger = ffi::TSLogger {
+            *unsafe { Box::from_raw(prev_logger.payload.cast::<LoggerPayload<'a>>()) }
+        };
+        update(&mut payload);
+
+        let c_logger = if payload.is_empty() {
+            ffi::TSLogger {
                 payload: ptr::null_mut(),
                 log: None,
-            };
-        }
+            }
+        } else {
+            let has_callback = payload.has_callback();
+            ffi::TSLogger {
+                payload: Box::into_raw(Box::new(payload)).cast::<c_void>(),
+                log: if has_callback { Some(log) } else { None },
+            }
+        };
 
         unsafe { ffi::ts_parser_set_logger(self.0.as_ptr(), c_logger) };
     }
@@ -748,6 +1137,8 @@ impl Parser {
         #[cfg(unix)] file: &impl AsRawFd,
         #[cfg(windows)] file: &impl AsRawHandle,
     ) {
+        self.stop_printing_dot_graphs();
+
         #[cfg(unix)]
         {
             let fd = file.as_raw_fd();
@@ -771,7 +1162,58 @@ impl Parser {
     #[cfg(feature = "std")]
     #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
     pub fn stop_printing_dot_graphs(&mut self) {
+        let writer = unsafe { ffi::ts_parser_dot_graph_writer(self.0.as_ptr()) };
         unsafe { ffi::ts_parser_print_dot_graphs(self.0.as_ptr(), -1) }
+        if !writer.payload.is_null() {
+            let mut writer = unsafe { Box::from_raw(writer.payload.cast::<DotGraphWriter>()) };
+            let _ = writer.flush();
+        }
+    }
+
+    /// Set the writer to which the parser should write debugging graphs
+    /// during parsing, instead of a file. The graphs are formatted in the DOT
+    /// language, and each one is passed to the writer with a single call to
+    /// [`write_all`](io::Write::write_all). Errors returned by the writer are
+    /// ignored.
+    ///
+    /// The parser owns the writer until [`Parser::stop_printing_dot_graphs`]
+    /// is called, or until the parser is dropped.
+    #[doc(alias = "ts_parser_set_dot_graph_writer")]
+    #[cfg(not(target_os = "wasi"))]
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn print_dot_graphs_to<'a>(&mut self, writer: impl io::Write + Send + 'a) {
+        // This C function is passed to Tree-sitter as the dot graph writer.
+        unsafe extern "C" fn write(payload: *mut c_void, data: *const c_char, length: u32) {
+            let writer = payload.cast::<DotGraphWriter>().as_mut().unwrap();
+            let graph = slice::from_raw_parts(data.cast::<u8>(), length as usize);
+            let _ = writer.write_all(graph);
+        }
+
+        self.stop_printing_dot_graphs();
+        let writer: DotGraphWriter<'a> = Box::new(writer);
+        let c_writer = ffi::TSDotGraphWriter {
+            payload: Box::into_raw(Box::new(writer)).cast::<c_void>(),
+            write: Some(write),
+        };
+        unsafe { ffi::ts_parser_set_dot_graph_writer(self.0.as_ptr(), c_writer) };
+    }
+
+    /// Set whether the parser should time the lexer and the external scanner,
+    /// and measure the size of each tree it produces.
+    ///
+    /// This adds some overhead to parsing, so it is disabled by default. The
+    /// results are available from [`Parser::parse_stats`].
+    #[doc(alias = "ts_parser_set_record_stats")]
+    pub fn set_record_stats(&mut self, record_stats: bool) {
+        unsafe { ffi::ts_parser_set_record_stats(self.0.as_ptr(), record_stats) }
+    }
+
+    /// Get the counters collected during the parser's most recent parse.
+    #[doc(alias = "ts_parser_parse_stats")]
+    #[must_use]
+    pub fn parse_stats(&self) -> ParseStats {
+        unsafe { ffi::ts_parser_parse_stats(self.0.as_ptr()) }.into()
     }
 
     /// Parse a slice of UTF8 text.
@@ -797,6 +1239,39 @@ impl Parser {
         )
     }
 
+    /// Parse UTF8 text that is read from a reader, such as a file.
+    ///
+    /// The text is read in chunks of 64 KiB, and only the most recently read chunk is kept in
+    /// memory, so that large files can be parsed without reading all of them into memory. When
+    /// the parser needs text from a different part of the file, for example when it reuses nodes
+    /// from `old_tree`, the reader seeks to that text. Byte offsets are relative to the start of
+    /// the reader, and the text is passed to the parser as it is, even if it isn't valid UTF8.
+    ///
+    /// # Arguments:
+    /// * `reader` The reader to read the UTF8-encoded text from.
+    /// * `old_tree` A previous syntax tree parsed from the same document. If the text of the
+    ///   document has changed since `old_tree` was created, then you must edit `old_tree` to match
+    ///   the new text using [`Tree::edit`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from or seeking within the reader fails. Otherwise, returns
+    /// `None` in the same cases as [`Parser::parse`].
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn parse_io<R: io::Read + io::Seek>(
+        &mut self,
+        reader: R,
+        old_tree: Option<&Tree>,
+    ) -> io::Result<Option<Tree>> {
+        let mut input = io_input::IoInput::new(reader);
+        let tree = self.parse_with_options(&mut |i, _| input.read(i), old_tree, None);
+        match input.into_error() {
+            Some(error) => Err(error),
+            None => Ok(tree),
+        }
+    }
+
     /// Parse a slice of UTF16 text.
     ///
     /// # Arguments:
@@ -848,7 +1323,8 @@ impl Parser {
     /// * `old_tree` A previous syntax tree parsed from the same document. If the text of the
     ///   document has changed since `old_tree` was created, then you must edit `old_tree` to match
     ///   the new text using [`Tree::edit`].
-    /// * `options` Options for parsing the text. This can be used to set a progress callback.
+    /// * `options` Options for parsing the text. This can be used to set a progress callback or a
+    ///   deadline.
     pub fn parse_with_options<T: AsRef<[u8]>, F: FnMut(usize, Point) -> T>(
         &mut self,
         callback: &mut F,
@@ -857,16 +1333,6 @@ impl Parser {
     ) -> Option<Tree> {
         type Payload<'a, F, T> = (&'a mut F, Option<T>);
 
-        // This C function is passed to Tree-sitter as the progress callback.
-        unsafe extern "C" fn progress(state: *mut ffi::TSParseState) -> bool {
-            let callback = (*state)
-                .payload
-                .cast::<ParseProgressCallback>()
-                .as_mut()
-                .unwrap();
-            callback(&ParseState::from_raw(state))
-        }
-
         // This C function is passed to Tree-sitter as the input callback.
         unsafe extern "C" fn read<T: AsRef<[u8]>, F: FnMut(usize, Point) -> T>(
             payload: *mut c_void,
@@ -881,25 +1347,8 @@ impl Parser {
             slice.as_ptr().cast::<c_char>()
         }
 
-        let empty_options = ffi::TSParseOptions {
-            payload: ptr::null_mut(),
-            progress_callback: None,
-        };
-
-        let mut callback_ptr;
-        let parse_options = if let Some(options) = options {
-            if let Some(cb) = options.progress_callback {
-                callback_ptr = cb;
-                ffi::TSParseOptions {
-                    payload: core::ptr::addr_of_mut!(callback_ptr).cast::<c_void>(),
-                    progress_callback: Some(progress),
-                }
-            } else {
-                empty_options
-            }
-        } else {
-            empty_options
-        };
+        let mut options = options.unwrap_or_default();
+        let parse_options = options.as_raw();
 
         // A pointer to this payload is passed on every call to the `read` C function.
         // The payload contains two things:
@@ -981,7 +1430,8 @@ impl Parser {
     /// * `old_tree` A previous syntax tree parsed from the same document. If the text of the
     ///   document has changed since `old_tree` was created, then you must edit `old_tree` to match
     ///   the new text using [`Tree::edit`].
-    /// * `options` Options for parsing the text. This can be used to set a progress callback.
+    /// * `options` Options for parsing the text. This can be used to set a progress callback or a
+    ///   deadline.
     pub fn parse_utf16_le_with_options<T: AsRef<[u16]>, F: FnMut(usize, Point) -> T>(
         &mut self,
         callback: &mut F,
@@ -990,15 +1440,6 @@ impl Parser {
     ) -> Option<Tree> {
         type Payload<'a, F, T> = (&'a mut F, Option<T>);
 
-        unsafe extern "C" fn progress(state: *mut ffi::TSParseState) -> bool {
-            let callback = (*state)
-                .payload
-                .cast::<ParseProgressCallback>()
-                .as_mut()
-                .unwrap();
-            callback(&ParseState::from_raw(state))
-        }
-
         // This C function is passed to Tree-sitter as the input callback.
         unsafe extern "C" fn read<T: AsRef<[u16]>, F: FnMut(usize, Point) -> T>(
             payload: *mut c_void,
@@ -1019,25 +1460,8 @@ impl Parser {
             slice.as_ptr().cast::<c_char>()
         }
 
-        let empty_options = ffi::TSParseOptions {
-            payload: ptr::null_mut(),
-            progress_callback: None,
-        };
-
-        let mut callback_ptr;
-        let parse_options = if let Some(options) = options {
-            if let Some(cb) = options.progress_callback {
-                callback_ptr = cb;
-                ffi::TSParseOptions {
-                    payload: core::ptr::addr_of_mut!(callback_ptr).cast::<c_void>(),
-                    progress_callback: Some(progress),
-                }
-            } else {
-                empty_options
-            }
-        } else {
-            empty_options
-        };
+        let mut options = options.unwrap_or_default();
+        let parse_options = options.as_raw();
 
         // A pointer to this payload is passed on every call to the `read` C function.
         // The payload contains two things:
@@ -1097,7 +1521,8 @@ impl Parser {
     /// * `old_tree` A previous syntax tree parsed from the same document. If the text of the
     ///   document has changed since `old_tree` was created, then you must edit `old_tree` to match
     ///   the new text using [`Tree::edit`].
-    /// * `options` Options for parsing the text. This can be used to set a progress callback.
+    /// * `options` Options for parsing the text. This can be used to set a progress callback or a
+    ///   deadline.
     pub fn parse_utf16_be_with_options<T: AsRef<[u16]>, F: FnMut(usize, Point) -> T>(
         &mut self,
         callback: &mut F,
@@ -1106,16 +1531,6 @@ impl Parser {
     ) -> Option<Tree> {
         type Payload<'a, F, T> = (&'a mut F, Option<T>);
 
-        // This C function is passed to Tree-sitter as the progress callback.
-        unsafe extern "C" fn progress(state: *mut ffi::TSParseState) -> bool {
-            let callback = (*state)
-                .payload
-                .cast::<ParseProgressCallback>()
-                .as_mut()
-                .unwrap();
-            callback(&ParseState::from_raw(state))
-        }
-
         // This C function is passed to Tree-sitter as the input callback.
         unsafe extern "C" fn read<T: AsRef<[u16]>, F: FnMut(usize, Point) -> T>(
             payload: *mut c_void,
@@ -1136,25 +1551,8 @@ impl Parser {
             slice.as_ptr().cast::<c_char>()
         }
 
-        let empty_options = ffi::TSParseOptions {
-            payload: ptr::null_mut(),
-            progress_callback: None,
-        };
-
-        let mut callback_ptr;
-        let parse_options = if let Some(options) = options {
-            if let Some(cb) = options.progress_callback {
-                callback_ptr = cb;
-                ffi::TSParseOptions {
-                    payload: core::ptr::addr_of_mut!(callback_ptr).cast::<c_void>(),
-                    progress_callback: Some(progress),
-                }
-            } else {
-                empty_options
-            }
-        } else {
-            empty_options
-        };
+        let mut options = options.unwrap_or_default();
+        let parse_options = options.as_raw();
 
         // A pointer to this payload is passed on every call to the `read` C function.
         // The payload contains two things:
@@ -1193,7 +1591,8 @@ impl Parser {
     /// * `old_tree` A previous syntax tree parsed from the same document. If the text of the
     ///   document has changed since `old_tree` was created, then you must edit `old_tree` to match
     ///   the new text using [`Tree::edit`].
-    /// * `options` Options for parsing the text. This can be used to set a progress callback.
+    /// * `options` Options for parsing the text. This can be used to set a progress callback or a
+    ///   deadline.
     ///
     /// Additionally, you must set the generic parameter [`D`] to a type that implements the
     /// [`Decode`] trait. This trait has a single method, [`decode`](Decode::decode), which takes a
@@ -1207,15 +1606,6 @@ impl Parser {
     ) -> Option<Tree> {
         type Payload<'a, F, T> = (&'a mut F, Option<T>);
 
-        unsafe extern "C" fn progress(state: *mut ffi::TSParseState) -> bool {
-            let callback = (*state)
-                .payload
-                .cast::<ParseProgressCallback>()
-                .as_mut()
-                .unwrap();
-            callback(&ParseState::from_raw(state))
-        }
-
         // At compile time, create a C-compatible callback that calls the custom `decode` method.
         unsafe extern "C" fn decode_fn<D: Decode>(
             data: *const u8,
@@ -1243,25 +1633,8 @@ impl Parser {
             slice.as_ptr().cast::<c_char>()
         }
 
-        let empty_options = ffi::TSParseOptions {
-            payload: ptr::null_mut(),
-            progress_callback: None,
-        };
-
-        let mut callback_ptr;
-        let parse_options = if let Some(options) = options {
-            if let Some(cb) = options.progress_callback {
-                callback_ptr = cb;
-                ffi::TSParseOptions {
-                    payload: core::ptr::addr_of_mut!(callback_ptr).cast::<c_void>(),
-                    progress_callback: Some(progress),
-                }
-            } else {
-                empty_options
-            }
-        } else {
-            empty_options
-        };
+        let mut options = options.unwrap_or_default();
+        let parse_options = options.as_raw();
 
         // A pointer to this payload is passed on every call to the `read` C function.
         // The payload contains two things:
@@ -1344,35 +1717,52 @@ impl Parser {
     /// ```text
     ///     ranges[i].end_byte <= ranges[i + 1].start_byte
     /// ```
-    /// If this requirement is not satisfied, method will return
-    /// [`IncludedRangesError`] error with an offset in the passed ranges
-    /// slice pointing to a first incorrect range.
+    /// The same must hold for the ranges' points, since the text isn't
+    /// available to check that each point matches its byte offset.
+    ///
+    /// If these requirements are not satisfied, this method will return an
+    /// [`IncludedRangesError`] with the index of the first invalid range, and
+    /// the reason that it is invalid. The parser's included ranges are left
+    /// unchanged.
     #[doc(alias = "ts_parser_set_included_ranges")]
     pub fn set_included_ranges(&mut self, ranges: &[Range]) -> Result<(), IncludedRangesError> {
+        let mut prev_end_byte = 0;
+        let mut prev_end_point = Point::default();
+        for (index, range) in ranges.iter().enumerate() {
+            let kind = if range.end_byte < range.start_byte {
+                IncludedRangesErrorKind::EndBeforeStart
+            } else if range.start_byte < prev_end_byte {
+                IncludedRangesErrorKind::OverlapsPrevious
+            } else if range.end_point < range.start_point || range.start_point < prev_end_point {
+                IncludedRangesErrorKind::PointsOutOfOrder
+            } else {
+                prev_end_byte = range.end_byte;
+                prev_end_point = range.end_point;
+                continue;
+            };
+            return Err(IncludedRangesError { index, kind });
+        }
+
+        // The ranges have already been checked more thoroughly than the C
+        // library checks them, so this can't fail.
         let ts_ranges = ranges.iter().copied().map(Into::into).collect::<Vec<_>>();
-        let result = unsafe {
+        unsafe {
             ffi::ts_parser_set_included_ranges(
                 self.0.as_ptr(),
                 ts_ranges.as_ptr(),
                 ts_ranges.len() as u32,
             )
         };
-
-        if result {
-            Ok(())
-        } else {
-            let mut prev_end_byte = 0;
-            for (i, range) in ranges.iter().enumerate() {
-                if range.start_byte < prev_end_byte || range.end_byte < range.start_byte {
-                    return Err(IncludedRangesError(i));
-                }
-                prev_end_byte = range.end_byte;
-            }
-            Err(IncludedRangesError(0))
-        }
+        Ok(())
     }
 
     /// Get the ranges of text that the parser will include when parsing.
+    ///
+    /// These are the ranges that the parser actually uses. If no ranges have
+    /// been set, or an empty slice was passed to [`set_included_ranges`], this
+    /// returns a single range that spans the entire document.
+    ///
+    /// [`set_included_ranges`]: Parser::set_included_ranges
     #[doc(alias = "ts_parser_included_ranges")]
     #[must_use]
     pub fn included_ranges(&self) -> Vec<Range> {
@@ -1433,7 +1823,7 @@ impl Parser {
 impl Drop for Parser {
     fn drop(&mut self) {
         self.stop_printing_dot_graphs();
-        self.set_logger(None);
+        self.update_logger_payload(|payload| *payload = LoggerPayload::default());
         unsafe { ffi::ts_parser_delete(self.0.as_ptr()) }
     }
 }
@@ -1482,20 +1872,51 @@ impl Tree {
         unsafe { ffi::ts_tree_edit(self.0.as_ptr(), &edit) };
     }
 
+    /// Edit the syntax tree to keep it in sync with source code that has been edited in several
+    /// places at once.
+    ///
+    /// The edits must be in the order in which they appear in the document, must not overlap,
+    /// and must all be relative to the text before any of them were made, like the edits that
+    /// [`edits_between`] returns. They are applied in reverse order, so that applying one edit
+    /// doesn't shift the positions of the ones that are yet to be applied.
+    pub fn edit_all(&mut self, edits: &[InputEdit]) {
+        for edit in edits.iter().rev() {
+            self.edit(edit);
+        }
+    }
+
     /// Create a new [`TreeCursor`] starting from the root of the tree.
     #[must_use]
     pub fn walk(&self) -> TreeCursor {
         self.root_node().walk()
     }
 
-    /// Compare this old edited syntax tree to a new syntax tree representing
-    /// the same document, returning a sequence of ranges whose syntactic
-    /// structure has changed.
+    /// Iterate over all of the nodes in the tree in pre-order, so that each node comes before
+    /// its descendants.
     ///
-    /// For this to work correctly, this syntax tree must have been edited such
-    /// that its ranges match up to the new tree. Generally, you'll want to
-    /// call this method right after calling one of the [`Parser::parse`]
-    /// functions. Call it on the old tree that was passed to parse, and
+    /// See also [`Node::preorder`].
+    #[must_use]
+    pub fn preorder(&self) -> Traversal {
+        self.root_node().preorder()
+    }
+
+    /// Iterate over all of the nodes in the tree in post-order, so that each node comes after
+    /// its descendants.
+    ///
+    /// See also [`Node::postorder`].
+    #[must_use]
+    pub fn postorder(&self) -> Traversal {
+        self.root_node().postorder()
+    }
+
+    /// Compare this old edited syntax tree to a new syntax tree representing
+    /// the same document, returning a sequence of ranges whose syntactic
+    /// structure has changed.
+    ///
+    /// For this to work correctly, this syntax tree must have been edited such
+    /// that its ranges match up to the new tree. Generally, you'll want to
+    /// call this method right after calling one of the [`Parser::parse`]
+    /// functions. Call it on the old tree that was passed to parse, and
     /// pass the new tree that was returned from `parse`.
     #[doc(alias = "ts_tree_get_changed_ranges")]
     #[must_use]
@@ -1511,6 +1932,43 @@ impl Tree {
         }
     }
 
+    /// Iterate over the syntax errors in the tree, in the order in which they appear in the
+    /// document.
+    ///
+    /// Only subtrees that contain errors are visited, so this takes time proportional to the
+    /// number of errors rather than to the size of the tree. Errors that are nested inside of
+    /// an `ERROR` node aren't returned separately.
+    pub fn errors(&self) -> impl Iterator<Item = SyntaxError> {
+        self.root_node()
+            .preorder()
+            .prune(|node| !node.has_error() || node.is_error())
+            .filter_map(|node| {
+                let kind = if node.is_error() {
+                    SyntaxErrorKind::Error
+                } else if node.is_missing() {
+                    SyntaxErrorKind::Missing(node.kind())
+                } else {
+                    return None;
+                };
+                let context = iter::successors(node.parent(), Node::parent)
+                    .find(Node::is_named)
+                    .map(|ancestor| ancestor.kind());
+                Some(SyntaxError {
+                    node,
+                    kind,
+                    context,
+                })
+            })
+    }
+
+    /// Get the ranges of the syntax errors in the tree.
+    ///
+    /// See also [`errors`](Tree::errors).
+    #[must_use]
+    pub fn error_ranges(&self) -> Vec<Range> {
+        self.errors().map(|error| error.node.range()).collect()
+    }
+
     /// Get the included ranges that were used to parse the syntax tree.
     #[doc(alias = "ts_tree_included_ranges")]
     #[must_use]
@@ -1525,6 +1983,71 @@ impl Tree {
         }
     }
 
+    /// Get the number of bytes of memory that the syntax tree uses.
+    ///
+    /// This includes the memory for all of the tree's nodes, even the ones that it shares with
+    /// other trees, such as its clones, or trees that reused its nodes when they were parsed
+    /// incrementally. The memory that is freed when the tree is dropped can therefore be less
+    /// than this, and adding up the memory usage of several related trees overestimates how
+    /// much memory they use together.
+    #[doc(alias = "ts_tree_memory_usage")]
+    #[must_use]
+    pub fn memory_usage(&self) -> usize {
+        unsafe { ffi::ts_tree_memory_usage(self.0.as_ptr()) }
+    }
+
+    /// Serialize the syntax tree into a compact binary format, which can be turned back into a
+    /// tree with [`Tree::from_bytes`] without parsing the source code again.
+    ///
+    /// The format stores each node's symbol, range, flags, and the production that determines
+    /// its children's fields, but not the source code itself. Edits that haven't been followed
+    /// by a new parse are not preserved.
+    #[doc(alias = "ts_tree_serialize")]
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut length = 0u32;
+        unsafe {
+            let ptr = ffi::ts_tree_serialize(self.0.as_ptr(), core::ptr::addr_of_mut!(length));
+            let result = slice::from_raw_parts(ptr.cast::<u8>(), length as usize).to_vec();
+            (FREE_FN)(ptr.cast::<c_void>());
+            result
+        }
+    }
+
+    /// Create a syntax tree from the bytes produced by [`Tree::to_bytes`], for a document that is
+    /// `source_len` bytes long.
+    ///
+    /// The bytes must have been serialized with the same version of the format, and may only
+    /// refer to symbols, fields, and parse states that exist in the given language, and to nodes
+    /// that fit within the document.
+    #[doc(alias = "ts_tree_deserialize")]
+    pub fn from_bytes(
+        language: &Language,
+        source_len: usize,
+        bytes: &[u8],
+    ) -> Result<Self, TreeDeserializeError> {
+        let length = u32::try_from(bytes.len()).map_err(|_| TreeDeserializeError::Format)?;
+        let source_len = u32::try_from(source_len).unwrap_or(u32::MAX);
+        let mut error = ffi::TSTreeDeserializeErrorNone;
+        let ptr = unsafe {
+            ffi::ts_tree_deserialize(
+                language.0,
+                source_len,
+                bytes.as_ptr().cast::<c_char>(),
+                length,
+                core::ptr::addr_of_mut!(error),
+            )
+        };
+        NonNull::new(ptr).map(Self).ok_or(match error {
+            ffi::TSTreeDeserializeErrorVersion => TreeDeserializeError::Version,
+            ffi::TSTreeDeserializeErrorSymbol => TreeDeserializeError::Symbol,
+            ffi::TSTreeDeserializeErrorField => TreeDeserializeError::Field,
+            ffi::TSTreeDeserializeErrorParseState => TreeDeserializeError::ParseState,
+            ffi::TSTreeDeserializeErrorRange => TreeDeserializeError::Range,
+            _ => TreeDeserializeError::Format,
+        })
+    }
+
     /// Print a graph of the tree to the given file descriptor.
     /// The graph is formatted in the DOT language. You may want to pipe this
     /// graph directly to a `dot(1)` process in order to generate SVG
@@ -1550,6 +2073,14 @@ impl Tree {
             unsafe { ffi::ts_tree_print_dot_graph(self.0.as_ptr(), handle as i32) }
         }
     }
+
+    /// Get a graph of the tree, formatted in the DOT language.
+    ///
+    /// See [`Node::to_dot`] for more information.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        self.root_node().to_dot()
+    }
 }
 
 impl fmt::Debug for Tree {
@@ -1700,6 +2231,74 @@ impl<'tree> Node<'tree> {
         unsafe { ffi::ts_node_is_missing(self.0) }
     }
 
+    /// Get the parse state that the parser was in when it reached this node, if this node is an
+    /// error or is missing.
+    ///
+    /// Neither kind of node records a parse state of its own, so this is the state after the
+    /// node that comes before it, or the state at the start of the closest ancestor that it is
+    /// at the start of. Passing it to [`Language::lookahead_iterator`] gives the symbols that the
+    /// parser would have accepted instead of this node.
+    ///
+    /// This returns `None` for any other node.
+    #[must_use]
+    pub fn expected_parse_state(&self) -> Option<u16> {
+        if !self.is_error() && !self.is_missing() {
+            return None;
+        }
+
+        // State `0` is the state that the parser is in while it recovers from an error, and
+        // nodes that directly contain an error have no parse state.
+        let is_valid = |state: u16| state != 0 && state != u16::MAX;
+        let mut node = *self;
+        loop {
+            let mut sibling = node.prev_sibling();
+            while let Some(previous) = sibling {
+                if !previous.is_extra() && is_valid(previous.parse_state()) {
+                    let state = previous.next_parse_state();
+                    if is_valid(state) {
+                        return Some(state);
+                    }
+                }
+                sibling = previous.prev_sibling();
+            }
+            match node.parent() {
+                Some(parent) if is_valid(parent.parse_state()) => {
+                    return Some(parent.parse_state())
+                }
+                Some(parent) => node = parent,
+                // The parser starts in state `1`.
+                None => return Some(1),
+            }
+        }
+    }
+
+    /// Get the kinds of nodes that the parser would have accepted instead of this node, if this
+    /// node is an error or is missing.
+    ///
+    /// These include both tokens and larger nodes that can start at this position, but not
+    /// hidden or supertype nodes. They are sorted and deduplicated. For any other node, this
+    /// returns an empty vector.
+    ///
+    /// See [`expected_parse_state`](Node::expected_parse_state) for how the parse state that
+    /// they are valid in is found.
+    #[must_use]
+    pub fn expected_kinds(&self) -> Vec<&'static str> {
+        let language = self.language();
+        let Some(lookahead) = self
+            .expected_parse_state()
+            .and_then(|state| language.lookahead_iterator(state))
+        else {
+            return Vec::new();
+        };
+        let mut kinds = lookahead
+            .filter(|symbol| language.node_kind_is_visible(*symbol))
+            .filter_map(|symbol| language.node_kind_for_id(symbol))
+            .collect::<Vec<_>>();
+        kinds.sort_unstable();
+        kinds.dedup();
+        kinds
+    }
+
     /// Get the byte offsets where this node starts.
     #[doc(alias = "ts_node_start_byte")]
     #[must_use]
@@ -1732,6 +2331,14 @@ impl<'tree> Node<'tree> {
         }
     }
 
+    /// Get the range of this node in terms of rows and UTF-16 code units, like the ranges of
+    /// the Language Server Protocol, using a [`LineIndex`] for the text that the node's tree
+    /// was parsed from.
+    #[must_use]
+    pub fn lsp_range(&self, line_index: &LineIndex) -> ops::Range<Point> {
+        line_index.utf16_range(self.byte_range())
+    }
+
     /// Get this node's start position in terms of rows and columns.
     #[doc(alias = "ts_node_start_point")]
     #[must_use]
@@ -1815,6 +2422,33 @@ impl<'tree> Node<'tree> {
         Self::new(unsafe { ffi::ts_node_child_by_field_id(self.0, field_id) })
     }
 
+    /// Get the node at the end of a path of nested fields, by following the first child with
+    /// each field name in turn.
+    ///
+    /// For example, `node.child_by_field_path(&["declarator", "name"])` is the same as
+    /// `node.child_by_field_name("declarator").and_then(|n| n.child_by_field_name("name"))`.
+    /// Returns `None` if any of the fields is missing. If some of the fields may have multiple
+    /// children, access all of the matching nodes using
+    /// [`children_by_field_path`](Node::children_by_field_path).
+    #[must_use]
+    pub fn child_by_field_path(&self, path: &[&str]) -> Option<Self> {
+        let language = self.language();
+        path.iter().try_fold(*self, |node, field_name| {
+            node.child_by_field_id(language.field_id_for_name(field_name)?.get())
+        })
+    }
+
+    /// Get the node at the end of a path of nested fields, given by their numerical ids.
+    ///
+    /// See also [`child_by_field_path`](Node::child_by_field_path). Resolving the field names
+    /// to ids once, using [`Language::field_id_for_name`], avoids looking them up every time.
+    #[must_use]
+    pub fn child_by_field_id_path(&self, path: &[FieldId]) -> Option<Self> {
+        path.iter().try_fold(*self, |node, field_id| {
+            node.child_by_field_id(field_id.get())
+        })
+    }
+
     /// Get the field name of this node's child at the given index.
     #[doc(alias = "ts_node_field_name_for_child")]
     #[must_use]
@@ -1856,6 +2490,35 @@ impl<'tree> Node<'tree> {
         })
     }
 
+    /// Iterate over this node's children, along with the name of the field that each child
+    /// belongs to, if any.
+    ///
+    /// See also [`Node::children`].
+    pub fn children_with_fields<'cursor>(
+        &self,
+        cursor: &'cursor mut TreeCursor<'tree>,
+    ) -> impl ExactSizeIterator<Item = (Option<&'static str>, Node<'tree>)> + 'cursor {
+        cursor.reset(*self);
+        cursor.goto_first_child();
+        (0..self.child_count()).map(move |_| {
+            let result = (cursor.field_name(), cursor.node());
+            cursor.goto_next_sibling();
+            result
+        })
+    }
+
+    /// Iterate over this node's children that belong to a field, along with the field's name.
+    ///
+    /// A field with several children yields each of them, in order. See also
+    /// [`Node::children_with_fields`].
+    pub fn fields<'cursor>(
+        &self,
+        cursor: &'cursor mut TreeCursor<'tree>,
+    ) -> impl Iterator<Item = (&'static str, Node<'tree>)> + 'cursor {
+        self.children_with_fields(cursor)
+            .filter_map(|(field_name, child)| Some((field_name?, child)))
+    }
+
     /// Iterate over this node's named children.
     ///
     /// See also [`Node::children`].
@@ -1908,6 +2571,43 @@ impl<'tree> Node<'tree> {
         })
     }
 
+    /// Get all of the nodes at the end of a path of nested fields, by following every child with
+    /// each field name in turn, in the order that they appear in the document.
+    ///
+    /// See also [`Node::child_by_field_path`].
+    pub fn children_by_field_path(
+        &self,
+        path: &[&str],
+        cursor: &mut TreeCursor<'tree>,
+    ) -> Vec<Self> {
+        let language = self.language();
+        path.iter()
+            .map(|field_name| language.field_id_for_name(field_name))
+            .collect::<Option<Vec<_>>>()
+            .map_or_else(Vec::new, |path| {
+                self.children_by_field_id_path(&path, cursor)
+            })
+    }
+
+    /// Get all of the nodes at the end of a path of nested fields, given by their numerical ids.
+    ///
+    /// See also [`Node::children_by_field_path`].
+    pub fn children_by_field_id_path(
+        &self,
+        path: &[FieldId],
+        cursor: &mut TreeCursor<'tree>,
+    ) -> Vec<Self> {
+        let mut nodes = Vec::from([*self]);
+        for field_id in path {
+            let mut children = Vec::new();
+            for node in nodes {
+                children.extend(node.children_by_field_id(*field_id, cursor));
+            }
+            nodes = children;
+        }
+        nodes
+    }
+
     /// Iterate over this node's children with a given field id.
     ///
     /// See also [`Node::children_by_field_name`].
@@ -2051,6 +2751,68 @@ impl<'tree> Node<'tree> {
         result
     }
 
+    /// Get a graph of this node and its descendants, formatted in the DOT
+    /// language. You may want to pipe this graph directly to a `dot(1)`
+    /// process in order to generate SVG output.
+    ///
+    /// Like in the graphs that [`Tree::print_dot_graph`] prints, leaf nodes are
+    /// drawn without a border, extra nodes are gray, and nodes that have been
+    /// edited are outlined in green. Unlike those graphs, only the nodes that
+    /// are visible in the tree are included, and each edge is labeled with the
+    /// child's field name, if it has one.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut graph = String::from("digraph tree {\nedge [arrowhead=none]\n");
+        let mut cursor = self.walk();
+        let mut parent_ids = Vec::new();
+        let mut node_count = 0;
+        loop {
+            let node = cursor.node();
+            let id = node_count;
+            node_count += 1;
+
+            write!(graph, "node_{id} [label=\"{}\"", DotString(node.kind())).unwrap();
+            if node.child_count() == 0 {
+                graph.push_str(", shape=plaintext");
+            }
+            if node.is_extra() {
+                graph.push_str(", fontcolor=gray");
+            }
+            if node.has_changes() {
+                graph.push_str(", color=green, penwidth=2");
+            }
+            writeln!(
+                graph,
+                ", tooltip=\"range: {} - {}\\nstart: {}\\nend: {}\"]",
+                node.start_byte(),
+                node.end_byte(),
+                node.start_position(),
+                node.end_position(),
+            )
+            .unwrap();
+
+            if let Some(parent_id) = parent_ids.last() {
+                write!(graph, "node_{parent_id} -> node_{id}").unwrap();
+                if let Some(field_name) = cursor.field_name() {
+                    write!(graph, " [label=\"{}\"]", DotString(field_name)).unwrap();
+                }
+                graph.push('\n');
+            }
+
+            if cursor.goto_first_child() {
+                parent_ids.push(id);
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    graph.push_str("}\n");
+                    return graph;
+                }
+                parent_ids.pop();
+            }
+        }
+    }
+
     pub fn utf8_text<'a>(&self, source: &'a [u8]) -> Result<&'a str, str::Utf8Error> {
         str::from_utf8(&source[self.start_byte()..self.end_byte()])
     }
@@ -2060,6 +2822,20 @@ impl<'tree> Node<'tree> {
         &source[self.start_byte()..self.end_byte()]
     }
 
+    /// Get the text of this node from a source that is stored in chunks, like a rope.
+    ///
+    /// The returned iterator yields the node's text in pieces, one for every chunk that the node
+    /// overlaps, which are borrowed from the chunks rather than copied. It yields nothing for a
+    /// zero-width node, and stops early if the chunk provider runs out of text.
+    pub fn text_with<'a, C: ChunkProvider<'a>>(&self, chunk_provider: C) -> NodeTextChunks<'a, C> {
+        NodeTextChunks {
+            chunk_provider,
+            byte: self.start_byte(),
+            end_byte: self.end_byte(),
+            _phantom: PhantomData,
+        }
+    }
+
     /// Create a new [`TreeCursor`] starting from this node.
     ///
     /// Note that the given node is considered the root of the cursor,
@@ -2070,6 +2846,26 @@ impl<'tree> Node<'tree> {
         TreeCursor(unsafe { ffi::ts_tree_cursor_new(self.0) }, PhantomData)
     }
 
+    /// Iterate over this node and all of its descendants in pre-order, so that each node comes
+    /// before its descendants.
+    ///
+    /// Like a [`TreeCursor`] created with [`walk`](Node::walk), the traversal doesn't leave this
+    /// node, and the depths that it reports are relative to it.
+    #[must_use]
+    pub fn preorder(&self) -> Traversal<'tree> {
+        Traversal::new(self.walk(), TraversalOrder::Preorder)
+    }
+
+    /// Iterate over this node and all of its descendants in post-order, so that each node comes
+    /// after its descendants.
+    ///
+    /// Like a [`TreeCursor`] created with [`walk`](Node::walk), the traversal doesn't leave this
+    /// node, and the depths that it reports are relative to it.
+    #[must_use]
+    pub fn postorder(&self) -> Traversal<'tree> {
+        Traversal::new(self.walk(), TraversalOrder::Postorder)
+    }
+
     /// Edit this node to keep it in-sync with source code that has been edited.
     ///
     /// This function is only rarely needed. When you edit a syntax tree with
@@ -2492,6 +3288,63 @@ impl Query {
         unsafe { Self::from_raw_parts(ptr, source) }
     }
 
+    /// Create a query from the bytes produced by [`Query::serialize`], without compiling its
+    /// source code again.
+    ///
+    /// This fails if the bytes were serialized with a different version of the format, or for a
+    /// language with different node types or fields than the given one. The query should then be
+    /// compiled from its source code with [`Query::new`] instead.
+    #[doc(alias = "ts_query_deserialize")]
+    pub fn deserialize(language: &Language, bytes: &[u8]) -> Result<Self, QueryDeserializeError> {
+        let length = u32::try_from(bytes.len()).map_err(|_| QueryDeserializeError::Format)?;
+        let mut error = ffi::TSQueryDeserializeErrorNone;
+        let ptr = unsafe {
+            ffi::ts_query_deserialize(
+                language.0,
+                bytes.as_ptr().cast::<c_char>(),
+                length,
+                core::ptr::addr_of_mut!(error),
+            )
+        };
+        if ptr.is_null() {
+            return Err(match error {
+                ffi::TSQueryDeserializeErrorVersion => QueryDeserializeError::Version,
+                ffi::TSQueryDeserializeErrorLanguage => QueryDeserializeError::Language,
+                ffi::TSQueryDeserializeErrorSymbol => QueryDeserializeError::Symbol,
+                ffi::TSQueryDeserializeErrorField => QueryDeserializeError::Field,
+                _ => QueryDeserializeError::Format,
+            });
+        }
+
+        // The names and strings are read as UTF-8 without checking it, which only compiling a
+        // query from a `str` guarantees.
+        let is_utf8 = |name: *const c_char, length: u32| {
+            str::from_utf8(unsafe { slice::from_raw_parts(name.cast::<u8>(), length as usize) })
+                .is_ok()
+        };
+        let mut length = 0u32;
+        let capture_count = unsafe { ffi::ts_query_capture_count(ptr) };
+        let string_count = unsafe { ffi::ts_query_string_count(ptr) };
+        if !(0..capture_count).all(|i| {
+            let name = unsafe {
+                ffi::ts_query_capture_name_for_id(ptr, i, core::ptr::addr_of_mut!(length))
+            };
+            is_utf8(name, length)
+        }) || !(0..string_count).all(|i| {
+            let value = unsafe {
+                ffi::ts_query_string_value_for_id(ptr, i, core::ptr::addr_of_mut!(length))
+            };
+            is_utf8(value, length)
+        }) {
+            unsafe { ffi::ts_query_delete(ptr) };
+            return Err(QueryDeserializeError::Format);
+        }
+
+        // The predicates were already checked when the query was compiled, so they can only
+        // fail to load if the bytes were tampered with.
+        unsafe { Self::from_raw_parts(ptr, "") }.map_err(|_| QueryDeserializeError::Format)
+    }
+
     #[doc(hidden)]
     unsafe fn from_raw_parts(ptr: *mut ffi::TSQuery, source: &str) -> Result<Self, QueryError> {
         let ptr = {
@@ -2831,6 +3684,25 @@ impl Query {
             .map(|ix| ix as u32)
     }
 
+    /// Get the indices of the patterns that contain the capture with the given name.
+    ///
+    /// This can be used with [`QueryCursor::set_enabled_patterns`] to only match the patterns
+    /// that produce certain captures.
+    #[must_use]
+    pub fn patterns_for_capture(&self, name: &str) -> Vec<usize> {
+        let Some(capture_index) = self.capture_index_for_name(name) else {
+            return Vec::new();
+        };
+        self.capture_quantifiers
+            .iter()
+            .enumerate()
+            .filter(|(_, quantifiers)| {
+                quantifiers[capture_index as usize] != CaptureQuantifier::Zero
+            })
+            .map(|(pattern_index, _)| pattern_index)
+            .collect()
+    }
+
     /// Get the properties that are checked for the given pattern index.
     ///
     /// This includes predicates with the operators `is?` and `is-not?`.
@@ -2883,6 +3755,25 @@ impl Query {
         unsafe { ffi::ts_query_disable_pattern(self.ptr.as_ptr(), index as u32) }
     }
 
+    /// Serialize the compiled query into a binary format, which can be turned back into a query
+    /// with [`Query::deserialize`], so that loading it again is faster than compiling its source
+    /// code.
+    ///
+    /// The format stores the query's pattern steps, capture names, predicates, and string values,
+    /// along with a fingerprint of the query's language. Captures and patterns that have been
+    /// disabled stay disabled.
+    #[doc(alias = "ts_query_serialize")]
+    #[must_use]
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut length = 0u32;
+        unsafe {
+            let ptr = ffi::ts_query_serialize(self.ptr.as_ptr(), core::ptr::addr_of_mut!(length));
+            let result = slice::from_raw_parts(ptr.cast::<u8>(), length as usize).to_vec();
+            (FREE_FN)(ptr.cast::<c_void>());
+            result
+        }
+    }
+
     /// Check if a given pattern within a query has a single root node.
     #[doc(alias = "ts_query_is_pattern_rooted")]
     #[must_use]
@@ -2897,6 +3788,14 @@ impl Query {
         unsafe { ffi::ts_query_is_pattern_non_local(self.ptr.as_ptr(), index as u32) }
     }
 
+    /// Get the depth of the deepest node in a given pattern, relative to the pattern's root
+    /// nodes, which are at depth zero.
+    #[doc(alias = "ts_query_pattern_depth")]
+    #[must_use]
+    pub fn pattern_depth(&self, index: usize) -> usize {
+        unsafe { ffi::ts_query_pattern_depth(self.ptr.as_ptr(), index as u32) as usize }
+    }
+
     /// Check if a given step in a query is 'definite'.
     ///
     /// A query step is 'definite' if its parent pattern will be guaranteed to
@@ -2984,6 +3883,9 @@ impl QueryCursor {
     pub fn new() -> Self {
         Self {
             ptr: unsafe { NonNull::new_unchecked(ffi::ts_query_cursor_new()) },
+            predicate_handlers: Vec::new(),
+            range: QUERY_CURSOR_DEFAULT_RANGE,
+            containment: false,
         }
     }
 
@@ -3039,6 +3941,49 @@ impl QueryCursor {
         unsafe { ffi::ts_query_cursor_did_exceed_match_limit(self.ptr.as_ptr()) }
     }
 
+    /// Return the maximum number of steps that this cursor can take while executing a query.
+    #[doc(alias = "ts_query_cursor_step_limit")]
+    #[must_use]
+    pub fn step_limit(&self) -> u64 {
+        unsafe { ffi::ts_query_cursor_step_limit(self.ptr.as_ptr()) }
+    }
+
+    /// Set the maximum number of steps that this cursor can take while executing a query.
+    ///
+    /// A step is one check of an in-progress match against a node. Queries whose patterns can
+    /// match in many different ways, like patterns with nested or repeated wildcards, can take a
+    /// very large number of steps on some inputs, so this bounds the time that an untrusted query
+    /// can run for. When the cursor reaches the limit, it stops looking for matches, but still
+    /// returns the ones that it has already finished, and
+    /// [`did_exceed_step_limit`](QueryCursor::did_exceed_step_limit) returns true.
+    ///
+    /// The step count is reset whenever the cursor starts executing a query. By default, there is
+    /// no limit, which is represented by [`u64::MAX`].
+    #[doc(alias = "ts_query_cursor_set_step_limit")]
+    pub fn set_step_limit(&mut self, limit: u64) {
+        unsafe {
+            ffi::ts_query_cursor_set_step_limit(self.ptr.as_ptr(), limit);
+        }
+    }
+
+    /// Check if, on its last execution, this cursor reached its maximum number of steps and
+    /// stopped early, which means that some matches may be missing.
+    #[doc(alias = "ts_query_cursor_did_exceed_step_limit")]
+    #[must_use]
+    pub fn did_exceed_step_limit(&self) -> bool {
+        unsafe { ffi::ts_query_cursor_did_exceed_step_limit(self.ptr.as_ptr()) }
+    }
+
+    /// Get the counters collected during this cursor's most recent execution.
+    ///
+    /// The counters are reset whenever the cursor starts executing a query, with
+    /// [`matches`](QueryCursor::matches) or [`captures`](QueryCursor::captures).
+    #[doc(alias = "ts_query_cursor_stats")]
+    #[must_use]
+    pub fn stats(&self) -> QueryCursorStats {
+        unsafe { ffi::ts_query_cursor_stats(self.ptr.as_ptr()) }.into()
+    }
+
     /// Iterate over all of the matches in the order that they were found.
     ///
     /// Each match contains the index of the pattern that matched, and a list of
@@ -3065,6 +4010,10 @@ impl QueryCursor {
             buffer1: Vec::default(),
             buffer2: Vec::default(),
             current_match: None,
+            predicate_handlers: &self.predicate_handlers,
+            range: &mut self.range,
+            containing_node_range: self.containment.then(|| node.range()),
+            include_unsatisfied: false,
             _options: None,
             _phantom: PhantomData,
         }
@@ -3122,6 +4071,10 @@ impl QueryCursor {
             buffer1: Vec::default(),
             buffer2: Vec::default(),
             current_match: None,
+            predicate_handlers: &self.predicate_handlers,
+            range: &mut self.range,
+            containing_node_range: self.containment.then(|| node.range()),
+            include_unsatisfied: false,
             _options: query_options,
             _phantom: PhantomData,
         }
@@ -3152,6 +4105,9 @@ impl QueryCursor {
             buffer1: Vec::default(),
             buffer2: Vec::default(),
             current_match: None,
+            predicate_handlers: &self.predicate_handlers,
+            range: &mut self.range,
+            containing_node_range: self.containment.then(|| node.range()),
             _options: None,
             _phantom: PhantomData,
         }
@@ -3208,15 +4164,101 @@ impl QueryCursor {
             buffer1: Vec::default(),
             buffer2: Vec::default(),
             current_match: None,
+            predicate_handlers: &self.predicate_handlers,
+            range: &mut self.range,
+            containing_node_range: self.containment.then(|| node.range()),
             _options: query_options,
             _phantom: PhantomData,
         }
     }
 
+    /// Iterate over the matches that intersect the parts of `new_tree` that changed since
+    /// `old_tree`, so that the matches from `old_tree` can be updated without running the
+    /// query over the whole tree again.
+    ///
+    /// `old_tree` must have been edited to match the new text, and `new_tree` is the result
+    /// of parsing the new text with it. The changed parts are the ranges from
+    /// [`Tree::changed_ranges`], together with the nodes that were edited without changing
+    /// the tree's structure, such as a renamed identifier. Each of them is expanded to the
+    /// ancestor that is as many levels above it as the query's deepest pattern, plus one if a
+    /// pattern has several root nodes, so that every match that depends on a changed node
+    /// has a capture within the expanded ranges, which are available from
+    /// [`QueryChangedMatches::ranges`].
+    ///
+    /// A match intersects the ranges if one of its captures does, as checked by
+    /// [`QueryChangedMatches::intersects`]. Removing the matches from `old_tree` that
+    /// intersect the ranges, and adding the ones that are returned here, gives the same
+    /// matches as running the query over `new_tree`. Matches without captures are never
+    /// returned, because they can't be located.
+    ///
+    /// This replaces the cursor's byte range with one that covers the expanded ranges.
+    pub fn matches_in_changed_ranges<
+        'query,
+        'cursor: 'query,
+        'tree,
+        T: TextProvider<I>,
+        I: AsRef<[u8]>,
+    >(
+        &'cursor mut self,
+        query: &'query Query,
+        old_tree: &Tree,
+        new_tree: &'tree Tree,
+        text_provider: T,
+    ) -> QueryChangedMatches<'query, 'tree, T, I> {
+        let depth = (0..query.pattern_count())
+            .map(|i| query.pattern_depth(i) + usize::from(!query.is_pattern_rooted(i)))
+            .max()
+            .unwrap_or(0);
+
+        let mut changed_ranges = old_tree.changed_ranges(new_tree).collect::<Vec<_>>();
+        collect_edited_ranges(old_tree.root_node(), &mut changed_ranges);
+        let mut ranges = Vec::new();
+        for range in changed_ranges {
+            for tree in [old_tree, new_tree] {
+                let root = tree.root_node();
+                let mut node = root
+                    .descendant_for_byte_range(range.start_byte, range.end_byte)
+                    .unwrap_or(root);
+                for _ in 0..depth {
+                    let Some(parent) = node.parent() else {
+                        break;
+                    };
+                    node = parent;
+                }
+                ranges.push(range_union(&range, &node.range()));
+            }
+        }
+        ranges.sort_unstable_by_key(|range| range.start_byte);
+        let mut merged_ranges = Vec::<Range>::with_capacity(ranges.len());
+        for range in ranges {
+            match merged_ranges.last_mut() {
+                Some(last) if range.start_byte <= last.end_byte => {
+                    *last = range_union(last, &range);
+                }
+                _ => merged_ranges.push(range),
+            }
+        }
+
+        // Start one byte early, so that a node which ends where a range starts, and has an
+        // empty capture at its end, is still visited. Without any ranges, the query runs on
+        // an empty range at the end of the tree, which doesn't match anything.
+        let end_byte = new_tree.root_node().end_byte();
+        let byte_range = match (merged_ranges.first(), merged_ranges.last()) {
+            (Some(first), Some(last)) => first.start_byte.saturating_sub(1)..last.end_byte,
+            _ => end_byte..end_byte,
+        };
+        self.set_byte_range(byte_range);
+        QueryChangedMatches {
+            matches: self.matches(query, new_tree.root_node(), text_provider),
+            ranges: merged_ranges,
+        }
+    }
+
     /// Set the range in which the query will be executed, in terms of byte
     /// offsets.
     #[doc(alias = "ts_query_cursor_set_byte_range")]
     pub fn set_byte_range(&mut self, range: ops::Range<usize>) -> &mut Self {
+        set_query_byte_range(&mut self.range, &range);
         unsafe {
             ffi::ts_query_cursor_set_byte_range(
                 self.ptr.as_ptr(),
@@ -3231,6 +4273,7 @@ impl QueryCursor {
     /// columns.
     #[doc(alias = "ts_query_cursor_set_point_range")]
     pub fn set_point_range(&mut self, range: ops::Range<Point>) -> &mut Self {
+        set_query_point_range(&mut self.range, &range);
         unsafe {
             ffi::ts_query_cursor_set_point_range(
                 self.ptr.as_ptr(),
@@ -3254,6 +4297,10 @@ impl QueryCursor {
     /// that are parts of the pattern may be searched at any depth what
     /// defined by the pattern structure.
     ///
+    /// For example, with a max start depth of `Some(0)`, running a query on a
+    /// function's node only returns the matches whose root is that function,
+    /// and not the ones for the functions that are nested inside of it.
+    ///
     /// Set to `None` to remove the maximum start depth.
     #[doc(alias = "ts_query_cursor_set_max_start_depth")]
     pub fn set_max_start_depth(&mut self, max_start_depth: Option<u32>) -> &mut Self {
@@ -3265,6 +4312,90 @@ impl QueryCursor {
         }
         self
     }
+
+    /// Set whether to only return the matches whose captures are all contained
+    /// within the node that the query is executed on, and within the byte and
+    /// point ranges of the cursor.
+    ///
+    /// By default, a match is returned as long as it intersects the cursor's
+    /// ranges, so when a query is executed on the root node with a byte range
+    /// that covers a single function, it also returns the matches for the
+    /// enclosing class or module, whose captures extend beyond the function.
+    /// With containment, those matches are skipped, like matches whose
+    /// predicates are not satisfied.
+    pub fn set_containment(&mut self, containment: bool) -> &mut Self {
+        self.containment = containment;
+        self
+    }
+
+    /// Register a handler for a custom predicate, such as `#is-snake-case?`, so that the
+    /// matches that don't satisfy it are skipped, like the ones that don't satisfy `#eq?` or
+    /// `#match?`.
+    ///
+    /// The `name` is the predicate's operator, including its trailing `?`, and the handler is
+    /// also used for the predicate's `#not-`, `#any-` and `#any-not-` forms. The handler
+    /// receives the match, the predicate's arguments, and a [`QueryPredicateText`] that reads
+    /// the text of a node.
+    ///
+    /// If the predicate's first argument is a capture, the handler is called once for each
+    /// node of that capture, with a match in which that capture only contains that node. Like
+    /// the built-in predicates, the predicate is satisfied if every call returns `true`, or if
+    /// any call does for the `#any-` forms, and the `#not-` forms negate each call's result.
+    ///
+    /// A handler replaces any handler that was registered with the same name. The built-in
+    /// predicates can't be overridden, and predicates without a handler are ignored, as before.
+    pub fn set_predicate_handler(
+        &mut self,
+        name: &str,
+        handler: impl Fn(&QueryMatch, &[QueryPredicateArg], &mut QueryPredicateText) -> bool
+            + Send
+            + Sync
+            + 'static,
+    ) -> &mut Self {
+        let handler = Box::new(handler);
+        if let Some(entry) = self
+            .predicate_handlers
+            .iter_mut()
+            .find(|(existing_name, _)| **existing_name == *name)
+        {
+            entry.1 = handler;
+        } else {
+            self.predicate_handlers.push((name.into(), handler));
+        }
+        self
+    }
+
+    /// Set which of a query's patterns this cursor should match, without changing the query.
+    ///
+    /// Each element of `enabled_patterns` tells whether the pattern with that index is enabled.
+    /// Patterns whose index is past the end of the slice are enabled, so passing an empty slice
+    /// enables every pattern. Unlike [`Query::disable_pattern`], this lets different cursors
+    /// match different subsets of the same query's patterns at the same time.
+    #[doc(alias = "ts_query_cursor_set_enabled_patterns")]
+    pub fn set_enabled_patterns(&mut self, enabled_patterns: &[bool]) -> &mut Self {
+        unsafe {
+            ffi::ts_query_cursor_set_enabled_patterns(
+                self.ptr.as_ptr(),
+                enabled_patterns.as_ptr(),
+                enabled_patterns.len() as u32,
+            );
+        }
+        self
+    }
+
+    /// Restore all of this cursor's settings to their defaults, so that it can be reused as
+    /// though it were new.
+    ///
+    /// This clears the byte and point ranges, the match limit, the step limit, the maximum start
+    /// depth, the timeout, the enabled patterns, and the containment, but keeps the predicate
+    /// handlers. The memory that the cursor has allocated is kept, which makes resetting a cursor
+    /// cheaper than creating a new one.
+    #[doc(alias = "ts_query_cursor_reset")]
+    pub fn reset(&mut self) {
+        self.range = QUERY_CURSOR_DEFAULT_RANGE;
+        self.containment = false;
+        unsafe { ffi::ts_query_cursor_reset(self.ptr.as_ptr()) }
+    }
 }
 
 impl<'tree> QueryMatch<'_, 'tree> {
@@ -3303,6 +4434,88 @@ impl<'tree> QueryMatch<'_, 'tree> {
         }
     }
 
+    /// Check that all of the match's captures are within the node's range and the cursor's
+    /// range, if the cursor only returns the matches that are contained in them.
+    fn is_contained(&self, node_range: Option<&Range>, cursor_range: &Range) -> bool {
+        let Some(node_range) = node_range else {
+            return true;
+        };
+        self.captures.iter().all(|capture| {
+            let range = capture.node.range();
+            range_contains(node_range, &range) && range_contains(cursor_range, &range)
+        })
+    }
+
+    /// Check the predicates that have a handler registered with
+    /// [`QueryCursor::set_predicate_handler`].
+    fn satisfies_custom_predicates<I: AsRef<[u8]>>(
+        &self,
+        query: &Query,
+        predicate_handlers: &[(Box<str>, QueryPredicateHandler)],
+        buffer: &mut Vec<u8>,
+        text_provider: &mut impl TextProvider<I>,
+    ) -> bool {
+        if predicate_handlers.is_empty() {
+            return true;
+        }
+
+        let mut read = |node: Node, buffer: &mut Vec<u8>| {
+            for chunk in text_provider.text(node) {
+                buffer.extend_from_slice(chunk.as_ref());
+            }
+        };
+        let mut text = QueryPredicateText {
+            read: &mut read,
+            buffer,
+        };
+        let mut captures = Vec::new();
+
+        query.general_predicates[self.pattern_index]
+            .iter()
+            .all(|predicate| {
+                let operator = &*predicate.operator;
+                let (operator, match_all_nodes) = match operator.strip_prefix("any-") {
+                    Some(rest) if find_handler(predicate_handlers, operator).is_none() => {
+                        (rest, false)
+                    }
+                    _ => (operator, true),
+                };
+                let (operator, is_positive) = match operator.strip_prefix("not-") {
+                    Some(rest) if find_handler(predicate_handlers, operator).is_none() => {
+                        (rest, false)
+                    }
+                    _ => (operator, true),
+                };
+                let Some(handler) = find_handler(predicate_handlers, operator) else {
+                    return true;
+                };
+
+                let Some(&QueryPredicateArg::Capture(capture_ix)) = predicate.args.first() else {
+                    return handler(self, &predicate.args, &mut text) == is_positive;
+                };
+                for node in self.nodes_for_capture_index(capture_ix) {
+                    captures.clear();
+                    captures.extend(
+                        self.captures
+                            .iter()
+                            .filter(|capture| capture.index != capture_ix || capture.node == node),
+                    );
+                    let node_match = QueryMatch {
+                        captures: &captures,
+                        ..*self
+                    };
+                    let is_positive_match = handler(&node_match, &predicate.args, &mut text);
+                    if is_positive_match != is_positive && match_all_nodes {
+                        return false;
+                    }
+                    if is_positive_match == is_positive && !match_all_nodes {
+                        return true;
+                    }
+                }
+                match_all_nodes
+            })
+    }
+
     pub fn satisfies_text_predicates<I: AsRef<[u8]>>(
         &self,
         query: &Query,
@@ -3410,6 +4623,86 @@ impl<'tree> QueryMatch<'_, 'tree> {
     }
 }
 
+/// The byte and point ranges of a new query cursor, which span the whole document.
+const QUERY_CURSOR_DEFAULT_RANGE: Range = Range {
+    start_byte: 0,
+    end_byte: usize::MAX,
+    start_point: Point::new(0, 0),
+    end_point: Point::new(usize::MAX, usize::MAX),
+};
+
+/// Update the copy of a query cursor's byte range, in the same way as
+/// `ts_query_cursor_set_byte_range`.
+fn set_query_byte_range(cursor_range: &mut Range, range: &ops::Range<usize>) {
+    let end_byte = if range.end == 0 {
+        usize::MAX
+    } else {
+        range.end
+    };
+    if range.start <= end_byte {
+        cursor_range.start_byte = range.start;
+        cursor_range.end_byte = end_byte;
+    }
+}
+
+/// Update the copy of a query cursor's point range, in the same way as
+/// `ts_query_cursor_set_point_range`.
+fn set_query_point_range(cursor_range: &mut Range, range: &ops::Range<Point>) {
+    let end_point = if range.end == Point::new(0, 0) {
+        QUERY_CURSOR_DEFAULT_RANGE.end_point
+    } else {
+        range.end
+    };
+    if range.start <= end_point {
+        cursor_range.start_point = range.start;
+        cursor_range.end_point = end_point;
+    }
+}
+
+fn range_union(a: &Range, b: &Range) -> Range {
+    Range {
+        start_byte: a.start_byte.min(b.start_byte),
+        end_byte: a.end_byte.max(b.end_byte),
+        start_point: a.start_point.min(b.start_point),
+        end_point: a.end_point.max(b.end_point),
+    }
+}
+
+/// Check if a byte range intersects any of the given ranges. An empty byte range intersects
+/// a range that it's within, including at the range's start, but not at its end.
+fn ranges_intersect(ranges: &[Range], byte_range: &ops::Range<usize>) -> bool {
+    ranges.iter().any(|range| {
+        byte_range.start < range.end_byte
+            && (byte_range.end > range.start_byte || byte_range.start == range.start_byte)
+    })
+}
+
+/// Collect the ranges of the deepest nodes that were edited, which [`Tree::changed_ranges`]
+/// leaves out if the edit didn't change the tree's structure.
+fn collect_edited_ranges(node: Node, ranges: &mut Vec<Range>) {
+    if !node.has_changes() {
+        return;
+    }
+    let mut cursor = node.walk();
+    let mut has_edited_children = false;
+    for child in node.children(&mut cursor) {
+        if child.has_changes() {
+            has_edited_children = true;
+            collect_edited_ranges(child, ranges);
+        }
+    }
+    if !has_edited_children {
+        ranges.push(node.range());
+    }
+}
+
+fn range_contains(outer: &Range, inner: &Range) -> bool {
+    inner.start_byte >= outer.start_byte
+        && inner.end_byte <= outer.end_byte
+        && inner.start_point >= outer.start_point
+        && inner.end_point <= outer.end_point
+}
+
 impl QueryProperty {
     #[must_use]
     pub fn new(key: &str, value: Option<&str>, capture_id: Option<usize>) -> Self {
@@ -3435,12 +4728,22 @@ impl<'query, 'tree: 'query, T: TextProvider<I>, I: AsRef<[u8]>> StreamingIterato
                 let mut m = MaybeUninit::<ffi::TSQueryMatch>::uninit();
                 if ffi::ts_query_cursor_next_match(self.ptr, m.as_mut_ptr()) {
                     let result = QueryMatch::new(&m.assume_init(), self.ptr);
-                    if result.satisfies_text_predicates(
-                        self.query,
-                        &mut self.buffer1,
-                        &mut self.buffer2,
-                        &mut self.text_provider,
-                    ) {
+                    if !result.is_contained(self.containing_node_range.as_ref(), self.range) {
+                        continue;
+                    }
+                    if self.include_unsatisfied
+                        || (result.satisfies_text_predicates(
+                            self.query,
+                            &mut self.buffer1,
+                            &mut self.buffer2,
+                            &mut self.text_provider,
+                        ) && result.satisfies_custom_predicates(
+                            self.query,
+                            self.predicate_handlers,
+                            &mut self.buffer1,
+                            &mut self.text_provider,
+                        ))
+                    {
                         break Some(result);
                     }
                 } else {
@@ -3479,12 +4782,20 @@ impl<'query, 'tree: 'query, T: TextProvider<I>, I: AsRef<[u8]>> StreamingIterato
                     core::ptr::addr_of_mut!(capture_index),
                 ) {
                     let result = QueryMatch::new(&m.assume_init(), self.ptr);
-                    if result.satisfies_text_predicates(
-                        self.query,
-                        &mut self.buffer1,
-                        &mut self.buffer2,
-                        &mut self.text_provider,
-                    ) {
+                    if result.is_contained(self.containing_node_range.as_ref(), self.range)
+                        && result.satisfies_text_predicates(
+                            self.query,
+                            &mut self.buffer1,
+                            &mut self.buffer2,
+                            &mut self.text_provider,
+                        )
+                        && result.satisfies_custom_predicates(
+                            self.query,
+                            self.predicate_handlers,
+                            &mut self.buffer1,
+                            &mut self.text_provider,
+                        )
+                    {
                         break Some((result, capture_index as usize));
                     }
                     result.remove();
@@ -3508,9 +4819,59 @@ impl<'query, 'tree: 'query, T: TextProvider<I>, I: AsRef<[u8]>> StreamingIterato
     }
 }
 
+impl<'query, 'tree: 'query, T: TextProvider<I>, I: AsRef<[u8]>> StreamingIterator
+    for QueryChangedMatches<'query, 'tree, T, I>
+{
+    type Item = QueryMatch<'query, 'tree>;
+
+    fn advance(&mut self) {
+        loop {
+            self.matches.advance();
+            match self.matches.get() {
+                Some(m)
+                    if !m.captures.iter().any(|capture| {
+                        ranges_intersect(&self.ranges, &capture.node.byte_range())
+                    }) => {}
+                _ => break,
+            }
+        }
+    }
+
+    fn get(&self) -> Option<&Self::Item> {
+        self.matches.get()
+    }
+}
+
+impl<'query, 'tree: 'query, T: TextProvider<I>, I: AsRef<[u8]>> StreamingIteratorMut
+    for QueryChangedMatches<'query, 'tree, T, I>
+{
+    fn get_mut(&mut self) -> Option<&mut Self::Item> {
+        self.matches.get_mut()
+    }
+}
+
+impl<T: TextProvider<I>, I: AsRef<[u8]>> QueryChangedMatches<'_, '_, T, I> {
+    /// Get the ranges that changed, expanded to cover the matches that depend on them, in
+    /// order and without overlaps.
+    #[must_use]
+    pub fn ranges(&self) -> &[Range] {
+        &self.ranges
+    }
+
+    /// Check if a node with the given byte range intersects the changed ranges.
+    ///
+    /// A match from the old tree is stale if one of its captures intersects them, once the
+    /// capture's range is adjusted for the edits.
+    #[must_use]
+    pub fn intersects(&self, byte_range: ops::Range<usize>) -> bool {
+        ranges_intersect(&self.ranges, &byte_range)
+    }
+}
+
 impl<T: TextProvider<I>, I: AsRef<[u8]>> QueryMatches<'_, '_, T, I> {
     #[doc(alias = "ts_query_cursor_set_byte_range")]
     pub fn set_byte_range(&mut self, range: ops::Range<usize>) {
+        set_query_byte_range(self.range, &range);
         unsafe {
             ffi::ts_query_cursor_set_byte_range(self.ptr, range.start as u32, range.end as u32);
         }
@@ -3518,15 +4879,28 @@ impl<T: TextProvider<I>, I: AsRef<[u8]>> QueryMatches<'_, '_, T, I> {
 
     #[doc(alias = "ts_query_cursor_set_point_range")]
     pub fn set_point_range(&mut self, range: ops::Range<Point>) {
+        set_query_point_range(self.range, &range);
         unsafe {
             ffi::ts_query_cursor_set_point_range(self.ptr, range.start.into(), range.end.into());
         }
     }
+
+    /// Set whether to also return the matches whose text predicates, such as
+    /// `#eq?` and `#match?`, or whose predicates with a handler from
+    /// [`QueryCursor::set_predicate_handler`], are not satisfied, instead of
+    /// skipping them.
+    ///
+    /// Use [`QueryMatch::satisfies_text_predicates`] to tell them apart from
+    /// the other matches.
+    pub fn set_include_unsatisfied_matches(&mut self, include: bool) {
+        self.include_unsatisfied = include;
+    }
 }
 
 impl<T: TextProvider<I>, I: AsRef<[u8]>> QueryCaptures<'_, '_, T, I> {
     #[doc(alias = "ts_query_cursor_set_byte_range")]
     pub fn set_byte_range(&mut self, range: ops::Range<usize>) {
+        set_query_byte_range(self.range, &range);
         unsafe {
             ffi::ts_query_cursor_set_byte_range(self.ptr, range.start as u32, range.end as u32);
         }
@@ -3534,12 +4908,32 @@ impl<T: TextProvider<I>, I: AsRef<[u8]>> QueryCaptures<'_, '_, T, I> {
 
     #[doc(alias = "ts_query_cursor_set_point_range")]
     pub fn set_point_range(&mut self, range: ops::Range<Point>) {
+        set_query_point_range(self.range, &range);
         unsafe {
             ffi::ts_query_cursor_set_point_range(self.ptr, range.start.into(), range.end.into());
         }
     }
 }
 
+impl QueryPredicateText<'_> {
+    /// Get the text of the given node.
+    pub fn text(&mut self, node: Node) -> &[u8] {
+        self.buffer.clear();
+        (self.read)(node, self.buffer);
+        self.buffer
+    }
+}
+
+/// Find the handler that was registered for a custom predicate.
+fn find_handler<'a>(
+    predicate_handlers: &'a [(Box<str>, QueryPredicateHandler)],
+    name: &str,
+) -> Option<&'a QueryPredicateHandler> {
+    predicate_handlers
+        .iter()
+        .find_map(|(handler_name, handler)| (**handler_name == *name).then_some(handler))
+}
+
 impl fmt::Debug for QueryMatch<'_, '_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -3571,6 +4965,198 @@ impl<'a> TextProvider<&'a [u8]> for &'a [u8] {
     }
 }
 
+impl<'a, F> ChunkProvider<'a> for F
+where
+    F: FnMut(usize) -> Option<(&'a [u8], usize)>,
+{
+    fn chunk_at_byte(&mut self, byte: usize) -> Option<(&'a [u8], usize)> {
+        (self)(byte)
+    }
+}
+
+impl<'a> ChunkProvider<'a> for &'a [u8] {
+    fn chunk_at_byte(&mut self, byte: usize) -> Option<(&'a [u8], usize)> {
+        (byte < self.len()).then_some((*self, 0))
+    }
+}
+
+impl<'a> StrChunks<'a> {
+    /// Create a chunk provider from the chunks of a text, in order.
+    #[must_use]
+    pub fn new(chunks: impl IntoIterator<Item = &'a str>) -> Self {
+        chunks.into_iter().collect()
+    }
+}
+
+impl<'a> FromIterator<&'a str> for StrChunks<'a> {
+    fn from_iter<T: IntoIterator<Item = &'a str>>(iter: T) -> Self {
+        let mut offset = 0;
+        let chunks = iter
+            .into_iter()
+            .filter(|chunk| !chunk.is_empty())
+            .map(|chunk| {
+                let start = offset;
+                offset += chunk.len();
+                (start, chunk)
+            })
+            .collect();
+        Self { chunks }
+    }
+}
+
+impl<'a> ChunkProvider<'a> for &StrChunks<'a> {
+    fn chunk_at_byte(&mut self, byte: usize) -> Option<(&'a [u8], usize)> {
+        let index = self
+            .chunks
+            .partition_point(|(start, chunk)| start + chunk.len() <= byte);
+        let (start, chunk) = self.chunks.get(index)?;
+        Some((chunk.as_bytes(), *start))
+    }
+}
+
+impl<'a, C: ChunkProvider<'a>> Iterator for NodeTextChunks<'a, C> {
+    type Item = Result<&'a str, str::Utf8Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.byte >= self.end_byte {
+            return None;
+        }
+        let Some((chunk, chunk_start)) = self
+            .chunk_provider
+            .chunk_at_byte(self.byte)
+            .filter(|(chunk, start)| (*start..start + chunk.len()).contains(&self.byte))
+        else {
+            self.byte = self.end_byte;
+            return None;
+        };
+        let end = self.end_byte.min(chunk_start + chunk.len());
+        let piece = &chunk[self.byte - chunk_start..end - chunk_start];
+        self.byte = end;
+        Some(str::from_utf8(piece))
+    }
+}
+
+impl<'a, C: ChunkProvider<'a>> iter::FusedIterator for NodeTextChunks<'a, C> {}
+
+impl<'tree> Traversal<'tree> {
+    fn new(cursor: TreeCursor<'tree>, order: TraversalOrder) -> Self {
+        Self {
+            cursor,
+            order,
+            named_only: false,
+            prune: |_| false,
+            started: false,
+            done: false,
+        }
+    }
+}
+
+impl<'tree, P: FnMut(&Node<'tree>) -> bool> Traversal<'tree, P> {
+    /// Only return named nodes.
+    ///
+    /// The descendants of anonymous nodes are still visited.
+    #[must_use]
+    pub const fn named_only(mut self) -> Self {
+        self.named_only = true;
+        self
+    }
+
+    /// Don't descend into the nodes for which the given predicate returns `true`.
+    ///
+    /// The nodes themselves are still returned, but none of their descendants are.
+    #[must_use]
+    pub fn prune<Q: FnMut(&Node<'tree>) -> bool>(self, predicate: Q) -> Traversal<'tree, Q> {
+        Traversal {
+            cursor: self.cursor,
+            order: self.order,
+            named_only: self.named_only,
+            prune: predicate,
+            started: self.started,
+            done: self.done,
+        }
+    }
+
+    /// Get the depth of the node that was returned most recently, relative to the node that the
+    /// traversal started from.
+    #[must_use]
+    pub fn depth(&self) -> u32 {
+        self.cursor.depth()
+    }
+
+    /// Get the name of the field of the node that was returned most recently.
+    #[must_use]
+    pub fn field_name(&self) -> Option<&'static str> {
+        self.cursor.field_name()
+    }
+
+    /// Get the numerical id of the field of the node that was returned most recently.
+    #[must_use]
+    pub fn field_id(&self) -> Option<FieldId> {
+        self.cursor.field_id()
+    }
+
+    fn is_pruned(&mut self) -> bool {
+        (self.prune)(&self.cursor.node())
+    }
+
+    /// Move the cursor down to the first node that comes after the current one in post-order.
+    fn descend(&mut self) {
+        while !self.is_pruned() && self.cursor.goto_first_child() {}
+    }
+
+    fn advance(&mut self) {
+        match self.order {
+            TraversalOrder::Preorder => {
+                if !self.is_pruned() && self.cursor.goto_first_child() {
+                    return;
+                }
+                while !self.cursor.goto_next_sibling() {
+                    if !self.cursor.goto_parent() {
+                        self.done = true;
+                        return;
+                    }
+                }
+            }
+            TraversalOrder::Postorder => {
+                if self.cursor.goto_next_sibling() {
+                    self.descend();
+                } else if !self.cursor.goto_parent() {
+                    self.done = true;
+                }
+            }
+        }
+    }
+}
+
+impl<'tree, P: FnMut(&Node<'tree>) -> bool> Iterator for Traversal<'tree, P> {
+    type Item = Node<'tree>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done {
+                return None;
+            }
+            if self.started {
+                self.advance();
+            } else {
+                self.started = true;
+                if self.order == TraversalOrder::Postorder {
+                    self.descend();
+                }
+            }
+            if self.done {
+                return None;
+            }
+            let node = self.cursor.node();
+            if !self.named_only || node.is_named() {
+                return Some(node);
+            }
+        }
+    }
+}
+
+impl<'tree, P: FnMut(&Node<'tree>) -> bool> iter::FusedIterator for Traversal<'tree, P> {}
+
 impl PartialEq for Query {
     fn eq(&self, other: &Self) -> bool {
         self.ptr == other.ptr
@@ -3596,6 +5182,23 @@ impl Point {
     }
 }
 
+/// A string that is escaped so that it can be quoted in the DOT language.
+struct DotString<'a>(&'a str);
+
+impl fmt::Display for DotString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' | '\\' => write!(f, "\\{c}")?,
+                '\n' => f.write_str("\\n")?,
+                '\t' => f.write_str("\\t")?,
+                _ => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Display for Point {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "({}, {})", self.row, self.column)
@@ -3715,7 +5318,18 @@ const fn predicate_error(row: usize, message: String) -> QueryError {
 
 impl fmt::Display for IncludedRangesError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Incorrect range by index: {}", self.0)
+        let reason = match self.kind {
+            IncludedRangesErrorKind::EndBeforeStart => "it ends before it starts",
+            IncludedRangesErrorKind::OverlapsPrevious => "it overlaps the previous range",
+            IncludedRangesErrorKind::PointsOutOfOrder => {
+                "its points are out of order with its byte offsets"
+            }
+        };
+        write!(
+            f,
+            "Incorrect range by index: {}, because {reason}",
+            self.index
+        )
     }
 }
 
@@ -3729,6 +5343,37 @@ impl fmt::Display for LanguageError {
     }
 }
 
+impl fmt::Display for TreeDeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Format => "The bytes are not a serialized syntax tree",
+            Self::Version => "The syntax tree was serialized with an incompatible version",
+            Self::Symbol => "The syntax tree contains a symbol that doesn't exist in the language",
+            Self::Field => {
+                "The syntax tree contains a production that doesn't exist in the language"
+            }
+            Self::ParseState => {
+                "The syntax tree contains a parse state that doesn't exist in the language"
+            }
+            Self::Range => "The syntax tree contains a node that doesn't fit in the source code",
+        };
+        write!(f, "{msg}")
+    }
+}
+
+impl fmt::Display for QueryDeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Format => "The bytes are not a serialized query",
+            Self::Version => "The query was serialized with an incompatible version",
+            Self::Language => "The query was serialized for a different language",
+            Self::Symbol => "The query was serialized for a language with different node types",
+            Self::Field => "The query was serialized for a language with different fields",
+        };
+        write!(f, "{msg}")
+    }
+}
+
 impl fmt::Display for QueryError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let msg = match self.kind {
@@ -3762,59 +5407,13 @@ pub fn format_sexp(sexp: &str, initial_indent_level: usize) -> String {
     let mut formatted = String::new();
     let mut has_field = false;
 
-    let mut c_iter = sexp.chars().peekable();
-    let mut s = String::with_capacity(sexp.len());
-    let mut quote = '\0';
-    let mut saw_paren = false;
-    let mut did_last = false;
-
-    let mut fetch_next_str = |next: &mut String| {
-        next.clear();
-        while let Some(c) = c_iter.next() {
-            if c == '\'' || c == '"' {
-                quote = c;
-            } else if c == ' ' || (c == ')' && quote != '\0') {
-                if let Some(next_c) = c_iter.peek() {
-                    if *next_c == quote {
-                        next.push(c);
-                        next.push(*next_c);
-                        c_iter.next();
-                        quote = '\0';
-                        continue;
-                    }
-                }
-                break;
-            }
-            if c == ')' {
-                saw_paren = true;
-                break;
+    for token in sexp_tokens(sexp) {
+        if token == ")" {
+            if indent_level > 0 {
+                indent_level -= 1;
+                formatted.push(')');
             }
-            next.push(c);
-        }
-
-        // at the end
-        if c_iter.peek().is_none() && next.is_empty() {
-            if saw_paren {
-                // but did we see a ) before ending?
-                saw_paren = false;
-                return Some(());
-            }
-            if !did_last {
-                // but did we account for the end empty string as if we're splitting?
-                did_last = true;
-                return Some(());
-            }
-            return None;
-        }
-        Some(())
-    };
-
-    while fetch_next_str(&mut s).is_some() {
-        if s.is_empty() && indent_level > 0 {
-            // ")"
-            indent_level -= 1;
-            write!(formatted, ")").unwrap();
-        } else if s.starts_with('(') {
+        } else if token.starts_with('(') {
             if has_field {
                 has_field = false;
             } else {
@@ -3828,35 +5427,73 @@ pub fn format_sexp(sexp: &str, initial_indent_level: usize) -> String {
             }
 
             // "(node_name"
-            write!(formatted, "{s}").unwrap();
-
-            // "(MISSING node_name" or "(UNEXPECTED 'x'"
-            if s.starts_with("(MISSING") || s.starts_with("(UNEXPECTED") {
-                fetch_next_str(&mut s).unwrap();
-                if s.is_empty() {
-                    while indent_level > 0 {
-                        indent_level -= 1;
-                        write!(formatted, ")").unwrap();
-                    }
-                } else {
-                    write!(formatted, " {s}").unwrap();
-                }
-            }
-        } else if s.ends_with(':') {
+            write!(formatted, "{token}").unwrap();
+        } else if token.ends_with(':') && !token.starts_with(['"', '\'']) {
             // "field:"
             writeln!(formatted).unwrap();
             for _ in 0..indent_level {
                 write!(formatted, "  ").unwrap();
             }
-            write!(formatted, "{s} ").unwrap();
+            write!(formatted, "{token} ").unwrap();
             has_field = true;
             indent_level += 1;
+        } else {
+            // The rest of "(MISSING node_name", "(UNEXPECTED 'x'", or "(#text "x""
+            write!(formatted, " {token}").unwrap();
         }
     }
 
     formatted
 }
 
+/// Splits an S-expression into `(node_name`, `)`, `field:`, and other tokens, like node names
+/// and quoted strings. A quoted string only ends at a matching quote that isn't escaped and is
+/// followed by whitespace or a parenthesis, so that `(MISSING ")")` and `(UNEXPECTED ''')` are
+/// split correctly.
+fn sexp_tokens(sexp: &str) -> Vec<&str> {
+    let bytes = sexp.as_bytes();
+    let is_delimiter = |i: usize| {
+        bytes.get(i).map_or(true, |b| {
+            b.is_ascii_whitespace() || *b == b'(' || *b == b')'
+        })
+    };
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b')' => i += 1,
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() {
+                    if bytes[i] == b'\\' {
+                        i += 2;
+                    } else if bytes[i] == quote && is_delimiter(i + 1) {
+                        i += 1;
+                        break;
+                    } else {
+                        i += 1;
+                    }
+                }
+                i = i.min(bytes.len());
+            }
+            _ => {
+                i += 1;
+                while !is_delimiter(i) {
+                    i += 1;
+                }
+            }
+        }
+        tokens.push(&sexp[start..i]);
+    }
+    tokens
+}
+
 pub fn wasm_stdlib_symbols() -> impl Iterator<Item = &'static str> {
     const WASM_STDLIB_SYMBOLS: &str = include_str!(concat!(env!("OUT_DIR"), "/stdlib-symbols.txt"));
 
@@ -3873,9 +5510,16 @@ static mut FREE_FN: unsafe extern "C" fn(ptr: *mut c_void) = free;
 
 /// Sets the memory allocation functions that the core library should use.
 ///
+/// Passing `None` for any of the functions switches back to the default implementation of that
+/// function. The allocator is global to the whole process, so it applies to every parser, tree,
+/// and query, including ones that were created on other threads.
+///
 /// # Safety
 ///
-/// This function uses FFI and mutates a static global.
+/// This function uses FFI and mutates a static global, so it must not be called while other
+/// threads use the library. If it's called after the library has already been used, then either
+/// all of the existing objects must have been dropped, or the new allocator must be able to
+/// free memory that was allocated by the old one.
 #[doc(alias = "ts_set_allocator")]
 pub unsafe fn set_allocator(
     new_malloc: Option<unsafe extern "C" fn(size: usize) -> *mut c_void>,
@@ -3897,6 +5541,14 @@ impl error::Error for LanguageError {}
 #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
 impl error::Error for QueryError {}
 
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl error::Error for TreeDeserializeError {}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl error::Error for QueryDeserializeError {}
+
 unsafe impl Send for Language {}
 unsafe impl Sync for Language {}
 
@@ -0,0 +1,162 @@
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+use core::ops;
+
+use super::{InputEdit, Point};
+
+/// A copy of a text document that converts between byte offsets, [`Point`]s, and the UTF-16
+/// based positions that the Language Server Protocol uses.
+///
+/// Like the [`Point`]s of a [`Tree`](super::Tree), rows are separated by `\n`, and byte columns
+/// count every byte before the `\n`, including the `\r` of a CRLF line ending. UTF-16 columns
+/// follow the Language Server Protocol instead, which doesn't count either part of a line
+/// ending as part of the line.
+///
+/// Positions that are past the end of the text are clamped to it: a byte offset past the end
+/// refers to the end of the text, a row past the last one refers to the end of the text, and a
+/// column past the end of its row refers to the end of that row, before its line ending.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineIndex {
+    text: String,
+    /// The byte offset at which each row starts.
+    row_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    /// Create an index for the given text.
+    #[must_use]
+    pub fn new(text: &str) -> Self {
+        let mut row_starts = Vec::from([0]);
+        row_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        Self {
+            text: text.into(),
+            row_starts,
+        }
+    }
+
+    /// Get the text that this index was created for, with any edits applied.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Get the number of rows in the text, which is one more than the number of `\n`s.
+    #[must_use]
+    pub fn row_count(&self) -> usize {
+        self.row_starts.len()
+    }
+
+    /// Apply an edit to the text, where `inserted_text` is the text that replaces the edit's
+    /// old range, from `edit.start_byte` to `edit.new_end_byte` in the new text.
+    ///
+    /// Only the edit's byte offsets are used, so they have to be relative to the text as it is
+    /// before this edit, which is why several edits are applied in reverse order, like with
+    /// [`Tree::edit_all`](super::Tree::edit_all).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `inserted_text` doesn't have the length of the edit's new range, or if the
+    /// edit's old range isn't within the text on character boundaries.
+    pub fn edit(&mut self, edit: &InputEdit, inserted_text: &str) {
+        assert_eq!(
+            inserted_text.len(),
+            edit.new_end_byte - edit.start_byte,
+            "The inserted text must have the length of the edit's new range",
+        );
+        self.text
+            .replace_range(edit.start_byte..edit.old_end_byte, inserted_text);
+
+        let first_row = self.row_starts.partition_point(|&i| i <= edit.start_byte);
+        let last_row = self.row_starts.partition_point(|&i| i <= edit.old_end_byte);
+        let later_row_starts = self
+            .row_starts
+            .drain(last_row..)
+            .map(|i| i - edit.old_end_byte + edit.new_end_byte)
+            .collect::<Vec<_>>();
+        self.row_starts.truncate(first_row);
+        self.row_starts.extend(
+            inserted_text
+                .match_indices('\n')
+                .map(|(i, _)| edit.start_byte + i + 1),
+        );
+        self.row_starts.extend(later_row_starts);
+    }
+
+    /// Get the position of a byte offset, with a column in bytes.
+    #[must_use]
+    pub fn byte_to_point(&self, byte: usize) -> Point {
+        let byte = byte.min(self.text.len());
+        let row = self.row_starts.partition_point(|&i| i <= byte) - 1;
+        Point::new(row, byte - self.row_starts[row])
+    }
+
+    /// Get the byte offset of a position with a column in bytes.
+    #[must_use]
+    pub fn point_to_byte(&self, point: Point) -> usize {
+        let Some(&row_start) = self.row_starts.get(point.row) else {
+            return self.text.len();
+        };
+        let row_end = self.row_end(point.row);
+        (row_start + point.column).min(row_end)
+    }
+
+    /// Get the position of a byte offset, with a column in UTF-16 code units.
+    ///
+    /// A byte offset within a character refers to the start of that character, and one within
+    /// a line ending refers to the end of the row.
+    #[must_use]
+    pub fn byte_to_utf16_cu(&self, byte: usize) -> Point {
+        let mut byte = byte.min(self.text.len());
+        while !self.text.is_char_boundary(byte) {
+            byte -= 1;
+        }
+        let row = self.row_starts.partition_point(|&i| i <= byte) - 1;
+        let row_start = self.row_starts[row];
+        let column = self.text[row_start..byte.min(self.row_content_end(row))]
+            .encode_utf16()
+            .count();
+        Point::new(row, column)
+    }
+
+    /// Get the byte offset of a position with a column in UTF-16 code units.
+    ///
+    /// A column within a surrogate pair refers to the start of its character.
+    #[must_use]
+    pub fn utf16_cu_to_byte(&self, point: Point) -> usize {
+        let Some(&row_start) = self.row_starts.get(point.row) else {
+            return self.text.len();
+        };
+        let row_content_end = self.row_content_end(point.row);
+        let mut column = 0;
+        for (i, c) in self.text[row_start..row_content_end].char_indices() {
+            column += c.len_utf16();
+            if column > point.column {
+                return row_start + i;
+            }
+        }
+        row_content_end
+    }
+
+    /// Get the range of rows and UTF-16 columns that corresponds to a range of bytes.
+    #[must_use]
+    pub fn utf16_range(&self, range: ops::Range<usize>) -> ops::Range<Point> {
+        self.byte_to_utf16_cu(range.start)..self.byte_to_utf16_cu(range.end)
+    }
+
+    /// The byte offset at which a row ends, before its `\n`.
+    fn row_end(&self, row: usize) -> usize {
+        self.row_starts
+            .get(row + 1)
+            .map_or(self.text.len(), |next_row_start| next_row_start - 1)
+    }
+
+    /// The byte offset at which a row's content ends, before its `\n` or `\r\n`.
+    fn row_content_end(&self, row: usize) -> usize {
+        let row_end = self.row_end(row);
+        if row_end < self.text.len() && self.text[..row_end].ends_with('\r') {
+            row_end - 1
+        } else {
+            row_end
+        }
+    }
+}
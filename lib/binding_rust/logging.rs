@@ -0,0 +1,287 @@
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+
+use super::{LogType, Logger, StructuredLogger};
+
+/// The kind of event that a log message describes, which [`Parser::set_log_filter`] uses to
+/// choose which messages are logged.
+///
+/// [`Parser::set_log_filter`]: super::Parser::set_log_filter
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LogCategory {
+    /// The characters that the lexer consumes and skips.
+    Lex,
+    /// The start of each attempt to lex a token, and the lookahead token that it produces.
+    Lookahead,
+    /// Tokens being shifted onto the parse stack.
+    Shift,
+    /// Symbols on the parse stack being reduced to a node.
+    Reduce,
+    /// Nodes from the old tree being reused, or not, during an incremental parse.
+    Reuse,
+    /// Syntax errors being detected and recovered from.
+    ErrorRecovery,
+    /// Calls to the external scanner, along with the characters that it consumes and skips, and
+    /// the messages that it logs itself.
+    ExternalScanner,
+    /// Everything else, such as the parse starting and finishing, the stack version that is
+    /// processed next, and the choices between ambiguous stack versions.
+    Other,
+}
+
+/// The lexing function that was running when a message was logged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LexFunction {
+    /// The language's main lexing function.
+    Main,
+    /// The lexing function that checks if a word token is one of the language's keywords.
+    Keyword,
+    /// The language's external scanner.
+    ExternalScanner,
+}
+
+/// A log message, along with the structured information that it contains.
+///
+/// Most messages start with a name, like `reduce` or `consume`, followed by fields that are
+/// formatted as `key:value` pairs and separated by commas, like
+/// `reduce sym:struct_item, child_count:3`.
+#[derive(Clone, Copy, Debug)]
+pub struct LogMessage<'a> {
+    log_type: LogType,
+    text: &'a str,
+    category: LogCategory,
+    lex_function: Option<LexFunction>,
+    lex_state: Option<u16>,
+    byte_offset: Option<usize>,
+}
+
+impl<'a> LogMessage<'a> {
+    /// Get the type of the message, which tells if it was logged by the parser or the lexer.
+    #[must_use]
+    pub const fn log_type(&self) -> LogType {
+        self.log_type
+    }
+
+    /// Get the text of the message.
+    #[must_use]
+    pub const fn text(&self) -> &'a str {
+        self.text
+    }
+
+    /// Get the kind of event that the message describes.
+    #[must_use]
+    pub const fn category(&self) -> LogCategory {
+        self.category
+    }
+
+    /// Get the name of the message, which is the text before its first space.
+    #[must_use]
+    pub fn name(&self) -> &'a str {
+        self.text
+            .split_once(' ')
+            .map_or(self.text, |(name, _)| name)
+    }
+
+    /// Get the `key:value` fields that follow the name of the message.
+    pub fn fields(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.text
+            .split_once(' ')
+            .map_or("", |(_, fields)| fields)
+            .split(", ")
+            .filter_map(|field| field.split_once(':'))
+            .filter(|(key, _)| {
+                !key.is_empty()
+                    && key
+                        .bytes()
+                        .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+            })
+    }
+
+    /// Get the value of the field with the given key.
+    #[must_use]
+    pub fn field(&self, key: &str) -> Option<&'a str> {
+        self.fields()
+            .find_map(|(field_key, value)| (field_key == key).then_some(value))
+    }
+
+    /// Get the parse state or lex state that the message refers to.
+    #[must_use]
+    pub fn state(&self) -> Option<u16> {
+        self.field("state")?.parse().ok()
+    }
+
+    /// Get the name of the symbol that the message refers to.
+    #[must_use]
+    pub fn symbol(&self) -> Option<&'a str> {
+        self.field("sym").or_else(|| self.field("symbol"))
+    }
+
+    /// Get the lexing function that was running when the message was logged.
+    ///
+    /// This is only set for lex messages, and for the parse messages that start lexing.
+    #[must_use]
+    pub const fn lex_function(&self) -> Option<LexFunction> {
+        self.lex_function
+    }
+
+    /// Get the lex state that the lexer was in when the message was logged.
+    ///
+    /// This is only set for lex messages, and for the parse messages that start lexing.
+    #[must_use]
+    pub const fn lex_state(&self) -> Option<u16> {
+        self.lex_state
+    }
+
+    /// Get the byte offset in the document that the parser was at when the message was logged.
+    ///
+    /// This is the position of the stack version that the parser was processing, and for lex
+    /// messages, the position at which the lexer started. It's `None` for the messages that are
+    /// logged before the parser has processed any stack version.
+    #[must_use]
+    pub const fn byte_offset(&self) -> Option<usize> {
+        self.byte_offset
+    }
+}
+
+/// The state of a parser's logging, which is passed to the C logging callback.
+#[derive(Default)]
+pub struct LoggerPayload<'a> {
+    pub logger: Option<Logger<'a>>,
+    pub structured_logger: Option<StructuredLogger<'a>>,
+    pub filter: Option<Box<[LogCategory]>>,
+    /// The lexing function that is running, and the lex state that it started in.
+    lexer: Option<(LexFunction, u16)>,
+    byte_offset: Option<usize>,
+}
+
+impl LoggerPayload<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.logger.is_none() && self.structured_logger.is_none() && self.filter.is_none()
+    }
+
+    pub fn has_callback(&self) -> bool {
+        self.logger.is_some() || self.structured_logger.is_some()
+    }
+
+    pub fn log(&mut self, log_type: LogType, text: &str) {
+        let message = self.message(log_type, text);
+        if self
+            .filter
+            .as_ref()
+            .is_some_and(|filter| !filter.contains(&message.category))
+        {
+            return;
+        }
+        if let Some(logger) = &mut self.logger {
+            logger(log_type, text);
+        }
+        if let Some(structured_logger) = &mut self.structured_logger {
+            structured_logger(&message);
+        }
+    }
+
+    /// Build a structured message from a message's text, keeping track of the lexing function
+    /// and the position that later messages refer to.
+    fn message<'a>(&mut self, log_type: LogType, text: &'a str) -> LogMessage<'a> {
+        let mut message = LogMessage {
+            log_type,
+            text,
+            category: LogCategory::Other,
+            lex_function: None,
+            lex_state: None,
+            byte_offset: None,
+        };
+
+        let name = message.name();
+        if log_type == LogType::Parse {
+            if name == "new_parse" {
+                self.lexer = None;
+                self.byte_offset = None;
+            }
+            let lex_function = match name {
+                "lex_internal" => Some(LexFunction::Main),
+                "lex_keyword" => Some(LexFunction::Keyword),
+                "lex_external" => Some(LexFunction::ExternalScanner),
+                _ => None,
+            };
+            if let Some(lex_function) = lex_function {
+                self.lexer = message.state().map(|state| (lex_function, state));
+            }
+            if let Some(byte_offset) = message.field("byte").and_then(|byte| byte.parse().ok()) {
+                self.byte_offset = Some(byte_offset);
+            }
+            message.category = parse_category(name);
+        } else {
+            message.category = match self.lexer {
+                Some((LexFunction::ExternalScanner, _)) => LogCategory::ExternalScanner,
+                _ => LogCategory::Lex,
+            };
+        }
+
+        if log_type == LogType::Lex || name.starts_with("lex_") {
+            message.lex_function = self.lexer.map(|(lex_function, _)| lex_function);
+            message.lex_state = self.lexer.map(|(_, lex_state)| lex_state);
+        }
+        message.byte_offset = self.byte_offset;
+        message
+    }
+}
+
+/// Get the category of a parse message with the given name.
+fn parse_category(name: &str) -> LogCategory {
+    match name {
+        "lex_internal"
+        | "lex_keyword"
+        | "lexed_lookahead"
+        | "switch"
+        | "no_lookahead_after_non_terminal_extra" => LogCategory::Lookahead,
+        "lex_external" | "ignore_empty_external_token" => LogCategory::ExternalScanner,
+        "shift" | "shift_extra" => LogCategory::Shift,
+        "reduce" => LogCategory::Reduce,
+        "parse_after_edit"
+        | "different_included_range"
+        | "reuse_node"
+        | "before_reusable_node"
+        | "past_reusable_node"
+        | "reusable_node_has_different_external_scanner_state"
+        | "breakdown_top_of_stack"
+        | "state_mismatch" => LogCategory::Reuse,
+        _ if name.starts_with("cant_reuse_node") => LogCategory::Reuse,
+        "detect_error"
+        | "skip_unrecognized_character"
+        | "skip_token"
+        | "recover_to_previous"
+        | "recover_with_missing"
+        | "recover_eof"
+        | "select_smaller_error"
+        | "resume"
+        | "condense" => LogCategory::ErrorRecovery,
+        _ => LogCategory::Other,
+    }
+}
+
+/// Forward a log message to the `tracing` crate.
+#[cfg(feature = "tracing")]
+pub fn trace(message: &LogMessage) {
+    macro_rules! event {
+        ($level:expr) => {
+            tracing::event!(
+                target: "tree_sitter",
+                $level,
+                category = ?message.category(),
+                state = message.state(),
+                symbol = message.symbol(),
+                lex_function = message.lex_function().map(tracing::field::debug),
+                lex_state = message.lex_state(),
+                byte_offset = message.byte_offset(),
+                "{}",
+                message.text(),
+            )
+        };
+    }
+
+    match message.log_type() {
+        LogType::Parse => event!(tracing::Level::DEBUG),
+        LogType::Lex => event!(tracing::Level::TRACE),
+    }
+}
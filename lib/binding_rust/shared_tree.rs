@@ -0,0 +1,80 @@
+#[cfg(not(feature = "std"))]
+use alloc::sync::Arc;
+use core::ops::Deref;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+
+use super::{InputEdit, Tree};
+
+/// A [`Tree`] that can be shared between threads, and that is copied when it's edited.
+///
+/// Cloning a `SharedTree` only increments a reference count, so a clone can be handed to each
+/// thread that needs to read the tree, for example to walk it with a [`TreeCursor`] or to run a
+/// [`QueryCursor`] over it. Each of those threads needs its own cursor, but they can all read
+/// the same tree at the same time.
+///
+/// When a thread needs to edit the tree, [`SharedTree::make_mut`] gives it a [`Tree`] of its own
+/// to edit, which is only copied if other clones of the `SharedTree` still exist. The other
+/// clones keep seeing the tree as it was before the edit, so the readers never observe a
+/// partially edited tree. Copying a tree is cheap, because the copy shares all of its nodes with
+/// the original until they are edited.
+///
+/// [`TreeCursor`]: super::TreeCursor
+/// [`QueryCursor`]: super::QueryCursor
+#[derive(Clone, Debug)]
+pub struct SharedTree(Arc<Tree>);
+
+impl SharedTree {
+    /// Create a shared tree from a tree.
+    #[must_use]
+    pub fn new(tree: Tree) -> Self {
+        Self(Arc::new(tree))
+    }
+
+    /// Get a tree that can be edited, copying the tree first if it's shared with other clones
+    /// of this `SharedTree`.
+    pub fn make_mut(&mut self) -> &mut Tree {
+        Arc::make_mut(&mut self.0)
+    }
+
+    /// Edit the tree to keep it in sync with source code that has been edited, copying the tree
+    /// first if it's shared with other clones of this `SharedTree`.
+    ///
+    /// See [`Tree::edit`] for more information.
+    pub fn edit(&mut self, edit: &InputEdit) {
+        self.make_mut().edit(edit);
+    }
+
+    /// Get the tree, copying it if it's shared with other clones of this `SharedTree`.
+    #[must_use]
+    pub fn into_tree(self) -> Tree {
+        Arc::try_unwrap(self.0).unwrap_or_else(|tree| Tree::clone(&tree))
+    }
+
+    /// Check if two shared trees are clones of each other that refer to the same tree, which
+    /// means that neither of them has been edited since they were cloned.
+    #[must_use]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Deref for SharedTree {
+    type Target = Tree;
+
+    fn deref(&self) -> &Tree {
+        &self.0
+    }
+}
+
+impl AsRef<Tree> for SharedTree {
+    fn as_ref(&self) -> &Tree {
+        &self.0
+    }
+}
+
+impl From<Tree> for SharedTree {
+    fn from(tree: Tree) -> Self {
+        Self::new(tree)
+    }
+}
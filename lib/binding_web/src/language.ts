@@ -163,6 +163,24 @@ export class Language {
     return C._ts_language_type_is_visible_wasm(this[0], typeId) ? true : false;
   }
 
+  /**
+   * Get the node type id of the language's word token, which is the token
+   * that keywords are lexed as before they are checked against the language's
+   * keywords, or `null` if the language has no word token.
+   */
+  get wordToken(): number | null {
+    return C._ts_language_word_token(this[0]) || null;
+  }
+
+  /**
+   * Check if a node type is one of the language's keywords, which are the
+   * string tokens that are lexed as the word token first, and then matched
+   * against the word's text.
+   */
+  nodeTypeIsKeyword(typeId: number): boolean {
+    return C._ts_language_is_keyword(this[0], typeId) ? true : false;
+  }
+
   /**
    * Get the supertypes ids of this language.
    *
@@ -54,6 +54,20 @@ describe('Language', () => {
     });
   });
 
+  describe('.wordToken, .nodeTypeIsKeyword', () => {
+    it('identifies the keywords that are lexed as the word token', () => {
+      expect(JavaScript.nodeTypeForId(JavaScript.wordToken!)).toBe('identifier');
+
+      const functionId = JavaScript.idForNodeType('function', false)!;
+      const starId = JavaScript.idForNodeType('*', false)!;
+      const identifierId = JavaScript.idForNodeType('identifier', true)!;
+      expect(JavaScript.nodeTypeIsKeyword(functionId)).toBe(true);
+      expect(JavaScript.nodeTypeIsKeyword(starId)).toBe(false);
+      expect(JavaScript.nodeTypeIsKeyword(identifierId)).toBe(false);
+      expect(JavaScript.nodeTypeIsKeyword(10000)).toBe(false);
+    });
+  });
+
   describe('Supertypes', () => {
     it('gets the supertypes and subtypes of a parser', () => {
       const supertypes = Rust.supertypes;
@@ -261,6 +261,18 @@ declare module 'web-tree-sitter' {
 		 * Check if a node type is visible.
 		 */
 		nodeTypeIsVisible(typeId: number): boolean;
+		/**
+		 * Get the node type id of the language's word token, which is the token
+		 * that keywords are lexed as before they are checked against the language's
+		 * keywords, or `null` if the language has no word token.
+		 */
+		get wordToken(): number | null;
+		/**
+		 * Check if a node type is one of the language's keywords, which are the
+		 * string tokens that are lexed as the word token first, and then matched
+		 * against the word's text.
+		 */
+		nodeTypeIsKeyword(typeId: number): boolean;
 		/**
 		 * Get the supertypes ids of this language.
 		 *
@@ -26,7 +26,7 @@ extern "C" {
  * The Tree-sitter library is generally backwards-compatible with languages
  * generated using older CLI versions, but is not forwards-compatible.
  */
-#define TREE_SITTER_LANGUAGE_VERSION 15
+#define TREE_SITTER_LANGUAGE_VERSION 16
 
 /**
  * The earliest ABI version that is supported by the current version of the
@@ -102,6 +102,19 @@ typedef struct TSParseOptions {
   bool (*progress_callback)(TSParseState *state);
 } TSParseOptions;
 
+typedef struct TSParseStats {
+  uint32_t lex_count;
+  uint32_t external_scan_count;
+  uint64_t lex_nanos;
+  uint64_t external_scan_nanos;
+  uint32_t state_count;
+  uint32_t stack_split_count;
+  uint32_t stack_merge_count;
+  uint32_t reused_node_count;
+  uint32_t reused_byte_count;
+  uint64_t tree_byte_count;
+} TSParseStats;
+
 typedef enum TSLogType {
   TSLogTypeParse,
   TSLogTypeLex,
@@ -112,6 +125,11 @@ typedef struct TSLogger {
   void (*log)(void *payload, TSLogType log_type, const char *buffer);
 } TSLogger;
 
+typedef struct TSDotGraphWriter {
+  void *payload;
+  void (*write)(void *payload, const char *data, uint32_t length);
+} TSDotGraphWriter;
+
 typedef struct TSInputEdit {
   uint32_t start_byte;
   uint32_t old_end_byte;
@@ -174,6 +192,25 @@ typedef enum TSQueryError {
   TSQueryErrorLanguage,
 } TSQueryError;
 
+typedef enum TSTreeDeserializeError {
+  TSTreeDeserializeErrorNone = 0,
+  TSTreeDeserializeErrorFormat,
+  TSTreeDeserializeErrorVersion,
+  TSTreeDeserializeErrorSymbol,
+  TSTreeDeserializeErrorField,
+  TSTreeDeserializeErrorParseState,
+  TSTreeDeserializeErrorRange,
+} TSTreeDeserializeError;
+
+typedef enum TSQueryDeserializeError {
+  TSQueryDeserializeErrorNone = 0,
+  TSQueryDeserializeErrorFormat,
+  TSQueryDeserializeErrorVersion,
+  TSQueryDeserializeErrorLanguage,
+  TSQueryDeserializeErrorSymbol,
+  TSQueryDeserializeErrorField,
+} TSQueryDeserializeError;
+
 typedef struct TSQueryCursorState {
   void *payload;
   uint32_t current_byte_offset;
@@ -184,6 +221,12 @@ typedef struct TSQueryCursorOptions {
   bool (*progress_callback)(TSQueryCursorState *state);
 } TSQueryCursorOptions;
 
+typedef struct TSQueryCursorStats {
+  uint32_t node_count;
+  uint32_t state_count;
+  uint32_t max_in_progress_match_count;
+} TSQueryCursorStats;
+
 /**
  * The metadata associated with a language.
  *
@@ -308,6 +351,8 @@ const TSRange *ts_parser_included_ranges(
  *    the same arguments.
  * 4. Parsing was cancelled due to the progress callback returning true. This callback
  *    is passed in [`ts_parser_parse_with_options`] inside the [`TSParseOptions`] struct.
+ *    It is called periodically during parsing, and also while a long token is being
+ *    lexed, so that a slow lex function or external scanner can be cancelled too.
  *
  * [`read`]: TSInput::read
  * [`payload`]: TSInput::payload
@@ -430,6 +475,63 @@ TSLogger ts_parser_logger(const TSParser *self);
  */
 void ts_parser_print_dot_graphs(TSParser *self, int fd);
 
+/**
+ * Set the writer to which the parser should pass debugging graphs during
+ * parsing, as an alternative to [`ts_parser_print_dot_graphs`] that doesn't
+ * require a file descriptor. Each graph is passed to the writer's `write`
+ * function in a single call, formatted in the DOT language. You can turn off
+ * this logging by passing a writer whose `write` function is `NULL`.
+ *
+ * The parser does not take ownership over the writer payload. If a writer was
+ * previously assigned, the caller is responsible for releasing any memory
+ * owned by the previous writer.
+ */
+void ts_parser_set_dot_graph_writer(TSParser *self, TSDotGraphWriter writer);
+
+/**
+ * Get the parser's current dot graph writer.
+ */
+TSDotGraphWriter ts_parser_dot_graph_writer(const TSParser *self);
+
+/**
+ * Set whether the parser should measure the time spent in the lexer and the
+ * external scanner, and the memory used by the resulting tree. This slows
+ * parsing down, so it is off by default. See [`ts_parser_parse_stats`].
+ */
+void ts_parser_set_record_stats(TSParser *self, bool record_stats);
+
+/**
+ * Get statistics about the parser's most recent parse, which are reset whenever
+ * a new parse is started:
+ * 1. [`lex_count`] and [`external_scan_count`]: The number of times that the
+ *    internal lexer and the external scanner were called.
+ * 2. [`lex_nanos`] and [`external_scan_nanos`]: The time spent in the internal
+ *    lexer and the external scanner, if recording was enabled with
+ *    [`ts_parser_set_record_stats`].
+ * 3. [`state_count`]: The number of times that the parser looked up the actions
+ *    for a parse state and a lookahead token.
+ * 4. [`stack_split_count`] and [`stack_merge_count`]: The number of times that
+ *    the parse stack was split because of an ambiguity, and the number of times
+ *    that two versions of the stack were merged back together.
+ * 5. [`reused_node_count`] and [`reused_byte_count`]: The number of nodes from
+ *    the old tree that were reused, and the number of bytes that they span.
+ * 6. [`tree_byte_count`]: The memory used by the nodes of the resulting tree,
+ *    including nodes that were reused from the old tree, if recording was
+ *    enabled with [`ts_parser_set_record_stats`].
+ *
+ * [`lex_count`]: TSParseStats::lex_count
+ * [`external_scan_count`]: TSParseStats::external_scan_count
+ * [`lex_nanos`]: TSParseStats::lex_nanos
+ * [`external_scan_nanos`]: TSParseStats::external_scan_nanos
+ * [`state_count`]: TSParseStats::state_count
+ * [`stack_split_count`]: TSParseStats::stack_split_count
+ * [`stack_merge_count`]: TSParseStats::stack_merge_count
+ * [`reused_node_count`]: TSParseStats::reused_node_count
+ * [`reused_byte_count`]: TSParseStats::reused_byte_count
+ * [`tree_byte_count`]: TSParseStats::tree_byte_count
+ */
+TSParseStats ts_parser_parse_stats(const TSParser *self);
+
 /******************/
 /* Section - Tree */
 /******************/
@@ -474,6 +576,16 @@ const TSLanguage *ts_tree_language(const TSTree *self);
  */
 TSRange *ts_tree_included_ranges(const TSTree *self, uint32_t *length);
 
+/**
+ * Get the number of bytes of memory that the syntax tree uses.
+ *
+ * This includes the memory for all of the tree's nodes, even the ones that
+ * it shares with other trees, such as copies of it made with [`ts_tree_copy`]
+ * or trees that reused its nodes when they were parsed. So the memory that
+ * is freed when the tree is deleted can be less than this.
+ */
+size_t ts_tree_memory_usage(const TSTree *self);
+
 /**
  * Edit the syntax tree to keep it in sync with source code that has been
  * edited.
@@ -515,6 +627,38 @@ TSRange *ts_tree_get_changed_ranges(
  */
 void ts_tree_print_dot_graph(const TSTree *self, int file_descriptor);
 
+/**
+ * Serialize the syntax tree into a compact binary format, so that it can be
+ * stored or sent elsewhere and turned back into a tree using
+ * [`ts_tree_deserialize`], without parsing the source code again.
+ *
+ * The format stores each node's symbol, range, flags, and the production that
+ * determines its children's fields, but not the source code itself. Edits that
+ * haven't been followed by a new parse are not preserved.
+ *
+ * The returned buffer is allocated using `malloc` and the caller is responsible
+ * for freeing it using `free`. Its length will be written to the given `length`
+ * pointer.
+ */
+char *ts_tree_serialize(const TSTree *self, uint32_t *length);
+
+/**
+ * Create a syntax tree from the bytes produced by [`ts_tree_serialize`], for a
+ * document that is `source_length` bytes long.
+ *
+ * The bytes are checked to have been serialized with the same version of the
+ * format, to only refer to symbols, fields, and parse states that exist in the
+ * given language, and to describe nodes that fit within the document. If they
+ * don't, this returns `NULL` and writes the reason to the `error` pointer.
+ */
+TSTree *ts_tree_deserialize(
+  const TSLanguage *language,
+  uint32_t source_length,
+  const char *bytes,
+  uint32_t length,
+  TSTreeDeserializeError *error
+);
+
 /******************/
 /* Section - Node */
 /******************/
@@ -983,6 +1127,12 @@ bool ts_query_is_pattern_rooted(const TSQuery *self, uint32_t pattern_index);
  */
 bool ts_query_is_pattern_non_local(const TSQuery *self, uint32_t pattern_index);
 
+/*
+ * Get the depth of the deepest node in the given pattern, relative to the
+ * pattern's root nodes, which are at depth zero.
+ */
+uint32_t ts_query_pattern_depth(const TSQuery *self, uint32_t pattern_index);
+
 /*
  * Check if a given pattern is guaranteed to match once a given step is reached.
  * The step is specified by its byte offset in the query's source code.
@@ -1033,6 +1183,37 @@ void ts_query_disable_capture(TSQuery *self, const char *name, uint32_t length);
  */
 void ts_query_disable_pattern(TSQuery *self, uint32_t pattern_index);
 
+/**
+ * Serialize the compiled query into a binary format, so that it can be stored
+ * and turned back into a query using [`ts_query_deserialize`], without
+ * compiling its source code again.
+ *
+ * The format stores the query's pattern steps, capture names, predicates, and
+ * string values, along with a fingerprint of the query's language. Captures
+ * and patterns that have been disabled stay disabled.
+ *
+ * The returned buffer is allocated using `malloc` and the caller is responsible
+ * for freeing it using `free`. Its length will be written to the given `length`
+ * pointer.
+ */
+char *ts_query_serialize(const TSQuery *self, uint32_t *length);
+
+/**
+ * Create a query from the bytes produced by [`ts_query_serialize`].
+ *
+ * The bytes are checked to have been serialized with the same version of the
+ * format, for a language with the same symbols and fields as the given one,
+ * and to only refer to symbols, fields, captures, and steps that exist. If
+ * they don't, this returns `NULL` and writes the reason to the `error`
+ * pointer, and the query should be compiled from its source code instead.
+ */
+TSQuery *ts_query_deserialize(
+  const TSLanguage *language,
+  const char *bytes,
+  uint32_t length,
+  TSQueryDeserializeError *error
+);
+
 /**
  * Create a new cursor for executing a given query.
  *
@@ -1092,6 +1273,26 @@ bool ts_query_cursor_did_exceed_match_limit(const TSQueryCursor *self);
 uint32_t ts_query_cursor_match_limit(const TSQueryCursor *self);
 void ts_query_cursor_set_match_limit(TSQueryCursor *self, uint32_t limit);
 
+/**
+ * Manage the maximum number of steps that this query cursor can take while
+ * executing a query.
+ *
+ * A step is one check of an in-progress match against a node, which is the
+ * unit of work that grows when a query's patterns can match in many different
+ * ways, like patterns with nested or repeated wildcards. If the cursor reaches
+ * this limit, it halts: it stops looking for new matches and discards its
+ * in-progress ones, but the matches that it has already finished are still
+ * returned. The limit is checked before each node is processed, so the cursor
+ * can take slightly more steps than the limit, for the states that it checks
+ * against the last node.
+ *
+ * The step count is reset whenever [`ts_query_cursor_exec`] is called. By
+ * default, there is no limit, which is represented by `UINT64_MAX`.
+ */
+bool ts_query_cursor_did_exceed_step_limit(const TSQueryCursor *self);
+uint64_t ts_query_cursor_step_limit(const TSQueryCursor *self);
+void ts_query_cursor_set_step_limit(TSQueryCursor *self, uint64_t limit);
+
 /**
  * @deprecated use [`ts_query_cursor_exec_with_options`] and pass in a callback instead, this will be removed in 0.26.
  *
@@ -1183,6 +1384,49 @@ bool ts_query_cursor_next_capture(
  */
 void ts_query_cursor_set_max_start_depth(TSQueryCursor *self, uint32_t max_start_depth);
 
+/**
+ * Set which of a query's patterns the query cursor should match, without
+ * changing the query itself. The `enabled_patterns` array contains a flag for
+ * each pattern index, and the patterns whose index is greater than or equal to
+ * `length` are enabled, so passing a `length` of zero enables every pattern.
+ *
+ * Unlike [`ts_query_disable_pattern`], this makes it possible for different
+ * cursors to match different subsets of the same query's patterns at the same
+ * time.
+ */
+void ts_query_cursor_set_enabled_patterns(
+  TSQueryCursor *self,
+  const bool *enabled_patterns,
+  uint32_t length
+);
+
+/**
+ * Restore all of the query cursor's settings to their defaults: its byte and
+ * point ranges, its match limit, its step limit, its maximum start depth, its
+ * timeout, and its enabled patterns.
+ * This also discards any in-progress or unreturned matches. The memory that
+ * the cursor has allocated is kept, so that a cursor can be reused for many
+ * queries more cheaply than creating a new one for each of them.
+ */
+void ts_query_cursor_reset(TSQueryCursor *self);
+
+/**
+ * Get statistics about the query cursor's most recent execution, which are
+ * reset whenever [`ts_query_cursor_exec`] is called:
+ * 1. [`node_count`]: The number of nodes that the cursor visited.
+ * 2. [`state_count`]: The number of times that an in-progress state was
+ *    checked against a node.
+ * 3. [`max_in_progress_match_count`]: The largest number of in-progress
+ *    matches that were storing captures at the same time. This is the number
+ *    that the match limit bounds, so if it reaches the limit, then
+ *    [`ts_query_cursor_did_exceed_match_limit`] may return true.
+ *
+ * [`node_count`]: TSQueryCursorStats::node_count
+ * [`state_count`]: TSQueryCursorStats::state_count
+ * [`max_in_progress_match_count`]: TSQueryCursorStats::max_in_progress_match_count
+ */
+TSQueryCursorStats ts_query_cursor_stats(const TSQueryCursor *self);
+
 /**********************/
 /* Section - Language */
 /**********************/
@@ -1208,6 +1452,27 @@ uint32_t ts_language_symbol_count(const TSLanguage *self);
 */
 uint32_t ts_language_state_count(const TSLanguage *self);
 
+/**
+ * Get the number of external tokens in this language.
+ */
+uint32_t ts_language_external_token_count(const TSLanguage *self);
+
+/**
+ * Get the numerical id of the language's word token, which is the token that
+ * keywords are lexed as before they are checked against the language's
+ * keywords, or zero if the language has no word token. If the language has
+ * several word tokens, this is the first of them.
+ */
+TSSymbol ts_language_word_token(const TSLanguage *self);
+
+/**
+ * Check if the given token is one of the language's keywords: a string token
+ * that is lexed by first lexing a word token, and then checking whether the
+ * word's text is the keyword's. This is always false for languages that were
+ * loaded from WebAssembly with a [`TSWasmStore`].
+ */
+bool ts_language_is_keyword(const TSLanguage *self, TSSymbol symbol);
+
 /**
  * Get the numerical id for the given node type string.
  */
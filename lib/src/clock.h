@@ -49,6 +49,12 @@ static inline bool clock_is_gt(TSClock self, TSClock other) {
   return self > other;
 }
 
+static inline uint64_t clock_nanos_since(TSClock self) {
+  LARGE_INTEGER frequency;
+  QueryPerformanceFrequency(&frequency);
+  return (clock_now() - self) * 1000000000 / (uint64_t)frequency.QuadPart;
+}
+
 #elif defined(CLOCK_MONOTONIC)
 
 // POSIX with monotonic clock support (Linux, macOS)
@@ -100,6 +106,11 @@ static inline bool clock_is_gt(TSClock self, TSClock other) {
   return self.tv_nsec > other.tv_nsec;
 }
 
+static inline uint64_t clock_nanos_since(TSClock self) {
+  TSClock now = clock_now();
+  return (uint64_t)(now.tv_sec - self.tv_sec) * 1000000000 + (uint64_t)now.tv_nsec - (uint64_t)self.tv_nsec;
+}
+
 #else
 
 // POSIX without monotonic clock support
@@ -141,6 +152,10 @@ static inline bool clock_is_gt(TSClock self, TSClock other) {
   return self > other;
 }
 
+static inline uint64_t clock_nanos_since(TSClock self) {
+  return (clock_now() - self) * 1000000000 / (uint64_t)CLOCKS_PER_SEC;
+}
+
 #endif
 
 #endif  // TREE_SITTER_CLOCK_H_
@@ -1,4 +1,5 @@
 #include "./language.h"
+#include "./lexer.h"
 #include "./wasm_store.h"
 #include "tree_sitter/api.h"
 #include <string.h>
@@ -24,6 +25,74 @@ uint32_t ts_language_state_count(const TSLanguage *self) {
   return self->state_count;
 }
 
+uint32_t ts_language_external_token_count(const TSLanguage *self) {
+  return self->external_token_count;
+}
+
+TSSymbol ts_language_word_token(const TSLanguage *self) {
+  return self->keyword_capture_token;
+}
+
+typedef struct {
+  const char *string;
+  uint32_t length;
+} KeywordInput;
+
+static const char *ts_language__keyword_input_read(
+  void *payload,
+  uint32_t byte,
+  TSPoint point,
+  uint32_t *length
+) {
+  (void)point;
+  const KeywordInput *input = payload;
+  if (byte >= input->length) {
+    *length = 0;
+    return "";
+  }
+  *length = input->length - byte;
+  return input->string + byte;
+}
+
+// A keyword's symbol name is its text, so a token is a keyword if the keyword
+// lex function of one of the language's word tokens lexes its entire name as
+// that token.
+bool ts_language_is_keyword(const TSLanguage *self, TSSymbol symbol) {
+  if (
+    !self->keyword_lex_fn ||
+    ts_language_is_wasm(self) ||
+    symbol == 0 ||
+    symbol >= self->token_count ||
+    ts_language_is_keyword_capture_token(self, symbol)
+  ) return false;
+
+  const char *name = ts_language_symbol_name(self, symbol);
+  if (!name || !name[0]) return false;
+  KeywordInput input = {name, (uint32_t)strlen(name)};
+
+  Lexer lexer;
+  ts_lexer_init(&lexer);
+  ts_lexer_set_input(&lexer, (TSInput) {
+    &input,
+    ts_language__keyword_input_read,
+    TSInputEncodingUTF8,
+    NULL,
+  });
+
+  bool result = false;
+  uint16_t word_token_count = ts_language_keyword_capture_token_count(self);
+  for (TSStateId lex_state = 0; lex_state < word_token_count && !result; lex_state++) {
+    ts_lexer_reset(&lexer, length_zero());
+    ts_lexer_start(&lexer);
+    result =
+      self->keyword_lex_fn(&lexer.data, lex_state) &&
+      lexer.data.result_symbol == symbol &&
+      lexer.token_end_position.bytes == input.length;
+  }
+  ts_lexer_delete(&lexer);
+  return result;
+}
+
 const TSSymbol *ts_language_supertypes(const TSLanguage *self, uint32_t *length) {
   if (self->abi_version >= LANGUAGE_VERSION_WITH_RESERVED_WORDS) {
     *length = self->supertype_count;
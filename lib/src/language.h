@@ -10,6 +10,7 @@ extern "C" {
 
 #define ts_builtin_sym_error_repeat (ts_builtin_sym_error - 1)
 
+#define LANGUAGE_VERSION_WITH_MULTIPLE_WORD_TOKENS 16
 #define LANGUAGE_VERSION_WITH_RESERVED_WORDS 15
 #define LANGUAGE_VERSION_WITH_PRIMARY_STATES 14
 
@@ -53,6 +54,51 @@ static inline const TSParseAction *ts_language_actions(
   return entry.actions;
 }
 
+// The number of word tokens that the language uses to capture keywords. Languages
+// with a single word token only store it in `keyword_capture_token`.
+static inline uint16_t ts_language_keyword_capture_token_count(const TSLanguage *self) {
+  if (
+    self->abi_version >= LANGUAGE_VERSION_WITH_MULTIPLE_WORD_TOKENS &&
+    self->keyword_capture_token_count > 0
+  ) {
+    return self->keyword_capture_token_count;
+  }
+  return self->keyword_capture_token != 0 ? 1 : 0;
+}
+
+static inline TSSymbol ts_language_keyword_capture_token(
+  const TSLanguage *self,
+  uint16_t index
+) {
+  return index == 0 ? self->keyword_capture_token : self->keyword_capture_tokens[index];
+}
+
+// If the given symbol is one of the language's word tokens, find the state in
+// which the keyword lex function should start lexing the keywords that it captures.
+static inline bool ts_language_keyword_lex_state(
+  const TSLanguage *self,
+  TSSymbol symbol,
+  TSStateId *lex_state
+) {
+  if (symbol == 0) return false;
+  uint16_t count = ts_language_keyword_capture_token_count(self);
+  for (uint16_t i = 0; i < count; i++) {
+    if (ts_language_keyword_capture_token(self, i) == symbol) {
+      *lex_state = i;
+      return true;
+    }
+  }
+  return false;
+}
+
+static inline bool ts_language_is_keyword_capture_token(
+  const TSLanguage *self,
+  TSSymbol symbol
+) {
+  TSStateId lex_state;
+  return ts_language_keyword_lex_state(self, symbol, &lex_state);
+}
+
 static inline bool ts_language_has_reduce_action(
   const TSLanguage *self,
   TSStateId state,
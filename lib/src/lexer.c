@@ -25,6 +25,7 @@
   }
 
 static const int32_t BYTE_ORDER_MARK = 0xFEFF;
+static const uint32_t ADVANCE_COUNT_PER_PROGRESS_CHECK = 1024;
 
 static const TSRange DEFAULT_RANGE = {
   .start_point = {
@@ -122,11 +123,17 @@ static void ts_lexer__get_lookahead(Lexer *self) {
   self->lookahead_size = decode(chunk, size, &self->data.lookahead);
 
   // If this chunk ended in the middle of a multi-byte character,
-  // try again with a fresh chunk.
+  // try again with a fresh chunk. If there is no more text, then
+  // the incomplete character is at the end of the input.
   if (self->data.lookahead == TS_DECODE_ERROR && size < 4) {
     ts_lexer__get_chunk(self);
     chunk = (const uint8_t *)self->chunk;
     size = self->chunk_size;
+    if (size == 0) {
+      self->lookahead_size = 1;
+      self->data.lookahead = '\0';
+      return;
+    }
     self->lookahead_size = decode(chunk, size, &self->data.lookahead);
   }
 
@@ -136,6 +143,7 @@ static void ts_lexer__get_lookahead(Lexer *self) {
 }
 
 static void ts_lexer_goto(Lexer *self, Length position) {
+  self->canceled = false;
   if (position.bytes != self->current_position.bytes) {
     ts_lexer__invalidate_column_data(self);
   }
@@ -262,6 +270,23 @@ static void ts_lexer__advance(TSLexer *_self, bool skip) {
   }
 
   ts_lexer__do_advance(self, skip);
+
+  // Every so often, give the parser a chance to cancel. If it does, then
+  // move to the EOF state, so that the lex function or the external scanner
+  // stops consuming characters.
+  if (
+    self->progress_callback &&
+    ++self->advance_count >= ADVANCE_COUNT_PER_PROGRESS_CHECK
+  ) {
+    self->advance_count = 0;
+    if (!self->progress_callback(self->progress_payload, self->current_position.bytes)) {
+      self->canceled = true;
+      self->current_included_range_index = self->included_range_count;
+      ts_lexer__clear_chunk(self);
+      self->data.lookahead = '\0';
+      self->lookahead_size = 1;
+    }
+  }
 }
 
 // Mark that a token match has completed. This can be called multiple
@@ -295,7 +320,7 @@ static uint32_t ts_lexer__get_column(TSLexer *_self) {
 
   self->did_get_column = true;
 
-  if (!self->column_data.valid) {
+  if (!self->column_data.valid && !self->canceled) {
     // Record current position
     uint32_t goal_byte = self->current_position.bytes;
 
@@ -369,10 +394,14 @@ void ts_lexer_init(Lexer *self) {
       .payload = NULL,
       .log = NULL
     },
+    .progress_callback = NULL,
+    .progress_payload = NULL,
     .included_ranges = NULL,
     .included_range_count = 0,
     .current_included_range_index = 0,
+    .advance_count = 0,
     .did_get_column = false,
+    .canceled = false,
     .column_data = {
       .valid = false,
       .value = 0
@@ -392,9 +421,10 @@ void ts_lexer_set_input(Lexer *self, TSInput input) {
 }
 
 // Move the lexer to the given position. This doesn't do any work
-// if the parser is already at the given position.
+// if the parser is already at the given position, unless lexing was
+// canceled there.
 void ts_lexer_reset(Lexer *self, Length position) {
-  if (position.bytes != self->current_position.bytes) {
+  if (position.bytes != self->current_position.bytes || self->canceled) {
     ts_lexer_goto(self, position);
   }
 }
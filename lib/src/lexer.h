@@ -15,6 +15,11 @@ typedef struct {
   bool valid;
 } ColumnData;
 
+// A function that the lexer calls periodically while it advances, so that
+// a long-running lex function or external scanner can be canceled. It
+// returns false if lexing should stop.
+typedef bool (*LexerProgressCallback)(void *payload, uint32_t byte_offset);
+
 typedef struct {
   TSLexer data;
   Length current_position;
@@ -25,13 +30,17 @@ typedef struct {
   const char *chunk;
   TSInput input;
   TSLogger logger;
+  LexerProgressCallback progress_callback;
+  void *progress_payload;
 
   uint32_t included_range_count;
   uint32_t current_included_range_index;
   uint32_t chunk_start;
   uint32_t chunk_size;
   uint32_t lookahead_size;
+  uint32_t advance_count;
   bool did_get_column;
+  bool canceled;
   ColumnData column_data;
 
   char debug_buffer[TREE_SITTER_SERIALIZATION_BUFFER_SIZE];
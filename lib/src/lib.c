@@ -9,4 +9,5 @@
 #include "./subtree.c"
 #include "./tree_cursor.c"
 #include "./tree.c"
+#include "./tree_serialization.c"
 #include "./wasm_store.c"
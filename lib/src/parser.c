@@ -65,12 +65,14 @@
   if (self->dot_graph_file) {                                                    \
     ts_stack_print_dot_graph(self->stack, self->language, self->dot_graph_file); \
     fputs("\n\n", self->dot_graph_file);                                         \
+    ts_parser__flush_dot_graph(self);                                            \
   }
 
 #define LOG_TREE(tree)                                                      \
   if (self->dot_graph_file) {                                               \
     ts_subtree_print_dot_graph(tree, self->language, self->dot_graph_file); \
     fputs("\n", self->dot_graph_file);                                      \
+    ts_parser__flush_dot_graph(self);                                       \
   }
 
 #define SYM_NAME(symbol) ts_language_symbol_name(self->language, symbol)
@@ -104,6 +106,7 @@ struct TSParser {
   ReusableNode reusable_node;
   void *external_scanner_payload;
   FILE *dot_graph_file;
+  TSDotGraphWriter dot_graph_writer;
   TSClock end_clock;
   TSDuration timeout_duration;
   unsigned accept_count;
@@ -113,10 +116,12 @@ struct TSParser {
   TSRangeArray included_range_differences;
   TSParseOptions parse_options;
   TSParseState parse_state;
+  TSParseStats stats;
   unsigned included_range_difference_index;
   bool has_scanner_error;
   bool canceled_balancing;
   bool has_error;
+  bool record_stats;
 };
 
 typedef struct {
@@ -160,6 +165,22 @@ static const char *ts_string_input_read(
 
 // Parser - Private
 
+// When the parser has a dot graph writer, its graphs are written to a temporary
+// file, and each one is read back and passed to the writer once it's complete.
+// The file is then reused for the next graph.
+static void ts_parser__flush_dot_graph(TSParser *self) {
+  if (!self->dot_graph_writer.write) return;
+  long length = ftell(self->dot_graph_file);
+  if (length <= 0) return;
+
+  char *graph = ts_malloc(length);
+  rewind(self->dot_graph_file);
+  size_t size = fread(graph, 1, length, self->dot_graph_file);
+  rewind(self->dot_graph_file);
+  self->dot_graph_writer.write(self->dot_graph_writer.payload, graph, (uint32_t)size);
+  ts_free(graph);
+}
+
 static void ts_parser__log(TSParser *self) {
   if (self->lexer.logger.log) {
     self->lexer.logger.log(
@@ -176,6 +197,7 @@ static void ts_parser__log(TSParser *self) {
       fputc(*chr, self->dot_graph_file);
     }
     fprintf(self->dot_graph_file, "\"\n}\n\n");
+    ts_parser__flush_dot_graph(self);
   }
 }
 
@@ -344,20 +366,40 @@ static bool ts_parser__better_version_exists(
   return false;
 }
 
+// Start timing a call to the lexer or the external scanner, if the parser is
+// recording statistics.
+static inline TSClock ts_parser__stats_clock(TSParser *self) {
+  return self->record_stats ? clock_now() : clock_null();
+}
+
+static inline void ts_parser__record_time(TSParser *self, TSClock start, uint64_t *nanos) {
+  if (self->record_stats) *nanos += clock_nanos_since(start);
+}
+
 static bool ts_parser__call_main_lex_fn(TSParser *self, TSLexerMode lex_mode) {
+  bool result;
+  TSClock start = ts_parser__stats_clock(self);
   if (ts_language_is_wasm(self->language)) {
-    return ts_wasm_store_call_lex_main(self->wasm_store, lex_mode.lex_state);
+    result = ts_wasm_store_call_lex_main(self->wasm_store, lex_mode.lex_state);
   } else {
-    return self->language->lex_fn(&self->lexer.data, lex_mode.lex_state);
+    result = self->language->lex_fn(&self->lexer.data, lex_mode.lex_state);
   }
+  ts_parser__record_time(self, start, &self->stats.lex_nanos);
+  self->stats.lex_count++;
+  return result;
 }
 
-static bool ts_parser__call_keyword_lex_fn(TSParser *self) {
+static bool ts_parser__call_keyword_lex_fn(TSParser *self, TSStateId lex_state) {
+  bool result;
+  TSClock start = ts_parser__stats_clock(self);
   if (ts_language_is_wasm(self->language)) {
-    return ts_wasm_store_call_lex_keyword(self->wasm_store, 0);
+    result = ts_wasm_store_call_lex_keyword(self->wasm_store, lex_state);
   } else {
-    return self->language->keyword_lex_fn(&self->lexer.data, 0);
+    result = self->language->keyword_lex_fn(&self->lexer.data, lex_state);
   }
+  ts_parser__record_time(self, start, &self->stats.lex_nanos);
+  self->stats.lex_count++;
+  return result;
 }
 
 static void ts_parser__external_scanner_create(
@@ -446,8 +488,10 @@ static bool ts_parser__external_scanner_scan(
   TSParser *self,
   TSStateId external_lex_state
 ) {
+  bool result;
+  TSClock start = ts_parser__stats_clock(self);
   if (ts_language_is_wasm(self->language)) {
-    bool result = ts_wasm_store_call_scanner_scan(
+    result = ts_wasm_store_call_scanner_scan(
       self->wasm_store,
       (uintptr_t)self->external_scanner_payload,
       external_lex_state * self->language->external_token_count
@@ -455,18 +499,20 @@ static bool ts_parser__external_scanner_scan(
     if (ts_wasm_store_has_error(self->wasm_store)) {
       self->has_scanner_error = true;
     }
-    return result;
   } else {
     const bool *valid_external_tokens = ts_language_enabled_external_tokens(
       self->language,
       external_lex_state
     );
-    return self->language->external_scanner.scan(
+    result = self->language->external_scanner.scan(
       self->external_scanner_payload,
       &self->lexer.data,
       valid_external_tokens
     );
   }
+  ts_parser__record_time(self, start, &self->stats.external_scan_nanos);
+  self->stats.external_scan_count++;
+  return result;
 }
 
 static bool ts_parser__can_reuse_first_leaf(
@@ -491,7 +537,7 @@ static bool ts_parser__can_reuse_first_leaf(
     table_entry->action_count > 0 &&
     memcmp(&leaf_lex_mode, &current_lex_mode, sizeof(TSLexerMode)) == 0 &&
     (
-      leaf_symbol != self->language->keyword_capture_token ||
+      !ts_language_is_keyword_capture_token(self->language, leaf_symbol) ||
       (!ts_subtree_is_keyword(tree) && ts_subtree_parse_state(tree) == state)
     )
   ) return true;
@@ -529,6 +575,7 @@ static Subtree ts_parser__lex(
   uint32_t external_scanner_state_len = 0;
   bool external_scanner_state_changed = false;
   ts_lexer_reset(&self->lexer, start_position);
+  self->lexer.advance_count = 0;
 
   for (;;) {
     bool found_token = false;
@@ -537,15 +584,16 @@ static Subtree ts_parser__lex(
 
     if (lex_mode.external_lex_state != 0) {
       LOG(
-        "lex_external state:%d, row:%u, column:%u",
+        "lex_external state:%d, row:%u, column:%u, byte:%u",
         lex_mode.external_lex_state,
         current_position.extent.row,
-        current_position.extent.column
+        current_position.extent.column,
+        current_position.bytes
       );
       ts_lexer_start(&self->lexer);
       ts_parser__external_scanner_deserialize(self, external_token);
       found_token = ts_parser__external_scanner_scan(self, lex_mode.external_lex_state);
-      if (self->has_scanner_error) return NULL_SUBTREE;
+      if (self->has_scanner_error || self->lexer.canceled) return NULL_SUBTREE;
       ts_lexer_finish(&self->lexer, &lookahead_end_byte);
 
       if (found_token) {
@@ -591,13 +639,15 @@ static Subtree ts_parser__lex(
     }
 
     LOG(
-      "lex_internal state:%d, row:%u, column:%u",
+      "lex_internal state:%d, row:%u, column:%u, byte:%u",
       lex_mode.lex_state,
       current_position.extent.row,
-      current_position.extent.column
+      current_position.extent.column,
+      current_position.bytes
     );
     ts_lexer_start(&self->lexer);
     found_token = ts_parser__call_main_lex_fn(self, lex_mode);
+    if (self->lexer.canceled) return NULL_SUBTREE;
     ts_lexer_finish(&self->lexer, &lookahead_end_byte);
     if (found_token) break;
 
@@ -622,6 +672,7 @@ static Subtree ts_parser__lex(
         break;
       }
       self->lexer.data.advance(&self->lexer.data, false);
+      if (self->lexer.canceled) return NULL_SUBTREE;
     }
 
     error_end_position = self->lexer.current_position;
@@ -644,18 +695,26 @@ static Subtree ts_parser__lex(
   } else {
     bool is_keyword = false;
     TSSymbol symbol = self->lexer.data.result_symbol;
+    TSStateId keyword_lex_state;
     Length padding = length_sub(self->lexer.token_start_position, start_position);
     Length size = length_sub(self->lexer.token_end_position, self->lexer.token_start_position);
     uint32_t lookahead_bytes = lookahead_end_byte - self->lexer.token_end_position.bytes;
 
     if (found_external_token) {
       symbol = self->language->external_scanner.symbol_map[symbol];
-    } else if (symbol == self->language->keyword_capture_token && symbol != 0) {
+    } else if (ts_language_keyword_lex_state(self->language, symbol, &keyword_lex_state)) {
       uint32_t end_byte = self->lexer.token_end_position.bytes;
+      LOG(
+        "lex_keyword state:%d, row:%u, column:%u, byte:%u",
+        keyword_lex_state,
+        self->lexer.token_start_position.extent.row,
+        self->lexer.token_start_position.extent.column,
+        self->lexer.token_start_position.bytes
+      );
       ts_lexer_reset(&self->lexer, self->lexer.token_start_position);
       ts_lexer_start(&self->lexer);
 
-      is_keyword = ts_parser__call_keyword_lex_fn(self);
+      is_keyword = ts_parser__call_keyword_lex_fn(self, keyword_lex_state);
 
       if (
         is_keyword &&
@@ -1555,6 +1614,13 @@ static bool ts_parser__check_progress(TSParser *self, Subtree *lookahead, const
   return true;
 }
 
+// The lexer calls this function periodically while it advances, so that a long
+// run of the lex function or the external scanner can be canceled as well.
+static bool ts_parser__check_lexer_progress(void *payload, uint32_t position) {
+  TSParser *self = payload;
+  return ts_parser__check_progress(self, NULL, &position, OP_COUNT_PER_PARSER_TIMEOUT_CHECK);
+}
+
 static bool ts_parser__advance(
   TSParser *self,
   StackVersion version,
@@ -1590,7 +1656,7 @@ static bool ts_parser__advance(
     if (needs_lex) {
       needs_lex = false;
       lookahead = ts_parser__lex(self, version, state);
-      if (self->has_scanner_error) return false;
+      if (self->has_scanner_error || self->lexer.canceled) return false;
 
       if (lookahead.ptr) {
         ts_parser__set_cached_token(self, position, last_external_token, lookahead);
@@ -1611,12 +1677,15 @@ static bool ts_parser__advance(
       return false;
     }
 
+    self->stats.state_count++;
+
     // Process each parse action for the current lookahead token in
     // the current state. If there are multiple actions, then this is
     // an ambiguous state. REDUCE actions always create a new stack
     // version, whereas SHIFT actions update the existing stack version
     // and terminate this loop.
     StackVersion last_reduction_version = STACK_VERSION_NONE;
+    uint32_t reduction_version_count = 0;
     for (uint32_t i = 0; i < table_entry.action_count; i++) {
       TSParseAction action = table_entry.actions[i];
 
@@ -1637,6 +1706,13 @@ static bool ts_parser__advance(
             next_state = ts_language_next_state(self->language, state, ts_subtree_symbol(lookahead));
           }
 
+          // The versions created by the reductions of an ambiguous state
+          // continue alongside the shifted one.
+          self->stats.stack_split_count += reduction_version_count;
+          if (did_reuse) {
+            self->stats.reused_node_count++;
+            self->stats.reused_byte_count += ts_subtree_total_bytes(lookahead);
+          }
           ts_parser__shift(self, version, next_state, lookahead, action.shift.extra);
           if (did_reuse) reusable_node_advance(&self->reusable_node);
           return true;
@@ -1653,6 +1729,7 @@ static bool ts_parser__advance(
           );
           if (reduction_version != STACK_VERSION_NONE) {
             last_reduction_version = reduction_version;
+            reduction_version_count++;
           }
           break;
         }
@@ -1679,6 +1756,9 @@ static bool ts_parser__advance(
     // with one of the stack versions created by a reduction, and continue
     // processing this version of the stack with the same lookahead symbol.
     if (last_reduction_version != STACK_VERSION_NONE) {
+      // One of the new versions takes the place of the current version, and
+      // the others are splits of the stack.
+      self->stats.stack_split_count += reduction_version_count - 1;
       ts_stack_renumber_version(self->stack, last_reduction_version, version);
       LOG_STACK();
       state = ts_stack_state(self->stack, version);
@@ -1709,29 +1789,33 @@ static bool ts_parser__advance(
       return true;
     }
 
-    // If the current lookahead token is a keyword that is not valid, but the
-    // default word token *is* valid, then treat the lookahead token as the word
+    // If the current lookahead token is a keyword that is not valid, but a
+    // word token *is* valid, then treat the lookahead token as the word
     // token instead.
     if (
       ts_subtree_is_keyword(lookahead) &&
-      ts_subtree_symbol(lookahead) != self->language->keyword_capture_token &&
+      !ts_language_is_keyword_capture_token(self->language, ts_subtree_symbol(lookahead)) &&
       !ts_language_is_reserved_word(self->language, state, ts_subtree_symbol(lookahead))
     ) {
-      ts_language_table_entry(
-        self->language,
-        state,
-        self->language->keyword_capture_token,
-        &table_entry
-      );
-      if (table_entry.action_count > 0) {
+      TSSymbol word_token = 0;
+      uint16_t word_token_count = ts_language_keyword_capture_token_count(self->language);
+      for (uint16_t i = 0; i < word_token_count; i++) {
+        TSSymbol candidate = ts_language_keyword_capture_token(self->language, i);
+        ts_language_table_entry(self->language, state, candidate, &table_entry);
+        if (table_entry.action_count > 0) {
+          word_token = candidate;
+          break;
+        }
+      }
+      if (word_token != 0) {
         LOG(
           "switch from_keyword:%s, to_word_token:%s",
           TREE_NAME(lookahead),
-          SYM_NAME(self->language->keyword_capture_token)
+          SYM_NAME(word_token)
         );
 
         MutableSubtree mutable_lookahead = ts_subtree_make_mut(&self->tree_pool, lookahead);
-        ts_subtree_set_symbol(&mutable_lookahead, self->language->keyword_capture_token, self->language);
+        ts_subtree_set_symbol(&mutable_lookahead, word_token, self->language);
         lookahead = ts_subtree_from_mut(mutable_lookahead);
         continue;
       }
@@ -1759,6 +1843,14 @@ static bool ts_parser__advance(
   }
 }
 
+// Versions that were created while recovering from an error are merged too, so
+// a merge is only counted while there is a split that it could have rejoined.
+static inline void ts_parser__record_merge(TSParser *self) {
+  if (self->stats.stack_merge_count < self->stats.stack_split_count) {
+    self->stats.stack_merge_count++;
+  }
+}
+
 static unsigned ts_parser__condense_stack(TSParser *self) {
   bool made_changes = false;
   unsigned min_error_cost = UINT_MAX;
@@ -1794,6 +1886,7 @@ static unsigned ts_parser__condense_stack(TSParser *self) {
         case ErrorComparisonPreferLeft:
         case ErrorComparisonNone:
           if (ts_stack_merge(self->stack, j, i)) {
+            ts_parser__record_merge(self);
             made_changes = true;
             i--;
             j = i;
@@ -1803,6 +1896,7 @@ static unsigned ts_parser__condense_stack(TSParser *self) {
         case ErrorComparisonPreferRight:
           made_changes = true;
           if (ts_stack_merge(self->stack, j, i)) {
+            ts_parser__record_merge(self);
             i--;
             j = i;
           } else {
@@ -1916,6 +2010,29 @@ static bool ts_parser__balance_subtree(TSParser *self) {
   return true;
 }
 
+// Measure the memory used by the heap-allocated subtrees of a tree.
+static uint64_t ts_parser__tree_byte_count(Subtree tree) {
+  uint64_t result = 0;
+  SubtreeArray stack = array_new();
+  array_push(&stack, tree);
+  while (stack.size > 0) {
+    Subtree subtree = array_pop(&stack);
+    if (subtree.data.is_inline) continue;
+    uint32_t child_count = ts_subtree_child_count(subtree);
+    result += ts_subtree_alloc_size(child_count);
+    if (child_count == 0 && subtree.ptr->has_external_tokens) {
+      const ExternalScannerState *state = &subtree.ptr->external_scanner_state;
+      if (state->length > sizeof(state->short_data)) result += state->length;
+    }
+    Subtree *children = ts_subtree_children(subtree);
+    for (uint32_t i = 0; i < child_count; i++) {
+      array_push(&stack, children[i]);
+    }
+  }
+  array_delete(&stack);
+  return result;
+}
+
 static bool ts_parser_has_outstanding_parse(TSParser *self) {
   return (
     self->canceled_balancing ||
@@ -1930,6 +2047,8 @@ static bool ts_parser_has_outstanding_parse(TSParser *self) {
 TSParser *ts_parser_new(void) {
   TSParser *self = ts_calloc(1, sizeof(TSParser));
   ts_lexer_init(&self->lexer);
+  self->lexer.progress_callback = ts_parser__check_lexer_progress;
+  self->lexer.progress_payload = self;
   array_init(&self->reduce_actions);
   array_reserve(&self->reduce_actions, 4);
   self->tree_pool = ts_subtree_pool_new(32);
@@ -1937,12 +2056,14 @@ TSParser *ts_parser_new(void) {
   self->finished_tree = NULL_SUBTREE;
   self->reusable_node = reusable_node_new();
   self->dot_graph_file = NULL;
+  self->dot_graph_writer = (TSDotGraphWriter) {NULL, NULL};
   self->cancellation_flag = NULL;
   self->timeout_duration = 0;
   self->language = NULL;
   self->has_scanner_error = false;
   self->has_error = false;
   self->canceled_balancing = false;
+  self->record_stats = false;
   self->external_scanner_payload = NULL;
   self->end_clock = clock_null();
   self->operation_count = 0;
@@ -2018,6 +2139,7 @@ void ts_parser_print_dot_graphs(TSParser *self, int fd) {
   if (self->dot_graph_file) {
     fclose(self->dot_graph_file);
   }
+  self->dot_graph_writer = (TSDotGraphWriter) {NULL, NULL};
 
   if (fd >= 0) {
     #ifdef _WIN32
@@ -2030,6 +2152,28 @@ void ts_parser_print_dot_graphs(TSParser *self, int fd) {
   }
 }
 
+void ts_parser_set_dot_graph_writer(TSParser *self, TSDotGraphWriter writer) {
+  ts_parser_print_dot_graphs(self, -1);
+  if (writer.write) {
+    self->dot_graph_writer = writer;
+    #ifndef __wasi__
+    self->dot_graph_file = tmpfile();
+    #endif
+  }
+}
+
+TSDotGraphWriter ts_parser_dot_graph_writer(const TSParser *self) {
+  return self->dot_graph_writer;
+}
+
+void ts_parser_set_record_stats(TSParser *self, bool record_stats) {
+  self->record_stats = record_stats;
+}
+
+TSParseStats ts_parser_parse_stats(const TSParser *self) {
+  return self->stats;
+}
+
 const size_t *ts_parser_cancellation_flag(const TSParser *self) {
   return (const size_t *)self->cancellation_flag;
 }
@@ -2112,6 +2256,7 @@ TSTree *ts_parser_parse(
     LOG("resume_parsing");
     if (self->canceled_balancing) goto balance;
   } else {
+    self->stats = (TSParseStats) {0};
     ts_parser__external_scanner_create(self);
     if (self->has_scanner_error) goto exit;
 
@@ -2147,12 +2292,13 @@ TSTree *ts_parser_parse(
       bool allow_node_reuse = version_count == 1;
       while (ts_stack_is_active(self->stack, version)) {
         LOG(
-          "process version:%u, version_count:%u, state:%d, row:%u, col:%u",
+          "process version:%u, version_count:%u, state:%d, row:%u, col:%u, byte:%u",
           version,
           ts_stack_version_count(self->stack),
           ts_stack_state(self->stack, version),
           ts_stack_position(self->stack, version).extent.row,
-          ts_stack_position(self->stack, version).extent.column
+          ts_stack_position(self->stack, version).extent.column,
+          ts_stack_position(self->stack, version).bytes
         );
 
         if (!ts_parser__advance(self, version, allow_node_reuse)) {
@@ -2202,6 +2348,9 @@ balance:
   self->canceled_balancing = false;
   LOG("done");
   LOG_TREE(self->finished_tree);
+  if (self->record_stats) {
+    self->stats.tree_byte_count = ts_parser__tree_byte_count(self->finished_tree);
+  }
 
   result = ts_tree_new(
     self->finished_tree,
@@ -307,6 +307,7 @@ struct TSQueryCursor {
   TSTreeCursor cursor;
   Array(QueryState) states;
   Array(QueryState) finished_states;
+  Array(bool) enabled_patterns;
   CaptureListPool capture_list_pool;
   uint32_t depth;
   uint32_t max_start_depth;
@@ -319,11 +320,15 @@ struct TSQueryCursor {
   TSDuration timeout_duration;
   const TSQueryCursorOptions *query_options;
   TSQueryCursorState query_state;
+  TSQueryCursorStats stats;
+  uint64_t step_count;
+  uint64_t step_limit;
   unsigned operation_count;
   bool on_visible_node;
   bool ascending;
   bool halted;
   bool did_exceed_match_limit;
+  bool did_exceed_step_limit;
 };
 
 static const TSQueryError PARENT_DONE = -1;
@@ -451,12 +456,18 @@ static CaptureList *capture_list_pool_get_mut(CaptureListPool *self, uint16_t id
 }
 
 static bool capture_list_pool_is_empty(const CaptureListPool *self) {
-  // The capture list pool is empty if all allocated lists are in use, and we
-  // have reached the maximum allowed number of allocated lists.
-  return self->free_capture_list_count == 0 && self->list.size >= self->max_capture_list_count;
+  // The capture list pool is empty if the maximum allowed number of lists are
+  // in use.
+  return self->list.size - self->free_capture_list_count >= self->max_capture_list_count;
 }
 
 static uint16_t capture_list_pool_acquire(CaptureListPool *self) {
+  // Don't put more capture lists in use than the requested maximum, even if
+  // more than that were allocated while the maximum was higher.
+  if (self->list.size - self->free_capture_list_count >= self->max_capture_list_count) {
+    return NONE;
+  }
+
   // First see if any already allocated capture list is currently unused.
   if (self->free_capture_list_count > 0) {
     for (uint16_t i = 0; i < (uint16_t)self->list.size; i++) {
@@ -468,12 +479,8 @@ static uint16_t capture_list_pool_acquire(CaptureListPool *self) {
     }
   }
 
-  // Otherwise allocate and initialize a new capture list, as long as that
-  // doesn't put us over the requested maximum.
+  // Otherwise allocate and initialize a new capture list.
   uint32_t i = self->list.size;
-  if (i >= self->max_capture_list_count) {
-    return NONE;
-  }
   CaptureList list;
   array_init(&list);
   array_push(&self->list, list);
@@ -3010,6 +3017,22 @@ bool ts_query_is_pattern_non_local(
   }
 }
 
+uint32_t ts_query_pattern_depth(
+  const TSQuery *self,
+  uint32_t pattern_index
+) {
+  if (pattern_index >= self->patterns.size) return 0;
+  const QueryPattern *pattern = &self->patterns.contents[pattern_index];
+  uint32_t depth = 0;
+  for (unsigned i = 0; i < pattern->steps.length; i++) {
+    const QueryStep *step = &self->steps.contents[pattern->steps.offset + i];
+    if (step->depth != PATTERN_DONE_MARKER && step->depth > depth) {
+      depth = step->depth;
+    }
+  }
+  return depth;
+}
+
 bool ts_query_is_pattern_guaranteed_at_step(
   const TSQuery *self,
   uint32_t byte_offset
@@ -3072,6 +3095,606 @@ void ts_query_disable_pattern(
   }
 }
 
+/**********************
+ * Query serialization
+ **********************/
+
+// The binary format of a serialized query.
+//
+// All integers are little-endian. The format starts with the bytes `TSQY`,
+// followed by the version of the format and a checksum of the rest of the
+// bytes, and by the symbol count, the field count, and a checksum of the
+// symbol and field names of the language that the query was compiled for, all
+// as `uint32_t`s.
+//
+// The rest of the format consists of the query's arrays, in the order in which
+// they are declared in `TSQuery`. Each array starts with its size as a
+// `uint32_t`, followed by its elements:
+// * A symbol table's elements are names, each with its length as a `uint32_t`
+//   followed by its bytes.
+// * A capture quantifier list's elements are bytes.
+// * A step is its symbol, supertype symbol, field id, capture ids, depth,
+//   alternative index, negated field list id, and flags, as `uint16_t`s.
+// * A pattern map entry is its step index and pattern index as `uint16_t`s,
+//   followed by whether it is rooted as a byte.
+// * A predicate step is its type as a byte and its value id as a `uint32_t`.
+// * A pattern is the offset and the length of its steps and its predicate
+//   steps, its start byte, and its end byte, as `uint32_t`s, followed by
+//   whether it is non-local as a byte.
+// * A step offset is its byte offset as a `uint32_t` and its step index as a
+//   `uint16_t`.
+// * Negated field ids and repetition symbols are `uint16_t`s.
+//
+// The format ends with the number of patterns with a wildcard root as a
+// `uint16_t`.
+
+#define TS_QUERY_SERIALIZATION_MAGIC "TSQY"
+#define TS_QUERY_SERIALIZATION_VERSION 1
+
+typedef enum {
+  QueryStepFlagIsNamed = 1 << 0,
+  QueryStepFlagIsImmediate = 1 << 1,
+  QueryStepFlagIsLastChild = 1 << 2,
+  QueryStepFlagIsPassThrough = 1 << 3,
+  QueryStepFlagIsDeadEnd = 1 << 4,
+  QueryStepFlagAlternativeIsImmediate = 1 << 5,
+  QueryStepFlagContainsCaptures = 1 << 6,
+  QueryStepFlagRootPatternGuaranteed = 1 << 7,
+  QueryStepFlagParentPatternGuaranteed = 1 << 8,
+  QueryStepFlagIsMissing = 1 << 9,
+  QueryStepFlagAll = (1 << 10) - 1,
+} QueryStepFlag;
+
+typedef Array(uint8_t) QueryByteArray;
+
+typedef struct {
+  const uint8_t *data;
+  uint32_t length;
+  uint32_t offset;
+} QueryByteReader;
+
+#define TS_QUERY_SERIALIZATION_CHECKSUM_OFFSET 8
+#define TS_QUERY_SERIALIZATION_CHECKSUM_SEED 2166136261u
+
+// Add the given bytes to an FNV-1a checksum.
+static uint32_t ts_query_serialization__checksum(uint32_t hash, const uint8_t *bytes, uint32_t length) {
+  for (uint32_t i = 0; i < length; i++) {
+    hash = (hash ^ bytes[i]) * 16777619u;
+  }
+  return hash;
+}
+
+// A checksum of the names and kinds of a language's symbols and the names of
+// its fields, which tells apart the languages that a query's ids refer to.
+static uint32_t ts_query_serialization__language_checksum(const TSLanguage *language) {
+  uint32_t hash = TS_QUERY_SERIALIZATION_CHECKSUM_SEED;
+  uint32_t symbol_count = ts_language_symbol_count(language);
+  for (TSSymbol symbol = 0; symbol < symbol_count; symbol++) {
+    TSSymbolMetadata metadata = ts_language_symbol_metadata(language, symbol);
+    const char *name = ts_language_symbol_name(language, symbol);
+    uint8_t kind = metadata.visible | metadata.named << 1;
+    if (name) hash = ts_query_serialization__checksum(hash, (const uint8_t *)name, strlen(name) + 1);
+    hash = ts_query_serialization__checksum(hash, &kind, 1);
+  }
+  for (TSFieldId field_id = 1; field_id <= language->field_count; field_id++) {
+    const char *name = language->field_names[field_id];
+    if (name) hash = ts_query_serialization__checksum(hash, (const uint8_t *)name, strlen(name) + 1);
+  }
+  return hash;
+}
+
+// Serialization
+
+static void ts_query_serialization__write_u8(QueryByteArray *self, uint8_t value) {
+  array_push(self, value);
+}
+
+static void ts_query_serialization__write_u16(QueryByteArray *self, uint16_t value) {
+  array_push(self, (uint8_t)value);
+  array_push(self, (uint8_t)(value >> 8));
+}
+
+static void ts_query_serialization__write_u32(QueryByteArray *self, uint32_t value) {
+  for (unsigned i = 0; i < 4; i++) {
+    array_push(self, (uint8_t)(value >> (8 * i)));
+  }
+}
+
+static void ts_query_serialization__write_symbol_table(QueryByteArray *self, const SymbolTable *table) {
+  ts_query_serialization__write_u32(self, table->slices.size);
+  for (unsigned i = 0; i < table->slices.size; i++) {
+    Slice slice = table->slices.contents[i];
+    ts_query_serialization__write_u32(self, slice.length);
+    array_extend(self, slice.length, (const uint8_t *)&table->characters.contents[slice.offset]);
+  }
+}
+
+static void ts_query_serialization__write_step(QueryByteArray *self, const QueryStep *step) {
+  uint16_t flags = 0;
+  if (step->is_named) flags |= QueryStepFlagIsNamed;
+  if (step->is_immediate) flags |= QueryStepFlagIsImmediate;
+  if (step->is_last_child) flags |= QueryStepFlagIsLastChild;
+  if (step->is_pass_through) flags |= QueryStepFlagIsPassThrough;
+  if (step->is_dead_end) flags |= QueryStepFlagIsDeadEnd;
+  if (step->alternative_is_immediate) flags |= QueryStepFlagAlternativeIsImmediate;
+  if (step->contains_captures) flags |= QueryStepFlagContainsCaptures;
+  if (step->root_pattern_guaranteed) flags |= QueryStepFlagRootPatternGuaranteed;
+  if (step->parent_pattern_guaranteed) flags |= QueryStepFlagParentPatternGuaranteed;
+  if (step->is_missing) flags |= QueryStepFlagIsMissing;
+
+  ts_query_serialization__write_u16(self, step->symbol);
+  ts_query_serialization__write_u16(self, step->supertype_symbol);
+  ts_query_serialization__write_u16(self, step->field);
+  for (unsigned i = 0; i < MAX_STEP_CAPTURE_COUNT; i++) {
+    ts_query_serialization__write_u16(self, step->capture_ids[i]);
+  }
+  ts_query_serialization__write_u16(self, step->depth);
+  ts_query_serialization__write_u16(self, step->alternative_index);
+  ts_query_serialization__write_u16(self, step->negated_field_list_id);
+  ts_query_serialization__write_u16(self, flags);
+}
+
+char *ts_query_serialize(const TSQuery *self, uint32_t *length) {
+  QueryByteArray buffer = array_new();
+  array_extend(&buffer, 4, TS_QUERY_SERIALIZATION_MAGIC);
+  ts_query_serialization__write_u32(&buffer, TS_QUERY_SERIALIZATION_VERSION);
+
+  // Reserve space for the checksum, which is written once the rest of the bytes are.
+  ts_query_serialization__write_u32(&buffer, 0);
+  ts_query_serialization__write_u32(&buffer, ts_language_symbol_count(self->language));
+  ts_query_serialization__write_u32(&buffer, self->language->field_count);
+  ts_query_serialization__write_u32(&buffer, ts_query_serialization__language_checksum(self->language));
+
+  ts_query_serialization__write_symbol_table(&buffer, &self->captures);
+  ts_query_serialization__write_symbol_table(&buffer, &self->predicate_values);
+
+  ts_query_serialization__write_u32(&buffer, self->capture_quantifiers.size);
+  for (unsigned i = 0; i < self->capture_quantifiers.size; i++) {
+    CaptureQuantifiers *capture_quantifiers = &self->capture_quantifiers.contents[i];
+    ts_query_serialization__write_u32(&buffer, capture_quantifiers->size);
+    array_extend(&buffer, capture_quantifiers->size, capture_quantifiers->contents);
+  }
+
+  ts_query_serialization__write_u32(&buffer, self->steps.size);
+  for (unsigned i = 0; i < self->steps.size; i++) {
+    ts_query_serialization__write_step(&buffer, &self->steps.contents[i]);
+  }
+
+  ts_query_serialization__write_u32(&buffer, self->pattern_map.size);
+  for (unsigned i = 0; i < self->pattern_map.size; i++) {
+    PatternEntry *entry = &self->pattern_map.contents[i];
+    ts_query_serialization__write_u16(&buffer, entry->step_index);
+    ts_query_serialization__write_u16(&buffer, entry->pattern_index);
+    ts_query_serialization__write_u8(&buffer, entry->is_rooted);
+  }
+
+  ts_query_serialization__write_u32(&buffer, self->predicate_steps.size);
+  for (unsigned i = 0; i < self->predicate_steps.size; i++) {
+    TSQueryPredicateStep *step = &self->predicate_steps.contents[i];
+    ts_query_serialization__write_u8(&buffer, (uint8_t)step->type);
+    ts_query_serialization__write_u32(&buffer, step->value_id);
+  }
+
+  ts_query_serialization__write_u32(&buffer, self->patterns.size);
+  for (unsigned i = 0; i < self->patterns.size; i++) {
+    QueryPattern *pattern = &self->patterns.contents[i];
+    ts_query_serialization__write_u32(&buffer, pattern->steps.offset);
+    ts_query_serialization__write_u32(&buffer, pattern->steps.length);
+    ts_query_serialization__write_u32(&buffer, pattern->predicate_steps.offset);
+    ts_query_serialization__write_u32(&buffer, pattern->predicate_steps.length);
+    ts_query_serialization__write_u32(&buffer, pattern->start_byte);
+    ts_query_serialization__write_u32(&buffer, pattern->end_byte);
+    ts_query_serialization__write_u8(&buffer, pattern->is_non_local);
+  }
+
+  ts_query_serialization__write_u32(&buffer, self->step_offsets.size);
+  for (unsigned i = 0; i < self->step_offsets.size; i++) {
+    StepOffset *step_offset = &self->step_offsets.contents[i];
+    ts_query_serialization__write_u32(&buffer, step_offset->byte_offset);
+    ts_query_serialization__write_u16(&buffer, step_offset->step_index);
+  }
+
+  ts_query_serialization__write_u32(&buffer, self->negated_fields.size);
+  for (unsigned i = 0; i < self->negated_fields.size; i++) {
+    ts_query_serialization__write_u16(&buffer, self->negated_fields.contents[i]);
+  }
+
+  ts_query_serialization__write_u32(&buffer, self->repeat_symbols_with_rootless_patterns.size);
+  for (unsigned i = 0; i < self->repeat_symbols_with_rootless_patterns.size; i++) {
+    ts_query_serialization__write_u16(&buffer, self->repeat_symbols_with_rootless_patterns.contents[i]);
+  }
+
+  ts_query_serialization__write_u16(&buffer, self->wildcard_root_pattern_count);
+
+  uint32_t checksum_end = TS_QUERY_SERIALIZATION_CHECKSUM_OFFSET + 4;
+  uint32_t checksum = ts_query_serialization__checksum(
+    TS_QUERY_SERIALIZATION_CHECKSUM_SEED,
+    &buffer.contents[checksum_end],
+    buffer.size - checksum_end
+  );
+  for (unsigned i = 0; i < 4; i++) {
+    buffer.contents[TS_QUERY_SERIALIZATION_CHECKSUM_OFFSET + i] = (uint8_t)(checksum >> (8 * i));
+  }
+
+  *length = buffer.size;
+  return (char *)buffer.contents;
+}
+
+// Deserialization
+
+static bool ts_query_serialization__read_u8(QueryByteReader *self, uint8_t *value) {
+  if (self->length - self->offset < 1) return false;
+  *value = self->data[self->offset];
+  self->offset += 1;
+  return true;
+}
+
+static bool ts_query_serialization__read_u16(QueryByteReader *self, uint16_t *value) {
+  if (self->length - self->offset < 2) return false;
+  const uint8_t *bytes = &self->data[self->offset];
+  *value = (uint16_t)(bytes[0] | bytes[1] << 8);
+  self->offset += 2;
+  return true;
+}
+
+static bool ts_query_serialization__read_u32(QueryByteReader *self, uint32_t *value) {
+  if (self->length - self->offset < 4) return false;
+  const uint8_t *bytes = &self->data[self->offset];
+  *value = 0;
+  for (unsigned i = 0; i < 4; i++) {
+    *value |= (uint32_t)bytes[i] << (8 * i);
+  }
+  self->offset += 4;
+  return true;
+}
+
+// Read the size of an array whose elements take up at least `element_size`
+// bytes each, so that a corrupted size can't cause a huge allocation.
+static bool ts_query_serialization__read_size(
+  QueryByteReader *self,
+  uint32_t element_size,
+  uint32_t *size
+) {
+  return
+    ts_query_serialization__read_u32(self, size) &&
+    (uint64_t)*size * element_size <= self->length - self->offset;
+}
+
+static bool ts_query_serialization__read_symbol_table(QueryByteReader *self, SymbolTable *table) {
+  uint32_t size;
+  if (!ts_query_serialization__read_size(self, 4, &size)) return false;
+  for (uint32_t i = 0; i < size; i++) {
+    uint32_t length;
+    if (
+      !ts_query_serialization__read_u32(self, &length) ||
+      self->length - self->offset < length
+    ) return false;
+    Slice slice = {.offset = table->characters.size, .length = length};
+    array_extend(&table->characters, length, (const char *)&self->data[self->offset]);
+    array_push(&table->characters, 0);
+    array_push(&table->slices, slice);
+    self->offset += length;
+  }
+  return true;
+}
+
+static bool ts_query_serialization__read_step(QueryByteReader *self, QueryStep *step) {
+  uint16_t flags;
+  bool result =
+    ts_query_serialization__read_u16(self, &step->symbol) &&
+    ts_query_serialization__read_u16(self, &step->supertype_symbol) &&
+    ts_query_serialization__read_u16(self, &step->field);
+  for (unsigned i = 0; result && i < MAX_STEP_CAPTURE_COUNT; i++) {
+    result = ts_query_serialization__read_u16(self, &step->capture_ids[i]);
+  }
+  if (
+    !result ||
+    !ts_query_serialization__read_u16(self, &step->depth) ||
+    !ts_query_serialization__read_u16(self, &step->alternative_index) ||
+    !ts_query_serialization__read_u16(self, &step->negated_field_list_id) ||
+    !ts_query_serialization__read_u16(self, &flags) ||
+    (flags & ~QueryStepFlagAll)
+  ) return false;
+
+  step->is_named = flags & QueryStepFlagIsNamed;
+  step->is_immediate = flags & QueryStepFlagIsImmediate;
+  step->is_last_child = flags & QueryStepFlagIsLastChild;
+  step->is_pass_through = flags & QueryStepFlagIsPassThrough;
+  step->is_dead_end = flags & QueryStepFlagIsDeadEnd;
+  step->alternative_is_immediate = flags & QueryStepFlagAlternativeIsImmediate;
+  step->contains_captures = flags & QueryStepFlagContainsCaptures;
+  step->root_pattern_guaranteed = flags & QueryStepFlagRootPatternGuaranteed;
+  step->parent_pattern_guaranteed = flags & QueryStepFlagParentPatternGuaranteed;
+  step->is_missing = flags & QueryStepFlagIsMissing;
+  return true;
+}
+
+static bool ts_query_serialization__is_valid_symbol(const TSLanguage *language, TSSymbol symbol) {
+  return symbol < ts_language_symbol_count(language) || symbol == ts_builtin_sym_error;
+}
+
+static TSQueryDeserializeError ts_query_serialization__read_arrays(QueryByteReader *reader, TSQuery *self) {
+  const TSLanguage *language = self->language;
+  if (
+    !ts_query_serialization__read_symbol_table(reader, &self->captures) ||
+    !ts_query_serialization__read_symbol_table(reader, &self->predicate_values)
+  ) return TSQueryDeserializeErrorFormat;
+
+  uint32_t size;
+  if (!ts_query_serialization__read_size(reader, 4, &size)) return TSQueryDeserializeErrorFormat;
+  for (uint32_t i = 0; i < size; i++) {
+    uint32_t quantifier_count;
+    if (!ts_query_serialization__read_size(reader, 1, &quantifier_count)) return TSQueryDeserializeErrorFormat;
+    CaptureQuantifiers capture_quantifiers = capture_quantifiers_new();
+    array_extend(&capture_quantifiers, quantifier_count, &reader->data[reader->offset]);
+    array_push(&self->capture_quantifiers, capture_quantifiers);
+    reader->offset += quantifier_count;
+    for (uint32_t j = 0; j < quantifier_count; j++) {
+      if (capture_quantifiers.contents[j] > TSQuantifierOneOrMore) return TSQueryDeserializeErrorFormat;
+    }
+  }
+
+  if (!ts_query_serialization__read_size(reader, 20, &size) || size > NONE) {
+    return TSQueryDeserializeErrorFormat;
+  }
+  array_grow_by(&self->steps, size);
+  for (uint32_t i = 0; i < size; i++) {
+    if (!ts_query_serialization__read_step(reader, &self->steps.contents[i])) {
+      return TSQueryDeserializeErrorFormat;
+    }
+  }
+
+  if (!ts_query_serialization__read_size(reader, 5, &size)) return TSQueryDeserializeErrorFormat;
+  array_grow_by(&self->pattern_map, size);
+  for (uint32_t i = 0; i < size; i++) {
+    PatternEntry *entry = &self->pattern_map.contents[i];
+    uint8_t is_rooted;
+    if (
+      !ts_query_serialization__read_u16(reader, &entry->step_index) ||
+      !ts_query_serialization__read_u16(reader, &entry->pattern_index) ||
+      !ts_query_serialization__read_u8(reader, &is_rooted)
+    ) return TSQueryDeserializeErrorFormat;
+    entry->is_rooted = is_rooted;
+  }
+
+  if (!ts_query_serialization__read_size(reader, 5, &size)) return TSQueryDeserializeErrorFormat;
+  array_grow_by(&self->predicate_steps, size);
+  for (uint32_t i = 0; i < size; i++) {
+    TSQueryPredicateStep *step = &self->predicate_steps.contents[i];
+    uint8_t type;
+    if (
+      !ts_query_serialization__read_u8(reader, &type) ||
+      !ts_query_serialization__read_u32(reader, &step->value_id)
+    ) return TSQueryDeserializeErrorFormat;
+    step->type = (TSQueryPredicateStepType)type;
+  }
+
+  if (!ts_query_serialization__read_size(reader, 25, &size)) return TSQueryDeserializeErrorFormat;
+  array_grow_by(&self->patterns, size);
+  for (uint32_t i = 0; i < size; i++) {
+    QueryPattern *pattern = &self->patterns.contents[i];
+    uint8_t is_non_local;
+    if (
+      !ts_query_serialization__read_u32(reader, &pattern->steps.offset) ||
+      !ts_query_serialization__read_u32(reader, &pattern->steps.length) ||
+      !ts_query_serialization__read_u32(reader, &pattern->predicate_steps.offset) ||
+      !ts_query_serialization__read_u32(reader, &pattern->predicate_steps.length) ||
+      !ts_query_serialization__read_u32(reader, &pattern->start_byte) ||
+      !ts_query_serialization__read_u32(reader, &pattern->end_byte) ||
+      !ts_query_serialization__read_u8(reader, &is_non_local)
+    ) return TSQueryDeserializeErrorFormat;
+    pattern->is_non_local = is_non_local;
+  }
+
+  if (!ts_query_serialization__read_size(reader, 6, &size)) return TSQueryDeserializeErrorFormat;
+  array_grow_by(&self->step_offsets, size);
+  for (uint32_t i = 0; i < size; i++) {
+    StepOffset *step_offset = &self->step_offsets.contents[i];
+    if (
+      !ts_query_serialization__read_u32(reader, &step_offset->byte_offset) ||
+      !ts_query_serialization__read_u16(reader, &step_offset->step_index)
+    ) return TSQueryDeserializeErrorFormat;
+  }
+
+  if (!ts_query_serialization__read_size(reader, 2, &size)) return TSQueryDeserializeErrorFormat;
+  array_grow_by(&self->negated_fields, size);
+  for (uint32_t i = 0; i < size; i++) {
+    if (!ts_query_serialization__read_u16(reader, &self->negated_fields.contents[i])) {
+      return TSQueryDeserializeErrorFormat;
+    }
+  }
+
+  if (!ts_query_serialization__read_size(reader, 2, &size)) return TSQueryDeserializeErrorFormat;
+  array_grow_by(&self->repeat_symbols_with_rootless_patterns, size);
+  for (uint32_t i = 0; i < size; i++) {
+    TSSymbol *symbol = &self->repeat_symbols_with_rootless_patterns.contents[i];
+    if (!ts_query_serialization__read_u16(reader, symbol)) return TSQueryDeserializeErrorFormat;
+    if (*symbol >= ts_language_symbol_count(language)) return TSQueryDeserializeErrorSymbol;
+  }
+
+  if (
+    !ts_query_serialization__read_u16(reader, &self->wildcard_root_pattern_count) ||
+    reader->offset != reader->length
+  ) return TSQueryDeserializeErrorFormat;
+  return TSQueryDeserializeErrorNone;
+}
+
+// Check that every id and index in the query refers to something that exists,
+// so that executing the query never reads past the end of one of its arrays.
+static TSQueryDeserializeError ts_query_serialization__validate(const TSQuery *self) {
+  const TSLanguage *language = self->language;
+  uint32_t step_count = self->steps.size;
+  uint32_t capture_count = self->captures.slices.size;
+
+  // Each pattern's steps end with a step that marks the end of the pattern,
+  // so the last step has to be one of those.
+  if (step_count == 0 || array_back(&self->steps)->depth != PATTERN_DONE_MARKER) {
+    return TSQueryDeserializeErrorFormat;
+  }
+  if (self->negated_fields.size == 0 || *array_back(&self->negated_fields) != 0) {
+    return TSQueryDeserializeErrorFormat;
+  }
+  for (unsigned i = 0; i < self->negated_fields.size; i++) {
+    if (self->negated_fields.contents[i] > language->field_count) return TSQueryDeserializeErrorField;
+  }
+
+  // A cursor finds the root of a pattern whose root is a wildcard by walking
+  // back from the pattern's second step, so every step below a root has to
+  // come after one.
+  bool has_root_step = false;
+  for (unsigned i = 0; i < step_count; i++) {
+    QueryStep *step = &self->steps.contents[i];
+    if (step->depth == 0 && !step->is_dead_end && !step->is_pass_through) {
+      has_root_step = true;
+    } else if (step->depth > 0 && step->depth != PATTERN_DONE_MARKER && !has_root_step) {
+      return TSQueryDeserializeErrorFormat;
+    }
+    if (
+      !ts_query_serialization__is_valid_symbol(language, step->symbol) ||
+      !ts_query_serialization__is_valid_symbol(language, step->supertype_symbol)
+    ) return TSQueryDeserializeErrorSymbol;
+    if (step->field > language->field_count) return TSQueryDeserializeErrorField;
+    if (
+      (step->alternative_index != NONE && step->alternative_index >= step_count) ||
+      step->negated_field_list_id >= self->negated_fields.size
+    ) return TSQueryDeserializeErrorFormat;
+    for (unsigned j = 0; j < MAX_STEP_CAPTURE_COUNT; j++) {
+      if (step->capture_ids[j] != NONE && step->capture_ids[j] >= capture_count) {
+        return TSQueryDeserializeErrorFormat;
+      }
+    }
+  }
+
+  uint32_t pattern_count = self->patterns.size;
+  if (self->capture_quantifiers.size != pattern_count) return TSQueryDeserializeErrorFormat;
+  for (unsigned i = 0; i < pattern_count; i++) {
+    QueryPattern *pattern = &self->patterns.contents[i];
+    if (
+      (uint64_t)pattern->steps.offset + pattern->steps.length > step_count ||
+      (uint64_t)pattern->predicate_steps.offset + pattern->predicate_steps.length > self->predicate_steps.size ||
+      pattern->start_byte > pattern->end_byte
+    ) return TSQueryDeserializeErrorFormat;
+  }
+
+  // The patterns with a wildcard root come first in the pattern map.
+  if (self->wildcard_root_pattern_count > self->pattern_map.size) return TSQueryDeserializeErrorFormat;
+  for (unsigned i = 0; i < self->pattern_map.size; i++) {
+    PatternEntry *entry = &self->pattern_map.contents[i];
+    // A pattern's first step can be followed by the step that ends it.
+    if (entry->step_index + 1u >= step_count || entry->pattern_index >= pattern_count) {
+      return TSQueryDeserializeErrorFormat;
+    }
+  }
+
+  for (unsigned i = 0; i < self->predicate_steps.size; i++) {
+    TSQueryPredicateStep *step = &self->predicate_steps.contents[i];
+    bool is_valid;
+    switch (step->type) {
+      case TSQueryPredicateStepTypeDone:
+        is_valid = step->value_id == 0;
+        break;
+      case TSQueryPredicateStepTypeCapture:
+        is_valid = step->value_id < capture_count;
+        break;
+      case TSQueryPredicateStepTypeString:
+        is_valid = step->value_id < self->predicate_values.slices.size;
+        break;
+      default:
+        is_valid = false;
+        break;
+    }
+    if (!is_valid) return TSQueryDeserializeErrorFormat;
+  }
+
+  for (unsigned i = 0; i < self->step_offsets.size; i++) {
+    if (self->step_offsets.contents[i].step_index >= step_count) return TSQueryDeserializeErrorFormat;
+  }
+  return TSQueryDeserializeErrorNone;
+}
+
+TSQuery *ts_query_deserialize(
+  const TSLanguage *language,
+  const char *bytes,
+  uint32_t length,
+  TSQueryDeserializeError *error
+) {
+  if (
+    !language ||
+    language->abi_version > TREE_SITTER_LANGUAGE_VERSION ||
+    language->abi_version < TREE_SITTER_MIN_COMPATIBLE_LANGUAGE_VERSION
+  ) {
+    *error = TSQueryDeserializeErrorLanguage;
+    return NULL;
+  }
+  if (length < 4 || memcmp(bytes, TS_QUERY_SERIALIZATION_MAGIC, 4) != 0) {
+    *error = TSQueryDeserializeErrorFormat;
+    return NULL;
+  }
+  QueryByteReader reader = {.data = (const uint8_t *)bytes, .length = length, .offset = 4};
+  uint32_t version, checksum, symbol_count, field_count, language_checksum;
+  if (!ts_query_serialization__read_u32(&reader, &version)) {
+    *error = TSQueryDeserializeErrorFormat;
+    return NULL;
+  }
+  if (version != TS_QUERY_SERIALIZATION_VERSION) {
+    *error = TSQueryDeserializeErrorVersion;
+    return NULL;
+  }
+  if (
+    !ts_query_serialization__read_u32(&reader, &checksum) ||
+    checksum != ts_query_serialization__checksum(
+      TS_QUERY_SERIALIZATION_CHECKSUM_SEED,
+      &reader.data[reader.offset],
+      reader.length - reader.offset
+    ) ||
+    !ts_query_serialization__read_u32(&reader, &symbol_count) ||
+    !ts_query_serialization__read_u32(&reader, &field_count) ||
+    !ts_query_serialization__read_u32(&reader, &language_checksum)
+  ) {
+    *error = TSQueryDeserializeErrorFormat;
+    return NULL;
+  }
+  if (symbol_count != ts_language_symbol_count(language)) {
+    *error = TSQueryDeserializeErrorSymbol;
+    return NULL;
+  }
+  if (field_count != language->field_count) {
+    *error = TSQueryDeserializeErrorField;
+    return NULL;
+  }
+  if (language_checksum != ts_query_serialization__language_checksum(language)) {
+    *error = TSQueryDeserializeErrorLanguage;
+    return NULL;
+  }
+
+  TSQuery *self = ts_malloc(sizeof(TSQuery));
+  *self = (TSQuery) {
+    .steps = array_new(),
+    .pattern_map = array_new(),
+    .captures = symbol_table_new(),
+    .capture_quantifiers = array_new(),
+    .predicate_values = symbol_table_new(),
+    .predicate_steps = array_new(),
+    .patterns = array_new(),
+    .step_offsets = array_new(),
+    .string_buffer = array_new(),
+    .negated_fields = array_new(),
+    .repeat_symbols_with_rootless_patterns = array_new(),
+    .wildcard_root_pattern_count = 0,
+    .language = ts_language_copy(language),
+  };
+
+  *error = ts_query_serialization__read_arrays(&reader, self);
+  if (*error == TSQueryDeserializeErrorNone) {
+    *error = ts_query_serialization__validate(self);
+  }
+  if (*error != TSQueryDeserializeErrorNone) {
+    ts_query_delete(self);
+    return NULL;
+  }
+  return self;
+}
+
 /***************
  * QueryCursor
  ***************/
@@ -3080,16 +3703,20 @@ TSQueryCursor *ts_query_cursor_new(void) {
   TSQueryCursor *self = ts_malloc(sizeof(TSQueryCursor));
   *self = (TSQueryCursor) {
     .did_exceed_match_limit = false,
+    .did_exceed_step_limit = false,
     .ascending = false,
     .halted = false,
     .states = array_new(),
     .finished_states = array_new(),
+    .enabled_patterns = array_new(),
     .capture_list_pool = capture_list_pool_new(),
     .start_byte = 0,
     .end_byte = UINT32_MAX,
     .start_point = {0, 0},
     .end_point = POINT_MAX,
     .max_start_depth = UINT32_MAX,
+    .step_count = 0,
+    .step_limit = UINT64_MAX,
     .timeout_duration = 0,
     .end_clock = clock_null(),
     .operation_count = 0,
@@ -3102,6 +3729,7 @@ TSQueryCursor *ts_query_cursor_new(void) {
 void ts_query_cursor_delete(TSQueryCursor *self) {
   array_delete(&self->states);
   array_delete(&self->finished_states);
+  array_delete(&self->enabled_patterns);
   ts_tree_cursor_delete(&self->cursor);
   capture_list_pool_delete(&self->capture_list_pool);
   ts_free(self);
@@ -3119,6 +3747,18 @@ void ts_query_cursor_set_match_limit(TSQueryCursor *self, uint32_t limit) {
   self->capture_list_pool.max_capture_list_count = limit;
 }
 
+bool ts_query_cursor_did_exceed_step_limit(const TSQueryCursor *self) {
+  return self->did_exceed_step_limit;
+}
+
+uint64_t ts_query_cursor_step_limit(const TSQueryCursor *self) {
+  return self->step_limit;
+}
+
+void ts_query_cursor_set_step_limit(TSQueryCursor *self, uint64_t limit) {
+  self->step_limit = limit;
+}
+
 uint64_t ts_query_cursor_timeout_micros(const TSQueryCursor *self) {
   return duration_to_micros(self->timeout_duration);
 }
@@ -3127,6 +3767,45 @@ void ts_query_cursor_set_timeout_micros(TSQueryCursor *self, uint64_t timeout_mi
   self->timeout_duration = duration_from_micros(timeout_micros);
 }
 
+void ts_query_cursor_set_enabled_patterns(
+  TSQueryCursor *self,
+  const bool *enabled_patterns,
+  uint32_t length
+) {
+  array_clear(&self->enabled_patterns);
+  array_extend(&self->enabled_patterns, length, enabled_patterns);
+}
+
+void ts_query_cursor_reset(TSQueryCursor *self) {
+  array_clear(&self->states);
+  array_clear(&self->finished_states);
+  array_clear(&self->enabled_patterns);
+  capture_list_pool_reset(&self->capture_list_pool);
+  self->capture_list_pool.max_capture_list_count = UINT32_MAX;
+  self->query = NULL;
+  self->query_options = NULL;
+  self->query_state = (TSQueryCursorState) {0};
+  self->stats = (TSQueryCursorStats) {0};
+  self->start_byte = 0;
+  self->end_byte = UINT32_MAX;
+  self->start_point = (TSPoint) {0, 0};
+  self->end_point = POINT_MAX;
+  self->max_start_depth = UINT32_MAX;
+  self->step_count = 0;
+  self->step_limit = UINT64_MAX;
+  self->timeout_duration = 0;
+  self->end_clock = clock_null();
+  self->operation_count = 0;
+  self->ascending = false;
+  self->halted = true;
+  self->did_exceed_match_limit = false;
+  self->did_exceed_step_limit = false;
+}
+
+TSQueryCursorStats ts_query_cursor_stats(const TSQueryCursor *self) {
+  return self->stats;
+}
+
 #ifdef DEBUG_EXECUTE_QUERY
 #define LOG(...) fprintf(stderr, __VA_ARGS__)
 #else
@@ -3175,6 +3854,9 @@ void ts_query_cursor_exec(
   self->halted = false;
   self->query = query;
   self->did_exceed_match_limit = false;
+  self->did_exceed_step_limit = false;
+  self->stats = (TSQueryCursorStats) {0};
+  self->step_count = 0;
   self->operation_count = 0;
   if (self->timeout_duration) {
     self->end_clock = clock_after(clock_now(), self->timeout_duration);
@@ -3365,6 +4047,17 @@ void ts_query_cursor__compare_captures(
   }
 }
 
+// Patterns whose index is past the end of the cursor's `enabled_patterns`
+// array are enabled, so an empty array enables every pattern.
+static inline bool ts_query_cursor__pattern_is_enabled(
+  const TSQueryCursor *self,
+  uint16_t pattern_index
+) {
+  return
+    pattern_index >= self->enabled_patterns.size ||
+    self->enabled_patterns.contents[pattern_index];
+}
+
 static void ts_query_cursor__add_state(
   TSQueryCursor *self,
   const PatternEntry *pattern
@@ -3437,6 +4130,12 @@ static CaptureList *ts_query_cursor__prepare_to_capture(
   if (state->capture_list_id == NONE) {
     state->capture_list_id = capture_list_pool_acquire(&self->capture_list_pool);
 
+    uint32_t in_progress_match_count =
+      self->capture_list_pool.list.size - self->capture_list_pool.free_capture_list_count;
+    if (in_progress_match_count > self->stats.max_in_progress_match_count) {
+      self->stats.max_in_progress_match_count = in_progress_match_count;
+    }
+
     // If there are no capture lists left in the pool, then terminate whichever
     // state has captured the earliest node in the document, and steal its
     // capture list.
@@ -3595,6 +4294,12 @@ static inline bool ts_query_cursor__advance(
 ) {
   bool did_match = false;
   for (;;) {
+    if (!self->halted && self->step_count >= self->step_limit) {
+      LOG("halt at step limit\n");
+      self->halted = true;
+      self->did_exceed_step_limit = true;
+    }
+
     if (self->halted) {
       while (self->states.size > 0) {
         QueryState state = array_pop(&self->states);
@@ -3739,6 +4444,7 @@ static inline bool ts_query_cursor__advance(
       bool node_intersects_range = !node_precedes_range && !node_follows_range;
 
       if (self->on_visible_node) {
+        self->stats.node_count++;
         TSSymbol symbol = ts_node_symbol(node);
         bool is_named = ts_node_is_named(node);
         bool is_missing = ts_node_is_missing(node);
@@ -3787,7 +4493,8 @@ static inline bool ts_query_cursor__advance(
                 (parent_intersects_range && !parent_is_error)) &&
               (!step->field || field_id == step->field) &&
               (!step->supertype_symbol || supertype_count > 0) &&
-              (start_depth <= self->max_start_depth)
+              (start_depth <= self->max_start_depth) &&
+              ts_query_cursor__pattern_is_enabled(self, pattern->pattern_index)
             ) {
               ts_query_cursor__add_state(self, pattern);
             }
@@ -3809,7 +4516,8 @@ static inline bool ts_query_cursor__advance(
                 node_intersects_range :
                 (parent_intersects_range && !parent_is_error)) &&
               (!step->field || field_id == step->field) &&
-              (start_depth <= self->max_start_depth)
+              (start_depth <= self->max_start_depth) &&
+              ts_query_cursor__pattern_is_enabled(self, pattern->pattern_index)
             ) {
               ts_query_cursor__add_state(self, pattern);
             }
@@ -3828,6 +4536,8 @@ static inline bool ts_query_cursor__advance(
           QueryStep *step = &self->query->steps.contents[state->step_index];
           state->has_in_progress_alternatives = false;
           copy_count = 0;
+          self->stats.state_count++;
+          self->step_count++;
 
           // Check that the node matches all of the criteria for the next
           // step of the pattern.
@@ -99,6 +99,28 @@ TSRange *ts_tree_included_ranges(const TSTree *self, uint32_t *length) {
   return ranges;
 }
 
+size_t ts_tree_memory_usage(const TSTree *self) {
+  size_t result = sizeof(TSTree) + self->included_range_count * sizeof(TSRange);
+  SubtreeArray stack = array_new();
+  array_push(&stack, self->root);
+  while (stack.size > 0) {
+    Subtree tree = array_pop(&stack);
+    if (tree.data.is_inline) continue;
+    result += ts_subtree_alloc_size(tree.ptr->child_count);
+    if (tree.ptr->child_count > 0) {
+      Subtree *children = ts_subtree_children(tree);
+      array_extend(&stack, tree.ptr->child_count, children);
+    } else if (
+      tree.ptr->has_external_tokens &&
+      tree.ptr->external_scanner_state.length > sizeof(tree.ptr->external_scanner_state.short_data)
+    ) {
+      result += tree.ptr->external_scanner_state.length;
+    }
+  }
+  array_delete(&stack);
+  return result;
+}
+
 TSRange *ts_tree_get_changed_ranges(const TSTree *old_tree, const TSTree *new_tree, uint32_t *length) {
   TreeCursor cursor1 = {NULL, array_new(), 0};
   TreeCursor cursor2 = {NULL, array_new(), 0};
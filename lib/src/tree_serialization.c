@@ -0,0 +1,483 @@
+#include "tree_sitter/api.h"
+#include "./alloc.h"
+#include "./array.h"
+#include "./language.h"
+#include "./length.h"
+#include "./subtree.h"
+#include "./tree.h"
+
+// The binary format of a serialized syntax tree.
+//
+// All integers are little-endian. The format starts with a header, containing:
+// * The bytes `TSTR`, followed by the version of the format as a `uint32_t`.
+// * The number of included ranges as a `uint32_t`, followed by each range's
+//   start byte, end byte, start point, and end point, as `uint32_t`s.
+// * The number of subtrees as a `uint32_t`.
+//
+// The subtrees follow in post-order, so that each subtree's children come
+// right before it. Each subtree starts with its symbol and its flags as
+// `uint16_t`s, and its child count as a `uint32_t`. Leaves have a terminal
+// symbol, and parents, which are flagged as such because they may have no
+// children, have a non-terminal symbol. Either can be an `ERROR` node.
+// * A leaf continues with its padding and its size, each as a byte count, a
+//   row, and a column, its lookahead byte count, and its parse state. An error
+//   leaf is followed by its lookahead character as an `int32_t`, and a token
+//   produced by an external scanner by the length and the bytes of the
+//   scanner's serialized state.
+// * A parent continues with its production id and its parse state as
+//   `uint16_t`s, and its dynamic precedence as an `int32_t`. The field ids of
+//   its children are determined by its production id, and its position and
+//   size by its children.
+
+#define TS_TREE_SERIALIZATION_MAGIC "TSTR"
+#define TS_TREE_SERIALIZATION_VERSION 1
+
+typedef enum {
+  SubtreeFlagExtra = 1 << 0,
+  SubtreeFlagMissing = 1 << 1,
+  SubtreeFlagKeyword = 1 << 2,
+  SubtreeFlagFragileLeft = 1 << 3,
+  SubtreeFlagFragileRight = 1 << 4,
+  SubtreeFlagHasExternalTokens = 1 << 5,
+  SubtreeFlagExternalScannerStateChange = 1 << 6,
+  SubtreeFlagDependsOnColumn = 1 << 7,
+  SubtreeFlagParent = 1 << 8,
+  SubtreeFlagAll = (1 << 9) - 1,
+} SubtreeFlag;
+
+typedef Array(uint8_t) ByteArray;
+
+typedef struct {
+  const uint8_t *data;
+  uint32_t length;
+  uint32_t offset;
+} ByteReader;
+
+typedef struct {
+  Subtree tree;
+  uint32_t child_index;
+} SerializationEntry;
+
+// Serialization
+
+static void ts_tree_serialization__write_u16(ByteArray *self, uint16_t value) {
+  array_push(self, (uint8_t)value);
+  array_push(self, (uint8_t)(value >> 8));
+}
+
+static void ts_tree_serialization__write_u32(ByteArray *self, uint32_t value) {
+  for (unsigned i = 0; i < 4; i++) {
+    array_push(self, (uint8_t)(value >> (8 * i)));
+  }
+}
+
+static void ts_tree_serialization__write_point(ByteArray *self, TSPoint point) {
+  ts_tree_serialization__write_u32(self, point.row);
+  ts_tree_serialization__write_u32(self, point.column);
+}
+
+static void ts_tree_serialization__write_length(ByteArray *self, Length length) {
+  ts_tree_serialization__write_u32(self, length.bytes);
+  ts_tree_serialization__write_point(self, length.extent);
+}
+
+static void ts_tree_serialization__write_subtree(
+  ByteArray *self,
+  const TSLanguage *language,
+  Subtree tree
+) {
+  TSSymbol symbol = ts_subtree_symbol(tree);
+  uint32_t child_count = ts_subtree_child_count(tree);
+  bool is_leaf = child_count == 0 && (symbol < language->token_count || symbol == ts_builtin_sym_error);
+  uint16_t flags = 0;
+  if (ts_subtree_extra(tree)) flags |= SubtreeFlagExtra;
+  if (ts_subtree_missing(tree)) flags |= SubtreeFlagMissing;
+  if (ts_subtree_is_keyword(tree)) flags |= SubtreeFlagKeyword;
+  if (ts_subtree_fragile_left(tree)) flags |= SubtreeFlagFragileLeft;
+  if (ts_subtree_fragile_right(tree)) flags |= SubtreeFlagFragileRight;
+  if (is_leaf) {
+    if (ts_subtree_has_external_tokens(tree)) flags |= SubtreeFlagHasExternalTokens;
+    if (ts_subtree_has_external_scanner_state_change(tree)) flags |= SubtreeFlagExternalScannerStateChange;
+    if (ts_subtree_depends_on_column(tree)) flags |= SubtreeFlagDependsOnColumn;
+  } else {
+    flags |= SubtreeFlagParent;
+  }
+
+  ts_tree_serialization__write_u16(self, symbol);
+  ts_tree_serialization__write_u16(self, flags);
+  ts_tree_serialization__write_u32(self, child_count);
+
+  if (is_leaf) {
+    ts_tree_serialization__write_length(self, ts_subtree_padding(tree));
+    ts_tree_serialization__write_length(self, ts_subtree_size(tree));
+    ts_tree_serialization__write_u32(self, ts_subtree_lookahead_bytes(tree));
+    ts_tree_serialization__write_u16(self, ts_subtree_parse_state(tree));
+    if (ts_subtree_is_error(tree)) {
+      ts_tree_serialization__write_u32(self, (uint32_t)tree.ptr->lookahead_char);
+    } else if (flags & SubtreeFlagHasExternalTokens) {
+      const ExternalScannerState *state = &tree.ptr->external_scanner_state;
+      ts_tree_serialization__write_u32(self, state->length);
+      array_extend(self, state->length, ts_external_scanner_state_data(state));
+    }
+  } else {
+    ts_tree_serialization__write_u16(self, ts_subtree_production_id(tree));
+    ts_tree_serialization__write_u16(self, ts_subtree_parse_state(tree));
+    ts_tree_serialization__write_u32(self, (uint32_t)ts_subtree_dynamic_precedence(tree));
+  }
+}
+
+char *ts_tree_serialize(const TSTree *self, uint32_t *length) {
+  ByteArray buffer = array_new();
+  array_extend(&buffer, 4, TS_TREE_SERIALIZATION_MAGIC);
+  ts_tree_serialization__write_u32(&buffer, TS_TREE_SERIALIZATION_VERSION);
+
+  ts_tree_serialization__write_u32(&buffer, self->included_range_count);
+  for (unsigned i = 0; i < self->included_range_count; i++) {
+    TSRange *range = &self->included_ranges[i];
+    ts_tree_serialization__write_u32(&buffer, range->start_byte);
+    ts_tree_serialization__write_u32(&buffer, range->end_byte);
+    ts_tree_serialization__write_point(&buffer, range->start_point);
+    ts_tree_serialization__write_point(&buffer, range->end_point);
+  }
+
+  // Reserve space for the number of subtrees, which is written once they have all been visited.
+  uint32_t subtree_count_offset = buffer.size;
+  ts_tree_serialization__write_u32(&buffer, 0);
+  uint32_t subtree_count = 0;
+
+  Array(SerializationEntry) stack = array_new();
+  array_push(&stack, ((SerializationEntry) {.tree = self->root, .child_index = 0}));
+  while (stack.size > 0) {
+    SerializationEntry *entry = array_back(&stack);
+    if (entry->child_index < ts_subtree_child_count(entry->tree)) {
+      Subtree child = ts_subtree_children(entry->tree)[entry->child_index];
+      entry->child_index++;
+      array_push(&stack, ((SerializationEntry) {.tree = child, .child_index = 0}));
+    } else {
+      ts_tree_serialization__write_subtree(&buffer, self->language, entry->tree);
+      subtree_count++;
+      array_pop(&stack);
+    }
+  }
+  array_delete(&stack);
+
+  for (unsigned i = 0; i < 4; i++) {
+    buffer.contents[subtree_count_offset + i] = (uint8_t)(subtree_count >> (8 * i));
+  }
+
+  *length = buffer.size;
+  return (char *)buffer.contents;
+}
+
+// Deserialization
+
+static bool ts_tree_serialization__read_u16(ByteReader *self, uint16_t *value) {
+  if (self->length - self->offset < 2) return false;
+  const uint8_t *bytes = &self->data[self->offset];
+  *value = (uint16_t)(bytes[0] | bytes[1] << 8);
+  self->offset += 2;
+  return true;
+}
+
+static bool ts_tree_serialization__read_u32(ByteReader *self, uint32_t *value) {
+  if (self->length - self->offset < 4) return false;
+  const uint8_t *bytes = &self->data[self->offset];
+  *value = 0;
+  for (unsigned i = 0; i < 4; i++) {
+    *value |= (uint32_t)bytes[i] << (8 * i);
+  }
+  self->offset += 4;
+  return true;
+}
+
+static bool ts_tree_serialization__read_point(ByteReader *self, TSPoint *point) {
+  return
+    ts_tree_serialization__read_u32(self, &point->row) &&
+    ts_tree_serialization__read_u32(self, &point->column);
+}
+
+static bool ts_tree_serialization__read_length(ByteReader *self, Length *length) {
+  return
+    ts_tree_serialization__read_u32(self, &length->bytes) &&
+    ts_tree_serialization__read_point(self, &length->extent);
+}
+
+// Whether a leaf's padding or size could belong to a real source: it can't
+// span more lines than it has bytes, or more columns than bytes on one line.
+static bool ts_tree_serialization__is_valid_length(Length length) {
+  if (length.extent.row == 0) return length.extent.column <= length.bytes;
+  return length.extent.row <= length.bytes;
+}
+
+// Whether a symbol can be a leaf's: a terminal, or an `ERROR` for a skipped
+// character.
+static bool ts_tree_serialization__is_valid_leaf_symbol(const TSLanguage *language, TSSymbol symbol) {
+  return symbol < language->token_count || symbol == ts_builtin_sym_error;
+}
+
+// Whether a symbol can be a parent's: a non-terminal, or an `ERROR` containing
+// the nodes that were skipped.
+static bool ts_tree_serialization__is_valid_parent_symbol(const TSLanguage *language, TSSymbol symbol) {
+  return
+    (symbol >= language->token_count && symbol < language->symbol_count) ||
+    symbol == ts_builtin_sym_error ||
+    symbol == ts_builtin_sym_error_repeat;
+}
+
+static bool ts_tree_serialization__is_valid_state(const TSLanguage *language, TSStateId state) {
+  return state < language->state_count || state == TS_TREE_STATE_NONE;
+}
+
+static TSTreeDeserializeError ts_tree_serialization__read_leaf(
+  ByteReader *reader,
+  SubtreePool *pool,
+  const TSLanguage *language,
+  TSSymbol symbol,
+  uint16_t flags,
+  uint64_t *total_bytes,
+  uint32_t source_length,
+  Subtree *result
+) {
+  Length padding, size;
+  uint32_t lookahead_bytes;
+  TSStateId parse_state;
+  if (
+    !ts_tree_serialization__read_length(reader, &padding) ||
+    !ts_tree_serialization__read_length(reader, &size) ||
+    !ts_tree_serialization__read_u32(reader, &lookahead_bytes) ||
+    !ts_tree_serialization__read_u16(reader, &parse_state)
+  ) return TSTreeDeserializeErrorFormat;
+  if (!ts_tree_serialization__is_valid_state(language, parse_state)) {
+    return TSTreeDeserializeErrorParseState;
+  }
+
+  // The leaves appear in the order of the document, so their lengths add up to
+  // the offset where the last one ends, which can't be past the end of the source.
+  *total_bytes += (uint64_t)padding.bytes + size.bytes;
+  if (
+    !ts_tree_serialization__is_valid_length(padding) ||
+    !ts_tree_serialization__is_valid_length(size) ||
+    *total_bytes > source_length ||
+    ((flags & SubtreeFlagMissing) && size.bytes > 0)
+  ) return TSTreeDeserializeErrorRange;
+
+  bool has_external_tokens = flags & SubtreeFlagHasExternalTokens;
+  if (symbol == ts_builtin_sym_error) {
+    uint32_t lookahead_char;
+    if (has_external_tokens || !ts_tree_serialization__read_u32(reader, &lookahead_char)) {
+      return TSTreeDeserializeErrorFormat;
+    }
+    *result = ts_subtree_new_error(
+      pool, (int32_t)lookahead_char, padding, size, lookahead_bytes, parse_state, language
+    );
+  } else {
+    const char *external_scanner_state = NULL;
+    uint32_t external_scanner_state_length = 0;
+    if (has_external_tokens) {
+      if (
+        !ts_tree_serialization__read_u32(reader, &external_scanner_state_length) ||
+        external_scanner_state_length > TREE_SITTER_SERIALIZATION_BUFFER_SIZE ||
+        reader->length - reader->offset < external_scanner_state_length
+      ) return TSTreeDeserializeErrorFormat;
+      external_scanner_state = (const char *)&reader->data[reader->offset];
+      reader->offset += external_scanner_state_length;
+    } else if (flags & SubtreeFlagExternalScannerStateChange) {
+      return TSTreeDeserializeErrorFormat;
+    }
+
+    *result = ts_subtree_new_leaf(
+      pool, symbol, padding, size, lookahead_bytes, parse_state,
+      has_external_tokens, flags & SubtreeFlagDependsOnColumn,
+      flags & SubtreeFlagKeyword, language
+    );
+    if (has_external_tokens) {
+      SubtreeHeapData *data = (SubtreeHeapData *)result->ptr;
+      ts_external_scanner_state_init(
+        &data->external_scanner_state,
+        external_scanner_state,
+        external_scanner_state_length
+      );
+      data->has_external_scanner_state_change = flags & SubtreeFlagExternalScannerStateChange;
+    }
+  }
+
+  MutableSubtree mutable_result = ts_subtree_to_mut_unsafe(*result);
+  ts_subtree_set_extra(&mutable_result, flags & SubtreeFlagExtra);
+  if (flags & SubtreeFlagMissing) {
+    if (mutable_result.data.is_inline) {
+      mutable_result.data.is_missing = true;
+    } else {
+      mutable_result.ptr->is_missing = true;
+    }
+  }
+  *result = ts_subtree_from_mut(mutable_result);
+  return TSTreeDeserializeErrorNone;
+}
+
+static TSTreeDeserializeError ts_tree_serialization__read_parent(
+  ByteReader *reader,
+  SubtreeArray *stack,
+  const TSLanguage *language,
+  TSSymbol symbol,
+  uint16_t flags,
+  uint32_t child_count,
+  Subtree *result
+) {
+  uint16_t production_id;
+  TSStateId parse_state;
+  uint32_t dynamic_precedence;
+  if (
+    child_count > stack->size ||
+    (flags & ~(SubtreeFlagExtra | SubtreeFlagFragileLeft | SubtreeFlagFragileRight | SubtreeFlagParent)) ||
+    !ts_tree_serialization__read_u16(reader, &production_id) ||
+    !ts_tree_serialization__read_u16(reader, &parse_state) ||
+    !ts_tree_serialization__read_u32(reader, &dynamic_precedence)
+  ) return TSTreeDeserializeErrorFormat;
+  if (!ts_tree_serialization__is_valid_state(language, parse_state)) {
+    return TSTreeDeserializeErrorParseState;
+  }
+
+  // A production's aliases and fields are looked up by the index of each
+  // child that isn't an extra, so there can't be more of them than the
+  // longest production has.
+  Subtree *children = &stack->contents[stack->size - child_count];
+  if (production_id > 0) {
+    if (production_id >= language->production_id_count) return TSTreeDeserializeErrorField;
+    uint32_t structural_child_count = 0;
+    for (uint32_t i = 0; i < child_count; i++) {
+      if (!ts_subtree_extra(children[i])) structural_child_count++;
+    }
+    if (structural_child_count > language->max_alias_sequence_length) {
+      return TSTreeDeserializeErrorField;
+    }
+  }
+
+  SubtreeArray node_children = array_new();
+  array_extend(&node_children, child_count, children);
+  stack->size -= child_count;
+  MutableSubtree node = ts_subtree_new_node(symbol, &node_children, production_id, language);
+  node.ptr->extra = flags & SubtreeFlagExtra;
+  node.ptr->fragile_left = flags & SubtreeFlagFragileLeft;
+  node.ptr->fragile_right = flags & SubtreeFlagFragileRight;
+  node.ptr->parse_state = parse_state;
+  node.ptr->dynamic_precedence = (int32_t)dynamic_precedence;
+  *result = ts_subtree_from_mut(node);
+  return TSTreeDeserializeErrorNone;
+}
+
+static TSTreeDeserializeError ts_tree_serialization__read_subtrees(
+  ByteReader *reader,
+  SubtreePool *pool,
+  const TSLanguage *language,
+  uint32_t source_length,
+  SubtreeArray *stack
+) {
+  uint32_t subtree_count;
+  if (!ts_tree_serialization__read_u32(reader, &subtree_count)) return TSTreeDeserializeErrorFormat;
+
+  uint64_t total_bytes = 0;
+  for (uint32_t i = 0; i < subtree_count; i++) {
+    TSSymbol symbol;
+    uint16_t flags;
+    uint32_t child_count;
+    if (
+      !ts_tree_serialization__read_u16(reader, &symbol) ||
+      !ts_tree_serialization__read_u16(reader, &flags) ||
+      !ts_tree_serialization__read_u32(reader, &child_count) ||
+      (flags & ~SubtreeFlagAll)
+    ) return TSTreeDeserializeErrorFormat;
+    bool is_parent = flags & SubtreeFlagParent;
+    if (!is_parent && child_count > 0) return TSTreeDeserializeErrorFormat;
+    if (
+      is_parent
+        ? !ts_tree_serialization__is_valid_parent_symbol(language, symbol)
+        : !ts_tree_serialization__is_valid_leaf_symbol(language, symbol)
+    ) return TSTreeDeserializeErrorSymbol;
+
+    Subtree subtree;
+    TSTreeDeserializeError error = is_parent
+      ? ts_tree_serialization__read_parent(
+        reader, stack, language, symbol, flags, child_count, &subtree
+      )
+      : ts_tree_serialization__read_leaf(
+        reader, pool, language, symbol, flags, &total_bytes, source_length, &subtree
+      );
+    if (error != TSTreeDeserializeErrorNone) return error;
+    array_push(stack, subtree);
+  }
+
+  if (stack->size != 1 || reader->offset != reader->length) return TSTreeDeserializeErrorFormat;
+  return TSTreeDeserializeErrorNone;
+}
+
+TSTree *ts_tree_deserialize(
+  const TSLanguage *language,
+  uint32_t source_length,
+  const char *bytes,
+  uint32_t length,
+  TSTreeDeserializeError *error
+) {
+  if (length < 4 || memcmp(bytes, TS_TREE_SERIALIZATION_MAGIC, 4) != 0) {
+    *error = TSTreeDeserializeErrorFormat;
+    return NULL;
+  }
+  ByteReader reader = {.data = (const uint8_t *)bytes, .length = length, .offset = 4};
+  uint32_t version;
+  if (!ts_tree_serialization__read_u32(&reader, &version)) {
+    *error = TSTreeDeserializeErrorFormat;
+    return NULL;
+  }
+  if (version != TS_TREE_SERIALIZATION_VERSION) {
+    *error = TSTreeDeserializeErrorVersion;
+    return NULL;
+  }
+
+  // A parser always uses at least one included range, which spans the whole
+  // document by default.
+  uint32_t included_range_count;
+  if (!ts_tree_serialization__read_u32(&reader, &included_range_count) || included_range_count == 0) {
+    *error = TSTreeDeserializeErrorFormat;
+    return NULL;
+  }
+  Array(TSRange) included_ranges = array_new();
+  *error = TSTreeDeserializeErrorNone;
+  for (uint32_t i = 0; i < included_range_count; i++) {
+    TSRange range;
+    if (
+      !ts_tree_serialization__read_u32(&reader, &range.start_byte) ||
+      !ts_tree_serialization__read_u32(&reader, &range.end_byte) ||
+      !ts_tree_serialization__read_point(&reader, &range.start_point) ||
+      !ts_tree_serialization__read_point(&reader, &range.end_point)
+    ) {
+      *error = TSTreeDeserializeErrorFormat;
+      break;
+    }
+    if (
+      range.start_byte > range.end_byte ||
+      (included_ranges.size > 0 && range.start_byte < array_back(&included_ranges)->end_byte)
+    ) {
+      *error = TSTreeDeserializeErrorRange;
+      break;
+    }
+    array_push(&included_ranges, range);
+  }
+
+  SubtreePool pool = ts_subtree_pool_new(0);
+  SubtreeArray stack = array_new();
+  if (*error == TSTreeDeserializeErrorNone) {
+    *error = ts_tree_serialization__read_subtrees(&reader, &pool, language, source_length, &stack);
+  }
+
+  TSTree *result = NULL;
+  if (*error == TSTreeDeserializeErrorNone) {
+    result = ts_tree_new(stack.contents[0], language, included_ranges.contents, included_ranges.size);
+  } else {
+    for (uint32_t i = 0; i < stack.size; i++) {
+      ts_subtree_release(&pool, stack.contents[i]);
+    }
+  }
+  array_delete(&stack);
+  array_delete(&included_ranges);
+  ts_subtree_pool_delete(&pool);
+  return result;
+}
@@ -161,6 +161,8 @@ typedef struct {
   int32_t supertype_map_slices;
   int32_t supertype_map_entries;
   TSLanguageMetadata metadata;
+  int32_t keyword_capture_tokens;
+  uint16_t keyword_capture_token_count;
 } LanguageInWasmMemory;
 
 // LexerInWasmMemory - The memory layout of a `TSLexer` when compiled to wasm32.
@@ -1242,6 +1244,9 @@ const TSLanguage *ts_wasm_store_load_language(
     wasm_language.supertype_symbols,
     wasm_language.supertype_map_entries,
     wasm_language.supertype_map_slices,
+    wasm_language.abi_version >= LANGUAGE_VERSION_WITH_MULTIPLE_WORD_TOKENS
+      ? wasm_language.keyword_capture_tokens
+      : 0,
     wasm_language.external_token_count > 0 ? wasm_language.external_scanner.states : 0,
     wasm_language.external_token_count > 0 ? wasm_language.external_scanner.symbol_map : 0,
     wasm_language.external_token_count > 0 ? wasm_language.external_scanner.create : 0,
@@ -1414,6 +1419,17 @@ const TSLanguage *ts_wasm_store_load_language(
     language->max_reserved_word_set_size = wasm_language.max_reserved_word_set_size;
   }
 
+  if (
+    language->abi_version >= LANGUAGE_VERSION_WITH_MULTIPLE_WORD_TOKENS &&
+    wasm_language.keyword_capture_token_count > 0
+  ) {
+    language->keyword_capture_tokens = copy(
+      &memory[wasm_language.keyword_capture_tokens],
+      wasm_language.keyword_capture_token_count * sizeof(TSSymbol)
+    );
+    language->keyword_capture_token_count = wasm_language.keyword_capture_token_count;
+  }
+
   if (language->external_token_count > 0) {
     language->external_scanner.symbol_map = copy(
       &memory[wasm_language.external_scanner.symbol_map],
@@ -1800,6 +1816,7 @@ void ts_wasm_language_release(const TSLanguage *self) {
     ts_free((void *)self->lex_modes);
     ts_free((void *)self->name);
     ts_free((void *)self->reserved_words);
+    ts_free((void *)self->keyword_capture_tokens);
     ts_free((void *)self->parse_actions);
     ts_free((void *)self->parse_table);
     ts_free((void *)self->primary_state_ids);
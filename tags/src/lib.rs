@@ -18,8 +18,8 @@ use regex::Regex;
 use streaming_iterator::StreamingIterator;
 use thiserror::Error;
 use tree_sitter::{
-    Language, LossyUtf8, ParseOptions, Parser, Point, Query, QueryCursor, QueryError,
-    QueryPredicateArg, Tree,
+    Language, LossyUtf8, Node, ParseOptions, Parser, Point, Query, QueryCursor, QueryError,
+    QueryMatches, QueryPredicateArg, Tree,
 };
 
 const MAX_LINE_LEN: usize = 180;
@@ -31,6 +31,9 @@ const CANCELLATION_CHECK_INTERVAL: usize = 100;
 pub struct TagsConfiguration {
     pub language: Language,
     pub query: Query,
+    /// How the docs of the tags are extracted from their `@doc` captures, for the patterns that
+    /// don't configure it with predicates
+    pub docs: DocsConfig,
     syntax_type_names: Vec<Box<[u8]>>,
     c_syntax_type_names: Vec<*const u8>,
     capture_map: HashMap<u32, NamedCapture>,
@@ -43,6 +46,46 @@ pub struct TagsConfiguration {
     pattern_info: Vec<PatternInfo>,
 }
 
+/// How the nodes captured with `@doc` are turned into the docs of a tag.
+///
+/// Each setting can also be configured for a single pattern with a predicate on the `@doc`
+/// capture, which takes precedence over the setting here:
+///
+/// * `(#strip! @doc "regex")` sets [`strip_regex`](Self::strip_regex).
+/// * `(#strip-lines! @doc "regex")` sets [`line_strip_regex`](Self::line_strip_regex).
+/// * `(#join! @doc " ")` sets [`separator`](Self::separator).
+/// * `(#max-gap! @doc 1)` sets [`max_gap`](Self::max_gap).
+/// * `(#allow-trailing! @doc)` sets [`allow_trailing`](Self::allow_trailing).
+#[derive(Debug, Clone)]
+pub struct DocsConfig {
+    /// A regex whose matches are removed from the text of each doc node
+    pub strip_regex: Option<Regex>,
+    /// A regex whose matches are removed from each line of the text of each doc node, like the
+    /// `///` at the start of each line of a doc comment
+    pub line_strip_regex: Option<Regex>,
+    /// The text that the docs of consecutive doc nodes are joined with
+    pub separator: String,
+    /// The number of lines that can separate a doc node from the next doc node, or from the
+    /// node that the docs are selected to be adjacent to with `#select-adjacent!`
+    pub max_gap: usize,
+    /// Whether the docs can come after the node that they are selected to be adjacent to with
+    /// `#select-adjacent!`, like the inner doc comments at the start of a module's body. These
+    /// docs are only used when there are no docs before the node.
+    pub allow_trailing: bool,
+}
+
+impl Default for DocsConfig {
+    fn default() -> Self {
+        Self {
+            strip_regex: None,
+            line_strip_regex: None,
+            separator: "\n".to_string(),
+            max_gap: 0,
+            allow_trailing: false,
+        }
+    }
+}
+
 unsafe impl Send for TagsConfiguration {}
 unsafe impl Sync for TagsConfiguration {}
 
@@ -81,6 +124,8 @@ pub enum Error {
     InvalidLanguage,
     #[error("Invalid capture @{0}. Expected one of: @definition.*, @reference.*, @doc, @name, @local.(scope|definition|reference).")]
     InvalidCapture(String),
+    #[error("Invalid predicate: {0}")]
+    InvalidPredicate(String),
 }
 
 #[derive(Debug, Default)]
@@ -89,6 +134,10 @@ struct PatternInfo {
     local_scope_inherits: bool,
     name_must_be_non_local: bool,
     doc_strip_regex: Option<Regex>,
+    doc_line_strip_regex: Option<Regex>,
+    doc_separator: Option<String>,
+    docs_max_gap: Option<usize>,
+    docs_allow_trailing: Option<bool>,
 }
 
 #[derive(Debug)]
@@ -113,11 +162,36 @@ where
     prev_line_info: Option<LineInfo>,
     config: &'a TagsConfiguration,
     cancellation_flag: Option<&'a AtomicUsize>,
+    progress_callback: Option<&'a mut dyn FnMut(&TagsProgress) -> bool>,
+    progress: TagsProgress,
+    /// Tags whose names start before this offset are skipped
+    start_byte: usize,
     iter_count: usize,
     tag_queue: Vec<(Tag, usize)>,
     scopes: Vec<LocalScope<'a>>,
 }
 
+/// How far [`TagsContext::generate_tags_from`] has gotten, which is passed to its progress
+/// callback.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TagsProgress {
+    /// The number of tags that have been generated
+    pub tag_count: usize,
+    /// The byte offset that tag generation could be resumed from
+    pub byte_offset: usize,
+}
+
+/// The tags generated by [`TagsContext::generate_tags_from`].
+#[derive(Debug)]
+pub struct PartialTags {
+    pub tags: Vec<Tag>,
+    /// If the progress callback stopped the generation of tags, the byte offset to pass to
+    /// `generate_tags_from` to generate the rest of them
+    pub resume_byte: Option<usize>,
+    /// Whether the syntax tree of the document has any errors
+    pub has_error: bool,
+}
+
 struct LineInfo {
     utf8_position: Point,
     utf8_byte: usize,
@@ -126,6 +200,12 @@ struct LineInfo {
 }
 
 impl TagsConfiguration {
+    /// Creates a `TagsConfiguration` from a tags query and a locals query.
+    ///
+    /// Every `@definition.<kind>` and `@reference.<kind>` capture in the tags query is a tag, and
+    /// its kind can be any name, like `function`, `call`, or a name that only makes sense for a
+    /// particular language, like `route`. The kinds are given syntax type ids in the order that
+    /// they first appear in the query, so the same query always produces the same ids.
     pub fn new(language: Language, tags_query: &str, locals_query: &str) -> Result<Self, Error> {
         let query = Query::new(&language, &format!("{locals_query}{tags_query}"))?;
 
@@ -156,14 +236,17 @@ impl TagsConfiguration {
                 _ => {
                     let mut is_definition = false;
 
-                    let kind = if name.starts_with("definition.") {
+                    let kind = if let Some(kind) = name.strip_prefix("definition.") {
                         is_definition = true;
-                        name.trim_start_matches("definition.")
-                    } else if name.starts_with("reference.") {
-                        name.trim_start_matches("reference.")
+                        kind
+                    } else if let Some(kind) = name.strip_prefix("reference.") {
+                        kind
                     } else {
                         return Err(Error::InvalidCapture((*name).to_string()));
                     };
+                    if kind.is_empty() {
+                        return Err(Error::InvalidCapture((*name).to_string()));
+                    }
 
                     if let Ok(cstr) = CString::new(kind) {
                         let c_kind = cstr.to_bytes_with_nul().to_vec().into_boxed_slice();
@@ -220,6 +303,24 @@ impl TagsConfiguration {
                                     let regex = Regex::new(pattern.as_ref())?;
                                     info.doc_strip_regex = Some(regex);
                                 }
+                                ("strip-lines!", Some(QueryPredicateArg::String(pattern))) => {
+                                    let regex = Regex::new(pattern.as_ref())?;
+                                    info.doc_line_strip_regex = Some(regex);
+                                }
+                                ("join!", Some(QueryPredicateArg::String(separator))) => {
+                                    info.doc_separator = Some(separator.to_string());
+                                }
+                                ("max-gap!", Some(QueryPredicateArg::String(gap))) => {
+                                    let gap = gap.parse().map_err(|_| {
+                                        Error::InvalidPredicate(format!(
+                                            "#max-gap! expects a number of lines, got `{gap}`"
+                                        ))
+                                    })?;
+                                    info.docs_max_gap = Some(gap);
+                                }
+                                ("allow-trailing!", None) => {
+                                    info.docs_allow_trailing = Some(true);
+                                }
                                 _ => {}
                             }
                         }
@@ -232,6 +333,7 @@ impl TagsConfiguration {
         Ok(Self {
             language,
             query,
+            docs: DocsConfig::default(),
             syntax_type_names,
             c_syntax_type_names,
             capture_map,
@@ -257,6 +359,18 @@ impl TagsConfiguration {
             str::from_utf8(cstr).expect("syntax type name was not valid utf-8")
         }
     }
+
+    /// The syntax type id of a kind of tag, if the query has any tags of that kind.
+    #[must_use]
+    pub fn syntax_type_id(&self, name: &str) -> Option<u32> {
+        (0..self.syntax_type_names.len() as u32).find(|id| self.syntax_type_name(*id) == name)
+    }
+
+    /// The names of all of the kinds of tags in the query, in the order of their syntax type ids.
+    #[must_use]
+    pub fn syntax_type_names(&self) -> impl ExactSizeIterator<Item = &str> {
+        (0..self.syntax_type_names.len() as u32).map(|id| self.syntax_type_name(id))
+    }
 }
 
 impl Default for TagsContext {
@@ -284,6 +398,73 @@ impl TagsContext {
         source: &'a [u8],
         cancellation_flag: Option<&'a AtomicUsize>,
     ) -> Result<(impl Iterator<Item = Result<Tag, Error>> + 'a, bool), Error> {
+        self.tags_iter(config, source, 0, cancellation_flag, None)
+    }
+
+    /// Generate the tags of a document whose names start at or after `start_byte`, in several
+    /// steps.
+    ///
+    /// The progress callback is called periodically, about every hundred query matches, and
+    /// tag generation stops when it returns `true`, for example once a deadline has passed. The
+    /// tags that were generated until then are returned along with the byte offset to resume
+    /// from, and calling this again with that offset generates the rest of the tags, without any
+    /// of the ones that were already generated. The document is parsed again each time, and
+    /// the callback is also called while parsing, so returning `true` before any tags have been
+    /// generated returns `start_byte` itself as the offset to resume from.
+    pub fn generate_tags_from(
+        &mut self,
+        config: &TagsConfiguration,
+        source: &[u8],
+        start_byte: usize,
+        progress_callback: &mut dyn FnMut(&TagsProgress) -> bool,
+    ) -> Result<PartialTags, Error> {
+        let (mut iter, has_error) =
+            match self.tags_iter(config, source, start_byte, None, Some(progress_callback)) {
+                Ok(result) => result,
+                Err(Error::Cancelled) => {
+                    return Ok(PartialTags {
+                        tags: Vec::new(),
+                        resume_byte: Some(start_byte),
+                        has_error: false,
+                    })
+                }
+                Err(e) => return Err(e),
+            };
+
+        let mut tags = Vec::new();
+        while let Some(tag) = iter.next() {
+            match tag {
+                Ok(tag) => tags.push(tag),
+                Err(Error::Cancelled) => {
+                    return Ok(PartialTags {
+                        tags,
+                        resume_byte: Some(iter.progress.byte_offset),
+                        has_error,
+                    })
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(PartialTags {
+            tags,
+            resume_byte: None,
+            has_error,
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn tags_iter<'a>(
+        &'a mut self,
+        config: &'a TagsConfiguration,
+        source: &'a [u8],
+        start_byte: usize,
+        cancellation_flag: Option<&'a AtomicUsize>,
+        mut progress_callback: Option<&'a mut dyn FnMut(&TagsProgress) -> bool>,
+    ) -> Result<(TagsIter<'a, QueryMatches<'a, 'a, &'a [u8], &'a [u8]>>, bool), Error> {
+        let progress = TagsProgress {
+            tag_count: 0,
+            byte_offset: start_byte,
+        };
         self.parser
             .set_language(&config.language)
             .map_err(|_| Error::InvalidLanguage)?;
@@ -302,6 +483,8 @@ impl TagsContext {
                 Some(ParseOptions::new().progress_callback(&mut |_| {
                     if let Some(cancellation_flag) = cancellation_flag {
                         cancellation_flag.load(Ordering::SeqCst) != 0
+                    } else if let Some(progress_callback) = progress_callback.as_mut() {
+                        progress_callback(&progress)
                     } else {
                         false
                     }
@@ -313,6 +496,13 @@ impl TagsContext {
         // moved. But the tree is really just a pointer, so it's actually ok to
         // move it.
         let tree_ref = unsafe { mem::transmute::<&Tree, &'static Tree>(&tree) };
+
+        // Without a locals query, the matches before the start can't affect the tags after it.
+        if config.tags_pattern_index == 0 {
+            self.cursor.set_byte_range(start_byte..usize::MAX);
+        } else {
+            self.cursor.set_byte_range(0..usize::MAX);
+        }
         let matches = self
             .cursor
             .matches(&config.query, tree_ref.root_node(), source);
@@ -323,6 +513,9 @@ impl TagsContext {
                 source,
                 config,
                 cancellation_flag,
+                progress_callback,
+                progress,
+                start_byte,
                 prev_line_info: None,
                 tag_queue: Vec::new(),
                 iter_count: 0,
@@ -346,14 +539,22 @@ where
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             // Periodically check for cancellation, returning `Cancelled` error if the
-            // cancellation flag was flipped.
-            if let Some(cancellation_flag) = self.cancellation_flag {
+            // cancellation flag was flipped or the progress callback asked to stop.
+            if self.cancellation_flag.is_some() || self.progress_callback.is_some() {
                 self.iter_count += 1;
                 if self.iter_count >= CANCELLATION_CHECK_INTERVAL {
                     self.iter_count = 0;
-                    if cancellation_flag.load(Ordering::Relaxed) != 0 {
+                    if self
+                        .cancellation_flag
+                        .is_some_and(|flag| flag.load(Ordering::Relaxed) != 0)
+                    {
                         return Some(Err(Error::Cancelled));
                     }
+                    if let Some(progress_callback) = self.progress_callback.as_mut() {
+                        if progress_callback(&self.progress) {
+                            return Some(Err(Error::Cancelled));
+                        }
+                    }
                 }
             }
 
@@ -364,9 +565,11 @@ where
                     && self.tag_queue[0].0.name_range.end < last_entry.0.name_range.start
                 {
                     let tag = self.tag_queue.remove(0).0;
-                    if tag.is_ignored() {
+                    if tag.is_ignored() || tag.name_range.start < self.start_byte {
                         continue;
                     }
+                    self.progress.tag_count += 1;
+                    self.progress.byte_offset = tag.name_range.end;
                     return Some(Ok(tag));
                 }
             }
@@ -467,38 +670,51 @@ where
 
                         // If needed, filter the doc nodes based on their ranges, selecting
                         // only the slice that are adjacent to some specified node.
-                        let mut docs_start_index = 0;
-                        if let (Some(docs_adjacent_node), false) =
-                            (docs_adjacent_node, doc_nodes.is_empty())
-                        {
-                            docs_start_index = doc_nodes.len();
-                            let mut start_row = docs_adjacent_node.start_position().row;
-                            while docs_start_index > 0 {
-                                let doc_node = &doc_nodes[docs_start_index - 1];
-                                let prev_doc_end_row = doc_node.end_position().row;
-                                if prev_doc_end_row + 1 >= start_row {
-                                    docs_start_index -= 1;
-                                    start_row = doc_node.start_position().row;
-                                } else {
-                                    break;
-                                }
-                            }
-                        }
+                        let docs_config = &self.config.docs;
+                        let docs_range = docs_adjacent_node.map_or(0..doc_nodes.len(), |node| {
+                            adjacent_docs_range(
+                                &doc_nodes,
+                                node,
+                                pattern_info.docs_max_gap.unwrap_or(docs_config.max_gap),
+                                pattern_info
+                                    .docs_allow_trailing
+                                    .unwrap_or(docs_config.allow_trailing),
+                            )
+                        });
 
                         // Generate a doc string from all of the doc nodes, applying any strip
                         // regexes.
+                        let strip_regex = pattern_info
+                            .doc_strip_regex
+                            .as_ref()
+                            .or(docs_config.strip_regex.as_ref());
+                        let line_strip_regex = pattern_info
+                            .doc_line_strip_regex
+                            .as_ref()
+                            .or(docs_config.line_strip_regex.as_ref());
+                        let separator = pattern_info
+                            .doc_separator
+                            .as_deref()
+                            .unwrap_or(&docs_config.separator);
                         let mut docs = None;
-                        for doc_node in &doc_nodes[docs_start_index..] {
+                        for doc_node in &doc_nodes[docs_range] {
                             if let Ok(content) = str::from_utf8(&self.source[doc_node.byte_range()])
                             {
-                                let content = pattern_info.doc_strip_regex.as_ref().map_or_else(
+                                let mut content = strip_regex.map_or_else(
                                     || content.to_string(),
                                     |regex| regex.replace_all(content, "").to_string(),
                                 );
+                                if let Some(regex) = line_strip_regex {
+                                    content = content
+                                        .lines()
+                                        .map(|line| regex.replace_all(line, ""))
+                                        .collect::<Vec<_>>()
+                                        .join("\n");
+                                }
                                 match &mut docs {
                                     None => docs = Some(content),
                                     Some(d) => {
-                                        d.push('\n');
+                                        d.push_str(separator);
                                         d.push_str(&content);
                                     }
                                 }
@@ -583,7 +799,13 @@ where
             }
             // If there are no more matches, then drain the queue.
             else if !self.tag_queue.is_empty() {
-                return Some(Ok(self.tag_queue.remove(0).0));
+                let tag = self.tag_queue.remove(0).0;
+                if tag.name_range.start < self.start_byte {
+                    continue;
+                }
+                self.progress.tag_count += 1;
+                self.progress.byte_offset = tag.name_range.end;
+                return Some(Ok(tag));
             } else {
                 return None;
             }
@@ -592,6 +814,12 @@ where
 }
 
 impl Tag {
+    /// The name of this tag's kind, like `function` for a `@definition.function` capture.
+    #[must_use]
+    pub fn syntax_type_name<'a>(&self, config: &'a TagsConfiguration) -> &'a str {
+        config.syntax_type_name(self.syntax_type_id)
+    }
+
     #[must_use]
     const fn ignored(name_range: Range<usize>) -> Self {
         Self {
@@ -612,6 +840,53 @@ impl Tag {
     }
 }
 
+/// The range of the doc nodes that are adjacent to `node`: the ones right before it, each one at
+/// most `max_gap` lines before the next one, or if there are none of those and `allow_trailing` is
+/// set, the ones right after it.
+fn adjacent_docs_range(
+    doc_nodes: &[Node],
+    node: Node,
+    max_gap: usize,
+    allow_trailing: bool,
+) -> Range<usize> {
+    let preceding_count = doc_nodes
+        .iter()
+        .take_while(|doc_node| doc_node.end_byte() <= node.start_byte())
+        .count();
+    let mut start = preceding_count;
+    let mut row = node.start_position().row;
+    while start > 0 {
+        let doc_node = &doc_nodes[start - 1];
+        if doc_node.end_position().row + 1 + max_gap >= row {
+            start -= 1;
+            row = doc_node.start_position().row;
+        } else {
+            break;
+        }
+    }
+    if start < preceding_count || !allow_trailing {
+        return start..preceding_count;
+    }
+
+    let following_start = preceding_count
+        + doc_nodes[preceding_count..]
+            .iter()
+            .take_while(|doc_node| doc_node.start_byte() < node.end_byte())
+            .count();
+    let mut end = following_start;
+    let mut row = node.end_position().row;
+    while end < doc_nodes.len() {
+        let doc_node = &doc_nodes[end];
+        if doc_node.start_position().row <= row + 1 + max_gap {
+            end += 1;
+            row = doc_node.end_position().row;
+        } else {
+            break;
+        }
+    }
+    following_start..end
+}
+
 fn line_range(
     text: &[u8],
     start_byte: usize,
@@ -0,0 +1,18 @@
+module.exports = grammar({
+  name: 'aliased_external_tokens',
+
+  externals: $ => [
+    alias($._raw_body, $.content),
+    alias($._line_comment, $.comment),
+  ],
+
+  extras: $ => [/\s/, $._line_comment],
+
+  rules: {
+    document: $ => repeat(choice($.string, $.raw_string)),
+
+    string: $ => seq('"', optional($._raw_body), '"'),
+
+    raw_string: $ => seq("'", optional(alias($._raw_body, $.raw_content)), "'"),
+  },
+});
@@ -0,0 +1,59 @@
+#include "tree_sitter/parser.h"
+
+enum {
+  RAW_BODY,
+  LINE_COMMENT,
+};
+
+void *tree_sitter_aliased_external_tokens_external_scanner_create() { return NULL; }
+
+void tree_sitter_aliased_external_tokens_external_scanner_destroy(void *payload) {}
+
+unsigned tree_sitter_aliased_external_tokens_external_scanner_serialize(
+  void *payload,
+  char *buffer
+) { return 0; }
+
+void tree_sitter_aliased_external_tokens_external_scanner_deserialize(
+  void *payload,
+  const char *buffer,
+  unsigned length
+) {}
+
+bool tree_sitter_aliased_external_tokens_external_scanner_scan(
+  void *payload,
+  TSLexer *lexer,
+  const bool *valid_symbols
+) {
+  if (valid_symbols[RAW_BODY]) {
+    bool has_content = false;
+    while (
+      lexer->lookahead != '"' &&
+      lexer->lookahead != '\'' &&
+      !lexer->eof(lexer)
+    ) {
+      lexer->advance(lexer, false);
+      has_content = true;
+    }
+
+    if (has_content) {
+      lexer->result_symbol = RAW_BODY;
+      return true;
+    }
+  }
+
+  while (lexer->lookahead == ' ' || lexer->lookahead == '\n') {
+    lexer->advance(lexer, true);
+  }
+
+  if (valid_symbols[LINE_COMMENT] && lexer->lookahead == '#') {
+    while (lexer->lookahead != '\n' && !lexer->eof(lexer)) {
+      lexer->advance(lexer, false);
+    }
+
+    lexer->result_symbol = LINE_COMMENT;
+    return true;
+  }
+
+  return false;
+}
@@ -0,0 +1,26 @@
+// This grammar uses case-insensitive keywords, which are extracted as keywords
+// of the `identifier` word token.
+
+module.exports = grammar({
+  name: 'case_insensitive_keywords',
+
+  word: $ => $.identifier,
+
+  rules: {
+    program: $ => repeat($.statement),
+
+    statement: $ => seq(
+      caseInsensitive('select'),
+      commaSep1($.identifier),
+      caseInsensitive('from'),
+      $.identifier,
+      ';',
+    ),
+
+    identifier: _ => /[a-zA-Z_]+/,
+  },
+});
+
+function commaSep1(rule) {
+  return seq(rule, repeat(seq(',', rule)));
+}
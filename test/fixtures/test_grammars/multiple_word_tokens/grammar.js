@@ -0,0 +1,17 @@
+module.exports = grammar({
+  name: "multiple_word_tokens",
+
+  word: $ => [$.identifier, $.tag_name],
+
+  rules: {
+    document: $ => repeat(choice($.statement, $.tag)),
+
+    statement: $ => seq(choice("if", "let"), $.identifier, ";"),
+
+    tag: $ => seq("<", choice("Fragment", "Slot", $.tag_name), ">"),
+
+    identifier: _ => /[a-z_]+/,
+
+    tag_name: _ => /[A-Z][a-zA-Z]*/,
+  },
+});
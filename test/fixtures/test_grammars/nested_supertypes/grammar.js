@@ -0,0 +1,30 @@
+module.exports = grammar({
+  name: "nested_supertypes",
+
+  supertypes: $ => [$._expression, $._literal],
+
+  rules: {
+    program: $ => repeat(seq(field("value", $._expression), ";")),
+
+    _expression: $ => choice(
+      $._literal,
+      $.identifier,
+      $.binary_expression,
+    ),
+
+    _literal: $ => choice(
+      $.number,
+      $.string,
+    ),
+
+    binary_expression: $ => prec.left(seq(
+      field("left", $._expression),
+      "+",
+      field("right", $._expression),
+    )),
+
+    identifier: _ => /[a-z]+/,
+    number: _ => /\d+/,
+    string: _ => /"[^"]*"/,
+  },
+});
@@ -0,0 +1,55 @@
+// Automatically generated by tree-sitter from node-types.json. Do not edit.
+
+export interface UnnamedNode<T extends string> {
+  type: T;
+  named: false;
+}
+
+/** The `_literal` supertype. */
+export type LiteralNode = NumberNode | StringNode;
+
+/** The `_expression` supertype. */
+export type ExpressionNode = LiteralNode | BinaryExpressionNode | IdentifierNode;
+
+export interface BinaryExpressionNode {
+  type: "binary_expression";
+  named: true;
+  fields: {
+    left: ExpressionNode;
+    right: ExpressionNode;
+  };
+}
+
+export interface ProgramNode {
+  type: "program";
+  named: true;
+  fields: {
+    value: ExpressionNode[];
+  };
+}
+
+export interface IdentifierNode {
+  type: "identifier";
+  named: true;
+}
+
+export interface NumberNode {
+  type: "number";
+  named: true;
+}
+
+export interface StringNode {
+  type: "string";
+  named: true;
+}
+
+export type NamedNode =
+  | BinaryExpressionNode
+  | ProgramNode
+  | IdentifierNode
+  | NumberNode
+  | StringNode;
+export type UnnamedNodeType =
+  | "+"
+  | ";";
+export type Node = NamedNode | UnnamedNode<UnnamedNodeType>;
@@ -0,0 +1,10 @@
+module.exports = grammar({
+  name: "supertype_with_only_tokens",
+
+  supertypes: $ => [$._literal],
+
+  rules: {
+    program: $ => repeat($._literal),
+    _literal: $ => choice("true", "false", "null"),
+  },
+});
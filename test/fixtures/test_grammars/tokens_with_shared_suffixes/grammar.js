@@ -0,0 +1,44 @@
+module.exports = grammar({
+  name: 'tokens_with_shared_suffixes',
+
+  word: $ => $.identifier,
+
+  rules: {
+    program: $ => repeat($.column_definition),
+
+    column_definition: $ => seq(
+      $.identifier,
+      $.type,
+      optional(seq('default', $._literal)),
+      ';',
+    ),
+
+    type: _ => token(choice(
+      'int',
+      'integer',
+      'bigint',
+      'smallint',
+      'tinyint',
+      'mediumint',
+      'char',
+      'varchar',
+      'nchar',
+      'nvarchar',
+      'text',
+      'tinytext',
+      'mediumtext',
+      'longtext',
+    )),
+
+    _literal: $ => choice($.number, $.string),
+
+    number: _ => /\d+(\.\d+)?|\.\d+/,
+
+    string: _ => token(choice(
+      seq('\'', /[^']*/, '\''),
+      seq('"', /[^"]*/, '"'),
+    )),
+
+    identifier: _ => /[a-z_]+/,
+  },
+});
@@ -15,7 +15,7 @@ use notify_debouncer_full::new_debouncer;
 
 use crate::{bail_on_err, watch_wasm, CheckWasmExports};
 
-const EXCLUDES: [&str; 27] = [
+const EXCLUDES: [&str; 44] = [
     // Unneeded because the JS side has its own way of implementing it
     "ts_node_child_by_field_name",
     "ts_node_edit",
@@ -31,7 +31,18 @@ const EXCLUDES: [&str; 27] = [
     "ts_parser_set_cancellation_flag",
     "ts_parser_cancellation_flag",
     "ts_parser_print_dot_graphs",
+    "ts_parser_set_dot_graph_writer",
+    "ts_parser_dot_graph_writer",
+    "ts_parser_set_record_stats",
+    "ts_parser_parse_stats",
     "ts_tree_print_dot_graph",
+    "ts_tree_serialize",
+    "ts_tree_deserialize",
+    "ts_query_serialize",
+    "ts_query_deserialize",
+    "ts_query_pattern_depth",
+    "ts_language_external_token_count",
+    "ts_tree_memory_usage",
     "ts_parser_set_wasm_store",
     "ts_parser_take_wasm_store",
     "ts_parser_language",
@@ -47,6 +58,12 @@ const EXCLUDES: [&str; 27] = [
     "ts_query_cursor_match_limit",
     "ts_query_cursor_remove_match",
     "ts_query_cursor_timeout_micros",
+    "ts_query_cursor_reset",
+    "ts_query_cursor_stats",
+    "ts_query_cursor_set_enabled_patterns",
+    "ts_query_cursor_did_exceed_step_limit",
+    "ts_query_cursor_step_limit",
+    "ts_query_cursor_set_step_limit",
 ];
 
 pub fn run(args: &CheckWasmExports) -> Result<()> {